@@ -1,10 +1,8 @@
 use conreg_client::conf::{
     ClientConfigBuilder, ConRegConfigBuilder, ConfigConfigBuilder, DiscoveryConfigBuilder,
 };
-use conreg_client::{AppConfig, init_with};
-use rocket::data::{ByteUnit, Limits};
+use conreg_client::{AppConfig, try_init_with};
 use rocket::{Config, get};
-use std::net::IpAddr;
 
 /// 将一个HTTP服务注册到 `Conreg Server`。
 ///
@@ -53,10 +51,10 @@ async fn main() {
         .unwrap();
 
     // 初始化
-    init_with(config).await;
+    try_init_with(config).await.unwrap();
 
     // 从配置中心获取配置
-    let h = tokio::spawn(async move {
+    let _h = tokio::spawn(async move {
         loop {
             // 获取配置
             let name = AppConfig::get::<String>("name");
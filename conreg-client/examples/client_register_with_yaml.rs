@@ -1,10 +1,5 @@
 use conreg_client::AppConfig;
-use conreg_client::conf::{
-    ClientConfigBuilder, ConRegConfigBuilder, ConfigConfigBuilder, DiscoveryConfigBuilder,
-};
-use rocket::data::{ByteUnit, Limits};
 use rocket::{Config, get};
-use std::net::IpAddr;
 
 /// 通过yaml配置文件注册客户端。
 ///
@@ -17,7 +12,9 @@ use std::net::IpAddr;
 #[tokio::main]
 async fn main() {
     // 初始化
-    conreg_client::init_from_file("./conreg-client/examples/bootstrap.yaml").await;
+    conreg_client::try_init_from_file("./conreg-client/examples/bootstrap.yaml")
+        .await
+        .unwrap();
 
     // 从配置中心获取配置
     tokio::spawn(async move {
@@ -16,7 +16,9 @@ trait ExampleClient {
 
 #[tokio::main]
 async fn main() {
-    conreg_client::init_from_file("./conreg-client/examples/bootstrap.yaml").await;
+    conreg_client::try_init_from_file("./conreg-client/examples/bootstrap.yaml")
+        .await
+        .unwrap();
     let client = ExampleClientImpl::default();
 
     let response = client.hello().await.unwrap();
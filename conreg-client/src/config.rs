@@ -1,82 +1,271 @@
-use crate::conf::{ConfigConfig, ServerAddr};
+#[cfg(feature = "grpc")]
+use crate::conf::Protocol;
+use crate::conf::{ClientConfig, ConfigConfig};
+use crate::enc_dec::DecryptKey;
+#[cfg(feature = "grpc")]
+use crate::grpc;
 use crate::network::HTTP;
-use crate::protocol::request::{GetConfigReq, WatchConfigChangeReq};
-use crate::{AppConfig, ConRegConfig};
+#[cfg(feature = "grpc")]
+use crate::protocol::request::WatchConfigChangeReq;
+use crate::protocol::request::{ConfigReportReq, GetConfigReq, WatchConfigsReq};
+use crate::snapshot::ConfigSnapshot;
+use crate::timer::{Ticker, jitter};
+use crate::{AppConfig, CONFIGS, ConRegConfig};
 use anyhow::Context;
 use dashmap::DashMap;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
-use std::collections::HashMap;
-use std::sync::LazyLock;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
 use std::time::Duration;
 
+/// 配置补偿间隔
+const COMPENSATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 配置重新加载锁
+///
+/// 监听、轮询、补偿和手动刷新都是先读取已加载的配置，拉取后再整体替换，
+/// 并发执行时后写入的旧内容会覆盖先写入的新内容，因此拉取和替换需要串行执行
+static RELOAD_LOCK: LazyLock<tokio::sync::Mutex<()>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(()));
+
+/// 默认脱敏的配置项名称，配置项路径中任一段包含其中之一（忽略大小写）时脱敏
+pub(crate) const DEFAULT_MASK_PATTERNS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "credential",
+    "private-key",
+    "private_key",
+    "api-key",
+    "api_key",
+    "apikey",
+    "access-key",
+    "access_key",
+];
+/// 脱敏后的值
+const MASKED_VALUE: &str = "******";
+
+/// 初始化时设置的脱敏配置项名称，均为小写，未设置时使用[`DEFAULT_MASK_PATTERNS`]
+static MASK_PATTERNS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// 设置脱敏的配置项名称，为None时使用默认值
+pub(crate) fn set_mask_patterns(patterns: Option<&[String]>) {
+    if let Some(patterns) = patterns {
+        let _ = MASK_PATTERNS.set(
+            patterns
+                .iter()
+                .map(|pattern| pattern.to_lowercase())
+                .collect(),
+        );
+    }
+}
+
+/// 当前脱敏的配置项名称，用于导出配置、日志和错误信息
+pub(crate) fn mask_patterns() -> Vec<String> {
+    match MASK_PATTERNS.get() {
+        Some(patterns) => patterns.clone(),
+        None => DEFAULT_MASK_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+    }
+}
+
+/// 格式化标签选择器，格式为`env=prod,region=eu`，按标签名排序
+fn format_labels(labels: &HashMap<String, String>) -> Option<String> {
+    if labels.is_empty() {
+        return None;
+    }
+    let mut labels = labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>();
+    labels.sort();
+    Some(labels.join(","))
+}
+
+/// 是否为配置中心不可达导致的错误
+fn is_unreachable(e: &anyhow::Error) -> bool {
+    #[cfg(feature = "grpc")]
+    if grpc::is_unavailable(e) {
+        return true;
+    }
+    e.downcast_ref::<reqwest::Error>().is_some()
+}
+
+#[derive(Clone)]
 pub struct ConfigClient {
+    /// 服务ID
+    service_id: String,
+    /// 客户端配置
+    client: ClientConfig,
     // 配置的配置😅
     config: ConfigConfig,
+    /// 配置源的序号，合并配置时序号大的配置源优先
+    source: usize,
+    /// 通信协议
+    #[cfg(feature = "grpc")]
+    protocol: Protocol,
+    /// 配置本地快照，未配置`snapshot-path`时为空
+    snapshot: Option<Arc<ConfigSnapshot>>,
 }
 
 impl ConfigClient {
-    pub fn new(config: &ConRegConfig) -> Self {
+    /// 创建指定配置源的配置客户端，每个配置源独立拉取和监听配置
+    pub fn new(config: &ConRegConfig, source: usize) -> Self {
+        let config_config = config
+            .config
+            .as_ref()
+            .and_then(|sources| sources.as_slice().get(source))
+            .cloned()
+            .context("config not set, unable to create config client")
+            .unwrap();
         ConfigClient {
-            config: config
-                .config
+            service_id: config.service_id.clone(),
+            client: config.client.clone(),
+            source,
+            #[cfg(feature = "grpc")]
+            protocol: config.protocol,
+            snapshot: config_config
+                .snapshot_path
                 .clone()
-                .context("config not set, unable to create config client")
-                .unwrap(),
+                .map(|path| Arc::new(ConfigSnapshot::new(path))),
+            config: config_config,
         }
     }
 
     /// 初始化配置
+    ///
+    /// 配置中心不可达且设置了本地快照时，使用快照中的配置
     pub(crate) async fn load(&self) -> anyhow::Result<Configs> {
-        let mut contents = vec![];
-        for id in self.config.config_ids.iter() {
-            let content = Self::fetch_config(
-                &self.config.server_addr,
-                &self.config.namespace,
-                id,
-                &self.config.auth_token,
-            )
-            .await?;
-            contents.push((id.clone(), content));
-        }
+        let (contents, md5s) = match self.fetch_all().await {
+            Ok((contents, md5s)) => {
+                self.save_snapshot(&contents, &md5s);
+                (contents, md5s)
+            }
+            Err(e) if is_unreachable(&e) => match &self.snapshot {
+                Some(snapshot) => {
+                    log::error!("config center unreachable, {}", e);
+                    snapshot.load(&self.config)?
+                }
+                None => return Err(e),
+            },
+            Err(e) => return Err(e),
+        };
 
         // 启动监听，监听配置变化
-        self.start_watch().await?;
+        // 如果所有配置都指定了轮询间隔，则为纯轮询模式，不再启动长轮询监听
+        if self
+            .config
+            .config_ids
+            .iter()
+            .all(|id| self.config.poll_interval.contains_key(id))
+        {
+            log::info!("all configs use polling mode, skip watch");
+        } else {
+            self.start_watch().await?;
+        }
+
+        // 启动轮询任务，按指定间隔拉取配置
+        self.start_poll().await?;
 
         // 启动补偿任务，定时拉取配置
         self.start_compensate().await?;
 
-        Configs::from_contents(contents)
+        Ok(Configs::from_contents(contents)?
+            .decrypt(self.config.decrypt_key.as_deref())?
+            .with_md5s(md5s))
+    }
+
+    /// 从配置中心加载所有配置ID的配置内容，返回配置内容和配置MD5
+    async fn fetch_all(&self) -> anyhow::Result<(Vec<ConfigContent>, HashMap<String, String>)> {
+        let mut contents = vec![];
+        let mut md5s = HashMap::new();
+        for id in self.config.config_ids.iter() {
+            let (content, md5) = self.fetch_config(id).await?;
+            contents.push(content);
+            md5s.insert(id.clone(), md5);
+        }
+        Ok((contents, md5s))
+    }
+
+    /// 保存配置本地快照，未设置快照路径时忽略
+    fn save_snapshot(&self, contents: &[ConfigContent], md5s: &HashMap<String, String>) {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.save(&self.config, contents, md5s);
+        }
     }
 
     /// 从配置中心加载指定配置ID的配置内容
     ///
-    /// - server_addr: 配置中心地址
-    /// - namespace: 命名空间
-    /// - config_id: 配置ID
-    /// - auth_token: 鉴权token
-    async fn fetch_config(
-        server_addr: &ServerAddr,
-        namespace: &str,
+    /// 按配置源的标签选择器获取与之最匹配的配置变体，返回配置内容和配置MD5
+    async fn fetch_config(&self, config_id: &str) -> anyhow::Result<(ConfigContent, String)> {
+        self.fetch_config_if_changed(config_id, None)
+            .await?
+            .with_context(|| format!("config id [ {} ] not modified", config_id))
+    }
+
+    /// 从配置中心加载指定配置ID的配置内容，配置MD5与`md5`一致时返回None
+    ///
+    /// 服务端MD5一致时返回304且不返回配置内容，避免重复下载未变化的配置。
+    /// gRPC协议不支持条件获取，始终返回配置内容
+    async fn fetch_config_if_changed(
+        &self,
         config_id: &str,
-        auth_token: &Option<String>,
-    ) -> anyhow::Result<String> {
-        let url = server_addr.build_url("/api/config/get")?;
+        md5: Option<&str>,
+    ) -> anyhow::Result<Option<(ConfigContent, String)>> {
         let query = GetConfigReq {
-            namespace_id: namespace.to_string(),
+            namespace_id: self.config.namespace.clone(),
             id: config_id.to_string(),
+            labels: format_labels(&self.config.labels),
+            md5: md5.map(str::to_string),
         };
 
-        let result = HTTP
-            .get::<HashMap<String, Value>>(
-                &url,
-                query,
-                match auth_token {
-                    Some(token) => Some(vec![(crate::NS_TOKEN_HEADER, token.as_str())]),
-                    None => None,
+        #[cfg(feature = "grpc")]
+        if self.protocol == Protocol::Grpc {
+            let entry = grpc::get_config(&self.config.server_addr, query, &self.config.auth_token)
+                .await?
+                .ok_or(anyhow::anyhow!(
+                    "config id [ {} ] not found in server",
+                    config_id
+                ))?;
+            let format = ConfigFormat::detect(Some(&entry.format), config_id)?;
+            log::info!("config {} fetched", config_id);
+            return Ok(Some((
+                ConfigContent {
+                    config_id: config_id.to_string(),
+                    format,
+                    content: entry.content,
                 },
-            )
-            .await?;
+                entry.md5,
+            )));
+        }
+
+        let query = &query;
+        let Some(result) = self
+            .config
+            .server_addr
+            .request("/api/config/get", |url| async move {
+                HTTP.get_modified::<HashMap<String, Value>>(
+                    &url,
+                    query,
+                    match &self.config.auth_token {
+                        Some(token) => Some(vec![(crate::NS_TOKEN_HEADER, token.as_str())]),
+                        None => None,
+                    },
+                )
+                .await
+            })
+            .await?
+        else {
+            log::debug!("config {} not modified", config_id);
+            return Ok(None);
+        };
 
         let content = result
             .get("content")
@@ -87,70 +276,79 @@ impl ConfigClient {
             ))?
             .as_str()
             .unwrap();
+        let md5 = result
+            .get("md5")
+            .and_then(|md5| md5.as_str())
+            .unwrap_or_default();
+        // 旧版本服务端不返回格式，此时根据配置ID的扩展名判断
+        let format = ConfigFormat::detect(
+            result.get("format").and_then(|format| format.as_str()),
+            config_id,
+        )?;
         log::info!("config {} fetched", config_id);
 
-        Ok(content.to_string())
+        Ok(Some((
+            ConfigContent {
+                config_id: config_id.to_string(),
+                format,
+                content: content.to_string(),
+            },
+            md5.to_string(),
+        )))
     }
 
     /// 开启配置变更监听任务
     ///
-    /// 目前使用长轮询的方式，在没有配置变更时，server会阻塞29秒后返回false；
-    /// 在有配置变更时，server会立即返回true，然后重新从server拉取配置。
+    /// HTTP协议使用长轮询的方式，提交已加载配置的MD5，在没有配置变更时，server会阻塞29秒后返回空列表；
+    /// 在有配置变更时，server会立即返回MD5不一致的配置ID，然后只重新拉取这些配置。
+    /// 指定了轮询间隔的配置由轮询任务拉取，不参与监听。
     async fn start_watch(&self) -> anyhow::Result<()> {
+        #[cfg(feature = "grpc")]
+        if self.protocol == Protocol::Grpc {
+            self.start_grpc_watch();
+            return Ok(());
+        }
+        let client = self.clone();
         let config_clone = self.config.clone();
         tokio::spawn(async move {
             log::info!(
                 "start watch config changes in namespace: {}",
                 config_clone.namespace
             );
-            let url = config_clone
-                .server_addr
-                .build_url("/api/config/watch")
-                .context("build url error from server addr")
-                .unwrap();
-            let query = WatchConfigChangeReq {
-                namespace_id: config_clone.namespace.clone(),
-            };
+            let labels = format_labels(&config_clone.labels);
 
             loop {
-                match HTTP.get::<Option<String>>(&url, &query, None).await {
-                    Ok(changed_config_id) => {
-                        if changed_config_id.is_none() {
-                            log::info!("config no changed");
-                            continue;
-                        }
-                        log::info!("config changed, reloading config");
-                        let mut contents = vec![];
-                        for id in config_clone.config_ids.iter() {
-                            let content = Self::fetch_config(
-                                &config_clone.server_addr,
-                                &config_clone.namespace,
-                                id,
-                                &config_clone.auth_token,
-                            )
-                            .await
-                            .unwrap();
-                            contents.push((id.clone(), content));
+                let (_, md5s) = client.current_contents();
+                let query = WatchConfigsReq {
+                    namespace_id: config_clone.namespace.clone(),
+                    configs: client
+                        .watched_ids()
+                        .map(|id| (id.clone(), md5s.get(id).cloned().unwrap_or_default()))
+                        .collect(),
+                    labels: labels.clone(),
+                };
+                // 每次监听重新选择节点，节点不可用时转移到其他节点
+                let query = &query;
+                let res = config_clone
+                    .server_addr
+                    .request("/api/config/watch", |url| async move {
+                        HTTP.post::<Vec<String>>(&url, query).await
+                    })
+                    .await;
+                match res {
+                    Ok(changed_ids) if changed_ids.is_empty() => log::info!("config no changed"),
+                    Ok(changed_ids) => {
+                        log::info!("config changed: {:?}, reloading config", changed_ids);
+                        if let Err(e) = client.refresh_ids(&changed_ids).await {
+                            log::error!("reload config error: {:#}", e);
+                            // 避免拉取失败时立即重复请求，sleep 5s（附加随机抖动）后重试
+                            tokio::time::sleep(jitter(Duration::from_secs(5))).await;
                         }
-                        // 新配置
-                        let config = Configs::from_contents(contents).unwrap();
-                        // 展平后的配置
-                        let new_configs = config.get_all().clone();
-
-                        // 重新加载
-                        AppConfig::reload(config);
-                        log::info!("config reloaded");
-
-                        // 通知listeners配置变更
-                        Self::notify_config_change(
-                            &changed_config_id.unwrap(), // SAFE: 已经校验了None
-                            &new_configs,
-                        );
                     }
                     Err(e) => {
                         log::error!("watch config changes error: {}", e);
-                        // when some error, sleep 0.5s and retry
-                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        // when some error, sleep about 0.5s (jittered) and retry
+                        tokio::time::sleep(jitter(Duration::from_millis(500))).await;
                     }
                 };
             }
@@ -158,10 +356,86 @@ impl ConfigClient {
         Ok(())
     }
 
+    /// 通过gRPC服务端流监听配置变更
+    ///
+    /// 一个流持续接收命名空间下的所有变更，流断开后带上最后收到的游标重新建立，
+    /// 服务端立即补发断开期间的变更。游标失效时（如连接到其他节点）重新拉取所有监听的配置
+    #[cfg(feature = "grpc")]
+    fn start_grpc_watch(&self) {
+        let client = self.clone();
+        tokio::spawn(async move {
+            log::info!(
+                "start watch config changes in namespace: {} over grpc",
+                client.config.namespace
+            );
+            let mut query = WatchConfigChangeReq {
+                namespace_id: client.config.namespace.clone(),
+                cursor: String::new(),
+            };
+            loop {
+                match grpc::watch_config(&client.config.server_addr, query.clone()).await {
+                    Ok(mut stream) => loop {
+                        match stream.message().await {
+                            Ok(Some(changed)) => {
+                                if changed.reset {
+                                    client.on_watch_reset().await;
+                                } else if !changed.config_id.is_empty() {
+                                    client.on_config_change(&changed.config_id).await;
+                                }
+                                query.cursor = changed.cursor;
+                            }
+                            Ok(None) => {
+                                log::warn!("watch stream closed by server");
+                                break;
+                            }
+                            Err(e) => {
+                                log::error!("watch config changes error: {}", e);
+                                break;
+                            }
+                        }
+                    },
+                    Err(e) => log::error!("watch config changes error: {}", e),
+                }
+                tokio::time::sleep(jitter(Duration::from_millis(500))).await;
+            }
+        });
+    }
+
+    /// 游标失效时无法得知断开期间变更了哪些配置，重新拉取所有监听中的配置
+    #[cfg(feature = "grpc")]
+    async fn on_watch_reset(&self) {
+        let ids = self.watched_ids().cloned().collect::<Vec<_>>();
+        log::info!("watch cursor expired, reloading config: {:?}", ids);
+        if let Err(e) = self.refresh_ids(&ids).await {
+            log::error!("reload config error: {:#}", e);
+        }
+    }
+
+    /// 处理gRPC推送的配置变更，只重新拉取监听中的配置
+    #[cfg(feature = "grpc")]
+    async fn on_config_change(&self, changed_config_id: &str) {
+        if !self.watched_ids().any(|id| id == changed_config_id) {
+            return;
+        }
+        log::info!("config {} changed, reloading config", changed_config_id);
+        if let Err(e) = self.refresh_ids(&[changed_config_id.to_string()]).await {
+            log::error!("reload config error: {:#}", e);
+        }
+    }
+
+    /// 参与变更监听的配置ID，即未指定轮询间隔的配置
+    fn watched_ids(&self) -> impl Iterator<Item = &String> {
+        self.config
+            .config_ids
+            .iter()
+            .filter(|id| !self.config.poll_interval.contains_key(*id))
+    }
+
     /// 开启配置补偿任务
     ///
-    /// 每60秒从配置中心同步一次配置
+    /// 每60秒（附加随机抖动）从配置中心同步一次配置，并上报当前已应用的配置MD5
     async fn start_compensate(&self) -> anyhow::Result<()> {
+        let client = self.clone();
         let config_clone = self.config.clone();
         tokio::spawn(async move {
             log::info!(
@@ -169,72 +443,490 @@ impl ConfigClient {
                 config_clone.namespace
             );
 
+            let mut ticker = Ticker::new(COMPENSATE_INTERVAL);
             loop {
-                tokio::time::sleep(Duration::from_secs(60)).await;
+                ticker.tick().await;
 
                 log::debug!("starting fetch config");
+                let guard = RELOAD_LOCK.lock().await;
+                let (current, current_md5s) = client.current_contents();
                 let mut contents = vec![];
+                let mut md5s = HashMap::new();
                 for id in config_clone.config_ids.iter() {
-                    match Self::fetch_config(
-                        &config_clone.server_addr,
-                        &config_clone.namespace,
-                        id,
-                        &config_clone.auth_token,
-                    )
-                    .await
+                    // 携带已加载配置的MD5，未变化的配置不重复下载
+                    let loaded = current
+                        .iter()
+                        .find(|item| item.config_id == *id)
+                        .zip(current_md5s.get(id));
+                    match client
+                        .fetch_config_if_changed(id, loaded.map(|(_, md5)| md5.as_str()))
+                        .await
                     {
-                        Ok(res) => contents.push((id.clone(), res)),
-                        Err(e) => {
-                            log::error!("fetch config error: {}", e);
-                            tokio::time::sleep(Duration::from_millis(500)).await;
+                        Ok(Some((content, md5))) => {
+                            contents.push(content);
+                            md5s.insert(id.clone(), md5);
+                        }
+                        // 未变化或拉取失败时保留已加载的配置，避免网络抖动时清空配置
+                        res => {
+                            if let Err(e) = res {
+                                log::error!("fetch config {} error: {}", id, e);
+                                tokio::time::sleep(Duration::from_millis(500)).await;
+                            }
+                            if let Some((content, md5)) = loaded {
+                                contents.push(content.clone());
+                                md5s.insert(id.clone(), md5.clone());
+                            }
                         }
                     };
                 }
-                AppConfig::reload(Configs::from_contents(contents).unwrap());
-                log::debug!("config fetch success");
+                if md5s == current_md5s && contents == current {
+                    drop(guard);
+                    log::debug!("config not changed");
+                    client.report().await;
+                    continue;
+                }
+                // 与监听任务一样重新加载并通知监听器，重新加载后会上报配置MD5
+                if let Err(e) = client.reload(contents, md5s).await {
+                    log::error!("reload config error: {:#}", e);
+                }
+                drop(guard);
             }
         });
         Ok(())
     }
 
+    /// 立即从配置中心拉取所有配置并重新加载
+    ///
+    /// 内容发生变化的配置会通知对应的监听器
+    pub(crate) async fn refresh(&self) -> anyhow::Result<()> {
+        let _guard = RELOAD_LOCK.lock().await;
+        let (contents, md5s) = self.fetch_all().await?;
+        self.reload(contents, md5s).await
+    }
+
+    /// 配置源是否包含指定配置ID
+    pub(crate) fn contains(&self, config_id: &str) -> bool {
+        self.config.config_ids.iter().any(|id| id == config_id)
+    }
+
+    /// 立即从配置中心拉取指定配置并重新加载
+    pub(crate) async fn refresh_one(&self, config_id: &str) -> anyhow::Result<()> {
+        if !self.contains(config_id) {
+            anyhow::bail!("config id [ {} ] not in config-ids", config_id);
+        }
+        self.refresh_ids(&[config_id.to_string()]).await
+    }
+
+    /// 从配置中心拉取指定的多个配置，替换已加载的对应配置后重新加载
+    ///
+    /// 任一配置拉取失败时不做任何修改
+    async fn refresh_ids(&self, config_ids: &[String]) -> anyhow::Result<()> {
+        let _guard = RELOAD_LOCK.lock().await;
+        let (mut contents, mut md5s) = self.current_contents();
+        for config_id in config_ids {
+            let (content, md5) = self.fetch_config(config_id).await?;
+            match contents
+                .iter_mut()
+                .find(|item| item.config_id == *config_id)
+            {
+                Some(item) => *item = content,
+                None => contents.push(content),
+            }
+            md5s.insert(config_id.clone(), md5);
+        }
+        self.reload(contents, md5s).await
+    }
+
+    /// 当前配置源已加载的原始配置内容及配置MD5
+    fn current_contents(&self) -> (Vec<ConfigContent>, HashMap<String, String>) {
+        match CONFIGS.get() {
+            Some(configs) => match configs.read().expect("read lock error").source(self.source) {
+                Some(configs) => (configs.contents.clone(), configs.md5s.clone()),
+                None => (vec![], HashMap::new()),
+            },
+            None => (vec![], HashMap::new()),
+        }
+    }
+
+    /// 合并所有配置源后展平的配置
+    fn merged_configs() -> HashMap<String, Value> {
+        match CONFIGS.get() {
+            Some(configs) => configs
+                .read()
+                .expect("read lock error")
+                .merged()
+                .get_all()
+                .clone(),
+            None => HashMap::new(),
+        }
+    }
+
+    /// 使用新的配置内容重新加载，并通知内容变化的配置的监听器
+    ///
+    /// 调用方需持有[`RELOAD_LOCK`]
+    async fn reload(
+        &self,
+        contents: Vec<ConfigContent>,
+        md5s: HashMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let (old_contents, _) = self.current_contents();
+        let changed_ids = contents
+            .iter()
+            .filter(|item| !old_contents.contains(item))
+            .map(|item| item.config_id.clone())
+            .collect::<Vec<_>>();
+        if changed_ids.is_empty() && contents.len() == old_contents.len() {
+            log::debug!("config not changed");
+            return Ok(());
+        }
+
+        let config = Configs::from_contents(contents)?
+            .decrypt(self.config.decrypt_key.as_deref())?
+            .with_md5s(md5s);
+        self.save_snapshot(&config.contents, &config.md5s);
+        AppConfig::reload(self.source, config);
+        log::info!("config refreshed");
+        self.report().await;
+
+        let new_configs = Self::merged_configs();
+        for id in changed_ids {
+            Self::notify_config_change(&id, &new_configs);
+        }
+        Ok(())
+    }
+
+    /// 开启配置轮询任务
+    ///
+    /// 为指定了轮询间隔的配置单独启动定时拉取任务，用于长轮询连接被代理阻断的环境，
+    /// 轮询间隔相同的配置共享同一个时钟
+    async fn start_poll(&self) -> anyhow::Result<()> {
+        for (config_id, interval) in self.config.poll_interval.iter() {
+            if !self.config.config_ids.contains(config_id) {
+                log::warn!(
+                    "poll interval set for config {} which is not in config-ids, ignored",
+                    config_id
+                );
+                continue;
+            }
+            let client = self.clone();
+            let config_id = config_id.clone();
+            let interval = Duration::from_secs((*interval).max(1));
+            tokio::spawn(async move {
+                log::info!(
+                    "start polling config {} every {}s",
+                    config_id,
+                    interval.as_secs()
+                );
+                let mut ticker = Ticker::new(interval);
+                loop {
+                    ticker.tick().await;
+                    if let Err(e) = client.refresh_one(&config_id).await {
+                        log::error!("poll config {} error: {}", config_id, e);
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// 上报当前配置源已应用的配置MD5，用于在控制台确认配置是否已在本实例生效
+    pub(crate) async fn report(&self) {
+        let configs = match CONFIGS.get() {
+            Some(configs) => match configs.read().expect("read lock error").source(self.source) {
+                Some(configs) => configs.md5s.clone(),
+                None => return,
+            },
+            None => return,
+        };
+        let req = ConfigReportReq {
+            namespace_id: self.config.namespace.clone(),
+            service_id: self.service_id.clone(),
+            instance_id: self.client.gen_instance_id(),
+            address: format!("{}:{}", self.client.address, self.client.port),
+            configs,
+        };
+        let req = &req;
+        let query = [("namespace_id", req.namespace_id.as_str())];
+        let query = &query;
+        let res = self
+            .config
+            .server_addr
+            .request("/api/config/report", |url| async move {
+                HTTP.post_with::<()>(
+                    &url,
+                    query,
+                    req,
+                    self.config
+                        .auth_token
+                        .as_deref()
+                        .map(|token| vec![(crate::NS_TOKEN_HEADER, token)]),
+                )
+                .await
+            })
+            .await;
+        if let Err(e) = res {
+            log::warn!("report config status error: {}", e);
+        }
+    }
+
     /// 配置变更通知
     fn notify_config_change(config_id: &str, changed_configs: &HashMap<String, Value>) {
-        let listeners = CONFIG_LISTENER.listeners.get(config_id);
-        if let Some(listeners) = listeners
-            && !listeners.is_empty()
-        {
-            for handler in &*listeners {
-                handler(changed_configs)
+        // 先复制监听器再调用，避免监听器中添加或移除监听器时死锁
+        let listeners = match CONFIG_LISTENER.listeners.get(config_id) {
+            Some(listeners) => listeners
+                .iter()
+                .map(|(_, handler)| handler.clone())
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+        for handler in listeners {
+            handler(changed_configs)
+        }
+    }
+}
+
+/// 配置格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+    Properties,
+}
+
+impl ConfigFormat {
+    /// 根据名称或扩展名解析格式，如`yaml`、`yml`、`properties`
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "json" => Some(ConfigFormat::Json),
+            "toml" => Some(ConfigFormat::Toml),
+            "properties" | "ini" => Some(ConfigFormat::Properties),
+            _ => None,
+        }
+    }
+
+    /// 判断配置的格式，优先使用服务端返回的格式，其次使用配置ID的扩展名
+    fn detect(format: Option<&str>, config_id: &str) -> anyhow::Result<Self> {
+        format
+            .and_then(Self::parse)
+            .or_else(|| {
+                config_id
+                    .rsplit_once('.')
+                    .and_then(|(_, ext)| Self::parse(ext))
+            })
+            .ok_or_else(|| anyhow::anyhow!("unsupported config format: {}", config_id))
+    }
+}
+
+/// 从配置中心获取的原始配置
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConfigContent {
+    pub(crate) config_id: String,
+    pub(crate) format: ConfigFormat,
+    pub(crate) content: String,
+}
+
+impl ConfigContent {
+    /// 转换为合并配置时使用的配置源
+    ///
+    /// properties格式的key按`.`拆分为嵌套结构，与yaml的展平规则保持一致
+    fn to_source(
+        &self,
+    ) -> anyhow::Result<config::File<config::FileSourceString, config::FileFormat>> {
+        let source = match self.format {
+            ConfigFormat::Yaml => config::File::from_str(&self.content, config::FileFormat::Yaml),
+            ConfigFormat::Json => config::File::from_str(&self.content, config::FileFormat::Json),
+            ConfigFormat::Toml => config::File::from_str(&self.content, config::FileFormat::Toml),
+            ConfigFormat::Properties => {
+                let yaml = serde_yaml::to_string(&Self::parse_properties(&self.content)?)?;
+                config::File::from_str(&yaml, config::FileFormat::Yaml)
+            }
+        };
+        Ok(source)
+    }
+
+    /// 解析properties内容，`a.b=1`解析为`{a: {b: 1}}`，值为数字或布尔值时保留其类型
+    fn parse_properties(content: &str) -> anyhow::Result<Value> {
+        let mut root = Mapping::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(['=', ':']) else {
+                anyhow::bail!("invalid properties line: {}", line);
+            };
+            let value = value.trim();
+            let value = match serde_yaml::from_str::<Value>(value) {
+                Ok(value @ (Value::Bool(_) | Value::Number(_))) => value,
+                _ => Value::String(value.to_string()),
+            };
+
+            let mut keys = key.trim().split('.').peekable();
+            let mut current = &mut root;
+            while let Some(key) = keys.next() {
+                let key = Value::String(key.to_string());
+                if keys.peek().is_none() {
+                    current.insert(key, value);
+                    break;
+                }
+                let entry = current
+                    .entry(key)
+                    .or_insert_with(|| Value::Mapping(Mapping::new()));
+                if !entry.is_mapping() {
+                    *entry = Value::Mapping(Mapping::new());
+                }
+                // SAFE: 已确保为Mapping
+                current = entry.as_mapping_mut().unwrap();
             }
         }
+        Ok(Value::Mapping(root))
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Configs {
     /// 展平后的配置，以`.`分隔
     pub flatten_config: HashMap<String, Value>,
     /// 合并后的配置
     pub merged_config: HashMap<String, Value>,
+    /// 原始配置内容，用于单独刷新某个配置时重新合并
+    #[serde(skip)]
+    contents: Vec<ConfigContent>,
+    /// 配置MD5，key为配置ID
+    #[serde(skip)]
+    md5s: HashMap<String, String>,
+}
+
+/// 所有配置源的配置
+pub(crate) struct ConfigStore {
+    /// 各配置源的配置，与配置源的顺序一致
+    sources: Vec<Configs>,
+    /// 合并后的配置
+    merged: Configs,
+}
+
+impl ConfigStore {
+    pub(crate) fn new(sources: Vec<Configs>) -> anyhow::Result<Self> {
+        let merged = Configs::merge(&sources)?;
+        Ok(Self { sources, merged })
+    }
+
+    /// 合并后的配置
+    pub(crate) fn merged(&self) -> &Configs {
+        &self.merged
+    }
+
+    /// 指定配置源的配置
+    fn source(&self, source: usize) -> Option<&Configs> {
+        self.sources.get(source)
+    }
+
+    /// 替换指定配置源的配置并重新合并，返回替换前合并后的配置
+    ///
+    /// 合并失败时保持原配置不变
+    pub(crate) fn replace(&mut self, source: usize, configs: Configs) -> anyhow::Result<Configs> {
+        let Some(current) = self.sources.get_mut(source) else {
+            anyhow::bail!("config source {} not found", source);
+        };
+        let old = std::mem::replace(current, configs);
+        match Configs::merge(&self.sources) {
+            Ok(merged) => Ok(std::mem::replace(&mut self.merged, merged)),
+            Err(e) => {
+                self.sources[source] = old;
+                Err(e)
+            }
+        }
+    }
 }
 
-type ConfigListeners = DashMap<String, Vec<fn(&HashMap<String, Value>)>>;
+/// 配置变更监听函数
+pub(crate) type ConfigListenerFn = Arc<dyn Fn(&HashMap<String, Value>) + Send + Sync>;
+type ConfigListeners = DashMap<String, Vec<(u64, ConfigListenerFn)>>;
+/// 配置项变更监听函数，参数为变更前和变更后的值
+pub(crate) type KeyListenerFn = Arc<dyn Fn(Option<&Value>, Option<&Value>) + Send + Sync>;
 /// 配置变更监听
 struct ConfigListener {
-    /// key为配置ID，value为监听函数
+    /// key为配置ID，value为(监听器ID, 监听函数)
     listeners: ConfigListeners,
+    /// key为配置项或配置项前缀，value为(监听器ID, 监听函数)
+    key_listeners: DashMap<String, Vec<(u64, KeyListenerFn)>>,
+    /// 下一个监听器ID
+    next_id: AtomicU64,
 }
 static CONFIG_LISTENER: LazyLock<ConfigListener> = LazyLock::new(|| ConfigListener {
     listeners: DashMap::new(),
+    key_listeners: DashMap::new(),
+    next_id: AtomicU64::new(1),
 });
 
+/// 监听器的监听对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ListenerTarget {
+    /// 配置ID
+    Config(String),
+    /// 配置项或配置项前缀
+    Key(String),
+}
+
+/// Handle of a configuration listener, used to remove the listener
+///
+/// Dropping the handle does not remove the listener.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerHandle {
+    target: ListenerTarget,
+    id: u64,
+}
+
+impl ListenerHandle {
+    /// Configuration ID the listener is added to, `None` for key listeners
+    pub fn config_id(&self) -> Option<&str> {
+        match &self.target {
+            ListenerTarget::Config(config_id) => Some(config_id),
+            ListenerTarget::Key(_) => None,
+        }
+    }
+
+    /// Configuration key or key prefix the listener is added to, `None` for configuration listeners
+    pub fn key(&self) -> Option<&str> {
+        match &self.target {
+            ListenerTarget::Key(key) => Some(key),
+            ListenerTarget::Config(_) => None,
+        }
+    }
+
+    /// Remove the listener, returns false if it has already been removed
+    pub fn remove(&self) -> bool {
+        fn remove_from<T>(listeners: &DashMap<String, Vec<(u64, T)>>, key: &str, id: u64) -> bool {
+            let Some(mut listeners) = listeners.get_mut(key) else {
+                return false;
+            };
+            let len = listeners.len();
+            listeners.retain(|(listener_id, _)| *listener_id != id);
+            len != listeners.len()
+        }
+
+        match &self.target {
+            ListenerTarget::Config(config_id) => {
+                remove_from(&CONFIG_LISTENER.listeners, config_id, self.id)
+            }
+            ListenerTarget::Key(key) => remove_from(&CONFIG_LISTENER.key_listeners, key, self.id),
+        }
+    }
+}
+
+/// 配置绑定，配置变更时以新配置调用，返回false表示绑定已失效（接收端已全部关闭）
+type ConfigBinding = Box<dyn Fn(&Configs) -> bool + Send + Sync>;
+static CONFIG_BINDINGS: LazyLock<Mutex<Vec<ConfigBinding>>> = LazyLock::new(Default::default);
+
 impl Configs {
-    fn from_contents(contents: Vec<(String, String)>) -> anyhow::Result<Self> {
+    fn from_contents(contents: Vec<ConfigContent>) -> anyhow::Result<Self> {
         let mut builder = config::Config::builder();
 
-        for (config_id, content) in contents {
-            let format = Self::get_format(config_id.as_str())?;
-            builder = builder.add_source(config::File::from_str(&content, format));
+        for item in contents.iter() {
+            builder = builder.add_source(item.to_source()?);
         }
 
         // 合并配置
@@ -258,19 +950,85 @@ impl Configs {
         Ok(Configs {
             flatten_config,
             merged_config,
+            contents,
+            md5s: HashMap::new(),
         })
     }
 
-    fn get_format(config_id: &str) -> anyhow::Result<config::FileFormat> {
-        let format = config_id.split('.').next_back().expect("invalid config id");
-        let format = match format {
-            "yaml" | "yml" => config::FileFormat::Yaml,
-            "json" => config::FileFormat::Json,
-            "ini" | "properties" => config::FileFormat::Ini,
-            "toml" => config::FileFormat::Toml,
-            _ => anyhow::bail!("unsupported config format: {}", config_id),
+    /// 读取本地覆盖文件，文件不存在时返回None
+    ///
+    /// 文件格式根据扩展名判断，内容不做解密
+    pub(crate) fn from_override_file(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let name = path.to_string_lossy();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("read config override file {}", name))?;
+        let configs = Self::from_contents(vec![ConfigContent {
+            config_id: name.to_string(),
+            format: ConfigFormat::detect(None, &name)?,
+            content,
+        }])
+        .with_context(|| format!("parse config override file {}", name))?;
+        Ok(Some(configs))
+    }
+
+    /// 按顺序合并多个配置源的配置，相同的配置项后面的配置源覆盖前面的配置源
+    ///
+    /// 各配置源的配置已分别解密，合并后不再需要解密
+    fn merge(sources: &[Configs]) -> anyhow::Result<Self> {
+        if let [source] = sources {
+            return Ok(source.clone());
+        }
+        let contents = sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| {
+                Ok(ConfigContent {
+                    config_id: format!("source-{}", index),
+                    format: ConfigFormat::Yaml,
+                    content: serde_yaml::to_string(&source.merged_config)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut merged = Self::from_contents(contents)?;
+        merged.contents = sources
+            .iter()
+            .flat_map(|source| source.contents.clone())
+            .collect();
+        Ok(merged)
+    }
+
+    fn with_md5s(mut self, md5s: HashMap<String, String>) -> Self {
+        self.md5s = md5s;
+        self
+    }
+
+    /// 使用`decrypt-key`解密`DEC(...)`形式的配置值，未配置密钥时不处理
+    ///
+    /// 原始配置内容保持加密，本地快照中不会出现明文
+    fn decrypt(mut self, key: Option<&str>) -> anyhow::Result<Self> {
+        let Some(key) = key else {
+            return Ok(self);
         };
-        Ok(format)
+        let key = DecryptKey::parse(key)?;
+        for (name, value) in self.flatten_config.iter_mut() {
+            key.decrypt_value(value)
+                .with_context(|| format!("decrypt config {} failed", name))?;
+        }
+        for value in self.merged_config.values_mut() {
+            key.decrypt_value(value)?;
+        }
+        Ok(self)
+    }
+
+    /// 获取配置的格式
+    pub fn format(&self, config_id: &str) -> Option<ConfigFormat> {
+        self.contents
+            .iter()
+            .find(|item| item.config_id == config_id)
+            .map(|item| item.format)
     }
 
     /// 展开yaml的key，通过"."分隔
@@ -311,6 +1069,189 @@ impl Configs {
         self.merged_config.get(key)
     }
 
+    /// 将合并后的配置反序列化为指定类型
+    pub fn bind<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        let value = Value::Mapping(Mapping::from_iter(
+            self.merged_config
+                .iter()
+                .map(|(k, v)| (k.as_str().into(), v.clone())),
+        ));
+        serde_yaml::from_value(value).map_err(|e| anyhow::anyhow!(self.redact(&e.to_string())))
+    }
+
+    /// 将指定前缀下的子配置反序列化为指定类型
+    ///
+    /// 示例：`bind_at("db.pool")`
+    pub fn bind_at<T: DeserializeOwned>(&self, prefix: &str) -> anyhow::Result<T> {
+        if prefix.is_empty() {
+            return self.bind();
+        }
+        match self.value(prefix) {
+            Some(value) => serde_yaml::from_value(value)
+                .map_err(|e| anyhow::anyhow!(self.redact(&e.to_string()))),
+            None => anyhow::bail!("config key [ {} ] not found", prefix),
+        }
+    }
+
+    /// 将合并后的配置导出为yaml，按配置项名称脱敏
+    ///
+    /// 配置项路径中任一段包含`mask_patterns`之一（忽略大小写）时，其值替换为`******`，顶层配置项按名称排序
+    pub fn dump(&self, mask_patterns: &[&str]) -> anyhow::Result<String> {
+        let patterns = mask_patterns
+            .iter()
+            .map(|pattern| pattern.to_lowercase())
+            .collect::<Vec<_>>();
+        let mut keys = self.merged_config.keys().collect::<Vec<_>>();
+        keys.sort();
+        let mut root = Mapping::new();
+        for key in keys {
+            let mut value = self.merged_config[key].clone();
+            Self::mask_value(&mut value, key, &patterns);
+            root.insert(key.as_str().into(), value);
+        }
+        Ok(serde_yaml::to_string(&Value::Mapping(root))?)
+    }
+
+    /// 将消息中出现的敏感配置项的值替换为`******`，用于日志和错误信息
+    ///
+    /// 反序列化失败等错误信息中可能包含配置项的值
+    pub(crate) fn redact(&self, message: &str) -> String {
+        self.redact_with(message, &mask_patterns())
+    }
+
+    fn redact_with(&self, message: &str, patterns: &[String]) -> String {
+        let mut secrets = vec![];
+        for (key, value) in self.merged_config.iter() {
+            Self::visit_sensitive(&mut value.clone(), key, patterns, &mut |value| {
+                Self::collect_scalars(value, &mut secrets)
+            });
+        }
+        // 先替换较长的值，避免较短的值是其一部分时替换不完整
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        secrets
+            .into_iter()
+            .fold(message.to_string(), |message, secret| {
+                message.replace(&secret, MASKED_VALUE)
+            })
+    }
+
+    /// 脱敏后的配置项的值，用于日志
+    ///
+    /// 配置项路径中任一段匹配时整体脱敏，否则脱敏其下匹配的子配置项
+    fn masked(key: &str, value: Option<&Value>, patterns: &[String]) -> String {
+        let Some(value) = value else {
+            return "null".to_string();
+        };
+        let mut value = value.clone();
+        if key
+            .split('.')
+            .any(|segment| Self::is_sensitive(segment, patterns))
+        {
+            Self::mask_all(&mut value);
+        } else {
+            Self::mask_value(&mut value, "", patterns);
+        }
+        match value {
+            Value::String(value) => value,
+            Value::Number(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+            Value::Null => "null".to_string(),
+            value => serde_yaml::to_string(&value)
+                .map(|value| value.trim_end().replace('\n', " "))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 配置项名称是否包含任一脱敏名称
+    fn is_sensitive(segment: &str, patterns: &[String]) -> bool {
+        let segment = segment.to_lowercase();
+        patterns
+            .iter()
+            .any(|pattern| segment.contains(pattern.as_str()))
+    }
+
+    /// 脱敏配置项，`segment`为配置项在父级中的名称，匹配时脱敏其下所有的值
+    fn mask_value(value: &mut Value, segment: &str, patterns: &[String]) {
+        Self::visit_sensitive(value, segment, patterns, &mut Self::mask_all);
+    }
+
+    /// 查找需要脱敏的子配置，对其调用`f`，`segment`为配置项在父级中的名称
+    fn visit_sensitive<F: FnMut(&mut Value)>(
+        value: &mut Value,
+        segment: &str,
+        patterns: &[String],
+        f: &mut F,
+    ) {
+        if Self::is_sensitive(segment, patterns) {
+            f(value);
+            return;
+        }
+        match value {
+            Value::Mapping(mapping) => {
+                for (key, value) in mapping.iter_mut() {
+                    let key = match key {
+                        Value::String(s) => s.clone(),
+                        Value::Number(num) => num.to_string(),
+                        _ => continue,
+                    };
+                    Self::visit_sensitive(value, &key, patterns, f);
+                }
+            }
+            Value::Sequence(values) => {
+                for value in values.iter_mut() {
+                    Self::visit_sensitive(value, "", patterns, f);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 收集所有叶子节点的字符串和数字值
+    fn collect_scalars(value: &Value, scalars: &mut Vec<String>) {
+        match value {
+            Value::Mapping(mapping) => mapping
+                .values()
+                .for_each(|value| Self::collect_scalars(value, scalars)),
+            Value::Sequence(values) => values
+                .iter()
+                .for_each(|value| Self::collect_scalars(value, scalars)),
+            Value::String(value) if !value.is_empty() => scalars.push(value.clone()),
+            Value::Number(value) => scalars.push(value.to_string()),
+            _ => {}
+        }
+    }
+
+    /// 脱敏所有的叶子节点
+    fn mask_all(value: &mut Value) {
+        match value {
+            Value::Mapping(mapping) => mapping.values_mut().for_each(Self::mask_all),
+            Value::Sequence(values) => values.iter_mut().for_each(Self::mask_all),
+            Value::Null => {}
+            value => *value = Value::from(MASKED_VALUE),
+        }
+    }
+
+    /// 合并后的配置内容是否与另一个配置相同
+    pub(crate) fn same_content(&self, other: &Configs) -> bool {
+        self.merged_config == other.merged_config
+    }
+
+    /// 添加配置绑定
+    pub(crate) fn add_binding(binding: ConfigBinding) {
+        CONFIG_BINDINGS
+            .lock()
+            .expect("lock config bindings error")
+            .push(binding);
+    }
+
+    /// 以当前配置通知所有绑定，并移除已失效的绑定
+    pub(crate) fn notify_bindings(&self) {
+        CONFIG_BINDINGS
+            .lock()
+            .expect("lock config bindings error")
+            .retain(|binding| binding(self));
+    }
+
     /// 获取所有配置项
     #[allow(unused)]
     pub fn get_all(&self) -> &HashMap<String, Value> {
@@ -323,26 +1264,115 @@ impl Configs {
         self.flatten_config.contains_key(key)
     }
 
-    /// 添加配置监听器
-    pub fn add_listener(config_id: &str, handler: fn(&HashMap<String, Value>)) {
-        if let Some(mut handlers) = CONFIG_LISTENER.listeners.get_mut(config_id) {
-            handlers.push(handler);
-        } else {
-            CONFIG_LISTENER
-                .listeners
-                .insert(config_id.to_string(), vec![handler]);
+    /// 添加配置监听器，返回的句柄可用于移除监听器
+    pub fn add_listener(config_id: &str, handler: ConfigListenerFn) -> ListenerHandle {
+        let id = CONFIG_LISTENER.next_id.fetch_add(1, Ordering::Relaxed);
+        CONFIG_LISTENER
+            .listeners
+            .entry(config_id.to_string())
+            .or_default()
+            .push((id, handler));
+        ListenerHandle {
+            target: ListenerTarget::Config(config_id.to_string()),
+            id,
+        }
+    }
+
+    /// 添加配置项监听器，返回的句柄可用于移除监听器
+    pub fn add_key_listener(key: &str, handler: KeyListenerFn) -> ListenerHandle {
+        let id = CONFIG_LISTENER.next_id.fetch_add(1, Ordering::Relaxed);
+        CONFIG_LISTENER
+            .key_listeners
+            .entry(key.to_string())
+            .or_default()
+            .push((id, handler));
+        ListenerHandle {
+            target: ListenerTarget::Key(key.to_string()),
+            id,
+        }
+    }
+
+    /// 获取配置项的值，不是叶子节点时返回合并后的子配置
+    ///
+    /// 示例：`value("db.pool")`
+    fn value(&self, key: &str) -> Option<Value> {
+        if let Some(value) = self.flatten_config.get(key) {
+            return Some(value.clone());
+        }
+        let mut segments = key.split('.');
+        let mut value = self.merged_config.get(segments.next()?)?;
+        for segment in segments {
+            value = value.as_mapping()?.get(segment)?;
         }
+        Some(value.clone())
+    }
+
+    /// 计算配置项监听器需要通知的变更，返回(监听函数, 变更前的值, 变更后的值)
+    ///
+    /// 监听的配置项本身或以其为前缀的任一配置项发生变化时通知
+    pub(crate) fn key_changes(
+        old: &Configs,
+        new: &Configs,
+    ) -> Vec<(KeyListenerFn, Option<Value>, Option<Value>)> {
+        let changed_keys = old
+            .flatten_config
+            .keys()
+            .chain(new.flatten_config.keys())
+            .filter(|key| old.flatten_config.get(*key) != new.flatten_config.get(*key))
+            .collect::<Vec<_>>();
+        if changed_keys.is_empty() {
+            return vec![];
+        }
+        if log::log_enabled!(log::Level::Debug) {
+            let patterns = mask_patterns();
+            for key in changed_keys.iter().collect::<BTreeSet<_>>() {
+                log::debug!(
+                    "config key [ {} ] changed: {} -> {}",
+                    key,
+                    Self::masked(key, old.flatten_config.get(*key), &patterns),
+                    Self::masked(key, new.flatten_config.get(*key), &patterns)
+                );
+            }
+        }
+
+        let mut changes = vec![];
+        for entry in CONFIG_LISTENER.key_listeners.iter() {
+            let key = entry.key();
+            let changed = changed_keys.iter().any(|changed| {
+                changed.as_str() == key
+                    || changed
+                        .strip_prefix(key.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            });
+            if !changed || entry.value().is_empty() {
+                continue;
+            }
+            let (old_value, new_value) = (old.value(key), new.value(key));
+            for (_, handler) in entry.value() {
+                changes.push((handler.clone(), old_value.clone(), new_value.clone()));
+            }
+        }
+        changes
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn content(config_id: &str, content: &str) -> ConfigContent {
+        ConfigContent {
+            config_id: config_id.to_string(),
+            format: ConfigFormat::detect(None, config_id).unwrap(),
+            content: content.to_string(),
+        }
+    }
+
     #[test]
     fn test_app_config() {
         let contents = vec![
-            (
-                "test1.yaml".to_string(),
+            content(
+                "test1.yaml",
                 r#"
             a: 1
             b: 2
@@ -354,11 +1384,10 @@ mod tests {
             h:
               - 1
               - 2
-            "#
-                .to_string(),
+            "#,
             ),
-            (
-                "test2.yaml".to_string(),
+            content(
+                "test2.yaml",
                 r#"
             a: 5
             b: 6
@@ -370,8 +1399,7 @@ mod tests {
             h:
               - 1
               - 3
-            "#
-                .to_string(),
+            "#,
             ),
         ];
         let config = Configs::from_contents(contents).unwrap();
@@ -379,4 +1407,328 @@ mod tests {
         println!("{:?}", config.get("a"));
         println!("{:?}", config.get("h"));
     }
+
+    #[test]
+    fn test_formats() {
+        let contents = vec![
+            content("a.yaml", "app:\n  name: a\n  port: 80\n"),
+            content("b.json", r#"{"app": {"port": 81, "debug": true}}"#),
+            content("c.toml", "[app]\ntimeout = 5\n"),
+            content(
+                "d.properties",
+                "# comment\napp.name=d\napp.tags.first = x:y\n",
+            ),
+        ];
+        let config = Configs::from_contents(contents).unwrap();
+        assert_eq!(config.get("app.name"), Some(&Value::from("d")));
+        assert_eq!(config.get("app.port"), Some(&Value::from(81)));
+        assert_eq!(config.get("app.debug"), Some(&Value::from(true)));
+        assert_eq!(config.get("app.timeout"), Some(&Value::from(5)));
+        assert_eq!(config.get("app.tags.first"), Some(&Value::from("x:y")));
+        assert_eq!(config.format("c.toml"), Some(ConfigFormat::Toml));
+    }
+
+    #[test]
+    fn test_bind() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct App {
+            name: String,
+            port: u16,
+        }
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Settings {
+            app: App,
+        }
+
+        let config = Configs::from_contents(vec![
+            content("a.yaml", "app:\n  name: a\n  port: 80\n"),
+            content("b.properties", "app.port=81\n"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.bind::<Settings>().unwrap(),
+            Settings {
+                app: App {
+                    name: "a".to_string(),
+                    port: 81
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_bind_at() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Pool {
+            size: u32,
+        }
+
+        let config = Configs::from_contents(vec![
+            content("a.yaml", "db:\n  url: mysql://a\n  pool:\n    size: 5\n"),
+            content("b.properties", "db.pool.size=8\n"),
+        ])
+        .unwrap();
+        assert_eq!(config.bind_at::<Pool>("db.pool").unwrap(), Pool { size: 8 });
+        assert_eq!(
+            config.bind_at::<String>("db.url").unwrap(),
+            "mysql://a".to_string()
+        );
+        assert!(config.bind_at::<Pool>("cache").is_err());
+    }
+
+    #[test]
+    fn test_bind_watch_reload() {
+        use std::sync::RwLock;
+        use std::sync::atomic::AtomicBool;
+
+        let port = |port: u16| {
+            Configs::from_contents(vec![content("watch.yaml", &format!("port: {}\n", port))])
+                .unwrap()
+        };
+        let store = Arc::new(RwLock::new(ConfigStore::new(vec![port(80)]).unwrap()));
+        let reloader = Arc::new(Mutex::new(None));
+        let started = AtomicBool::new(false);
+        let receiver = {
+            let store = store.clone();
+            let reloader = reloader.clone();
+            AppConfig::watch_store(&store.clone(), move |configs| {
+                // 读取初始值后、注册绑定前重新加载
+                if !started.swap(true, Ordering::SeqCst) {
+                    let store = store.clone();
+                    *reloader.lock().unwrap() = Some(std::thread::spawn(move || {
+                        AppConfig::reload_store(&store, 0, port(81))
+                    }));
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                configs.bind_at::<u16>("port")
+            })
+            .unwrap()
+        };
+        let handle = reloader.lock().unwrap().take().unwrap();
+        handle.join().unwrap();
+        assert_eq!(*receiver.borrow(), 81);
+    }
+
+    #[test]
+    fn test_dump() {
+        let config = Configs::from_contents(vec![content(
+            "a.yaml",
+            "name: app\ndb:\n  url: mysql://a\n  Password: p\nsecrets:\n  - a\n  - b\nclients:\n  - id: x\n    api-key: k\n",
+        )])
+        .unwrap();
+        let dump = config.dump(DEFAULT_MASK_PATTERNS).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Value>(&dump).unwrap(),
+            serde_yaml::from_str::<Value>(
+                "clients:\n  - id: x\n    api-key: '******'\ndb:\n  url: mysql://a\n  Password: '******'\nname: app\nsecrets:\n  - '******'\n  - '******'\n"
+            )
+            .unwrap()
+        );
+        // 顶层配置项按名称排序
+        assert!(dump.starts_with("clients:"));
+        assert!(config.dump(&[]).unwrap().contains("Password: p"));
+    }
+
+    #[test]
+    fn test_redact() {
+        let config = Configs::from_contents(vec![content(
+            "a.yaml",
+            "name: app\ndb:\n  port: 5432\n  password: hunter22\nauth:\n  token: 123456\n",
+        )])
+        .unwrap();
+        let patterns = DEFAULT_MASK_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect::<Vec<_>>();
+        let e = config.bind_at::<u16>("db.password").unwrap_err();
+        assert!(!e.to_string().contains("hunter22"));
+        assert!(e.to_string().contains(MASKED_VALUE));
+        assert_eq!(
+            config.redact_with("invalid value: integer `123456`, app 5432", &patterns),
+            "invalid value: integer `******`, app 5432"
+        );
+        assert_eq!(config.redact_with("hunter22", &[]), "hunter22");
+
+        assert_eq!(
+            Configs::masked("db.password", config.get("db.password"), &patterns),
+            MASKED_VALUE
+        );
+        assert_eq!(
+            Configs::masked("db.port", config.get("db.port"), &patterns),
+            "5432"
+        );
+        let db = Configs::masked("db", config.get_raw("db"), &patterns);
+        assert!(db.contains("password: '******'") && db.contains("port: 5432"));
+        assert_eq!(Configs::masked("db.password", None, &patterns), "null");
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let key = "11".repeat(32);
+        let password = DecryptKey::parse(&key).unwrap().encrypt("secret").unwrap();
+        let contents = vec![
+            content("a.yaml", &format!("db:\n  password: {}\n", password)),
+            content("b.properties", &format!("db.user={}\n", password)),
+        ];
+
+        let config = Configs::from_contents(contents.clone())
+            .unwrap()
+            .decrypt(Some(&key))
+            .unwrap();
+        assert_eq!(config.get("db.password"), Some(&Value::from("secret")));
+        assert_eq!(config.get("db.user"), Some(&Value::from("secret")));
+        assert_eq!(
+            config.get_raw("db").unwrap()["password"],
+            Value::from("secret")
+        );
+        assert!(config.contents[0].content.contains(&password));
+
+        let config = Configs::from_contents(contents.clone()).unwrap();
+        let config = config.decrypt(None).unwrap();
+        assert_eq!(config.get("db.password"), Some(&Value::from(password)));
+        let config = Configs::from_contents(contents).unwrap();
+        assert!(config.decrypt(Some(&"22".repeat(32))).is_err());
+    }
+
+    #[test]
+    fn test_merge_sources() {
+        let key = "11".repeat(32);
+        let password = DecryptKey::parse(&key).unwrap().encrypt("secret").unwrap();
+        let shared = Configs::from_contents(vec![content(
+            "shared.yaml",
+            "db:\n  host: shared\n  port: 3306\nname: shared\n",
+        )])
+        .unwrap();
+        let app = Configs::from_contents(vec![content(
+            "app.properties",
+            &format!("db.host=app\ndb.password={}\n", password),
+        )])
+        .unwrap()
+        .decrypt(Some(&key))
+        .unwrap();
+
+        let mut store = ConfigStore::new(vec![shared, app]).unwrap();
+        let merged = store.merged();
+        assert_eq!(merged.get("db.host"), Some(&Value::from("app")));
+        assert_eq!(merged.get("db.port"), Some(&Value::from(3306)));
+        assert_eq!(merged.get("db.password"), Some(&Value::from("secret")));
+        assert_eq!(merged.get("name"), Some(&Value::from("shared")));
+        assert_eq!(
+            merged.format("app.properties"),
+            Some(ConfigFormat::Properties)
+        );
+
+        let shared = Configs::from_contents(vec![content(
+            "shared.yaml",
+            "db:\n  host: shared\n  port: 3307\n",
+        )])
+        .unwrap();
+        let old = store.replace(0, shared).unwrap();
+        assert_eq!(old.get("db.port"), Some(&Value::from(3306)));
+        let merged = store.merged();
+        assert_eq!(merged.get("db.port"), Some(&Value::from(3307)));
+        assert_eq!(merged.get("db.host"), Some(&Value::from("app")));
+        assert_eq!(merged.get("name"), None);
+        assert_eq!(
+            store.source(0).unwrap().get("db.host"),
+            Some(&Value::from("shared"))
+        );
+        assert!(
+            store
+                .replace(2, Configs::from_contents(vec![]).unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_override_file() {
+        let dir = std::env::temp_dir().join(format!("conreg-override-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("conreg.override.yaml");
+        assert!(Configs::from_override_file(&path).unwrap().is_none());
+
+        std::fs::write(&path, "db:\n  host: localhost\n").unwrap();
+        let source = Configs::from_contents(vec![content(
+            "app.yaml",
+            "db:\n  host: shared\n  port: 3306\n",
+        )])
+        .unwrap();
+        let override_configs = Configs::from_override_file(&path).unwrap().unwrap();
+        let store = ConfigStore::new(vec![source, override_configs]).unwrap();
+        assert_eq!(
+            store.merged().get("db.host"),
+            Some(&Value::from("localhost"))
+        );
+        assert_eq!(store.merged().get("db.port"), Some(&Value::from(3306)));
+
+        std::fs::write(&path, "db: [").unwrap();
+        assert!(Configs::from_override_file(&path).is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_listener_handle() {
+        let count = Arc::new(AtomicU64::new(0));
+        let counter = count.clone();
+        let handle = Configs::add_listener(
+            "listener.yaml",
+            Arc::new(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }),
+        );
+        ConfigClient::notify_config_change("listener.yaml", &HashMap::new());
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+
+        assert!(handle.remove());
+        assert!(!handle.remove());
+        ConfigClient::notify_config_change("listener.yaml", &HashMap::new());
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_key_listener() {
+        let old = Configs::from_contents(vec![content(
+            "key.yaml",
+            "key_listener:\n  pool:\n    size: 10\n    idle: 2\n  name: a\n",
+        )])
+        .unwrap();
+        let new = Configs::from_contents(vec![content(
+            "key.yaml",
+            "key_listener:\n  pool:\n    size: 10\n    idle: 3\n  name: a\n",
+        )])
+        .unwrap();
+
+        let noop: KeyListenerFn = Arc::new(|_, _| {});
+        let size = Configs::add_key_listener("key_listener.pool.size", noop.clone());
+        let name = Configs::add_key_listener("key_listener.name", noop.clone());
+        let pool = Configs::add_key_listener("key_listener.pool", noop.clone());
+        let prefix = Configs::add_key_listener("key_listener.po", noop);
+        assert_eq!(pool.key(), Some("key_listener.pool"));
+        assert_eq!(pool.config_id(), None);
+
+        let changes = Configs::key_changes(&old, &new);
+        assert_eq!(changes.len(), 1);
+        let (_, old_value, new_value) = &changes[0];
+        assert_eq!(old_value.as_ref().unwrap()["idle"], Value::Number(2.into()));
+        assert_eq!(new_value.as_ref().unwrap()["idle"], Value::Number(3.into()));
+
+        for handle in [size, name, pool, prefix] {
+            assert!(handle.remove());
+        }
+        assert!(Configs::key_changes(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            ConfigFormat::detect(Some("json"), "app").unwrap(),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::detect(Some("text"), "app.yml").unwrap(),
+            ConfigFormat::Yaml
+        );
+        assert!(ConfigFormat::detect(None, "app").is_err());
+    }
 }
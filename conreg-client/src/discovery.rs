@@ -1,12 +1,168 @@
+#[cfg(feature = "grpc")]
+use crate::conf::Protocol;
 use crate::conf::{ClientConfig, ConRegConfig, DiscoveryConfig};
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::lb::ZoneAffinity;
 use crate::network::HTTP;
-use crate::protocol::Instance;
-use crate::protocol::request::{GetInstancesReq, HeartbeatReq, RegisterReq};
-use crate::protocol::response::HeartbeatResult;
+use crate::protocol::request::{
+    DeregisterReq, GetActiveSetsReq, GetInstancesReq, HeartbeatReq, RegisterReq, UpdateMetaReq,
+    WatchInstancesReq,
+};
+use crate::protocol::response::{HeartbeatResponse, HeartbeatResult, InstancesSnapshot};
+use crate::protocol::{EvictionNotice, Instance};
+use crate::timer::{Ticker, jitter};
+use crate::udp::UdpHeartbeat;
+use anyhow::bail;
 use dashmap::DashMap;
+use serde_yaml::Value;
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::sync::Arc;
-use std::time::Duration;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// 等待服务实例时的拉取间隔
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+/// 服务生效实例集合（蓝绿部署）的检查间隔
+const ACTIVE_SET_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// UDP心跳等待响应的默认超时时间，毫秒
+const UDP_HEARTBEAT_TIMEOUT_MS: u64 = 1000;
+/// 监听服务实例出错后的重试间隔
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 驱逐通知监听函数
+pub(crate) type EvictionListener = Arc<dyn Fn(&EvictionNotice) + Send + Sync>;
+
+/// (监听ID, 监听函数)
+static EVICTION_LISTENERS: LazyLock<RwLock<Vec<(u64, EvictionListener)>>> =
+    LazyLock::new(|| RwLock::new(vec![]));
+
+/// 下一个驱逐通知监听ID
+static NEXT_EVICTION_LISTENER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// 添加驱逐通知监听函数，返回的句柄可用于移除监听函数
+pub(crate) fn add_eviction_listener(handler: EvictionListener) -> EvictionListenerHandle {
+    let id = NEXT_EVICTION_LISTENER_ID.fetch_add(1, Ordering::Relaxed);
+    EVICTION_LISTENERS.write().unwrap().push((id, handler));
+    EvictionListenerHandle { id }
+}
+
+/// 通知所有驱逐通知监听函数
+fn notify_eviction(notice: &EvictionNotice) {
+    // 复制监听函数后再调用，监听函数中可以移除监听
+    let handlers = EVICTION_LISTENERS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(_, handler)| handler.clone())
+        .collect::<Vec<_>>();
+    for handler in handlers {
+        handler(notice);
+    }
+}
+
+/// Handle of an eviction handler, used to remove the handler
+///
+/// Dropping the handle does not remove the handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvictionListenerHandle {
+    id: u64,
+}
+
+impl EvictionListenerHandle {
+    /// Remove the handler, returns false if it has already been removed
+    pub fn remove(&self) -> bool {
+        let mut listeners = EVICTION_LISTENERS.write().unwrap();
+        let len = listeners.len();
+        listeners.retain(|(id, _)| *id != self.id);
+        len != listeners.len()
+    }
+}
+
+/// 服务实例变化监听函数
+pub(crate) type SubscriberFn = Arc<dyn Fn(&[Instance]) + Send + Sync>;
+
+/// 服务的订阅者
+#[derive(Default)]
+struct Subscribers {
+    /// (订阅ID, 监听函数)
+    handlers: Vec<(u64, SubscriberFn)>,
+    /// 最近一次通知的实例，监听任务首次返回前为None
+    instances: Option<Vec<Instance>>,
+}
+
+/// 服务订阅者，key为服务ID。存在时表示该服务的监听任务正在运行
+static SUBSCRIBERS: LazyLock<DashMap<String, Subscribers>> = LazyLock::new(DashMap::new);
+
+/// 下一个订阅ID
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Handle of a service subscription, used to unsubscribe
+///
+/// Dropping the handle does not unsubscribe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    service_id: String,
+    id: u64,
+}
+
+impl Subscription {
+    /// Service ID subscribed to
+    pub fn service_id(&self) -> &str {
+        &self.service_id
+    }
+
+    /// Unsubscribe, returns false if it has already been unsubscribed
+    ///
+    /// The service is no longer watched once all its subscriptions are removed.
+    pub fn unsubscribe(&self) -> bool {
+        let Some(mut subscribers) = SUBSCRIBERS.get_mut(&self.service_id) else {
+            return false;
+        };
+        let len = subscribers.handlers.len();
+        subscribers.handlers.retain(|(id, _)| *id != self.id);
+        len != subscribers.handlers.len()
+    }
+}
+
+/// Statistics of the local view of the registry, see [`AppDiscovery::stats`](crate::AppDiscovery::stats)
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryStats {
+    /// Services known to this client, key is the service ID
+    pub services: HashMap<String, ServiceStats>,
+    /// Time of the last successful fetch of any service, None if nothing has been fetched
+    pub last_fetch_at: Option<SystemTime>,
+    /// Heartbeats failed in a row since the last accepted one
+    pub consecutive_heartbeat_failures: u64,
+}
+
+/// Statistics of a service known to this client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceStats {
+    /// Number of available instances in the local cache
+    pub instances: usize,
+    /// Time the instances were last fetched from, or confirmed unchanged by, the server
+    pub last_fetch_at: SystemTime,
+}
+
+/// 缓存的服务实例
+#[derive(Debug, Clone)]
+struct CachedInstances {
+    instances: Vec<Instance>,
+    /// 最近一次从注册中心同步成功的时间
+    fetched_at: SystemTime,
+}
+
+impl CachedInstances {
+    fn new(instances: Vec<Instance>) -> Self {
+        Self {
+            instances,
+            fetched_at: SystemTime::now(),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct DiscoveryClient {
@@ -16,6 +172,16 @@ pub struct DiscoveryClient {
     client: ClientConfig,
     /// 注册中心配置
     config: DiscoveryConfig,
+    /// 实例元数据，初始为配置中的元数据，运行时更新后重新注册时使用更新后的值
+    meta: Arc<RwLock<HashMap<String, Value>>>,
+    /// 通信协议
+    #[cfg(feature = "grpc")]
+    protocol: Protocol,
+    /// gRPC心跳流，首次心跳时建立，出错后在下次心跳时重新建立
+    #[cfg(feature = "grpc")]
+    heartbeat_stream: Arc<tokio::sync::Mutex<Option<grpc::HeartbeatStream>>>,
+    /// UDP心跳连接，开启`udp_heartbeat`时使用
+    udp_heartbeat: Arc<UdpHeartbeat>,
 }
 
 impl DiscoveryClient {
@@ -24,7 +190,31 @@ impl DiscoveryClient {
             service_id: config.service_id.clone(),
             client: config.client.clone(),
             config: config.discovery.clone().unwrap(),
+            meta: Arc::new(RwLock::new(
+                config
+                    .discovery
+                    .as_ref()
+                    .map(Self::initial_meta)
+                    .unwrap_or_default(),
+            )),
+            #[cfg(feature = "grpc")]
+            protocol: config.protocol,
+            #[cfg(feature = "grpc")]
+            heartbeat_stream: Arc::new(tokio::sync::Mutex::new(None)),
+            udp_heartbeat: Arc::new(UdpHeartbeat::default()),
+        }
+    }
+
+    /// 初始元数据，配置了可用区和地域时加入`zone`和`region`，元数据中已有的值优先
+    fn initial_meta(config: &DiscoveryConfig) -> HashMap<String, Value> {
+        let mut meta = config.meta.clone();
+        for (key, value) in [("zone", &config.zone), ("region", &config.region)] {
+            if let Some(value) = value {
+                meta.entry(key.to_string())
+                    .or_insert_with(|| Value::from(value.as_str()));
+            }
         }
+        meta
     }
 
     /// 注册服务实例，并返回注册的实例
@@ -40,21 +230,70 @@ impl DiscoveryClient {
             service_id: self.service_id.clone(),
             ip: self.client.address.clone(),
             port: self.client.port,
-            meta: self.config.meta.clone(),
+            meta: self.meta.read().unwrap().clone(),
         };
-        let instance = HTTP
-            .post::<Instance>(
-                &self
-                    .config
-                    .server_addr
-                    .build_url("/api/discovery/instance/register")?,
-                req,
-            )
+        #[cfg(feature = "grpc")]
+        if self.protocol == Protocol::Grpc {
+            let instance = grpc::register(&self.config.server_addr, req).await?;
+            log::info!("register instance with service id: {}", self.service_id);
+            return Ok(instance);
+        }
+        let req = &req;
+        let instance = self
+            .config
+            .server_addr
+            .request_once("/api/discovery/instance/register", |url| async move {
+                HTTP.post::<Instance>(&url, req).await
+            })
             .await?;
         log::info!("register instance with service id: {}", self.service_id);
         Ok(instance)
     }
 
+    /// 注销本服务实例，注销后其他服务立即不再获取到该实例
+    pub(crate) async fn deregister(&self) -> anyhow::Result<()> {
+        let req = DeregisterReq {
+            namespace_id: self.config.namespace.clone(),
+            service_id: self.service_id.clone(),
+            instance_id: self.client.gen_instance_id(),
+        };
+        let req = &req;
+        self.config
+            .server_addr
+            .request("/api/discovery/instance/deregister", |url| async move {
+                HTTP.post::<()>(&url, req).await
+            })
+            .await?;
+        log::info!("deregister instance with service id: {}", self.service_id);
+        Ok(())
+    }
+
+    /// 更新本实例的元数据，meta中的key覆盖原有的值，其余元数据保持不变
+    pub(crate) async fn update_meta(&self, meta: HashMap<String, String>) -> anyhow::Result<()> {
+        let req = UpdateMetaReq {
+            namespace_id: self.config.namespace.clone(),
+            service_id: self.service_id.clone(),
+            instance_id: self.client.gen_instance_id(),
+            meta: meta.clone(),
+        };
+        let req = &req;
+        self.config
+            .server_addr
+            .request_once("/api/discovery/instance/update-meta", |url| async move {
+                HTTP.post::<Instance>(&url, req).await
+            })
+            .await?;
+        self.meta.write().unwrap().extend(
+            meta.into_iter()
+                .map(|(key, value)| (key, Value::String(value))),
+        );
+        log::info!(
+            "metadata of instance updated, service id: {}",
+            self.service_id
+        );
+        Ok(())
+    }
+
     /// 获取可用服务实例
     ///
     /// 可用服务实例是指实例状态为`UP`的实例
@@ -63,18 +302,78 @@ impl DiscoveryClient {
             namespace_id: self.config.namespace.clone(),
             service_id: service_id.to_string(),
         };
-        HTTP.get::<Vec<Instance>>(
-            &self
-                .config
-                .server_addr
-                .build_url("/api/discovery/instance/available")?,
-            req,
-            match &self.config.auth_token {
-                Some(token) => Some(vec![(crate::NS_TOKEN_HEADER, token)]),
-                None => None,
-            },
-        )
-        .await
+        #[cfg(feature = "grpc")]
+        if self.protocol == Protocol::Grpc {
+            return grpc::fetch_instances(&self.config.server_addr, req, &self.config.auth_token)
+                .await;
+        }
+        let req = &req;
+        self.config
+            .server_addr
+            .request("/api/discovery/instance/available", |url| async move {
+                HTTP.get::<Vec<Instance>>(
+                    &url,
+                    req,
+                    match &self.config.auth_token {
+                        Some(token) => Some(vec![(crate::NS_TOKEN_HEADER, token)]),
+                        None => None,
+                    },
+                )
+                .await
+            })
+            .await
+    }
+
+    /// 监听服务可用实例的变化（长轮询）
+    ///
+    /// 实例列表的版本与`version`不一致时立即返回当前实例，29秒内没有变化时返回None
+    async fn watch_instances(
+        &self,
+        service_id: &str,
+        version: &str,
+    ) -> anyhow::Result<Option<InstancesSnapshot>> {
+        let req = WatchInstancesReq {
+            namespace_id: self.config.namespace.clone(),
+            service_id: service_id.to_string(),
+            version: version.to_string(),
+        };
+        let req = &req;
+        self.config
+            .server_addr
+            .request("/api/discovery/instance/watch", |url| async move {
+                HTTP.get::<Option<InstancesSnapshot>>(
+                    &url,
+                    req,
+                    match &self.config.auth_token {
+                        Some(token) => Some(vec![(crate::NS_TOKEN_HEADER, token)]),
+                        None => None,
+                    },
+                )
+                .await
+            })
+            .await
+    }
+
+    /// 获取命名空间下各服务当前生效的实例集合，key为服务ID
+    async fn fetch_active_sets(&self) -> anyhow::Result<HashMap<String, String>> {
+        let req = GetActiveSetsReq {
+            namespace_id: self.config.namespace.clone(),
+        };
+        let req = &req;
+        self.config
+            .server_addr
+            .request("/api/discovery/service/active-sets", |url| async move {
+                HTTP.get::<HashMap<String, String>>(
+                    &url,
+                    req,
+                    match &self.config.auth_token {
+                        Some(token) => Some(vec![(crate::NS_TOKEN_HEADER, token)]),
+                        None => None,
+                    },
+                )
+                .await
+            })
+            .await
     }
 
     /// 发送心跳
@@ -83,62 +382,253 @@ impl DiscoveryClient {
     /// - Ok: 成功
     /// - NoInstanceFound: 找不到实例，需要重新注册
     /// - Unknown: 未知结果，可能出现在客户端和服务端版本不兼容时
-    async fn heartbeat(&self) -> anyhow::Result<HeartbeatResult> {
+    ///
+    /// 本实例曾因心跳超时被判定为不健康或已被移除时，同时返回驱逐通知
+    async fn heartbeat(&self) -> anyhow::Result<(HeartbeatResult, Option<EvictionNotice>)> {
         let req = HeartbeatReq {
             namespace_id: self.config.namespace.clone(),
             service_id: self.service_id.to_string(),
             instance_id: self.client.gen_instance_id(),
+            with_notice: true,
         };
-        HTTP.post::<HeartbeatResult>(
-            &self
-                .config
-                .server_addr
-                .build_url("/api/discovery/heartbeat")?,
-            req,
+        if self.config.udp_heartbeat {
+            let timeout = Duration::from_millis(
+                self.config
+                    .heartbeat_timeout_ms
+                    .unwrap_or(UDP_HEARTBEAT_TIMEOUT_MS),
+            );
+            return self
+                .udp_heartbeat
+                .heartbeat(
+                    &self.config.server_addr,
+                    req,
+                    &self.config.auth_token,
+                    timeout,
+                )
+                .await;
+        }
+        #[cfg(feature = "grpc")]
+        if self.protocol == Protocol::Grpc {
+            let mut stream = self.heartbeat_stream.lock().await;
+            let heartbeat = match stream.as_mut() {
+                Some(heartbeat) => heartbeat,
+                None => {
+                    stream.insert(grpc::HeartbeatStream::connect(&self.config.server_addr).await?)
+                }
+            };
+            let res = self.with_timeout(heartbeat.heartbeat(req)).await;
+            // 出错或超时后流中可能残留未读取的响应，重新建立
+            if res.is_err() {
+                *stream = None;
+            }
+            return res;
+        }
+        let req = &req;
+        let res = self
+            .with_timeout(
+                self.config
+                    .server_addr
+                    .request("/api/discovery/heartbeat", |url| async move {
+                        HTTP.post::<HeartbeatResponse>(&url, req).await
+                    }),
+            )
+            .await?;
+        Ok(match res {
+            HeartbeatResponse::Result(result) => (result, None),
+            HeartbeatResponse::WithNotice { result, eviction } => (result, eviction),
+        })
+    }
+
+    /// 按`heartbeat_timeout_ms`限制心跳请求的时间，未配置时不限制
+    async fn with_timeout<T>(
+        &self,
+        future: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        let Some(timeout) = self.config.heartbeat_timeout_ms else {
+            return future.await;
+        };
+        match tokio::time::timeout(Duration::from_millis(timeout), future).await {
+            Ok(res) => res,
+            Err(_) => bail!("heartbeat timed out after {}ms", timeout),
+        }
+    }
+
+    /// 心跳间隔
+    fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.config.heartbeat_interval.max(1))
+    }
+
+    /// 服务实例同步间隔
+    fn fetch_interval(&self) -> Duration {
+        Duration::from_secs(self.config.fetch_interval.max(1))
+    }
+
+    /// 注册服务实例，失败时按`register_retries`重试
+    pub(crate) async fn register_with_retry(&self) -> anyhow::Result<Instance> {
+        self.retry(
+            self.config.register_retries,
+            self.register_max_backoff(),
+            || self.register(),
         )
         .await
     }
+
+    /// 注册重试的最大等待时间
+    fn register_max_backoff(&self) -> Duration {
+        Duration::from_millis(
+            self.config
+                .register_max_backoff_ms
+                .max(self.config.retry_backoff_ms),
+        )
+    }
+
+    /// 执行请求，失败时最多重试`max_retries`次
+    ///
+    /// 重试等待时间从`retry_backoff_ms`开始每次翻倍，不超过`max`，并附加随机抖动
+    async fn retry<T, F, Fut>(&self, max_retries: u32, max: Duration, f: F) -> anyhow::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms).min(max);
+        let mut retries = 0;
+        loop {
+            match f().await {
+                Ok(res) => return Ok(res),
+                Err(e) if retries < max_retries => {
+                    retries += 1;
+                    log::debug!(
+                        "request failed, retry {}/{} in {:?}: {}",
+                        retries,
+                        max_retries,
+                        backoff,
+                        e
+                    );
+                    tokio::time::sleep(jitter(backoff)).await;
+                    backoff = (backoff * 2).min(max);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Discovery {
     /// 服务实例缓存
-    services: Arc<DashMap<String, Vec<Instance>>>,
+    services: Arc<DashMap<String, CachedInstances>>,
     /// 服务发现client，负责与服务注册中心通信
     client: DiscoveryClient,
+    /// 是否已注销，注销后停止心跳，不再重新注册
+    shutdown: Arc<AtomicBool>,
+    /// 连续失败的心跳次数，心跳被服务端接受后清零
+    heartbeat_failures: Arc<AtomicU64>,
 }
 
 impl Discovery {
-    pub(crate) async fn new(client: DiscoveryClient) -> Self {
+    /// 创建服务发现并启动后台任务
+    ///
+    /// `registered`为false时，在心跳任务中重试注册，注册成功后再开始心跳
+    pub(crate) async fn new(client: DiscoveryClient, registered: bool) -> Self {
         let discovery = Discovery {
             services: Arc::new(DashMap::new()),
             client,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            heartbeat_failures: Arc::new(AtomicU64::new(0)),
         };
         // 启动同步任务
         discovery.start_fetch_task();
+        // 启动生效实例集合检查任务
+        discovery.start_active_set_task();
         // 启动心跳任务
-        discovery.start_heartbeat();
+        discovery.start_heartbeat(registered);
         discovery
     }
 
     /// 定时从注册中心同步服务实例
     ///
-    /// 同步间隔时间：30秒
+    /// 同步间隔时间：`fetch_interval`，默认30秒，附加随机抖动。同步失败时按`max_retries`重试
     fn start_fetch_task(&self) {
         log::info!("start service instances fetch task");
         let client = Arc::new(self.client.clone());
         let services = self.services.clone();
         tokio::spawn(async move {
-            let mut interval_timer = tokio::time::interval(Duration::from_secs(30));
+            let interval = client.fetch_interval();
+            let mut ticker = Ticker::new(interval);
             loop {
-                interval_timer.tick().await;
+                ticker.tick().await;
                 let service_ids: Vec<String> =
                     services.iter().map(|entry| entry.key().clone()).collect();
                 for service_id in service_ids {
+                    let res = client
+                        .retry(client.config.max_retries, interval, || {
+                            Self::fetch_instances_(&client, &service_id)
+                        })
+                        .await;
+                    match res {
+                        Ok(instances) => {
+                            services.insert(service_id, CachedInstances::new(instances));
+                        }
+                        Err(e) => {
+                            log::error!(
+                                "fetch service instance error, service id: {}, error: {}",
+                                service_id,
+                                e
+                            );
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// 定时检查服务当前生效的实例集合（蓝绿部署）
+    ///
+    /// 生效集合变化时立即刷新该服务的实例，使所有调用方几乎同时切换流量，而不必等待下一个同步周期。
+    ///
+    /// 检查间隔：5秒，附加随机抖动
+    fn start_active_set_task(&self) {
+        let client = Arc::new(self.client.clone());
+        let services = self.services.clone();
+        tokio::spawn(async move {
+            // 已应用的生效集合，key为服务ID
+            let mut active_sets: HashMap<String, String> = HashMap::new();
+            let mut ticker = Ticker::new(ACTIVE_SET_CHECK_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if services.is_empty() {
+                    continue;
+                }
+                let latest = match client.fetch_active_sets().await {
+                    Ok(latest) => latest,
+                    Err(e) => {
+                        log::debug!("fetch active sets error: {}", e);
+                        continue;
+                    }
+                };
+                let service_ids: Vec<String> =
+                    services.iter().map(|entry| entry.key().clone()).collect();
+                for service_id in service_ids {
+                    let active_set = latest.get(&service_id);
+                    if active_sets.get(&service_id) == active_set {
+                        continue;
+                    }
+                    log::info!(
+                        "active set of service {} changed to {:?}",
+                        service_id,
+                        active_set
+                    );
                     match Self::fetch_instances_(&client, &service_id).await {
                         Ok(instances) => {
-                            services.insert(service_id, instances);
+                            services.insert(service_id.clone(), CachedInstances::new(instances));
+                            match active_set {
+                                Some(active_set) => {
+                                    active_sets.insert(service_id, active_set.clone())
+                                }
+                                None => active_sets.remove(&service_id),
+                            };
                         }
+                        // 刷新失败时不记录，下次检查时重试
                         Err(e) => {
                             log::error!(
                                 "fetch service instance error, service id: {}, error: {}",
@@ -154,48 +644,136 @@ impl Discovery {
 
     /// 开启定时心跳
     ///
-    /// 心跳间隔：5秒
-    fn start_heartbeat(&self) {
+    /// 心跳间隔：`heartbeat_interval`，默认5秒，附加随机抖动。注册后立即发送第一次心跳，使实例尽快变为可用状态。
+    /// 心跳失败时按`max_retries`重试，重试等待时间不超过心跳间隔
+    fn start_heartbeat(&self, registered: bool) {
         let client = Arc::new(self.client.clone());
+        let shutdown = self.shutdown.clone();
+        let heartbeat_failures = self.heartbeat_failures.clone();
         tokio::spawn(async move {
-            let mut interval_timer = tokio::time::interval(Duration::from_secs(5));
+            if !registered && !Self::register_until_success(&client, &shutdown).await {
+                return;
+            }
+            let interval = client.heartbeat_interval();
+            let mut ticker = Ticker::new(interval);
             loop {
-                interval_timer.tick().await;
+                if shutdown.load(Ordering::Acquire) {
+                    log::info!("instance deregistered, heartbeat stopped");
+                    break;
+                }
                 log::debug!("ping");
-                match client.heartbeat().await {
-                    Ok(res) => match res {
-                        HeartbeatResult::Ok => {
-                            log::debug!("pong");
-                        }
-                        // 心跳时发现本实例在注册中心不存在了，尝试重新注册服务
-                        HeartbeatResult::NoInstanceFound => {
-                            log::warn!("no instance found, try re-register");
-                            if let Err(e) = client.register().await {
-                                log::error!("register error:{}", e);
-                            }
+                match client
+                    .retry(client.config.max_retries, interval, || client.heartbeat())
+                    .await
+                {
+                    Ok((res, eviction)) => {
+                        // 本实例曾被判定为不健康或已被移除，通知监听函数
+                        if let Some(notice) = eviction {
+                            log::warn!(
+                                "instance was evicted: {}, removed: {}",
+                                notice.reason,
+                                notice.removed
+                            );
+                            notify_eviction(&notice);
                         }
-                        HeartbeatResult::Rejected => {
-                            log::warn!("heartbeat rejected");
+                        if matches!(res, HeartbeatResult::Ok) {
+                            heartbeat_failures.store(0, Ordering::Relaxed);
+                        } else {
+                            heartbeat_failures.fetch_add(1, Ordering::Relaxed);
                         }
-                        // 未知结果，可能客户端和服务端版本不匹配
-                        HeartbeatResult::Unknown => {
-                            log::error!("Unknown heartbeat result");
+                        match res {
+                            HeartbeatResult::Ok => {
+                                log::debug!("pong");
+                            }
+                            // 心跳时发现本实例在注册中心不存在了，尝试重新注册服务
+                            // 已注销时，心跳可能在注销前发出，此时不再重新注册
+                            HeartbeatResult::NoInstanceFound
+                                if shutdown.load(Ordering::Acquire) => {}
+                            HeartbeatResult::NoInstanceFound => {
+                                log::warn!("no instance found, try re-register");
+                                if let Err(e) = client.register().await {
+                                    log::error!("register error:{}", e);
+                                }
+                            }
+                            HeartbeatResult::Rejected => {
+                                log::warn!("heartbeat rejected");
+                            }
+                            // 未知结果，可能客户端和服务端版本不匹配
+                            HeartbeatResult::Unknown => {
+                                log::error!("Unknown heartbeat result");
+                            }
                         }
-                    },
+                    }
                     Err(e) => {
+                        heartbeat_failures.fetch_add(1, Ordering::Relaxed);
                         log::error!("heartbeat error: {}", e);
                     }
                 }
+                ticker.tick().await;
             }
         });
     }
 
+    /// 持续重试注册直到成功，返回false表示注册前已注销
+    ///
+    /// 重试等待时间从`retry_backoff_ms`开始每次翻倍，不超过`register_max_backoff_ms`，并附加随机抖动
+    async fn register_until_success(client: &DiscoveryClient, shutdown: &AtomicBool) -> bool {
+        let max = client.register_max_backoff();
+        let mut backoff = Duration::from_millis(client.config.retry_backoff_ms).min(max);
+        let mut attempt = 1u64;
+        loop {
+            if shutdown.load(Ordering::Acquire) {
+                log::info!("instance deregistered, registration stopped");
+                return false;
+            }
+            log::warn!(
+                "instance not registered, retry registration {} in {:?}",
+                attempt,
+                backoff
+            );
+            tokio::time::sleep(jitter(backoff)).await;
+            if shutdown.load(Ordering::Acquire) {
+                log::info!("instance deregistered, registration stopped");
+                return false;
+            }
+            match client.register().await {
+                Ok(_) => {
+                    log::info!("instance registered after {} retries", attempt);
+                    return true;
+                }
+                Err(e) => log::warn!("register error: {}", e),
+            }
+            attempt += 1;
+            backoff = (backoff * 2).min(max);
+        }
+    }
+
+    /// 更新本实例的元数据
+    pub(crate) async fn update_meta(&self, meta: HashMap<String, String>) -> anyhow::Result<()> {
+        self.client.update_meta(meta).await
+    }
+
+    /// 停止心跳并从注册中心注销本实例
+    ///
+    /// 重复调用时仅第一次注销，之后直接返回
+    pub(crate) async fn shutdown(&self) -> anyhow::Result<()> {
+        if self.shutdown.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        self.client.deregister().await
+    }
+
+    /// 配置中的就近选择设置，未开启时为空
+    pub(crate) fn zone_affinity(&self) -> Option<ZoneAffinity> {
+        ZoneAffinity::from_config(&self.client.config)
+    }
+
     /// 获取可用服务实例
     ///
     /// 优先取本地缓存，如果本地缓存不存在，则从注册中心同步
     pub(crate) async fn get_instances(&self, service_id: &str) -> Vec<Instance> {
         match self.services.get(service_id) {
-            Some(instances) => instances.clone(),
+            Some(cached) => cached.instances.clone(),
             None => self.fetch_instances(service_id).await.unwrap_or_else(|e| {
                 log::error!("Failed to fetch instances: {}", e);
                 vec![]
@@ -203,14 +781,167 @@ impl Discovery {
         }
     }
 
+    /// 订阅服务的可用实例
+    ///
+    /// 服务的第一个订阅者启动该服务的监听任务；监听任务已有结果时，立即以当前实例通知新的订阅者
+    pub(crate) fn subscribe(&self, service_id: &str, handler: SubscriberFn) -> Subscription {
+        let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+        let mut start = false;
+        let instances = {
+            let mut subscribers = SUBSCRIBERS
+                .entry(service_id.to_string())
+                .or_insert_with(|| {
+                    start = true;
+                    Subscribers::default()
+                });
+            subscribers.handlers.push((id, handler.clone()));
+            subscribers.instances.clone()
+        };
+        if start {
+            self.start_watch_task(service_id.to_string());
+        }
+        if let Some(instances) = instances {
+            handler(&instances);
+        }
+        Subscription {
+            service_id: service_id.to_string(),
+            id,
+        }
+    }
+
+    /// 通过长轮询监听服务可用实例的变化，更新本地缓存并通知订阅者
+    ///
+    /// 服务没有订阅者时停止
+    fn start_watch_task(&self, service_id: String) {
+        log::info!("start watch instances of service {}", service_id);
+        let client = self.client.clone();
+        let services = self.services.clone();
+        tokio::spawn(async move {
+            let mut version = String::new();
+            loop {
+                if SUBSCRIBERS
+                    .remove_if(&service_id, |_, subscribers| {
+                        subscribers.handlers.is_empty()
+                    })
+                    .is_some()
+                {
+                    log::info!("stop watch instances of service {}", service_id);
+                    break;
+                }
+                match client.watch_instances(&service_id, &version).await {
+                    Ok(Some(snapshot)) => {
+                        log::info!(
+                            "instances of service {} changed, available: {}",
+                            service_id,
+                            snapshot.instances.len()
+                        );
+                        version = snapshot.version;
+                        services.insert(
+                            service_id.clone(),
+                            CachedInstances::new(snapshot.instances.clone()),
+                        );
+                        Self::notify_subscribers(&service_id, snapshot.instances);
+                    }
+                    Ok(None) => {
+                        if let Some(mut cached) = services.get_mut(&service_id) {
+                            cached.fetched_at = SystemTime::now();
+                        }
+                        log::debug!("instances of service {} not changed", service_id)
+                    }
+                    Err(e) => {
+                        log::error!("watch instances of service {} error: {}", service_id, e);
+                        tokio::time::sleep(jitter(WATCH_RETRY_INTERVAL)).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// 以新的实例通知服务的订阅者
+    fn notify_subscribers(service_id: &str, instances: Vec<Instance>) {
+        // 先复制监听函数再调用，避免监听函数中订阅或取消订阅时死锁
+        let handlers = match SUBSCRIBERS.get_mut(service_id) {
+            Some(mut subscribers) => {
+                subscribers.instances = Some(instances.clone());
+                subscribers
+                    .handlers
+                    .iter()
+                    .map(|(_, handler)| handler.clone())
+                    .collect::<Vec<_>>()
+            }
+            None => return,
+        };
+        for handler in handlers {
+            handler(&instances);
+        }
+    }
+
+    /// 等待服务的可用实例数达到`min`
+    ///
+    /// 每秒从注册中心拉取一次，超时后返回错误
+    pub(crate) async fn wait_for_instances(
+        &self,
+        service_id: &str,
+        min: usize,
+        timeout: Duration,
+    ) -> anyhow::Result<Vec<Instance>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut available = 0;
+        loop {
+            match self.fetch_instances(service_id).await {
+                Ok(instances) if instances.len() >= min => return Ok(instances),
+                Ok(instances) => available = instances.len(),
+                Err(e) => log::debug!("fetch instances of {} error: {}", service_id, e),
+            }
+            if tokio::time::Instant::now() >= deadline {
+                bail!(
+                    "timed out waiting for {} instances of service {}, {} available",
+                    min,
+                    service_id,
+                    available
+                );
+            }
+            log::info!(
+                "waiting for service {} instances: {}/{}",
+                service_id,
+                available,
+                min
+            );
+            tokio::time::sleep_until(deadline.min(tokio::time::Instant::now() + WAIT_INTERVAL))
+                .await;
+        }
+    }
+
     /// 从注册中心中同步可用的服务实例
     async fn fetch_instances(&self, service_id: &str) -> anyhow::Result<Vec<Instance>> {
         let instances = self.client.fetch_instances(service_id).await?;
-        self.services
-            .insert(service_id.to_string(), instances.clone());
+        self.services.insert(
+            service_id.to_string(),
+            CachedInstances::new(instances.clone()),
+        );
         Ok(instances)
     }
 
+    /// 本地缓存的服务实例和心跳的统计信息
+    pub(crate) fn stats(&self) -> DiscoveryStats {
+        let services = self
+            .services
+            .iter()
+            .map(|entry| {
+                let stats = ServiceStats {
+                    instances: entry.instances.len(),
+                    last_fetch_at: entry.fetched_at,
+                };
+                (entry.key().clone(), stats)
+            })
+            .collect::<HashMap<_, _>>();
+        DiscoveryStats {
+            last_fetch_at: services.values().map(|stats| stats.last_fetch_at).max(),
+            services,
+            consecutive_heartbeat_failures: self.heartbeat_failures.load(Ordering::Relaxed),
+        }
+    }
+
     async fn fetch_instances_(
         client: &DiscoveryClient,
         service_id: &str,
@@ -219,3 +950,167 @@ impl Discovery {
         Ok(instances)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::ConRegConfigWrapper;
+    use std::sync::Mutex;
+
+    fn discovery() -> Discovery {
+        let config = serde_yaml::from_str::<ConRegConfigWrapper>(
+            r#"
+conreg:
+  service-id: test
+  client:
+    address: 127.0.0.1
+    port: 8080
+  discovery:
+    server-addr: 127.0.0.1:1
+    max-retries: 2
+    retry-backoff-ms: 1
+    register-max-backoff-ms: 2
+"#,
+        )
+        .unwrap();
+        Discovery {
+            services: Arc::new(DashMap::new()),
+            client: DiscoveryClient::new(&config.conreg),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            heartbeat_failures: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn instance(service_id: &str, port: u16) -> Instance {
+        Instance {
+            id: format!("{}-{}", service_id, port),
+            service_id: service_id.to_string(),
+            ip: "127.0.0.1".to_string(),
+            port,
+            meta: HashMap::new(),
+            effective_weight: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let client = discovery().client;
+        assert_eq!(client.register_max_backoff(), Duration::from_millis(2));
+
+        // 失败次数不超过重试次数时返回成功的结果
+        let calls = AtomicU64::new(0);
+        let res = client
+            .retry(2, Duration::from_millis(2), || async {
+                match calls.fetch_add(1, Ordering::Relaxed) {
+                    0 | 1 => bail!("unavailable"),
+                    n => Ok(n),
+                }
+            })
+            .await;
+        assert_eq!(res.unwrap(), 2);
+
+        // 重试次数用完后返回最后一次的错误
+        let calls = AtomicU64::new(0);
+        let res = client
+            .retry(1, Duration::from_millis(2), || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err::<(), _>(anyhow::anyhow!("unavailable"))
+            })
+            .await;
+        assert_eq!(res.unwrap_err().to_string(), "unavailable");
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_stats() {
+        let discovery = discovery();
+        let stats = discovery.stats();
+        assert!(stats.services.is_empty());
+        assert_eq!(stats.last_fetch_at, None);
+
+        discovery.services.insert(
+            "a".to_string(),
+            CachedInstances::new(vec![instance("a", 1), instance("a", 2)]),
+        );
+        discovery
+            .services
+            .insert("b".to_string(), CachedInstances::new(vec![]));
+        discovery.heartbeat_failures.store(3, Ordering::Relaxed);
+
+        let stats = discovery.stats();
+        assert_eq!(stats.services["a"].instances, 2);
+        assert_eq!(stats.services["b"].instances, 0);
+        assert_eq!(
+            stats.last_fetch_at,
+            Some(
+                stats.services["a"]
+                    .last_fetch_at
+                    .max(stats.services["b"].last_fetch_at)
+            )
+        );
+        assert_eq!(stats.consecutive_heartbeat_failures, 3);
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let discovery = discovery();
+        // 预先登记订阅者，不启动监听任务
+        SUBSCRIBERS.insert("test-subscribe".to_string(), Subscribers::default());
+
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        let subscription = discovery.subscribe(
+            "test-subscribe",
+            Arc::new(move |instances: &[Instance]| {
+                received_clone.lock().unwrap().push(instances.len());
+            }),
+        );
+        assert_eq!(subscription.service_id(), "test-subscribe");
+        // 尚未收到实例时不立即通知
+        assert!(received.lock().unwrap().is_empty());
+
+        Discovery::notify_subscribers("test-subscribe", vec![instance("test-subscribe", 1)]);
+        assert_eq!(*received.lock().unwrap(), vec![1]);
+
+        // 后订阅的监听函数立即收到最近一次通知的实例
+        let late = Arc::new(Mutex::new(vec![]));
+        let late_clone = late.clone();
+        let late_subscription = discovery.subscribe(
+            "test-subscribe",
+            Arc::new(move |instances: &[Instance]| {
+                late_clone.lock().unwrap().push(instances.len());
+            }),
+        );
+        assert_eq!(*late.lock().unwrap(), vec![1]);
+
+        assert!(subscription.unsubscribe());
+        assert!(!subscription.unsubscribe());
+        Discovery::notify_subscribers("test-subscribe", vec![]);
+        assert_eq!(*received.lock().unwrap(), vec![1]);
+        assert_eq!(*late.lock().unwrap(), vec![1, 0]);
+        assert!(late_subscription.unsubscribe());
+    }
+
+    #[test]
+    fn test_eviction_listener() {
+        let notice = EvictionNotice {
+            reason: "heartbeat timeout".to_string(),
+            removed: true,
+            last_heartbeat: "".to_string(),
+            evicted_at: None,
+        };
+        // 监听函数可以捕获状态
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        let handle = add_eviction_listener(Arc::new(move |notice: &EvictionNotice| {
+            received_clone.lock().unwrap().push(notice.removed);
+        }));
+        notify_eviction(&notice);
+        assert_eq!(*received.lock().unwrap(), vec![true]);
+
+        assert!(handle.remove());
+        assert!(!handle.remove());
+        notify_eviction(&notice);
+        assert_eq!(*received.lock().unwrap(), vec![true]);
+    }
+}
@@ -0,0 +1,128 @@
+//! 配置值加解密
+//!
+//! 配置中心中的敏感配置值可以加密存储，以`DEC(密文)`的形式写入配置，客户端在展平配置时使用
+//! `decrypt-key`解密，应用通过`AppConfig::get`获取到的是明文。
+//!
+//! 密文格式与服务端`enc_dec.rs`一致：使用ChaCha20-Poly1305加密，
+//! 明文为`预留标记(2字节) + 内容长度(8字节，大端) + 内容`，密文为`base58(nonce(12字节) + 加密结果)`。
+
+use anyhow::{Context, bail};
+use base58::{FromBase58, ToBase58};
+use chacha20poly1305::aead::{Aead, OsRng};
+use chacha20poly1305::{AeadCore, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use serde_yaml::Value;
+
+const NONCE_LEN: usize = 12;
+/// 预留标记和内容长度占用的字节数
+const HEADER_LEN: usize = 10;
+
+/// 解密密钥，32字节，配置为64个字符的十六进制字符串
+pub(crate) struct DecryptKey(ChaCha20Poly1305);
+
+impl DecryptKey {
+    pub(crate) fn parse(key: &str) -> anyhow::Result<Self> {
+        let key = hex::decode(key.trim()).context("decrypt key must be a hex string")?;
+        if key.len() != 32 {
+            bail!("decrypt key must be 32 bytes (64 hex characters)");
+        }
+        Ok(Self(ChaCha20Poly1305::new(Key::from_slice(&key))))
+    }
+
+    /// 加密，返回`DEC(密文)`
+    pub(crate) fn encrypt(&self, content: &str) -> anyhow::Result<String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut data = Vec::with_capacity(HEADER_LEN + content.len());
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&(content.len() as u64).to_be_bytes());
+        data.extend_from_slice(content.as_bytes());
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, data.as_ref())
+            .map_err(|_| anyhow::anyhow!("encrypt config value failed"))?;
+
+        let mut result = nonce.to_vec();
+        result.extend_from_slice(&ciphertext);
+        Ok(format!("DEC({})", result.to_base58()))
+    }
+
+    /// 解密`DEC(...)`中的密文
+    fn decrypt(&self, ciphertext: &str) -> anyhow::Result<String> {
+        let data = ciphertext
+            .from_base58()
+            .map_err(|_| anyhow::anyhow!("invalid base58 ciphertext"))?;
+        if data.len() < NONCE_LEN {
+            bail!("ciphertext too short");
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("wrong decrypt key or corrupted ciphertext"))?;
+        if plaintext.len() < HEADER_LEN {
+            bail!("plaintext too short");
+        }
+        let len = u64::from_be_bytes(plaintext[2..HEADER_LEN].try_into()?) as usize;
+        let Some(content) = plaintext.get(HEADER_LEN..HEADER_LEN + len) else {
+            bail!("invalid plaintext length");
+        };
+        Ok(String::from_utf8(content.to_vec())?)
+    }
+
+    /// 解密配置值中所有`DEC(...)`形式的字符串，包括嵌套的映射和列表
+    pub(crate) fn decrypt_value(&self, value: &mut Value) -> anyhow::Result<()> {
+        match value {
+            Value::String(s) => {
+                if let Some(ciphertext) = s
+                    .trim()
+                    .strip_prefix("DEC(")
+                    .and_then(|s| s.strip_suffix(')'))
+                {
+                    *s = self.decrypt(ciphertext)?;
+                }
+            }
+            Value::Mapping(mapping) => {
+                for (_, value) in mapping.iter_mut() {
+                    self.decrypt_value(value)?;
+                }
+            }
+            Value::Sequence(sequence) => {
+                for value in sequence.iter_mut() {
+                    self.decrypt_value(value)?;
+                }
+            }
+            Value::Tagged(tagged) => self.decrypt_value(&mut tagged.value)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_enc_dec() {
+        let key = DecryptKey::parse(KEY).unwrap();
+        let encrypted = key.encrypt("p@ssw0rd").unwrap();
+        assert!(encrypted.starts_with("DEC("));
+
+        let mut value: Value = serde_yaml::from_str(&format!(
+            "db:\n  password: {}\n  hosts: [\"{}\", plain]\nport: 3306",
+            encrypted, encrypted
+        ))
+        .unwrap();
+        key.decrypt_value(&mut value).unwrap();
+        assert_eq!(value["db"]["password"].as_str(), Some("p@ssw0rd"));
+        assert_eq!(value["db"]["hosts"][0].as_str(), Some("p@ssw0rd"));
+        assert_eq!(value["db"]["hosts"][1].as_str(), Some("plain"));
+        assert_eq!(value["port"].as_u64(), Some(3306));
+
+        let other = DecryptKey::parse(&"ff".repeat(32)).unwrap();
+        let mut value = Value::String(encrypted);
+        assert!(other.decrypt_value(&mut value).is_err());
+        assert!(DecryptKey::parse("abc").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! gRPC传输，启用`grpc`特性并设置`protocol: grpc`时使用
+//!
+//! 服务端需要通过`--enable-grpc`启动，gRPC端口为HTTP端口 + [`GRPC_PORT_OFFSET`]。
+//! 配置监听和心跳使用长连接的流，替代长轮询和每次心跳一个HTTP请求。
+
+use crate::conf::ServerAddr;
+use crate::protocol::request::{
+    GetConfigReq, GetInstancesReq, HeartbeatReq, RegisterReq, WatchConfigChangeReq,
+};
+use crate::protocol::response::HeartbeatResult;
+use crate::protocol::{EvictionNotice, Instance};
+use anyhow::{Context, bail};
+use conreg_grpc::config_client::ConfigClient;
+use conreg_grpc::discovery_client::DiscoveryClient;
+use conreg_grpc::{ConfigChanged, ConfigEntry, GRPC_PORT_OFFSET, NS_TOKEN_METADATA};
+use dashmap::DashMap;
+use serde_yaml::Value;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Status, Streaming};
+
+/// 连接超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// gRPC连接，key为gRPC地址，连接在首次请求时建立，断开后自动重连
+static CHANNELS: LazyLock<DashMap<String, Channel>> = LazyLock::new(DashMap::new);
+
+impl ServerAddr {
+    /// 选择首选的服务端节点，返回其gRPC连接，节点的选择顺序见[`ServerAddr::candidates`]
+    fn grpc_channel(&self) -> anyhow::Result<Channel> {
+        let addresses = self.candidates();
+        let Some(address) = addresses.first() else {
+            bail!("server address not set");
+        };
+        let (host, port) = address
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+            .with_context(|| format!("invalid server address: {}", address))?;
+        let port = port
+            .checked_add(GRPC_PORT_OFFSET)
+            .with_context(|| format!("gRPC port of {} out of range", address))?;
+        let url = format!("http://{}:{}", host, port);
+        if let Some(channel) = CHANNELS.get(&url) {
+            return Ok(channel.clone());
+        }
+        let channel = Endpoint::from_shared(url.clone())?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect_lazy();
+        CHANNELS.insert(url, channel.clone());
+        Ok(channel)
+    }
+}
+
+/// 是否为服务端不可达导致的错误
+pub(crate) fn is_unavailable(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<Status>()
+        .is_some_and(|status| status.code() == Code::Unavailable)
+}
+
+/// 创建携带命名空间Token的请求
+fn request<T>(message: T, auth_token: &Option<String>) -> anyhow::Result<Request<T>> {
+    let mut request = Request::new(message);
+    if let Some(token) = auth_token {
+        request
+            .metadata_mut()
+            .insert(NS_TOKEN_METADATA, token.parse()?);
+    }
+    Ok(request)
+}
+
+/// 获取配置，配置不存在时返回None
+pub(crate) async fn get_config(
+    server_addr: &ServerAddr,
+    req: GetConfigReq,
+    auth_token: &Option<String>,
+) -> anyhow::Result<Option<ConfigEntry>> {
+    let message = conreg_grpc::GetConfigRequest {
+        namespace_id: req.namespace_id,
+        id: req.id,
+        labels: req.labels,
+    };
+    let res = ConfigClient::new(server_addr.grpc_channel()?)
+        .get_config(request(message, auth_token)?)
+        .await?;
+    Ok(res.into_inner().entry)
+}
+
+/// 监听命名空间下的配置变化，返回推送变更配置ID的流
+pub(crate) async fn watch_config(
+    server_addr: &ServerAddr,
+    req: WatchConfigChangeReq,
+) -> anyhow::Result<Streaming<ConfigChanged>> {
+    let message = conreg_grpc::WatchConfigRequest {
+        namespace_id: req.namespace_id,
+        cursor: req.cursor,
+    };
+    let res = ConfigClient::new(server_addr.grpc_channel()?)
+        .watch_config(message)
+        .await?;
+    Ok(res.into_inner())
+}
+
+/// 注册服务实例
+pub(crate) async fn register(
+    server_addr: &ServerAddr,
+    req: RegisterReq,
+) -> anyhow::Result<Instance> {
+    let message = conreg_grpc::RegisterRequest {
+        namespace_id: req.namespace_id,
+        service_id: req.service_id,
+        ip: req.ip,
+        port: req.port as u32,
+        // 服务端元数据值为字符串
+        meta: req
+            .meta
+            .into_iter()
+            .map(|(key, value)| (key, meta_value(value)))
+            .collect(),
+        // 客户端重启后以新的元数据替换旧实例
+        conflict_policy: conreg_grpc::ConflictPolicy::Replace as i32,
+    };
+    let res = DiscoveryClient::new(server_addr.grpc_channel()?)
+        .register(message)
+        .await?;
+    Ok(res.into_inner().into())
+}
+
+/// 获取可用服务实例
+pub(crate) async fn fetch_instances(
+    server_addr: &ServerAddr,
+    req: GetInstancesReq,
+    auth_token: &Option<String>,
+) -> anyhow::Result<Vec<Instance>> {
+    let message = conreg_grpc::GetInstancesRequest {
+        namespace_id: req.namespace_id,
+        service_id: req.service_id,
+    };
+    let res = DiscoveryClient::new(server_addr.grpc_channel()?)
+        .get_instances(request(message, auth_token)?)
+        .await?;
+    Ok(res
+        .into_inner()
+        .instances
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+/// 心跳流，每发送一个心跳请求，服务端返回一个响应
+#[derive(Debug)]
+pub(crate) struct HeartbeatStream {
+    sender: mpsc::Sender<conreg_grpc::HeartbeatRequest>,
+    responses: Streaming<conreg_grpc::HeartbeatResponse>,
+}
+
+impl HeartbeatStream {
+    /// 建立心跳流
+    pub(crate) async fn connect(server_addr: &ServerAddr) -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::channel(1);
+        let res = DiscoveryClient::new(server_addr.grpc_channel()?)
+            .heartbeat(ReceiverStream::new(receiver))
+            .await?;
+        Ok(HeartbeatStream {
+            sender,
+            responses: res.into_inner(),
+        })
+    }
+
+    /// 发送心跳并等待响应
+    pub(crate) async fn heartbeat(
+        &mut self,
+        req: HeartbeatReq,
+    ) -> anyhow::Result<(HeartbeatResult, Option<EvictionNotice>)> {
+        let message = conreg_grpc::HeartbeatRequest {
+            namespace_id: req.namespace_id,
+            service_id: req.service_id,
+            instance_id: req.instance_id,
+        };
+        if self.sender.send(message).await.is_err() {
+            bail!("heartbeat stream closed");
+        }
+        match self.responses.message().await? {
+            Some(res) => Ok((res.result().into(), res.eviction.map(Into::into))),
+            None => bail!("heartbeat stream closed by server"),
+        }
+    }
+}
+
+/// 元数据值转换为字符串
+fn meta_value(value: Value) -> String {
+    match value {
+        Value::String(value) => value,
+        Value::Null => String::new(),
+        value => serde_yaml::to_string(&value)
+            .map(|value| value.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+impl From<conreg_grpc::Instance> for Instance {
+    fn from(value: conreg_grpc::Instance) -> Self {
+        Instance {
+            id: value.id,
+            service_id: value.service_id,
+            ip: value.ip,
+            port: value.port as u16,
+            meta: value
+                .meta
+                .into_iter()
+                .map(|(key, value)| (key, Value::String(value)))
+                .collect(),
+            effective_weight: value.effective_weight,
+        }
+    }
+}
+
+impl From<conreg_grpc::HeartbeatResult> for HeartbeatResult {
+    fn from(value: conreg_grpc::HeartbeatResult) -> Self {
+        match value {
+            conreg_grpc::HeartbeatResult::Ok => HeartbeatResult::Ok,
+            conreg_grpc::HeartbeatResult::NoInstanceFound => HeartbeatResult::NoInstanceFound,
+            conreg_grpc::HeartbeatResult::Rejected => HeartbeatResult::Rejected,
+            conreg_grpc::HeartbeatResult::Unknown => HeartbeatResult::Unknown,
+        }
+    }
+}
+
+impl From<conreg_grpc::EvictionNotice> for EvictionNotice {
+    fn from(value: conreg_grpc::EvictionNotice) -> Self {
+        EvictionNotice {
+            reason: value.reason,
+            removed: value.removed,
+            last_heartbeat: value.last_heartbeat,
+            evicted_at: value.evicted_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meta_value() {
+        assert_eq!(meta_value(Value::String("a".into())), "a");
+        assert_eq!(meta_value(Value::Number(2.into())), "2");
+        assert_eq!(meta_value(Value::Bool(true)), "true");
+        assert_eq!(meta_value(Value::Null), "");
+    }
+}
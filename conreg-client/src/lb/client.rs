@@ -10,18 +10,50 @@
 //! - `lb-wr`：按照加权随机负载策略获取服务实例
 //! - `lb-rr`：按照轮询负载策略获取服务实例
 //! - `lb-wrr`：按照加权轮询负载策略获取服务实例
+//! - `lb-ch`：按照一致性哈希负载策略获取服务实例
+//! - `lb-s`：按照会话保持负载策略获取服务实例
+//! - `lb-rt`：按照响应时间加权负载策略获取服务实例
+//!
+//! 通过`*_with`方法可为单次请求指定负载策略和实例过滤条件（[`RequestOptions`]），
+//! 例如将请求固定到灰度实例或指定可用区，而不影响服务已设置的负载策略。
+//! 只需指定负载策略时可直接传入[`LoadBalanceStrategy`]。
+//!
+//! 通过[`LoadBalanceClient::service`]可按service_id和路径构建请求（[`ServiceRequest`]），
+//! 不需要拼接`lb://`格式的url，service_id或路径不合法时返回错误。
+//!
+//! 通过[`LoadBalanceClient::set_base_path`]和[`LoadBalanceClient::set_default_header`]
+//! 可为服务设置统一的路径前缀和默认请求头，解析url时自动应用。
+//!
+//! 通过[`LoadBalanceClient::resolve`]构建请求时可获取被选中的实例，便于日志记录和故障关联。
+//!
+//! 通过[`LoadBalanceClient::send`]发送的请求会记录目标实例的成功率和延迟，
+//! 加权负载策略据此自适应调整实例权重，响应时间加权负载策略只按成功率和延迟选择实例。
+//! 使用其他客户端发送请求时，可通过[`LoadBalanceClient::record`]上报请求结果。
+//!
+//! 通过[`LoadBalanceClient::set_retry_policy`]或[`RequestOptions::retry`]设置失败重试策略后，
+//! 通过[`LoadBalanceClient::execute`]发送的请求在连接失败、超时或响应可重试的状态码时，
+//! 自动换一个未请求过的实例重试。
+//!
+//! 一致性哈希负载的哈希key优先使用[`RequestOptions::with_hash_key`]指定的值，
+//! 其次按[`LoadBalanceClient::set_hash_key`]设置的来源（请求头、Cookie或请求路径）获取，默认使用请求路径。
+//! 会话保持负载使用同样的key作为会话key，但没有会话key时随机选择实例，不使用请求路径。
 
-use crate::Instance;
 use crate::lb::{
-    LoadBalance, LoadBalanceError, RandomLoadBalance, RoundRobinLoadBalance,
-    WeightRandomLoadBalance, WeightRoundRobinLoadBalance,
+    ConsistentHashLoadBalance, InstanceStat, InstanceStats, LoadBalanceError, RandomLoadBalance,
+    ResponseTimeLoadBalance, RoundRobinLoadBalance, StickyLoadBalance, WeightRandomLoadBalance,
+    WeightRoundRobinLoadBalance, ZoneAffinity, stats,
 };
+use crate::network;
+use crate::{AppDiscovery, Instance};
 use dashmap::DashMap;
-use reqwest::{Client, Method, RequestBuilder, Url};
-use std::time::Duration;
+use reqwest::header::{COOKIE, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode, Url};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// 负载均衡策略
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum LoadBalanceStrategy {
     /// 轮询
     RoundRobin,
@@ -32,6 +64,12 @@ pub enum LoadBalanceStrategy {
     Random,
     /// 加权随机
     WeightedRandom,
+    /// 一致性哈希
+    ConsistentHash,
+    /// 会话保持
+    Sticky,
+    /// 响应时间加权
+    ResponseTime,
 }
 
 impl LoadBalanceStrategy {
@@ -41,16 +79,385 @@ impl LoadBalanceStrategy {
             LoadBalanceStrategy::WeightedRoundRobin => "lb-wrr",
             LoadBalanceStrategy::Random => "lb-r",
             LoadBalanceStrategy::WeightedRandom => "lb-wr",
+            LoadBalanceStrategy::ConsistentHash => "lb-ch",
+            LoadBalanceStrategy::Sticky => "lb-s",
+            LoadBalanceStrategy::ResponseTime => "lb-rt",
+        }
+    }
+
+    /// 按负载协议获取负载策略，`lb`及非负载协议返回None
+    pub fn from_schema(schema: &str) -> Option<Self> {
+        match schema {
+            "lb-rr" => Some(LoadBalanceStrategy::RoundRobin),
+            "lb-wrr" => Some(LoadBalanceStrategy::WeightedRoundRobin),
+            "lb-r" => Some(LoadBalanceStrategy::Random),
+            "lb-wr" => Some(LoadBalanceStrategy::WeightedRandom),
+            "lb-ch" => Some(LoadBalanceStrategy::ConsistentHash),
+            "lb-s" => Some(LoadBalanceStrategy::Sticky),
+            "lb-rt" => Some(LoadBalanceStrategy::ResponseTime),
+            _ => None,
         }
     }
 }
 
+/// 失败重试策略
+///
+/// 连接失败、超时或响应状态码可重试时，换一个未请求过的实例重试，没有其他可用实例时返回最后一次的结果。
+///
+/// 注意：按状态码重试时非幂等的请求（如POST）可能被重复处理，连接失败时请求未发出，重试是安全的。
+/// 请求体为流时无法重试。
+///
+/// ```rust
+/// client.set_retry_policy(
+///     "your_service_id",
+///     RetryPolicy::new(3)
+///         .retryable_statuses(vec![StatusCode::BAD_GATEWAY, StatusCode::SERVICE_UNAVAILABLE])
+///         .per_try_timeout(Duration::from_secs(2)),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// 最大尝试次数，包含第一次请求
+    pub max_attempts: u32,
+    /// 需要重试的响应状态码，为空时所有5xx状态码都重试
+    pub retryable_statuses: Vec<StatusCode>,
+    /// 每次尝试的超时时间，为空时不限制
+    pub per_try_timeout: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// 创建重试策略，最多尝试`max_attempts`次（包含第一次请求）
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            retryable_statuses: vec![],
+            per_try_timeout: None,
+        }
+    }
+
+    /// 设置需要重试的响应状态码
+    pub fn retryable_statuses(mut self, statuses: Vec<StatusCode>) -> Self {
+        self.retryable_statuses = statuses;
+        self
+    }
+
+    /// 设置每次尝试的超时时间
+    pub fn per_try_timeout(mut self, timeout: Duration) -> Self {
+        self.per_try_timeout = Some(timeout);
+        self
+    }
+
+    fn is_retryable_status(&self, status: StatusCode) -> bool {
+        if self.retryable_statuses.is_empty() {
+            status.is_server_error()
+        } else {
+            self.retryable_statuses.contains(&status)
+        }
+    }
+
+    fn is_retryable(&self, result: &reqwest::Result<Response>) -> bool {
+        match result {
+            Ok(response) => self.is_retryable_status(response.status()),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+/// 一致性哈希负载的哈希key来源，也是会话保持负载的会话key来源
+#[derive(Debug, Clone, PartialEq)]
+pub enum HashKey {
+    /// 请求路径，不含查询参数
+    Path,
+    /// 请求头的值，从[`RequestOptions::header`]和服务默认请求头中获取，不存在时使用请求路径
+    Header(HeaderName),
+    /// Cookie的值，从`Cookie`请求头中按名称获取，不存在时使用请求路径
+    Cookie(String),
+}
+
+/// 请求的负载key
+#[derive(Debug, Clone)]
+struct RequestKey {
+    /// 会话key，由单次请求指定或按服务设置的来源获取，请求中不存在时为空
+    session: Option<String>,
+    /// 请求路径
+    path: String,
+}
+
+impl RequestKey {
+    /// 一致性哈希key，没有会话key时使用请求路径
+    fn hash_key(&self) -> &str {
+        self.session.as_deref().unwrap_or(&self.path)
+    }
+}
+
+/// 从`Cookie`请求头中获取指定名称的Cookie值
+fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.trim_matches('"').to_string())
+}
+
+/// 实例过滤条件，返回true的实例参与负载
+pub type InstanceFilter = Box<dyn Fn(&Instance) -> bool + Send + Sync>;
+
+/// 单次请求的负载选项
+///
+/// ```rust
+/// let options = RequestOptions::default()
+///     .strategy(LoadBalanceStrategy::RoundRobin)
+///     .filter(|i| i.meta.get("version").and_then(|v| v.as_str()) == Some("2"));
+/// let response = client
+///     .get_with("lb://your_service_id/hello", options)
+///     .await
+///     .unwrap()
+///     .send()
+///     .await;
+/// ```
+#[derive(Default)]
+pub struct RequestOptions {
+    /// 负载策略，优先于协议和服务已设置的负载策略
+    pub strategy: Option<LoadBalanceStrategy>,
+    /// 实例过滤条件
+    pub filter: Option<InstanceFilter>,
+    /// 一致性哈希负载的哈希key，优先于服务设置的哈希key来源
+    pub hash_key: Option<String>,
+    /// 请求头，覆盖服务的默认请求头
+    pub headers: HeaderMap,
+    /// 失败重试策略，优先于服务设置的重试策略
+    pub retry: Option<RetryPolicy>,
+}
+
+impl RequestOptions {
+    /// 设置负载策略
+    pub fn strategy(mut self, strategy: LoadBalanceStrategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// 设置实例过滤条件
+    pub fn filter(mut self, filter: impl Fn(&Instance) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// 设置一致性哈希负载的哈希key，如用户ID、会话ID
+    pub fn with_hash_key(mut self, key: impl Into<String>) -> Self {
+        self.hash_key = Some(key.into());
+        self
+    }
+
+    /// 设置请求头，可作为一致性哈希负载的哈希key来源
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// 设置失败重试策略
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+/// 只指定负载策略的负载选项
+impl From<LoadBalanceStrategy> for RequestOptions {
+    fn from(strategy: LoadBalanceStrategy) -> Self {
+        RequestOptions::default().strategy(strategy)
+    }
+}
+
+/// 拼接服务的负载协议url，如`lb://your_service_id/hello`
+///
+/// path不以`/`或`?`开头时自动补`/`。service_id不是合法的主机名（如包含`/`、`:`或空格）时返回错误。
+fn service_url(service_id: &str, path: &str) -> Result<String, LoadBalanceError> {
+    let url = if path.is_empty() || path.starts_with('/') || path.starts_with('?') {
+        format!("lb://{}{}", service_id, path)
+    } else {
+        format!("lb://{}/{}", service_id, path)
+    };
+    match Url::parse(&url) {
+        Ok(parsed_url) if !service_id.is_empty() && parsed_url.host_str() == Some(service_id) => {
+            Ok(url)
+        }
+        _ => Err(LoadBalanceError::InvalidUrl(url)),
+    }
+}
+
+/// 按service_id和路径构建请求，不需要拼接`lb://`格式的url
+///
+/// 通过[`LoadBalanceClient::service`]创建。
+///
+/// ```rust
+/// let response = client
+///     .service("your_service_id")
+///     .strategy(LoadBalanceStrategy::RoundRobin)
+///     .get("/hello")
+///     .await?
+///     .send()
+///     .await;
+/// ```
+pub struct ServiceRequest<'a> {
+    client: &'a LoadBalanceClient,
+    service_id: String,
+    options: RequestOptions,
+}
+
+impl ServiceRequest<'_> {
+    /// 设置负载选项，覆盖之前设置的负载策略、过滤条件等
+    pub fn options(mut self, options: impl Into<RequestOptions>) -> Self {
+        self.options = options.into();
+        self
+    }
+
+    /// 设置负载策略，优先于服务已设置的负载策略
+    pub fn strategy(mut self, strategy: LoadBalanceStrategy) -> Self {
+        self.options = self.options.strategy(strategy);
+        self
+    }
+
+    /// 设置实例过滤条件
+    pub fn filter(mut self, filter: impl Fn(&Instance) -> bool + Send + Sync + 'static) -> Self {
+        self.options = self.options.filter(filter);
+        self
+    }
+
+    /// 设置一致性哈希负载的哈希key，如用户ID、会话ID
+    pub fn with_hash_key(mut self, key: impl Into<String>) -> Self {
+        self.options = self.options.with_hash_key(key);
+        self
+    }
+
+    /// 设置请求头
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.options = self.options.header(name, value);
+        self
+    }
+
+    /// 设置失败重试策略
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.options = self.options.retry(retry);
+        self
+    }
+
+    pub async fn get(self, path: &str) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request(Method::GET, path).await
+    }
+
+    pub async fn post(self, path: &str) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request(Method::POST, path).await
+    }
+
+    pub async fn put(self, path: &str) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request(Method::PUT, path).await
+    }
+
+    pub async fn delete(self, path: &str) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request(Method::DELETE, path).await
+    }
+
+    pub async fn patch(self, path: &str) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request(Method::PATCH, path).await
+    }
+
+    pub async fn head(self, path: &str) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request(Method::HEAD, path).await
+    }
+
+    pub async fn request(
+        self,
+        method: Method,
+        path: &str,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        Ok(self.resolve(method, path).await?.builder)
+    }
+
+    /// 构建请求，并返回被选中的实例，见[`LoadBalanceClient::resolve`]
+    pub async fn resolve(
+        self,
+        method: Method,
+        path: &str,
+    ) -> Result<LoadBalanceRequest, LoadBalanceError> {
+        let url = service_url(&self.service_id, path)?;
+        self.client.resolve(method, &url, self.options).await
+    }
+}
+
+/// 重试时重新选择实例所需的信息
+struct RetryContext {
+    policy: RetryPolicy,
+    strategy: Option<LoadBalanceStrategy>,
+    filter: Option<Arc<InstanceFilter>>,
+    key: RequestKey,
+}
+
+impl Debug for RetryContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryContext")
+            .field("policy", &self.policy)
+            .field("strategy", &self.strategy)
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 已选择目标实例的请求
+#[derive(Debug)]
+pub struct LoadBalanceRequest {
+    /// 被选中的服务实例，非负载协议的url时为空
+    pub instance: Option<Instance>,
+    /// 请求构建器
+    pub builder: RequestBuilder,
+    /// 重试信息，未设置重试策略时为空
+    retry: Option<RetryContext>,
+}
+
+impl LoadBalanceRequest {
+    /// 被选中的服务实例
+    pub fn instance(&self) -> Option<&Instance> {
+        self.instance.as_ref()
+    }
+
+    /// 修改请求，如添加请求头、请求体等
+    pub fn map(mut self, f: impl FnOnce(RequestBuilder) -> RequestBuilder) -> Self {
+        self.builder = f(self.builder);
+        self
+    }
+}
+
+/// 服务默认请求设置
+#[derive(Debug, Default)]
+struct ServiceDefaults {
+    /// 路径前缀，如`/api/v2`
+    base_path: String,
+    /// 默认请求头
+    headers: HeaderMap,
+}
+
 /// 负载均衡客户端
 pub struct LoadBalanceClient {
     /// HTTP客户端
     client: Client,
     /// 服务负载策略配置，key为service_id，value为负载策略
     strategies: DashMap<String, LoadBalanceStrategy>,
+    /// 服务默认请求设置，key为service_id
+    service_defaults: DashMap<String, ServiceDefaults>,
+    /// 服务的哈希key来源，key为service_id
+    hash_keys: DashMap<String, HashKey>,
+    /// 服务的失败重试策略，key为service_id
+    retry_policies: DashMap<String, RetryPolicy>,
+    /// 服务的就近选择设置，key为service_id，未设置时使用注册中心配置
+    zone_affinities: DashMap<String, Option<ZoneAffinity>>,
     /// 随机负载均衡
     random_lb: RandomLoadBalance,
     /// 加权随机负载均衡
@@ -59,27 +466,53 @@ pub struct LoadBalanceClient {
     round_robin_lb: RoundRobinLoadBalance,
     /// 加权轮询负载均衡
     weight_round_robin_lb: WeightRoundRobinLoadBalance,
+    /// 一致性哈希负载均衡
+    consistent_hash_lb: ConsistentHashLoadBalance,
+    /// 会话保持负载均衡
+    sticky_lb: StickyLoadBalance,
+    /// 响应时间加权负载均衡
+    response_time_lb: ResponseTimeLoadBalance,
+    /// 实例请求统计
+    stats: Arc<InstanceStats>,
 }
 
 /// 解析url。
 ///
-/// 将lb://xxx格式的url解析为http://xxx:port的url
+/// 将lb://xxx格式的url解析为http://xxx:port的url，并拼接服务的路径前缀
 ///
 macro_rules! impl_parse_url {
-    ($self:expr, $scheme:expr, $strategy:expr, $url:expr, $parsed_url:expr) => {{
+    ($self:expr, $scheme:expr, $strategy:expr, $url:expr, $parsed_url:expr, $options:expr) => {{
         // 服务ID
-        let service_id = $parsed_url.host_str().unwrap();
-        let instance = $self.get_instance(service_id, $strategy).await?;
-        let res = $url.replace(
+        let service_id = $parsed_url
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| LoadBalanceError::InvalidUrl($url.to_string()))?;
+        let key = $self.request_key(service_id, &$parsed_url, $options);
+        let instance = $self
+            .get_instance(
+                service_id,
+                $options.strategy.or($strategy),
+                $options.filter.as_ref(),
+                &key,
+            )
+            .await?;
+        let base_path = $self
+            .service_defaults
+            .get(service_id)
+            .map(|defaults| defaults.base_path.clone())
+            .unwrap_or_default();
+        let res = $url.replacen(
             &format!("{}://{}", $scheme, service_id),
             &format!(
-                "{}{}:{}",
+                "{}{}:{}{}",
                 LoadBalanceClient::HTTP_PREFIX,
                 instance.ip,
-                instance.port
+                instance.port,
+                base_path
             ),
+            1,
         );
-        Ok(res)
+        Ok((res, Some(instance)))
     }};
 }
 
@@ -88,19 +521,49 @@ impl LoadBalanceClient {
         Self::new_with_connect_timeout(Duration::from_secs(5))
     }
 
+    /// 创建负载均衡客户端，连接超时时间为`timeout`
+    ///
+    /// 在conreg初始化之后创建时，使用初始化配置中的代理和根证书（[`HttpConfig`](crate::conf::HttpConfig)）。
+    /// 创建HTTP客户端失败时（如根证书文件在初始化后被删除）记录警告，并使用默认的HTTP客户端
     pub fn new_with_connect_timeout(timeout: Duration) -> Self {
-        let client = Client::builder()
-            .connect_timeout(timeout)
-            .build()
-            .expect("Failed to build HTTP client");
+        let client = Self::build_client(timeout).unwrap_or_else(|e| {
+            log::warn!(
+                "build HTTP client error: {:#}, fallback to the default client",
+                e
+            );
+            Client::new()
+        });
+        Self::with_client(client)
+    }
+
+    /// 创建连接超时时间为`timeout`的HTTP客户端，并应用初始化配置中的代理和根证书
+    fn build_client(timeout: Duration) -> anyhow::Result<Client> {
+        let mut builder = Client::builder().connect_timeout(timeout);
+        if let Some(config) = network::http_config() {
+            builder = config.apply(builder)?;
+        }
+        Ok(builder.build()?)
+    }
+
+    /// 使用指定的HTTP客户端创建负载均衡客户端，可自行设置代理、证书等
+    pub fn with_client(client: Client) -> Self {
+        let stats = Arc::new(InstanceStats::default());
 
         Self {
             client,
             strategies: Default::default(),
+            service_defaults: Default::default(),
+            hash_keys: Default::default(),
+            retry_policies: Default::default(),
+            zone_affinities: Default::default(),
             random_lb: RandomLoadBalance,
-            weight_random_lb: WeightRandomLoadBalance::default(),
+            weight_random_lb: WeightRandomLoadBalance::with_stats(stats.clone()),
             round_robin_lb: RoundRobinLoadBalance::default(),
-            weight_round_robin_lb: WeightRoundRobinLoadBalance::default(),
+            weight_round_robin_lb: WeightRoundRobinLoadBalance::with_stats(stats.clone()),
+            consistent_hash_lb: ConsistentHashLoadBalance::default(),
+            sticky_lb: StickyLoadBalance::default(),
+            response_time_lb: ResponseTimeLoadBalance::with_stats(stats.clone()),
+            stats,
         }
     }
 
@@ -111,9 +574,102 @@ impl LoadBalanceClient {
         self.strategies.insert(service_id.into(), strategy);
     }
 
+    /// 设置服务的路径前缀
+    ///
+    /// 设置后，`lb://payments/charge`将解析为`http://ip:port/api/v2/charge`
+    ///
+    /// - service_id：服务id
+    /// - base_path：路径前缀，如`/api/v2`
+    pub fn set_base_path(&mut self, service_id: impl Into<String>, base_path: &str) {
+        // 统一为以`/`开头、不以`/`结尾的格式
+        let base_path = match base_path.trim_matches('/') {
+            "" => String::new(),
+            path => format!("/{}", path),
+        };
+        self.service_defaults
+            .entry(service_id.into())
+            .or_default()
+            .base_path = base_path;
+    }
+
+    /// 设置服务的默认请求头，对该服务的所有请求生效
+    ///
+    /// - service_id：服务id
+    pub fn set_default_header(
+        &mut self,
+        service_id: impl Into<String>,
+        name: HeaderName,
+        value: HeaderValue,
+    ) {
+        self.service_defaults
+            .entry(service_id.into())
+            .or_default()
+            .headers
+            .insert(name, value);
+    }
+
+    /// 设置服务的一致性哈希key来源，未设置时使用请求路径
+    ///
+    /// 同时作为会话保持负载的会话key来源，未设置时会话保持负载随机选择实例
+    ///
+    /// - service_id：服务id
+    /// - hash_key：哈希key来源
+    pub fn set_hash_key(&mut self, service_id: impl Into<String>, hash_key: HashKey) {
+        self.hash_keys.insert(service_id.into(), hash_key);
+    }
+
+    /// 设置服务的失败重试策略，仅对通过[`LoadBalanceClient::execute`]发送的请求生效
+    ///
+    /// - service_id：服务id
+    /// - policy：重试策略
+    pub fn set_retry_policy(&mut self, service_id: impl Into<String>, policy: RetryPolicy) {
+        self.retry_policies.insert(service_id.into(), policy);
+    }
+
+    /// 设置服务的就近选择，优先于注册中心配置中的`zone-affinity`，为空时关闭
+    ///
+    /// - service_id：服务id
+    /// - affinity：就近选择设置
+    pub fn set_zone_affinity(
+        &mut self,
+        service_id: impl Into<String>,
+        affinity: Option<ZoneAffinity>,
+    ) {
+        self.zone_affinities.insert(service_id.into(), affinity);
+    }
+
+    /// 获取请求的负载key
+    ///
+    /// 会话key优先使用单次请求指定的key，其次按服务设置的来源获取
+    fn request_key(&self, service_id: &str, url: &Url, options: &RequestOptions) -> RequestKey {
+        let path = url.path().to_string();
+        let session = match &options.hash_key {
+            Some(key) => Some(key.clone()),
+            None => match self.hash_keys.get(service_id).as_deref() {
+                Some(HashKey::Path) => Some(path.clone()),
+                Some(HashKey::Header(name)) => {
+                    let value = options.headers.get(name).cloned().or_else(|| {
+                        self.service_defaults
+                            .get(service_id)
+                            .and_then(|defaults| defaults.headers.get(name).cloned())
+                    });
+                    value.map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                }
+                Some(HashKey::Cookie(name)) => cookie_value(&options.headers, name).or_else(|| {
+                    self.service_defaults
+                        .get(service_id)
+                        .and_then(|defaults| cookie_value(&defaults.headers, name))
+                }),
+                None => None,
+            },
+        };
+        RequestKey { session, path }
+    }
+
     /// 获取服务实例
     ///
-    /// 优先按传入的负载策略获取实例，如果不指定策略则使用已设置的，如果未设置则使用默认的负载策略
+    /// 优先按传入的负载策略获取实例，如果不指定策略则使用已设置的，如果未设置则使用默认的负载策略。
+    /// 如果指定了过滤条件，则只从满足条件的实例中选择。
     ///
     /// # Errors
     /// - 当没有可用实例时。
@@ -122,20 +678,24 @@ impl LoadBalanceClient {
         &self,
         service_id: &str,
         specify_strategy: Option<LoadBalanceStrategy>,
+        filter: Option<&InstanceFilter>,
+        key: &RequestKey,
     ) -> Result<Instance, LoadBalanceError> {
         // 如果指定了strategy，使用指定的strategy获取实例
         if let Some(strategy) = specify_strategy {
-            return self.get_instance_(service_id, &strategy).await;
+            return self.get_instance_(service_id, &strategy, filter, key).await;
         }
 
         // 从服务的负载策略中查找并获取实例
-        if let Some(strategy) = self.strategies.get(service_id) {
-            return self.get_instance_(service_id, &strategy).await;
+        if let Some(strategy) = self.strategies.get(service_id).map(|s| *s) {
+            return self.get_instance_(service_id, &strategy, filter, key).await;
         }
 
         // 缓存中没有，即未设置过负载策略，使用默认的策略获取实例
         let default_strategy = LoadBalanceStrategy::default();
-        let result = self.get_instance_(service_id, &default_strategy).await;
+        let result = self
+            .get_instance_(service_id, &default_strategy, filter, key)
+            .await;
 
         // 添加默认的到strategies
         self.strategies
@@ -145,21 +705,52 @@ impl LoadBalanceClient {
     }
 
     /// 按负载策略获取服务实例
+    ///
+    /// 开启就近选择时只在本可用区（或地域）的实例中选择
     /// - service_id：服务id
     /// - strategy：负载策略
+    /// - filter：实例过滤条件
+    /// - key：一致性哈希和会话保持使用的负载key
     async fn get_instance_(
         &self,
         service_id: &str,
         strategy: &LoadBalanceStrategy,
+        filter: Option<&InstanceFilter>,
+        key: &RequestKey,
     ) -> Result<Instance, LoadBalanceError> {
+        let mut instances = AppDiscovery::get_instances(service_id)
+            .await
+            .map_err(|e| LoadBalanceError::GetInstancesError(e.to_string()))?;
+        if let Some(filter) = filter {
+            instances.retain(|instance| filter(instance));
+        }
+        // 先过滤再就近选择，本可用区没有满足条件的实例时溢出到其他可用区
+        let affinity = match self.zone_affinities.get(service_id) {
+            Some(affinity) => affinity.clone(),
+            None => ZoneAffinity::configured(),
+        };
+        if let Some(affinity) = affinity {
+            instances = affinity.select(instances);
+        }
         match strategy {
-            LoadBalanceStrategy::Random => self.random_lb.get_instance(service_id).await,
+            LoadBalanceStrategy::Random => self.random_lb.select(service_id, instances),
             LoadBalanceStrategy::WeightedRandom => {
-                self.weight_random_lb.get_instance(service_id).await
+                self.weight_random_lb.select(service_id, instances)
             }
-            LoadBalanceStrategy::RoundRobin => self.round_robin_lb.get_instance(service_id).await,
+            LoadBalanceStrategy::RoundRobin => self.round_robin_lb.select(service_id, instances),
             LoadBalanceStrategy::WeightedRoundRobin => {
-                self.weight_round_robin_lb.get_instance(service_id).await
+                self.weight_round_robin_lb.select(service_id, instances)
+            }
+            LoadBalanceStrategy::ConsistentHash => {
+                self.consistent_hash_lb
+                    .select(service_id, instances, key.hash_key())
+            }
+            LoadBalanceStrategy::Sticky => {
+                self.sticky_lb
+                    .select(service_id, instances, key.session.as_deref())
+            }
+            LoadBalanceStrategy::ResponseTime => {
+                self.response_time_lb.select(service_id, instances)
             }
         }
     }
@@ -167,69 +758,159 @@ impl LoadBalanceClient {
 
     /// 解析url。
     ///
-    /// 将lb://xxx格式的url解析为http://xxx:port的url
+    /// 将lb://xxx格式的url解析为http://xxx:port的url，返回解析后的url和被选中的实例（非负载协议时为空）
     ///
-    async fn parse_url(&self, url: &str) -> Result<String, LoadBalanceError> {
-        let parsed_url = Url::parse(url).unwrap();
+    pub(crate) async fn parse_url(
+        &self,
+        url: &str,
+        options: &RequestOptions,
+    ) -> Result<(String, Option<Instance>), LoadBalanceError> {
+        let parsed_url =
+            Url::parse(url).map_err(|_| LoadBalanceError::InvalidUrl(url.to_string()))?;
         let scheme = parsed_url.scheme();
         match scheme {
             "lb" => {
-                impl_parse_url!(self, "lb", None, url, parsed_url)
+                impl_parse_url!(self, "lb", None, url, parsed_url, options)
             }
             "lb-r" => impl_parse_url!(
                 self,
                 "lb-r",
                 Some(LoadBalanceStrategy::Random),
                 url,
-                parsed_url
+                parsed_url,
+                options
             ),
             "lb-wr" => impl_parse_url!(
                 self,
                 "lb-wr",
                 Some(LoadBalanceStrategy::WeightedRandom),
                 url,
-                parsed_url
+                parsed_url,
+                options
             ),
             "lb-rr" => impl_parse_url!(
                 self,
                 "lb-rr",
                 Some(LoadBalanceStrategy::RoundRobin),
                 url,
-                parsed_url
+                parsed_url,
+                options
             ),
             "lb-wrr" => impl_parse_url!(
                 self,
                 "lb-wrr",
                 Some(LoadBalanceStrategy::WeightedRoundRobin),
                 url,
-                parsed_url
+                parsed_url,
+                options
+            ),
+            "lb-ch" => impl_parse_url!(
+                self,
+                "lb-ch",
+                Some(LoadBalanceStrategy::ConsistentHash),
+                url,
+                parsed_url,
+                options
             ),
-            _ => Ok(url.to_string()),
+            "lb-s" => impl_parse_url!(
+                self,
+                "lb-s",
+                Some(LoadBalanceStrategy::Sticky),
+                url,
+                parsed_url,
+                options
+            ),
+            "lb-rt" => impl_parse_url!(
+                self,
+                "lb-rt",
+                Some(LoadBalanceStrategy::ResponseTime),
+                url,
+                parsed_url,
+                options
+            ),
+            _ => Ok((url.to_string(), None)),
+        }
+    }
+
+    /// 按service_id和路径构建请求，见[`ServiceRequest`]
+    pub fn service(&self, service_id: impl Into<String>) -> ServiceRequest<'_> {
+        ServiceRequest {
+            client: self,
+            service_id: service_id.into(),
+            options: RequestOptions::default(),
         }
     }
 
     pub async fn get(&self, url: &str) -> Result<RequestBuilder, LoadBalanceError> {
-        Ok(self.client.get(self.parse_url(url).await?))
+        self.get_with(url, RequestOptions::default()).await
+    }
+
+    pub async fn get_with(
+        &self,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request_with(Method::GET, url, options).await
     }
 
     pub async fn post(&self, url: &str) -> Result<RequestBuilder, LoadBalanceError> {
-        Ok(self.client.post(self.parse_url(url).await?))
+        self.post_with(url, RequestOptions::default()).await
+    }
+
+    pub async fn post_with(
+        &self,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request_with(Method::POST, url, options).await
     }
 
     pub async fn put(&self, url: &str) -> Result<RequestBuilder, LoadBalanceError> {
-        Ok(self.client.put(self.parse_url(url).await?))
+        self.put_with(url, RequestOptions::default()).await
+    }
+
+    pub async fn put_with(
+        &self,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request_with(Method::PUT, url, options).await
     }
 
     pub async fn delete(&self, url: &str) -> Result<RequestBuilder, LoadBalanceError> {
-        Ok(self.client.delete(self.parse_url(url).await?))
+        self.delete_with(url, RequestOptions::default()).await
+    }
+
+    pub async fn delete_with(
+        &self,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request_with(Method::DELETE, url, options).await
     }
 
     pub async fn patch(&self, url: &str) -> Result<RequestBuilder, LoadBalanceError> {
-        Ok(self.client.patch(self.parse_url(url).await?))
+        self.patch_with(url, RequestOptions::default()).await
+    }
+
+    pub async fn patch_with(
+        &self,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request_with(Method::PATCH, url, options).await
     }
 
     pub async fn head(&self, url: &str) -> Result<RequestBuilder, LoadBalanceError> {
-        Ok(self.client.head(self.parse_url(url).await?))
+        self.head_with(url, RequestOptions::default()).await
+    }
+
+    pub async fn head_with(
+        &self,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        self.request_with(Method::HEAD, url, options).await
     }
 
     pub async fn request(
@@ -237,7 +918,215 @@ impl LoadBalanceClient {
         method: Method,
         url: &str,
     ) -> Result<RequestBuilder, LoadBalanceError> {
-        Ok(self.client.request(method, self.parse_url(url).await?))
+        self.request_with(method, url, RequestOptions::default())
+            .await
+    }
+
+    /// 按单次请求的负载选项构建请求
+    pub async fn request_with(
+        &self,
+        method: Method,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<RequestBuilder, LoadBalanceError> {
+        Ok(self.resolve(method, url, options).await?.builder)
+    }
+
+    /// 构建请求，并返回被选中的实例
+    ///
+    /// 可用于记录请求失败时的目标实例，或据此实现自定义的实例排除逻辑。
+    pub async fn resolve(
+        &self,
+        method: Method,
+        url: &str,
+        options: impl Into<RequestOptions>,
+    ) -> Result<LoadBalanceRequest, LoadBalanceError> {
+        let mut options = options.into();
+        let (resolved_url, instance) = self.parse_url(url, &options).await?;
+        let mut builder = self.client.request(method, resolved_url);
+        // 添加服务的默认请求头
+        if let Some(defaults) = instance
+            .as_ref()
+            .and_then(|instance| self.service_defaults.get(&instance.service_id))
+        {
+            builder = builder.headers(defaults.headers.clone());
+        }
+        builder = builder.headers(std::mem::take(&mut options.headers));
+        let retry = instance
+            .as_ref()
+            .and_then(|instance| self.retry_context(&instance.service_id, url, options));
+        Ok(LoadBalanceRequest {
+            instance,
+            builder,
+            retry,
+        })
+    }
+
+    /// 获取重试时重新选择实例所需的信息，未设置重试策略时返回None
+    fn retry_context(
+        &self,
+        service_id: &str,
+        url: &str,
+        options: RequestOptions,
+    ) -> Option<RetryContext> {
+        let policy = options.retry.clone().or_else(|| {
+            self.retry_policies
+                .get(service_id)
+                .map(|policy| policy.clone())
+        })?;
+        // url已在选择实例时校验
+        let parsed_url = Url::parse(url).ok()?;
+        Some(RetryContext {
+            policy,
+            strategy: options
+                .strategy
+                .or(LoadBalanceStrategy::from_schema(parsed_url.scheme())),
+            key: self.request_key(service_id, &parsed_url, &options),
+            filter: options.filter.map(Arc::new),
+        })
+    }
+
+    /// 发送通过[`LoadBalanceClient::resolve`]构建的请求
+    ///
+    /// 设置了重试策略时，失败后换一个未请求过的实例重试，见[`RetryPolicy`]。
+    ///
+    /// 最终响应的实例会写入响应的扩展中，可通过`response.extensions().get::<Instance>()`获取。
+    pub async fn execute(&self, request: LoadBalanceRequest) -> reqwest::Result<Response> {
+        let (client, built) = request.builder.build_split();
+        let mut built = built?;
+        let (Some(mut instance), Some(retry)) = (request.instance.clone(), request.retry) else {
+            let mut response = self.send_request(&client, built).await?;
+            if let Some(instance) = request.instance {
+                response.extensions_mut().insert(instance);
+            }
+            return Ok(response);
+        };
+
+        if let Some(timeout) = retry.policy.per_try_timeout {
+            *built.timeout_mut() = Some(timeout);
+        }
+        let mut tried = vec![stats::address(&instance)];
+        loop {
+            let next = if tried.len() < retry.policy.max_attempts as usize {
+                built.try_clone()
+            } else {
+                None
+            };
+            let result = self.send_request(&client, built).await;
+            let next = match next {
+                Some(next) if retry.policy.is_retryable(&result) => {
+                    self.next_attempt(&instance.service_id, next, &retry, &tried)
+                        .await
+                }
+                _ => None,
+            };
+            let Some((next_built, next_instance)) = next else {
+                let mut response = result?;
+                response.extensions_mut().insert(instance);
+                return Ok(response);
+            };
+            log::warn!(
+                "request to {} failed: {}, retrying on {}",
+                tried.last().map(String::as_str).unwrap_or_default(),
+                match &result {
+                    Ok(response) => response.status().to_string(),
+                    Err(e) => e.to_string(),
+                },
+                stats::address(&next_instance)
+            );
+            tried.push(stats::address(&next_instance));
+            built = next_built;
+            instance = next_instance;
+        }
+    }
+
+    /// 选择一个未请求过的实例，并将请求的目标改为该实例，没有其他可用实例时返回None
+    async fn next_attempt(
+        &self,
+        service_id: &str,
+        mut request: Request,
+        retry: &RetryContext,
+        tried: &[String],
+    ) -> Option<(Request, Instance)> {
+        let user_filter = retry.filter.clone();
+        let tried = tried.to_vec();
+        let filter: InstanceFilter = Box::new(move |instance| {
+            !tried.contains(&stats::address(instance))
+                && user_filter.as_ref().is_none_or(|filter| filter(instance))
+        });
+        let instance = self
+            .get_instance(service_id, retry.strategy, Some(&filter), &retry.key)
+            .await
+            .ok()?;
+        request.url_mut().set_host(Some(&instance.ip)).ok()?;
+        request.url_mut().set_port(Some(instance.port)).ok()?;
+        Some((request, instance))
+    }
+
+    /// 发送请求，并记录目标实例的成功率和延迟
+    ///
+    /// 连接失败或响应状态码为5xx时视为失败。
+    pub async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let (client, request) = request.build_split();
+        self.send_request(&client, request?).await
+    }
+
+    async fn send_request(&self, client: &Client, request: Request) -> reqwest::Result<Response> {
+        let address = match (
+            request.url().host_str(),
+            request.url().port_or_known_default(),
+        ) {
+            (Some(host), Some(port)) => Some(format!("{}:{}", host, port)),
+            _ => None,
+        };
+
+        let start = Instant::now();
+        let result = client.execute(request).await;
+        if let Some(address) = address {
+            let success = match &result {
+                Ok(response) => !response.status().is_server_error(),
+                Err(_) => false,
+            };
+            self.stats.record(&address, success, start.elapsed());
+        }
+        result
+    }
+
+    /// 服务的默认请求头
+    #[cfg(feature = "tower")]
+    pub(crate) fn default_headers(&self, service_id: &str) -> Option<HeaderMap> {
+        self.service_defaults
+            .get(service_id)
+            .map(|defaults| defaults.headers.clone())
+    }
+
+    /// 记录实例的请求结果，用于自适应调整权重和响应时间加权负载
+    ///
+    /// 通过[`LoadBalanceClient::send`]和[`LoadBalanceClient::execute`]发送的请求会自动记录，
+    /// 使用其他客户端向[`LoadBalanceClient::resolve`]选中的实例发送请求时，可通过该方法上报结果。
+    pub fn record(&self, instance: &Instance, success: bool, latency: Duration) {
+        self.stats
+            .record(&stats::address(instance), success, latency);
+    }
+
+    /// 获取服务各实例的请求统计
+    ///
+    /// 仅包含通过[`LoadBalanceClient::send`]请求过的实例。
+    pub async fn instance_stats(
+        &self,
+        service_id: &str,
+    ) -> Result<Vec<(Instance, InstanceStat)>, LoadBalanceError> {
+        let instances = AppDiscovery::get_instances(service_id)
+            .await
+            .map_err(|e| LoadBalanceError::GetInstancesError(e.to_string()))?;
+        Ok(instances
+            .into_iter()
+            .filter_map(|instance| {
+                self.stats
+                    .get(&stats::address(&instance))
+                    .map(|stat| (instance, stat))
+            })
+            .collect())
     }
 
     pub fn get_client(&self) -> &Client {
@@ -255,7 +1144,7 @@ impl Default for LoadBalanceClient {
 mod tests {
     use super::*;
     use crate::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
-    use crate::init_with;
+    use crate::try_init_with;
 
     #[tokio::test]
     async fn test_load_balance_client() {
@@ -274,6 +1163,121 @@ mod tests {
         println!("Response: {:?}", response.unwrap().text().await.unwrap());
     }
 
+    #[test]
+    fn test_retry_policy() {
+        for strategy in [
+            LoadBalanceStrategy::RoundRobin,
+            LoadBalanceStrategy::WeightedRoundRobin,
+            LoadBalanceStrategy::Random,
+            LoadBalanceStrategy::WeightedRandom,
+            LoadBalanceStrategy::ConsistentHash,
+            LoadBalanceStrategy::Sticky,
+            LoadBalanceStrategy::ResponseTime,
+        ] {
+            assert_eq!(
+                LoadBalanceStrategy::from_schema(strategy.as_schema()),
+                Some(strategy)
+            );
+        }
+        assert_eq!(LoadBalanceStrategy::from_schema("lb"), None);
+
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(policy.is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!policy.is_retryable_status(StatusCode::NOT_FOUND));
+
+        let policy = policy.retryable_statuses(vec![StatusCode::SERVICE_UNAVAILABLE]);
+        assert!(!policy.is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(policy.is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[test]
+    fn test_cookie_value() {
+        let mut headers = HeaderMap::new();
+        headers.append(COOKIE, HeaderValue::from_static("theme=dark; SESSION=abc"));
+        headers.append(COOKIE, HeaderValue::from_static("token=\"xyz\""));
+        assert_eq!(cookie_value(&headers, "SESSION").as_deref(), Some("abc"));
+        assert_eq!(cookie_value(&headers, "token").as_deref(), Some("xyz"));
+        assert_eq!(cookie_value(&headers, "session"), None);
+        assert_eq!(cookie_value(&HeaderMap::new(), "SESSION"), None);
+    }
+
+    #[tokio::test]
+    async fn test_parse_invalid_url() {
+        let client = LoadBalanceClient::new();
+        for url in [
+            "",
+            " ",
+            "not a url",
+            "lb",
+            "lb:",
+            "lb:/",
+            "lb://",
+            "lb:///path",
+            "lb:svc/path",
+            "lb-wrr://",
+            "://svc/path",
+            "/relative/path",
+            "http//svc",
+        ] {
+            let result = client.parse_url(url, &RequestOptions::default()).await;
+            assert!(
+                matches!(result, Err(LoadBalanceError::InvalidUrl(_))),
+                "{:?}: {:?}",
+                url,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_service_url() {
+        for (service_id, path, url) in [
+            ("svc", "/hello", "lb://svc/hello"),
+            ("svc", "hello?id=1", "lb://svc/hello?id=1"),
+            ("svc", "?id=1", "lb://svc?id=1"),
+            ("svc", "", "lb://svc"),
+            ("user-service.v2", "/", "lb://user-service.v2/"),
+        ] {
+            assert_eq!(service_url(service_id, path).unwrap(), url);
+        }
+        for (service_id, path) in [
+            ("", "/hello"),
+            ("svc/x", "/hello"),
+            ("svc:80", "/hello"),
+            ("user@svc", "/hello"),
+            ("s vc", "/hello"),
+            ("svc?", "/hello"),
+        ] {
+            assert!(
+                matches!(
+                    service_url(service_id, path),
+                    Err(LoadBalanceError::InvalidUrl(_))
+                ),
+                "{:?} {:?}",
+                service_id,
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_random_url() {
+        // 随机拼接url片段，确保任意输入都不会panic
+        const PARTS: [&str; 16] = [
+            "lb", "lb-r", "lb-wrr", "http", ":", "/", "//", "?", "#", "@", "[", "]", "%", "svc",
+            "\u{0}", "\u{4e2d}",
+        ];
+        let client = LoadBalanceClient::new();
+        for _ in 0..1000 {
+            let url = (0..fastrand::usize(0..8))
+                .map(|_| PARTS[fastrand::usize(0..PARTS.len())])
+                .collect::<String>();
+            let _ = client.parse_url(&url, &RequestOptions::default()).await;
+            let _ = service_url(&url, &url);
+        }
+    }
+
     async fn init_client() {
         let config = ConRegConfigBuilder::default()
             .client(ClientConfigBuilder::default().port(8001).build().unwrap())
@@ -286,6 +1290,6 @@ mod tests {
             .build()
             .unwrap();
 
-        init_with(config).await;
+        try_init_with(config).await.unwrap();
     }
 }
@@ -0,0 +1,162 @@
+use crate::Instance;
+use crate::lb::{LoadBalance, LoadBalanceError, stats};
+use dashmap::DashMap;
+
+/// 每个实例的默认虚拟节点数
+const DEFAULT_VIRTUAL_NODES: usize = 160;
+
+/// 哈希环
+#[derive(Debug)]
+struct Ring {
+    /// 构建哈希环的实例地址，已排序，用于判断实例列表是否变化
+    addresses: Vec<String>,
+    /// 虚拟节点，按哈希值排序，value为实例地址
+    nodes: Vec<(u64, String)>,
+}
+
+impl Ring {
+    fn new(addresses: Vec<String>, virtual_nodes: usize) -> Self {
+        let mut nodes = addresses
+            .iter()
+            .flat_map(|address| {
+                (0..virtual_nodes)
+                    .map(move |i| (hash(&format!("{}#{}", address, i)), address.clone()))
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_unstable();
+        Self { addresses, nodes }
+    }
+
+    /// 顺时针查找key命中的第一个虚拟节点，返回实例地址
+    fn locate(&self, key: &str) -> &str {
+        let hash = hash(key);
+        let index = self.nodes.partition_point(|(node, _)| *node < hash);
+        &self.nodes[index % self.nodes.len()].1
+    }
+}
+
+/// 取md5的前8字节作为哈希值，保证不同进程的计算结果一致
+fn hash(key: &str) -> u64 {
+    let digest = md5::compute(key.as_bytes());
+    u64::from_be_bytes(digest.0[..8].try_into().unwrap())
+}
+
+/// 一致性哈希负载均衡
+///
+/// 按请求的哈希key选择实例，相同key的请求会落到同一实例上；
+/// 实例上下线时只有少量key会迁移到其他实例。
+#[derive(Debug)]
+pub struct ConsistentHashLoadBalance {
+    /// 每个实例的虚拟节点数
+    virtual_nodes: usize,
+    /// 每个服务的哈希环
+    rings: DashMap<String, Ring>,
+}
+
+impl Default for ConsistentHashLoadBalance {
+    fn default() -> Self {
+        Self::new(DEFAULT_VIRTUAL_NODES)
+    }
+}
+
+impl ConsistentHashLoadBalance {
+    /// 创建一致性哈希负载均衡
+    ///
+    /// - virtual_nodes：每个实例的虚拟节点数，越大分布越均匀
+    pub fn new(virtual_nodes: usize) -> Self {
+        Self {
+            virtual_nodes: virtual_nodes.max(1),
+            rings: Default::default(),
+        }
+    }
+
+    /// 按哈希key从给定的实例列表中选择一个实例
+    pub(crate) fn select(
+        &self,
+        service_id: &str,
+        instances: Vec<Instance>,
+        key: &str,
+    ) -> Result<Instance, LoadBalanceError> {
+        if instances.is_empty() {
+            return Err(LoadBalanceError::NoAvailableInstance(
+                service_id.to_string(),
+            ));
+        }
+        if instances.len() == 1 {
+            return Ok(instances[0].clone());
+        }
+
+        let mut addresses = instances.iter().map(stats::address).collect::<Vec<_>>();
+        addresses.sort_unstable();
+        addresses.dedup();
+
+        // 实例列表未变化时复用哈希环
+        let mut ring = self
+            .rings
+            .entry(service_id.to_string())
+            .or_insert_with(|| Ring::new(addresses.clone(), self.virtual_nodes));
+        if ring.addresses != addresses {
+            *ring = Ring::new(addresses, self.virtual_nodes);
+        }
+
+        let address = ring.locate(key);
+        instances
+            .iter()
+            .find(|instance| stats::address(instance) == address)
+            .cloned()
+            .ok_or_else(|| LoadBalanceError::NoAvailableInstance(service_id.to_string()))
+    }
+}
+
+impl LoadBalance for ConsistentHashLoadBalance {
+    /// 未指定哈希key，随机生成key选择实例
+    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
+        let instances = self.instances(service_id).await?;
+        self.select(service_id, instances, &fastrand::u64(..).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn instance(port: u16) -> Instance {
+        Instance {
+            ip: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_consistent_hash() {
+        let lb = ConsistentHashLoadBalance::default();
+        let instances = (8001..=8004).map(instance).collect::<Vec<_>>();
+
+        // 相同key总是落到同一实例，且分布大致均匀
+        let mut selected = HashMap::new();
+        let mut counts = HashMap::new();
+        for i in 0..4000 {
+            let key = format!("user-{}", i);
+            let port = lb.select("svc", instances.clone(), &key).unwrap().port;
+            assert_eq!(
+                lb.select("svc", instances.clone(), &key).unwrap().port,
+                port
+            );
+            *counts.entry(port).or_insert(0) += 1;
+            selected.insert(key, port);
+        }
+        assert_eq!(counts.len(), 4);
+        assert!(counts.values().all(|count| *count > 500), "{:?}", counts);
+
+        // 移除一个实例后，只有原本落在该实例上的key迁移
+        let remaining = instances[..3].to_vec();
+        for (key, port) in selected {
+            let new_port = lb.select("svc", remaining.clone(), &key).unwrap().port;
+            if port != 8004 {
+                assert_eq!(new_port, port, "{}", key);
+            }
+        }
+    }
+}
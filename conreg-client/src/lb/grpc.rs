@@ -0,0 +1,209 @@
+//! # gRPC负载均衡
+//! 基于tonic的负载均衡通道，通过`lb://service_id`创建[`Channel`]，在服务的可用实例间负载请求。
+//!
+//! 通道创建时加载服务当前的可用实例，之后订阅服务实例的变化（见[`AppDiscovery::subscribe`]），
+//! 实例上线、下线或心跳超时后自动增删通道中的端点，不需要重新创建通道。
+//!
+//! 实例的选择使用tonic内置的P2C（Power of Two Choices）负载，参与负载的实例可通过过滤条件和就近选择限定。
+//! 通道的所有克隆都被释放后停止订阅。
+
+use crate::lb::{InstanceFilter, LoadBalanceError, ZoneAffinity};
+use crate::{AppDiscovery, Instance};
+use std::collections::HashSet;
+use tokio::sync::{mpsc, watch};
+use tonic::transport::channel::Change;
+use tonic::transport::{Channel, Endpoint};
+
+/// 通道接收端点变化的缓冲大小
+const CHANGE_CAPACITY: usize = 64;
+
+/// 端点配置函数
+type EndpointFn = Box<dyn Fn(Endpoint) -> Endpoint + Send + Sync>;
+
+/// A tonic [`Channel`] balancing requests over the available instances of a service
+///
+/// Instances are kept up to date from the registry: new instances are added to the channel and
+/// deregistered or unhealthy ones are removed while the channel is in use.
+///
+/// ```rust
+/// // With the default settings
+/// let channel = lb::grpc::channel("lb://your_service_id").await?;
+/// let mut client = GreeterClient::new(channel);
+///
+/// // Or customize the endpoints and the instances to use
+/// let channel = DiscoverChannel::new("lb://your_service_id")?
+///     .filter(|i| i.meta.get("version").and_then(|v| v.as_str()) == Some("2"))
+///     .endpoint(|endpoint| endpoint.timeout(Duration::from_secs(3)))
+///     .connect()
+///     .await?;
+/// ```
+pub struct DiscoverChannel {
+    service_id: String,
+    filter: Option<InstanceFilter>,
+    zone_affinity: Option<ZoneAffinity>,
+    endpoint: Option<EndpointFn>,
+}
+
+impl DiscoverChannel {
+    /// Balance over the instances of the service in `url`, e.g. `lb://your_service_id`
+    pub fn new(url: &str) -> Result<Self, LoadBalanceError> {
+        let service_id = url
+            .strip_prefix("lb://")
+            .map(|service_id| service_id.trim_end_matches('/'))
+            .filter(|service_id| !service_id.is_empty() && !service_id.contains('/'))
+            .ok_or_else(|| LoadBalanceError::InvalidUrl(url.to_string()))?;
+        Ok(Self {
+            service_id: service_id.to_string(),
+            filter: None,
+            zone_affinity: ZoneAffinity::configured(),
+            endpoint: None,
+        })
+    }
+
+    /// Only balance over the instances matching `filter`
+    pub fn filter(mut self, filter: impl Fn(&Instance) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Set the zone affinity, default: the `zone-affinity` of the discovery configuration
+    pub fn zone_affinity(mut self, zone_affinity: Option<ZoneAffinity>) -> Self {
+        self.zone_affinity = zone_affinity;
+        self
+    }
+
+    /// Configure the endpoint of every instance, e.g. timeouts or TLS
+    pub fn endpoint(mut self, f: impl Fn(Endpoint) -> Endpoint + Send + Sync + 'static) -> Self {
+        self.endpoint = Some(Box::new(f));
+        self
+    }
+
+    /// Create the channel with the current instances and keep them up to date
+    ///
+    /// Connections are established lazily. Requests wait for an instance when there is none.
+    pub async fn connect(self) -> Result<Channel, LoadBalanceError> {
+        let instances = AppDiscovery::get_instances(&self.service_id)
+            .await
+            .map_err(|e| LoadBalanceError::GetInstancesError(e.to_string()))?;
+        let (changed_tx, changed_rx) = watch::channel(instances.clone());
+        let subscription = AppDiscovery::subscribe(&self.service_id, move |instances| {
+            changed_tx.send_replace(instances.to_vec());
+        })
+        .map_err(|e| LoadBalanceError::GetInstancesError(e.to_string()))?;
+
+        let (channel, tx) = Channel::balance_channel(CHANGE_CAPACITY);
+        let mut endpoints = HashSet::new();
+        self.apply(&instances, &mut endpoints, &tx).await;
+        tokio::spawn(async move {
+            self.watch(changed_rx, endpoints, tx).await;
+            subscription.unsubscribe();
+        });
+        Ok(channel)
+    }
+
+    /// 实例变化时更新通道中的端点，通道被释放后返回
+    async fn watch(
+        &self,
+        mut changed: watch::Receiver<Vec<Instance>>,
+        mut endpoints: HashSet<String>,
+        tx: mpsc::Sender<Change<String, Endpoint>>,
+    ) {
+        loop {
+            tokio::select! {
+                _ = tx.closed() => return,
+                result = changed.changed() => {
+                    if result.is_err() {
+                        return;
+                    }
+                }
+            }
+            let instances = changed.borrow_and_update().clone();
+            if !self.apply(&instances, &mut endpoints, &tx).await {
+                return;
+            }
+        }
+    }
+
+    /// 将通道中的端点更新为参与负载的实例，通道被释放时返回false
+    ///
+    /// - endpoints：通道中当前端点的地址
+    async fn apply(
+        &self,
+        instances: &[Instance],
+        endpoints: &mut HashSet<String>,
+        tx: &mpsc::Sender<Change<String, Endpoint>>,
+    ) -> bool {
+        let mut instances = instances.to_vec();
+        if let Some(filter) = &self.filter {
+            instances.retain(|instance| filter(instance));
+        }
+        if let Some(affinity) = &self.zone_affinity {
+            instances = affinity.select(instances);
+        }
+
+        let mut changes = vec![];
+        let mut current = HashSet::new();
+        for instance in instances {
+            let address = format!("{}:{}", instance.ip, instance.port);
+            if !current.insert(address.clone()) || endpoints.contains(&address) {
+                continue;
+            }
+            let endpoint = match Endpoint::from_shared(format!("http://{}", address)) {
+                Ok(endpoint) => endpoint,
+                Err(e) => {
+                    log::warn!("invalid endpoint of instance {}: {}", instance.id, e);
+                    continue;
+                }
+            };
+            let endpoint = match &self.endpoint {
+                Some(f) => f(endpoint),
+                None => endpoint,
+            };
+            changes.push(Change::Insert(address, endpoint));
+        }
+        for address in endpoints.iter() {
+            if !current.contains(address) {
+                changes.push(Change::Remove(address.clone()));
+            }
+        }
+        if !changes.is_empty() {
+            log::info!(
+                "grpc endpoints of service {} changed: {:?}",
+                self.service_id,
+                current
+            );
+        }
+        *endpoints = current;
+        for change in changes {
+            if tx.send(change).await.is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Create a channel balancing over the instances of the service in `url`, e.g. `lb://your_service_id`
+///
+/// See [`DiscoverChannel`].
+pub async fn channel(url: &str) -> Result<Channel, LoadBalanceError> {
+    DiscoverChannel::new(url)?.connect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        for url in ["lb://user-service", "lb://user-service/"] {
+            assert_eq!(
+                DiscoverChannel::new(url).unwrap().service_id,
+                "user-service"
+            );
+        }
+        for url in ["lb://", "http://user-service", "lb://user-service/path"] {
+            assert!(DiscoverChannel::new(url).is_err());
+        }
+    }
+}
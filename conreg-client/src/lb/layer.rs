@@ -0,0 +1,222 @@
+//! # tower中间件
+//! 将负载均衡适配为tower的[`Layer`]和[`Service`]，可以在hyper、axum、tonic等基于tower的客户端中使用，
+//! 不必使用内置的reqwest客户端。
+//!
+//! 请求的uri使用负载协议（如`lb://service_id/path`）时，按[`LoadBalanceClient`]的设置选择实例，
+//! 将uri改写为`http://ip:port/path`后交给内层服务发送，其他uri原样透传。
+//!
+//! 被选中的实例写入请求和响应的扩展中，请求结果计入实例的请求统计，响应状态码为5xx或请求失败时视为失败。
+
+use crate::Instance;
+use crate::lb::{LoadBalanceClient, LoadBalanceError, RequestOptions};
+use http::header::HeaderMap;
+use http::{Request, Response, Uri};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{BoxError, Layer, Service};
+
+/// A tower [`Layer`] resolving load balance urls to service instances
+///
+/// Wraps any HTTP client service, e.g. a hyper client, with conreg discovery
+/// and load balancing. Requests to `lb://service_id/path` (or `lb-rr://`, `lb-ch://`, ...) are
+/// sent to an instance selected by the strategies, filters and zone affinity set on the
+/// [`LoadBalanceClient`]; other requests pass through unchanged.
+///
+/// The selected [`Instance`] is available in the extensions of both the request and the response.
+///
+/// ```rust
+/// let mut lb = LoadBalanceClient::new();
+/// lb.set_strategy("your_service_id", LoadBalanceStrategy::RoundRobin);
+///
+/// let client = Client::builder(TokioExecutor::new()).build_http::<Full<Bytes>>();
+/// let mut service = ServiceBuilder::new()
+///     .layer(ConregDiscoverLayer::new(lb))
+///     .service(client);
+///
+/// let request = Request::get("lb://your_service_id/hello").body(Full::default()).unwrap();
+/// let response = service.ready().await.unwrap().call(request).await;
+/// ```
+#[derive(Clone)]
+pub struct ConregDiscoverLayer {
+    client: Arc<LoadBalanceClient>,
+}
+
+impl ConregDiscoverLayer {
+    /// Resolve instances with the settings of `client`
+    pub fn new(client: LoadBalanceClient) -> Self {
+        Self::from_arc(Arc::new(client))
+    }
+
+    /// Share a load balance client with other layers or the bundled reqwest client
+    pub fn from_arc(client: Arc<LoadBalanceClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl<S> Layer<S> for ConregDiscoverLayer {
+    type Service = ConregDiscover<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConregDiscover {
+            inner,
+            client: self.client.clone(),
+        }
+    }
+}
+
+/// A tower [`Service`] sending load balance requests to service instances, see [`ConregDiscoverLayer`]
+#[derive(Clone)]
+pub struct ConregDiscover<S> {
+    inner: S,
+    client: Arc<LoadBalanceClient>,
+}
+
+impl<S> ConregDiscover<S> {
+    /// Wrap `inner` with the settings of `client`
+    pub fn new(inner: S, client: Arc<LoadBalanceClient>) -> Self {
+        Self { inner, client }
+    }
+
+    /// The wrapped service
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for ConregDiscover<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        // 使用已就绪的内层服务发送请求，克隆的服务留给下一次请求
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let client = self.client.clone();
+        Box::pin(async move {
+            let Some(instance) = resolve(&client, &mut request).await? else {
+                return inner.call(request).await.map_err(Into::into);
+            };
+            request.extensions_mut().insert(instance.clone());
+
+            let start = Instant::now();
+            let result = inner.call(request).await;
+            let success = match &result {
+                Ok(response) => !response.status().is_server_error(),
+                Err(_) => false,
+            };
+            client.record(&instance, success, start.elapsed());
+            let mut response = result.map_err(Into::into)?;
+            response.extensions_mut().insert(instance);
+            Ok(response)
+        })
+    }
+}
+
+/// 解析请求的uri，使用负载协议时改写为被选中的实例地址，并添加服务的默认请求头
+///
+/// 返回被选中的实例，非负载协议时为空
+async fn resolve<B>(
+    client: &LoadBalanceClient,
+    request: &mut Request<B>,
+) -> Result<Option<Instance>, LoadBalanceError> {
+    // 只有带协议的绝对uri才可能使用负载协议
+    if request.uri().scheme().is_none() {
+        return Ok(None);
+    }
+    let options = RequestOptions {
+        headers: request.headers().clone(),
+        ..Default::default()
+    };
+    let (url, instance) = client
+        .parse_url(&request.uri().to_string(), &options)
+        .await?;
+    let Some(instance) = instance else {
+        return Ok(None);
+    };
+    *request.uri_mut() = url
+        .parse::<Uri>()
+        .map_err(|_| LoadBalanceError::InvalidUrl(url))?;
+    // 请求中已有的请求头优先
+    let defaults = client
+        .default_headers(&instance.service_id)
+        .unwrap_or_default();
+    merge_headers(request.headers_mut(), defaults);
+    Ok(Some(instance))
+}
+
+/// 添加请求中不存在的请求头
+fn merge_headers(headers: &mut HeaderMap, defaults: HeaderMap) {
+    for name in defaults.keys() {
+        if headers.contains_key(name) {
+            continue;
+        }
+        for value in defaults.get_all(name) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+    use std::future::{Ready, poll_fn, ready};
+
+    /// 返回请求uri的服务
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = Response<String>;
+        type Error = BoxError;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            ready(Ok(Response::new(request.uri().to_string())))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pass_through() {
+        let mut service = ConregDiscoverLayer::new(LoadBalanceClient::new()).layer(Echo);
+        for uri in ["http://127.0.0.1:8000/hello", "/hello"] {
+            poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+            let request = Request::get(uri).body(()).unwrap();
+            let response = service.call(request).await.unwrap();
+            assert_eq!(response.body(), uri);
+            assert!(response.extensions().get::<Instance>().is_none());
+        }
+    }
+
+    #[test]
+    fn test_merge_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-a", HeaderValue::from_static("request"));
+        let mut defaults = HeaderMap::new();
+        defaults.insert("x-a", HeaderValue::from_static("default"));
+        defaults.append("x-b", HeaderValue::from_static("1"));
+        defaults.append("x-b", HeaderValue::from_static("2"));
+        merge_headers(&mut headers, defaults);
+        assert_eq!(headers.get_all("x-a").iter().count(), 1);
+        assert_eq!(headers["x-a"], "request");
+        assert_eq!(headers.get_all("x-b").iter().count(), 2);
+    }
+}
@@ -12,13 +12,56 @@
 //! ## [`WeightRoundRobinLoadBalance`]
 //! Weighted Round Robin: Select from the service list according to weights.
 //!
+//! ## [`ConsistentHashLoadBalance`]
+//! Consistent Hash: Select by hashing a per-request key (an explicit key, a header or the
+//! request path) onto a ring of virtual nodes, so that the same user or session always lands
+//! on the same instance. Only a small share of keys move when instances come and go.
+//!
+//! ## [`StickyLoadBalance`]
+//! Sticky Session: Pin each session (a cookie or header value, see [`HashKey`]) to the instance
+//! first selected for it, so that sessions stay put when instances are added. When the pinned
+//! instance disappears, or the pin has been idle for too long, a new instance is selected
+//! randomly and pinned. Requests without a session key are balanced randomly.
+//!
+//! ## [`ResponseTimeLoadBalance`]
+//! Weighted Response Time: Select by weights computed only from the success rate and latency
+//! the client measured for each instance (see [Adaptive Weights](#adaptive-weights)), ignoring
+//! the configured weights, so that faster instances receive proportionally more traffic.
+//! Instances without enough samples are weighted like the fastest one until measured.
+//!
+//! ## Zone Affinity
+//! Instances carry their zone and region in the `zone` and `region` metadata. With
+//! [`ZoneAffinity`], every strategy only selects among the instances in the same zone as the
+//! caller, spilling over to the same region and then to all instances when the local pool has
+//! fewer available instances than the threshold. Enable it for all load balancers with
+//! `zone-affinity` in the discovery configuration, or per service with
+//! [`LoadBalanceClient::set_zone_affinity`].
+//!
+//! ## Tower Middleware
+//! With the `tower` feature, [`ConregDiscoverLayer`] plugs discovery and load balancing into
+//! tower based clients such as hyper, axum or tonic. Requests to `lb://your_service_id/path`
+//! are sent to the instance selected with the settings of the wrapped [`LoadBalanceClient`].
+//!
+//! ## gRPC Channels
+//! With the `grpc` feature, [`grpc::channel`] creates a tonic channel for `lb://your_service_id`
+//! that balances over the available instances of the service and follows instances coming
+//! and going.
+//!
 //! ## About Weights
 //! Weights can be set through service metadata, typically with a suggested weight range of 1-100.
 //!
+//! ## Adaptive Weights
+//! Requests sent through [`LoadBalanceClient::send`] record the success rate and latency of
+//! the target instance. The weighted strategies of the client scale the configured weights
+//! by these statistics, so that failing or slow instances receive less traffic even when the
+//! server-side weights are stale. Use [`LoadBalanceClient::instance_stats`] to inspect them.
+//! When requests are sent with another client, report their results with
+//! [`LoadBalanceClient::record`].
+//!
 //! # Usage
 //! ```rust
 //! // Initialize Discovery
-//! let _ = init().await;
+//! try_init().await.unwrap();
 //!
 //! // Create a load balance client
 //! let mut client = LoadBalanceClient::new();
@@ -26,6 +69,10 @@
 //! // Optional: Set the load balancing strategy for a service
 //! client.set_strategy("your_service_id", LoadBalanceStrategy::Random);
 //!
+//! // Optional: Set a base path and default headers applied to all calls of a service
+//! client.set_base_path("your_service_id", "/api/v2");
+//! client.set_default_header("your_service_id", AUTHORIZATION, HeaderValue::from_static("token"));
+//!
 //! // Make a request
 //! let response = client
 //!     .get("lb://your_service_id/hello")
@@ -34,31 +81,96 @@
 //!     .send()
 //!     .await;
 //!
+//! // Or build the request from the service id and path, without formatting an `lb://` url
+//! let response = client
+//!     .service("your_service_id")
+//!     .strategy(LoadBalanceStrategy::RoundRobin)
+//!     .get("/hello")
+//!     .await
+//!     .unwrap()
+//!     .send()
+//!     .await;
+//!
+//! // Override the strategy of a single call
+//! let request = client
+//!     .get_with("lb://your_service_id/hello", LoadBalanceStrategy::RoundRobin)
+//!     .await
+//!     .unwrap();
+//!
+//! // Or send it through the client to collect instance statistics
+//! let request = client.get("lb://your_service_id/hello").await.unwrap();
+//! let response = client.send(request).await;
+//!
+//! // Get the selected instance, e.g. for logging
+//! let request = client
+//!     .resolve(Method::GET, "lb://your_service_id/hello", RequestOptions::default())
+//!     .await
+//!     .unwrap();
+//! println!("Selected instance: {:?}", request.instance());
+//! let response = client.execute(request).await;
+//!
 //! println!("Response: {:?}", response.unwrap().text().await.unwrap());
+//!
+//! // Retry failed requests sent through `execute` on another instance
+//! client.set_retry_policy("your_service_id", RetryPolicy::new(3));
+//!
+//! // Route requests of the same user to the same instance
+//! client.set_hash_key("your_service_id", HashKey::Header(HeaderName::from_static("x-user-id")));
+//! let response = client
+//!     .get_with(
+//!         "lb-ch://your_service_id/profile",
+//!         RequestOptions::default().header(HeaderName::from_static("x-user-id"), HeaderValue::from_static("42")),
+//!     )
+//!     .await;
 //! ```
 pub mod client;
+mod consistent_hash;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "tower")]
+mod layer;
 mod random;
+mod response_time;
 mod round;
+mod stats;
+mod sticky;
 mod weight_random;
 mod weight_round;
+mod zone;
 
 use crate::{AppDiscovery, Instance};
-pub use client::LoadBalanceClient;
+pub use client::{
+    HashKey, InstanceFilter, LoadBalanceClient, LoadBalanceRequest, LoadBalanceStrategy,
+    RequestOptions, RetryPolicy, ServiceRequest,
+};
+pub use consistent_hash::ConsistentHashLoadBalance;
+#[cfg(feature = "tower")]
+pub use layer::{ConregDiscover, ConregDiscoverLayer};
 pub use random::RandomLoadBalance;
+pub use response_time::ResponseTimeLoadBalance;
 pub use round::RoundRobinLoadBalance;
+pub use stats::{InstanceStat, InstanceStats};
+pub use sticky::StickyLoadBalance;
 pub use weight_random::WeightRandomLoadBalance;
 pub use weight_round::WeightRoundRobinLoadBalance;
+pub use zone::ZoneAffinity;
 
 pub trait LoadBalance {
     /// Get the list of service instances
+    ///
+    /// Only the instances in the local zone when `zone-affinity` is enabled in the discovery configuration.
     fn instances(
         &self,
         service_id: &str,
     ) -> impl Future<Output = Result<Vec<Instance>, LoadBalanceError>> + Send {
         async {
-            AppDiscovery::get_instances(service_id)
+            let instances = AppDiscovery::get_instances(service_id)
                 .await
-                .map_err(|e| LoadBalanceError::GetInstancesError(e.to_string()))
+                .map_err(|e| LoadBalanceError::GetInstancesError(e.to_string()))?;
+            Ok(match ZoneAffinity::configured() {
+                Some(affinity) => affinity.select(instances),
+                None => instances,
+            })
         }
     }
 
@@ -75,6 +187,8 @@ pub enum LoadBalanceError {
     GetInstancesError(String),
     /// No available instance
     NoAvailableInstance(String),
+    /// Malformed request url
+    InvalidUrl(String),
 }
 
 impl std::fmt::Display for LoadBalanceError {
@@ -84,6 +198,7 @@ impl std::fmt::Display for LoadBalanceError {
             LoadBalanceError::NoAvailableInstance(s) => {
                 write!(f, "No available instance for service: {}", s)
             }
+            LoadBalanceError::InvalidUrl(url) => write!(f, "Invalid url: {}", url),
         }
     }
 }
@@ -0,0 +1,97 @@
+use crate::Instance;
+use crate::lb::{InstanceStats, LoadBalance, LoadBalanceError};
+use std::sync::Arc;
+
+/// 响应时间加权负载均衡
+///
+/// 按客户端统计的各实例成功率和延迟（指数加权移动平均）计算权重，响应越快、失败越少的实例被选中的概率越高，
+/// 不使用服务端下发的权重。统计来自通过[`LoadBalanceClient::send`](crate::lb::LoadBalanceClient::send)、
+/// [`LoadBalanceClient::execute`](crate::lb::LoadBalanceClient::execute)发送的请求，
+/// 或通过[`LoadBalanceClient::record`](crate::lb::LoadBalanceClient::record)上报的请求结果。
+///
+/// 没有足够统计的实例与最快的实例权重相同，异常实例保留少量流量以便恢复后重新获得流量。
+#[derive(Debug, Default)]
+pub struct ResponseTimeLoadBalance {
+    /// 实例请求统计
+    stats: Arc<InstanceStats>,
+}
+
+impl ResponseTimeLoadBalance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用实例请求统计创建
+    pub fn with_stats(stats: Arc<InstanceStats>) -> Self {
+        Self { stats }
+    }
+
+    /// 从给定的实例列表中选择一个实例
+    pub(crate) fn select(
+        &self,
+        service_id: &str,
+        instances: Vec<Instance>,
+    ) -> Result<Instance, LoadBalanceError> {
+        if instances.is_empty() {
+            return Err(LoadBalanceError::NoAvailableInstance(
+                service_id.to_string(),
+            ));
+        }
+        if instances.len() == 1 {
+            return Ok(instances[0].clone());
+        }
+
+        let weights = self.stats.response_time_weights(&instances);
+        let total_weight: u64 = weights.iter().sum();
+        let random_weight: u64 = fastrand::u64(0..total_weight);
+        let mut current_weight = 0;
+        for (instance, weight) in instances.iter().zip(weights) {
+            current_weight += weight;
+            if random_weight < current_weight {
+                return Ok(instance.clone());
+            }
+        }
+
+        // 理论上不会执行到这里
+        Ok(instances[0].clone())
+    }
+}
+
+impl LoadBalance for ResponseTimeLoadBalance {
+    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
+        let instances = self.instances(service_id).await?;
+        self.select(service_id, instances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn instance(port: u16) -> Instance {
+        Instance {
+            ip: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_response_time_load_balance() {
+        let stats = Arc::new(InstanceStats::default());
+        let lb = ResponseTimeLoadBalance::with_stats(stats.clone());
+        let instances = vec![instance(8001), instance(8002)];
+        for _ in 0..20 {
+            stats.record("127.0.0.1:8001", true, Duration::from_millis(5));
+            stats.record("127.0.0.1:8002", true, Duration::from_millis(100));
+        }
+
+        // 延迟为1/20，被选中的概率约为1/21
+        let slow = (0..2000)
+            .filter(|_| lb.select("test", instances.clone()).unwrap().port == 8002)
+            .count();
+        assert!(slow > 0 && slow < 300, "{}", slow);
+        assert!(lb.select("test", vec![]).is_err());
+    }
+}
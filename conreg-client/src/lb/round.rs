@@ -10,12 +10,13 @@ impl RoundRobinLoadBalance {
     pub fn new() -> Self {
         Self::default()
     }
-}
-
-impl LoadBalance for RoundRobinLoadBalance {
-    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
-        let instances = self.instances(service_id).await?;
 
+    /// 从给定的实例列表中选择一个实例
+    pub(crate) fn select(
+        &self,
+        service_id: &str,
+        instances: Vec<Instance>,
+    ) -> Result<Instance, LoadBalanceError> {
         if instances.is_empty() {
             return Err(LoadBalanceError::NoAvailableInstance(
                 service_id.to_string(),
@@ -30,13 +31,20 @@ impl LoadBalance for RoundRobinLoadBalance {
     }
 }
 
+impl LoadBalance for RoundRobinLoadBalance {
+    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
+        let instances = self.instances(service_id).await?;
+        self.select(service_id, instances)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::init;
+    use crate::try_init;
     #[tokio::test]
     async fn test_round_robin_balance() {
-        let _ = init().await;
+        let _ = try_init().await;
         let lb = RoundRobinLoadBalance::default();
         let instances = lb
             .get_instance("conreg_client-ecdb9f5551f4f00c")
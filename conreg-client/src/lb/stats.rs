@@ -0,0 +1,190 @@
+//! # 实例请求统计
+//! 负载均衡客户端基于自身流量采集每个目标实例的成功率与延迟，
+//! 加权负载策略据此对服务端下发的权重进行自适应调整：
+//!
+//! `有效权重 = 权重 * 成功率 * (最快实例延迟 / 实例延迟)`
+//!
+//! 响应时间负载策略不使用服务端下发的权重，按`成功率 * (最快实例延迟 / 实例延迟)`计算权重。
+//!
+//! - 样本数不足或长时间未更新的实例不参与调整，使用原始权重
+//! - 调整系数存在下限，保证异常实例仍能获得少量流量以便恢复
+
+use crate::Instance;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// 指数加权移动平均的平滑系数
+const EWMA_ALPHA: f64 = 0.2;
+/// 参与权重调整所需的最少样本数
+const MIN_SAMPLES: u64 = 10;
+/// 统计过期时间，超过该时间未更新的统计不参与权重调整
+const STAT_EXPIRE: Duration = Duration::from_secs(60);
+/// 权重放大倍数，避免调整后的整数权重精度丢失
+const WEIGHT_SCALE: f64 = 100.0;
+/// 权重调整系数下限
+const MIN_FACTOR: f64 = 0.05;
+
+/// Request statistics of a service instance, collected from the client's own traffic.
+#[derive(Debug, Clone)]
+pub struct InstanceStat {
+    /// Instance address, in the format `ip:port`
+    pub address: String,
+    /// Total number of requests
+    pub requests: u64,
+    /// Number of failed requests (connection errors or 5xx responses)
+    pub failures: u64,
+    /// Recent success rate (exponentially weighted), between 0 and 1
+    pub success_rate: f64,
+    /// Recent average latency (exponentially weighted)
+    pub latency: Duration,
+    /// Time of the last request
+    pub last_request_time: Instant,
+}
+
+/// 实例请求统计，key为实例地址
+#[derive(Debug, Default)]
+pub struct InstanceStats {
+    stats: DashMap<String, InstanceStat>,
+}
+
+impl InstanceStats {
+    /// 记录一次请求结果
+    pub fn record(&self, address: &str, success: bool, latency: Duration) {
+        let now = Instant::now();
+        let success_value = if success { 1.0 } else { 0.0 };
+        let mut stat = self
+            .stats
+            .entry(address.to_string())
+            .or_insert_with(|| InstanceStat {
+                address: address.to_string(),
+                requests: 0,
+                failures: 0,
+                success_rate: success_value,
+                latency,
+                last_request_time: now,
+            });
+        stat.requests += 1;
+        if !success {
+            stat.failures += 1;
+        }
+        stat.success_rate = EWMA_ALPHA * success_value + (1.0 - EWMA_ALPHA) * stat.success_rate;
+        stat.latency = latency.mul_f64(EWMA_ALPHA) + stat.latency.mul_f64(1.0 - EWMA_ALPHA);
+        stat.last_request_time = now;
+    }
+
+    /// 获取指定地址的统计
+    pub fn get(&self, address: &str) -> Option<InstanceStat> {
+        self.stats.get(address).map(|s| s.clone())
+    }
+
+    /// 计算实例的有效权重，返回值与instances一一对应
+    pub fn effective_weights(&self, instances: &[Instance]) -> Vec<u64> {
+        instances
+            .iter()
+            .zip(self.factors(instances))
+            .map(|(instance, factor)| {
+                ((instance.get_weight() as f64 * WEIGHT_SCALE * factor).round() as u64).max(1)
+            })
+            .collect()
+    }
+
+    /// 只按成功率和延迟计算实例的权重，不使用服务端下发的权重，返回值与instances一一对应
+    ///
+    /// 没有足够统计的实例与最快的实例权重相同，以便新实例获得流量并积累统计
+    pub fn response_time_weights(&self, instances: &[Instance]) -> Vec<u64> {
+        self.factors(instances)
+            .into_iter()
+            .map(|factor| ((WEIGHT_SCALE * factor).round() as u64).max(1))
+            .collect()
+    }
+
+    /// 计算实例的权重调整系数，返回值与instances一一对应
+    fn factors(&self, instances: &[Instance]) -> Vec<f64> {
+        let stats = instances
+            .iter()
+            .map(|instance| {
+                self.get(&address(instance)).filter(|s| {
+                    s.requests >= MIN_SAMPLES && s.last_request_time.elapsed() < STAT_EXPIRE
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // 最快实例的延迟，作为延迟系数的基准
+        let min_latency = stats
+            .iter()
+            .flatten()
+            .map(|s| s.latency)
+            .min()
+            .unwrap_or_default();
+
+        stats
+            .into_iter()
+            .map(|stat| match stat {
+                Some(stat) => {
+                    let latency_factor = if stat.latency.is_zero() {
+                        1.0
+                    } else {
+                        min_latency.as_secs_f64() / stat.latency.as_secs_f64()
+                    };
+                    (stat.success_rate * latency_factor).max(MIN_FACTOR)
+                }
+                None => 1.0,
+            })
+            .collect()
+    }
+}
+
+/// 实例地址，格式为`ip:port`
+pub(crate) fn address(instance: &Instance) -> String {
+    format!("{}:{}", instance.ip, instance.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(port: u16) -> Instance {
+        Instance {
+            ip: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_effective_weights() {
+        let stats = InstanceStats::default();
+        let instances = vec![instance(8001), instance(8002), instance(8003)];
+        for _ in 0..MIN_SAMPLES {
+            stats.record("127.0.0.1:8001", true, Duration::from_millis(10));
+            stats.record("127.0.0.1:8002", false, Duration::from_millis(10));
+        }
+
+        let weights = stats.effective_weights(&instances);
+        // 全部成功的实例保持原始权重
+        assert_eq!(weights[0], 100);
+        // 持续失败的实例降到下限
+        assert_eq!(weights[1], (WEIGHT_SCALE * MIN_FACTOR) as u64);
+        // 无统计的实例保持原始权重
+        assert_eq!(weights[2], 100);
+    }
+
+    #[test]
+    fn test_response_time_weights() {
+        let stats = InstanceStats::default();
+        let mut instances = vec![instance(8001), instance(8002), instance(8003)];
+        // 服务端下发的权重不影响结果
+        instances[0].meta.insert("weight".to_string(), 10.into());
+        for _ in 0..MIN_SAMPLES {
+            stats.record("127.0.0.1:8001", true, Duration::from_millis(10));
+            stats.record("127.0.0.1:8002", true, Duration::from_millis(40));
+        }
+
+        let weights = stats.response_time_weights(&instances);
+        // 权重与延迟成反比
+        assert_eq!(weights[0], 100);
+        assert_eq!(weights[1], 25);
+        // 无统计的实例与最快的实例相同
+        assert_eq!(weights[2], 100);
+    }
+}
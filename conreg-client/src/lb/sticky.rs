@@ -0,0 +1,162 @@
+use crate::Instance;
+use crate::lb::{LoadBalance, LoadBalanceError, RandomLoadBalance, stats};
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// 会话绑定的默认空闲过期时间
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// 绑定数超过该值时清理过期的绑定
+const PRUNE_THRESHOLD: usize = 10000;
+
+/// 会话绑定的实例
+#[derive(Debug)]
+struct Pin {
+    /// 实例地址
+    address: String,
+    /// 最后一次使用的时间
+    last_used: Instant,
+}
+
+/// 会话保持负载均衡
+///
+/// 按请求的会话key（如Cookie或请求头中的会话ID）将客户端绑定到首次选中的实例上，
+/// 绑定的实例下线或绑定空闲过期后，随机选择一个新的实例重新绑定。
+/// 请求中没有会话key时随机选择实例，不绑定。
+///
+/// 适用于无法共享会话的有状态服务。与一致性哈希不同，实例上线时已有的会话不会迁移。
+#[derive(Debug)]
+pub struct StickyLoadBalance {
+    /// 绑定的空闲过期时间
+    idle_timeout: Duration,
+    /// 会话绑定，key为(service_id, 会话key)
+    pins: DashMap<(String, String), Pin>,
+    random: RandomLoadBalance,
+}
+
+impl Default for StickyLoadBalance {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDLE_TIMEOUT)
+    }
+}
+
+impl StickyLoadBalance {
+    /// 创建会话保持负载均衡
+    ///
+    /// - idle_timeout：绑定的空闲过期时间，超过该时间未使用的绑定失效
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            pins: Default::default(),
+            random: RandomLoadBalance,
+        }
+    }
+
+    /// 按会话key从给定的实例列表中选择一个实例
+    pub(crate) fn select(
+        &self,
+        service_id: &str,
+        instances: Vec<Instance>,
+        key: Option<&str>,
+    ) -> Result<Instance, LoadBalanceError> {
+        let Some(key) = key else {
+            return self.random.select(service_id, instances);
+        };
+        let pin_key = (service_id.to_string(), key.to_string());
+        if let Some(mut pin) = self.pins.get_mut(&pin_key)
+            && pin.last_used.elapsed() < self.idle_timeout
+            && let Some(instance) = instances
+                .iter()
+                .find(|instance| stats::address(instance) == pin.address)
+        {
+            pin.last_used = Instant::now();
+            return Ok(instance.clone());
+        }
+
+        // 未绑定或绑定的实例已不可用，重新选择并绑定
+        let instance = self.random.select(service_id, instances)?;
+        if self.pins.len() >= PRUNE_THRESHOLD {
+            self.pins
+                .retain(|_, pin| pin.last_used.elapsed() < self.idle_timeout);
+        }
+        self.pins.insert(
+            pin_key,
+            Pin {
+                address: stats::address(&instance),
+                last_used: Instant::now(),
+            },
+        );
+        Ok(instance)
+    }
+}
+
+impl LoadBalance for StickyLoadBalance {
+    /// 未指定会话key，随机选择实例
+    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
+        let instances = self.instances(service_id).await?;
+        self.select(service_id, instances, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instances(ports: &[u16]) -> Vec<Instance> {
+        ports
+            .iter()
+            .map(|port| Instance {
+                ip: "127.0.0.1".to_string(),
+                port: *port,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sticky_load_balance() {
+        let lb = StickyLoadBalance::default();
+        let all = instances(&[1, 2, 3, 4]);
+
+        // 同一会话始终落到同一实例
+        let pinned = lb.select("test", all.clone(), Some("a")).unwrap().port;
+        for _ in 0..20 {
+            assert_eq!(
+                lb.select("test", all.clone(), Some("a")).unwrap().port,
+                pinned
+            );
+        }
+        // 新实例上线不影响已有的会话
+        let mut more = all.clone();
+        more.extend(instances(&[5, 6]));
+        assert_eq!(lb.select("test", more, Some("a")).unwrap().port, pinned);
+
+        // 绑定的实例下线后重新绑定到其他实例
+        let rest = all
+            .into_iter()
+            .filter(|instance| instance.port != pinned)
+            .collect::<Vec<_>>();
+        let repinned = lb.select("test", rest.clone(), Some("a")).unwrap().port;
+        assert_ne!(repinned, pinned);
+        for _ in 0..20 {
+            assert_eq!(
+                lb.select("test", rest.clone(), Some("a")).unwrap().port,
+                repinned
+            );
+        }
+
+        // 没有会话key时随机选择
+        assert!(lb.select("test", rest, None).is_ok());
+        assert!(lb.select("test", vec![], Some("a")).is_err());
+    }
+
+    #[test]
+    fn test_sticky_idle_timeout() {
+        let lb = StickyLoadBalance::new(Duration::ZERO);
+        let all = instances(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        // 绑定立即过期，多次选择会落到不同实例
+        let ports = (0..50)
+            .map(|_| lb.select("test", all.clone(), Some("a")).unwrap().port)
+            .collect::<std::collections::HashSet<_>>();
+        assert!(ports.len() > 1);
+    }
+}
@@ -1,19 +1,40 @@
 use crate::Instance;
-use crate::lb::{LoadBalance, LoadBalanceError};
+use crate::lb::{InstanceStats, LoadBalance, LoadBalanceError};
+use std::sync::Arc;
 
 #[derive(Debug, Default)]
-pub struct WeightRandomLoadBalance {}
+pub struct WeightRandomLoadBalance {
+    /// 实例请求统计，设置后按统计自适应调整权重
+    stats: Option<Arc<InstanceStats>>,
+}
 
 impl WeightRandomLoadBalance {
     pub fn new() -> Self {
         Self::default()
     }
-}
 
-impl LoadBalance for WeightRandomLoadBalance {
-    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
-        let instances = self.instances(service_id).await?;
+    /// 使用实例请求统计创建，按各实例的成功率和延迟自适应调整权重
+    pub fn with_stats(stats: Arc<InstanceStats>) -> Self {
+        Self { stats: Some(stats) }
+    }
 
+    /// 获取实例权重，返回值与instances一一对应
+    fn weights(&self, instances: &[Instance]) -> Vec<u64> {
+        match &self.stats {
+            Some(stats) => stats.effective_weights(instances),
+            None => instances
+                .iter()
+                .map(|instance| instance.get_weight())
+                .collect(),
+        }
+    }
+
+    /// 从给定的实例列表中选择一个实例
+    pub(crate) fn select(
+        &self,
+        service_id: &str,
+        instances: Vec<Instance>,
+    ) -> Result<Instance, LoadBalanceError> {
         if instances.is_empty() {
             return Err(LoadBalanceError::NoAvailableInstance(
                 service_id.to_string(),
@@ -24,15 +45,15 @@ impl LoadBalance for WeightRandomLoadBalance {
         }
 
         // 计算总权重
-        let total_weight: u64 = instances.iter().map(|instance| instance.get_weight()).sum();
+        let weights = self.weights(&instances);
+        let total_weight: u64 = weights.iter().sum();
 
         // 生成0到总权重之间的随机数
         let random_weight: u64 = fastrand::u64(0..total_weight);
 
         // 根据随机数和权重选择实例
         let mut current_weight = 0;
-        for instance in &instances {
-            let weight = instance.get_weight();
+        for (instance, weight) in instances.iter().zip(weights) {
             current_weight += weight;
             if random_weight < current_weight {
                 return Ok(instance.clone());
@@ -44,13 +65,20 @@ impl LoadBalance for WeightRandomLoadBalance {
     }
 }
 
+impl LoadBalance for WeightRandomLoadBalance {
+    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
+        let instances = self.instances(service_id).await?;
+        self.select(service_id, instances)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::init;
+    use crate::try_init;
     #[tokio::test]
     async fn test_random_weight_load_balance() {
-        let _ = init().await;
+        let _ = try_init().await;
         let lb = WeightRandomLoadBalance::default();
         let instances = lb
             .get_instance("conreg_client-ecdb9f5551f4f00c")
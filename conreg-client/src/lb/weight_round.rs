@@ -1,24 +1,47 @@
 use crate::Instance;
-use crate::lb::{LoadBalance, LoadBalanceError};
+use crate::lb::{InstanceStats, LoadBalance, LoadBalanceError};
 use dashmap::DashMap;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug, Default)]
 pub struct WeightRoundRobinLoadBalance {
     /// 每个服务的当前权重索引
     current_weight: DashMap<String, AtomicUsize>,
+    /// 实例请求统计，设置后按统计自适应调整权重
+    stats: Option<Arc<InstanceStats>>,
 }
 
 impl WeightRoundRobinLoadBalance {
     pub fn new() -> Self {
         Self::default()
     }
-}
 
-impl LoadBalance for WeightRoundRobinLoadBalance {
-    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
-        let instances = self.instances(service_id).await?;
+    /// 使用实例请求统计创建，按各实例的成功率和延迟自适应调整权重
+    pub fn with_stats(stats: Arc<InstanceStats>) -> Self {
+        Self {
+            stats: Some(stats),
+            ..Default::default()
+        }
+    }
 
+    /// 获取实例权重，返回值与instances一一对应
+    fn weights(&self, instances: &[Instance]) -> Vec<u64> {
+        match &self.stats {
+            Some(stats) => stats.effective_weights(instances),
+            None => instances
+                .iter()
+                .map(|instance| instance.get_weight())
+                .collect(),
+        }
+    }
+
+    /// 从给定的实例列表中选择一个实例
+    pub(crate) fn select(
+        &self,
+        service_id: &str,
+        instances: Vec<Instance>,
+    ) -> Result<Instance, LoadBalanceError> {
         if instances.is_empty() {
             return Err(LoadBalanceError::NoAvailableInstance(
                 service_id.to_string(),
@@ -29,7 +52,8 @@ impl LoadBalance for WeightRoundRobinLoadBalance {
         }
 
         // 计算总权重
-        let total_weight: u64 = instances.iter().map(|instance| instance.get_weight()).sum();
+        let weights = self.weights(&instances);
+        let total_weight: u64 = weights.iter().sum();
 
         let mut current_pos = self
             .current_weight
@@ -41,8 +65,7 @@ impl LoadBalance for WeightRoundRobinLoadBalance {
 
         // 根据权重选择实例
         let mut current_weight = 0;
-        for instance in &instances {
-            let weight = instance.get_weight();
+        for (instance, weight) in instances.iter().zip(weights) {
             current_weight += weight;
             if current_pos < current_weight as usize {
                 return Ok(instance.clone());
@@ -54,13 +77,20 @@ impl LoadBalance for WeightRoundRobinLoadBalance {
     }
 }
 
+impl LoadBalance for WeightRoundRobinLoadBalance {
+    async fn get_instance(&self, service_id: &str) -> Result<Instance, LoadBalanceError> {
+        let instances = self.instances(service_id).await?;
+        self.select(service_id, instances)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::init;
+    use crate::try_init;
     #[tokio::test]
     async fn test_weight_load_balance() {
-        let _ = init().await;
+        let _ = try_init().await;
         let lb = WeightRoundRobinLoadBalance::default();
         for _ in 0..20 {
             let instances = lb
@@ -0,0 +1,138 @@
+//! # 就近选择实例
+//! 实例通过元数据中的`zone`和`region`标识所在的可用区和地域，开启就近选择后，
+//! 负载策略只在以下范围中的第一个满足条件的范围内选择实例：
+//!
+//! 1. 与本实例同一可用区的实例
+//! 2. 与本实例同一地域的实例，需配置地域
+//! 3. 所有实例
+//!
+//! 范围内的可用实例数不少于`min_instances`时视为满足条件，
+//! 注册中心只返回健康的实例，因此本可用区的实例不健康时会溢出到其他可用区。
+
+use crate::Instance;
+use crate::conf::DiscoveryConfig;
+
+/// Zone affinity of load balancers
+///
+/// Prefers instances in the same zone, then in the same region, then anywhere, so that
+/// cross-zone traffic only happens when the local pool is too small.
+///
+/// ```rust
+/// let affinity = ZoneAffinity::new("cn-east-1a")
+///     .region("cn-east-1")
+///     .min_instances(2);
+/// client.set_zone_affinity("your_service_id", Some(affinity));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneAffinity {
+    zone: String,
+    region: Option<String>,
+    min_instances: usize,
+}
+
+impl ZoneAffinity {
+    /// Prefer instances in `zone`
+    pub fn new(zone: impl Into<String>) -> Self {
+        Self {
+            zone: zone.into(),
+            region: None,
+            min_instances: 1,
+        }
+    }
+
+    /// Spill over to instances in `region` before all instances
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Minimum number of available instances in the zone (or region) to stay there, default: 1
+    pub fn min_instances(mut self, min_instances: usize) -> Self {
+        self.min_instances = min_instances.max(1);
+        self
+    }
+
+    /// 从注册中心配置创建，未开启就近选择或未配置可用区时为空
+    pub(crate) fn from_config(config: &DiscoveryConfig) -> Option<Self> {
+        if !config.zone_affinity {
+            return None;
+        }
+        let zone = config.zone.as_ref()?;
+        let mut affinity = Self::new(zone).min_instances(config.zone_min_instances);
+        affinity.region = config.region.clone();
+        Some(affinity)
+    }
+
+    /// 注册中心配置中的就近选择设置，未初始化或未开启时为空
+    pub(crate) fn configured() -> Option<Self> {
+        crate::DISCOVERY.get()?.zone_affinity()
+    }
+
+    /// 选出参与负载的实例
+    pub(crate) fn select(&self, instances: Vec<Instance>) -> Vec<Instance> {
+        let in_zone = |instance: &Instance| instance.zone() == Some(self.zone.as_str());
+        let in_region = |instance: &Instance| {
+            self.region.is_some() && instance.region() == self.region.as_deref()
+        };
+        for in_scope in [&in_zone as &dyn Fn(&Instance) -> bool, &in_region] {
+            if instances
+                .iter()
+                .filter(|instance| in_scope(instance))
+                .count()
+                >= self.min_instances
+            {
+                return instances
+                    .into_iter()
+                    .filter(|instance| in_scope(instance))
+                    .collect();
+            }
+        }
+        instances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml::Value;
+    use std::collections::HashMap;
+
+    fn instance(port: u16, zone: &str, region: &str) -> Instance {
+        Instance {
+            port,
+            meta: HashMap::from([
+                ("zone".to_string(), Value::from(zone)),
+                ("region".to_string(), Value::from(region)),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn ports(instances: Vec<Instance>) -> Vec<u16> {
+        instances.iter().map(|instance| instance.port).collect()
+    }
+
+    #[test]
+    fn test_zone_affinity() {
+        let instances = vec![
+            instance(1, "a", "east"),
+            instance(2, "b", "east"),
+            instance(3, "a", "east"),
+            instance(4, "c", "west"),
+        ];
+        let affinity = ZoneAffinity::new("a").region("east");
+        assert_eq!(ports(affinity.select(instances.clone())), vec![1, 3]);
+
+        // 本可用区实例数不足时溢出到同一地域
+        let affinity = affinity.min_instances(3);
+        assert_eq!(ports(affinity.select(instances.clone())), vec![1, 2, 3]);
+
+        // 同一地域也不足时使用所有实例
+        let affinity = ZoneAffinity::new("c").region("west").min_instances(2);
+        assert_eq!(ports(affinity.select(instances.clone())), vec![1, 2, 3, 4]);
+
+        // 未配置地域时直接使用所有实例
+        let affinity = ZoneAffinity::new("d");
+        assert_eq!(ports(affinity.select(instances)), vec![1, 2, 3, 4]);
+    }
+}
@@ -10,6 +10,8 @@
 //! - Service Discovery: Register and discover service instances
 //! - Load Balancing: Multiple load balancing strategies (Random, Round-Robin, Weighted, etc.)
 //! - Declarative HTTP Client: Feign-like declarative microservice calling (requires `feign` feature)
+//! - gRPC Transport: Streamed config watch and heartbeats instead of HTTP polling (requires `grpc` feature)
+//! - Tower Middleware: Load balancing for hyper, axum and tonic clients (requires `tower` feature)
 //!
 //! # Quick Start
 //!
@@ -23,8 +25,10 @@
 //!   service-id: test
 //!   # Client configuration, this information will be submitted to the registry as basic information of the service instance
 //!   client:
-//!     # Listening address
+//!     # Listening address, or `auto` to detect the outbound IP on startup
 //!     address: 127.0.0.1
+//!     # Optional, with `address: auto`, pick the address from a CIDR or an interface name
+//!     # prefer-network: 10.0.0.0/8
 //!     # Port
 //!     port: 8000
 //!   # Configuration center configuration
@@ -36,6 +40,19 @@
 //!     config-ids:
 //!       - test.yaml
 //!     auth-token: your_token
+//!     # Optional, polling interval in seconds for specific configurations.
+//!     # Use it when long-poll connections are blocked by proxies.
+//!     # poll-interval:
+//!     #   test.yaml: 10
+//!     # Optional, label selector for configuration variants, e.g. per environment or region.
+//!     # labels:
+//!     #   env: prod
+//!     #   region: eu
+//!     # Optional, local snapshot of the configurations.
+//!     # If the configuration center is unreachable during startup, the last saved snapshot is used.
+//!     # snapshot-path: .conreg/config-snapshot.yaml
+//!     # Optional, 32 bytes hex key to decrypt values stored as `DEC(...)`.
+//!     # decrypt-key: ${CONREG_DECRYPT_KEY}
 //!   # Registry configuration
 //!   discovery:
 //!     # Registry address
@@ -44,6 +61,40 @@
 //!       - 127.0.0.1:8001
 //!       - 127.0.0.1:8002
 //!     auth-token: your_token
+//!     # Optional, discover all server nodes from the cluster membership.
+//!     # When enabled, `server-addr` only needs a single seed address,
+//!     # and added or removed nodes are picked up automatically.
+//!     # discover-servers: true
+//!     # Optional, heartbeat and instance refresh intervals in seconds, default: 5 and 30.
+//!     # heartbeat-interval: 5
+//!     # fetch-interval: 30
+//!     # Optional, timeout of a heartbeat request, and retries of failed heartbeats and
+//!     # instance refreshes with an exponential backoff starting at `retry-backoff-ms`.
+//!     # heartbeat-timeout-ms: 1000
+//!     # max-retries: 2
+//!     # retry-backoff-ms: 500
+//!     # Optional, retries of a failed registration during initialization, with the backoff capped
+//!     # at `register-max-backoff-ms`. With `fail-open`, the application still starts when the
+//!     # registration fails, and the registration is retried in the background.
+//!     # register-retries: 5
+//!     # register-max-backoff-ms: 30000
+//!     # fail-open: true
+//!     # Optional, send heartbeats over UDP, requires the server started with `--enable-udp-heartbeat`.
+//!     # udp-heartbeat: true
+//!     # Optional, zone and region of this instance, registered as metadata. With `zone-affinity`,
+//!     # load balancers prefer instances in the same zone, then the same region, and spill over when
+//!     # fewer than `zone-min-instances` instances are available there.
+//!     # zone: cn-east-1a
+//!     # region: cn-east-1
+//!     # zone-affinity: true
+//!     # zone-min-instances: 2
+//!   # Optional, HTTP client configuration for networks where the system proxy and certificates do not apply
+//!   # http:
+//!   #   proxy: http://proxy.example.com:3128
+//!   #   no-proxy: localhost,10.0.0.0/8
+//!   #   # Extra root CA certificates in PEM format, trusted in addition to the built-in ones
+//!   #   ca-certs:
+//!   #     - /etc/ssl/corp-ca.pem
 //! ```
 //!
 //! Then, initialize in the `main` function:
@@ -52,7 +103,7 @@
 //! #[tokio::main]
 //! async fn main() {
 //!     // Initialization
-//!     init().await;
+//!     try_init().await.unwrap();
 //!     // Get configuration item
 //!     println!("{:?}", AppConfig::get::<String>("name"));
 //!     // Get service instances
@@ -61,6 +112,21 @@
 //! }
 //! ```
 //!
+//! ## Handle Initialization Errors
+//!
+//! The `try_init*` functions return an error if the servers are unreachable or the bootstrap
+//! configuration is invalid, so the application can decide how to proceed, e.g. fall back to
+//! local configurations. The `init*` functions that exit the process on failure are deprecated.
+//!
+//! ```rust
+//! #[tokio::main]
+//! async fn main() {
+//!     if let Err(e) = try_init().await {
+//!         eprintln!("conreg init failed, using local configurations: {}", e);
+//!     }
+//! }
+//! ```
+//!
 //! ## Namespace
 //!
 //! Conreg uses namespaces to isolate configurations and services. The default namespace is `public`.
@@ -74,7 +140,7 @@
 //! ```rust
 //! #[tokio::main]
 //! async fn main() {
-//!     init_with(
+//!     try_init_with(
 //!         ConRegConfigBuilder::default()
 //!             .config(
 //!                 ConfigConfigBuilder::default()
@@ -87,7 +153,8 @@
 //!             .build()
 //!             .unwrap(),
 //!     )
-//!         .await;
+//!         .await
+//!         .unwrap();
 //!     println!("{:?}", AppConfig::get::<String>("name"));
 //!     println!("{:?}", AppConfig::get::<u32>("age"));
 //! }
@@ -106,19 +173,192 @@
 //!       - your_config.yaml
 //! ```
 //!
-//! Then call the `init` method to initialize and get the configuration content.
+//! Values can reference environment variables with `${NAME}` or `${NAME:default}` placeholders,
+//! so the same file can be used in every environment:
+//!
+//! ```yaml
+//! conreg:
+//!   config:
+//!     server-addr: ${CONREG_SERVER:127.0.0.1:8000}
+//!     namespace: ${CONREG_NAMESPACE:public}
+//!     auth-token: ${CONREG_TOKEN:}
+//! ```
+//!
+//! Then call the `try_init` method to initialize and get the configuration content.
 //!
 //! ```rust
 //! #[tokio::main]
 //! async fn main() {
-//!     init().await;
+//!     try_init().await.unwrap();
 //!     // Or specify the configuration file path
-//!     // init_from_file("config.yaml").await;
+//!     // try_init_from_file("config.yaml").await.unwrap();
 //!     println!("{:?}", AppConfig::get::<String>("name"));
 //!     println!("{:?}", AppConfig::get::<u32>("age"));
 //! }
 //! ```
 //!
+//! ### Multiple Configuration Sources
+//!
+//! `config` can also be a list of sources, each with its own `server-addr`, `namespace`,
+//! `auth-token` and `config-ids`, e.g. configurations shared by all services plus the
+//! configurations of the application. The sources are merged in order, the latter source
+//! overwrites the same keys of the previous ones, and changes of every source are watched:
+//!
+//! ```yaml
+//! conreg:
+//!   config:
+//!     - server-addr: 127.0.0.1:8000
+//!       namespace: shared
+//!       config-ids:
+//!         - common.yaml
+//!     - server-addr: 127.0.0.1:8000
+//!       namespace: my-app
+//!       auth-token: your_token
+//!       config-ids:
+//!         - application.yaml
+//! ```
+//!
+//! With `ConRegConfigBuilder`, pass a `Vec<ConfigConfig>` to `config`.
+//!
+//! ### Local Overrides
+//!
+//! For development only, a local file can override configuration keys without publishing
+//! them to the shared configuration center. It is merged over the configurations of all
+//! sources, read once on startup and ignored if it does not exist:
+//!
+//! ```yaml
+//! conreg:
+//!   config:
+//!     server-addr: 127.0.0.1:8000
+//!     config-ids:
+//!       - application.yaml
+//!   # Enabled only when CONREG_OVERRIDE_FILE is set, e.g. CONREG_OVERRIDE_FILE=conreg.override.yaml
+//!   override-file: ${CONREG_OVERRIDE_FILE:}
+//! ```
+//!
+//! The format is detected from the file extension, e.g. `conreg.override.yaml`:
+//!
+//! ```yaml
+//! db:
+//!   host: localhost
+//! ```
+//!
+//! Do not enable it in production, as local values silently shadow the configuration center.
+//!
+//! ### Encrypted Values
+//!
+//! Secrets can be stored encrypted in the configuration center as `DEC(...)` values, and are
+//! decrypted when the configurations are loaded, so `AppConfig::get` returns the plain value.
+//! Set a 32 bytes key as 64 hex characters, e.g. generated by `openssl rand -hex 32`,
+//! preferably injected from the environment:
+//!
+//! ```yaml
+//! conreg:
+//!   config:
+//!     server-addr: 127.0.0.1:8000
+//!     config-ids:
+//!       - your_config.yaml
+//!     decrypt-key: ${CONREG_DECRYPT_KEY}
+//! ```
+//!
+//! Encrypt values with the same key using `AppConfig::encrypt`, or with `POST /api/system/encrypt` of a server
+//! started with the same `--decrypt-key`, and store them in the configuration:
+//!
+//! ```yaml
+//! db:
+//!   password: DEC(3vQB7B6MzqkMx7GX4v...)
+//! ```
+//!
+//! The local snapshot keeps the encrypted values. If a value cannot be decrypted, initialization fails.
+//!
+//! ### Refresh Configuration Immediately
+//!
+//! Configurations are updated automatically when they change on the server. To re-fetch them immediately,
+//! for example right after a deployment script publishes configurations, call `refresh`:
+//!
+//! ```rust
+//! #[tokio::main]
+//! async fn main() {
+//!     try_init().await.unwrap();
+//!     // Refresh all configurations
+//!     AppConfig::refresh().await.unwrap();
+//!     // Or refresh only one of them
+//!     AppConfig::refresh_one("test.yaml").await.unwrap();
+//! }
+//! ```
+//!
+//! ### Initialize from Server
+//!
+//! Bootstrap profiles can also be managed on the server per service, so that `bootstrap.yaml`
+//! does not need to be baked into every image:
+//!
+//! ```rust
+//! #[tokio::main]
+//! async fn main() {
+//!     try_init_from_url("http://127.0.0.1:8000/api/bootstrap/your_service_id?namespace_id=public")
+//!         .await
+//!         .unwrap();
+//!     // Or with a namespace token and the client address
+//!     // try_init_from_url_with(url, Some("token"), client).await.unwrap();
+//!     println!("{:?}", AppConfig::get::<String>("name"));
+//! }
+//! ```
+//!
+//! ### Bind to a Struct
+//!
+//! The merged configuration can be deserialized into a struct. `bind_watch` returns a
+//! `tokio::sync::watch::Receiver` that is updated automatically when the configuration changes:
+//!
+//! ```rust
+//! #[derive(Deserialize)]
+//! struct Settings {
+//!     name: String,
+//!     age: u32,
+//! }
+//!
+//! let settings = AppConfig::bind_watch::<Settings>().unwrap();
+//! println!("{}", settings.borrow().name);
+//! ```
+//!
+//! ### Dump the Effective Configuration
+//!
+//! `AppConfig::dump` returns the merged configuration the instance is using as YAML, with values of
+//! keys such as `password`, `secret` or `token` masked. `AppConfig::dump_response` wraps it in an
+//! `http::Response`, which can be mounted as a debug endpoint:
+//!
+//! ```rust
+//! println!("{}", AppConfig::dump().unwrap());
+//! // axum
+//! let app = Router::new().route("/debug/config", get(|| async { AppConfig::dump_response() }));
+//! ```
+//!
+//! The same patterns mask values in logs and in error messages, such as a failure to deserialize a
+//! configuration. Set `mask-patterns` to choose them, an empty list masks nothing:
+//!
+//! ```yaml
+//! conreg:
+//!   mask-patterns: [ password, secret, token, dsn ]
+//! ```
+//!
+//! ### Scoped Views
+//!
+//! `AppConfig::scoped` returns a view of the configuration under a key prefix, so each module
+//! only deals with its own section. Keys are relative to the prefix, and the section can be
+//! bound to a struct:
+//!
+//! ```rust
+//! #[derive(Deserialize)]
+//! struct Database {
+//!     url: String,
+//!     pool_size: u32,
+//! }
+//!
+//! let database = AppConfig::scoped("database");
+//! // Resolves `database.url`
+//! println!("{:?}", database.get::<String>("url"));
+//! let settings = database.bind_watch::<Database>().unwrap();
+//! ```
+//!
 //! ## Registry Center
 //!
 //! Used for service registration and discovery.
@@ -146,7 +386,7 @@
 //!         .build()
 //!         .unwrap();
 //!     let service_id = config.service_id.clone();
-//!     init_with(config).await;
+//!     try_init_with(config).await.unwrap();
 //!     let instances = AppDiscovery::get_instances(&service_id).await.unwrap();
 //!     println!("service instances: {:?}", instances);
 //! }
@@ -173,16 +413,70 @@
 //! ```rust
 //! #[tokio::main]
 //! async fn main() {
-//!     init().await;
+//!     try_init().await.unwrap();
 //!     // Or specify the configuration file path
-//!     // init_from_file("config.yaml").await;
-//!     init_with(config).await;
+//!     // try_init_from_file("config.yaml").await.unwrap();
 //!     let service_id = "your_service_id";
 //!     let instances = AppDiscovery::get_instances(service_id).await.unwrap();
 //!     println!("service instances: {:?}", instances);
 //! }
 //! ```
 //!
+//! ### Wait for Dependencies
+//!
+//! When a service depends on other services, it can wait until enough instances are available during startup:
+//!
+//! ```rust
+//! // Wait up to 60 seconds for at least 2 instances of user-service
+//! let instances = AppDiscovery::wait_for_instances("user-service", 2, Duration::from_secs(60)).await?;
+//! ```
+//!
+//! ### Subscribe to Instance Changes
+//!
+//! Instances are synced every 30 seconds by default. Subscribe to a service to be told about changes as soon
+//! as they happen, e.g. to rebuild a connection pool when an instance is deregistered:
+//!
+//! ```rust
+//! let subscription = AppDiscovery::subscribe("user-service", |instances| {
+//!     println!("user-service has {} instances", instances.len());
+//! })?;
+//! ```
+//!
+//! ### Graceful Shutdown
+//!
+//! Deregister the instance before the process exits, so other services stop routing traffic to it immediately
+//! instead of waiting for heartbeat timeouts:
+//!
+//! ```rust
+//! // In your own shutdown handler
+//! conreg_client::shutdown().await?;
+//!
+//! // Or let conreg-client handle Ctrl+C and SIGTERM, deregister and exit
+//! conreg_client::shutdown_on_signal();
+//! ```
+//!
+//! # gRPC Protocol
+//!
+//! By default the client communicates with the server over HTTP. Enable the `grpc` feature and set
+//! `protocol: grpc` to fetch configurations, register instances and query instances over gRPC.
+//! Configuration changes are pushed over a server stream instead of long polling, and heartbeats are
+//! sent over a single bidirectional stream instead of a request each. Other requests, such as config
+//! reports and deregistration, still use HTTP.
+//!
+//! The server must be started with `--enable-grpc`, it listens for gRPC on the HTTP port + 1000,
+//! `server-addr` still points to the HTTP port:
+//!
+//! ```yaml
+//! conreg:
+//!   protocol: grpc
+//!   config:
+//!     server-addr: 127.0.0.1:8000
+//!     config-ids:
+//!       - test.yaml
+//!   discovery:
+//!     server-addr: 127.0.0.1:8000
+//! ```
+//!
 //! # Load Balancing
 //!
 //! conreg-client provides a load balancing client based on `reqwest`, supporting custom protocol requests in the format `lb://service_id`.
@@ -190,11 +484,25 @@
 //!
 //! # Listen for Configuration Changes
 //!
-//! Add a handler function for the specified config_id, which will be called when the configuration changes.
+//! Add a handler for the specified config_id, which will be called when the configuration changes.
+//! The handler can be a closure capturing state, and the returned handle removes it later.
 //!
 //! ```rust
-//! AppConfig::add_listener("test.yaml", |config| {
-//! println!("Config changed, new config: {:?}", config);
+//! let (tx, rx) = std::sync::mpsc::channel();
+//! let handle = AppConfig::add_listener("test.yaml", move |config| {
+//!     println!("Config changed, new config: {:?}", config);
+//!     let _ = tx.send(());
+//! });
+//! // Remove the listener
+//! handle.remove();
+//! ```
+//!
+//! To listen for a single key, or all keys under a prefix, use `add_key_listener`.
+//! The handler is only called when that key changes, with both the old and new values.
+//!
+//! ```rust
+//! let handle = AppConfig::add_key_listener("db.pool.size", |old, new| {
+//!     println!("Pool size changed from {:?} to {:?}", old, new);
 //! });
 //! ```
 //!
@@ -216,23 +524,33 @@
 //! let user = client.get_user(1).await?;
 //! ```
 
-use crate::conf::{ConRegConfig, ConRegConfigWrapper};
-use crate::config::Configs;
+use crate::conf::{BootstrapProfile, ClientConfig, ConRegConfig, ConRegConfigWrapper};
+pub use crate::config::{ConfigFormat, ListenerHandle};
+pub use crate::discovery::{DiscoveryStats, EvictionListenerHandle, ServiceStats, Subscription};
+use crate::config::{ConfigStore, Configs};
 use crate::discovery::{Discovery, DiscoveryClient};
-pub use crate::protocol::Instance;
+pub use crate::protocol::{EvictionNotice, Instance};
 use anyhow::bail;
 use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::exit;
 use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
 
 pub mod conf;
 mod config;
 mod discovery;
+mod enc_dec;
+#[cfg(feature = "grpc")]
+mod grpc;
 pub mod lb;
 mod network;
 mod protocol;
+mod snapshot;
+mod timer;
+mod udp;
 mod utils;
 
 #[cfg(feature = "feign")]
@@ -274,8 +592,10 @@ impl From<crate::lb::LoadBalanceError> for FeignError {
 
 struct Conreg;
 
-/// Store configuration content
-static CONFIGS: OnceLock<Arc<RwLock<Configs>>> = OnceLock::new();
+/// Store configuration content of all configuration sources
+static CONFIGS: OnceLock<Arc<RwLock<ConfigStore>>> = OnceLock::new();
+/// Global instances for configuration clients, one per configuration source, used to refresh configurations manually
+static CONFIG_CLIENTS: OnceLock<Vec<config::ConfigClient>> = OnceLock::new();
 /// Global instance for service discovery
 static DISCOVERY: OnceLock<Discovery> = OnceLock::new();
 /// Request header for namespace authentication
@@ -293,20 +613,18 @@ impl Conreg {
         }
         let s = match std::fs::read_to_string(&file) {
             Ok(s) => s,
-            Err(e) => {
-                log::error!("no bootstrap.yaml found, {}", e);
-                exit(1);
-            }
+            Err(e) => bail!("no bootstrap.yaml found, {}", e),
         };
 
         log::info!("loaded bootstrap config from {}", file.display());
 
+        let s = match utils::resolve_placeholders(&s, |name| std::env::var(name).ok()) {
+            Ok(s) => s,
+            Err(e) => bail!("resolve bootstrap.yaml placeholders failed, {}", e),
+        };
         let config = match serde_yaml::from_str::<ConRegConfigWrapper>(&s) {
             Ok(config) => config,
-            Err(e) => {
-                log::error!("parse bootstrap.yaml failed, {}", e);
-                exit(1);
-            }
+            Err(e) => bail!("parse bootstrap.yaml failed, {}", e),
         };
 
         Self::init_with(&config.conreg).await?;
@@ -315,24 +633,126 @@ impl Conreg {
         Ok(())
     }
 
+    /// Initialize from a bootstrap profile stored on the server
+    async fn init_from_url(
+        url: &str,
+        auth_token: Option<&str>,
+        client: ClientConfig,
+    ) -> anyhow::Result<()> {
+        #[cfg(feature = "tracing")]
+        utils::init_log();
+
+        let parsed = reqwest::Url::parse(url)?;
+        let Some(host) = parsed.host_str() else {
+            bail!("invalid bootstrap url: {}", url);
+        };
+        let server = match parsed.port_or_known_default() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+
+        let headers = auth_token.map(|token| vec![(NS_TOKEN_HEADER, token)]);
+        let profile = network::HTTP
+            .get::<Option<BootstrapProfile>>(url, HashMap::<String, String>::new(), headers)
+            .await?;
+        let Some(profile) = profile else {
+            bail!("no bootstrap profile found at {}", url);
+        };
+
+        log::info!("loaded bootstrap config from {}", url);
+
+        Self::init_with(&profile.into_config(&server, auth_token, client)).await?;
+
+        log::info!("conreg init completed");
+        Ok(())
+    }
+
     async fn init_with(config: &ConRegConfig) -> anyhow::Result<()> {
         #[cfg(feature = "tracing")]
         utils::init_log();
 
-        if config.config.is_some() {
-            let config_client = config::ConfigClient::new(config);
-            let configs = config_client.load().await?;
-            CONFIGS.set(Arc::new(RwLock::new(configs))).map_err(|_| {
-                anyhow::anyhow!(
-                    "config has already been initialized, please do not initialize repeatedly"
-                )
-            })?;
+        #[cfg(not(feature = "grpc"))]
+        if config.protocol == conf::Protocol::Grpc {
+            bail!("protocol grpc requires the `grpc` feature of conreg-client");
+        }
+        config::set_mask_patterns(config.mask_patterns.as_deref());
+        network::HTTP.configure(&config.http)?;
+
+        let mut config = config.clone();
+        let target = config
+            .discovery
+            .as_ref()
+            .and_then(|discovery| discovery.server_addr.addresses().into_iter().next());
+        config.client.resolve_address(target.as_deref())?;
+        let config = &config;
+
+        if let Some(sources) = &config.config {
+            for config_config in sources.as_slice() {
+                if config_config.discover_servers {
+                    config_config.server_addr.discover_nodes().await;
+                }
+            }
+        }
+        if let Some(discovery_config) = &config.discovery
+            && discovery_config.discover_servers
+        {
+            discovery_config.server_addr.discover_nodes().await;
         }
 
-        if config.discovery.is_some() {
+        if let Some(sources) = &config.config {
+            let mut config_clients = vec![];
+            let mut configs = vec![];
+            for source in 0..sources.as_slice().len() {
+                let config_client = config::ConfigClient::new(config, source);
+                configs.push(config_client.load().await?);
+                config_clients.push(config_client);
+            }
+            // 本地覆盖文件优先级最高，排在所有配置源之后
+            if let Some(path) = config
+                .override_file
+                .as_ref()
+                .filter(|path| !path.as_os_str().is_empty())
+            {
+                match Configs::from_override_file(path)? {
+                    Some(override_configs) => {
+                        log::warn!(
+                            "config overridden by local file {}, for development only",
+                            path.display()
+                        );
+                        configs.push(override_configs);
+                    }
+                    None => {
+                        log::info!("config override file {} not found, skipped", path.display())
+                    }
+                }
+            }
+            CONFIGS
+                .set(Arc::new(RwLock::new(ConfigStore::new(configs)?)))
+                .map_err(|_| {
+                    anyhow::anyhow!(
+                        "config has already been initialized, please do not initialize repeatedly"
+                    )
+                })?;
+            for config_client in config_clients.iter() {
+                config_client.report().await;
+            }
+            let _ = CONFIG_CLIENTS.set(config_clients);
+        }
+
+        if let Some(discovery_config) = &config.discovery {
             let discovery_client = DiscoveryClient::new(config);
-            discovery_client.register().await?;
-            let discovery = Discovery::new(discovery_client).await;
+            let registered = match discovery_client.register_with_retry().await {
+                Ok(_) => true,
+                Err(e) if discovery_config.fail_open => {
+                    log::warn!(
+                        "register error: {}, the application starts and registration is retried in the background",
+                        e
+                    );
+                    false
+                }
+                Err(e) => return Err(e),
+            };
+            let discovery = Discovery::new(discovery_client, registered).await;
             DISCOVERY.set(discovery).map_err(|_| {
                 anyhow::anyhow!(
                     "discovery has already been initialized, please do not initialize repeatedly"
@@ -345,52 +765,258 @@ impl Conreg {
 }
 
 /// Initialize configuration center and registry center
-pub async fn init() {
-    match Conreg::init(None).await {
-        Ok(_) => {}
-        Err(e) => {
-            log::error!("conreg init failed: {}", e);
-            exit(1);
-        }
-    };
+///
+/// Returns an error instead of exiting the process if initialization fails,
+/// so that the caller can decide how to handle it, e.g. fall back to local configurations.
+pub async fn try_init() -> anyhow::Result<()> {
+    Conreg::init(None).await
 }
 
 /// Initialize configuration center and registry center from configuration file
-pub async fn init_from_file(path: impl Into<PathBuf>) {
-    match Conreg::init(Some(path.into())).await {
-        Ok(_) => {}
-        Err(e) => {
-            log::error!("conreg init failed: {}", e);
-            exit(1);
-        }
-    };
+///
+/// Returns an error instead of exiting the process if initialization fails.
+pub async fn try_init_from_file(path: impl Into<PathBuf>) -> anyhow::Result<()> {
+    Conreg::init(Some(path.into())).await
+}
+
+/// Initialize configuration center and registry center from a bootstrap profile stored on the server
+///
+/// The profile is managed centrally on the server per service, so `bootstrap.yaml` no longer needs
+/// to be baked into every image, e.g. `try_init_from_url("http://127.0.0.1:8000/api/bootstrap/my-service?namespace_id=public").await`.
+///
+/// The server that serves the profile is used as the config and registry center unless the profile
+/// specifies `server-addr`. Use [`try_init_from_url_with`] to pass a namespace token or the client address.
+pub async fn try_init_from_url(url: &str) -> anyhow::Result<()> {
+    try_init_from_url_with(url, None, ClientConfig::default()).await
+}
+
+/// Initialize from a bootstrap profile stored on the server, with namespace token and client configuration
+///
+/// - auth_token: namespace token used to fetch the profile, also used by the clients if the profile has no `auth-token`
+/// - client: address of this instance used for service registration
+pub async fn try_init_from_url_with(
+    url: &str,
+    auth_token: Option<&str>,
+    client: ClientConfig,
+) -> anyhow::Result<()> {
+    Conreg::init_from_url(url, auth_token, client).await
 }
 
 /// Initialize from custom configuration
+///
+/// Returns an error instead of exiting the process if initialization fails.
+pub async fn try_init_with(config: ConRegConfig) -> anyhow::Result<()> {
+    Conreg::init_with(&config).await
+}
+
+/// 初始化失败时记录日志并退出进程
+fn exit_on_error(result: anyhow::Result<()>) {
+    if let Err(e) = result {
+        log::error!("conreg init failed: {}", e);
+        exit(1);
+    }
+}
+
+/// Initialize configuration center and registry center, exit the process if failed
+#[deprecated(note = "exits the process on failure, use `try_init` instead")]
+pub async fn init() {
+    exit_on_error(try_init().await);
+}
+
+/// Initialize configuration center and registry center from configuration file, exit the process if failed
+#[deprecated(note = "exits the process on failure, use `try_init_from_file` instead")]
+pub async fn init_from_file(path: impl Into<PathBuf>) {
+    exit_on_error(try_init_from_file(path).await);
+}
+
+/// Initialize from a bootstrap profile stored on the server, exit the process if failed
+#[deprecated(note = "exits the process on failure, use `try_init_from_url` instead")]
+pub async fn init_from_url(url: &str) {
+    exit_on_error(try_init_from_url(url).await);
+}
+
+/// Initialize from a bootstrap profile stored on the server, with namespace token and client configuration,
+/// exit the process if failed
+#[deprecated(note = "exits the process on failure, use `try_init_from_url_with` instead")]
+pub async fn init_from_url_with(url: &str, auth_token: Option<&str>, client: ClientConfig) {
+    exit_on_error(try_init_from_url_with(url, auth_token, client).await);
+}
+
+/// Initialize from custom configuration, exit the process if failed
+#[deprecated(note = "exits the process on failure, use `try_init_with` instead")]
 pub async fn init_with(config: ConRegConfig) {
-    match Conreg::init_with(&config).await {
-        Ok(_) => {}
-        Err(e) => {
-            log::error!("conreg init failed: {}", e);
-            exit(1);
+    exit_on_error(try_init_with(config).await);
+}
+
+/// Deregister the current instance from the registry center
+///
+/// Other services stop routing traffic to the instance immediately instead of waiting for
+/// heartbeat timeouts. Heartbeats stop and the instance is not re-registered afterwards,
+/// so call it right before the process exits. Does nothing if discovery is not initialized.
+pub async fn shutdown() -> anyhow::Result<()> {
+    match DISCOVERY.get() {
+        Some(discovery) => discovery.shutdown().await,
+        None => Ok(()),
+    }
+}
+
+/// Deregister the current instance and exit when the process receives Ctrl+C or SIGTERM
+///
+/// The hook replaces the default signal handling, so the process exits with code 0 once the
+/// instance is deregistered. If the application handles signals itself, call [`shutdown`] in
+/// its own handler instead.
+pub fn shutdown_on_signal() {
+    tokio::spawn(async {
+        wait_for_signal().await;
+        log::info!("shutdown signal received, deregistering instance");
+        if let Err(e) = shutdown().await {
+            log::error!("deregister instance failed: {}", e);
         }
-    };
+        exit(0);
+    });
+}
+
+/// 等待Ctrl+C或SIGTERM信号
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(e) => {
+                log::error!("listen SIGTERM failed: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
 
 /// Application Configuration
 pub struct AppConfig;
 impl AppConfig {
-    fn reload(configs: Configs) {
+    /// 替换配置源的配置，合并后的配置发生变化时通知绑定和配置项监听器
+    fn reload(source: usize, configs: Configs) {
         match CONFIGS.get() {
             None => {
                 log::error!("config not init");
             }
-            Some(config) => {
-                *config.write().unwrap() = configs;
+            Some(config) => Self::reload_store(config, source, configs),
+        }
+    }
+
+    /// 替换配置存储中指定配置源的配置，在持有写锁时通知绑定
+    fn reload_store(store: &RwLock<ConfigStore>, source: usize, configs: Configs) {
+        let key_changes = {
+            let mut store = store.write().unwrap();
+            let old = match store.replace(source, configs) {
+                Ok(old) => old,
+                Err(e) => {
+                    log::error!("merge config failed, {:#}", e);
+                    return;
+                }
+            };
+            let new = store.merged();
+            if old.same_content(new) {
+                return;
             }
+            new.notify_bindings();
+            Configs::key_changes(&old, new)
+        };
+        // 在锁外调用配置项监听器，监听器中可以读取配置
+        for (handler, old_value, new_value) in key_changes {
+            handler(old_value.as_ref(), new_value.as_ref());
         }
     }
 
+    /// Deserialize the merged configuration into a struct
+    ///
+    /// The returned value is a snapshot, use [`AppConfig::bind_watch`] to keep it up to date.
+    ///
+    /// ```rust
+    /// #[derive(Deserialize)]
+    /// struct Settings {
+    ///     name: String,
+    ///     age: u32,
+    /// }
+    /// let settings = AppConfig::bind::<Settings>()?;
+    /// ```
+    pub fn bind<T: DeserializeOwned>() -> anyhow::Result<T> {
+        match CONFIGS.get() {
+            None => bail!("config not init"),
+            Some(config) => config.read().expect("read lock error").merged().bind(),
+        }
+    }
+
+    /// Deserialize the merged configuration into a struct and keep it up to date
+    ///
+    /// The value is deserialized again whenever the configuration changes. If the new configuration
+    /// can not be deserialized, an error is logged and the receiver keeps the previous value.
+    ///
+    /// ```rust
+    /// let mut settings = AppConfig::bind_watch::<Settings>()?;
+    /// println!("{}", settings.borrow().name);
+    /// tokio::spawn(async move {
+    ///     while settings.changed().await.is_ok() {
+    ///         println!("settings changed: {}", settings.borrow().name);
+    ///     }
+    /// });
+    /// ```
+    pub fn bind_watch<T: DeserializeOwned + Send + Sync + 'static>()
+    -> anyhow::Result<watch::Receiver<T>> {
+        Self::watch_with(|configs| configs.bind::<T>())
+    }
+
+    /// 以合并后的配置调用`bind`得到初始值，并在配置变更时重新调用，更新接收端的值
+    fn watch_with<T, F>(bind: F) -> anyhow::Result<watch::Receiver<T>>
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Configs) -> anyhow::Result<T> + Send + Sync + 'static,
+    {
+        match CONFIGS.get() {
+            None => bail!("config not init"),
+            Some(config) => Self::watch_store(config, bind),
+        }
+    }
+
+    /// 以配置存储中合并后的配置调用`bind`得到初始值并注册绑定
+    ///
+    /// 读取初始值到注册绑定期间持有读锁，重新加载需要写锁并在持有写锁时通知绑定，
+    /// 因此期间的重新加载会等待绑定注册后再通知，不会丢失
+    fn watch_store<T, F>(store: &RwLock<ConfigStore>, bind: F) -> anyhow::Result<watch::Receiver<T>>
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Configs) -> anyhow::Result<T> + Send + Sync + 'static,
+    {
+        let store = store.read().expect("read lock error");
+        let value = bind(store.merged())?;
+        let (sender, receiver) = watch::channel(value);
+        Configs::add_binding(Box::new(move |configs| {
+            if sender.is_closed() {
+                return false;
+            }
+            match bind(configs) {
+                Ok(value) => {
+                    let _ = sender.send(value);
+                }
+                Err(e) => log::error!(
+                    "bind config to {} failed, {}",
+                    std::any::type_name::<T>(),
+                    e
+                ),
+            }
+            true
+        }));
+        drop(store);
+        Ok(receiver)
+    }
+
     /// Get configuration value
     ///
     /// `key` is the key of the configuration item, such as `app.name`.
@@ -405,19 +1031,34 @@ impl AppConfig {
                 log::error!("config not init");
                 None
             }
-            Some(config) => match config.read().expect("read lock error").get(key) {
-                None => None,
-                Some(value) => match serde_yaml::from_value::<V>(value.clone()) {
+            Some(config) => {
+                let config = config.read().expect("read lock error");
+                let merged = config.merged();
+                match serde_yaml::from_value::<V>(merged.get(key)?.clone()) {
                     Ok(value) => Some(value),
                     Err(e) => {
-                        log::error!("parse config failed, {}", e);
+                        log::error!("parse config failed, {}", merged.redact(&e.to_string()));
                         None
                     }
-                },
-            },
+                }
+            }
         }
     }
 
+    /// Get a view of the configuration under a key prefix
+    ///
+    /// Keys passed to the view are relative to the prefix, which keeps module-level access short:
+    ///
+    /// ```rust
+    /// let database = AppConfig::scoped("database");
+    /// // Same as AppConfig::get::<String>("database.url")
+    /// let url = database.get::<String>("url");
+    /// let pool = database.bind::<PoolSettings>()?;
+    /// ```
+    pub fn scoped(prefix: &str) -> ScopedConfig {
+        ScopedConfig::new(prefix)
+    }
+
     /// Get raw configuration value
     pub fn get_raw<V: DeserializeOwned>(key: &str) -> Option<V> {
         match CONFIGS.get() {
@@ -425,25 +1066,236 @@ impl AppConfig {
                 log::error!("config not init");
                 None
             }
-            Some(config) => match config.read().expect("read lock error").get_raw(key) {
-                None => None,
-                Some(value) => match serde_yaml::from_value::<V>(value.clone()) {
+            Some(config) => {
+                let config = config.read().expect("read lock error");
+                let merged = config.merged();
+                match serde_yaml::from_value::<V>(merged.get_raw(key)?.clone()) {
                     Ok(value) => Some(value),
                     Err(e) => {
-                        log::error!("parse config failed, {}", e);
+                        log::error!("parse config failed, {}", merged.redact(&e.to_string()));
                         None
                     }
-                },
-            },
+                }
+            }
+        }
+    }
+
+    /// Dump the merged configuration the instance is using as YAML, with secrets masked
+    ///
+    /// Values whose key path contains a segment matching the `mask-patterns` of the client configuration,
+    /// by default such as `password`, `secret`, `token` or `api-key` (case-insensitive), are replaced
+    /// with `******`. Use [`AppConfig::dump_with`] to choose the patterns.
+    pub fn dump() -> anyhow::Result<String> {
+        let patterns = config::mask_patterns();
+        Self::dump_with(&patterns.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    /// Dump the merged configuration as YAML, masking values whose key path contains a segment
+    /// that contains any of `mask_patterns` (case-insensitive). An empty slice masks nothing.
+    pub fn dump_with(mask_patterns: &[&str]) -> anyhow::Result<String> {
+        match CONFIGS.get() {
+            None => bail!("config not init"),
+            Some(config) => config
+                .read()
+                .expect("read lock error")
+                .merged()
+                .dump(mask_patterns),
+        }
+    }
+
+    /// HTTP response of [`AppConfig::dump`], to be mounted as a debug endpoint in any web framework
+    /// built on the `http` crate. Only expose it to operators.
+    ///
+    /// ```rust
+    /// // axum
+    /// let app = Router::new().route("/debug/config", get(|| async { AppConfig::dump_response() }));
+    /// ```
+    pub fn dump_response() -> http::Response<String> {
+        let (status, content_type, body) = match Self::dump() {
+            Ok(dump) => (http::StatusCode::OK, "application/yaml", dump),
+            Err(e) => (
+                http::StatusCode::SERVICE_UNAVAILABLE,
+                "text/plain; charset=utf-8",
+                e.to_string(),
+            ),
+        };
+        let mut response = http::Response::new(body);
+        *response.status_mut() = status;
+        response.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(content_type),
+        );
+        response
+    }
+
+    /// Get the format of a loaded configuration
+    ///
+    /// The format is reported by the server, or detected from the extension of `config_id`
+    /// for older servers. Returns `None` if the configuration is not loaded.
+    pub fn format(config_id: &str) -> Option<ConfigFormat> {
+        match CONFIGS.get() {
+            None => {
+                log::error!("config not init");
+                None
+            }
+            Some(config) => config
+                .read()
+                .expect("read lock error")
+                .merged()
+                .format(config_id),
         }
     }
 
+    /// Encrypt a configuration value with a `decrypt-key`, returns `DEC(...)`
+    ///
+    /// Store the returned value in the configuration center, it is decrypted by clients
+    /// configured with the same key, see [Encrypted Values](crate#encrypted-values).
+    ///
+    /// ```rust
+    /// let value = AppConfig::encrypt(&std::env::var("CONREG_DECRYPT_KEY")?, "p@ssw0rd")?;
+    /// ```
+    pub fn encrypt(key: &str, value: &str) -> anyhow::Result<String> {
+        enc_dec::DecryptKey::parse(key)?.encrypt(value)
+    }
+
     /// Add configuration listener
     ///
     /// - `config_id`: Configuration ID
-    /// - `handler`: Configuration listener function, parameter is the changed, merged and flattened configuration content
-    pub fn add_listener(config_id: &str, handler: fn(&HashMap<String, serde_yaml::Value>)) {
-        Configs::add_listener(config_id, handler);
+    /// - `handler`: Configuration listener closure, parameter is the changed, merged and flattened configuration content
+    ///
+    /// Returns a handle that can be used to remove the listener.
+    pub fn add_listener<F>(config_id: &str, handler: F) -> ListenerHandle
+    where
+        F: Fn(&HashMap<String, serde_yaml::Value>) + Send + Sync + 'static,
+    {
+        Configs::add_listener(config_id, Arc::new(handler))
+    }
+
+    /// Add a listener for a configuration key
+    ///
+    /// - `key`: Flattened configuration key such as `db.pool.size`, or a key prefix such as `db.pool`
+    /// - `handler`: Called with the old and new value when the key, or any key under the prefix, changes.
+    ///   For a prefix, the values are the merged sub-configurations. `None` means the key does not exist.
+    ///
+    /// Unlike [`AppConfig::add_listener`], the handler is not called when other keys change.
+    /// Returns a handle that can be used to remove the listener.
+    pub fn add_key_listener<F>(key: &str, handler: F) -> ListenerHandle
+    where
+        F: Fn(Option<&serde_yaml::Value>, Option<&serde_yaml::Value>) + Send + Sync + 'static,
+    {
+        Configs::add_key_listener(key, Arc::new(handler))
+    }
+
+    /// Refresh all configurations immediately
+    ///
+    /// Bypasses the watch and compensation tasks and re-fetches all configurations from the server.
+    /// Useful right after a deployment script publishes configurations.
+    /// Listeners of the configurations whose content changed will be notified.
+    pub async fn refresh() -> anyhow::Result<()> {
+        let Some(clients) = CONFIG_CLIENTS.get() else {
+            bail!("config not initialized");
+        };
+        for client in clients {
+            client.refresh().await?;
+        }
+        Ok(())
+    }
+
+    /// Refresh the specified configuration immediately
+    ///
+    /// `config_id` must be one of the configured `config-ids`. With several configuration sources,
+    /// the configuration is refreshed in every source that lists it.
+    pub async fn refresh_one(config_id: &str) -> anyhow::Result<()> {
+        let Some(clients) = CONFIG_CLIENTS.get() else {
+            bail!("config not initialized");
+        };
+        let clients = clients
+            .iter()
+            .filter(|client| client.contains(config_id))
+            .collect::<Vec<_>>();
+        if clients.is_empty() {
+            bail!("config id [ {} ] not in config-ids", config_id);
+        }
+        for client in clients {
+            client.refresh_one(config_id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// View of the configuration under a key prefix, created by [`AppConfig::scoped`]
+///
+/// The view holds no configuration itself, every call reads the latest merged configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopedConfig {
+    prefix: String,
+}
+
+impl ScopedConfig {
+    fn new(prefix: &str) -> Self {
+        ScopedConfig {
+            prefix: prefix.trim_matches('.').to_string(),
+        }
+    }
+
+    /// Key prefix of the view, without the trailing `.`
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Full key of a key relative to the prefix
+    fn key(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.prefix, key)
+        }
+    }
+
+    /// Get a view of a nested prefix, e.g. `scoped("database").scoped("pool")` is `database.pool`
+    pub fn scoped(&self, prefix: &str) -> ScopedConfig {
+        ScopedConfig::new(&self.key(prefix.trim_matches('.')))
+    }
+
+    /// Get configuration value, `key` is relative to the prefix
+    ///
+    /// See [`AppConfig::get`].
+    pub fn get<V: DeserializeOwned>(&self, key: &str) -> Option<V> {
+        AppConfig::get(&self.key(key))
+    }
+
+    /// Deserialize the configuration under the prefix into a struct
+    ///
+    /// Returns an error if no configuration exists under the prefix.
+    pub fn bind<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        match CONFIGS.get() {
+            None => bail!("config not init"),
+            Some(config) => config
+                .read()
+                .expect("read lock error")
+                .merged()
+                .bind_at(&self.prefix),
+        }
+    }
+
+    /// Deserialize the configuration under the prefix into a struct and keep it up to date
+    ///
+    /// See [`AppConfig::bind_watch`].
+    pub fn bind_watch<T: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+    ) -> anyhow::Result<watch::Receiver<T>> {
+        let prefix = self.prefix.clone();
+        AppConfig::watch_with(move |configs| configs.bind_at::<T>(&prefix))
+    }
+
+    /// Add a listener for a configuration key relative to the prefix
+    ///
+    /// See [`AppConfig::add_key_listener`].
+    pub fn add_key_listener<F>(&self, key: &str, handler: F) -> ListenerHandle
+    where
+        F: Fn(Option<&serde_yaml::Value>, Option<&serde_yaml::Value>) + Send + Sync + 'static,
+    {
+        AppConfig::add_key_listener(&self.key(key), handler)
     }
 }
 
@@ -462,13 +1314,117 @@ impl AppDiscovery {
             }
         }
     }
+
+    /// Wait until at least `min` available instances of the specified service are registered
+    ///
+    /// Useful for startup ordering of interdependent services.
+    /// Returns the available instances, or an error if `timeout` elapses first.
+    ///
+    /// ```rust
+    /// let instances = AppDiscovery::wait_for_instances("user-service", 2, Duration::from_secs(60)).await?;
+    /// ```
+    pub async fn wait_for_instances(
+        service_id: &str,
+        min: usize,
+        timeout: Duration,
+    ) -> anyhow::Result<Vec<Instance>> {
+        match DISCOVERY.get() {
+            Some(discovery) => discovery.wait_for_instances(service_id, min, timeout).await,
+            None => {
+                bail!("discovery not initialized")
+            }
+        }
+    }
+    /// Subscribe to the available instances of a service
+    ///
+    /// The handler is called with the current instances once they are known, and again whenever
+    /// they change, e.g. an instance is registered, deregistered, taken offline or misses heartbeats.
+    /// Changes are long-polled from the server and usually arrive within a couple of seconds. The
+    /// local cache used by [`AppDiscovery::get_instances`] and the load balance client is updated
+    /// at the same time, so deregistered instances stop receiving requests right away.
+    ///
+    /// ```rust
+    /// let subscription = AppDiscovery::subscribe("user-service", |instances| {
+    ///     println!("user-service has {} instances", instances.len());
+    /// })?;
+    /// // Stop receiving changes
+    /// subscription.unsubscribe();
+    /// ```
+    pub fn subscribe<F>(service_id: &str, handler: F) -> anyhow::Result<Subscription>
+    where
+        F: Fn(&[Instance]) + Send + Sync + 'static,
+    {
+        match DISCOVERY.get() {
+            Some(discovery) => Ok(discovery.subscribe(service_id, Arc::new(handler))),
+            None => bail!("discovery not initialized"),
+        }
+    }
+
+    /// Update the metadata of this instance, e.g. weight, zone or version
+    ///
+    /// The given keys overwrite the current values and other keys are kept. The change is
+    /// replicated to all server nodes, so callers see it on their next instance refresh, and
+    /// it is kept when the instance is re-registered. Useful to ramp up the weight of a canary
+    /// or a freshly started instance.
+    ///
+    /// ```rust
+    /// AppDiscovery::update_metadata(HashMap::from([("weight".to_string(), "50".to_string())])).await?;
+    /// ```
+    pub async fn update_metadata(meta: HashMap<String, String>) -> anyhow::Result<()> {
+        match DISCOVERY.get() {
+            Some(discovery) => discovery.update_meta(meta).await,
+            None => bail!("discovery not initialized"),
+        }
+    }
+
+    /// Get statistics of the local view of the registry
+    ///
+    /// Includes the number of instances known per service, the time of the last successful
+    /// fetch and the number of heartbeats failed in a row, so applications can alarm when
+    /// their view of the registry goes stale.
+    ///
+    /// ```rust
+    /// let stats = AppDiscovery::stats()?;
+    /// if stats.consecutive_heartbeat_failures > 3 {
+    ///     log::warn!("registry unreachable");
+    /// }
+    /// ```
+    pub fn stats() -> anyhow::Result<DiscoveryStats> {
+        match DISCOVERY.get() {
+            Some(discovery) => Ok(discovery.stats()),
+            None => bail!("discovery not initialized"),
+        }
+    }
+
+    /// Add a handler called when the server reports that this instance was evicted
+    ///
+    /// The server returns an eviction notice on the next heartbeat after this instance was
+    /// marked unhealthy or removed because of missed heartbeats (e.g. a long GC pause or a
+    /// network partition). A removed instance is re-registered automatically; the handler can
+    /// be used to log, alert or warm caches again.
+    ///
+    /// Returns a handle that can be used to remove the handler.
+    ///
+    /// ```rust
+    /// let handle = AppDiscovery::on_evicted(|notice| {
+    ///     println!("evicted: {}, removed: {}", notice.reason, notice.removed);
+    /// });
+    /// // later
+    /// handle.remove();
+    /// ```
+    pub fn on_evicted<F>(handler: F) -> EvictionListenerHandle
+    where
+        F: Fn(&EvictionNotice) + Send + Sync + 'static,
+    {
+        discovery::add_eviction_listener(Arc::new(handler))
+    }
 }
 
 #[cfg(test)]
 #[allow(unused)]
 mod tests {
     use crate::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
-    use crate::{AppConfig, AppDiscovery, init};
+    use crate::{AppConfig, AppDiscovery, try_init};
     use reqwest::StatusCode;
     use reqwest::multipart::{Form, Part};
     use serde::{Deserialize, Serialize};
@@ -478,7 +1434,7 @@ mod tests {
     #[tokio::test]
     async fn test_config() {
         //init_log();
-        init().await;
+        try_init().await.unwrap();
         //init_from_file("bootstrap.yaml").await;
         /*init_with(
             ConRegConfigBuilder::default()
@@ -513,10 +1469,19 @@ mod tests {
         tokio::join!(h);
     }
 
+    #[test]
+    fn test_scoped_key() {
+        let database = AppConfig::scoped("database.");
+        assert_eq!(database.prefix(), "database");
+        assert_eq!(database.key("url"), "database.url");
+        assert_eq!(database.scoped("pool").key("size"), "database.pool.size");
+        assert_eq!(AppConfig::scoped("").key("name"), "name");
+    }
+
     #[tokio::test]
     async fn test_discovery() {
         //init_log();
-        init().await;
+        try_init().await.unwrap();
         // let config = ConRegConfigBuilder::default()
         //     .service_id("your_service_id")
         //     .client(
@@ -0,0 +1,124 @@
+//! 本机IP检测
+//!
+//! `client.address`配置为`auto`时，在注册实例前检测本机地址，避免在容器和多网卡主机中写死IP。
+
+use anyhow::{Context, bail};
+use std::net::{IpAddr, UdpSocket};
+
+/// 自动检测本机地址时`address`的配置值
+pub(crate) const AUTO: &str = "auto";
+
+/// 未指定服务端地址时用于检测出站地址的目标，不会发送数据
+const FALLBACK_TARGET: &str = "8.8.8.8:80";
+
+/// 检测本机用于注册的IP
+///
+/// - `prefer_network`为CIDR（如`10.0.0.0/8`）或网卡名称（如`eth0`）时，返回匹配的第一个非回环地址，优先IPv4
+/// - 否则通过UDP连接`target`（不发送数据）获取出站地址，失败时返回第一个非回环的IPv4地址
+pub(crate) fn detect(prefer_network: Option<&str>, target: Option<&str>) -> anyhow::Result<IpAddr> {
+    let interfaces = if_addrs::get_if_addrs().context("list network interfaces error")?;
+    let candidates = interfaces
+        .iter()
+        .filter(|interface| !interface.is_loopback())
+        .map(|interface| (interface.name.as_str(), interface.ip()));
+
+    if let Some(prefer_network) = prefer_network.map(str::trim).filter(|s| !s.is_empty()) {
+        let cidr = parse_cidr(prefer_network);
+        if cidr.is_none() && prefer_network.contains('/') {
+            bail!("invalid prefer-network: {}", prefer_network);
+        }
+        let matches = |name: &str, ip: IpAddr| match cidr {
+            Some((network, prefix)) => cidr_contains(network, prefix, ip),
+            None => name == prefer_network,
+        };
+        let matched = candidates
+            .filter(|(name, ip)| matches(name, *ip))
+            .map(|(_, ip)| ip)
+            .collect::<Vec<_>>();
+        return matched
+            .iter()
+            .find(|ip| ip.is_ipv4())
+            .or(matched.first())
+            .copied()
+            .with_context(|| format!("no address found in network {}", prefer_network));
+    }
+
+    for target in target.into_iter().chain([FALLBACK_TARGET]) {
+        if let Some(ip) = outbound_ip(target) {
+            return Ok(ip);
+        }
+    }
+    candidates
+        .map(|(_, ip)| ip)
+        .find(IpAddr::is_ipv4)
+        .context("no non-loopback address found")
+}
+
+/// 连接目标地址时本机使用的地址，UDP连接只选择路由，不发送数据
+fn outbound_ip(target: &str) -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect(target).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    // 目标为本机时得到回环地址，不能用于注册
+    (!ip.is_loopback() && !ip.is_unspecified()).then_some(ip)
+}
+
+/// 解析CIDR，如`10.0.0.0/8`，返回网络地址和前缀长度
+fn parse_cidr(s: &str) -> Option<(IpAddr, u8)> {
+    let (network, prefix) = s.split_once('/')?;
+    let network = network.parse::<IpAddr>().ok()?;
+    let prefix = prefix.parse::<u8>().ok()?;
+    let max = if network.is_ipv4() { 32 } else { 128 };
+    (prefix <= max).then_some((network, prefix))
+}
+
+/// IP是否在网络中
+fn cidr_contains(network: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cidr() {
+        let (network, prefix) = parse_cidr("10.0.0.0/8").unwrap();
+        assert!(cidr_contains(network, prefix, "10.1.2.3".parse().unwrap()));
+        assert!(!cidr_contains(
+            network,
+            prefix,
+            "192.168.1.1".parse().unwrap()
+        ));
+        assert!(!cidr_contains(network, prefix, "::1".parse().unwrap()));
+
+        let (network, prefix) = parse_cidr("0.0.0.0/0").unwrap();
+        assert!(cidr_contains(
+            network,
+            prefix,
+            "192.168.1.1".parse().unwrap()
+        ));
+
+        let (network, prefix) = parse_cidr("fd00::/8").unwrap();
+        assert!(cidr_contains(network, prefix, "fd12::1".parse().unwrap()));
+
+        assert_eq!(parse_cidr("10.0.0.0/33"), None);
+        assert_eq!(parse_cidr("eth0"), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(detect(Some("10.0.0.0/33"), None).is_err());
+        assert!(detect(Some("no-such-interface"), None).is_err());
+    }
+}
@@ -1,55 +1,133 @@
-use crate::conf::ServerAddr;
-use crate::protocol::response::Res;
+use crate::conf::{HttpConfig, ServerAddr};
+use crate::protocol::response::{ClusterNode, Res};
+use crate::timer::Ticker;
 use anyhow::bail;
+use dashmap::DashMap;
 use reqwest::StatusCode;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::Serialize;
 use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::future::Future;
 use std::str::FromStr;
-use std::sync::LazyLock;
-use std::time::Duration;
+use std::sync::{LazyLock, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+pub(crate) mod ip;
+
+/// 节点列表刷新间隔
+const NODES_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 节点连接失败后的冷却时间，冷却期间排在其他节点之后，冷却结束后重新参与选择
+const NODE_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// 连接失败的节点，value为冷却结束的时间
+static UNHEALTHY_NODES: LazyLock<DashMap<String, Instant>> = LazyLock::new(DashMap::new);
+
+/// 最近一次请求成功的节点，key为配置的种子地址
+static PREFERRED_NODES: LazyLock<DashMap<String, String>> = LazyLock::new(DashMap::new);
+
+/// 从集群成员中发现的服务端节点，key为配置的种子地址
+static SERVER_NODES: LazyLock<DashMap<String, Vec<String>>> = LazyLock::new(DashMap::new);
+
+/// 初始化时设置的HTTP客户端配置，之后创建的负载均衡客户端同样使用
+static HTTP_CONFIG: OnceLock<HttpConfig> = OnceLock::new();
 
 pub struct Network {
-    client: reqwest::Client,
+    /// 初始化时按[`HttpConfig`]重建，之前的请求（如获取引导配置）使用默认设置
+    client: RwLock<reqwest::Client>,
 }
 
-pub static HTTP: LazyLock<Network> = LazyLock::new(|| {
-    let client = reqwest::ClientBuilder::default()
-        .connect_timeout(Duration::from_secs(1))
-        .read_timeout(Duration::from_secs(60))
-        .build()
-        .unwrap();
-    Network { client }
+pub static HTTP: LazyLock<Network> = LazyLock::new(|| Network {
+    client: RwLock::new(Network::client_builder().build().unwrap()),
 });
 
+/// 初始化时设置的HTTP客户端配置
+pub(crate) fn http_config() -> Option<&'static HttpConfig> {
+    HTTP_CONFIG.get()
+}
+
 impl Network {
+    fn client_builder() -> reqwest::ClientBuilder {
+        reqwest::ClientBuilder::default()
+            .connect_timeout(Duration::from_secs(1))
+            .read_timeout(Duration::from_secs(60))
+    }
+
+    fn client(&self) -> reqwest::Client {
+        self.client.read().unwrap().clone()
+    }
+
+    /// 按配置重建HTTP客户端，配置无效（如代理地址错误、证书文件不存在）时返回错误
+    pub(crate) fn configure(&self, config: &HttpConfig) -> anyhow::Result<()> {
+        let client = config.apply(Self::client_builder())?.build()?;
+        *self.client.write().unwrap() = client;
+        let _ = HTTP_CONFIG.set(config.clone());
+        Ok(())
+    }
+
     pub async fn get<T: DeserializeOwned + Debug + Default>(
         &self,
         url: &str,
         query: impl Serialize + Debug,
         headers: Option<Vec<(&str, &str)>>,
     ) -> anyhow::Result<T> {
+        let response = self.send_get(url, query, headers).await?;
+        Self::read_data(response).await
+    }
+
+    /// 条件GET请求，服务端返回304（内容未变化）时返回None
+    pub async fn get_modified<T: DeserializeOwned + Debug + Default>(
+        &self,
+        url: &str,
+        query: impl Serialize + Debug,
+        headers: Option<Vec<(&str, &str)>>,
+    ) -> anyhow::Result<Option<T>> {
+        let response = self.send_get(url, query, headers).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        Self::read_data(response).await.map(Some)
+    }
+
+    async fn send_get(
+        &self,
+        url: &str,
+        query: impl Serialize + Debug,
+        headers: Option<Vec<(&str, &str)>>,
+    ) -> anyhow::Result<reqwest::Response> {
         log::debug!("GET {}, query: {:?}", url, query);
         let response = self
-            .client
+            .client()
             .get(url)
             .query(&query)
-            .headers(match headers {
-                Some(headers) => headers
-                    .into_iter()
-                    .map(|(k, v)| {
-                        (
-                            // SAFE: Header name is known
-                            HeaderName::from_str(k).unwrap(),
-                            HeaderValue::from_str(v).unwrap_or(HeaderValue::from_str("").unwrap()),
-                        )
-                    })
-                    .collect::<HeaderMap<_>>(),
-                None => HeaderMap::new(),
-            })
+            .headers(Self::header_map(headers))
             .send()
             .await?;
+        Ok(response)
+    }
+
+    fn header_map(headers: Option<Vec<(&str, &str)>>) -> HeaderMap {
+        match headers {
+            Some(headers) => headers
+                .into_iter()
+                .map(|(k, v)| {
+                    (
+                        // SAFE: Header name is known
+                        HeaderName::from_str(k).unwrap(),
+                        HeaderValue::from_str(v).unwrap_or(HeaderValue::from_str("").unwrap()),
+                    )
+                })
+                .collect::<HeaderMap<_>>(),
+            None => HeaderMap::new(),
+        }
+    }
+
+    /// 读取响应中的数据，响应状态或响应码不成功时返回错误
+    async fn read_data<T: DeserializeOwned + Debug + Default>(
+        response: reqwest::Response,
+    ) -> anyhow::Result<T> {
         if response.status() != StatusCode::OK {
             bail!("{}", response.text().await?);
         }
@@ -66,7 +144,7 @@ impl Network {
         body: impl Serialize + Debug,
     ) -> anyhow::Result<T> {
         log::debug!("POST {}, body: {:?}", url, body);
-        let response = self.client.post(url).json(&body).send().await?;
+        let response = self.client().post(url).json(&body).send().await?;
         if response.status() != StatusCode::OK {
             bail!("{}", response.text().await?);
         }
@@ -76,23 +154,322 @@ impl Network {
         }
         Ok(result.data.unwrap_or(Default::default()))
     }
+
+    /// 带查询参数和请求头的POST请求，用于需要命名空间认证的接口
+    pub async fn post_with<T: DeserializeOwned + Debug + Default>(
+        &self,
+        url: &str,
+        query: impl Serialize + Debug,
+        body: impl Serialize + Debug,
+        headers: Option<Vec<(&str, &str)>>,
+    ) -> anyhow::Result<T> {
+        log::debug!("POST {}, query: {:?}, body: {:?}", url, query, body);
+        let response = self
+            .client()
+            .post(url)
+            .query(&query)
+            .headers(Self::header_map(headers))
+            .json(&body)
+            .send()
+            .await?;
+        Self::read_data(response).await
+    }
 }
 
 impl ServerAddr {
-    pub fn build_url(&self, path: &str) -> anyhow::Result<String> {
+    /// 配置的种子地址
+    fn seeds(&self) -> Vec<String> {
         match self {
-            ServerAddr::Single(address) => {
-                let url = format!("http://{}{}", address, path);
-                Ok(url)
+            ServerAddr::Single(address) => vec![address.clone()],
+            ServerAddr::Cluster(addresses) => addresses.clone(),
+            ServerAddr::Unset => vec![],
+        }
+    }
+
+    /// 可用的服务端地址，已发现节点时使用发现的节点，否则使用种子地址
+    pub(crate) fn addresses(&self) -> Vec<String> {
+        let seeds = self.seeds();
+        match SERVER_NODES.get(&seeds.join(",")) {
+            Some(nodes) if !nodes.is_empty() => nodes.clone(),
+            _ => seeds,
+        }
+    }
+
+    /// 按优先级排序的服务端地址
+    ///
+    /// 最近请求成功的节点排在最前，没有时从随机节点开始，使客户端分散到各个节点，其余节点按配置顺序排列；
+    /// 冷却中的节点排在最后，所有节点都不可用时仍会尝试
+    pub(crate) fn candidates(&self) -> Vec<String> {
+        let mut addresses = self.addresses();
+        if addresses.is_empty() {
+            return addresses;
+        }
+        let start = PREFERRED_NODES
+            .get(&self.seeds().join(","))
+            .and_then(|preferred| addresses.iter().position(|address| *address == *preferred))
+            .unwrap_or_else(|| fastrand::usize(0..addresses.len()));
+        addresses.rotate_left(start);
+        let now = Instant::now();
+        // 稳定排序，保持节点之间原有的顺序
+        addresses.sort_by_key(|address| {
+            UNHEALTHY_NODES
+                .get(address)
+                .is_some_and(|until| *until > now)
+        });
+        addresses
+    }
+
+    /// 首选节点的URL，不进行故障转移，需要故障转移时使用[`ServerAddr::request`]
+    pub fn build_url(&self, path: &str) -> anyhow::Result<String> {
+        match self.candidates().first() {
+            Some(address) => Ok(format!("http://{}{}", address, path)),
+            None => bail!("discovery server address not set"),
+        }
+    }
+
+    /// 按[`ServerAddr::candidates`]的顺序依次向各个节点发送请求，直到请求成功或返回连接以外的错误
+    ///
+    /// 连接失败或超时的节点冷却[`NODE_COOLDOWN`]，得到响应的节点作为之后请求的首选节点。
+    /// 超时的请求可能已被服务端处理，因此只用于查询、监听等幂等请求，非幂等请求使用[`ServerAddr::request_once`]
+    pub(crate) async fn request<T, F, Fut>(&self, path: &str, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.request_with_failover(path, f, true).await
+    }
+
+    /// 发送非幂等请求，如注册实例、更新元数据
+    ///
+    /// 只在连接失败时转移到其他节点，请求超时时直接返回错误，避免同一请求被多个节点重复处理
+    pub(crate) async fn request_once<T, F, Fut>(&self, path: &str, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.request_with_failover(path, f, false).await
+    }
+
+    async fn request_with_failover<T, F, Fut>(
+        &self,
+        path: &str,
+        f: F,
+        idempotent: bool,
+    ) -> anyhow::Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut last_error = None;
+        for address in self.candidates() {
+            match f(format!("http://{}{}", address, path)).await {
+                Err(e) if is_unreachable(&e, idempotent) => {
+                    log::warn!("server {} unreachable, try next node: {}", address, e);
+                    UNHEALTHY_NODES.insert(address, Instant::now() + NODE_COOLDOWN);
+                    last_error = Some(e);
+                }
+                res => {
+                    if UNHEALTHY_NODES.remove(&address).is_some() {
+                        log::info!("server {} recovered", address);
+                    }
+                    PREFERRED_NODES.insert(self.seeds().join(","), address);
+                    return res;
+                }
             }
-            ServerAddr::Cluster(addresses) => {
-                let address = addresses[fastrand::usize(0..addresses.len())].clone();
-                let url = format!("http://{}{}", address, path);
-                Ok(url)
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("discovery server address not set")))
+    }
+
+    /// 从服务端获取集群节点列表，并定时刷新
+    ///
+    /// 相同种子地址只会启动一次，获取失败时继续使用种子地址
+    pub(crate) async fn discover_nodes(&self) {
+        let key = self.seeds().join(",");
+        if key.is_empty() || SERVER_NODES.contains_key(&key) {
+            return;
+        }
+        let nodes = self.fetch_nodes().await.unwrap_or_else(|e| {
+            log::warn!("discover server nodes failed, fallback to seeds: {}", e);
+            vec![]
+        });
+        log::info!("discovered server nodes: {:?}", nodes);
+        SERVER_NODES.insert(key.clone(), nodes);
+
+        let server_addr = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = Ticker::new(NODES_REFRESH_INTERVAL);
+            loop {
+                ticker.tick().await;
+                match server_addr.fetch_nodes().await {
+                    Ok(nodes) => {
+                        if SERVER_NODES.get(&key).is_none_or(|old| *old != nodes) {
+                            log::info!("server nodes changed: {:?}", nodes);
+                            SERVER_NODES.insert(key.clone(), nodes);
+                        }
+                    }
+                    Err(e) => log::warn!("refresh server nodes failed: {}", e),
+                }
             }
-            ServerAddr::Unset => {
-                bail!("discovery server address not set");
+        });
+    }
+
+    /// 依次尝试已知的节点和种子地址，获取集群节点列表
+    async fn fetch_nodes(&self) -> anyhow::Result<Vec<String>> {
+        let mut addresses = self.addresses();
+        fastrand::shuffle(&mut addresses);
+        for seed in self.seeds() {
+            if !addresses.contains(&seed) {
+                addresses.push(seed);
             }
         }
+        let mut last_error = None;
+        for address in addresses {
+            let url = format!("http://{}/api/cluster/nodes", address);
+            match HTTP
+                .get::<Vec<ClusterNode>>(&url, HashMap::<String, String>::new(), None)
+                .await
+            {
+                Ok(nodes) if !nodes.is_empty() => {
+                    let mut nodes = nodes.into_iter().map(|n| n.addr).collect::<Vec<_>>();
+                    nodes.sort();
+                    return Ok(nodes);
+                }
+                Ok(_) => last_error = Some(anyhow::anyhow!("no nodes returned from {}", address)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("server address not set")))
+    }
+}
+
+/// 是否为连接失败导致的错误，幂等请求的超时也视为节点不可用
+fn is_unreachable(e: &anyhow::Error, idempotent: bool) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_connect() || (idempotent && e.is_timeout()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_failover() {
+        // 端口1和2上没有服务，只有向端口1发送的请求会真正发出
+        let server_addr = ServerAddr::from(vec!["127.0.0.1:1", "127.0.0.1:2"]);
+        let send = |url: String| async move {
+            if url.starts_with("http://127.0.0.1:1/") {
+                HTTP.client().get(&url).send().await?;
+            }
+            Ok(url)
+        };
+        for _ in 0..3 {
+            let url = server_addr.request("/api/test", send).await.unwrap();
+            assert_eq!(url, "http://127.0.0.1:2/api/test");
+        }
+        assert_eq!(
+            server_addr.candidates(),
+            vec!["127.0.0.1:2".to_string(), "127.0.0.1:1".to_string()]
+        );
+        assert_eq!(
+            server_addr.build_url("/api/test").unwrap(),
+            "http://127.0.0.1:2/api/test"
+        );
+
+        // 应用错误不触发故障转移
+        let res = server_addr
+            .request("/api/test", |_| async {
+                Err::<(), _>(anyhow::anyhow!("no permission"))
+            })
+            .await;
+        assert_eq!(res.unwrap_err().to_string(), "no permission");
+        assert!(ServerAddr::Unset.request("/api/test", send).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request_once_timeout() {
+        // 只接受连接不返回响应的节点，请求总是超时
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        let server_addr = ServerAddr::from(vec![silent.as_str(), "127.0.0.1:3"]);
+        PREFERRED_NODES.insert(server_addr.seeds().join(","), silent.clone());
+        let send = |url: String| async move {
+            if url.starts_with("http://127.0.0.1:3/") {
+                return Ok(url);
+            }
+            reqwest::Client::new()
+                .post(&url)
+                .timeout(Duration::from_millis(100))
+                .send()
+                .await?;
+            Ok(url)
+        };
+
+        // 非幂等请求超时后不转移到其他节点
+        let e = server_addr
+            .request_once("/api/test", send)
+            .await
+            .unwrap_err();
+        assert!(e.downcast_ref::<reqwest::Error>().unwrap().is_timeout());
+
+        // 幂等请求超时后转移到其他节点
+        let url = server_addr.request("/api/test", send).await.unwrap();
+        assert_eq!(url, "http://127.0.0.1:3/api/test");
+
+        // 连接失败时非幂等请求也转移到其他节点
+        let server_addr = ServerAddr::from(vec!["127.0.0.1:4", "127.0.0.1:3"]);
+        PREFERRED_NODES.insert(server_addr.seeds().join(","), "127.0.0.1:4".to_string());
+        let send = |url: String| async move {
+            if url.starts_with("http://127.0.0.1:4/") {
+                reqwest::Client::new().post(&url).send().await?;
+            }
+            Ok(url)
+        };
+        let url = server_addr.request_once("/api/test", send).await.unwrap();
+        assert_eq!(url, "http://127.0.0.1:3/api/test");
+    }
+
+    #[tokio::test]
+    async fn test_get_modified() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 依次返回304和200的服务端
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/config/get", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let body = r#"{"code":0,"msg":"","data":{"md5":"abc"}}"#;
+            let responses = [
+                "HTTP/1.1 304 Not Modified\r\nconnection: close\r\n\r\n".to_string(),
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+            ];
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).await;
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        let query = [("md5", "abc")];
+        let res = HTTP
+            .get_modified::<HashMap<String, String>>(&url, query, None)
+            .await
+            .unwrap();
+        assert_eq!(res, None);
+        let res = HTTP
+            .get_modified::<HashMap<String, String>>(&url, query, None)
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap()["md5"], "abc");
     }
 }
@@ -18,14 +18,49 @@ pub struct Instance {
     pub port: u16,
     /// 元数据
     pub meta: HashMap<String, Value>,
+    /// 有效权重，由服务端按预热时长计算，为空时使用元数据中的权重
+    #[serde(default)]
+    pub effective_weight: Option<u64>,
+}
+
+/// Eviction notice
+///
+/// Returned by the server when this instance heartbeats again after it was considered dead
+/// because of heartbeat timeouts, which is often a symptom of long GC pauses or network issues.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvictionNotice {
+    /// 被判定为不健康的原因
+    pub reason: String,
+    /// 是否已被注册中心移除，已移除时客户端会自动重新注册
+    pub removed: bool,
+    /// 注册中心记录的最后一次心跳时间
+    pub last_heartbeat: String,
+    /// 移除时间，未移除时为空
+    pub evicted_at: Option<String>,
 }
 
 impl Instance {
     pub fn get_weight(&self) -> u64 {
+        if let Some(weight) = self.effective_weight {
+            return weight;
+        }
+        // 服务端元数据值为字符串
         self.meta
             .get("weight")
-            .unwrap_or(&Value::Number(1.into()))
-            .as_u64()
-            .unwrap()
+            .and_then(|weight| match weight {
+                Value::String(weight) => weight.parse().ok(),
+                weight => weight.as_u64(),
+            })
+            .unwrap_or(1)
+    }
+
+    /// 实例所在的可用区，取元数据中的`zone`
+    pub fn zone(&self) -> Option<&str> {
+        self.meta.get("zone").and_then(Value::as_str)
+    }
+
+    /// 实例所在的地域，取元数据中的`region`
+    pub fn region(&self) -> Option<&str> {
+        self.meta.get("region").and_then(Value::as_str)
     }
 }
@@ -6,11 +6,42 @@ use std::collections::HashMap;
 pub(crate) struct GetConfigReq {
     pub(crate) namespace_id: String,
     pub(crate) id: String,
+    /// 标签选择器，格式为`env=prod,region=eu`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) labels: Option<String>,
+    /// 已有配置的MD5，与服务端一致时服务端返回304
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) md5: Option<String>,
 }
 
+/// 监听命名空间下的配置变化，gRPC传输使用
+#[cfg(feature = "grpc")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct WatchConfigChangeReq {
     pub(crate) namespace_id: String,
+    /// 最后收到的变更游标，首次监听时为空
+    pub(crate) cursor: String,
+}
+
+/// 按MD5监听指定配置的变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct WatchConfigsReq {
+    pub(crate) namespace_id: String,
+    /// key为配置ID，value为已加载配置的MD5
+    pub(crate) configs: HashMap<String, String>,
+    /// 标签选择器，格式为`env=prod,region=eu`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) labels: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ConfigReportReq {
+    pub(crate) namespace_id: String,
+    pub(crate) service_id: String,
+    pub(crate) instance_id: String,
+    pub(crate) address: String,
+    /// key为配置ID，value为已应用的配置MD5
+    pub(crate) configs: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,15 +53,47 @@ pub(crate) struct RegisterReq {
     pub(crate) meta: HashMap<String, Value>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DeregisterReq {
+    pub(crate) namespace_id: String,
+    pub(crate) service_id: String,
+    pub(crate) instance_id: String,
+}
+
+/// 更新实例元数据，meta中的key覆盖原有的值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct UpdateMetaReq {
+    pub(crate) namespace_id: String,
+    pub(crate) service_id: String,
+    pub(crate) instance_id: String,
+    pub(crate) meta: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct GetInstancesReq {
     pub(crate) namespace_id: String,
     pub(crate) service_id: String,
 }
 
+/// 监听服务可用实例的变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct WatchInstancesReq {
+    pub(crate) namespace_id: String,
+    pub(crate) service_id: String,
+    /// 已知的实例列表版本，为空时立即返回当前实例
+    pub(crate) version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct GetActiveSetsReq {
+    pub(crate) namespace_id: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct HeartbeatReq {
     pub(crate) namespace_id: String,
     pub(crate) service_id: String,
     pub(crate) instance_id: String,
+    /// 是否返回驱逐通知
+    pub(crate) with_notice: bool,
 }
@@ -1,3 +1,4 @@
+use crate::protocol::{EvictionNotice, Instance};
 use serde::{Deserialize, Serialize};
 
 /// 响应结果
@@ -8,6 +9,22 @@ pub(crate) struct Res<T> {
     pub data: Option<T>,
 }
 
+/// 服务端集群节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ClusterNode {
+    /// 节点地址
+    pub addr: String,
+}
+
+/// 服务可用实例快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct InstancesSnapshot {
+    /// 实例列表的版本，下次监听时使用
+    pub version: String,
+    /// 可用实例
+    pub instances: Vec<Instance>,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub(crate) enum HeartbeatResult {
     /// Ok
@@ -21,6 +38,19 @@ pub(crate) enum HeartbeatResult {
     Unknown,
 }
 
+/// 心跳响应
+///
+/// 旧版本服务端仅返回心跳结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum HeartbeatResponse {
+    Result(HeartbeatResult),
+    WithNotice {
+        result: HeartbeatResult,
+        eviction: Option<EvictionNotice>,
+    },
+}
+
 impl From<String> for HeartbeatResult {
     fn from(s: String) -> Self {
         match s.as_str() {
@@ -30,3 +60,9 @@ impl From<String> for HeartbeatResult {
         }
     }
 }
+
+impl Default for HeartbeatResponse {
+    fn default() -> Self {
+        HeartbeatResponse::Result(HeartbeatResult::Unknown)
+    }
+}
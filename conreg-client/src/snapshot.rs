@@ -0,0 +1,219 @@
+//! 配置本地快照
+//!
+//! 每次成功从配置中心拉取配置后，将原始配置内容保存到本地文件。启动时如果配置中心不可达，
+//! 使用最近一次保存的快照初始化，避免配置中心故障导致服务无法启动。
+//! 使用快照启动后，监听和补偿任务照常运行，配置中心恢复后配置会自动更新。
+
+use crate::conf::ConfigConfig;
+use crate::config::{ConfigContent, ConfigFormat};
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 快照文件内容
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    /// 保存时间，Unix时间戳（秒）
+    saved_at: u64,
+    /// 命名空间
+    namespace: String,
+    /// 标签选择器
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    /// 配置内容，按`config-ids`的顺序
+    configs: Vec<SnapshotConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotConfig {
+    config_id: String,
+    format: ConfigFormat,
+    content: String,
+    md5: String,
+}
+
+/// 配置本地快照
+pub(crate) struct ConfigSnapshot {
+    /// 快照文件路径
+    path: PathBuf,
+    /// 上次保存的配置MD5，配置未变化时不重复写入
+    saved_md5s: Mutex<Option<HashMap<String, String>>>,
+}
+
+impl ConfigSnapshot {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            saved_md5s: Mutex::new(None),
+        }
+    }
+
+    /// 保存快照，失败时仅记录日志
+    ///
+    /// 先写入临时文件再重命名，避免进程退出时留下不完整的快照
+    pub(crate) fn save(
+        &self,
+        config: &ConfigConfig,
+        contents: &[ConfigContent],
+        md5s: &HashMap<String, String>,
+    ) {
+        let mut saved_md5s = self.saved_md5s.lock().unwrap();
+        if saved_md5s.as_ref() == Some(md5s) {
+            return;
+        }
+        match self.write(config, contents, md5s) {
+            Ok(_) => {
+                log::debug!("config snapshot saved to {}", self.path.display());
+                *saved_md5s = Some(md5s.clone());
+            }
+            Err(e) => log::warn!(
+                "save config snapshot to {} failed: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+
+    fn write(
+        &self,
+        config: &ConfigConfig,
+        contents: &[ConfigContent],
+        md5s: &HashMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let snapshot = Snapshot {
+            saved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            namespace: config.namespace.clone(),
+            labels: config.labels.clone(),
+            configs: contents
+                .iter()
+                .map(|item| SnapshotConfig {
+                    config_id: item.config_id.clone(),
+                    format: item.format,
+                    content: item.content.clone(),
+                    md5: md5s.get(&item.config_id).cloned().unwrap_or_default(),
+                })
+                .collect(),
+        };
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_yaml::to_string(&snapshot)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// 读取快照，返回配置内容和配置MD5
+    ///
+    /// 快照的命名空间、标签选择器必须与当前配置一致，且包含所有配置ID
+    pub(crate) fn load(
+        &self,
+        config: &ConfigConfig,
+    ) -> anyhow::Result<(Vec<ConfigContent>, HashMap<String, String>)> {
+        let text = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("read config snapshot {}", self.path.display()))?;
+        let snapshot = serde_yaml::from_str::<Snapshot>(&text)
+            .with_context(|| format!("parse config snapshot {}", self.path.display()))?;
+        if snapshot.namespace != config.namespace || snapshot.labels != config.labels {
+            bail!(
+                "config snapshot {} was saved for a different namespace or labels",
+                self.path.display()
+            );
+        }
+
+        let mut configs = snapshot
+            .configs
+            .into_iter()
+            .map(|item| (item.config_id.clone(), item))
+            .collect::<HashMap<_, _>>();
+        let mut contents = vec![];
+        let mut md5s = HashMap::new();
+        for id in config.config_ids.iter() {
+            let Some(item) = configs.remove(id) else {
+                bail!(
+                    "config id [ {} ] not found in config snapshot {}",
+                    id,
+                    self.path.display()
+                );
+            };
+            md5s.insert(id.clone(), item.md5);
+            contents.push(ConfigContent {
+                config_id: item.config_id,
+                format: item.format,
+                content: item.content,
+            });
+        }
+
+        let age = SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(snapshot.saved_at))
+            .unwrap_or_default();
+        log::warn!(
+            "config loaded from local snapshot {} saved {}s ago, configs may be stale until the config center is reachable",
+            self.path.display(),
+            age.as_secs()
+        );
+        *self.saved_md5s.lock().unwrap() = Some(md5s.clone());
+        Ok((contents, md5s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let path = std::env::temp_dir()
+            .join(format!("conreg-snapshot-{}", fastrand::u64(..)))
+            .join("config.yaml");
+        let config = ConfigConfig {
+            namespace: "public".to_string(),
+            config_ids: vec!["a.yaml".to_string(), "b.properties".to_string()],
+            ..Default::default()
+        };
+        let contents = vec![
+            ConfigContent {
+                config_id: "b.properties".to_string(),
+                format: ConfigFormat::Properties,
+                content: "name=b".to_string(),
+            },
+            ConfigContent {
+                config_id: "a.yaml".to_string(),
+                format: ConfigFormat::Yaml,
+                content: "name: a".to_string(),
+            },
+        ];
+        let md5s = HashMap::from([
+            ("a.yaml".to_string(), "1".to_string()),
+            ("b.properties".to_string(), "2".to_string()),
+        ]);
+
+        let snapshot = ConfigSnapshot::new(path.clone());
+        snapshot.save(&config, &contents, &md5s);
+        let (loaded, loaded_md5s) = ConfigSnapshot::new(path.clone()).load(&config).unwrap();
+        assert_eq!(loaded[0], contents[1]);
+        assert_eq!(loaded[1], contents[0]);
+        assert_eq!(loaded_md5s, md5s);
+
+        let other = ConfigConfig {
+            namespace: "other".to_string(),
+            ..config.clone()
+        };
+        assert!(snapshot.load(&other).is_err());
+        let missing = ConfigConfig {
+            config_ids: vec!["c.yaml".to_string()],
+            ..config
+        };
+        assert!(snapshot.load(&missing).is_err());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,106 @@
+//! 定时任务时钟
+//!
+//! 同一进程内周期相同的定时任务（心跳、实例同步、配置补偿和轮询等）共享一个时钟，
+//! 存在多个服务、多个配置或多个客户端句柄时只有一次唤醒。
+//!
+//! 时钟的首次触发时间在一个周期内随机，之后每次触发间隔附加±10%的随机抖动，
+//! 避免大量实例同时重启后，心跳和拉取请求始终在同一时刻集中到达服务端。
+
+use dashmap::DashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// 随机抖动比例
+const JITTER_RATIO: f64 = 0.1;
+
+/// 共享时钟，key为周期
+///
+/// 只保存弱引用，时钟任务所在的运行时关闭后可重新创建
+static TICKERS: LazyLock<DashMap<Duration, broadcast::WeakSender<()>>> =
+    LazyLock::new(DashMap::new);
+
+/// 在周期上附加±10%的随机抖动
+pub(crate) fn jitter(period: Duration) -> Duration {
+    let max = period.mul_f64(JITTER_RATIO);
+    period - max + max.mul_f64(2.0 * fastrand::f64())
+}
+
+/// 定时器，订阅同周期的共享时钟
+pub(crate) struct Ticker {
+    period: Duration,
+    receiver: broadcast::Receiver<()>,
+}
+
+impl Ticker {
+    pub(crate) fn new(period: Duration) -> Self {
+        Self {
+            period,
+            receiver: Self::subscribe(period),
+        }
+    }
+
+    fn subscribe(period: Duration) -> broadcast::Receiver<()> {
+        let mut weak = TICKERS.entry(period).or_insert_with(|| Self::spawn(period));
+        match weak.upgrade() {
+            Some(sender) => sender.subscribe(),
+            None => {
+                *weak = Self::spawn(period);
+                weak.upgrade()
+                    .map(|sender| sender.subscribe())
+                    .expect("ticker just spawned")
+            }
+        }
+    }
+
+    /// 启动时钟任务，首次触发前随机等待不超过一个周期的时间
+    fn spawn(period: Duration) -> broadcast::WeakSender<()> {
+        let (sender, _) = broadcast::channel(1);
+        let weak = sender.downgrade();
+        tokio::spawn(async move {
+            tokio::time::sleep(period.mul_f64(fastrand::f64())).await;
+            loop {
+                let _ = sender.send(());
+                tokio::time::sleep(jitter(period)).await;
+            }
+        });
+        weak
+    }
+
+    /// 等待下一次触发
+    pub(crate) async fn tick(&mut self) {
+        loop {
+            match self.receiver.recv().await {
+                // 处理不及时错过的触发合并为一次
+                Ok(()) | Err(RecvError::Lagged(_)) => return,
+                // 时钟任务已随运行时关闭，重新订阅
+                Err(RecvError::Closed) => self.receiver = Self::subscribe(self.period),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter() {
+        let period = Duration::from_secs(10);
+        for _ in 0..100 {
+            let jittered = jitter(period);
+            assert!(jittered >= Duration::from_secs(9) && jittered <= Duration::from_secs(11));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_ticker() {
+        let period = Duration::from_millis(50);
+        let mut t1 = Ticker::new(period);
+        let mut t2 = Ticker::new(period);
+        tokio::time::timeout(period * 4, t1.tick()).await.unwrap();
+        // 同一时钟的一次触发会同时唤醒所有订阅者
+        assert!(t2.receiver.try_recv().is_ok());
+    }
+}
@@ -0,0 +1,165 @@
+//! UDP心跳，设置`udp-heartbeat: true`时使用
+//!
+//! 服务端需要通过`--enable-udp-heartbeat`启动，在HTTP服务的端口上监听UDP。
+//! 每次心跳发送一个数据报并等待服务端响应，注册、注销等仍使用HTTP。
+//! 命名空间开启认证时，使用命名空间Token对请求签名。
+
+use crate::conf::ServerAddr;
+use crate::protocol::EvictionNotice;
+use crate::protocol::request::HeartbeatReq;
+use crate::protocol::response::{HeartbeatResponse, HeartbeatResult, Res};
+use anyhow::{Context, bail};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+
+/// 数据报的最大长度
+const MAX_DATAGRAM_SIZE: usize = 2048;
+
+/// UDP心跳请求
+#[derive(Debug, Serialize)]
+struct UdpHeartbeatReq {
+    namespace_id: String,
+    service_id: String,
+    instance_id: String,
+    /// 发送时间，毫秒时间戳
+    timestamp: i64,
+    /// 签名，未设置命名空间Token时为空
+    signature: Option<String>,
+}
+
+impl UdpHeartbeatReq {
+    fn new(req: HeartbeatReq, auth_token: &Option<String>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as i64)
+            .unwrap_or_default();
+        let mut req = UdpHeartbeatReq {
+            namespace_id: req.namespace_id,
+            service_id: req.service_id,
+            instance_id: req.instance_id,
+            timestamp,
+            signature: None,
+        };
+        req.signature = auth_token
+            .as_deref()
+            .map(|token| sign(token, &req.payload()));
+        req
+    }
+
+    /// 参与签名的内容，与服务端一致
+    fn payload(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.namespace_id, self.service_id, self.instance_id, self.timestamp
+        )
+    }
+}
+
+/// 使用命名空间Token计算签名，HMAC-SHA256，十六进制编码
+fn sign(auth_token: &str, payload: &str) -> String {
+    // HMAC支持任意长度的密钥
+    let mut mac = Hmac::<Sha256>::new_from_slice(auth_token.as_bytes()).unwrap();
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// UDP心跳连接
+///
+/// 首次心跳时选择首选的服务端节点并建立连接，出错或超时后关闭，下次心跳时重新建立，
+/// 避免读到之前超时请求的响应
+#[derive(Debug, Default)]
+pub(crate) struct UdpHeartbeat {
+    socket: Mutex<Option<UdpSocket>>,
+}
+
+impl UdpHeartbeat {
+    /// 发送心跳并等待响应，`timeout`内没有收到响应时返回错误
+    pub(crate) async fn heartbeat(
+        &self,
+        server_addr: &ServerAddr,
+        req: HeartbeatReq,
+        auth_token: &Option<String>,
+        timeout: Duration,
+    ) -> anyhow::Result<(HeartbeatResult, Option<EvictionNotice>)> {
+        let datagram = serde_json::to_vec(&UdpHeartbeatReq::new(req, auth_token))?;
+        let mut socket = self.socket.lock().await;
+        let connected = match socket.as_mut() {
+            Some(connected) => connected,
+            None => socket.insert(connect(server_addr).await?),
+        };
+        let res = match tokio::time::timeout(timeout, send(connected, &datagram)).await {
+            Ok(res) => res,
+            Err(_) => Err(anyhow::anyhow!(
+                "UDP heartbeat timed out after {}ms",
+                timeout.as_millis()
+            )),
+        };
+        if res.is_err() {
+            *socket = None;
+        }
+        let res = res?;
+        if res.code != 0 {
+            bail!("{}", res.msg);
+        }
+        Ok(match res.data.unwrap_or_default() {
+            HeartbeatResponse::Result(result) => (result, None),
+            HeartbeatResponse::WithNotice { result, eviction } => (result, eviction),
+        })
+    }
+}
+
+/// 选择首选的服务端节点，建立UDP连接
+async fn connect(server_addr: &ServerAddr) -> anyhow::Result<UdpSocket> {
+    let addresses = server_addr.candidates();
+    let Some(address) = addresses.first() else {
+        bail!("server address not set");
+    };
+    let target = tokio::net::lookup_host(address)
+        .await?
+        .next()
+        .with_context(|| format!("invalid server address: {}", address))?;
+    let local = if target.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(target).await?;
+    Ok(socket)
+}
+
+/// 发送数据报并读取响应
+async fn send(socket: &UdpSocket, datagram: &[u8]) -> anyhow::Result<Res<HeartbeatResponse>> {
+    socket.send(datagram).await?;
+    let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+    let len = socket.recv(&mut buf).await?;
+    Ok(serde_json::from_slice(&buf[..len])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let req = HeartbeatReq {
+            namespace_id: "public".to_string(),
+            service_id: "app".to_string(),
+            instance_id: "1".to_string(),
+            with_notice: true,
+        };
+        let req = UdpHeartbeatReq::new(req, &Some("token".to_string()));
+        assert_eq!(req.payload(), format!("public\napp\n1\n{}", req.timestamp));
+        assert_eq!(req.signature, Some(sign("token", &req.payload())));
+        assert_ne!(req.signature, Some(sign("other", &req.payload())));
+        // 与服务端的签名一致
+        assert_eq!(
+            sign("token", "public\napp\n1\n1700000000000"),
+            "b766a82eae42c9d07d579236cc166622f6eb0714176d9f6a2c3e9c1f0c9cc20b"
+        );
+    }
+}
@@ -34,3 +34,56 @@ pub(crate) fn init_log() {
         .init();
     TRACING_HAS_INIT.store(true, std::sync::atomic::Ordering::Relaxed);
 }
+
+/// 替换文本中的`${NAME}`和`${NAME:default}`占位符
+///
+/// 占位符的值由`lookup`提供，未找到且没有默认值时返回错误
+pub(crate) fn resolve_placeholders(
+    text: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            anyhow::bail!("unclosed placeholder: {}", &rest[start..]);
+        };
+        let expr = &rest[start + 2..start + end];
+        let (name, default) = match expr.split_once(':') {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (expr.trim(), None),
+        };
+        match lookup(name).or_else(|| default.map(String::from)) {
+            Some(value) => result.push_str(&value),
+            None => anyhow::bail!("environment variable {} is not set", name),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_placeholders() {
+        let lookup = |name: &str| (name == "HOST").then(|| "10.0.0.1".to_string());
+        assert_eq!(
+            resolve_placeholders("addr: ${HOST}:${PORT:8000}", lookup).unwrap(),
+            "addr: 10.0.0.1:8000"
+        );
+        assert_eq!(
+            resolve_placeholders("token: ${TOKEN:}", lookup).unwrap(),
+            "token: "
+        );
+        assert_eq!(
+            resolve_placeholders("no placeholder", lookup).unwrap(),
+            "no placeholder"
+        );
+        assert!(resolve_placeholders("token: ${TOKEN}", lookup).is_err());
+        assert!(resolve_placeholders("addr: ${HOST", lookup).is_err());
+    }
+}
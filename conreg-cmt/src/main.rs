@@ -1,10 +1,11 @@
 mod network;
 
 use crate::network::HTTP;
-use crate::network::response::RaftMetrics;
+use crate::network::response::{LintIssue, RaftMetrics};
 use anyhow::bail;
 use clap::{Parser, Subcommand};
 use serde_json::Value;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Parser, Debug)]
@@ -14,6 +15,11 @@ pub struct Args {
     #[arg(required = true, short, long, default_value = "127.0.0.1:8000")]
     server: String,
 
+    /// Cluster secret used to sign requests, printed by "init" or "rotate-secret"
+    /// if not set by `--cluster-secret` on the server
+    #[arg(long)]
+    cluster_secret: Option<String>,
+
     /// Command
     #[command(subcommand)]
     command: Commands,
@@ -53,6 +59,46 @@ enum Commands {
         #[arg(short, long, default_value_t = 5)]
         interval: u64,
     },
+    /// Rotate the cluster secret
+    ///
+    /// The new secret is replicated to all nodes before it is activated, and the old one
+    /// keeps being accepted until the next rotation.
+    RotateSecret {
+        /// New secret, randomly generated if not set
+        #[arg(long)]
+        secret: Option<String>,
+    },
+    /// Back up the configs of all namespaces to the object storage now
+    ///
+    /// The server must be started with "--backup-s3-endpoint".
+    Backup,
+    /// List the config backups in the object storage, from oldest to newest
+    ListBackups,
+    /// Restore configs from a backup, namespaces missing in the cluster are created
+    Restore {
+        /// Backup name, as printed by "backup" or "list-backups"
+        #[arg(required = true)]
+        name: String,
+        /// Namespaces to restore, all namespaces in the backup if not set
+        #[arg(long = "namespace")]
+        namespace_ids: Vec<String>,
+        /// Overwrite existing configs instead of skipping them
+        #[arg(long, default_value_t = false)]
+        overwrite: bool,
+    },
+    /// Check config files before publishing, e.g. for "lb://<service>" references to
+    /// services that don't exist in the namespace
+    Lint {
+        /// Config files to check
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Namespace the configs are published to
+        #[arg(long = "namespace", default_value = "public")]
+        namespace_id: String,
+        /// Namespace token, required if the namespace has authentication enabled
+        #[arg(long)]
+        ns_token: Option<String>,
+    },
 }
 
 fn parse_node(s: &str) -> Result<(u64, String), String> {
@@ -69,6 +115,9 @@ fn parse_node(s: &str) -> Result<(u64, String), String> {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    if let Some(secret) = &args.cluster_secret {
+        network::set_cluster_secret(secret);
+    }
     match &args.command {
         Commands::Init { nodes } => {
             init_cluster(&args.server, nodes).await?;
@@ -89,6 +138,29 @@ async fn main() -> anyhow::Result<()> {
         Commands::Monitor { interval } => {
             monitor_cluster(&args.server, *interval).await?;
         }
+        Commands::RotateSecret { secret } => {
+            rotate_secret(&args.server, secret.as_deref()).await?;
+        }
+        Commands::Backup => {
+            backup(&args.server).await?;
+        }
+        Commands::ListBackups => {
+            list_backups(&args.server).await?;
+        }
+        Commands::Restore {
+            name,
+            namespace_ids,
+            overwrite,
+        } => {
+            restore(&args.server, name, namespace_ids, *overwrite).await?;
+        }
+        Commands::Lint {
+            files,
+            namespace_id,
+            ns_token,
+        } => {
+            lint(&args.server, files, namespace_id, ns_token.as_deref()).await?;
+        }
     }
 
     Ok(())
@@ -98,6 +170,10 @@ fn build_url(server: &str, path: &str) -> String {
     format!("http://{}/api/cluster{}", server, path)
 }
 
+fn build_backup_url(server: &str, path: &str) -> String {
+    format!("http://{}/api/backup{}", server, path)
+}
+
 async fn init_cluster(server: &str, nodes: &Vec<(u64, String)>) -> anyhow::Result<()> {
     println!(
         "initializing cluster, nodes: \n {}",
@@ -216,6 +292,149 @@ async fn get_status(server: &str) -> anyhow::Result<RaftMetrics> {
     }
 }
 
+async fn rotate_secret(server: &str, secret: Option<&str>) -> anyhow::Result<()> {
+    // Rotation must be performed on the leader
+    let status = get_status(server).await?;
+    let Some(leader) = status.current_leader else {
+        bail!(" ❌ Cluster has no leader");
+    };
+    let Some(node) = status
+        .membership_config
+        .membership
+        .nodes
+        .get(&leader.to_string())
+    else {
+        bail!(" ❌ Leader {} not found in cluster members", leader);
+    };
+    println!(
+        "Rotating cluster secret on leader {} ({})",
+        leader, node.addr
+    );
+    match HTTP
+        .post::<String>(
+            build_url(&node.addr, "/rotate-secret"),
+            serde_json::json!({ "secret": secret }),
+        )
+        .await
+    {
+        Ok(secret) => {
+            println!(" ✅ Cluster secret rotated: {}", secret.unwrap_or_default());
+        }
+        Err(e) => {
+            println!(" ❌ Failed to rotate cluster secret: {}", e);
+        }
+    }
+    Ok(())
+}
+
+async fn backup(server: &str) -> anyhow::Result<()> {
+    println!("Backing up configs");
+    match HTTP
+        .post::<String>(build_backup_url(server, "/run"), ())
+        .await
+    {
+        Ok(name) => {
+            println!(" ✅ Backup {} created", name.unwrap_or_default());
+        }
+        Err(e) => {
+            println!(" ❌ Failed to back up configs: {}", e);
+        }
+    }
+    Ok(())
+}
+
+async fn list_backups(server: &str) -> anyhow::Result<()> {
+    match HTTP
+        .get::<Vec<String>>(build_backup_url(server, "/list"), None::<String>)
+        .await
+    {
+        Ok(names) => {
+            let names = names.unwrap_or_default();
+            if names.is_empty() {
+                println!("No backups found");
+            }
+            for name in names {
+                println!("{}", name);
+            }
+        }
+        Err(e) => {
+            println!(" ❌ Failed to list backups: {}", e);
+        }
+    }
+    Ok(())
+}
+
+async fn restore(
+    server: &str,
+    name: &str,
+    namespace_ids: &[String],
+    overwrite: bool,
+) -> anyhow::Result<()> {
+    println!("Restoring configs from backup {}", name);
+    match HTTP
+        .post::<Vec<String>>(
+            build_backup_url(server, "/restore"),
+            serde_json::json!({
+                "name": name,
+                "namespace_ids": namespace_ids,
+                "is_overwrite": overwrite,
+            }),
+        )
+        .await
+    {
+        Ok(restored) => {
+            println!(" ✅ Namespaces {:?} restored", restored.unwrap_or_default());
+        }
+        Err(e) => {
+            println!(" ❌ Failed to restore configs: {}", e);
+        }
+    }
+    Ok(())
+}
+
+async fn lint(
+    server: &str,
+    files: &[PathBuf],
+    namespace_id: &str,
+    ns_token: Option<&str>,
+) -> anyhow::Result<()> {
+    let url = reqwest::Url::parse_with_params(
+        &format!("http://{}/api/config/lint", server),
+        [("namespace_id", namespace_id)],
+    )?;
+    for file in files {
+        let content = std::fs::read_to_string(file)?;
+        match HTTP
+            .post_with_ns_token::<Vec<LintIssue>>(
+                url.clone(),
+                ns_token,
+                serde_json::json!({ "content": content }),
+            )
+            .await
+        {
+            Ok(issues) => {
+                let issues = issues.unwrap_or_default();
+                if issues.is_empty() {
+                    println!(" ✅ {}", file.display());
+                }
+                for issue in issues {
+                    println!(
+                        " ⚠️  {}:{}: {} [{}]",
+                        file.display(),
+                        issue.line,
+                        issue.message,
+                        issue.rule
+                    );
+                }
+            }
+            Err(e) => {
+                println!(" ❌ Failed to check {}: {}", file.display(), e);
+            }
+        }
+    }
+    Ok(())
+}
+
 #[rustfmt::skip]
 fn print_status(metrics: &RaftMetrics) {
     println!("┌────────────────────────────────────────────────────────────────┐");
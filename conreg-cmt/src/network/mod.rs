@@ -1,12 +1,14 @@
 pub(crate) mod response;
 
 use anyhow::bail;
+use hmac::{Hmac, Mac};
 use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fmt::Debug;
-use std::sync::LazyLock;
-use std::time::Duration;
+use std::sync::{LazyLock, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Res<T> {
@@ -15,6 +17,68 @@ pub(crate) struct Res<T> {
     data: Option<T>,
 }
 
+/// Header carrying the signing timestamp
+const CLUSTER_TIMESTAMP_HEADER: &str = "X-Cluster-Timestamp";
+/// Header carrying the single-use signing nonce
+const CLUSTER_NONCE_HEADER: &str = "X-Cluster-Nonce";
+/// Header carrying the request signature
+const CLUSTER_SIGNATURE_HEADER: &str = "X-Cluster-Signature";
+/// Header carrying the namespace token
+const NS_TOKEN_HEADER: &str = "X-NS-Token";
+
+static CLUSTER_SECRET: OnceLock<String> = OnceLock::new();
+
+/// Set the cluster secret used to sign every request
+pub(crate) fn set_cluster_secret(secret: &str) {
+    let _ = CLUSTER_SECRET.set(secret.to_string());
+}
+
+/// Sign the request with
+/// `HMAC-SHA256(secret, "{method}\n{path}?{query}\n{timestamp}\n{nonce}\n{sha256(body)}")`,
+/// the same as the server. Must be called after the query and body are set.
+fn with_cluster_signature(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let Some(secret) = CLUSTER_SECRET.get() else {
+        return builder;
+    };
+    let Some(Ok(request)) = builder.try_clone().map(|builder| builder.build()) else {
+        return builder;
+    };
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .unwrap_or_default();
+    let url = request.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(
+        format!(
+            "{}\n{}\n{}\n{}\n{}",
+            request.method(),
+            target,
+            timestamp,
+            nonce,
+            hex::encode(Sha256::digest(body))
+        )
+        .as_bytes(),
+    );
+    builder
+        .header(CLUSTER_TIMESTAMP_HEADER, timestamp)
+        .header(CLUSTER_NONCE_HEADER, nonce)
+        .header(
+            CLUSTER_SIGNATURE_HEADER,
+            hex::encode(mac.finalize().into_bytes()),
+        )
+}
+
 pub(crate) struct Network {
     client: reqwest::Client,
 }
@@ -35,7 +99,10 @@ impl Network {
         url: impl reqwest::IntoUrl,
         query: impl Serialize + Debug,
     ) -> anyhow::Result<Option<T>> {
-        let response = self.client.get(url).query(&query).send().await?;
+        let url = url.into_url()?;
+        let response = with_cluster_signature(self.client.get(url).query(&query))
+            .send()
+            .await?;
         if response.status() != StatusCode::OK {
             bail!("{}", response.text().await?);
         }
@@ -51,7 +118,30 @@ impl Network {
         url: impl reqwest::IntoUrl,
         body: impl Serialize + Debug,
     ) -> anyhow::Result<Option<T>> {
-        let response = self.client.post(url).json(&body).send().await?;
+        let url = url.into_url()?;
+        let response = with_cluster_signature(self.client.post(url).json(&body))
+            .send()
+            .await?;
+        Self::parse(response).await
+    }
+
+    /// Post to a namespace scoped api, authenticated by the namespace token if set
+    pub async fn post_with_ns_token<T: DeserializeOwned + Debug>(
+        &self,
+        url: impl reqwest::IntoUrl,
+        ns_token: Option<&str>,
+        body: impl Serialize + Debug,
+    ) -> anyhow::Result<Option<T>> {
+        let mut builder = self.client.post(url).json(&body);
+        if let Some(token) = ns_token {
+            builder = builder.header(NS_TOKEN_HEADER, token);
+        }
+        Self::parse(builder.send().await?).await
+    }
+
+    async fn parse<T: DeserializeOwned + Debug>(
+        response: reqwest::Response,
+    ) -> anyhow::Result<Option<T>> {
         if response.status() != StatusCode::OK {
             bail!("{}", response.text().await?);
         }
@@ -79,3 +79,10 @@ pub struct Replication {
     pub leader_id: LeaderId,
     pub index: u64,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub rule: String,
+    pub line: usize,
+    pub message: String,
+}
@@ -649,13 +649,17 @@ fn generate_request_building(
     analysis: &ParamAnalysis,
 ) -> proc_macro2::TokenStream {
     // 生成基础 HTTP 方法调用
-    let method_quote = match http_method {
-        "GET" => quote! { self.lb_client.get(&url).await },
-        "POST" => quote! { self.lb_client.post(&url).await },
-        "PUT" => quote! { self.lb_client.put(&url).await },
-        "DELETE" => quote! { self.lb_client.delete(&url).await },
-        "PATCH" => quote! { self.lb_client.patch(&url).await },
-        _ => quote! { self.lb_client.request(Method::GET, &url).await },
+    let method = match http_method {
+        "POST" => quote! { Method::POST },
+        "PUT" => quote! { Method::PUT },
+        "DELETE" => quote! { Method::DELETE },
+        "PATCH" => quote! { Method::PATCH },
+        _ => quote! { Method::GET },
+    };
+    let method_quote = quote! {
+        self.lb_client
+            .resolve(#method, &url, conreg_client::lb::RequestOptions::default())
+            .await
     };
 
     let header_quote = if !analysis.header_params.is_empty() {
@@ -712,13 +716,17 @@ fn generate_request_building(
     } else {
         quote! {}
     };
+    // 通过负载均衡客户端发送，以记录实例请求统计，并按服务设置的重试策略失败重试
     quote! {
-        #method_quote?
-        #header_quote
-        #body_quote
-        #json_quote
-        #form_quote
-        .send()
+        self.lb_client.execute(
+            #method_quote?
+            .map(|builder| builder
+                #header_quote
+                #body_quote
+                #json_quote
+                #form_quote
+            )
+        )
         .await
         .map_err(|e| {
             crate::FeignError::RequestError(e.to_string())
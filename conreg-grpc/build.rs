@@ -0,0 +1,21 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the vendored protoc so that no system installation is needed
+    let mut config = tonic_prost_build::Config::new();
+    config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+    // The vendored protoc ships the well-known types imported by the Envoy protos
+    let include = protoc_bin_vendored::include_path()?;
+    let include = include.to_str().ok_or("invalid protoc include path")?;
+    tonic_prost_build::configure().compile_with_config(
+        config,
+        &[
+            "proto/conreg.proto",
+            "proto/envoy/service/discovery/v3/discovery.proto",
+            "proto/envoy/service/cluster/v3/cds.proto",
+            "proto/envoy/service/endpoint/v3/eds.proto",
+            "proto/envoy/config/cluster/v3/cluster.proto",
+            "proto/envoy/config/endpoint/v3/endpoint.proto",
+        ],
+        &["proto", include],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! # Conreg gRPC
+//!
+//! gRPC protocol shared by [conreg-server](https://github.com/xgpxg/conreg) and
+//! [conreg-client](https://docs.rs/conreg-client), generated from `proto/conreg.proto`.
+//!
+//! The server serves it on the HTTP port + [`GRPC_PORT_OFFSET`] when started with `--enable-grpc`.
+//!
+//! The [`envoy`] module holds the subset of the Envoy xDS v3 API served on the same port when
+//! the server is started with `--enable-xds`, generated from `proto/envoy`.
+
+/// Offset of the gRPC port from the HTTP port of a server
+pub const GRPC_PORT_OFFSET: u16 = 1000;
+
+/// Metadata key of the namespace token
+pub const NS_TOKEN_METADATA: &str = "x-ns-token";
+
+tonic::include_proto!("conreg");
+
+/// Subset of the Envoy xDS v3 API, wire compatible with Envoy
+pub mod envoy {
+    pub mod config {
+        pub mod core {
+            pub mod v3 {
+                tonic::include_proto!("envoy.config.core.v3");
+            }
+        }
+        pub mod cluster {
+            pub mod v3 {
+                tonic::include_proto!("envoy.config.cluster.v3");
+            }
+        }
+        pub mod endpoint {
+            pub mod v3 {
+                tonic::include_proto!("envoy.config.endpoint.v3");
+            }
+        }
+    }
+    pub mod service {
+        pub mod discovery {
+            pub mod v3 {
+                tonic::include_proto!("envoy.service.discovery.v3");
+            }
+        }
+        pub mod cluster {
+            pub mod v3 {
+                tonic::include_proto!("envoy.service.cluster.v3");
+            }
+        }
+        pub mod endpoint {
+            pub mod v3 {
+                tonic::include_proto!("envoy.service.endpoint.v3");
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! 访问日志
+//!
+//! 记录HTTP请求的方法、路径、命名空间、客户端IP、耗时和状态码，
+//! 以JSON行的格式写入`{data_dir}/logs/access.log`，与应用日志分开，便于流量分析和滥用排查。
+//!
+//! 支持按比例采样和按路径前缀排除，状态码>=400的请求不参与采样，始终记录。
+
+use crate::Args;
+use chrono::Local;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::log;
+
+/// 写入队列容量，队列满时丢弃日志，避免影响请求处理
+const QUEUE_CAPACITY: usize = 10000;
+
+#[derive(Debug, Serialize)]
+struct AccessLogEntry {
+    time: String,
+    method: String,
+    path: String,
+    namespace_id: Option<String>,
+    client_ip: Option<String>,
+    status: u16,
+    /// 耗时，单位毫秒
+    latency_ms: f64,
+}
+
+/// 请求开始时间
+struct RequestStart(Instant);
+
+pub struct AccessLog {
+    /// 采样率，0~1
+    sample_rate: f64,
+    /// 排除的路径前缀
+    exclude: Vec<String>,
+    /// 已参与采样的请求数
+    counter: AtomicU64,
+    sender: mpsc::Sender<String>,
+}
+
+impl AccessLog {
+    /// 创建访问日志，未开启时返回None
+    pub fn new(args: &Args) -> anyhow::Result<Option<Self>> {
+        if !args.access_log {
+            return Ok(None);
+        }
+        let path = Path::new(&args.data_dir).join("logs").join("access.log");
+        std::fs::create_dir_all(path.parent().unwrap())?;
+
+        let (sender, mut receiver) = mpsc::channel::<String>(QUEUE_CAPACITY);
+        tokio::spawn(async move {
+            let file = match tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await
+            {
+                Ok(file) => file,
+                Err(e) => {
+                    log::error!("Failed to open access log {}: {}", path.display(), e);
+                    return;
+                }
+            };
+            let mut writer = tokio::io::BufWriter::new(file);
+            while let Some(line) = receiver.recv().await {
+                if let Err(e) = writer.write_all(line.as_bytes()).await {
+                    log::error!("Failed to write access log: {}", e);
+                }
+                // 队列中没有待写入的日志时再刷盘，减少系统调用
+                if receiver.is_empty()
+                    && let Err(e) = writer.flush().await
+                {
+                    log::error!("Failed to flush access log: {}", e);
+                }
+            }
+        });
+
+        Ok(Some(Self {
+            sample_rate: args.access_log_sample_rate.clamp(0f64, 1f64),
+            exclude: args.access_log_exclude.clone(),
+            counter: AtomicU64::new(0),
+            sender,
+        }))
+    }
+
+    /// 是否采样当前请求
+    ///
+    /// 按计数均匀采样：第n个请求在`floor(n * rate)`发生变化时被采样
+    fn sample(&self) -> bool {
+        if self.sample_rate >= 1f64 {
+            return true;
+        }
+        let n = self.counter.fetch_add(1, Ordering::Relaxed);
+        ((n + 1) as f64 * self.sample_rate).floor() > (n as f64 * self.sample_rate).floor()
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for AccessLog {
+    fn info(&self) -> Info {
+        Info {
+            name: "Access Log",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let path = req.uri().path().as_str();
+        if self.exclude.iter().any(|prefix| path.starts_with(prefix)) {
+            return;
+        }
+        let status = res.status().code;
+        if status < 400 && !self.sample() {
+            return;
+        }
+
+        let start = req.local_cache(|| RequestStart(Instant::now()));
+        let entry = AccessLogEntry {
+            time: Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            method: req.method().to_string(),
+            path: path.to_string(),
+            namespace_id: req
+                .query_value::<String>("namespace_id")
+                .and_then(|v| v.ok()),
+            client_ip: req.client_ip().map(|ip| ip.to_string()),
+            status,
+            latency_ms: start.0.elapsed().as_micros() as f64 / 1000f64,
+        };
+        match serde_json::to_string(&entry) {
+            Ok(mut line) => {
+                line.push('\n');
+                if self.sender.try_send(line).is_err() {
+                    log::debug!("access log queue full, entry dropped");
+                }
+            }
+            Err(e) => log::error!("Failed to serialize access log: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let access_log = AccessLog {
+            sample_rate: 0.1,
+            exclude: vec![],
+            counter: AtomicU64::new(0),
+            sender,
+        };
+        let sampled = (0..1000).filter(|_| access_log.sample()).count();
+        assert_eq!(sampled, 100);
+    }
+}
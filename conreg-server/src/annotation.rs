@@ -0,0 +1,260 @@
+//! 配置变更标注
+//!
+//! 将配置的发布、回滚和晋级事件推送到可观测性平台，便于在监控面板上将配置变更与延迟等指标叠加对比：
+//! - `--annotation-otlp-url`：OTLP/HTTP日志接收地址（JSON编码），如`http://otel-collector:4318/v1/logs`，
+//!   每次变更作为一条带事件名的日志记录上报
+//! - `--annotation-grafana-url`：Grafana地址，变更通过`POST /api/annotations`写入为标注，
+//!   需同时通过`--annotation-grafana-token`指定Service Account Token
+//!
+//! 标注仅在处理请求的节点上发送一次，发送失败时仅记录日志，不重试。
+
+use crate::Args;
+use chrono::Local;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::log;
+
+/// 请求超时时间
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+static ANNOTATOR: OnceLock<Annotator> = OnceLock::new();
+
+/// 变更动作
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, strum_macros::IntoStaticStr)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ChangeAction {
+    /// 发布
+    Publish,
+    /// 从历史版本回滚
+    Rollback,
+    /// 从其他命名空间晋级
+    Promote,
+}
+
+/// 内容差异摘要，按行统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DiffSummary {
+    /// 新增的行数
+    pub added: usize,
+    /// 删除的行数
+    pub removed: usize,
+}
+
+impl DiffSummary {
+    /// 统计两个版本之间增加和删除的行数，不考虑行的顺序
+    pub fn compute(old: &str, new: &str) -> Self {
+        let mut lines = HashMap::<&str, isize>::new();
+        for line in old.lines() {
+            *lines.entry(line).or_default() -= 1;
+        }
+        for line in new.lines() {
+            *lines.entry(line).or_default() += 1;
+        }
+        let mut summary = Self::default();
+        for count in lines.into_values() {
+            if count > 0 {
+                summary.added += count as usize;
+            } else {
+                summary.removed += count.unsigned_abs();
+            }
+        }
+        summary
+    }
+}
+
+/// 配置变更标注
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigAnnotation {
+    pub action: ChangeAction,
+    pub namespace_id: String,
+    pub config_id: String,
+    /// 操作人
+    pub operator: String,
+    /// 变更后的配置MD5
+    pub md5: String,
+    pub diff: DiffSummary,
+}
+
+impl ConfigAnnotation {
+    /// 标注的文本描述
+    fn text(&self) -> String {
+        format!(
+            "config {} {}/{} by {} (+{} -{} lines)",
+            <&str>::from(self.action),
+            self.namespace_id,
+            self.config_id,
+            self.operator,
+            self.diff.added,
+            self.diff.removed
+        )
+    }
+
+    /// 转换为OTLP日志请求
+    fn to_otlp(&self, node_id: u64, time_nanos: i64) -> Value {
+        let string = |key: &str, value: &str| json!({"key": key, "value": {"stringValue": value}});
+        let int =
+            |key: &str, value: usize| json!({"key": key, "value": {"intValue": value.to_string()}});
+        json!({
+            "resourceLogs": [{
+                "resource": {
+                    "attributes": [
+                        string("service.name", "conreg-server"),
+                        string("service.instance.id", &node_id.to_string()),
+                    ],
+                },
+                "scopeLogs": [{
+                    "scope": {"name": "conreg"},
+                    "logRecords": [{
+                        "timeUnixNano": time_nanos.to_string(),
+                        "severityNumber": 9,
+                        "severityText": "INFO",
+                        "eventName": format!("conreg.config.{}", <&str>::from(self.action)),
+                        "body": {"stringValue": self.text()},
+                        "attributes": [
+                            string("conreg.namespace_id", &self.namespace_id),
+                            string("conreg.config_id", &self.config_id),
+                            string("conreg.operator", &self.operator),
+                            string("conreg.md5", &self.md5),
+                            int("conreg.diff.added", self.diff.added),
+                            int("conreg.diff.removed", self.diff.removed),
+                        ],
+                    }],
+                }],
+            }],
+        })
+    }
+
+    /// 转换为Grafana标注请求
+    fn to_grafana(&self, time_millis: i64) -> Value {
+        json!({
+            "time": time_millis,
+            "tags": [
+                "conreg",
+                <&str>::from(self.action),
+                format!("namespace:{}", self.namespace_id),
+                format!("config:{}", self.config_id),
+            ],
+            "text": self.text(),
+        })
+    }
+}
+
+struct Annotator {
+    otlp_url: Option<String>,
+    grafana_url: Option<String>,
+    grafana_token: Option<String>,
+    node_id: u64,
+    client: reqwest::Client,
+}
+
+pub fn init(args: &Args) -> anyhow::Result<()> {
+    if args.annotation_grafana_url.is_some() && args.annotation_grafana_token.is_none() {
+        log::warn!("annotation_grafana_token is not set, grafana annotations may be rejected");
+    }
+    let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+    let _ = ANNOTATOR.set(Annotator {
+        otlp_url: args.annotation_otlp_url.clone(),
+        grafana_url: args
+            .annotation_grafana_url
+            .as_ref()
+            .map(|url| format!("{}/api/annotations", url.trim_end_matches('/'))),
+        grafana_token: args.annotation_grafana_token.clone(),
+        node_id: args.node_id,
+        client,
+    });
+    Ok(())
+}
+
+/// 异步发送配置变更标注
+pub fn send(annotation: ConfigAnnotation) {
+    let Some(annotator) = ANNOTATOR.get() else {
+        return;
+    };
+    if annotator.otlp_url.is_none() && annotator.grafana_url.is_none() {
+        return;
+    }
+    tokio::spawn(async move {
+        let now = Local::now();
+        if let Some(url) = &annotator.otlp_url {
+            let body = annotation.to_otlp(
+                annotator.node_id,
+                now.timestamp_nanos_opt().unwrap_or_default(),
+            );
+            post(url, annotator.client.post(url).json(&body)).await;
+        }
+        if let Some(url) = &annotator.grafana_url {
+            let mut request = annotator
+                .client
+                .post(url)
+                .json(&annotation.to_grafana(now.timestamp_millis()));
+            if let Some(token) = &annotator.grafana_token {
+                request = request.bearer_auth(token);
+            }
+            post(url, request).await;
+        }
+    });
+}
+
+async fn post(url: &str, request: reqwest::RequestBuilder) {
+    match request.send().await {
+        Ok(res) if res.status().is_success() => {
+            log::debug!("annotation sent to {}", url);
+        }
+        Ok(res) => log::warn!("annotation {} responded with {}", url, res.status()),
+        Err(e) => log::warn!("annotation {} send error: {}", url, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_summary() {
+        let diff = DiffSummary::compute("a: 1\nb: 2\nc: 3", "a: 1\nb: 20\nc: 3\nd: 4");
+        assert_eq!(
+            diff,
+            DiffSummary {
+                added: 2,
+                removed: 1
+            }
+        );
+        assert_eq!(
+            DiffSummary::compute("", "a: 1"),
+            DiffSummary {
+                added: 1,
+                removed: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_otlp_payload() {
+        let annotation = ConfigAnnotation {
+            action: ChangeAction::Rollback,
+            namespace_id: "public".to_string(),
+            config_id: "app.yaml".to_string(),
+            operator: "conreg".to_string(),
+            md5: "abc".to_string(),
+            diff: DiffSummary {
+                added: 1,
+                removed: 2,
+            },
+        };
+        let body = annotation.to_otlp(1, 0);
+        let record = &body["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["eventName"], "conreg.config.rollback");
+        assert_eq!(
+            record["body"]["stringValue"],
+            "config rollback public/app.yaml by conreg (+1 -2 lines)"
+        );
+        assert_eq!(
+            annotation.to_grafana(0)["tags"],
+            json!(["conreg", "rollback", "namespace:public", "config:app.yaml"])
+        );
+    }
+}
@@ -1,9 +1,10 @@
+use crate::bootstrap::BootstrapApp;
 use crate::config::ConfigApp;
 use crate::discovery::DiscoveryApp;
 use crate::namespace::NamespaceApp;
 use crate::raft::store::StateMachineData;
 use crate::raft::{LogStore, Network, NodeId, Raft, StateMachine};
-use crate::{Args, config, discovery, namespace, raft};
+use crate::{Args, bootstrap, config, discovery, namespace, raft};
 use anyhow::Context;
 use clap::Parser;
 use openraft::Config;
@@ -20,6 +21,10 @@ pub struct App {
     pub addr: String,
     /// Raft协议
     pub raft: Raft,
+    /// 日志存储
+    pub log_store: LogStore,
+    /// 是否以只读模式启动
+    pub read_only: bool,
     /// 状态机
     /// 注意这个需要共享状态，Raft应用log后会修改这个，在读取数据时，也从这里读
     pub state_machine: Arc<RwLock<StateMachineData>>,
@@ -32,6 +37,8 @@ pub struct App {
     pub namespace_app: NamespaceApp,
     /// 服务发现
     pub discovery_app: DiscoveryApp,
+    /// 客户端启动配置
+    pub bootstrap_app: BootstrapApp,
 }
 
 impl App {
@@ -40,6 +47,8 @@ impl App {
             heartbeat_interval: 500,
             election_timeout_min: 1500,
             election_timeout_max: 3000,
+            // 只读节点不参与选举，避免成为Leader后拒绝其他节点转发的写请求
+            enable_elect: !args.read_only,
             ..Default::default()
         };
 
@@ -79,15 +88,21 @@ impl App {
         // 服务发现实例
         let discovery_app = discovery::new_discovery_app(args).await;
 
+        // 客户端启动配置实例
+        let bootstrap_app = bootstrap::new_bootstrap_app(args).await;
+
         App {
             id: args.node_id,
             addr,
             raft,
+            log_store,
+            read_only: args.read_only,
             state_machine,
             other: Arc::new(Default::default()),
             config_app,
             namespace_app,
             discovery_app,
+            bootstrap_app,
         }
     }
 }
@@ -107,6 +122,8 @@ pub fn get_app() -> &'static App {
 impl App {
     /// 退出前清理资源
     pub fn clean(&self) {
+        // 保存配置变更日志，重启后客户端可以补齐变更
+        self.config_app.manager.save_journal();
         block_on(async {
             // 保存状态机快照
             if let Err(e) = self.raft.trigger().snapshot().await {
@@ -0,0 +1,549 @@
+//! 节点间鉴权
+//!
+//! 集群节点通过共享密钥相互鉴权。节点间的Raft通信、写请求转发，以及`/api/cluster`下会修改集群状态的接口，
+//! 都需要在请求头中携带签名：
+//! - `X-Cluster-Timestamp`：Unix时间戳（秒），与服务端时间相差不能超过[`MAX_CLOCK_SKEW_SECS`]
+//! - `X-Cluster-Nonce`：随机字符串，同一个值在签名有效期内只能使用一次，防止重放
+//! - `X-Cluster-Signature`：`HMAC-SHA256(密钥, "{method}\n{path}?{query}\n{timestamp}\n{nonce}\n{sha256(body)}")`的十六进制编码，
+//!   没有查询参数时不包含`?{query}`，请求体的摘要为十六进制编码
+//!
+//! 带请求体的接口通过[`ClusterJson`]读取请求体后校验签名，其他接口使用[`ClusterAuth`]，按空请求体校验。
+//!
+//! 密钥在初始化集群时生成（或使用`--cluster-secret`指定的值），通过Raft保存在状态机中，
+//! 所有节点一致。密钥可通过`POST /api/cluster/rotate-secret`轮换，轮换分两步：
+//! 1. 写入新密钥，各节点开始接受新密钥的签名，但仍使用旧密钥签名
+//! 2. 新密钥复制到所有节点后启用新密钥，旧密钥在下次轮换前仍被接受
+//!
+//! 这样轮换过程中不会出现节点因不认识对方的签名而无法通信的情况。
+//!
+//! `--cluster-secret`在集群未保存密钥前用于签名，之后仅作为额外可接受的密钥，
+//! 可用于恢复在轮换期间离线、未能获取新密钥的节点。未设置任何密钥时不校验，以兼容旧版本的部署方式。
+
+use crate::Args;
+use crate::Mode;
+use crate::app::get_app;
+use crate::raft::RaftRequest;
+use crate::raft::api::raft_write;
+use anyhow::bail;
+use hmac::{Hmac, Mac};
+use moka::sync::Cache;
+use rocket::data::{self, Data, FromData, Limits};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, outcome};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::sync::{LazyLock, OnceLock, RwLock};
+use std::time::Duration;
+use tracing::log;
+
+/// 签名时间戳的请求头
+pub const CLUSTER_TIMESTAMP_HEADER: &str = "X-Cluster-Timestamp";
+/// 签名随机数的请求头
+pub const CLUSTER_NONCE_HEADER: &str = "X-Cluster-Nonce";
+/// 签名的请求头
+pub const CLUSTER_SIGNATURE_HEADER: &str = "X-Cluster-Signature";
+/// 签名时间戳与服务端时间的最大偏差，单位秒
+pub const MAX_CLOCK_SKEW_SECS: i64 = 300;
+
+/// 轮换密钥时等待新密钥复制到所有节点的超时时间
+const REPLICATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 已使用的签名随机数的最大数量
+const MAX_NONCES: u64 = 200_000;
+
+/// 状态机中集群内部数据key的前缀，这些数据不能通过`/api/cluster/read`读取
+const CLUSTER_KEY_PREFIX: &str = "cluster:";
+/// 状态机中当前密钥的key
+const SECRET_KEY: &str = "cluster:secret";
+/// 状态机中上一个密钥的key
+const PREVIOUS_SECRET_KEY: &str = "cluster:secret:previous";
+/// 状态机中待启用密钥的key
+const NEXT_SECRET_KEY: &str = "cluster:secret:next";
+
+/// 集群密钥，调试输出时隐藏内容
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct ClusterSecret(String);
+
+impl ClusterSecret {
+    /// 生成随机密钥
+    pub fn generate() -> Self {
+        Self(format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for ClusterSecret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl Debug for ClusterSecret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ClusterSecret(***)")
+    }
+}
+
+/// 本节点已知的密钥
+#[derive(Debug, Default)]
+struct Secrets {
+    /// 启动参数指定的密钥
+    bootstrap: Option<String>,
+    /// 集群当前密钥
+    current: Option<String>,
+    /// 上一个密钥
+    previous: Option<String>,
+    /// 轮换中待启用的密钥
+    next: Option<String>,
+}
+
+impl Secrets {
+    /// 签名使用的密钥
+    fn signing(&self) -> Option<&str> {
+        self.current.as_deref().or(self.bootstrap.as_deref())
+    }
+
+    /// 可接受的密钥，为空时不校验
+    fn accepted(&self) -> Vec<&str> {
+        [&self.current, &self.next, &self.previous, &self.bootstrap]
+            .into_iter()
+            .filter_map(|secret| secret.as_deref())
+            .collect()
+    }
+}
+
+static SECRETS: OnceLock<RwLock<Secrets>> = OnceLock::new();
+
+/// 签名有效期内已使用的随机数，时间戳前后各允许[`MAX_CLOCK_SKEW_SECS`]，因此保留两倍的时间
+static NONCES: LazyLock<Cache<String, ()>> = LazyLock::new(|| {
+    Cache::builder()
+        .max_capacity(MAX_NONCES)
+        .time_to_live(Duration::from_secs(2 * MAX_CLOCK_SKEW_SECS as u64))
+        .build()
+});
+
+fn secrets() -> &'static RwLock<Secrets> {
+    SECRETS.get_or_init(Default::default)
+}
+
+/// 初始化启动参数指定的密钥
+pub fn init(args: &Args) -> anyhow::Result<()> {
+    let secret = args
+        .cluster_secret
+        .as_ref()
+        .map(|secret| secret.trim().to_string());
+    if secret.as_ref().is_some_and(|secret| secret.is_empty()) {
+        anyhow::bail!("Cluster secret cannot be empty");
+    }
+    let mut secrets = secrets().write().unwrap();
+    if secret.is_none() && secrets.current.is_none() && matches!(args.mode, Mode::Cluster) {
+        log::warn!(
+            "cluster secret is not set, cluster APIs are accessible without authentication until the cluster is initialized"
+        );
+    }
+    secrets.bootstrap = secret;
+    Ok(())
+}
+
+/// 启动参数指定的密钥
+pub fn bootstrap_secret() -> Option<ClusterSecret> {
+    secrets()
+        .read()
+        .unwrap()
+        .bootstrap
+        .as_deref()
+        .map(ClusterSecret::from)
+}
+
+/// 是否为集群内部数据（如集群密钥）的key
+pub fn is_cluster_key(key: &str) -> bool {
+    key.starts_with(CLUSTER_KEY_PREFIX)
+}
+
+/// 从状态机数据中加载密钥，在加载快照后调用
+pub fn load(data: &BTreeMap<String, String>) {
+    let mut secrets = secrets().write().unwrap();
+    secrets.current = data.get(SECRET_KEY).cloned();
+    secrets.previous = data.get(PREVIOUS_SECRET_KEY).cloned();
+    secrets.next = data.get(NEXT_SECRET_KEY).cloned();
+}
+
+/// 应用轮换的第一步：保存待启用的密钥
+pub fn apply_prepare(data: &mut BTreeMap<String, String>, secret: &ClusterSecret) {
+    data.insert(NEXT_SECRET_KEY.to_string(), secret.0.clone());
+    load(data);
+    log::info!("cluster secret prepared");
+}
+
+/// 应用轮换的第二步：启用待启用的密钥，当前密钥转为上一个密钥
+pub fn apply_activate(data: &mut BTreeMap<String, String>) {
+    let Some(next) = data.remove(NEXT_SECRET_KEY) else {
+        log::warn!("no prepared cluster secret to activate");
+        return;
+    };
+    match data.insert(SECRET_KEY.to_string(), next) {
+        Some(current) => data.insert(PREVIOUS_SECRET_KEY.to_string(), current),
+        None => data.remove(PREVIOUS_SECRET_KEY),
+    };
+    load(data);
+    log::info!("cluster secret activated");
+}
+
+/// 轮换集群密钥，只能在Leader节点执行，返回新密钥
+///
+/// 先写入待启用的密钥，等待其复制到所有节点后再启用。有节点不可用时无法完成复制，
+/// 此时新密钥保持待启用状态，节点恢复后可再次轮换。
+pub async fn rotate(secret: ClusterSecret) -> anyhow::Result<ClusterSecret> {
+    let app = get_app();
+    let metrics = app.raft.metrics().borrow().clone();
+    if metrics.current_leader != Some(app.id) {
+        let leader = metrics
+            .current_leader
+            .and_then(|id| metrics.membership_config.membership().get_node(&id))
+            .map(|node| node.addr.clone());
+        bail!(
+            "cluster secret can only be rotated on the leader node, current leader: {}",
+            leader.as_deref().unwrap_or("none")
+        );
+    }
+
+    let res = raft_write(RaftRequest::PrepareClusterSecret {
+        secret: secret.clone(),
+    })
+    .await;
+    let Some(response) = res.data else {
+        bail!("failed to prepare cluster secret: {}", res.msg);
+    };
+    let index = response.log_id.index;
+    app.raft
+        .wait(Some(REPLICATION_TIMEOUT))
+        .metrics(
+            |m| {
+                m.replication.as_ref().is_some_and(|replication| {
+                    replication
+                        .values()
+                        .all(|matched| matched.is_some_and(|log_id| log_id.index >= index))
+                })
+            },
+            "cluster secret replicated to all nodes",
+        )
+        .await?;
+    // 复制完成后各节点还需应用日志，稍作等待，避免启用后部分节点尚不认识新密钥
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let res = raft_write(RaftRequest::ActivateClusterSecret).await;
+    if !res.is_success() {
+        bail!("failed to activate cluster secret: {}", res.msg);
+    }
+    Ok(secret)
+}
+
+/// 请求中参与签名的内容
+struct SignedRequest<'a> {
+    method: &'a str,
+    /// 路径及查询参数，如`/api/config/md5?namespace_id=public&id=app.yaml`
+    target: String,
+    timestamp: Option<&'a str>,
+    nonce: Option<&'a str>,
+    signature: Option<&'a str>,
+    body: &'a [u8],
+}
+
+impl<'a> SignedRequest<'a> {
+    fn from_request(req: &'a Request<'_>, body: &'a [u8]) -> Self {
+        let uri = req.uri();
+        let target = match uri.query() {
+            Some(query) => format!("{}?{}", uri.path(), query),
+            None => uri.path().to_string(),
+        };
+        let headers = req.headers();
+        SignedRequest {
+            method: req.method().as_str(),
+            target,
+            timestamp: headers.get_one(CLUSTER_TIMESTAMP_HEADER),
+            nonce: headers.get_one(CLUSTER_NONCE_HEADER),
+            signature: headers.get_one(CLUSTER_SIGNATURE_HEADER),
+            body,
+        }
+    }
+}
+
+/// 待签名的内容
+fn message(method: &str, target: &str, timestamp: i64, nonce: &str, body: &[u8]) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        method,
+        target,
+        timestamp,
+        nonce,
+        hex::encode(Sha256::digest(body))
+    )
+}
+
+/// 计算签名
+fn sign(secret: &str, message: &str) -> String {
+    let mut mac = new_mac(secret, message);
+    hex::encode(mac.finalize_reset().into_bytes())
+}
+
+fn new_mac(secret: &str, message: &str) -> Hmac<Sha256> {
+    // HMAC可以接受任意长度的密钥
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(message.as_bytes());
+    mac
+}
+
+/// 为发往其他节点的请求添加签名，需要在设置查询参数和请求体之后调用
+///
+/// 请求体为流时无法计算摘要，不添加签名
+pub fn with_cluster_signature(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let secrets = secrets().read().unwrap();
+    let Some(secret) = secrets.signing() else {
+        return builder;
+    };
+    let Some(Ok(request)) = builder.try_clone().map(|builder| builder.build()) else {
+        return builder;
+    };
+    let Some(body) = request.body().map_or(Some(&[][..]), |body| body.as_bytes()) else {
+        return builder;
+    };
+    let url = request.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let timestamp = chrono::Utc::now().timestamp();
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let message = message(request.method().as_str(), &target, timestamp, &nonce, body);
+    builder
+        .header(CLUSTER_TIMESTAMP_HEADER, timestamp)
+        .header(CLUSTER_NONCE_HEADER, &nonce)
+        .header(CLUSTER_SIGNATURE_HEADER, sign(secret, &message))
+}
+
+/// 校验签名，未设置任何密钥时总是通过，签名通过后记录随机数，同一随机数再次使用时不通过
+fn verify(req: &SignedRequest, now: i64) -> bool {
+    let secrets = secrets().read().unwrap();
+    let accepted = secrets.accepted();
+    if accepted.is_empty() {
+        return true;
+    }
+    let (Some(timestamp), Some(nonce), Some(signature)) = (
+        req.timestamp.and_then(|t| t.parse::<i64>().ok()),
+        req.nonce.filter(|nonce| !nonce.is_empty()),
+        req.signature.and_then(|s| hex::decode(s).ok()),
+    ) else {
+        return false;
+    };
+    if (now - timestamp).abs() > MAX_CLOCK_SKEW_SECS {
+        return false;
+    }
+    let message = message(req.method, &req.target, timestamp, nonce, req.body);
+    let signed = accepted
+        .into_iter()
+        .any(|secret| new_mac(secret, &message).verify_slice(&signature).is_ok());
+    signed && NONCES.entry(nonce.to_string()).or_insert(()).is_fresh()
+}
+
+/// 校验请求的签名，`body`为请求体
+fn verify_request(req: &Request<'_>, body: &[u8]) -> bool {
+    let verified = verify(
+        &SignedRequest::from_request(req, body),
+        chrono::Utc::now().timestamp(),
+    );
+    if !verified {
+        log::warn!(
+            "rejected unauthenticated cluster request {} from {:?}",
+            req.uri(),
+            req.client_ip()
+        );
+    }
+    verified
+}
+
+/// 节点间鉴权，即请求头携带`X-Cluster-Timestamp`、`X-Cluster-Nonce`和`X-Cluster-Signature`
+///
+/// 按空请求体校验签名，带请求体的接口使用[`ClusterJson`]
+pub struct ClusterAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClusterAuth {
+    type Error = &'r str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if verify_request(req, &[]) {
+            Outcome::Success(ClusterAuth)
+        } else {
+            Outcome::Error((Status::Unauthorized, "Invalid Cluster Signature"))
+        }
+    }
+}
+
+/// 节点间鉴权的Json请求体，签名包含请求体的摘要
+pub struct ClusterJson<T>(pub T);
+
+impl<T> ClusterJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ClusterJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for ClusterJson<T> {
+    type Error = &'static str;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = req.limits().get("json").unwrap_or(Limits::JSON);
+        let body = match data.open(limit).into_bytes().await {
+            Ok(body) if body.is_complete() => body.into_inner(),
+            Ok(_) => {
+                log::warn!("cluster request {} exceeds {}", req.uri(), limit);
+                return outcome::Outcome::Error((Status::PayloadTooLarge, "Payload Too Large"));
+            }
+            Err(e) => {
+                log::warn!("read cluster request {} error: {}", req.uri(), e);
+                return outcome::Outcome::Error((Status::BadRequest, "Bad Request"));
+            }
+        };
+        if !verify_request(req, &body) {
+            return outcome::Outcome::Error((Status::Unauthorized, "Invalid Cluster Signature"));
+        }
+        match serde_json::from_slice(&body) {
+            Ok(value) => outcome::Outcome::Success(ClusterJson(value)),
+            Err(e) => {
+                log::warn!("parse cluster request {} error: {}", req.uri(), e);
+                outcome::Outcome::Error((Status::UnprocessableEntity, "Invalid Json"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "/api/cluster/append?force=true";
+    const BODY: &[u8] = br#"{"entries":[]}"#;
+
+    /// 测试共用全局的密钥，串行执行
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn request<'a>(
+        timestamp: &'a str,
+        nonce: &'a str,
+        signature: &'a str,
+        body: &'a [u8],
+    ) -> SignedRequest<'a> {
+        SignedRequest {
+            method: "POST",
+            target: TARGET.to_string(),
+            timestamp: Some(timestamp),
+            nonce: Some(nonce),
+            signature: Some(signature),
+            body,
+        }
+    }
+
+    #[test]
+    fn test_rotate() {
+        let _lock = LOCK.lock().unwrap();
+        let mut data = BTreeMap::new();
+        let first = ClusterSecret::from("first");
+        apply_prepare(&mut data, &first);
+        apply_activate(&mut data);
+        assert_eq!(data.get(SECRET_KEY).map(String::as_str), Some("first"));
+        assert!(!data.contains_key(PREVIOUS_SECRET_KEY));
+
+        let now = 1_700_000_000;
+        let ts = now.to_string();
+        let signature = sign("first", &message("POST", TARGET, now, "rotate-1", BODY));
+        assert!(verify(&request(&ts, "rotate-1", &signature, BODY), now));
+        // 签名过期或缺少签名
+        let expired = sign("first", &message("POST", TARGET, now, "rotate-2", BODY));
+        assert!(!verify(
+            &request(&ts, "rotate-2", &expired, BODY),
+            now + MAX_CLOCK_SKEW_SECS + 1
+        ));
+        let unsigned = SignedRequest {
+            timestamp: None,
+            nonce: None,
+            signature: None,
+            ..request(&ts, "", "", BODY)
+        };
+        assert!(!verify(&unsigned, now));
+
+        // 轮换中新旧密钥均可接受，签名仍使用旧密钥
+        apply_prepare(&mut data, &ClusterSecret::from("second"));
+        let second = sign("second", &message("POST", TARGET, now, "rotate-3", BODY));
+        assert!(verify(&request(&ts, "rotate-3", &second, BODY), now));
+        assert_eq!(secrets().read().unwrap().signing(), Some("first"));
+
+        apply_activate(&mut data);
+        assert_eq!(secrets().read().unwrap().signing(), Some("second"));
+        let previous = sign("first", &message("POST", TARGET, now, "rotate-4", BODY));
+        assert!(verify(&request(&ts, "rotate-4", &previous, BODY), now));
+        assert_eq!(
+            data.get(PREVIOUS_SECRET_KEY).map(String::as_str),
+            Some("first")
+        );
+    }
+
+    #[test]
+    fn test_tampered_request() {
+        let _lock = LOCK.lock().unwrap();
+        apply_prepare(&mut BTreeMap::new(), &ClusterSecret::from("tamper"));
+        let now = 1_700_000_000;
+        let ts = now.to_string();
+        let signed = |nonce: &str| sign("tamper", &message("POST", TARGET, now, nonce, BODY));
+
+        // 请求体、查询参数、路径、方法或随机数被修改
+        let signature = signed("tamper-1");
+        let body = SignedRequest {
+            body: br#"{"entries":[1]}"#,
+            ..request(&ts, "tamper-1", &signature, BODY)
+        };
+        assert!(!verify(&body, now));
+        let query = SignedRequest {
+            target: "/api/cluster/append?force=false".to_string(),
+            ..request(&ts, "tamper-1", &signature, BODY)
+        };
+        assert!(!verify(&query, now));
+        let path = SignedRequest {
+            target: "/api/cluster/vote?force=true".to_string(),
+            ..request(&ts, "tamper-1", &signature, BODY)
+        };
+        assert!(!verify(&path, now));
+        let method = SignedRequest {
+            method: "PUT",
+            ..request(&ts, "tamper-1", &signature, BODY)
+        };
+        assert!(!verify(&method, now));
+        assert!(!verify(&request(&ts, "tamper-2", &signature, BODY), now));
+
+        // 未被修改的请求通过，且只能使用一次
+        assert!(verify(&request(&ts, "tamper-1", &signature, BODY), now));
+        assert!(!verify(&request(&ts, "tamper-1", &signature, BODY), now));
+    }
+}
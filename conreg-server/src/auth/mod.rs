@@ -1,5 +1,7 @@
 //! Token鉴权
 
+pub mod cluster;
+
 use crate::app::get_app;
 use crate::cache;
 use crate::cache::caches::CacheKey;
@@ -9,6 +11,8 @@ use rocket::request::{FromRequest, Outcome};
 use serde::{Deserialize, Serialize};
 use tracing::log;
 
+pub use cluster::{ClusterAuth, ClusterJson};
+
 /// 当前登录用户信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPrincipal {
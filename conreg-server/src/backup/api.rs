@@ -0,0 +1,68 @@
+use crate::auth::{ClusterAuth, ClusterJson};
+use crate::backup;
+use crate::openapi::ApiDoc;
+use crate::protocol::res::Res;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![list, run, restore]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("list", "获取对象存储中的配置备份，按时间从旧到新排序")
+            .cluster_auth()
+            .response::<Vec<String>>(),
+        ApiDoc::new("run", "立即备份所有命名空间的配置")
+            .cluster_auth()
+            .response::<String>(),
+        ApiDoc::new("restore", "从备份恢复配置")
+            .cluster_auth()
+            .body::<RestoreBackupReq>()
+            .response::<Vec<String>>(),
+    ]
+}
+
+/// 从备份恢复配置
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct RestoreBackupReq {
+    /// 备份名称
+    name: String,
+    /// 恢复的命名空间，为空时恢复备份中的所有命名空间
+    #[serde(default)]
+    namespace_ids: Vec<String>,
+    /// 是否覆盖已存在的配置
+    #[serde(default)]
+    is_overwrite: bool,
+}
+
+/// 获取配置备份列表
+#[get("/list")]
+async fn list(_auth: ClusterAuth) -> Res<Vec<String>> {
+    match backup::list_backups().await {
+        Ok(names) => Res::success(names),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 立即备份，返回备份名称
+///
+/// 示例：`curl -X POST http://127.0.0.1:8000/api/backup/run`
+#[post("/run")]
+async fn run(_auth: ClusterAuth) -> Res<String> {
+    match backup::run_backup().await {
+        Ok(name) => Res::success(name),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 从备份恢复配置，返回恢复的命名空间ID
+#[post("/restore", data = "<req>")]
+async fn restore(req: ClusterJson<RestoreBackupReq>) -> Res<Vec<String>> {
+    let req = req.into_inner();
+    match backup::restore(&req.name, &req.namespace_ids, req.is_overwrite).await {
+        Ok(restored) => Res::success(restored),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
@@ -0,0 +1,242 @@
+//! 配置备份
+//!
+//! 通过启动参数`--backup-s3-endpoint`开启，定时将所有命名空间的配置导出到S3兼容的对象存储（如MinIO），
+//! 不依赖外部的定时脚本即可在集群外保留备份：
+//! - 每次备份的对象位于`{prefix}/{备份名称}/`下，备份名称为UTC时间，如`20260101T000000Z`，
+//!   包含命名空间列表`namespaces.json`，以及每个命名空间一个与控制台导出格式相同的zip文件
+//! - 定时备份仅在Leader节点执行，间隔由`--backup-interval-minutes`指定，
+//!   每次备份后按`--backup-retention`删除多余的旧备份
+//! - 通过`/api/backup`接口或`conreg-cmt backup`、`conreg-cmt restore`手动备份和恢复
+//!
+//! 恢复时先创建备份中存在而集群中不存在的命名空间，再导入配置，已有的配置默认跳过。
+
+use crate::Args;
+use crate::app::get_app;
+use crate::config::server::ExportLayout;
+use crate::namespace::server::Namespace;
+use crate::schedule::{self, TaskScope};
+use anyhow::{Context, bail};
+use chrono::Utc;
+use s3::S3Client;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::log;
+
+pub mod api;
+mod s3;
+
+/// 命名空间列表的文件名
+const NAMESPACES_FILE: &str = "namespaces.json";
+
+static BACKUP: OnceLock<Backup> = OnceLock::new();
+
+/// 备份配置
+#[derive(Debug)]
+struct Backup {
+    client: S3Client,
+    /// 备份在存储桶中的key前缀，不以`/`结尾
+    prefix: String,
+    /// 定时备份的间隔
+    interval: Duration,
+    /// 保留的备份数量
+    retention: usize,
+}
+
+pub fn init(args: &Args) -> anyhow::Result<()> {
+    let Some(endpoint) = &args.backup_s3_endpoint else {
+        return Ok(());
+    };
+    let (Some(access_key), Some(secret_key)) =
+        (&args.backup_s3_access_key, &args.backup_s3_secret_key)
+    else {
+        bail!("--backup-s3-access-key and --backup-s3-secret-key are required for backup");
+    };
+    if args.backup_interval_minutes == 0 {
+        bail!("Backup interval must be greater than 0");
+    }
+    if args.backup_retention == 0 {
+        bail!("Backup retention must be greater than 0");
+    }
+    let prefix = args.backup_s3_prefix.trim_matches('/');
+    if prefix.is_empty() {
+        bail!("Backup prefix can not be empty");
+    }
+    let client = S3Client::new(
+        endpoint,
+        &args.backup_s3_bucket,
+        &args.backup_s3_region,
+        access_key,
+        secret_key,
+    )?;
+    let _ = BACKUP.set(Backup {
+        client,
+        prefix: prefix.to_string(),
+        interval: Duration::from_secs(args.backup_interval_minutes * 60),
+        retention: args.backup_retention,
+    });
+    log::info!(
+        "config backup enabled, endpoint: {}, bucket: {}",
+        endpoint,
+        args.backup_s3_bucket
+    );
+    Ok(())
+}
+
+fn backup_config() -> anyhow::Result<&'static Backup> {
+    BACKUP
+        .get()
+        .context("backup is not enabled, start the server with --backup-s3-endpoint")
+}
+
+/// 启动定时备份任务，未开启备份时不启动
+pub fn start_backup_task() {
+    let Some(backup) = BACKUP.get() else {
+        return;
+    };
+    schedule::schedule(
+        "config-backup",
+        "备份所有命名空间的配置到对象存储",
+        TaskScope::Cluster,
+        backup.interval,
+        || async {
+            let name = run_backup().await?;
+            log::info!("config backup {} finished", name);
+            Ok(())
+        },
+    );
+}
+
+/// 备份所有命名空间的配置，并删除超出保留数量的旧备份，返回备份名称
+pub async fn run_backup() -> anyhow::Result<String> {
+    let backup = backup_config()?;
+    let name = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let namespaces = get_app().namespace_app.manager.get_all_namespace().await?;
+    let manager = &get_app().config_app.manager;
+    for namespace in &namespaces {
+        // 备份需要恢复出原始配置，不对敏感配置打码
+        let bundle = manager
+            .export(
+                &namespace.id,
+                vec![],
+                true,
+                None,
+                ExportLayout::Conreg,
+                &HashMap::new(),
+            )
+            .await?;
+        backup
+            .client
+            .put_object(&backup.key(&name, &format!("{}.zip", namespace.id)), bundle)
+            .await
+            .with_context(|| format!("upload namespace {} error", namespace.id))?;
+    }
+    // 命名空间列表最后上传，作为备份完整的标志
+    backup
+        .client
+        .put_object(
+            &backup.key(&name, NAMESPACES_FILE),
+            serde_json::to_vec(&namespaces)?,
+        )
+        .await?;
+
+    let mut backups = list_backups().await?;
+    backups.truncate(backups.len().saturating_sub(backup.retention));
+    for expired in backups {
+        if let Err(e) = delete_backup(backup, &expired).await {
+            log::warn!("delete expired backup {} error: {}", expired, e);
+        }
+    }
+    Ok(name)
+}
+
+/// 获取所有完整的备份名称，按时间从旧到新排序
+pub async fn list_backups() -> anyhow::Result<Vec<String>> {
+    let backup = backup_config()?;
+    let prefix = format!("{}/", backup.prefix);
+    let list = backup.client.list(&prefix, None).await?;
+    let mut names = list
+        .keys
+        .iter()
+        .filter_map(|key| key.strip_prefix(&prefix)?.strip_suffix(NAMESPACES_FILE))
+        .filter_map(|name| name.strip_suffix('/'))
+        .filter(|name| !name.is_empty() && !name.contains('/'))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+async fn delete_backup(backup: &Backup, name: &str) -> anyhow::Result<()> {
+    let list = backup.client.list(&backup.key(name, ""), None).await?;
+    // 命名空间列表最先删除，删除中断时剩余的对象不会被当作完整的备份
+    let (first, rest): (Vec<_>, Vec<_>) = list
+        .keys
+        .into_iter()
+        .partition(|key| key.ends_with(NAMESPACES_FILE));
+    for key in first.into_iter().chain(rest) {
+        backup.client.delete_object(&key).await?;
+    }
+    log::info!("expired backup {} deleted", name);
+    Ok(())
+}
+
+/// 从备份恢复配置，返回恢复的命名空间ID
+///
+/// `namespace_ids`为空时恢复所有命名空间，`is_overwrite`为false时跳过已存在的配置
+pub async fn restore(
+    name: &str,
+    namespace_ids: &[String],
+    is_overwrite: bool,
+) -> anyhow::Result<Vec<String>> {
+    let backup = backup_config()?;
+    let namespaces = backup
+        .client
+        .get_object(&backup.key(name, NAMESPACES_FILE))
+        .await
+        .with_context(|| format!("backup {} not found", name))?;
+    let namespaces: Vec<Namespace> = serde_json::from_slice(&namespaces)?;
+    for id in namespace_ids {
+        if !namespaces.iter().any(|namespace| &namespace.id == id) {
+            bail!("namespace {} not found in backup {}", id, name);
+        }
+    }
+
+    let namespace_manager = &get_app().namespace_app.manager;
+    let config_manager = &get_app().config_app.manager;
+    let mut restored = vec![];
+    for namespace in namespaces {
+        if !namespace_ids.is_empty() && !namespace_ids.contains(&namespace.id) {
+            continue;
+        }
+        if !namespace_manager.exists_namespace(&namespace.id).await? {
+            log::info!("restoring namespace {} from backup {}", namespace.id, name);
+            namespace_manager
+                .upsert_namespace_and_sync(
+                    &namespace.id,
+                    &namespace.name,
+                    namespace.description,
+                    namespace.is_auth,
+                    namespace.auth_token,
+                )
+                .await?;
+        }
+        let bundle = backup
+            .client
+            .get_object(&backup.key(name, &format!("{}.zip", namespace.id)))
+            .await?;
+        config_manager
+            .import_bytes(&namespace.id, bundle, is_overwrite, None)
+            .await
+            .with_context(|| format!("restore namespace {} error", namespace.id))?;
+        restored.push(namespace.id);
+    }
+    Ok(restored)
+}
+
+impl Backup {
+    /// 备份中对象的key
+    fn key(&self, name: &str, file: &str) -> String {
+        format!("{}/{}/{}", self.prefix, name, file)
+    }
+}
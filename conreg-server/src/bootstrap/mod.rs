@@ -0,0 +1,22 @@
+pub mod server;
+
+use crate::Args;
+use crate::bootstrap::server::BootstrapManager;
+use std::process::exit;
+use tracing::log;
+
+#[derive(Debug)]
+pub struct BootstrapApp {
+    pub manager: BootstrapManager,
+}
+
+pub async fn new_bootstrap_app(args: &Args) -> BootstrapApp {
+    let manager = BootstrapManager::new(args).await;
+    if let Err(e) = manager {
+        log::error!("create bootstrap app error: {}", e);
+        exit(1);
+    }
+    BootstrapApp {
+        manager: manager.unwrap(),
+    }
+}
@@ -0,0 +1,149 @@
+use crate::app::get_app;
+use crate::auth::{NamespaceAuth, UserPrincipal};
+use crate::bootstrap::server::BootstrapProfile;
+use crate::openapi::ApiDoc;
+use crate::protocol::res::{PageRes, Res};
+use chrono::Local;
+use rocket::serde::json::Json;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![upsert, delete, list, get]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("upsert", "创建或更新客户端启动配置")
+            .auth()
+            .body::<UpsertBootstrapProfileReq>()
+            .response::<()>(),
+        ApiDoc::new("delete", "删除客户端启动配置")
+            .auth()
+            .body::<DeleteBootstrapProfileReq>()
+            .response::<()>(),
+        ApiDoc::new("list", "分页查询客户端启动配置列表")
+            .auth()
+            .response::<PageRes<BootstrapProfile>>(),
+        ApiDoc::new("get", "客户端获取启动配置")
+            .namespace_auth()
+            .response::<Option<Value>>(),
+    ]
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct UpsertBootstrapProfileReq {
+    namespace_id: String,
+    service_id: String,
+    /// 服务端地址，为空时客户端使用获取启动配置的服务端
+    #[serde(default)]
+    server_addr: Vec<String>,
+    #[serde(default = "default_true")]
+    enable_config: bool,
+    #[serde(default)]
+    config_ids: Vec<String>,
+    #[serde(default = "default_true")]
+    enable_discovery: bool,
+    #[serde(default)]
+    meta: HashMap<String, String>,
+    description: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct DeleteBootstrapProfileReq {
+    namespace_id: String,
+    service_id: String,
+}
+
+/// 创建或更新客户端启动配置
+///
+/// 该接口仅在后台调用
+#[post("/upsert", data = "<req>")]
+async fn upsert(req: Json<UpsertBootstrapProfileReq>, _user: UserPrincipal) -> Res<()> {
+    let req = req.into_inner();
+    let profile = BootstrapProfile {
+        namespace_id: req.namespace_id,
+        service_id: req.service_id,
+        server_addr: req.server_addr,
+        enable_config: req.enable_config,
+        config_ids: req.config_ids,
+        enable_discovery: req.enable_discovery,
+        meta: req.meta,
+        description: req.description,
+        create_time: Local::now(),
+        update_time: Local::now(),
+    };
+    match get_app()
+        .bootstrap_app
+        .manager
+        .upsert_profile_and_sync(profile)
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 删除客户端启动配置
+///
+/// 该接口仅在后台调用
+#[post("/delete", data = "<req>")]
+async fn delete(req: Json<DeleteBootstrapProfileReq>, _user: UserPrincipal) -> Res<()> {
+    match get_app()
+        .bootstrap_app
+        .manager
+        .delete_profile_and_sync(&req.namespace_id, &req.service_id)
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 获取客户端启动配置列表（分页）
+///
+/// 该接口仅在后台调用
+#[get("/list?<namespace_id>&<page_num>&<page_size>")]
+async fn list(
+    namespace_id: &str,
+    page_num: i32,
+    page_size: i32,
+    _user: UserPrincipal,
+) -> Res<PageRes<BootstrapProfile>> {
+    match get_app()
+        .bootstrap_app
+        .manager
+        .list_profiles_with_page(namespace_id, page_num, page_size)
+        .await
+    {
+        Ok((total, list)) => Res::success(PageRes {
+            page_num,
+            page_size,
+            total,
+            list,
+        }),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 客户端获取启动配置
+///
+/// 返回结构与`bootstrap.yaml`中的`conreg`部分一致，不存在时返回空
+#[get("/<service_id>?<namespace_id>", rank = 2)]
+async fn get(service_id: &str, namespace_id: &str, _auth: NamespaceAuth) -> Res<Option<Value>> {
+    match get_app()
+        .bootstrap_app
+        .manager
+        .get_profile(namespace_id, service_id)
+        .await
+    {
+        Ok(profile) => Res::success(profile.map(|profile| profile.to_client_profile())),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
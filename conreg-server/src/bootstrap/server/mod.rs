@@ -0,0 +1,287 @@
+pub mod api;
+
+use crate::Args;
+use crate::app::get_app;
+use crate::db::DbPool;
+use crate::protocol::res::CodeError;
+use crate::raft::RaftRequest;
+use crate::raft::api::raft_write;
+use anyhow::bail;
+use chrono::{DateTime, Local};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+use std::collections::HashMap;
+use tracing::log;
+
+/// 客户端启动配置
+///
+/// 按服务集中管理客户端的`bootstrap.yaml`，客户端通过`init_from_url`从服务端获取，
+/// 不必再将启动配置打包到每个镜像中
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BootstrapProfile {
+    /// 命名空间ID，同时作为客户端配置中心和注册中心的命名空间
+    pub namespace_id: String,
+    /// 服务ID
+    pub service_id: String,
+    /// 服务端地址，为空时客户端使用获取启动配置的服务端
+    #[serde(default)]
+    pub server_addr: Vec<String>,
+    /// 是否启用配置中心
+    pub enable_config: bool,
+    /// 配置ID
+    #[serde(default)]
+    pub config_ids: Vec<String>,
+    /// 是否启用注册中心
+    pub enable_discovery: bool,
+    /// 服务实例元数据
+    #[serde(default)]
+    pub meta: HashMap<String, String>,
+    /// 描述
+    pub description: Option<String>,
+    /// 创建时间
+    pub create_time: DateTime<Local>,
+    /// 更新时间
+    pub update_time: DateTime<Local>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for BootstrapProfile {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        fn json<T: serde::de::DeserializeOwned + Default>(value: Option<String>) -> T {
+            value
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default()
+        }
+
+        Ok(BootstrapProfile {
+            namespace_id: row.try_get("namespace_id")?,
+            service_id: row.try_get("service_id")?,
+            server_addr: json(row.try_get("server_addr")?),
+            enable_config: row.try_get("enable_config")?,
+            config_ids: json(row.try_get("config_ids")?),
+            enable_discovery: row.try_get("enable_discovery")?,
+            meta: json(row.try_get("meta")?),
+            description: row.try_get("description")?,
+            create_time: row.try_get("create_time")?,
+            update_time: row.try_get("update_time")?,
+        })
+    }
+}
+
+impl BootstrapProfile {
+    /// 转换为客户端使用的启动配置，结构与`bootstrap.yaml`中的`conreg`部分一致
+    ///
+    /// 客户端地址与实例相关，由客户端自行指定；命名空间Token使用客户端获取启动配置时的Token，不在此返回
+    pub fn to_client_profile(&self) -> Value {
+        let mut profile = Map::new();
+        profile.insert("service-id".to_string(), json!(self.service_id));
+        let server_addr = (!self.server_addr.is_empty()).then(|| json!(self.server_addr));
+        if self.enable_config {
+            let mut config = json!({
+                "namespace": self.namespace_id,
+                "config-ids": self.config_ids,
+            });
+            if let Some(server_addr) = &server_addr {
+                config["server-addr"] = server_addr.clone();
+            }
+            profile.insert("config".to_string(), config);
+        }
+        if self.enable_discovery {
+            let mut discovery = json!({
+                "namespace": self.namespace_id,
+                "meta": self.meta,
+            });
+            if let Some(server_addr) = &server_addr {
+                discovery["server-addr"] = server_addr.clone();
+            }
+            profile.insert("discovery".to_string(), discovery);
+        }
+        Value::Object(profile)
+    }
+}
+
+/// 客户端启动配置管理
+#[derive(Debug)]
+pub struct BootstrapManager {}
+
+impl BootstrapManager {
+    pub async fn new(_args: &Args) -> anyhow::Result<Self> {
+        Ok(Self {})
+    }
+
+    pub async fn get_profile(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+    ) -> anyhow::Result<Option<BootstrapProfile>> {
+        let profile = sqlx::query_as(
+            "select * from bootstrap_profile where namespace_id = ? and service_id = ?",
+        )
+        .bind(namespace_id)
+        .bind(service_id)
+        .fetch_optional(DbPool::get())
+        .await?;
+        Ok(profile)
+    }
+
+    /// 创建或更新启动配置，并同步到集群
+    pub async fn upsert_profile_and_sync(&self, profile: BootstrapProfile) -> anyhow::Result<()> {
+        let namespace_manager = &get_app().namespace_app.manager;
+        if !namespace_manager
+            .exists_namespace(&profile.namespace_id)
+            .await?
+        {
+            bail!("namespace {} not found", profile.namespace_id);
+        }
+        namespace_manager
+            .check_not_suspended(&profile.namespace_id)
+            .await?;
+        if profile.service_id.trim().is_empty() {
+            bail!("service id is required");
+        }
+
+        // 保留创建时间
+        let create_time = self
+            .get_profile(&profile.namespace_id, &profile.service_id)
+            .await?
+            .map(|old| old.create_time)
+            .unwrap_or_else(Local::now);
+        self.sync(RaftRequest::UpsertBootstrapProfile {
+            profile: BootstrapProfile {
+                create_time,
+                update_time: Local::now(),
+                ..profile
+            },
+        })
+        .await
+    }
+
+    pub async fn upsert_profile(&self, profile: BootstrapProfile) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"insert into bootstrap_profile (namespace_id, service_id, server_addr, enable_config, config_ids, enable_discovery, meta, description, create_time, update_time)
+            values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            on conflict (namespace_id, service_id) do update set
+            server_addr = excluded.server_addr, enable_config = excluded.enable_config, config_ids = excluded.config_ids,
+            enable_discovery = excluded.enable_discovery, meta = excluded.meta, description = excluded.description,
+            update_time = excluded.update_time"#,
+        )
+        .bind(&profile.namespace_id)
+        .bind(&profile.service_id)
+        .bind(serde_json::to_string(&profile.server_addr)?)
+        .bind(profile.enable_config)
+        .bind(serde_json::to_string(&profile.config_ids)?)
+        .bind(profile.enable_discovery)
+        .bind(serde_json::to_string(&profile.meta)?)
+        .bind(&profile.description)
+        .bind(profile.create_time)
+        .bind(profile.update_time)
+        .execute(DbPool::get())
+        .await?;
+        Ok(())
+    }
+
+    /// 删除启动配置，并同步到集群
+    pub async fn delete_profile_and_sync(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+    ) -> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        self.sync(RaftRequest::DeleteBootstrapProfile {
+            namespace_id: namespace_id.to_string(),
+            service_id: service_id.to_string(),
+        })
+        .await
+    }
+
+    pub async fn delete_profile(&self, namespace_id: &str, service_id: &str) -> anyhow::Result<()> {
+        sqlx::query("delete from bootstrap_profile where namespace_id = ? and service_id = ?")
+            .bind(namespace_id)
+            .bind(service_id)
+            .execute(DbPool::get())
+            .await?;
+        Ok(())
+    }
+
+    /// 查询启动配置列表（分页）
+    pub async fn list_profiles_with_page(
+        &self,
+        namespace_id: &str,
+        page_num: i32,
+        page_size: i32,
+    ) -> anyhow::Result<(u64, Vec<BootstrapProfile>)> {
+        let total: u64 =
+            sqlx::query_scalar("SELECT COUNT(1) FROM bootstrap_profile WHERE namespace_id = ?")
+                .bind(namespace_id)
+                .fetch_one(DbPool::get())
+                .await?;
+        let offset = (page_num - 1) * page_size;
+
+        let rows: Vec<BootstrapProfile> = sqlx::query_as(
+            "SELECT * FROM bootstrap_profile WHERE namespace_id = ? ORDER BY create_time DESC LIMIT ?, ?",
+        )
+        .bind(namespace_id)
+        .bind(offset)
+        .bind(page_size)
+        .fetch_all(DbPool::get())
+        .await?;
+        Ok((total, rows))
+    }
+
+    async fn sync(&self, request: RaftRequest) -> anyhow::Result<()> {
+        log::info!("sync bootstrap profile request: {:?}", request);
+        let res = raft_write(request).await;
+        if !res.is_success() {
+            log::error!("sync bootstrap profile error: {:?}", res.msg);
+            bail!(CodeError {
+                code: res.code,
+                msg: format!("sync bootstrap profile error: {}", res.msg),
+            });
+        }
+        log::info!("sync bootstrap profile success");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_profile() {
+        let mut profile = BootstrapProfile {
+            namespace_id: "dev".to_string(),
+            service_id: "order".to_string(),
+            server_addr: vec![],
+            enable_config: true,
+            config_ids: vec!["order.yaml".to_string()],
+            enable_discovery: false,
+            meta: HashMap::new(),
+            description: None,
+            create_time: Local::now(),
+            update_time: Local::now(),
+        };
+        assert_eq!(
+            profile.to_client_profile(),
+            json!({
+                "service-id": "order",
+                "config": {"namespace": "dev", "config-ids": ["order.yaml"]},
+            })
+        );
+
+        profile.server_addr = vec!["10.0.0.1:8000".to_string()];
+        profile.enable_discovery = true;
+        let client_profile = profile.to_client_profile();
+        assert_eq!(
+            client_profile["config"]["server-addr"],
+            json!(["10.0.0.1:8000"])
+        );
+        assert_eq!(client_profile["discovery"]["namespace"], "dev");
+    }
+}
@@ -5,4 +5,8 @@ pub enum CacheKey {
     /// 0: 用户Token
     #[strum(to_string = "oag:user:token:{0}")]
     UserToken(String),
+    /// 集群定时任务锁
+    /// 0: 任务名称
+    #[strum(to_string = "oag:task:lock:{0}")]
+    TaskLock(String),
 }
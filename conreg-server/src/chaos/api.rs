@@ -0,0 +1,123 @@
+use crate::app::get_app;
+use crate::auth::{ClusterAuth, ClusterJson};
+use crate::chaos;
+use crate::chaos::Faults;
+use crate::discovery::DiscoveryState;
+use crate::openapi::ApiDoc;
+use crate::protocol::res::Res;
+use crate::raft::NodeId;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        get_faults,
+        set_faults,
+        clear_faults,
+        elect,
+        export_discovery,
+        import_discovery
+    ]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("get_faults", "获取本节点注入的Raft网络故障")
+            .cluster_auth()
+            .response::<Faults>(),
+        ApiDoc::new("set_faults", "为本节点发出的Raft请求注入延迟和丢包")
+            .cluster_auth()
+            .body::<Faults>(),
+        ApiDoc::new("clear_faults", "清除本节点注入的Raft网络故障").cluster_auth(),
+        ApiDoc::new("elect", "强制本节点发起选举")
+            .cluster_auth()
+            .response::<NodeId>(),
+        ApiDoc::new("export_discovery", "导出命名空间下所有服务实例的状态")
+            .cluster_auth()
+            .response::<DiscoveryState>(),
+        ApiDoc::new(
+            "import_discovery",
+            "导入服务实例的状态，替换命名空间下的所有服务实例",
+        )
+        .cluster_auth()
+        .body::<ImportDiscoveryReq>(),
+    ]
+}
+
+/// 获取本节点注入的故障
+#[get("/faults")]
+async fn get_faults(_auth: ClusterAuth) -> Res<Faults> {
+    Res::success(chaos::faults())
+}
+
+/// 注入故障
+///
+/// 示例：`curl -X POST http://127.0.0.1:8000/api/chaos/faults -d '{"delay_ms":200,"drop_rate":0.3,"targets":[2]}'`
+#[post("/faults", data = "<req>")]
+async fn set_faults(req: ClusterJson<Faults>) -> Res<()> {
+    match chaos::set_faults(req.0) {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 清除故障
+#[post("/faults/clear")]
+async fn clear_faults(_auth: ClusterAuth) -> Res<()> {
+    match chaos::set_faults(Faults::default()) {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 强制本节点发起选举，返回新任期的Leader
+///
+/// 当前Leader随之下台，可用于触发Leader切换，新Leader可能是任意节点
+#[post("/elect")]
+async fn elect(_auth: ClusterAuth) -> Res<NodeId> {
+    match chaos::elect().await {
+        Ok(leader) => Res::success(leader),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 导出命名空间下所有服务实例的状态
+///
+/// 实例按ID排序，相同的状态导出的结果一致，便于在测试中比对
+#[get("/discovery?<namespace_id>")]
+async fn export_discovery(namespace_id: &str, _auth: ClusterAuth) -> Res<DiscoveryState> {
+    match get_app()
+        .discovery_app
+        .manager
+        .export_state(namespace_id)
+        .await
+    {
+        Ok(state) => Res::success(state),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ImportDiscoveryReq {
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 服务实例的状态，通常来自导出接口
+    pub state: DiscoveryState,
+}
+
+/// 导入服务实例的状态
+///
+/// 替换命名空间下的所有服务实例，通过Raft同步到集群，用于在测试中构造确定的初始状态
+#[post("/discovery", data = "<req>")]
+async fn import_discovery(req: ClusterJson<ImportDiscoveryReq>) -> Res<()> {
+    let req = req.0;
+    match get_app()
+        .discovery_app
+        .manager
+        .import_state_and_sync(&req.namespace_id, req.state)
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
@@ -0,0 +1,174 @@
+//! 故障注入，用于测试
+//!
+//! 通过启动参数`--chaos`开启，仅用于开发和测试环境，开启后挂载`/api/chaos`接口：
+//! - 为本节点发出的Raft请求注入延迟和丢包，可指定仅对部分节点生效，模拟网络分区
+//! - 强制本节点发起选举，进入新的任期，触发Leader切换
+//! - 导出和导入服务实例的状态，在测试中构造和比对确定的注册中心状态
+//!
+//! 便于复现集群故障，测试客户端SDK的重试和故障转移逻辑。故障仅保存在内存中，不在节点间同步，重启后失效。
+
+use crate::Args;
+use crate::app::get_app;
+use crate::raft::NodeId;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::log;
+
+pub mod api;
+
+/// 强制选举的超时时间
+const ELECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// 每次发起选举后等待选出Leader的时间
+///
+/// 需大于Leader租约和选举超时之和，过早重新发起选举会打断其他节点正在进行的选举
+const ELECT_ROUND_TIMEOUT: Duration = Duration::from_secs(6);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static FAULTS: RwLock<Faults> = RwLock::new(Faults {
+    delay_ms: 0,
+    drop_rate: 0.0,
+    targets: BTreeSet::new(),
+});
+
+/// 注入的Raft网络故障
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Faults {
+    /// 每个请求发送前的延迟，单位毫秒
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// 丢弃请求的比例，0到1之间
+    #[serde(default)]
+    pub drop_rate: f64,
+    /// 生效的目标节点，为空时对所有节点生效
+    #[serde(default)]
+    pub targets: BTreeSet<NodeId>,
+}
+
+pub fn init(args: &Args) {
+    if args.chaos {
+        ENABLED.store(true, Ordering::Relaxed);
+        log::warn!(
+            "chaos mode enabled, faults can be injected via /api/chaos, do not use in production"
+        );
+    }
+}
+
+/// 是否开启了故障注入
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 获取当前注入的故障
+pub fn faults() -> Faults {
+    FAULTS.read().unwrap().clone()
+}
+
+/// 设置注入的故障，覆盖之前的设置
+pub fn set_faults(faults: Faults) -> anyhow::Result<()> {
+    if !(0f64..=1f64).contains(&faults.drop_rate) {
+        anyhow::bail!("drop rate must be between 0 and 1");
+    }
+    log::warn!("chaos faults set: {:?}", faults);
+    *FAULTS.write().unwrap() = faults;
+    Ok(())
+}
+
+/// 在发送Raft请求前注入故障
+///
+/// 按设置延迟后，按丢包比例返回错误，调用方应将其视为网络错误
+pub async fn inject(target: NodeId) -> anyhow::Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    let (delay, dropped) = {
+        let faults = FAULTS.read().unwrap();
+        if !faults.targets.is_empty() && !faults.targets.contains(&target) {
+            return Ok(());
+        }
+        (
+            Duration::from_millis(faults.delay_ms),
+            fastrand::f64() < faults.drop_rate,
+        )
+    };
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    if dropped {
+        anyhow::bail!("request to node {} dropped by chaos", target);
+    }
+    Ok(())
+}
+
+/// 强制本节点发起选举，返回新任期的Leader
+///
+/// 其他节点在Leader租约有效期内会拒绝投票，本节点日志落后时也不会当选，
+/// 因此重复发起选举，直到在更高的任期中选出Leader，Leader可能是任意节点
+pub async fn elect() -> anyhow::Result<NodeId> {
+    let app = get_app();
+    let term = app.raft.metrics().borrow().current_term;
+    let deadline = tokio::time::Instant::now() + ELECT_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        log::warn!("chaos: force node {} to elect", app.id);
+        app.raft.trigger().elect().await?;
+        let elected = app
+            .raft
+            .wait(Some(ELECT_ROUND_TIMEOUT))
+            .metrics(
+                |m| m.current_term > term && m.current_leader.is_some(),
+                "leader elected in a new term",
+            )
+            .await;
+        if let Ok(metrics) = elected {
+            return Ok(metrics.current_leader.unwrap());
+        }
+    }
+    anyhow::bail!("no leader elected in a new term in {:?}", ELECT_TIMEOUT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_faults() {
+        assert!(
+            set_faults(Faults {
+                drop_rate: 1.5,
+                ..Default::default()
+            })
+            .is_err()
+        );
+
+        // 未开启时不注入故障
+        set_faults(Faults {
+            drop_rate: 1.0,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(inject(1).await.is_ok());
+
+        ENABLED.store(true, Ordering::Relaxed);
+        assert!(inject(1).await.is_err());
+
+        // 只对目标节点生效
+        set_faults(Faults {
+            delay_ms: 10,
+            drop_rate: 1.0,
+            targets: BTreeSet::from([2]),
+        })
+        .unwrap();
+        assert!(inject(1).await.is_ok());
+        let start = std::time::Instant::now();
+        assert!(inject(2).await.is_err());
+        assert!(start.elapsed() >= Duration::from_millis(10));
+
+        set_faults(Faults::default()).unwrap();
+        assert!(inject(2).await.is_ok());
+        ENABLED.store(false, Ordering::Relaxed);
+    }
+}
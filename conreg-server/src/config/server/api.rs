@@ -1,55 +1,220 @@
+use crate::annotation::{ChangeAction, ConfigAnnotation, DiffSummary};
 use crate::app::get_app;
-use crate::auth::{NamespaceAuth, UserPrincipal};
-use crate::config::server::ConfigEntry;
+use crate::auth::{ClusterAuth, NamespaceAuth, UserPrincipal};
+use crate::config::server::diff::{ConfigCompare, ConfigDiff, compare_namespaces};
+use crate::config::server::encryption::EncryptionStatus;
+use crate::config::server::label::{Labels, parse_labels, split_variant_id, variant_id};
+use crate::config::server::lint::{LintIssue, lint_service_refs};
+use crate::config::server::listener::{ClientConfigReport, ConfigListenerStatus};
+use crate::config::server::sensitive;
+use crate::config::server::sensitive::mask_entry;
+use crate::config::server::{
+    ConfigEntry, ConfigListItem, ConfigPromotion, ConfigRevision, ConfigSearchHit, ExportLayout,
+    MAX_HISTORY_PAGE_SIZE, MAX_SEARCH_PAGE_SIZE,
+};
+use crate::openapi::ApiDoc;
 use crate::protocol::res::{PageRes, Res};
+use crate::system::check_ns_write_permission;
 use rocket::form::Form;
 use rocket::fs::TempFile;
+use rocket::http::Status;
+use rocket::response::status::Custom;
 use rocket::serde::json::Json;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tracing::log;
 
 pub fn routes() -> Vec<rocket::Route> {
     routes![
         upsert,
         get,
+        md5,
+        report,
+        listeners,
+        local_listeners,
         delete,
         recover,
+        promote,
         list,
+        search,
         list_history,
+        diff,
+        compare,
+        lint,
         watch,
+        watch_configs,
         export,
-        import
+        import,
+        get_sensitive,
+        set_sensitive,
+        get_encryption,
+        rotate_encryption_key
+    ]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("upsert", "创建或更新配置")
+            .auth()
+            .body::<UpsertConfigReq>()
+            .response::<ConfigRevision>(),
+        ApiDoc::new("get", "获取配置")
+            .namespace_auth()
+            .optional(&["labels", "md5"])
+            .response::<Option<ConfigEntry>>(),
+        ApiDoc::new("md5", "获取本节点数据库中配置的MD5").response::<Option<String>>(),
+        ApiDoc::new("report", "接收客户端上报的已应用配置MD5")
+            .body::<ClientConfigReport>()
+            .response::<()>(),
+        ApiDoc::new("listeners", "查询监听指定配置的客户端实例")
+            .auth()
+            .response::<Vec<ConfigListenerStatus>>(),
+        ApiDoc::new("local_listeners", "查询本节点上监听指定配置的客户端实例")
+            .response::<Vec<ConfigListenerStatus>>(),
+        ApiDoc::new("delete", "删除配置")
+            .auth()
+            .body::<DeleteConfigReq>()
+            .response::<()>(),
+        ApiDoc::new("recover", "从历史记录恢复配置")
+            .auth()
+            .body::<RecoverConfigReq>()
+            .response::<()>(),
+        ApiDoc::new("promote", "将配置的指定版本晋级到其他命名空间")
+            .auth()
+            .body::<PromoteConfigReq>()
+            .response::<ConfigPromotion>(),
+        ApiDoc::new("list", "分页查询配置列表")
+            .auth()
+            .optional(&["filter_text"])
+            .response::<PageRes<ConfigListItem>>(),
+        ApiDoc::new("search", "全文搜索配置")
+            .auth()
+            .response::<PageRes<ConfigSearchHit>>(),
+        ApiDoc::new("list_history", "分页查询配置历史")
+            .auth()
+            .optional(&["page_num", "before_id_"])
+            .response::<PageRes<ConfigEntry>>(),
+        ApiDoc::new("diff", "对比配置的两个历史版本")
+            .auth()
+            .optional(&["to_id_"])
+            .response::<ConfigDiff>(),
+        ApiDoc::new("compare", "对比两个命名空间下的配置")
+            .auth()
+            .optional(&["id"])
+            .response::<Vec<ConfigCompare>>(),
+        ApiDoc::new("lint", "校验配置内容，如引用的服务是否存在")
+            .namespace_auth()
+            .body::<LintConfigReq>()
+            .response::<Vec<LintIssue>>(),
+        ApiDoc::new("watch", "监听命名空间下的配置变化（长轮询）").response::<Option<String>>(),
+        ApiDoc::new("watch_configs", "按MD5监听指定配置的变化（长轮询）")
+            .body::<WatchConfigsReq>()
+            .response::<Vec<String>>(),
+        ApiDoc::new("export", "导出配置为zip文件")
+            .auth()
+            .body::<ExportConfigReq>(),
+        ApiDoc::new("import", "从zip文件导入配置（multipart/form-data）")
+            .auth()
+            .response::<()>(),
+        ApiDoc::new("get_sensitive", "获取配置的敏感配置项")
+            .auth()
+            .response::<Option<Vec<String>>>(),
+        ApiDoc::new("set_sensitive", "设置配置的敏感配置项")
+            .auth()
+            .body::<SetSensitiveReq>()
+            .response::<()>(),
+        ApiDoc::new("get_encryption", "获取命名空间的配置加密状态")
+            .auth()
+            .response::<EncryptionStatus>(),
+        ApiDoc::new(
+            "rotate_encryption_key",
+            "轮换命名空间的配置加密数据密钥，未开启加密时开启加密",
+        )
+        .auth()
+        .body::<RotateEncryptionKeyReq>()
+        .response::<EncryptionStatus>(),
     ]
 }
 
 /// 创建或更新配置
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct UpsertConfigReq {
     namespace_id: String,
     id: String,
     content: String,
     description: Option<String>,
     format: String,
+    /// 标签，不为空时创建或更新该配置ID下对应标签的变体
+    #[serde(default)]
+    labels: Labels,
+    /// 是否等待配置在本节点生效后再返回
+    verify: Option<bool>,
+    /// 是否等待配置在超过半数的节点上生效后再返回，为true时忽略`verify`
+    verify_quorum: Option<bool>,
+    /// 等待生效的超时时间，单位毫秒，默认5000
+    verify_timeout_ms: Option<u64>,
 }
 
 /// 删除配置
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct DeleteConfigReq {
     namespace_id: String,
     id: String,
 }
 
 /// 恢复配置
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct RecoverConfigReq {
     id_: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// 将配置的指定版本晋级到其他命名空间
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct PromoteConfigReq {
+    /// 来源命名空间，如`staging`
+    source_namespace_id: String,
+    /// 目标命名空间，如`prod`
+    target_namespace_id: String,
+    /// 配置ID
+    id: String,
+    /// 来源版本的配置历史ID，为空时晋级来源命名空间的当前配置
+    history_id_: Option<i64>,
+}
+
+/// 监听指定配置的变化
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct WatchConfigsReq {
+    namespace_id: String,
+    /// key为配置ID，value为客户端已加载配置的MD5
+    configs: BTreeMap<String, String>,
+    /// 标签选择器，格式为`env=prod,region=eu`，与客户端获取配置时一致
+    labels: Option<String>,
+}
+
+/// 设置配置的敏感配置项
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct SetSensitiveReq {
+    namespace_id: String,
+    /// 配置ID，对该配置ID下的所有标签变体生效
+    id: String,
+    /// 敏感配置项的路径，例如`spring.datasource.password`，为空时整个配置标记为敏感，为null时取消标记
+    keys: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct ExportConfigReq {
     namespace_id: String,
     ids: Vec<String>,
     is_all: bool,
+    /// 转换为指定格式后导出：yaml、json、properties
+    target_format: Option<String>,
+    /// 导出的目录结构
+    #[serde(default)]
+    layout: ExportLayout,
 }
 
 #[derive(Debug, FromForm)]
@@ -57,43 +222,181 @@ struct ImportConfigReq<'a> {
     namespace_id: String,
     file: TempFile<'a>,
     is_overwrite: bool,
+    /// 转换为指定格式后导入：yaml、json、properties
+    target_format: Option<String>,
 }
 /// 创建或更新配置
 ///
 /// 该接口仅在后台调用
+///
+/// 返回配置的MD5和提交的Raft日志索引。指定`verify`或`verify_quorum`时，
+/// 会等待配置实际生效后再返回，便于CI流水线确认发布完成。
 #[post("/upsert", data = "<req>")]
-async fn upsert(req: Json<UpsertConfigReq>, _user: UserPrincipal) -> Res<()> {
-    match get_app()
-        .config_app
-        .manager
+async fn upsert(req: Json<UpsertConfigReq>, user: UserPrincipal) -> Res<ConfigRevision> {
+    let manager = &get_app().config_app.manager;
+    let id = match variant_id(&req.id, &req.labels) {
+        Ok(id) => id,
+        Err(e) => return Res::error(&e.to_string()),
+    };
+    let old = manager
+        .get_config(&req.namespace_id, &id)
+        .await
+        .ok()
+        .flatten();
+    let revision = match manager
         .upsert_config_and_sync(
             &req.namespace_id,
-            &req.id,
+            &id,
             &req.content,
             req.description.clone(),
             &req.format,
         )
         .await
     {
-        Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Ok(revision) => revision,
+        Err(e) => return Res::from_error(&e),
+    };
+    annotate(
+        ChangeAction::Publish,
+        &req.namespace_id,
+        &id,
+        &user,
+        old.as_ref().map(|old| old.content.as_str()),
+        &req.content,
+        &revision,
+    );
+
+    let quorum = req.verify_quorum.unwrap_or(false);
+    if quorum || req.verify.unwrap_or(false) {
+        let timeout = Duration::from_millis(req.verify_timeout_ms.unwrap_or(5000));
+        if let Err(e) = manager
+            .verify_config_published(&req.namespace_id, &id, &revision.md5, quorum, timeout)
+            .await
+        {
+            return Res::from_error(&e);
+        }
     }
+
+    Res::success(revision)
 }
 
 /// 获取配置
-#[get("/get?<namespace_id>&<id>")]
-async fn get(namespace_id: &str, id: &str, _auth: NamespaceAuth) -> Res<Option<ConfigEntry>> {
+///
+/// `labels`为标签选择器，格式为`env=prod,region=eu`，返回与选择器最匹配的配置变体
+///
+/// `md5`为客户端已有配置的MD5，与当前配置一致时返回304且不返回配置内容，
+/// 用于客户端定时同步时避免重复下载未变化的配置
+///
+/// 后台只读用户获取敏感配置时，返回打码后的内容，见[`sensitive`]
+#[get("/get?<namespace_id>&<id>&<labels>&<md5>")]
+async fn get(
+    namespace_id: &str,
+    id: &str,
+    labels: Option<&str>,
+    md5: Option<&str>,
+    _auth: NamespaceAuth,
+    user: Option<UserPrincipal>,
+) -> Result<Res<Option<ConfigEntry>>, Custom<()>> {
+    let selector = match labels.map(parse_labels).transpose() {
+        Ok(selector) => selector.unwrap_or_default(),
+        Err(e) => return Ok(Res::error(&e.to_string())),
+    };
+    let manager = &get_app().config_app.manager;
+    match manager
+        .get_config_by_labels(namespace_id, id, &selector)
+        .await
+    {
+        Ok(entry) => {
+            if let Some(entry) = &entry {
+                manager.fetch_stats.record(namespace_id, &entry.id);
+                if md5 == Some(entry.md5.as_str()) {
+                    return Err(Custom(Status::NotModified, ()));
+                }
+            }
+            let mut entry = entry;
+            // 仅对后台用户打码，客户端通过命名空间Token获取时返回明文
+            if let (Some(user), Some(entry)) = (&user, &mut entry) {
+                match sensitive_keys_for(user, namespace_id).await {
+                    Ok(sensitive) => mask_entry(entry, &sensitive),
+                    Err(e) => return Ok(Res::error(&e.to_string())),
+                }
+            }
+            Ok(Res::success(entry))
+        }
+        Err(e) => Ok(Res::error(&e.to_string())),
+    }
+}
+
+/// 获取本节点数据库中配置的MD5
+///
+/// 用于节点间校验配置是否已生效
+#[get("/md5?<namespace_id>&<id>")]
+async fn md5(namespace_id: &str, id: &str, _auth: ClusterAuth) -> Res<Option<String>> {
+    match get_app()
+        .config_app
+        .manager
+        .get_config_md5(namespace_id, id)
+        .await
+    {
+        Ok(md5) => Res::success(md5),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 接收客户端上报的已应用配置MD5
+///
+/// 需要上报的命名空间的Token，上报内容中的命名空间需要与`namespace_id`一致
+#[post("/report?<namespace_id>", data = "<req>")]
+async fn report(
+    namespace_id: &str,
+    req: Json<ClientConfigReport>,
+    _auth: NamespaceAuth,
+) -> Res<()> {
+    if req.namespace_id != namespace_id {
+        return Res::error("namespace mismatch");
+    }
+    get_app().config_app.manager.listeners.report(req.0);
+    Res::success(())
+}
+
+/// 查询监听指定配置的客户端实例及其是否已更新到最新配置
+///
+/// 该接口仅在后台调用
+#[get("/listeners?<namespace_id>&<id>")]
+async fn listeners(
+    namespace_id: &str,
+    id: &str,
+    _user: UserPrincipal,
+) -> Res<Vec<ConfigListenerStatus>> {
     match get_app()
         .config_app
         .manager
-        .get_config(namespace_id, id)
+        .list_config_listeners(namespace_id, id)
         .await
     {
-        Ok(entry) => Res::success(entry),
+        Ok(res) => Res::success(res),
         Err(e) => Res::error(&e.to_string()),
     }
 }
 
+/// 查询本节点上监听指定配置的客户端实例
+///
+/// 用于节点间汇总监听状态
+#[get("/listeners/local?<namespace_id>&<id>")]
+async fn local_listeners(
+    namespace_id: &str,
+    id: &str,
+    _auth: ClusterAuth,
+) -> Res<Vec<ConfigListenerStatus>> {
+    Res::success(
+        get_app()
+            .config_app
+            .manager
+            .listeners
+            .list(namespace_id, id),
+    )
+}
+
 /// 删除配置
 ///
 /// 该接口仅在后台调用
@@ -106,7 +409,7 @@ async fn delete(req: Json<DeleteConfigReq>, _user: UserPrincipal) -> Res<()> {
         .await
     {
         Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -114,11 +417,108 @@ async fn delete(req: Json<DeleteConfigReq>, _user: UserPrincipal) -> Res<()> {
 ///
 /// 该接口仅在后台调用
 #[post("/recover", data = "<req>")]
-async fn recover(req: Json<RecoverConfigReq>, _user: UserPrincipal) -> Res<()> {
-    match get_app().config_app.manager.recovery(req.id_).await {
-        Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+async fn recover(req: Json<RecoverConfigReq>, user: UserPrincipal) -> Res<()> {
+    let manager = &get_app().config_app.manager;
+    let history = manager.get_history_by_id_(req.id_).await.ok().flatten();
+    let old = match &history {
+        Some(history) => manager
+            .get_config(&history.namespace_id, &history.id)
+            .await
+            .ok()
+            .flatten(),
+        None => None,
+    };
+    match manager.recovery(req.id_).await {
+        Ok(revision) => {
+            if let Some(history) = history {
+                annotate(
+                    ChangeAction::Rollback,
+                    &history.namespace_id,
+                    &history.id,
+                    &user,
+                    old.as_ref().map(|old| old.content.as_str()),
+                    &history.content,
+                    &revision,
+                );
+            }
+            Res::success(())
+        }
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 将配置的指定版本晋级到其他命名空间，如dev→staging→prod
+///
+/// 需要目标命名空间的写权限；来源配置对用户为敏感配置时拒绝晋级，避免通过晋级读取明文。
+/// 目标配置的描述中记录来源命名空间和版本，见[`ConfigManager::promote_and_sync`](super::ConfigManager::promote_and_sync)
+///
+/// 该接口仅在后台调用
+#[post("/promote", data = "<req>")]
+async fn promote(req: Json<PromoteConfigReq>, user: UserPrincipal) -> Res<ConfigPromotion> {
+    let req = req.into_inner();
+    if !check_ns_write_permission(&user, &req.target_namespace_id).await {
+        return Res::error("no permission");
+    }
+    match sensitive_keys_for(&user, &req.source_namespace_id).await {
+        Ok(sensitive) if sensitive.contains_key(split_variant_id(&req.id).0) => {
+            return Res::error("no permission");
+        }
+        Ok(_) => {}
+        Err(e) => return Res::error(&e.to_string()),
+    }
+    let manager = &get_app().config_app.manager;
+    let old = manager
+        .get_config(&req.target_namespace_id, &req.id)
+        .await
+        .ok()
+        .flatten();
+    let promotion = match manager
+        .promote_and_sync(
+            &req.source_namespace_id,
+            &req.target_namespace_id,
+            &req.id,
+            req.history_id_,
+        )
+        .await
+    {
+        Ok(promotion) => promotion,
+        Err(e) => return Res::from_error(&e),
+    };
+    if let Ok(Some(new)) = manager.get_config(&req.target_namespace_id, &req.id).await {
+        annotate(
+            ChangeAction::Promote,
+            &req.target_namespace_id,
+            &req.id,
+            &user,
+            old.as_ref().map(|old| old.content.as_str()),
+            &new.content,
+            &promotion.revision,
+        );
+    }
+    Res::success(promotion)
+}
+
+/// 发送配置变更标注，配置内容未改变时不发送
+fn annotate(
+    action: ChangeAction,
+    namespace_id: &str,
+    config_id: &str,
+    user: &UserPrincipal,
+    old_content: Option<&str>,
+    new_content: &str,
+    revision: &ConfigRevision,
+) {
+    if revision.log_index.is_none() {
+        return;
     }
+    crate::annotation::send(ConfigAnnotation {
+        action,
+        namespace_id: namespace_id.to_string(),
+        config_id: config_id.to_string(),
+        operator: user.username.clone(),
+        md5: revision.md5.clone(),
+        diff: DiffSummary::compute(old_content.unwrap_or_default(), new_content),
+    });
 }
 
 /// 获取配置列表（分页）
@@ -130,47 +530,236 @@ async fn list(
     page_num: i32,
     page_size: i32,
     filter_text: Option<String>,
-    _user: UserPrincipal,
-) -> Res<PageRes<ConfigEntry>> {
+    user: UserPrincipal,
+) -> Res<PageRes<ConfigListItem>> {
+    let sensitive = match sensitive_keys_for(&user, namespace_id).await {
+        Ok(sensitive) => sensitive,
+        Err(e) => return Res::error(&e.to_string()),
+    };
     match get_app()
         .config_app
         .manager
         .list_configs_with_page(namespace_id, page_num, page_size, filter_text)
         .await
     {
-        Ok(res) => Res::success(PageRes {
-            page_num,
-            page_size,
-            total: res.0,
-            list: res.1,
-        }),
+        Ok(mut res) => {
+            res.1
+                .iter_mut()
+                .for_each(|item| mask_entry(&mut item.entry, &sensitive));
+            Res::success(PageRes {
+                page_num,
+                page_size,
+                total: res.0,
+                list: res.1,
+            })
+        }
         Err(e) => Res::error(&e.to_string()),
     }
 }
 
+/// 全文搜索配置（分页）
+///
+/// `query`支持FTS5查询语法，例如：
+/// - `redis AND host`
+/// - `mysql OR postgres`
+/// - `datasource NOT test`
+/// - `data*`
+///
+/// `page_size`最大为[`MAX_SEARCH_PAGE_SIZE`]，超出时按最大值处理
+///
+/// 开启了加密的命名空间不支持全文搜索；后台只读用户搜索到敏感配置时，返回打码后的内容片段
+///
+/// 该接口仅在后台调用
+#[get("/fts?<namespace_id>&<query>&<page_num>&<page_size>")]
+async fn search(
+    namespace_id: &str,
+    query: &str,
+    page_num: i32,
+    page_size: i32,
+    user: UserPrincipal,
+) -> Res<PageRes<ConfigSearchHit>> {
+    let sensitive = match sensitive_keys_for(&user, namespace_id).await {
+        Ok(sensitive) => sensitive,
+        Err(e) => return Res::error(&e.to_string()),
+    };
+    let mut res = match get_app()
+        .config_app
+        .manager
+        .search_configs_with_page(namespace_id, query, page_num, page_size)
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => return Res::error(&e.to_string()),
+    };
+    for hit in res.1.iter_mut() {
+        if let Err(e) = mask_hit(hit, &sensitive).await {
+            return Res::error(&e.to_string());
+        }
+    }
+    Res::success(PageRes {
+        page_num: page_num.max(1),
+        page_size: page_size.clamp(1, MAX_SEARCH_PAGE_SIZE),
+        total: res.0,
+        list: res.1,
+    })
+}
+
 /// 获取配置历史列表
 ///
+/// 支持两种分页方式：
+/// - 传入`page_num`，按页码分页
+/// - 传入`before_id_`（上一页最后一条记录的`id_`），按游标分页，适用于历史记录较多的配置
+///
+/// `page_size`最大为[`MAX_HISTORY_PAGE_SIZE`]，超出时按最大值处理
+///
 /// 该接口仅在后台调用
-#[get("/histories?<namespace_id>&<id>&<page_num>&<page_size>")]
+#[get("/histories?<namespace_id>&<id>&<page_num>&<page_size>&<before_id_>")]
 async fn list_history(
     namespace_id: &str,
     id: &str,
-    page_num: i32,
+    page_num: Option<i32>,
     page_size: i32,
-    _user: UserPrincipal,
+    before_id_: Option<i64>,
+    user: UserPrincipal,
 ) -> Res<PageRes<ConfigEntry>> {
+    let page_num = page_num.unwrap_or(1);
+    let sensitive = match sensitive_keys_for(&user, namespace_id).await {
+        Ok(sensitive) => sensitive,
+        Err(e) => return Res::error(&e.to_string()),
+    };
     match get_app()
         .config_app
         .manager
-        .list_config_history_with_page(namespace_id, id, page_num, page_size)
+        .list_config_history_with_page(namespace_id, id, page_num, page_size, before_id_)
         .await
     {
-        Ok(res) => Res::success(PageRes {
-            page_num,
-            page_size,
-            total: res.0,
-            list: res.1,
-        }),
+        Ok(mut res) => {
+            res.1
+                .iter_mut()
+                .for_each(|entry| mask_entry(entry, &sensitive));
+            Res::success(PageRes {
+                page_num,
+                page_size: page_size.clamp(1, MAX_HISTORY_PAGE_SIZE),
+                total: res.0,
+                list: res.1,
+            })
+        }
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 对比配置的两个历史版本
+///
+/// 返回统一格式的文本差异，以及展开后发生变化的配置项；`to_id_`为空时与当前配置对比
+///
+/// 只读用户对比敏感配置时，使用打码后的内容对比
+///
+/// 该接口仅在后台调用
+#[get("/history/diff?<namespace_id>&<id>&<from_id_>&<to_id_>")]
+async fn diff(
+    namespace_id: &str,
+    id: &str,
+    from_id_: i64,
+    to_id_: Option<i64>,
+    user: UserPrincipal,
+) -> Res<ConfigDiff> {
+    let sensitive = match sensitive_keys_for(&user, namespace_id).await {
+        Ok(sensitive) => sensitive,
+        Err(e) => return Res::error(&e.to_string()),
+    };
+    match get_app()
+        .config_app
+        .manager
+        .diff_history(namespace_id, id, from_id_, to_id_, &sensitive)
+        .await
+    {
+        Ok(diff) => Res::success(diff),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 对比两个命名空间下的配置，如发布前对比staging和prod
+///
+/// 指定`id`时仅对比该配置，否则对比两个命名空间下的所有配置。返回每个配置的文本差异，
+/// 以及展开后不同的配置项，其中缺失的配置项标记为`Removed`（右侧缺失）或`Added`（左侧缺失）。
+///
+/// 只读用户对比敏感配置时，使用打码后的内容对比
+///
+/// 该接口仅在后台调用
+#[get("/compare?<left_ns>&<right_ns>&<id>")]
+async fn compare(
+    left_ns: &str,
+    right_ns: &str,
+    id: Option<&str>,
+    user: UserPrincipal,
+) -> Res<Vec<ConfigCompare>> {
+    let mut sides = vec![];
+    for namespace_id in [left_ns, right_ns] {
+        match configs_to_compare(&user, namespace_id, id).await {
+            Ok(configs) => sides.push(configs),
+            Err(e) => return Res::error(&e.to_string()),
+        }
+    }
+    let right = sides.pop().unwrap_or_default();
+    let left = sides.pop().unwrap_or_default();
+    Res::success(compare_namespaces(left_ns, right_ns, left, right))
+}
+
+/// 获取参与对比的配置，并按用户权限对敏感配置打码
+async fn configs_to_compare(
+    user: &UserPrincipal,
+    namespace_id: &str,
+    id: Option<&str>,
+) -> anyhow::Result<Vec<ConfigEntry>> {
+    let manager = &get_app().config_app.manager;
+    if !get_app()
+        .namespace_app
+        .manager
+        .exists_namespace(namespace_id)
+        .await?
+    {
+        anyhow::bail!("namespace {} not found", namespace_id);
+    }
+    let mut configs = match id {
+        Some(id) => manager
+            .get_config(namespace_id, id)
+            .await?
+            .into_iter()
+            .collect(),
+        None => manager.list_all_configs(namespace_id).await?,
+    };
+    let sensitive = sensitive_keys_for(user, namespace_id).await?;
+    configs
+        .iter_mut()
+        .for_each(|entry| mask_entry(entry, &sensitive));
+    Ok(configs)
+}
+
+/// 校验配置内容
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct LintConfigReq {
+    /// 配置内容
+    content: String,
+}
+
+/// 校验配置内容，返回发现的问题，校验结果不影响配置发布
+///
+/// 目前检查配置中`lb://<service_id>`等负载协议引用的服务是否存在于命名空间中，见[`lint`](super::lint)
+///
+/// 后台编辑器通过`X-Console`请求头调用，命令行工具通过命名空间Token调用
+#[post("/lint?<namespace_id>", data = "<req>")]
+async fn lint(
+    namespace_id: &str,
+    req: Json<LintConfigReq>,
+    _auth: NamespaceAuth,
+) -> Res<Vec<LintIssue>> {
+    match get_app()
+        .discovery_app
+        .manager
+        .list_service_ids(namespace_id)
+        .await
+    {
+        Ok(services) => Res::success(lint_service_refs(&req.content, &services)),
         Err(e) => Res::error(&e.to_string()),
     }
 }
@@ -179,44 +768,124 @@ async fn list_history(
 /// 返回值不为None时，表示配置有变化，由客户端调用`config/get`接口重新拉取配置
 /// 客户端也应该定时从`config/get`拉取配置，作为补偿操作。
 #[get("/watch?<namespace_id>")]
-async fn watch(namespace_id: &str) -> Res<Option<String>> {
-    let mut receiver = get_app().config_app.manager.sender.subscribe();
+async fn watch(namespace_id: &str, remote: Option<SocketAddr>) -> Res<Option<String>> {
+    let manager = &get_app().config_app.manager;
+    let mut receiver = manager.sender.subscribe();
+    let watcher = manager
+        .watchers
+        .register(namespace_id, remote.map(|addr| addr.to_string()));
     // 客户端超时时间为30秒，这里设置为29秒，留1秒防止客户端超时报错。
     let res = tokio::time::timeout(std::time::Duration::from_secs(29), async {
-        match receiver.recv().await {
-            Ok(event) => {
-                if event.namespace_id == namespace_id {
-                    log::info!("config changed, namespace id: {}", event.namespace_id);
-                    Res::success(Some(event.config_id))
-                } else {
-                    Res::success(None)
+        tokio::select! {
+            res = receiver.recv() => match res {
+                Ok(event) => {
+                    if event.namespace_id == namespace_id {
+                        log::info!("config changed, namespace id: {}", event.namespace_id);
+                        // 返回不带标签的配置ID，客户端按配置ID通知监听器
+                        Res::success(Some(split_variant_id(&event.config_id).0.to_string()))
+                    } else {
+                        Res::success(None)
+                    }
                 }
-            }
-            Err(_) => Res::success(None),
+                Err(_) => Res::success(None),
+            },
+            _ = watcher.closed() => Res::error("watch session closed by server"),
         }
     })
     .await;
     res.unwrap_or_else(|_| Res::success(None))
 }
 
+/// 监听指定配置的变化
+///
+/// 客户端提交已加载配置的MD5，有MD5不一致的配置时立即返回这些配置ID，
+/// 否则等待命名空间下这些配置的变更，超时返回空列表。客户端只需重新拉取返回的配置。
+#[post("/watch", data = "<req>")]
+async fn watch_configs(req: Json<WatchConfigsReq>, remote: Option<SocketAddr>) -> Res<Vec<String>> {
+    let req = req.into_inner();
+    let selector = match req.labels.as_deref().map(parse_labels).transpose() {
+        Ok(selector) => selector.unwrap_or_default(),
+        Err(e) => return Res::error(&e.to_string()),
+    };
+    let manager = &get_app().config_app.manager;
+    // 先订阅再对比，避免对比之后到订阅之前的变更丢失
+    let mut receiver = manager.subscribe();
+    let watcher = manager
+        .watchers
+        .register(&req.namespace_id, remote.map(|addr| addr.to_string()));
+    let changed = || manager.changed_configs(&req.namespace_id, &req.configs, &selector);
+    match changed().await {
+        Ok(changed) if !changed.is_empty() => return Res::success(changed),
+        Ok(_) => {}
+        Err(e) => return Res::error(&e.to_string()),
+    }
+    // 与不带MD5的监听一致，29秒后返回空列表
+    let res = tokio::time::timeout(Duration::from_secs(29), async {
+        loop {
+            tokio::select! {
+                res = receiver.recv() => match res {
+                    Ok(event)
+                        if event.namespace_id != req.namespace_id
+                            || !req.configs.contains_key(split_variant_id(&event.config_id).0) => {}
+                    Err(RecvError::Closed) => return Res::success(vec![]),
+                    // 监听的配置有变更，或者落后丢失了事件时，重新对比MD5
+                    // 变更的可能是其他标签的变体，或者内容未变，此时继续等待
+                    _ => match changed().await {
+                        Ok(changed) if !changed.is_empty() => {
+                            log::info!(
+                                "config changed, namespace id: {}, config ids: {:?}",
+                                req.namespace_id,
+                                changed
+                            );
+                            return Res::success(changed);
+                        }
+                        Ok(_) => {}
+                        Err(e) => return Res::error(&e.to_string()),
+                    },
+                },
+                _ = watcher.closed() => return Res::error("watch session closed by server"),
+            }
+        }
+    })
+    .await;
+    res.unwrap_or_else(|_| Res::success(vec![]))
+}
+
 /// 导出配置
 ///
-/// 支持导出命名空间下选中的配置或者全部配置。
+/// 支持导出命名空间下选中的配置或者全部配置，可选地转换配置格式，
+/// 或者导出为Spring Cloud Config仓库的目录结构。
+///
+/// 只读用户导出敏感配置时，导出打码后的内容
 ///
 /// 该接口仅在后台调用
 #[post("/export", data = "<req>")]
 async fn export(
     req: Json<ExportConfigReq>,
-    _user: UserPrincipal,
+    user: UserPrincipal,
 ) -> Result<Vec<u8>, rocket::http::Status> {
     let req = req.into_inner();
     let namespace_id = req.namespace_id;
     let ids = req.ids;
     let is_all = req.is_all;
+    let sensitive = match sensitive_keys_for(&user, &namespace_id).await {
+        Ok(sensitive) => sensitive,
+        Err(e) => {
+            log::error!("export config error: {}", e);
+            return Err(rocket::http::Status::InternalServerError);
+        }
+    };
     match get_app()
         .config_app
         .manager
-        .export(&namespace_id, ids, is_all)
+        .export(
+            &namespace_id,
+            ids,
+            is_all,
+            req.target_format.as_deref(),
+            req.layout,
+            &sensitive,
+        )
         .await
     {
         Ok(res) => Ok(res),
@@ -232,6 +901,8 @@ async fn export(
 /// 目前行为：
 /// - 支持同名配置覆盖导入或跳过
 /// - 当导入发生异常时，自动终止后续导入，已导入的 不会 回滚
+/// - 支持导入不带元数据的zip文件（如Spring Cloud Config仓库），按文件扩展名识别格式
+/// - 可选地转换配置格式后导入
 ///
 /// 该接口仅在后台调用
 #[post("/import", data = "<req>")]
@@ -240,10 +911,138 @@ async fn import(req: Form<ImportConfigReq<'_>>, _user: UserPrincipal) -> Res<()>
     match get_app()
         .config_app
         .manager
-        .import(&req.namespace_id, req.file, req.is_overwrite)
+        .import(
+            &req.namespace_id,
+            req.file,
+            req.is_overwrite,
+            req.target_format.as_deref(),
+        )
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 获取配置的敏感配置项
+///
+/// 未标记为敏感时返回null，为空表示整个配置
+#[get("/sensitive?<namespace_id>&<id>")]
+async fn get_sensitive(
+    namespace_id: &str,
+    id: &str,
+    _user: UserPrincipal,
+) -> Res<Option<Vec<String>>> {
+    match get_app()
+        .config_app
+        .manager
+        .get_sensitive_keys(namespace_id, id)
+        .await
+    {
+        Ok(keys) => Res::success(keys),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 设置配置的敏感配置项
+///
+/// 需要命名空间的写权限，该接口仅在后台调用
+#[post("/sensitive", data = "<req>")]
+async fn set_sensitive(req: Json<SetSensitiveReq>, user: UserPrincipal) -> Res<()> {
+    let req = req.into_inner();
+    if !check_ns_write_permission(&user, &req.namespace_id).await {
+        return Res::error("no permission");
+    }
+    match get_app()
+        .config_app
+        .manager
+        .set_sensitive_keys_and_sync(&req.namespace_id, &req.id, req.keys)
         .await
     {
         Ok(_) => Res::success(()),
         Err(e) => Res::error(&e.to_string()),
     }
 }
+
+/// 获取用户查看配置时需要打码的敏感配置项，key为配置ID
+///
+/// 有命名空间写权限的用户看到明文，返回空
+async fn sensitive_keys_for(
+    user: &UserPrincipal,
+    namespace_id: &str,
+) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    if check_ns_write_permission(user, namespace_id).await {
+        return Ok(HashMap::new());
+    }
+    get_app()
+        .config_app
+        .manager
+        .list_sensitive_keys(namespace_id)
+        .await
+}
+
+/// 命中的配置为敏感配置时，对内容片段打码
+async fn mask_hit(
+    hit: &mut ConfigSearchHit,
+    sensitive: &HashMap<String, Vec<String>>,
+) -> anyhow::Result<()> {
+    let Some(keys) = sensitive.get(split_variant_id(&hit.id).0) else {
+        return Ok(());
+    };
+    let content = get_app()
+        .config_app
+        .manager
+        .get_config(&hit.namespace_id, &hit.id)
+        .await?
+        .map(|entry| entry.content)
+        .unwrap_or_default();
+    hit.snippet = sensitive::mask_snippet(&hit.format, &content, keys, &hit.snippet);
+    Ok(())
+}
+
+/// 获取命名空间的配置加密状态
+#[get("/encryption?<namespace_id>")]
+async fn get_encryption(namespace_id: &str, _user: UserPrincipal) -> Res<EncryptionStatus> {
+    match get_app()
+        .config_app
+        .manager
+        .encryption
+        .status(namespace_id)
+        .await
+    {
+        Ok(status) => Res::success(status),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct RotateEncryptionKeyReq {
+    namespace_id: String,
+}
+
+/// 轮换命名空间的配置加密数据密钥，未开启加密时开启加密
+///
+/// 开启后不能关闭，仅管理员可操作，详见[`encryption`](crate::config::server::encryption)
+#[post("/encryption/rotate", data = "<req>")]
+async fn rotate_encryption_key(
+    req: Json<RotateEncryptionKeyReq>,
+    user: UserPrincipal,
+) -> Res<EncryptionStatus> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    match get_app()
+        .config_app
+        .manager
+        .rotate_data_key_and_sync(&req.namespace_id)
+        .await
+    {
+        Ok(key) => Res::success(EncryptionStatus {
+            namespace_id: key.namespace_id,
+            enabled: true,
+            key_id: Some(key.id),
+            key_create_time: Some(key.create_time),
+        }),
+        Err(e) => Res::from_error(&e),
+    }
+}
@@ -0,0 +1,292 @@
+//! 配置格式转换
+//!
+//! 导入导出配置时可选地转换配置格式，便于从其他配置中心迁入或迁出。
+//! 各格式实现[`FormatConverter`]，以YAML的值类型作为中间表示（保留键的顺序），
+//! 新增格式时实现该trait并在[`converter`]中注册即可。
+
+use anyhow::{Context, bail};
+use serde_yaml::{Mapping, Value};
+
+/// 配置格式转换器
+pub trait FormatConverter: Send + Sync {
+    /// 格式名称，与配置的`format`字段一致
+    fn format(&self) -> &'static str;
+    /// 该格式的文件扩展名，第一个为导出时使用的扩展名
+    fn extensions(&self) -> &'static [&'static str];
+    /// 解析配置内容
+    fn parse(&self, content: &str) -> anyhow::Result<Value>;
+    /// 生成配置内容
+    fn render(&self, value: &Value) -> anyhow::Result<String>;
+}
+
+struct Yaml;
+struct Json;
+struct Properties;
+
+static CONVERTERS: [&dyn FormatConverter; 3] = [&Yaml, &Json, &Properties];
+
+/// 根据格式名称获取转换器，不支持的格式返回None
+pub fn converter(format: &str) -> Option<&'static dyn FormatConverter> {
+    let format = format.to_lowercase();
+    CONVERTERS
+        .iter()
+        .find(|c| c.format() == format || c.extensions().contains(&format.as_str()))
+        .copied()
+}
+
+/// 根据文件扩展名获取转换器
+pub fn converter_by_file_name(file_name: &str) -> Option<&'static dyn FormatConverter> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    let extension = extension.to_lowercase();
+    CONVERTERS
+        .iter()
+        .find(|c| c.extensions().contains(&extension.as_str()))
+        .copied()
+}
+
+/// 转换配置内容的格式，返回转换后的配置ID、格式和内容
+///
+/// 配置ID的扩展名属于原格式时，替换为目标格式的扩展名
+pub fn convert(
+    id: &str,
+    format: &str,
+    content: &str,
+    target_format: &str,
+) -> anyhow::Result<(String, String, String)> {
+    let target = converter(target_format)
+        .with_context(|| format!("unsupported target format: {}", target_format))?;
+    let source = converter(format)
+        .with_context(|| format!("config {} with format {} can not be converted", id, format))?;
+    if source.format() == target.format() {
+        return Ok((id.to_string(), format.to_string(), content.to_string()));
+    }
+
+    let value = source
+        .parse(content)
+        .with_context(|| format!("parse config {} as {} error", id, source.format()))?;
+    let content = target
+        .render(&value)
+        .with_context(|| format!("convert config {} to {} error", id, target.format()))?;
+
+    let id = match id.rsplit_once('.') {
+        Some((name, extension)) if source.extensions().contains(&extension) => {
+            format!("{}.{}", name, target.extensions()[0])
+        }
+        _ => id.to_string(),
+    };
+    Ok((id, target.format().to_string(), content))
+}
+
+impl FormatConverter for Yaml {
+    fn format(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["yaml", "yml"]
+    }
+
+    fn parse(&self, content: &str) -> anyhow::Result<Value> {
+        Ok(serde_yaml::from_str(content)?)
+    }
+
+    fn render(&self, value: &Value) -> anyhow::Result<String> {
+        Ok(serde_yaml::to_string(value)?)
+    }
+}
+
+impl FormatConverter for Json {
+    fn format(&self) -> &'static str {
+        "json"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn parse(&self, content: &str) -> anyhow::Result<Value> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    fn render(&self, value: &Value) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(value)?)
+    }
+}
+
+/// properties格式
+///
+/// 键按`.`展开为嵌套结构，`key[0]`形式的键展开为列表，与Spring的处理方式一致
+impl FormatConverter for Properties {
+    fn format(&self) -> &'static str {
+        "properties"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["properties"]
+    }
+
+    fn parse(&self, content: &str) -> anyhow::Result<Value> {
+        let mut root = Value::Mapping(Mapping::new());
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(['=', ':'])
+                .with_context(|| format!("invalid properties line: {}", line))?;
+            let value = value.trim();
+            let value = serde_yaml::from_str::<Value>(value)
+                .ok()
+                .filter(|v| !v.is_mapping() && !v.is_sequence())
+                .unwrap_or_else(|| Value::String(value.to_string()));
+            insert_property(&mut root, key.trim(), value)?;
+        }
+        Ok(root)
+    }
+
+    fn render(&self, value: &Value) -> anyhow::Result<String> {
+        let lines = flatten(value)?
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::Bool(v) => format!("{}={}", key, v),
+                Value::Number(v) => format!("{}={}", key, v),
+                Value::String(v) => format!("{}={}", key, v.replace('\n', "\\n")),
+                _ => format!("{}=", key),
+            })
+            .collect::<Vec<_>>();
+        Ok(lines.join("\n"))
+    }
+}
+
+/// 将`a.b[0].c`形式的键拆分为路径
+fn property_path(key: &str) -> anyhow::Result<Vec<Value>> {
+    let mut path = vec![];
+    for part in key.split('.') {
+        let (name, indexes) = part.split_once('[').unwrap_or((part, ""));
+        if name.is_empty() {
+            bail!("invalid properties key: {}", key);
+        }
+        path.push(Value::String(name.to_string()));
+        if !indexes.is_empty() {
+            for index in indexes.trim_end_matches(']').split("][") {
+                let index = index
+                    .parse::<u64>()
+                    .with_context(|| format!("invalid properties key: {}", key))?;
+                path.push(Value::Number(index.into()));
+            }
+        }
+    }
+    Ok(path)
+}
+
+fn insert_property(root: &mut Value, key: &str, value: Value) -> anyhow::Result<()> {
+    let path = property_path(key)?;
+    let mut current = root;
+    for (i, segment) in path.iter().enumerate() {
+        let last = i == path.len() - 1;
+        let child = match segment {
+            Value::Number(index) => {
+                let index = index.as_u64().unwrap_or_default() as usize;
+                let Value::Sequence(list) = current else {
+                    bail!("properties key conflicts: {}", key);
+                };
+                if list.len() <= index {
+                    list.resize(index + 1, Value::Null);
+                }
+                &mut list[index]
+            }
+            _ => {
+                let Value::Mapping(map) = current else {
+                    bail!("properties key conflicts: {}", key);
+                };
+                map.entry(segment.clone()).or_insert(Value::Null)
+            }
+        };
+        if last {
+            if !child.is_null() {
+                bail!("properties key conflicts: {}", key);
+            }
+            *child = value;
+            return Ok(());
+        }
+        if child.is_null() {
+            *child = match path[i + 1] {
+                Value::Number(_) => Value::Sequence(vec![]),
+                _ => Value::Mapping(Mapping::new()),
+            };
+        }
+        current = child;
+    }
+    Ok(())
+}
+
+/// 将配置展开为`a.b[0].c`形式的键值对，值均为标量
+pub fn flatten(value: &Value) -> anyhow::Result<Vec<(String, Value)>> {
+    let mut properties = vec![];
+    flatten_property("", value, &mut properties)?;
+    Ok(properties)
+}
+
+fn flatten_property(
+    prefix: &str,
+    value: &Value,
+    properties: &mut Vec<(String, Value)>,
+) -> anyhow::Result<()> {
+    match value {
+        Value::Mapping(map) => {
+            for (key, value) in map {
+                let key = match key {
+                    Value::String(key) => key.clone(),
+                    Value::Number(key) => key.to_string(),
+                    Value::Bool(key) => key.to_string(),
+                    _ => bail!("unsupported key in properties: {:?}", key),
+                };
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_property(&key, value, properties)?;
+            }
+        }
+        Value::Sequence(list) => {
+            for (i, value) in list.iter().enumerate() {
+                flatten_property(&format!("{}[{}]", prefix, i), value, properties)?;
+            }
+        }
+        Value::Tagged(tagged) => flatten_property(prefix, &tagged.value, properties)?,
+        _ => properties.push((prefix.to_string(), value.clone())),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let yaml = "server:\n  port: 8080\n  hosts:\n  - a\n  - b\nname: demo\n";
+        let (id, format, properties) = convert("app.yml", "yaml", yaml, "properties").unwrap();
+        assert_eq!(id, "app.properties");
+        assert_eq!(format, "properties");
+        assert_eq!(
+            properties,
+            "server.port=8080\nserver.hosts[0]=a\nserver.hosts[1]=b\nname=demo"
+        );
+
+        let (id, _, back) = convert(&id, &format, &properties, "yaml").unwrap();
+        assert_eq!(id, "app.yaml");
+        assert_eq!(back, yaml);
+
+        let (_, _, json) = convert("app", "yaml", yaml, "json").unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()["server"]["hosts"][1],
+            "b"
+        );
+
+        assert!(convert("a.properties", "properties", "a=1\na.b=2", "yaml").is_err());
+        assert!(convert("a.toml", "toml", "a = 1", "yaml").is_err());
+    }
+}
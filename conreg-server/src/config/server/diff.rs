@@ -0,0 +1,485 @@
+//! 配置版本对比
+//!
+//! 对比配置的两个版本，返回统一格式（unified diff）的文本差异，以及按`a.b[0].c`形式展开后
+//! 发生变化的配置项。配置项的对比依赖[`convert`](super::convert)中的格式解析，
+//! 不支持解析的格式（如纯文本）仅返回文本差异。
+//!
+//! 也用于对比两个命名空间下的同名配置（如staging和prod），见[`compare_namespaces`]。
+
+use crate::config::server::ConfigEntry;
+use crate::config::server::convert::{converter, flatten};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+
+/// 文本差异中每处变更前后保留的上下文行数
+const CONTEXT_LINES: usize = 3;
+/// 逐行对比的最大规模（两个版本行数的乘积），超出时按整体替换处理
+const MAX_DIFF_CELLS: usize = 16 * 1024 * 1024;
+
+/// 配置版本对比结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigDiff {
+    /// 对比的起始版本，即配置历史ID
+    pub from_id_: i64,
+    /// 对比的目标版本，为空时表示当前配置
+    pub to_id_: Option<i64>,
+    /// 统一格式的文本差异
+    pub unified: String,
+    /// 发生变化的配置项，格式无法解析时为空
+    pub changes: Option<Vec<KeyChange>>,
+}
+
+/// 配置项变更类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum KeyChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// 发生变化的配置项
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct KeyChange {
+    /// 展开后的配置项，如`server.hosts[0]`
+    pub key: String,
+    pub kind: KeyChangeKind,
+    /// 变更前的值
+    pub old_value: Option<String>,
+    /// 变更后的值
+    pub new_value: Option<String>,
+}
+
+/// 两个命名空间下同名配置的对比状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum CompareStatus {
+    /// 内容相同
+    Same,
+    /// 内容不同
+    Different,
+    /// 仅左侧命名空间存在
+    LeftOnly,
+    /// 仅右侧命名空间存在
+    RightOnly,
+}
+
+/// 两个命名空间下同名配置的对比结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigCompare {
+    /// 配置ID
+    pub id: String,
+    pub status: CompareStatus,
+    /// 统一格式的文本差异，左侧为原版本，右侧为新版本
+    pub unified: String,
+    /// 不同的配置项，格式无法解析时为空。`Removed`表示右侧缺失的配置项，`Added`表示左侧缺失的配置项
+    pub changes: Option<Vec<KeyChange>>,
+}
+
+/// 对比两个命名空间下的配置，返回按配置ID排序的对比结果
+///
+/// 某一侧不存在的配置按空内容对比，其所有配置项均为缺失
+pub fn compare_namespaces(
+    left_ns: &str,
+    right_ns: &str,
+    left: Vec<ConfigEntry>,
+    right: Vec<ConfigEntry>,
+) -> Vec<ConfigCompare> {
+    let mut pairs: BTreeMap<String, (Option<ConfigEntry>, Option<ConfigEntry>)> = BTreeMap::new();
+    for entry in left {
+        let id = entry.id.clone();
+        pairs.entry(id).or_default().0 = Some(entry);
+    }
+    for entry in right {
+        let id = entry.id.clone();
+        pairs.entry(id).or_default().1 = Some(entry);
+    }
+    pairs
+        .into_iter()
+        .map(|(id, (left, right))| {
+            let status = match (&left, &right) {
+                (Some(left), Some(right)) if left.content == right.content => CompareStatus::Same,
+                (Some(_), Some(_)) => CompareStatus::Different,
+                (Some(_), None) => CompareStatus::LeftOnly,
+                (None, _) => CompareStatus::RightOnly,
+            };
+            // 缺失的一侧使用另一侧的格式解析空内容
+            let format = |entry: &Option<ConfigEntry>, other: &Option<ConfigEntry>| {
+                entry
+                    .as_ref()
+                    .or(other.as_ref())
+                    .map(|entry| entry.format.clone())
+                    .unwrap_or_default()
+            };
+            let (left_format, right_format) = (format(&left, &right), format(&right, &left));
+            let content = |entry: &Option<ConfigEntry>| {
+                entry
+                    .as_ref()
+                    .map(|entry| entry.content.clone())
+                    .unwrap_or_default()
+            };
+            let (left_content, right_content) = (content(&left), content(&right));
+            ConfigCompare {
+                unified: unified_diff(
+                    &format!("{}/{}", left_ns, id),
+                    &format!("{}/{}", right_ns, id),
+                    &left_content,
+                    &right_content,
+                ),
+                changes: changed_keys(&left_format, &left_content, &right_format, &right_content),
+                id,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// 对比配置的两个版本
+pub fn diff(
+    from: &ConfigEntry,
+    to: &ConfigEntry,
+    from_id_: i64,
+    to_id_: Option<i64>,
+) -> ConfigDiff {
+    let to_label = match to_id_ {
+        Some(id_) => id_.to_string(),
+        None => "current".to_string(),
+    };
+    ConfigDiff {
+        from_id_,
+        to_id_,
+        unified: unified_diff(
+            &format!("{}@{}", from.id, from_id_),
+            &format!("{}@{}", to.id, to_label),
+            &from.content,
+            &to.content,
+        ),
+        changes: changed_keys(&from.format, &from.content, &to.format, &to.content),
+    }
+}
+
+/// 生成统一格式的文本差异，内容相同时返回空字符串
+pub fn unified_diff(from_name: &str, to_name: &str, old: &str, new: &str) -> String {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let ops = diff_lines(&old, &new);
+    if ops.iter().all(|op| matches!(op, Op::Equal(..))) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", from_name, to_name);
+    let mut i = 0;
+    while i < ops.len() {
+        // 跳过未变化的行，找到下一处变更
+        let Some(start) = ops[i..]
+            .iter()
+            .position(|op| !matches!(op, Op::Equal(..)))
+            .map(|p| p + i)
+        else {
+            break;
+        };
+        // 相邻变更之间未变化的行不超过2倍上下文时合并为同一个hunk
+        let mut end = start;
+        let mut j = start;
+        while j < ops.len() {
+            if !matches!(ops[j], Op::Equal(..)) {
+                end = j;
+                j += 1;
+                continue;
+            }
+            let equal_run = ops[j..]
+                .iter()
+                .take_while(|op| matches!(op, Op::Equal(..)))
+                .count();
+            if j + equal_run >= ops.len() || equal_run > CONTEXT_LINES * 2 {
+                break;
+            }
+            j += equal_run;
+        }
+        let hunk_start = start.saturating_sub(CONTEXT_LINES);
+        let hunk_end = (end + CONTEXT_LINES + 1).min(ops.len());
+        let hunk = &ops[hunk_start..hunk_end];
+
+        let (old_start, new_start) = ops[..hunk_start]
+            .iter()
+            .fold((0, 0), |(o, n), op| match op {
+                Op::Equal(..) => (o + 1, n + 1),
+                Op::Delete(_) => (o + 1, n),
+                Op::Insert(_) => (o, n + 1),
+            });
+        let old_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Insert(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Delete(_)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        ));
+        for op in hunk {
+            match op {
+                Op::Equal(i, _) => out.push_str(&format!(" {}\n", old[*i])),
+                Op::Delete(i) => out.push_str(&format!("-{}\n", old[*i])),
+                Op::Insert(j) => out.push_str(&format!("+{}\n", new[*j])),
+            }
+        }
+        i = hunk_end;
+    }
+    out
+}
+
+/// hunk的行范围，行号从1开始，空范围的行号为前一行
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// 逐行对比操作，值为行在原版本或新版本中的下标
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// 基于最长公共子序列的逐行对比
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Op> {
+    // 去掉首尾相同的行，减少计算量
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut ops = (0..prefix).map(|i| Op::Equal(i, i)).collect::<Vec<_>>();
+    let (n, m) = (old_mid.len(), new_mid.len());
+    if n * m > MAX_DIFF_CELLS {
+        ops.extend((0..n).map(|i| Op::Delete(prefix + i)));
+        ops.extend((0..m).map(|j| Op::Insert(prefix + j)));
+    } else {
+        // lcs[i][j]为old_mid[i..]与new_mid[j..]的最长公共子序列长度
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                ops.push(Op::Equal(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(Op::Delete(prefix + i));
+                i += 1;
+            } else {
+                ops.push(Op::Insert(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let (old_offset, new_offset) = (old.len() - suffix, new.len() - suffix);
+    ops.extend((0..suffix).map(|k| Op::Equal(old_offset + k, new_offset + k)));
+    ops
+}
+
+/// 对比展开后的配置项，任一版本的格式无法解析时返回None
+pub fn changed_keys(
+    old_format: &str,
+    old: &str,
+    new_format: &str,
+    new: &str,
+) -> Option<Vec<KeyChange>> {
+    let old = flatten_content(old_format, old)?;
+    let mut new = flatten_content(new_format, new)?;
+
+    let mut changes = vec![];
+    for (key, old_value) in old {
+        match new.remove(&key) {
+            None => changes.push(KeyChange {
+                key,
+                kind: KeyChangeKind::Removed,
+                old_value: Some(old_value),
+                new_value: None,
+            }),
+            Some(new_value) if new_value != old_value => changes.push(KeyChange {
+                key,
+                kind: KeyChangeKind::Modified,
+                old_value: Some(old_value),
+                new_value: Some(new_value),
+            }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(new.into_iter().map(|(key, new_value)| KeyChange {
+        key,
+        kind: KeyChangeKind::Added,
+        old_value: None,
+        new_value: Some(new_value),
+    }));
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    Some(changes)
+}
+
+fn flatten_content(format: &str, content: &str) -> Option<BTreeMap<String, String>> {
+    if content.trim().is_empty() {
+        return Some(BTreeMap::new());
+    }
+    let value = converter(format)?.parse(content).ok()?;
+    let properties = flatten(&value).ok()?;
+    Some(
+        properties
+            .into_iter()
+            .map(|(key, value)| (key, scalar_to_string(&value)))
+            .collect(),
+    )
+}
+
+fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::String(v) => v.clone(),
+        Value::Bool(v) => v.to_string(),
+        Value::Number(v) => v.to_string(),
+        Value::Null => "null".to_string(),
+        _ => serde_yaml::to_string(value)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a: 1\nb: 2\nc: 3\n";
+        let new = "a: 1\nb: 20\nc: 3\nd: 4\n";
+        assert_eq!(
+            unified_diff("app.yaml@1", "app.yaml@2", old, new),
+            "--- app.yaml@1\n+++ app.yaml@2\n@@ -1,3 +1,4 @@\n a: 1\n-b: 2\n+b: 20\n c: 3\n+d: 4\n"
+        );
+        assert_eq!(unified_diff("a", "b", old, old), "");
+        assert_eq!(
+            unified_diff("a", "b", "", "x: 1\n"),
+            "--- a\n+++ b\n@@ -0,0 +1 @@\n+x: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old = (1..=20)
+            .map(|i| format!("k{}: {}", i, i))
+            .collect::<Vec<_>>();
+        let mut new = old.clone();
+        new[1] = "k2: x".to_string();
+        new[17] = "k18: x".to_string();
+        let diff = unified_diff("a", "b", &old.join("\n"), &new.join("\n"));
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n"));
+        assert!(diff.contains("@@ -15,6 +15,6 @@\n"));
+    }
+
+    #[test]
+    fn test_changed_keys() {
+        let changes = changed_keys(
+            "yaml",
+            "server:\n  port: 80\n  hosts: [a, b]\nname: demo\n",
+            "properties",
+            "server.port=81\nserver.hosts[0]=a\nversion=2\n",
+        )
+        .unwrap();
+        let keys = changes
+            .iter()
+            .map(|c| (c.key.as_str(), c.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                ("name", KeyChangeKind::Removed),
+                ("server.hosts[1]", KeyChangeKind::Removed),
+                ("server.port", KeyChangeKind::Modified),
+                ("version", KeyChangeKind::Added),
+            ]
+        );
+        assert_eq!(changes[2].new_value.as_deref(), Some("81"));
+        assert!(changed_keys("text", "a", "text", "b").is_none());
+    }
+
+    #[test]
+    fn test_compare_namespaces() {
+        let entry = |namespace_id: &str, id: &str, content: &str| ConfigEntry {
+            namespace_id: namespace_id.to_string(),
+            id: id.to_string(),
+            content: content.to_string(),
+            create_time: chrono::Local::now(),
+            update_time: chrono::Local::now(),
+            description: None,
+            format: "yaml".to_string(),
+            md5: ConfigEntry::gen_content_md5(content),
+            id_: 0,
+        };
+        let compares = compare_namespaces(
+            "staging",
+            "prod",
+            vec![
+                entry("staging", "app.yaml", "db:\n  host: a\n  pool: 10\n"),
+                entry("staging", "same.yaml", "a: 1\n"),
+                entry("staging", "new.yaml", "feature: true\n"),
+            ],
+            vec![
+                entry("prod", "app.yaml", "db:\n  host: b\n"),
+                entry("prod", "same.yaml", "a: 1\n"),
+                entry("prod", "old.yaml", "legacy: 1\n"),
+            ],
+        );
+        let status = compares
+            .iter()
+            .map(|c| (c.id.as_str(), c.status.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            status,
+            vec![
+                ("app.yaml", CompareStatus::Different),
+                ("new.yaml", CompareStatus::LeftOnly),
+                ("old.yaml", CompareStatus::RightOnly),
+                ("same.yaml", CompareStatus::Same),
+            ]
+        );
+        let changes = compares[0].changes.as_ref().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].key, "db.host");
+        assert_eq!(changes[1].key, "db.pool");
+        assert_eq!(changes[1].kind, KeyChangeKind::Removed);
+        assert!(
+            compares[0]
+                .unified
+                .starts_with("--- staging/app.yaml\n+++ prod/app.yaml\n")
+        );
+        assert_eq!(
+            compares[2].changes.as_ref().unwrap()[0].kind,
+            KeyChangeKind::Added
+        );
+        assert!(compares[3].unified.is_empty());
+        assert_eq!(compares[3].changes, Some(vec![]));
+    }
+}
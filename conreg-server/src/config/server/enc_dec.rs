@@ -1,8 +1,16 @@
+use crate::Args;
+use anyhow::{Context, bail};
 use base58::{FromBase58, ToBase58};
-use chacha20poly1305::aead::Aead;
+use chacha20poly1305::aead::{Aead, OsRng};
 use chacha20poly1305::{AeadCore, ChaCha20Poly1305, KeyInit, Nonce};
-use rocket::form::validate::Len;
-use uuid::Uuid;
+
+/// 未通过参数指定加解密密钥时读取的环境变量
+pub const DECRYPT_KEY_ENV: &str = "CONREG_DECRYPT_KEY";
+
+/// nonce长度
+const NONCE_LEN: usize = 12;
+/// 预留标记和内容长度占用的字节数
+const HEADER_LEN: usize = 10;
 
 #[derive(Debug)]
 pub enum EncDecError {
@@ -16,6 +24,8 @@ impl std::fmt::Display for EncDecError {
     }
 }
 
+impl std::error::Error for EncDecError {}
+
 #[derive(Debug)]
 pub struct EncDec {
     // 随机值，固定12字符，不参与正文加解密
@@ -32,7 +42,7 @@ pub struct EncDec {
 
 impl EncDec {
     pub fn new<P: Into<String>>(content: P) -> Self {
-        let mut nonce = Self::generate_nonce();
+        let nonce = Self::generate_nonce();
         let content = content.into();
         Self {
             nonce,
@@ -44,10 +54,7 @@ impl EncDec {
     }
     #[inline]
     fn generate_nonce() -> [u8; 12] {
-        let uuid = Uuid::new_v4();
-        let mut nonce = [0u8; 12];
-        nonce.copy_from_slice(&uuid.as_bytes()[..12]);
-        nonce
+        ChaCha20Poly1305::generate_nonce(&mut OsRng).into()
     }
 
     /// 加密
@@ -59,10 +66,9 @@ impl EncDec {
         let nonce = Nonce::from_slice(&self.nonce);
         let mut data = Vec::new();
         data.extend_from_slice(&[self.s1, self.s2]);
-        data.extend_from_slice(&self.content_len.to_be_bytes());
+        data.extend_from_slice(&(self.content_len as u64).to_be_bytes());
         data.extend_from_slice(self.content.as_bytes());
 
-        //data.extend_from_slice(&self.nonce);
         let ciphertext = cipher
             .encrypt(nonce, data.as_ref())
             .map_err(|e| anyhow::anyhow!("Encryption failed: {:?}", e))?;
@@ -75,39 +81,49 @@ impl EncDec {
     }
 
     /// 解密
+    ///
+    /// 密文可以带`DEC(...)`包裹，与客户端配置中的格式一致。
     pub fn decrypt(key: &[u8; 32], ciphertext: &str) -> Result<EncDec, EncDecError> {
         #[allow(deprecated)]
         let key = chacha20poly1305::Key::from_slice(key);
         let cipher = ChaCha20Poly1305::new(key);
 
         // 截取密文部分
-        // let ciphertext = ciphertext
-        //     .strip_prefix("DEC(")
-        //     .ok_or(EncDecError::InvalidFormat)?
-        //     .strip_suffix(")")
-        //     .ok_or(EncDecError::InvalidFormat)?;
+        let ciphertext = ciphertext.trim();
+        let ciphertext = ciphertext
+            .strip_prefix("DEC(")
+            .and_then(|ciphertext| ciphertext.strip_suffix(')'))
+            .unwrap_or(ciphertext);
 
         // 解码base58，得到12字节nonce+密文字节
         let nonce_ciphertext = ciphertext
             .from_base58()
             .map_err(|_| EncDecError::InvalidFormat)?;
-
-        let nonce = &nonce_ciphertext[..12];
-        let ciphertext = &nonce_ciphertext[12..];
+        if nonce_ciphertext.len() < NONCE_LEN {
+            return Err(EncDecError::InvalidFormat);
+        }
+        let (nonce, ciphertext) = nonce_ciphertext.split_at(NONCE_LEN);
 
         // 解密
         #[allow(deprecated)]
         let plaintext = cipher
-            .decrypt(Nonce::from_slice(nonce), ciphertext.as_ref())
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
             .map_err(|_| EncDecError::InvalidFormat)?;
+        if plaintext.len() < HEADER_LEN {
+            return Err(EncDecError::InvalidFormat);
+        }
 
         let s1: u8 = plaintext[0];
         let s2: u8 = plaintext[1];
-        let principal_len_bytes: [u8; 8] = plaintext[2..10]
+        let principal_len_bytes: [u8; 8] = plaintext[2..HEADER_LEN]
             .try_into()
             .map_err(|_| EncDecError::InvalidFormat)?;
-        let principal_len = usize::from_be_bytes(principal_len_bytes);
-        let principal = String::from_utf8(plaintext[10..10 + principal_len].to_vec()).unwrap();
+        let principal_len = u64::from_be_bytes(principal_len_bytes) as usize;
+        let principal = plaintext
+            .get(HEADER_LEN..HEADER_LEN.saturating_add(principal_len))
+            .ok_or(EncDecError::InvalidFormat)?;
+        let principal =
+            String::from_utf8(principal.to_vec()).map_err(|_| EncDecError::InvalidFormat)?;
 
         let nonce: [u8; 12] = nonce.try_into().map_err(|_| EncDecError::InvalidFormat)?;
 
@@ -121,6 +137,50 @@ impl EncDec {
     }
 }
 
+/// 加解密`DEC(...)`配置值的密钥，与客户端的`decrypt-key`相同
+pub struct DecryptKey([u8; 32]);
+
+impl std::fmt::Debug for DecryptKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DecryptKey(***)")
+    }
+}
+
+impl DecryptKey {
+    /// 通过`--decrypt-key`或环境变量`CONREG_DECRYPT_KEY`加载密钥，未指定时返回None
+    pub fn load(args: &Args) -> anyhow::Result<Option<Self>> {
+        let Some(key) = args
+            .decrypt_key
+            .clone()
+            .or_else(|| std::env::var(DECRYPT_KEY_ENV).ok())
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self::parse(&key)?))
+    }
+
+    fn parse(key: &str) -> anyhow::Result<Self> {
+        let key = hex::decode(key.trim()).context("decrypt key must be hex encoded")?;
+        match key.try_into() {
+            Ok(key) => Ok(Self(key)),
+            Err(key) => bail!(
+                "decrypt key must be 32 bytes (64 hex characters), got {} bytes",
+                key.len()
+            ),
+        }
+    }
+
+    /// 加密，返回`DEC(密文)`
+    pub fn encrypt(&self, content: &str) -> anyhow::Result<String> {
+        Ok(format!("DEC({})", EncDec::new(content).encrypt(&self.0)?))
+    }
+
+    /// 解密`DEC(密文)`或密文
+    pub fn decrypt(&self, ciphertext: &str) -> Result<String, EncDecError> {
+        Ok(EncDec::decrypt(&self.0, ciphertext)?.content)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,7 +192,30 @@ mod tests {
 
         let enc_dec = enc_dec.encrypt(key).unwrap();
         println!("{:?}", enc_dec);
-        let enc_dec = EncDec::decrypt(key, &enc_dec);
-        println!("{:?}", enc_dec);
+        let decrypted = EncDec::decrypt(key, &enc_dec).unwrap();
+        assert_eq!(decrypted.content, "1234567890");
+        let decrypted = EncDec::decrypt(key, &format!("DEC({})", enc_dec)).unwrap();
+        assert_eq!(decrypted.content, "1234567890");
+
+        assert!(EncDec::decrypt(&[1; 32], &enc_dec).is_err());
+        for ciphertext in ["", "DEC()", "0OIl", "abc", &enc_dec[..enc_dec.len() - 1]] {
+            assert!(EncDec::decrypt(key, ciphertext).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decrypt_key() {
+        let key = DecryptKey::parse(&"00".repeat(32)).unwrap();
+        let encrypted = key.encrypt("p@ssw0rd").unwrap();
+        assert!(encrypted.starts_with("DEC(") && encrypted.ends_with(')'));
+        assert_eq!(key.decrypt(&encrypted).unwrap(), "p@ssw0rd");
+        assert_eq!(
+            EncDec::decrypt(&[0; 32], &encrypted).unwrap().content,
+            "p@ssw0rd"
+        );
+        assert_eq!(format!("{:?}", key), "DecryptKey(***)");
+
+        assert!(DecryptKey::parse("abc").is_err());
+        assert!(DecryptKey::parse(&"00".repeat(16)).is_err());
     }
 }
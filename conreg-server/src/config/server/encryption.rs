@@ -0,0 +1,453 @@
+//! 配置加密存储
+//!
+//! 开启加密的命名空间使用独立的数据密钥（ChaCha20-Poly1305）加密数据库中的配置内容，包括当前配置和配置历史。
+//! 数据密钥由主密钥加密后保存在`config_key`表中，主密钥通过`--master-key`或环境变量`CONREG_MASTER_KEY`指定，
+//! 为64位十六进制（32字节），所有节点必须一致，可由KMS在启动时注入，不保存在数据库中。
+//!
+//! 加密后的内容格式为`conreg:enc:{数据密钥ID}:{十六进制编码的nonce+密文}`，读取时按数据密钥ID透明解密，
+//! 未加密的内容原样返回。
+//!
+//! 轮换数据密钥（首次轮换即开启加密）时，接收请求的节点生成新的数据密钥并通过Raft同步，各节点应用时在一个事务中
+//! 用新密钥重新加密该命名空间的所有配置内容并重建全文索引，然后删除旧的数据密钥。轮换过程中读取不受影响，
+//! 已解密的旧数据密钥在内存中保留，事务提交前读到的旧密文仍可解密。
+//!
+//! 注意：
+//! - 加密的命名空间的历史内容不与其他命名空间共用，见[`content_md5`]
+//! - 全文索引基于数据库中的内容，加密的命名空间无法按配置内容搜索
+//! - 加密仅针对数据库，Raft日志中的配置变更仍为明文，随日志清理删除
+
+use crate::Args;
+use crate::config::server::ConfigEntry;
+use crate::db::DbPool;
+use crate::protocol::id;
+use anyhow::{Context, bail};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+use tracing::log;
+
+/// 主密钥的环境变量，未指定`--master-key`时使用
+pub const MASTER_KEY_ENV: &str = "CONREG_MASTER_KEY";
+
+/// 加密内容的前缀
+const ENCRYPTED_PREFIX: &str = "conreg:enc:";
+
+/// nonce长度
+const NONCE_LEN: usize = 12;
+
+/// 命名空间的数据密钥，由主密钥加密
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DataKey {
+    /// 数据密钥ID，越大越新
+    pub id: i64,
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 主密钥加密后的数据密钥，十六进制编码
+    pub wrapped_key: String,
+    /// 创建时间
+    pub create_time: DateTime<Local>,
+}
+
+/// 命名空间的加密状态
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EncryptionStatus {
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 是否已开启加密
+    pub enabled: bool,
+    /// 当前数据密钥ID，未开启加密时为空
+    pub key_id: Option<i64>,
+    /// 当前数据密钥的创建时间，即上次轮换时间
+    pub key_create_time: Option<DateTime<Local>>,
+}
+
+/// 配置加密
+pub struct ConfigEncryption {
+    /// 主密钥，未指定时不能开启加密，也无法读取已加密的配置
+    master_key: Option<Key>,
+    /// 已解密的数据密钥，key为数据密钥ID
+    keys: DashMap<i64, Key>,
+    /// 命名空间当前的数据密钥ID，为None时表示未开启加密
+    active: DashMap<String, Option<i64>>,
+}
+
+impl Debug for ConfigEncryption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigEncryption")
+            .field("master_key", &self.master_key.as_ref().map(|_| "***"))
+            .field("active", &self.active)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConfigEncryption {
+    pub fn new(args: &Args) -> anyhow::Result<Self> {
+        let master_key = match args
+            .master_key
+            .clone()
+            .or_else(|| std::env::var(MASTER_KEY_ENV).ok())
+        {
+            Some(master_key) => Some(parse_master_key(&master_key)?),
+            None => None,
+        };
+        Ok(Self::with_master_key(master_key))
+    }
+
+    fn with_master_key(master_key: Option<Key>) -> Self {
+        Self {
+            master_key,
+            keys: DashMap::new(),
+            active: DashMap::new(),
+        }
+    }
+
+    fn master_key(&self) -> anyhow::Result<&Key> {
+        self.master_key.as_ref().with_context(|| {
+            format!(
+                "master key is not set, specify it with --master-key or {}",
+                MASTER_KEY_ENV
+            )
+        })
+    }
+
+    /// 为命名空间生成新的数据密钥
+    pub fn generate_key(&self, namespace_id: &str) -> anyhow::Result<DataKey> {
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        Ok(DataKey {
+            id: id::next(),
+            namespace_id: namespace_id.to_string(),
+            wrapped_key: self.wrap_key(&key)?,
+            create_time: Local::now(),
+        })
+    }
+
+    /// 使用主密钥加密数据密钥
+    fn wrap_key(&self, key: &Key) -> anyhow::Result<String> {
+        Ok(hex::encode(seal(self.master_key()?, key.as_slice())?))
+    }
+
+    /// 使用主密钥解密数据密钥
+    fn unwrap_key(&self, wrapped_key: &str) -> anyhow::Result<Key> {
+        let data = hex::decode(wrapped_key).context("invalid wrapped data key")?;
+        let key = open(self.master_key()?, &data)
+            .context("failed to unwrap data key, the master key may not match")?;
+        if key.len() != 32 {
+            bail!("invalid data key length {}", key.len());
+        }
+        Ok(*Key::from_slice(&key))
+    }
+
+    /// 获取数据密钥
+    async fn data_key(&self, key_id: i64) -> anyhow::Result<Key> {
+        if let Some(key) = self.keys.get(&key_id) {
+            return Ok(*key);
+        }
+        let wrapped_key: Option<String> =
+            sqlx::query_scalar("SELECT wrapped_key FROM config_key WHERE id = ?")
+                .bind(key_id)
+                .fetch_optional(DbPool::get())
+                .await?;
+        let Some(wrapped_key) = wrapped_key else {
+            bail!("data key {} not found", key_id);
+        };
+        let key = self.unwrap_key(&wrapped_key)?;
+        self.keys.insert(key_id, key);
+        Ok(key)
+    }
+
+    /// 获取命名空间当前的数据密钥ID，未开启加密时返回None
+    pub(crate) async fn active_key_id(&self, namespace_id: &str) -> anyhow::Result<Option<i64>> {
+        if let Some(key_id) = self.active.get(namespace_id) {
+            return Ok(*key_id);
+        }
+        let key_id: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(id) FROM config_key WHERE namespace_id = ?")
+                .bind(namespace_id)
+                .fetch_one(DbPool::get())
+                .await?;
+        self.active.insert(namespace_id.to_string(), key_id);
+        Ok(key_id)
+    }
+
+    /// 获取命名空间的加密状态
+    pub async fn status(&self, namespace_id: &str) -> anyhow::Result<EncryptionStatus> {
+        let key: Option<DataKey> = sqlx::query_as(
+            "SELECT * FROM config_key WHERE namespace_id = ? ORDER BY id DESC LIMIT 1",
+        )
+        .bind(namespace_id)
+        .fetch_optional(DbPool::get())
+        .await?;
+        Ok(EncryptionStatus {
+            namespace_id: namespace_id.to_string(),
+            enabled: key.is_some(),
+            key_id: key.as_ref().map(|key| key.id),
+            key_create_time: key.map(|key| key.create_time),
+        })
+    }
+
+    /// 使用命名空间当前的数据密钥加密配置内容，命名空间未开启加密时返回None
+    pub async fn encrypt(
+        &self,
+        namespace_id: &str,
+        content: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(key_id) = self.active_key_id(namespace_id).await? else {
+            return Ok(None);
+        };
+        let key = self.data_key(key_id).await?;
+        Ok(Some(encode(key_id, &key, content)?))
+    }
+
+    /// 解密配置内容，未加密的内容原样返回
+    pub async fn decrypt(&self, content: String) -> anyhow::Result<String> {
+        let Some((key_id, data)) = parse(&content) else {
+            return Ok(content);
+        };
+        let key = self.data_key(key_id).await?;
+        let data = hex::decode(data).context("invalid encrypted config content")?;
+        let content = open(&key, &data).with_context(|| {
+            format!("failed to decrypt config content with data key {}", key_id)
+        })?;
+        Ok(String::from_utf8(content)?)
+    }
+
+    /// 解密配置的内容
+    pub async fn decrypt_entry(&self, entry: &mut ConfigEntry) -> anyhow::Result<()> {
+        entry.content = self.decrypt(std::mem::take(&mut entry.content)).await?;
+        Ok(())
+    }
+
+    /// 轮换命名空间的数据密钥，命名空间未开启加密时开启加密
+    ///
+    /// 在一个事务中保存新的数据密钥，用新密钥重新加密命名空间的当前配置和配置历史，并删除旧的数据密钥。
+    /// 重复应用或应用比当前更旧的数据密钥时跳过。
+    ///
+    /// 注意：该方法不应该直接调用，而需要由raft apply log时调用，以保证数据一致性
+    pub async fn rotate(&self, key: DataKey) -> anyhow::Result<()> {
+        let namespace_id = key.namespace_id.as_str();
+        if let Some(current) = self.active_key_id(namespace_id).await?
+            && current >= key.id
+        {
+            log::info!(
+                "data key {} of namespace {} is not newer than the current key {}, skip",
+                key.id,
+                namespace_id,
+                current
+            );
+            return Ok(());
+        }
+        let new_key = self.unwrap_key(&key.wrapped_key)?;
+
+        let mut tx = DbPool::get().begin().await?;
+        // 被替换的明文所占用的空间清零，避免残留在数据库文件中
+        sqlx::query("PRAGMA secure_delete = ON")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT OR IGNORE INTO config_key (id, namespace_id, wrapped_key, create_time) VALUES (?, ?, ?, ?)",
+        )
+        .bind(key.id)
+        .bind(namespace_id)
+        .bind(&key.wrapped_key)
+        .bind(key.create_time)
+        .execute(&mut *tx)
+        .await?;
+
+        // 当前配置
+        let configs: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id_, content FROM config WHERE namespace_id = ?")
+                .bind(namespace_id)
+                .fetch_all(&mut *tx)
+                .await?;
+        for (id_, content) in configs {
+            let content = self.decrypt(content).await?;
+            sqlx::query("UPDATE config SET content = ? WHERE id_ = ?")
+                .bind(encode(key.id, &new_key, &content)?)
+                .bind(id_)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // 配置历史，与其他命名空间共用的历史内容另存一份
+        let contents: Vec<(String, String)> = sqlx::query_as(
+            "SELECT DISTINCT c.md5, c.content FROM config_history h JOIN config_content c ON c.md5 = h.content_md5 WHERE h.namespace_id = ?",
+        )
+        .bind(namespace_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for (md5, content) in contents {
+            let content = self.decrypt(content).await?;
+            let encrypted = encode(key.id, &new_key, &content)?;
+            let new_md5 = content_md5(namespace_id, &content);
+            if new_md5 == md5 {
+                sqlx::query("UPDATE config_content SET content = ? WHERE md5 = ?")
+                    .bind(&encrypted)
+                    .bind(&md5)
+                    .execute(&mut *tx)
+                    .await?;
+                continue;
+            }
+            sqlx::query("INSERT OR REPLACE INTO config_content (md5, content) VALUES (?, ?)")
+                .bind(&new_md5)
+                .bind(&encrypted)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "UPDATE config_history SET content_md5 = ? WHERE namespace_id = ? AND content_md5 = ?",
+            )
+            .bind(&new_md5)
+            .bind(namespace_id)
+            .bind(&md5)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "DELETE FROM config_content WHERE md5 NOT IN (SELECT content_md5 FROM config_history WHERE content_md5 IS NOT NULL)",
+        )
+        .execute(&mut *tx)
+        .await?;
+        // 全文索引中仍有明文的词条，重建索引
+        sqlx::query("INSERT INTO config_fts (config_fts) VALUES ('rebuild')")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM config_key WHERE namespace_id = ? AND id < ?")
+            .bind(namespace_id)
+            .bind(key.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.keys.insert(key.id, new_key);
+        self.active.insert(namespace_id.to_string(), Some(key.id));
+        log::info!(
+            "data key of namespace {} rotated to {}",
+            namespace_id,
+            key.id
+        );
+        Ok(())
+    }
+}
+
+/// 加密的命名空间中历史内容的MD5
+///
+/// 历史内容按MD5去重存储，加密的命名空间在MD5中加入命名空间ID，
+/// 避免与其他命名空间共用同一条内容，导致内容以明文或其他命名空间的密钥保存
+pub fn content_md5(namespace_id: &str, content: &str) -> String {
+    ConfigEntry::gen_content_md5(&format!("{}\n{}", namespace_id, content))
+}
+
+/// 解析主密钥
+fn parse_master_key(master_key: &str) -> anyhow::Result<Key> {
+    let key = hex::decode(master_key.trim()).context("master key must be hex encoded")?;
+    if key.len() != 32 {
+        bail!(
+            "master key must be 32 bytes (64 hex characters), got {} bytes",
+            key.len()
+        );
+    }
+    Ok(*Key::from_slice(&key))
+}
+
+/// 加密，返回nonce+密文
+fn seal(key: &Key, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+    let mut data = nonce.to_vec();
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// 解密nonce+密文
+fn open(key: &Key, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        bail!("encrypted data too short");
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    ChaCha20Poly1305::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("decryption failed"))
+}
+
+/// 加密配置内容
+fn encode(key_id: i64, key: &Key, content: &str) -> anyhow::Result<String> {
+    Ok(format!(
+        "{}{}:{}",
+        ENCRYPTED_PREFIX,
+        key_id,
+        hex::encode(seal(key, content.as_bytes())?)
+    ))
+}
+
+/// 解析加密的配置内容，返回数据密钥ID和十六进制编码的nonce+密文，未加密时返回None
+fn parse(content: &str) -> Option<(i64, &str)> {
+    let (key_id, data) = content.strip_prefix(ENCRYPTED_PREFIX)?.split_once(':')?;
+    Some((key_id.parse().ok()?, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_encrypt() {
+        let encryption =
+            ConfigEncryption::with_master_key(Some(parse_master_key(&"ab".repeat(32)).unwrap()));
+        let data_key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let wrapped_key = encryption.wrap_key(&data_key).unwrap();
+        assert_eq!(encryption.unwrap_key(&wrapped_key).unwrap(), data_key);
+        encryption.keys.insert(1, data_key);
+
+        let content = "db:\n  password: secret\n";
+        let encrypted = encode(1, &data_key, content).unwrap();
+        assert!(!encrypted.contains("secret"));
+        assert_eq!(parse(&encrypted).map(|(id, _)| id), Some(1));
+        // 每次加密使用不同的nonce
+        assert_ne!(encrypted, encode(1, &data_key, content).unwrap());
+        assert_eq!(
+            encryption.decrypt(encrypted.clone()).await.unwrap(),
+            content
+        );
+
+        // 未加密的内容原样返回
+        assert_eq!(
+            encryption.decrypt(content.to_string()).await.unwrap(),
+            content
+        );
+
+        // 密文被篡改
+        let mut tampered = encrypted.into_bytes();
+        let last = tampered.len() - 1;
+        tampered[last] = if tampered[last] == b'0' { b'1' } else { b'0' };
+        assert!(
+            encryption
+                .decrypt(String::from_utf8(tampered).unwrap())
+                .await
+                .is_err()
+        );
+
+        // 主密钥不一致时无法解密数据密钥
+        let other =
+            ConfigEncryption::with_master_key(Some(parse_master_key(&"cd".repeat(32)).unwrap()));
+        assert!(other.unwrap_key(&wrapped_key).is_err());
+        assert!(
+            ConfigEncryption::with_master_key(None)
+                .wrap_key(&data_key)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_master_key() {
+        assert!(parse_master_key(&"0f".repeat(32)).is_ok());
+        assert!(parse_master_key(&"0f".repeat(16)).is_err());
+        assert!(parse_master_key("not hex").is_err());
+        assert_ne!(content_md5("a", "x"), content_md5("b", "x"));
+    }
+}
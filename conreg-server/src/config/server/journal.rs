@@ -0,0 +1,222 @@
+//! 配置变更日志
+//!
+//! 按命名空间记录本节点最近的配置变更，每次变更分配一个递增的版本号。
+//! gRPC监听的客户端保存最后收到的游标（`{epoch}:{revision}`），重新连接时带上游标，
+//! 服务端立即补发游标之后的变更，不需要等待客户端的定时补偿。
+//!
+//! 正常退出时变更日志写入`{data_dir}/watch_journal.json`，重启后加载并删除该文件，
+//! 因此重启前刚应用的变更在重启后仍能补发给重新连接的客户端。
+//! 异常退出或者连接到其他节点时，epoch不一致，客户端需要重新拉取所有监听的配置。
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use tracing::log;
+
+/// 每个命名空间保留的变更条数
+const MAX_ENTRIES: usize = 256;
+
+/// 变更日志文件名
+const JOURNAL_FILE: &str = "watch_journal.json";
+
+/// 命名空间的变更记录
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct NamespaceJournal {
+    /// 最新版本号
+    revision: u64,
+    /// 最近的变更，(版本号, 配置ID)
+    entries: VecDeque<(u64, String)>,
+}
+
+/// 持久化格式
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalFile {
+    epoch: String,
+    namespaces: Vec<(String, NamespaceJournal)>,
+}
+
+/// 游标之后的变更
+#[derive(Debug, PartialEq)]
+pub enum Replay {
+    /// 游标之后的变更，(版本号, 配置ID)
+    Changes(Vec<(u64, String)>),
+    /// 游标已失效，客户端需要重新拉取所有配置
+    Reset,
+}
+
+/// 配置变更日志
+#[derive(Debug)]
+pub struct ChangeJournal {
+    /// 变更日志的标识，版本号只在同一个epoch内可比较
+    epoch: String,
+    namespaces: DashMap<String, NamespaceJournal>,
+}
+
+impl Default for ChangeJournal {
+    fn default() -> Self {
+        Self {
+            epoch: uuid::Uuid::new_v4().simple().to_string(),
+            namespaces: DashMap::new(),
+        }
+    }
+}
+
+impl ChangeJournal {
+    fn path(data_dir: &str) -> PathBuf {
+        Path::new(data_dir).join(JOURNAL_FILE)
+    }
+
+    /// 加载上次正常退出时保存的变更日志，加载后删除文件，不存在或者无法读取时使用新的变更日志
+    pub fn load(data_dir: &str) -> Self {
+        let path = Self::path(data_dir);
+        if !path.exists() {
+            return Self::default();
+        }
+        let journal = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice::<JournalFile>(&bytes)?));
+        // 删除文件，避免异常退出后再次启动时加载过期的变更日志
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("remove watch journal error: {}", e);
+        }
+        match journal {
+            Ok(file) => {
+                log::info!("watch journal loaded, epoch: {}", file.epoch);
+                Self {
+                    epoch: file.epoch,
+                    namespaces: file.namespaces.into_iter().collect(),
+                }
+            }
+            Err(e) => {
+                log::warn!("load watch journal error: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// 保存变更日志，在正常退出时调用
+    pub fn save(&self, data_dir: &str) -> anyhow::Result<()> {
+        let file = JournalFile {
+            epoch: self.epoch.clone(),
+            namespaces: self
+                .namespaces
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect(),
+        };
+        std::fs::write(Self::path(data_dir), serde_json::to_vec(&file)?)?;
+        Ok(())
+    }
+
+    /// 记录一次变更，返回版本号
+    pub fn record(&self, namespace_id: &str, config_id: &str) -> u64 {
+        let mut journal = self.namespaces.entry(namespace_id.to_string()).or_default();
+        journal.revision += 1;
+        let revision = journal.revision;
+        journal.entries.push_back((revision, config_id.to_string()));
+        if journal.entries.len() > MAX_ENTRIES {
+            journal.entries.pop_front();
+        }
+        revision
+    }
+
+    /// 命名空间当前的版本号
+    pub fn revision(&self, namespace_id: &str) -> u64 {
+        self.namespaces
+            .get(namespace_id)
+            .map(|journal| journal.revision)
+            .unwrap_or_default()
+    }
+
+    /// 指定版本号的游标
+    pub fn cursor_at(&self, revision: u64) -> String {
+        format!("{}:{}", self.epoch, revision)
+    }
+
+    /// 获取游标之后的变更
+    pub fn since(&self, namespace_id: &str, cursor: &str) -> Replay {
+        let Some(revision) = cursor
+            .split_once(':')
+            .filter(|(epoch, _)| *epoch == self.epoch)
+            .and_then(|(_, revision)| revision.parse::<u64>().ok())
+        else {
+            return Replay::Reset;
+        };
+        let Some(journal) = self.namespaces.get(namespace_id) else {
+            return if revision == 0 {
+                Replay::Changes(vec![])
+            } else {
+                Replay::Reset
+            };
+        };
+        if revision > journal.revision {
+            return Replay::Reset;
+        }
+        // 游标之后的变更已经被淘汰
+        let oldest = journal.entries.front().map(|(r, _)| *r).unwrap_or(1);
+        if revision + 1 < oldest {
+            return Replay::Reset;
+        }
+        Replay::Changes(
+            journal
+                .entries
+                .iter()
+                .filter(|(r, _)| *r > revision)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_journal() {
+        let dir = std::env::temp_dir().join(format!("conreg-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data_dir = dir.to_str().unwrap();
+
+        let journal = ChangeJournal::load(data_dir);
+        let cursor = journal.cursor_at(journal.revision("public"));
+        assert_eq!(journal.since("public", &cursor), Replay::Changes(vec![]));
+        assert_eq!(journal.record("public", "a.yaml"), 1);
+        assert_eq!(journal.record("public", "b.yaml"), 2);
+        assert_eq!(
+            journal.since("public", &cursor),
+            Replay::Changes(vec![(1, "a.yaml".to_string()), (2, "b.yaml".to_string())])
+        );
+        assert_eq!(
+            journal.since("public", &journal.cursor_at(1)),
+            Replay::Changes(vec![(2, "b.yaml".to_string())])
+        );
+        assert_eq!(journal.since("public", "other:1"), Replay::Reset);
+        assert_eq!(
+            journal.since("public", &journal.cursor_at(3)),
+            Replay::Reset
+        );
+
+        // 重启后游标仍然有效
+        journal.save(data_dir).unwrap();
+        let restored = ChangeJournal::load(data_dir);
+        assert_eq!(
+            restored.since("public", &cursor),
+            Replay::Changes(vec![(1, "a.yaml".to_string()), (2, "b.yaml".to_string())])
+        );
+        // 文件加载后删除，再次启动时使用新的epoch
+        assert_eq!(
+            ChangeJournal::load(data_dir).since("public", &cursor),
+            Replay::Reset
+        );
+
+        // 淘汰的变更无法补发
+        for _ in 0..MAX_ENTRIES {
+            restored.record("public", "c.yaml");
+        }
+        assert_eq!(restored.since("public", &cursor), Replay::Reset);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
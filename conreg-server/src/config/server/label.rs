@@ -0,0 +1,154 @@
+//! 配置标签
+//!
+//! 同一配置ID可以按标签（如`env=prod`、`region=eu`）保存多个变体，变体以
+//! `{配置ID}#{标签}`的形式作为独立配置存储，如`application.yaml#env=prod,region=eu`，
+//! 因此历史、回滚、导入导出等功能对变体同样适用。
+//!
+//! 客户端获取配置时携带标签选择器，服务端返回标签完全包含在选择器中且匹配标签数最多的变体，
+//! 没有匹配的变体时返回不带标签的配置。
+
+use anyhow::bail;
+use std::collections::BTreeMap;
+
+/// 配置ID与标签之间的分隔符
+pub const LABEL_SEPARATOR: char = '#';
+
+/// 配置标签，按标签名排序
+pub type Labels = BTreeMap<String, String>;
+
+/// 解析标签，格式为`env=prod,region=eu`
+pub fn parse_labels(text: &str) -> anyhow::Result<Labels> {
+    let mut labels = Labels::new();
+    for pair in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((key, value)) = pair.split_once('=') else {
+            bail!("invalid label: {}, expected key=value", pair);
+        };
+        labels.insert(key.trim().to_string(), value.trim().to_string());
+    }
+    validate_labels(&labels)?;
+    Ok(labels)
+}
+
+/// 格式化标签，格式为`env=prod,region=eu`
+pub fn format_labels(labels: &Labels) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn validate_labels(labels: &Labels) -> anyhow::Result<()> {
+    for (key, value) in labels {
+        if key.is_empty() || value.is_empty() {
+            bail!("label key and value must not be empty");
+        }
+        if [key, value]
+            .iter()
+            .any(|s| s.contains(['=', ',', LABEL_SEPARATOR]))
+        {
+            bail!(
+                "label {}={} must not contain '=', ',' or '{}'",
+                key,
+                value,
+                LABEL_SEPARATOR
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 生成配置变体的ID，标签为空时返回配置ID本身
+pub fn variant_id(config_id: &str, labels: &Labels) -> anyhow::Result<String> {
+    if labels.is_empty() {
+        return Ok(config_id.to_string());
+    }
+    validate_labels(labels)?;
+    if config_id.contains(LABEL_SEPARATOR) {
+        bail!(
+            "config id {} must not contain '{}' when labels are set",
+            config_id,
+            LABEL_SEPARATOR
+        );
+    }
+    Ok(format!(
+        "{}{}{}",
+        config_id,
+        LABEL_SEPARATOR,
+        format_labels(labels)
+    ))
+}
+
+/// 拆分配置变体的ID，返回配置ID和标签
+///
+/// 分隔符后的内容不是合法的标签时，视为普通的配置ID
+pub fn split_variant_id(id: &str) -> (&str, Labels) {
+    if let Some((config_id, labels)) = id.split_once(LABEL_SEPARATOR)
+        && let Ok(labels) = parse_labels(labels)
+        && !labels.is_empty()
+    {
+        return (config_id, labels);
+    }
+    (id, Labels::new())
+}
+
+/// 从配置及其变体的ID中选择与标签选择器最匹配的一个
+///
+/// 变体的标签必须全部包含在选择器中，多个变体匹配时选择标签数最多的
+pub fn select_variant<'a>(
+    config_id: &str,
+    selector: &Labels,
+    ids: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    ids.into_iter()
+        .filter_map(|id| {
+            let (base, labels) = split_variant_id(id);
+            (base == config_id
+                && labels
+                    .iter()
+                    .all(|(key, value)| selector.get(key) == Some(value)))
+            .then_some((labels.len(), id))
+        })
+        .max_by_key(|(matched, _)| *matched)
+        .map(|(_, id)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_id() {
+        let labels = parse_labels("region=eu, env=prod").unwrap();
+        let id = variant_id("app.yaml", &labels).unwrap();
+        assert_eq!(id, "app.yaml#env=prod,region=eu");
+        assert_eq!(split_variant_id(&id), ("app.yaml", labels));
+        assert_eq!(split_variant_id("a#b").0, "a#b");
+        assert!(parse_labels("env").is_err());
+        assert!(variant_id("a#b", &parse_labels("env=prod").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_select_variant() {
+        let ids = [
+            "app.yaml",
+            "app.yaml#env=prod",
+            "app.yaml#env=prod,region=eu",
+            "app.yaml#env=test",
+            "other.yaml#env=prod",
+        ];
+        let select =
+            |selector: &str| select_variant("app.yaml", &parse_labels(selector).unwrap(), ids);
+        assert_eq!(
+            select("env=prod,region=eu"),
+            Some("app.yaml#env=prod,region=eu")
+        );
+        assert_eq!(select("env=prod,region=us"), Some("app.yaml#env=prod"));
+        assert_eq!(select("env=dev"), Some("app.yaml"));
+        assert_eq!(select(""), Some("app.yaml"));
+        assert_eq!(
+            select_variant("app.yaml", &Labels::new(), ["app.yaml#env=prod"]),
+            None
+        );
+    }
+}
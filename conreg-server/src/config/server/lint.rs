@@ -0,0 +1,121 @@
+//! 配置校验
+//!
+//! 扫描配置内容中的负载协议引用（`lb://<service_id>`，以及`lb-r`、`lb-wrr`、`lb-ch`等），
+//! 引用的服务在命名空间中不存在时给出警告，用于发现服务ID拼写错误或服务尚未部署的情况。
+//!
+//! 校验结果仅作为提示，不影响配置的发布。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 校验规则：引用的服务不存在
+pub const RULE_UNKNOWN_SERVICE: &str = "unknown-service";
+
+/// 校验问题
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LintIssue {
+    /// 规则
+    pub rule: String,
+    /// 行号，从1开始
+    pub line: usize,
+    /// 说明
+    pub message: String,
+}
+
+/// 查找配置内容中引用的服务，返回行号和服务ID
+pub fn service_refs(content: &str) -> Vec<(usize, String)> {
+    let mut refs = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let mut rest = line;
+        while let Some(pos) = rest.find("://") {
+            let (before, after) = rest.split_at(pos);
+            rest = &after[3..];
+            if !is_lb_scheme(before) {
+                continue;
+            }
+            let service_id = rest
+                .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .next()
+                .unwrap_or_default();
+            if !service_id.is_empty() {
+                refs.push((index + 1, service_id.to_string()));
+            }
+        }
+    }
+    refs
+}
+
+/// `://`之前的内容是否以负载协议结尾，即`lb`或`lb-xxx`
+fn is_lb_scheme(before: &str) -> bool {
+    let scheme = before
+        .rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .next()
+        .unwrap_or_default();
+    match scheme.strip_prefix("lb") {
+        Some("") => true,
+        Some(suffix) => {
+            suffix.len() > 1
+                && suffix.starts_with('-')
+                && suffix[1..].chars().all(|c| c.is_ascii_lowercase())
+        }
+        None => false,
+    }
+}
+
+/// 校验配置中引用的服务是否存在
+///
+/// - services：命名空间中已存在的服务ID
+pub fn lint_service_refs(content: &str, services: &HashSet<String>) -> Vec<LintIssue> {
+    service_refs(content)
+        .into_iter()
+        .filter(|(_, service_id)| !services.contains(service_id))
+        .map(|(line, service_id)| LintIssue {
+            rule: RULE_UNKNOWN_SERVICE.to_string(),
+            line,
+            message: format!("service '{}' does not exist in the namespace", service_id),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_refs() {
+        let content = r#"
+order:
+  url: lb://order-service/api/orders
+  backup: "lb-wrr://order.backup:8080"
+  cache: lb-ch://cache_svc
+other: http://example.com/lb://x
+blob: clb://not-a-service
+urls: [lb://a, lb-r://b]
+empty: lb:///path
+"#;
+        assert_eq!(
+            service_refs(content),
+            vec![
+                (3, "order-service".to_string()),
+                (4, "order.backup".to_string()),
+                (5, "cache_svc".to_string()),
+                (6, "x".to_string()),
+                (8, "a".to_string()),
+                (8, "b".to_string()),
+            ]
+        );
+
+        let services = HashSet::from(["order-service".to_string(), "a".to_string()]);
+        let issues = lint_service_refs(content, &services);
+        assert_eq!(
+            issues.iter().map(|issue| issue.line).collect::<Vec<_>>(),
+            vec![4, 5, 6, 8]
+        );
+        assert!(
+            issues
+                .iter()
+                .all(|issue| issue.rule == RULE_UNKNOWN_SERVICE)
+        );
+    }
+}
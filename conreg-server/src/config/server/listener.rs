@@ -0,0 +1,102 @@
+//! 配置监听状态
+//!
+//! 客户端定时上报当前已应用的配置MD5，用于在控制台查看每个配置在哪些客户端实例上已是最新、
+//! 哪些实例仍在使用旧版本，以确认配置发布是否真正生效。
+//!
+//! 上报数据仅保存在接收上报的节点内存中，查询时需要汇总集群所有节点的数据。
+
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 上报过期时间，超过该时间未上报的客户端视为已下线，单位秒
+const REPORT_EXPIRE_SECS: i64 = 180;
+
+/// 客户端上报的配置状态
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClientConfigReport {
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 服务ID
+    pub service_id: String,
+    /// 实例ID
+    pub instance_id: String,
+    /// 客户端地址
+    pub address: String,
+    /// 已应用的配置，key为配置ID，value为配置MD5
+    pub configs: HashMap<String, String>,
+}
+
+/// 某个配置在某个客户端实例上的监听状态
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigListenerStatus {
+    /// 服务ID
+    pub service_id: String,
+    /// 实例ID
+    pub instance_id: String,
+    /// 客户端地址
+    pub address: String,
+    /// 客户端已应用的配置MD5
+    pub md5: String,
+    /// 是否为最新配置，由查询方根据当前配置MD5计算
+    #[serde(default)]
+    pub up_to_date: bool,
+    /// 最后上报时间
+    pub reported_at: DateTime<Local>,
+}
+
+#[derive(Debug)]
+struct Report {
+    report: ClientConfigReport,
+    reported_at: DateTime<Local>,
+}
+
+/// 配置监听状态注册表
+#[derive(Debug, Default)]
+pub struct ConfigListeners {
+    /// key为(命名空间ID, 实例ID)
+    reports: DashMap<(String, String), Report>,
+}
+
+impl ConfigListeners {
+    /// 记录客户端上报
+    pub fn report(&self, report: ClientConfigReport) {
+        self.remove_expired();
+        self.reports.insert(
+            (report.namespace_id.clone(), report.instance_id.clone()),
+            Report {
+                report,
+                reported_at: Local::now(),
+            },
+        );
+    }
+
+    /// 查询本节点上监听指定配置的客户端
+    pub fn list(&self, namespace_id: &str, config_id: &str) -> Vec<ConfigListenerStatus> {
+        self.remove_expired();
+        self.reports
+            .iter()
+            .filter(|r| r.report.namespace_id == namespace_id)
+            .filter_map(|r| {
+                r.report
+                    .configs
+                    .get(config_id)
+                    .map(|md5| ConfigListenerStatus {
+                        service_id: r.report.service_id.clone(),
+                        instance_id: r.report.instance_id.clone(),
+                        address: r.report.address.clone(),
+                        md5: md5.clone(),
+                        up_to_date: false,
+                        reported_at: r.reported_at,
+                    })
+            })
+            .collect()
+    }
+
+    fn remove_expired(&self) {
+        let deadline = Local::now() - chrono::Duration::seconds(REPORT_EXPIRE_SECS);
+        self.reports.retain(|_, r| r.reported_at > deadline);
+    }
+}
@@ -1,6 +1,17 @@
 use crate::Args;
+use crate::app::get_app;
+use crate::auth::cluster::with_cluster_signature;
+use crate::config::server::diff::ConfigDiff;
+use crate::config::server::enc_dec::DecryptKey;
+use crate::config::server::encryption::{ConfigEncryption, DataKey};
+use crate::config::server::journal::ChangeJournal;
+use crate::config::server::label::{LABEL_SEPARATOR, Labels, select_variant, split_variant_id};
+use crate::config::server::listener::{ConfigListenerStatus, ConfigListeners};
+use crate::config::server::stats::{ConfigFetchStat, ConfigFetchStats};
+use crate::config::server::watcher::Watchers;
 use crate::db::DbPool;
 use crate::protocol::id;
+use crate::protocol::res::{CodeError, Res};
 use crate::raft::RaftRequest;
 use crate::raft::api::raft_write;
 use anyhow::{Context, bail};
@@ -8,15 +19,41 @@ use chrono::{DateTime, Local};
 use dashmap::DashMap;
 use indexmap::IndexMap;
 use rocket::fs::TempFile;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 use std::io::{Cursor, Write};
+use std::time::Duration;
 use tracing::log;
 
 pub mod api;
-
-#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub mod convert;
+pub mod diff;
+pub mod enc_dec;
+pub mod encryption;
+pub mod journal;
+pub mod label;
+pub mod lint;
+pub mod listener;
+pub mod sensitive;
+pub mod spring;
+pub mod stats;
+pub mod watcher;
+
+/// 配置历史每页最大条数
+pub const MAX_HISTORY_PAGE_SIZE: i32 = 100;
+
+/// 全文搜索每页最大条数
+pub const MAX_SEARCH_PAGE_SIZE: i32 = 100;
+
+/// 发布校验轮询间隔
+const VERIFY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 查询配置历史，历史内容从`config_content`中关联得到
+const SELECT_HISTORY: &str = "SELECT h.id_, h.namespace_id, h.id, COALESCE(c.content, h.content) AS content, h.create_time, h.update_time, h.description, h.format, h.md5 FROM config_history h LEFT JOIN config_content c ON c.md5 = h.content_md5";
+
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct ConfigEntry {
     /// 递增ID
     pub id_: i64,
@@ -38,12 +75,90 @@ pub struct ConfigEntry {
     pub md5: String,
 }
 
+/// 导出配置的目录结构
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum ExportLayout {
+    /// 配置文件位于根目录，附带`.metadata.yaml`元数据
+    #[default]
+    Conreg,
+    /// 兼容Spring Cloud Config仓库，配置文件位于以命名空间ID命名的目录下，
+    /// 可直接作为Config Server的git或native仓库使用（`search-paths`设置为命名空间ID）
+    SpringCloudConfig,
+}
+
+/// 全文搜索命中结果
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigSearchHit {
+    /// 递增ID
+    pub id_: i64,
+    /// 命名空间
+    pub namespace_id: String,
+    /// 配置ID
+    pub id: String,
+    /// 配置格式
+    pub format: String,
+    /// 描述
+    pub description: Option<String>,
+    /// 更新时间
+    pub update_time: DateTime<Local>,
+    /// 高亮后的配置ID
+    pub id_highlight: String,
+    /// 高亮后的配置内容片段
+    pub snippet: String,
+}
+
+/// 配置列表项，包含配置的获取统计
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigListItem {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub entry: ConfigEntry,
+    /// 客户端获取次数，近似值
+    pub fetch_count: i64,
+    /// 最后获取时间，从未被获取时为空
+    pub last_fetch_time: Option<DateTime<Local>>,
+}
+
+/// 配置发布后的版本信息
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigRevision {
+    /// 配置MD5
+    pub md5: String,
+    /// 提交该变更的Raft日志索引，配置未变化时为空
+    pub log_index: Option<u64>,
+}
+
+/// 配置晋级结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigPromotion {
+    /// 来源命名空间
+    pub source_namespace_id: String,
+    /// 来源版本的配置历史ID，为空时表示来源命名空间的当前配置
+    pub source_history_id_: Option<i64>,
+    /// 来源版本的MD5
+    pub source_md5: String,
+    /// 目标命名空间中配置的新版本
+    pub revision: ConfigRevision,
+}
+
 impl ConfigEntry {
+    /// 配置ID或配置内容是否包含`filter`，与SQLite的`LIKE`一致，ASCII字母不区分大小写
+    fn matches_filter(&self, filter: &str) -> bool {
+        let filter = filter.to_ascii_lowercase();
+        self.id.to_ascii_lowercase().contains(&filter)
+            || self.content.to_ascii_lowercase().contains(&filter)
+    }
+
     /// 计算配置内容的MD5
     pub fn gen_md5(content: &str, description: &Option<String>) -> String {
         let digest = md5::compute(format!("{}{:?}", content, description));
         format!("{:x}", digest)
     }
+
+    /// 计算配置内容本身的MD5，用于历史内容去重
+    pub fn gen_content_md5(content: &str) -> String {
+        format!("{:x}", md5::compute(content))
+    }
 }
 
 /// 配置管理
@@ -55,15 +170,29 @@ pub struct ConfigManager {
     sender: tokio::sync::broadcast::Sender<ConfigChangeEvent>,
     /// 配置缓存
     config_cache: DashMap<(String, String), Option<ConfigEntry>>,
+    /// 配置监听会话
+    pub watchers: Watchers,
+    /// 客户端上报的配置监听状态
+    pub listeners: ConfigListeners,
+    /// 配置获取统计
+    pub fetch_stats: ConfigFetchStats,
+    /// 配置变更日志
+    pub journal: ChangeJournal,
+    /// 配置加密
+    pub encryption: ConfigEncryption,
+    /// `DEC(...)`配置值的加解密密钥，未指定时为None
+    pub decrypt_key: Option<DecryptKey>,
 }
 
 /// 配置变更事件
 #[derive(Debug, Clone)]
 pub struct ConfigChangeEvent {
     /// 命名空间ID
-    namespace_id: String,
+    pub namespace_id: String,
     /// 配置ID
-    config_id: String,
+    pub config_id: String,
+    /// 变更日志中的版本号
+    pub revision: u64,
 }
 
 impl ConfigManager {
@@ -73,16 +202,38 @@ impl ConfigManager {
             args: args.clone(),
             sender,
             config_cache: DashMap::new(),
+            watchers: Watchers::default(),
+            listeners: ConfigListeners::default(),
+            fetch_stats: ConfigFetchStats::default(),
+            journal: ChangeJournal::load(&args.data_dir),
+            encryption: ConfigEncryption::new(args)?,
+            decrypt_key: DecryptKey::load(args)?,
         })
     }
 
+    /// 订阅配置变更事件
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<ConfigChangeEvent> {
+        self.sender.subscribe()
+    }
+
+    /// 先记录变更日志再发送通知，订阅后读取变更日志的监听不会遗漏变更
     fn notify_config_change(&self, namespace_id: String, config_id: String) {
+        let revision = self.journal.record(&namespace_id, &config_id);
         let _ = self.sender.send(ConfigChangeEvent {
             namespace_id,
             config_id,
+            revision,
         });
     }
 
+    /// 保存配置变更日志，在正常退出时调用
+    pub fn save_journal(&self) {
+        match self.journal.save(&self.args.data_dir) {
+            Ok(()) => log::info!("watch journal saved"),
+            Err(e) => log::error!("save watch journal error: {}", e),
+        }
+    }
+
     /// 获取配置
     pub async fn get_config(
         &self,
@@ -96,12 +247,15 @@ impl ConfigManager {
         {
             return Ok(config.clone());
         }
-        let config: Option<ConfigEntry> =
+        let mut config: Option<ConfigEntry> =
             sqlx::query_as("SELECT * FROM config WHERE namespace_id = ? AND id = ?")
                 .bind(namespace_id)
                 .bind(config_id)
                 .fetch_optional(DbPool::get())
                 .await?;
+        if let Some(config) = config.as_mut() {
+            self.encryption.decrypt_entry(config).await?;
+        }
 
         if self.args.enable_cache_config {
             self.config_cache.insert(
@@ -113,6 +267,47 @@ impl ConfigManager {
         Ok(config)
     }
 
+    /// 获取命名空间下的所有配置，包括标签变体，按配置ID排序
+    pub async fn list_all_configs(&self, namespace_id: &str) -> anyhow::Result<Vec<ConfigEntry>> {
+        let mut configs: Vec<ConfigEntry> =
+            sqlx::query_as("SELECT * FROM config WHERE namespace_id = ? ORDER BY id")
+                .bind(namespace_id)
+                .fetch_all(DbPool::get())
+                .await?;
+        self.decrypt_entries(configs.iter_mut()).await?;
+
+        Ok(configs)
+    }
+
+    /// 按标签选择器获取配置
+    ///
+    /// 返回标签完全包含在选择器中且匹配标签数最多的变体，没有匹配的变体时返回不带标签的配置，
+    /// 详见[`label`]
+    pub async fn get_config_by_labels(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+        selector: &Labels,
+    ) -> anyhow::Result<Option<ConfigEntry>> {
+        if selector.is_empty() {
+            return self.get_config(namespace_id, config_id).await;
+        }
+        let prefix = format!("{}{}", config_id, LABEL_SEPARATOR);
+        let ids: Vec<String> = sqlx::query_scalar(
+            "SELECT id FROM config WHERE namespace_id = ? AND (id = ? OR substr(id, 1, ?) = ?)",
+        )
+        .bind(namespace_id)
+        .bind(config_id)
+        .bind(prefix.chars().count() as i64)
+        .bind(&prefix)
+        .fetch_all(DbPool::get())
+        .await?;
+        match select_variant(config_id, selector, ids.iter().map(String::as_str)) {
+            Some(id) => self.get_config(namespace_id, id).await,
+            None => Ok(None),
+        }
+    }
+
     /// 创建或更新配置，并同步到集群的其他节点
     pub async fn upsert_config_and_sync(
         &self,
@@ -121,7 +316,12 @@ impl ConfigManager {
         content: &str,
         description: Option<String>,
         format: &str,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<ConfigRevision> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
         // 旧配置
         let config = self.get_config(namespace_id, config_id).await?;
         // 新配置的MD5
@@ -129,10 +329,13 @@ impl ConfigManager {
         // 配置内容未改变，不处理
         if config.is_some() && config.as_ref().unwrap().md5 == md5 {
             log::info!("config content not change");
-            return Ok(());
+            return Ok(ConfigRevision {
+                md5,
+                log_index: None,
+            });
         }
 
-        match config {
+        let log_index = match config {
             None => {
                 let entry = ConfigEntry {
                     id_: id::next(),
@@ -142,11 +345,11 @@ impl ConfigManager {
                     create_time: Local::now(),
                     update_time: Local::now(),
                     description,
-                    md5,
+                    md5: md5.clone(),
                     format: format.to_string(),
                 };
                 // 同步数据
-                self.sync(RaftRequest::SetConfig { entry }).await?;
+                self.sync(RaftRequest::SetConfig { entry }).await?
             }
             Some(old) => {
                 let entry = ConfigEntry {
@@ -157,28 +360,213 @@ impl ConfigManager {
                     create_time: old.create_time,
                     update_time: Local::now(),
                     description,
-                    md5,
+                    md5: md5.clone(),
                     format: format.to_string(),
                 };
                 // 同步数据
-                self.sync(RaftRequest::UpdateConfig { entry }).await?;
+                self.sync(RaftRequest::UpdateConfig { entry }).await?
+            }
+        };
+
+        Ok(ConfigRevision {
+            md5,
+            log_index: Some(log_index),
+        })
+    }
+
+    /// 从数据库中读取配置的MD5，不经过缓存
+    pub async fn get_config_md5(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let md5: Option<String> =
+            sqlx::query_scalar("SELECT md5 FROM config WHERE namespace_id = ? AND id = ?")
+                .bind(namespace_id)
+                .bind(config_id)
+                .fetch_optional(DbPool::get())
+                .await?;
+        Ok(md5)
+    }
+
+    /// 对比客户端已加载配置的MD5，返回MD5不一致的配置ID
+    ///
+    /// - configs: key为配置ID，value为客户端已加载配置的MD5
+    /// - selector: 标签选择器，与客户端获取配置时一致
+    ///
+    /// 配置不存在时MD5视为空字符串
+    pub async fn changed_configs(
+        &self,
+        namespace_id: &str,
+        configs: &BTreeMap<String, String>,
+        selector: &Labels,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut changed = vec![];
+        for (config_id, md5) in configs {
+            let current = self
+                .get_config_by_labels(namespace_id, config_id, selector)
+                .await?
+                .map(|entry| entry.md5)
+                .unwrap_or_default();
+            if current != *md5 {
+                changed.push(config_id.clone());
             }
         }
+        Ok(changed)
+    }
 
+    /// 校验配置已发布生效
+    ///
+    /// 配置变更提交到Raft后，由各节点异步应用到数据库，该方法轮询本节点数据库，
+    /// 直到配置的MD5与期望值一致。`quorum`为true时，还需要超过半数的投票节点上生效。
+    pub async fn verify_config_published(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+        md5: &str,
+        quorum: bool,
+        timeout: Duration,
+    ) -> anyhow::Result<()> {
+        let verify = async {
+            loop {
+                if self
+                    .get_config_md5(namespace_id, config_id)
+                    .await?
+                    .as_deref()
+                    == Some(md5)
+                {
+                    break;
+                }
+                tokio::time::sleep(VERIFY_POLL_INTERVAL).await;
+            }
+            if quorum {
+                self.wait_quorum_published(namespace_id, config_id, md5)
+                    .await?;
+            }
+            anyhow::Ok(())
+        };
+        match tokio::time::timeout(timeout, verify).await {
+            Ok(res) => res,
+            Err(_) => bail!(
+                "config {} in {} not published within {}ms",
+                config_id,
+                namespace_id,
+                timeout.as_millis()
+            ),
+        }
+    }
+
+    /// 等待超过半数的投票节点上配置生效，本节点已生效
+    async fn wait_quorum_published(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+        md5: &str,
+    ) -> anyhow::Result<()> {
+        let app = get_app();
+        let raft_metrics = app.raft.metrics().borrow().clone();
+        let membership = raft_metrics.membership_config.membership();
+        let voters = membership.voter_ids().collect::<Vec<_>>();
+        let quorum = voters.len() / 2 + 1;
+
+        let mut peers = voters
+            .iter()
+            .filter(|id| **id != app.id)
+            .filter_map(|id| membership.get_node(id))
+            .map(|node| node.addr.clone())
+            .collect::<Vec<_>>();
+        // 本节点已生效
+        let mut published = 1;
+
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        while published < quorum {
+            let mut pending = Vec::new();
+            for addr in peers {
+                if peer_config_md5(&client, &addr, namespace_id, config_id)
+                    .await
+                    .is_some_and(|m| m == md5)
+                {
+                    published += 1;
+                } else {
+                    pending.push(addr);
+                }
+            }
+            peers = pending;
+            if published < quorum {
+                tokio::time::sleep(VERIFY_POLL_INTERVAL).await;
+            }
+        }
         Ok(())
     }
 
+    /// 查询监听指定配置的客户端实例，汇总集群所有节点的上报数据
+    ///
+    /// 结果中未更新到最新配置的实例排在前面
+    pub async fn list_config_listeners(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+    ) -> anyhow::Result<Vec<ConfigListenerStatus>> {
+        let mut list = self.listeners.list(namespace_id, config_id);
+
+        let app = get_app();
+        let raft_metrics = app.raft.metrics().borrow().clone();
+        let peers = raft_metrics
+            .membership_config
+            .membership()
+            .nodes()
+            .filter(|(id, _)| **id != app.id)
+            .map(|(_, node)| node.addr.clone())
+            .collect::<Vec<_>>();
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        for addr in peers {
+            list.extend(peer_config_listeners(&client, &addr, namespace_id, config_id).await);
+        }
+
+        // 同一实例可能先后上报到不同节点，仅保留最新的上报
+        let mut latest: BTreeMap<String, ConfigListenerStatus> = BTreeMap::new();
+        for status in list {
+            match latest.get(&status.instance_id) {
+                Some(old) if old.reported_at >= status.reported_at => {}
+                _ => {
+                    latest.insert(status.instance_id.clone(), status);
+                }
+            }
+        }
+
+        let md5 = self.get_config_md5(namespace_id, config_id).await?;
+        let mut list = latest
+            .into_values()
+            .map(|mut status| {
+                status.up_to_date = md5.as_ref() == Some(&status.md5);
+                status
+            })
+            .collect::<Vec<_>>();
+        list.sort_by(|a, b| {
+            (a.up_to_date, &a.service_id, &a.instance_id).cmp(&(
+                b.up_to_date,
+                &b.service_id,
+                &b.instance_id,
+            ))
+        });
+        Ok(list)
+    }
+
     /// 新增配置
     ///
     /// 注意：该方法不应该直接调用，而需要由raft apply log时调用，以保证数据一致性
     pub async fn insert_config(&self, entry: ConfigEntry) -> anyhow::Result<()> {
+        let content = self
+            .encryption
+            .encrypt(&entry.namespace_id, &entry.content)
+            .await?;
         sqlx::query(
             "INSERT INTO config (id_, namespace_id, id, content, description,format, create_time, update_time, md5) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
             .bind(entry.id_)
             .bind(&entry.namespace_id)
             .bind(&entry.id)
-            .bind(&entry.content)
+            .bind(content.as_ref().unwrap_or(&entry.content))
             .bind(&entry.description)
             .bind(&entry.format)
             .bind(entry.create_time)
@@ -199,10 +587,14 @@ impl ConfigManager {
     ///
     /// 注意：该方法不应该直接调用，而需要由raft apply log时调用，以保证数据一致性
     pub async fn update_config(&self, entry: ConfigEntry) -> anyhow::Result<()> {
+        let content = self
+            .encryption
+            .encrypt(&entry.namespace_id, &entry.content)
+            .await?;
         sqlx::query(
             "UPDATE config SET content = ?, description = ?, update_time = ?, format = ?, md5 = ? WHERE id_ = ?",
         )
-            .bind(&entry.content)
+            .bind(content.as_ref().unwrap_or(&entry.content))
             .bind(&entry.description)
             .bind(entry.update_time)
             .bind(&entry.format)
@@ -232,6 +624,11 @@ impl ConfigManager {
         namespace_id: &str,
         config_id: &str,
     ) -> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
         self.sync(RaftRequest::DeleteConfig {
             namespace_id: namespace_id.to_string(),
             id: config_id.to_string(),
@@ -254,56 +651,230 @@ impl ConfigManager {
         Ok(())
     }
 
+    /// 设置配置的敏感配置项，并同步到集群
+    ///
+    /// `keys`为None时取消标记，为空时整个配置标记为敏感，详见[`sensitive`]
+    pub async fn set_sensitive_keys_and_sync(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+        keys: Option<Vec<String>>,
+    ) -> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        let keys = keys.map(|keys| {
+            keys.iter()
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .collect()
+        });
+        self.sync(RaftRequest::SetConfigSensitiveKeys {
+            namespace_id: namespace_id.to_string(),
+            id: split_variant_id(config_id).0.to_string(),
+            keys,
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_sensitive_keys(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+        keys: Option<Vec<String>>,
+    ) -> anyhow::Result<()> {
+        match keys {
+            Some(keys) => {
+                sqlx::query(
+                    "INSERT OR REPLACE INTO config_sensitive (namespace_id, id, keys, update_time) VALUES (?, ?, ?, ?)",
+                )
+                .bind(namespace_id)
+                .bind(config_id)
+                .bind(serde_json::to_string(&keys)?)
+                .bind(Local::now())
+                .execute(DbPool::get())
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM config_sensitive WHERE namespace_id = ? AND id = ?")
+                    .bind(namespace_id)
+                    .bind(config_id)
+                    .execute(DbPool::get())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 获取配置的敏感配置项，未标记为敏感时返回None，为空表示整个配置
+    pub async fn get_sensitive_keys(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let keys: Option<String> = sqlx::query_scalar(
+            "SELECT keys FROM config_sensitive WHERE namespace_id = ? AND id = ?",
+        )
+        .bind(namespace_id)
+        .bind(split_variant_id(config_id).0)
+        .fetch_optional(DbPool::get())
+        .await?;
+        Ok(keys.map(|keys| serde_json::from_str(&keys)).transpose()?)
+    }
+
+    /// 获取命名空间下所有敏感配置的敏感配置项，key为配置ID
+    pub async fn list_sensitive_keys(
+        &self,
+        namespace_id: &str,
+    ) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT id, keys FROM config_sensitive WHERE namespace_id = ?")
+                .bind(namespace_id)
+                .fetch_all(DbPool::get())
+                .await?;
+        let mut sensitive = HashMap::new();
+        for (id, keys) in rows {
+            sensitive.insert(id, serde_json::from_str(&keys)?);
+        }
+        Ok(sensitive)
+    }
+
+    /// 累加配置获取统计
+    ///
+    /// 已删除的配置不再记录
+    pub async fn add_fetch_stats(&self, stats: Vec<ConfigFetchStat>) -> anyhow::Result<()> {
+        let mut tx = DbPool::get().begin().await?;
+        for stat in stats {
+            sqlx::query(
+                "INSERT INTO config_fetch_stat (namespace_id, id, fetch_count, last_fetch_time) \
+                 SELECT ?, ?, ?, ? WHERE EXISTS (SELECT 1 FROM config WHERE namespace_id = ? AND id = ?) \
+                 ON CONFLICT (namespace_id, id) DO UPDATE SET \
+                 fetch_count = fetch_count + excluded.fetch_count, \
+                 last_fetch_time = MAX(IFNULL(last_fetch_time, ''), excluded.last_fetch_time)",
+            )
+            .bind(&stat.namespace_id)
+            .bind(&stat.id)
+            .bind(stat.count as i64)
+            .bind(stat.last_fetch_time)
+            .bind(&stat.namespace_id)
+            .bind(&stat.id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
     #[allow(unused)]
     pub async fn get_history(
         &self,
         namespace_id: &str,
         config_id: &str,
     ) -> anyhow::Result<Vec<ConfigEntry>> {
-        let rows: Vec<ConfigEntry> = sqlx::query_as(
-            "SELECT * FROM config_history WHERE namespace_id = ? AND id = ? ORDER BY id_ DESC",
-        )
+        let mut rows: Vec<ConfigEntry> = sqlx::query_as(&format!(
+            "{} WHERE h.namespace_id = ? AND h.id = ? ORDER BY h.id_ DESC",
+            SELECT_HISTORY
+        ))
         .bind(namespace_id)
         .bind(config_id)
         .fetch_all(DbPool::get())
         .await?;
+        self.decrypt_entries(rows.iter_mut()).await?;
 
         Ok(rows)
     }
 
-    #[allow(unused)]
-    pub async fn get_history_by_id_(
-        &self,
-        id_: i64,
-    ) -> anyhow::Result<Option<ConfigEntry>> {
-        let row: Option<ConfigEntry> = sqlx::query_as(
-            "SELECT * FROM config_history WHERE id_ = ? ",
-        )
-            .bind(id_)
-            .fetch_optional(DbPool::get())
-            .await?;
+    pub async fn get_history_by_id_(&self, id_: i64) -> anyhow::Result<Option<ConfigEntry>> {
+        let mut row: Option<ConfigEntry> =
+            sqlx::query_as(&format!("{} WHERE h.id_ = ?", SELECT_HISTORY))
+                .bind(id_)
+                .fetch_optional(DbPool::get())
+                .await?;
+        self.decrypt_entries(row.iter_mut()).await?;
 
         Ok(row)
     }
 
+    /// 对比配置的两个历史版本
+    ///
+    /// `to_id_`为空时与当前配置对比，两个版本必须属于同一命名空间下的同一配置
+    ///
+    /// `sensitive`为需要打码的敏感配置项，两个版本打码后再对比，见[`sensitive::mask_entry`]
+    pub async fn diff_history(
+        &self,
+        namespace_id: &str,
+        config_id: &str,
+        from_id_: i64,
+        to_id_: Option<i64>,
+        sensitive: &HashMap<String, Vec<String>>,
+    ) -> anyhow::Result<ConfigDiff> {
+        let history = async |id_: i64| -> anyhow::Result<ConfigEntry> {
+            match self.get_history_by_id_(id_).await? {
+                Some(entry) if entry.namespace_id == namespace_id && entry.id == config_id => {
+                    Ok(entry)
+                }
+                _ => bail!("No history config found with id {}", id_),
+            }
+        };
+        let mut from = history(from_id_).await?;
+        let mut to = match to_id_ {
+            Some(id_) => history(id_).await?,
+            None => match self.get_config(namespace_id, config_id).await? {
+                Some(config) => config,
+                None => bail!("config {} not found", config_id),
+            },
+        };
+        sensitive::mask_entry(&mut from, sensitive);
+        sensitive::mask_entry(&mut to, sensitive);
+        Ok(diff::diff(&from, &to, from_id_, to_id_))
+    }
+
+    /// 添加历史记录
+    ///
+    /// 历史内容按内容的MD5去重保存在`config_content`中，历史记录仅保存内容的MD5，
+    /// 避免多次回滚或重复发布相同内容时重复存储
     pub async fn append_history(&self, entry: &ConfigEntry) -> anyhow::Result<()> {
         log::info!("append history: {:?}", entry);
+        let (content_md5, content) = match self
+            .encryption
+            .encrypt(&entry.namespace_id, &entry.content)
+            .await?
+        {
+            Some(content) => (
+                encryption::content_md5(&entry.namespace_id, &entry.content),
+                content,
+            ),
+            None => (
+                ConfigEntry::gen_content_md5(&entry.content),
+                entry.content.clone(),
+            ),
+        };
+        let mut tx = DbPool::get().begin().await?;
+        sqlx::query("INSERT OR IGNORE INTO config_content (md5, content) VALUES (?, ?)")
+            .bind(&content_md5)
+            .bind(&content)
+            .execute(&mut *tx)
+            .await?;
         // 保存历史
         sqlx::query(
-            "INSERT INTO config_history (id_, namespace_id, id, content, description, create_time, update_time, md5, format) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO config_history (id_, namespace_id, id, content, description, create_time, update_time, md5, format, content_md5) VALUES (?, ?, ?, '', ?, ?, ?, ?, ?, ?)",
         )
             // 注意这个ID，不能自增或随机生成，需要从entry中计算而来，以保证多节点下的数据的一致性
             .bind(entry.id_ + entry.update_time.timestamp_millis())
             .bind(&entry.namespace_id)
             .bind(&entry.id)
-            .bind(&entry.content)
             .bind(&entry.description)
             .bind(entry.create_time)
             .bind(entry.update_time)
             .bind(&entry.md5)
             .bind(&entry.format)
-            .execute(DbPool::get())
+            .bind(&content_md5)
+            .execute(&mut *tx)
             .await?;
+        tx.commit().await?;
 
         Ok(())
     }
@@ -314,18 +885,20 @@ impl ConfigManager {
             .bind(id)
             .execute(DbPool::get())
             .await?;
+        // 清理不再被引用的历史内容
+        sqlx::query(
+            "DELETE FROM config_content WHERE md5 NOT IN (SELECT content_md5 FROM config_history WHERE content_md5 IS NOT NULL)",
+        )
+        .execute(DbPool::get())
+        .await?;
         Ok(())
     }
 
     /// 恢复配置
     ///
     /// - id_: 配置历史ID
-    pub async fn recovery(&self, id_: i64) -> anyhow::Result<()> {
-        let history: Option<ConfigEntry> =
-            sqlx::query_as("SELECT * FROM config_history WHERE id_ = ?")
-                .bind(id_)
-                .fetch_optional(DbPool::get())
-                .await?;
+    pub async fn recovery(&self, id_: i64) -> anyhow::Result<ConfigRevision> {
+        let history = self.get_history_by_id_(id_).await?;
 
         if history.is_none() {
             bail!("No history config found with id {}", id_);
@@ -340,50 +913,155 @@ impl ConfigManager {
             history.description,
             &history.format,
         )
-        .await?;
+        .await
+    }
 
+    /// 将配置的指定版本从来源命名空间复制到目标命名空间（晋级），如dev→staging→prod
+    ///
+    /// - source_history_id_: 来源版本的配置历史ID，为空时复制来源命名空间的当前配置
+    ///
+    /// 目标配置的描述中记录来源命名空间和来源版本的MD5，便于在配置历史中追溯
+    pub async fn promote_and_sync(
+        &self,
+        source_namespace_id: &str,
+        target_namespace_id: &str,
+        config_id: &str,
+        source_history_id_: Option<i64>,
+    ) -> anyhow::Result<ConfigPromotion> {
+        if source_namespace_id == target_namespace_id {
+            bail!("source and target namespace can not be the same");
+        }
+        if !get_app()
+            .namespace_app
+            .manager
+            .exists_namespace(target_namespace_id)
+            .await?
+        {
+            bail!("namespace {} not found", target_namespace_id);
+        }
+        let source = match source_history_id_ {
+            Some(id_) => match self.get_history_by_id_(id_).await? {
+                Some(entry)
+                    if entry.namespace_id == source_namespace_id && entry.id == config_id =>
+                {
+                    entry
+                }
+                _ => bail!("No history config found with id {}", id_),
+            },
+            None => match self.get_config(source_namespace_id, config_id).await? {
+                Some(entry) => entry,
+                None => bail!("config {} not found", config_id),
+            },
+        };
+        let description = promoted_description(
+            source_namespace_id,
+            &source.md5,
+            source.description.as_deref(),
+        );
+        let revision = self
+            .upsert_config_and_sync(
+                target_namespace_id,
+                config_id,
+                &source.content,
+                Some(description),
+                &source.format,
+            )
+            .await?;
+        log::info!(
+            "config {} promoted from {} to {}",
+            config_id,
+            source_namespace_id,
+            target_namespace_id
+        );
+        Ok(ConfigPromotion {
+            source_namespace_id: source_namespace_id.to_string(),
+            source_history_id_,
+            source_md5: source.md5,
+            revision,
+        })
+    }
+
+    /// 解密从数据库中读取的配置内容
+    async fn decrypt_entries(
+        &self,
+        entries: impl Iterator<Item = &mut ConfigEntry>,
+    ) -> anyhow::Result<()> {
+        for entry in entries {
+            self.encryption.decrypt_entry(entry).await?;
+        }
         Ok(())
     }
 
+    /// 轮换命名空间的配置加密数据密钥，并同步到集群，命名空间未开启加密时开启加密
+    ///
+    /// 新的数据密钥在本节点生成，由主密钥加密后同步，详见[`encryption`]
+    pub async fn rotate_data_key_and_sync(&self, namespace_id: &str) -> anyhow::Result<DataKey> {
+        if !get_app()
+            .namespace_app
+            .manager
+            .exists_namespace(namespace_id)
+            .await?
+        {
+            bail!("namespace {} not found", namespace_id);
+        }
+        let key = self.encryption.generate_key(namespace_id)?;
+        self.sync(RaftRequest::RotateConfigKey { key: key.clone() })
+            .await?;
+        Ok(key)
+    }
+
     /// 将配置变更提交到raft集群执行，使得raft应用变更日志，以保持数据一致性，
     /// 同步操作会阻塞进行，直到raft日志同步成功（即超过半数的节点写入成功）
-    async fn sync(&self, request: RaftRequest) -> anyhow::Result<()> {
+    ///
+    /// 返回提交该变更的Raft日志索引
+    async fn sync(&self, request: RaftRequest) -> anyhow::Result<u64> {
         log::info!("sync config request: {:?}", request);
         let res = raft_write(request).await;
         if !res.is_success() {
             log::error!("sync config error: {:?}", res.msg);
-            bail!("sync config error: {}", res.msg);
+            bail!(CodeError {
+                code: res.code,
+                msg: format!("sync config error: {}", res.msg),
+            });
         }
         log::info!("sync config success");
-        Ok(())
+        Ok(res.data.map(|r| r.log_id.index).unwrap_or_default())
     }
 
     /// 查询配置列表（分页）
+    ///
+    /// `filter_text`按子串匹配配置ID和配置内容。命名空间开启加密时数据库中的内容为密文，
+    /// 解密后在内存中匹配和分页。
     pub async fn list_configs_with_page(
         &self,
         namespace_id: &str,
         page_num: i32,
         page_size: i32,
         filter_text: Option<String>,
-    ) -> anyhow::Result<(u64, Vec<ConfigEntry>)> {
-        let mut query_sql = "SELECT * FROM config WHERE namespace_id = ?".to_string();
-        let mut count_sql = "SELECT COUNT(1) FROM config WHERE namespace_id = ?".to_string();
-
-        if let Some(filter) = filter_text.as_ref()
-            && !filter.is_empty()
+    ) -> anyhow::Result<(u64, Vec<ConfigListItem>)> {
+        let filter_text = filter_text.filter(|filter| !filter.is_empty());
+        if let Some(filter) = &filter_text
+            && self.encryption.active_key_id(namespace_id).await?.is_some()
         {
-            query_sql.push_str(" AND (id LIKE ? OR content LIKE ?)");
-            count_sql.push_str(" AND (id LIKE ? OR content LIKE ?)");
+            return self
+                .filter_encrypted_configs(namespace_id, page_num, page_size, filter)
+                .await;
         }
 
-        query_sql.push_str(" ORDER BY id_ DESC LIMIT ?, ?");
+        let mut query_sql = "SELECT c.*, COALESCE(s.fetch_count, 0) AS fetch_count, s.last_fetch_time FROM config c LEFT JOIN config_fetch_stat s ON s.namespace_id = c.namespace_id AND s.id = c.id WHERE c.namespace_id = ?".to_string();
+        let mut count_sql = "SELECT COUNT(1) FROM config c WHERE c.namespace_id = ?".to_string();
+
+        if filter_text.is_some() {
+            query_sql.push_str(" AND (c.id LIKE ? OR c.content LIKE ?)");
+            count_sql.push_str(" AND (c.id LIKE ? OR c.content LIKE ?)");
+        }
+
+        query_sql.push_str(" ORDER BY c.id_ DESC LIMIT ?, ?");
 
         let mut query = sqlx::query_as(&query_sql).bind(namespace_id);
         let mut count_query = sqlx::query_scalar(&count_sql).bind(namespace_id);
 
-        if let Some(filter) = filter_text
-            && !filter.is_empty()
-        {
+        if let Some(filter) = filter_text {
             let filter_pattern = format!("%{}%", filter);
             query = query
                 .bind(filter_pattern.clone())
@@ -397,19 +1075,56 @@ impl ConfigManager {
         query = query.bind(offset).bind(page_size);
 
         let total: u64 = count_query.fetch_one(DbPool::get()).await?;
-        let rows: Vec<ConfigEntry> = query.fetch_all(DbPool::get()).await?;
+        let mut rows: Vec<ConfigListItem> = query.fetch_all(DbPool::get()).await?;
+        self.decrypt_entries(rows.iter_mut().map(|item| &mut item.entry))
+            .await?;
 
         Ok((total, rows))
     }
 
+    /// 解密命名空间的所有配置后按子串匹配配置ID和配置内容，并分页
+    async fn filter_encrypted_configs(
+        &self,
+        namespace_id: &str,
+        page_num: i32,
+        page_size: i32,
+        filter: &str,
+    ) -> anyhow::Result<(u64, Vec<ConfigListItem>)> {
+        let mut rows: Vec<ConfigListItem> = sqlx::query_as(
+            "SELECT c.*, COALESCE(s.fetch_count, 0) AS fetch_count, s.last_fetch_time FROM config c LEFT JOIN config_fetch_stat s ON s.namespace_id = c.namespace_id AND s.id = c.id WHERE c.namespace_id = ? ORDER BY c.id_ DESC",
+        )
+        .bind(namespace_id)
+        .fetch_all(DbPool::get())
+        .await?;
+        self.decrypt_entries(rows.iter_mut().map(|item| &mut item.entry))
+            .await?;
+        rows.retain(|item| item.entry.matches_filter(filter));
+
+        let total = rows.len() as u64;
+        let offset = ((page_num.max(1) - 1) * page_size.max(0)) as usize;
+        let rows = rows
+            .into_iter()
+            .skip(offset)
+            .take(page_size.max(0) as usize)
+            .collect();
+        Ok((total, rows))
+    }
+
     /// 查询配置历史列表（分页）
+    ///
+    /// - before_id_: 游标，传入上一页最后一条记录的`id_`时，使用keyset方式分页，
+    ///   此时忽略`page_num`，避免历史记录较多时OFFSET扫描过多的行
+    /// - page_size: 最大不超过[`MAX_HISTORY_PAGE_SIZE`]
     pub async fn list_config_history_with_page(
         &self,
         namespace_id: &str,
         id: &str,
         page_num: i32,
         page_size: i32,
+        before_id_: Option<i64>,
     ) -> anyhow::Result<(u64, Vec<ConfigEntry>)> {
+        let page_size = page_size.clamp(1, MAX_HISTORY_PAGE_SIZE);
+
         let total: u64 = sqlx::query_scalar(
             "SELECT COUNT(1) FROM config_history WHERE namespace_id = ? AND id = ?",
         )
@@ -418,31 +1133,115 @@ impl ConfigManager {
         .fetch_one(DbPool::get())
         .await?;
 
+        let mut rows: Vec<ConfigEntry> = match before_id_ {
+            Some(before_id_) => {
+                sqlx::query_as(&format!(
+                    "{} WHERE h.namespace_id = ? AND h.id = ? AND h.id_ < ? ORDER BY h.id_ DESC LIMIT ?",
+                    SELECT_HISTORY
+                ))
+                    .bind(namespace_id)
+                    .bind(id)
+                    .bind(before_id_)
+                    .bind(page_size)
+                    .fetch_all(DbPool::get())
+                    .await?
+            }
+            None => {
+                let offset = (page_num.max(1) - 1) * page_size;
+                sqlx::query_as(&format!(
+                    "{} WHERE h.namespace_id = ? AND h.id = ? ORDER BY h.id_ DESC LIMIT ?, ?",
+                    SELECT_HISTORY
+                ))
+                    .bind(namespace_id)
+                    .bind(id)
+                    .bind(offset)
+                    .bind(page_size)
+                    .fetch_all(DbPool::get())
+                    .await?
+            }
+        };
+        self.decrypt_entries(rows.iter_mut()).await?;
+
+        Ok((total, rows))
+    }
+
+    /// 全文搜索配置（分页）
+    ///
+    /// `query`使用FTS5的查询语法，支持`AND`、`OR`、`NOT`、前缀（`abc*`）以及短语（`"a b"`）等，
+    /// 命中的内容片段使用`<mark></mark>`标记。
+    ///
+    /// - page_size: 最大不超过[`MAX_SEARCH_PAGE_SIZE`]
+    ///
+    /// 全文索引保存的是数据库中的配置内容，开启了加密的命名空间中为密文，不支持全文搜索
+    pub async fn search_configs_with_page(
+        &self,
+        namespace_id: &str,
+        query: &str,
+        page_num: i32,
+        page_size: i32,
+    ) -> anyhow::Result<(u64, Vec<ConfigSearchHit>)> {
+        if self.encryption.active_key_id(namespace_id).await?.is_some() {
+            bail!(
+                "full-text search is not supported in namespace {} because its configs are encrypted",
+                namespace_id
+            );
+        }
+        let page_num = page_num.max(1);
+        let page_size = page_size.clamp(1, MAX_SEARCH_PAGE_SIZE);
+
+        let total: u64 = sqlx::query_scalar(
+            "SELECT COUNT(1) FROM config_fts WHERE config_fts MATCH ? AND namespace_id = ?",
+        )
+        .bind(query)
+        .bind(namespace_id)
+        .fetch_one(DbPool::get())
+        .await?;
+
         let offset = (page_num - 1) * page_size;
 
-        let rows: Vec<ConfigEntry> = sqlx::query_as(
-            "SELECT * FROM config_history WHERE namespace_id = ? AND id = ? ORDER BY id_ DESC LIMIT ?, ?",
+        let rows: Vec<ConfigSearchHit> = sqlx::query_as(
+            r#"
+            SELECT c.id_, c.namespace_id, c.id, c.format, c.description, c.update_time,
+                   highlight(config_fts, 1, '<mark>', '</mark>') AS id_highlight,
+                   snippet(config_fts, 2, '<mark>', '</mark>', '...', 32) AS snippet
+            FROM config_fts
+            JOIN config c ON c.id_ = config_fts.rowid
+            WHERE config_fts MATCH ? AND config_fts.namespace_id = ?
+            ORDER BY rank
+            LIMIT ?, ?
+            "#,
         )
-            .bind(namespace_id)
-            .bind(id)
-            .bind(offset)
-            .bind(page_size)
-            .fetch_all(DbPool::get())
-            .await?;
+        .bind(query)
+        .bind(namespace_id)
+        .bind(offset)
+        .bind(page_size)
+        .fetch_all(DbPool::get())
+        .await?;
 
         Ok((total, rows))
     }
 
+    /// 导出配置为zip文件
+    ///
+    /// 指定`target_format`时，将配置转换为目标格式后导出
+    ///
+    /// `sensitive`为需要打码的敏感配置项，打码后再导出，见[`sensitive::mask_entry`]
     pub(crate) async fn export(
         &self,
         namespace_id: &str,
         ids: Vec<String>,
         is_all: bool,
+        target_format: Option<&str>,
+        layout: ExportLayout,
+        sensitive: &HashMap<String, Vec<String>>,
     ) -> anyhow::Result<Vec<u8>> {
-        let list = if is_all {
+        let mut list: Vec<ConfigEntry> = if is_all {
             self.list_configs_with_page(namespace_id, 1, 10000, None)
                 .await?
                 .1
+                .into_iter()
+                .map(|item| item.entry)
+                .collect()
         } else {
             let mut list = Vec::new();
             for id in ids {
@@ -452,6 +1251,8 @@ impl ConfigManager {
             }
             list
         };
+        list.iter_mut()
+            .for_each(|entry| sensitive::mask_entry(entry, sensitive));
 
         // 元数据
         // yaml格式：
@@ -464,98 +1265,265 @@ impl ConfigManager {
         let mut zip = zip::ZipWriter::new(Cursor::new(&mut buffer));
 
         for item in list.into_iter() {
-            zip.start_file(&item.id, zip::write::FileOptions::<()>::default())?;
-            zip.write_all(item.content.as_bytes())?;
+            let (id, format, content) = match target_format {
+                Some(target_format) => {
+                    convert::convert(&item.id, &item.format, &item.content, target_format)?
+                }
+                None => (item.id, item.format, item.content),
+            };
+
+            let path = match layout {
+                ExportLayout::Conreg => id.clone(),
+                ExportLayout::SpringCloudConfig => format!("{}/{}", namespace_id, id),
+            };
+            zip.start_file(&path, zip::write::FileOptions::<()>::default())?;
+            zip.write_all(content.as_bytes())?;
 
             metadata.push(IndexMap::from([
-                ("id", item.id),
-                ("format", item.format),
+                ("id", id),
+                ("format", format),
                 ("description", item.description.unwrap_or_default()),
             ]));
         }
 
-        let metadata = BTreeMap::from([("metadata", metadata)]);
-
-        zip.start_file(".metadata.yaml", zip::write::FileOptions::<()>::default())?;
-        zip.write_all(serde_yaml::to_string(&metadata)?.as_bytes())?;
+        // Spring Cloud Config仓库不需要元数据，导入时按文件扩展名识别格式
+        if layout == ExportLayout::Conreg {
+            let metadata = BTreeMap::from([("metadata", metadata)]);
+            zip.start_file(".metadata.yaml", zip::write::FileOptions::<()>::default())?;
+            zip.write_all(serde_yaml::to_string(&metadata)?.as_bytes())?;
+        }
 
         let bytes = zip.finish()?;
 
         Ok(bytes.into_inner().to_owned())
     }
 
+    /// 从zip文件导入配置
+    ///
+    /// 没有`.metadata.yaml`元数据时（如Spring Cloud Config仓库），按文件扩展名识别格式，
+    /// 文件名作为配置ID，不支持的文件跳过。指定`target_format`时，将配置转换为目标格式后导入。
     pub(crate) async fn import<'a>(
         &self,
-        namespace_id: &String,
+        namespace_id: &str,
         file: TempFile<'a>,
         is_overwrite: bool,
+        target_format: Option<&str>,
     ) -> anyhow::Result<()> {
         let mut stream = file.open().await?;
 
         let mut buffer = Vec::new();
         tokio::io::copy(&mut stream, &mut buffer).await?;
 
+        self.import_bytes(namespace_id, buffer, is_overwrite, target_format)
+            .await
+    }
+
+    /// 从zip文件内容导入配置，见[`ConfigManager::import`]
+    pub(crate) async fn import_bytes(
+        &self,
+        namespace_id: &str,
+        buffer: Vec<u8>,
+        is_overwrite: bool,
+        target_format: Option<&str>,
+    ) -> anyhow::Result<()> {
         let mut zip = zip::ZipArchive::new(Cursor::new(buffer))?;
 
-        // 读取元数据文件内容
-        let mf_content = {
-            let mut mf = zip.by_name(".metadata.yaml")?;
-            let mut buf = Vec::new();
-            std::io::copy(&mut mf, &mut buf)?;
-            buf
+        // (配置ID, 格式, 描述, 文件路径)
+        let mut items = if zip.index_for_name(".metadata.yaml").is_some() {
+            // 读取元数据文件内容
+            let mf_content = {
+                let mut mf = zip.by_name(".metadata.yaml")?;
+                let mut buf = Vec::new();
+                std::io::copy(&mut mf, &mut buf)?;
+                buf
+            };
+            let metadata: IndexMap<String, Vec<IndexMap<String, String>>> =
+                serde_yaml::from_slice(&mf_content)?;
+            let mut items = vec![];
+            for item in metadata.get("metadata").context("no metadata")? {
+                let id = item.get("id").context("no id")?;
+                let format = item.get("format").context("no format")?;
+                items.push((
+                    id.clone(),
+                    format.clone(),
+                    item.get("description").cloned(),
+                    id.clone(),
+                ));
+            }
+            items
+        } else {
+            let mut items = vec![];
+            for path in zip.file_names() {
+                let id = path.rsplit('/').next().unwrap_or(path);
+                if id.is_empty() || id.starts_with('.') {
+                    continue;
+                }
+                match convert::converter_by_file_name(id) {
+                    Some(converter) => items.push((
+                        id.to_string(),
+                        converter.format().to_string(),
+                        None,
+                        path.to_string(),
+                    )),
+                    None => log::warn!("unsupported config file {}, skip", path),
+                }
+            }
+            items.sort();
+            items
         };
-        let metadata: IndexMap<String, Vec<IndexMap<String, String>>> =
-            serde_yaml::from_slice(&mf_content)?;
-
-        let mut items = metadata.get("metadata").context("no metadata")?.clone();
         items.reverse();
 
-        for item in items {
-            let id = item.get("id").unwrap();
+        for (id, format, description, path) in items {
+            let mut file = zip.by_name(&path)?;
 
-            if self.get_config(namespace_id, id).await?.is_some() && !is_overwrite {
+            let mut content = Vec::new();
+            std::io::copy(&mut file, &mut content)?;
+            let content = String::from_utf8_lossy(&content).to_string();
+
+            let (id, format, content) = match target_format {
+                Some(target_format) => convert::convert(&id, &format, &content, target_format)?,
+                None => (id, format, content),
+            };
+
+            if self.get_config(namespace_id, &id).await?.is_some() && !is_overwrite {
                 log::info!("config {} already exists in {}, skip", id, namespace_id);
                 continue;
             }
 
-            let format = item.get("format").context("no format")?;
-            let description = item.get("description");
-
-            let mut file = zip.by_name(id)?;
-
-            let mut content = Vec::new();
-            std::io::copy(&mut file, &mut content)?;
-
             log::info!("importing config {} to {}", id, namespace_id);
 
-            self.upsert_config_and_sync(
-                namespace_id,
-                id,
-                &String::from_utf8_lossy(&content),
-                description.map(|s| s.to_string()),
-                format,
-            )
-            .await?;
+            self.upsert_config_and_sync(namespace_id, &id, &content, description, &format)
+                .await?;
         }
 
         Ok(())
     }
 }
 
+/// 晋级后目标配置的描述，在来源配置的描述后记录来源命名空间和来源版本的MD5
+fn promoted_description(
+    source_namespace_id: &str,
+    source_md5: &str,
+    description: Option<&str>,
+) -> String {
+    let origin = format!("promoted from {} {}", source_namespace_id, source_md5);
+    match description {
+        Some(description) if !description.is_empty() => format!("{} ({})", description, origin),
+        _ => origin,
+    }
+}
+
+/// 查询其他节点上配置的MD5，请求失败时返回None
+async fn peer_config_md5(
+    client: &reqwest::Client,
+    addr: &str,
+    namespace_id: &str,
+    config_id: &str,
+) -> Option<String> {
+    let url = format!("http://{}/api/config/md5", addr);
+    let request = client
+        .get(&url)
+        .query(&[("namespace_id", namespace_id), ("id", config_id)]);
+    let res = with_cluster_signature(request)
+        .timeout(Duration::from_secs(1))
+        .send()
+        .await
+        .ok()?
+        .json::<Res<Option<String>>>()
+        .await
+        .ok()?;
+    res.data.flatten()
+}
+
+/// 查询其他节点上监听指定配置的客户端，请求失败时返回空
+async fn peer_config_listeners(
+    client: &reqwest::Client,
+    addr: &str,
+    namespace_id: &str,
+    config_id: &str,
+) -> Vec<ConfigListenerStatus> {
+    let url = format!("http://{}/api/config/listeners/local", addr);
+    let request = client
+        .get(&url)
+        .query(&[("namespace_id", namespace_id), ("id", config_id)]);
+    let res = async {
+        with_cluster_signature(request)
+            .timeout(Duration::from_secs(1))
+            .send()
+            .await?
+            .json::<Res<Vec<ConfigListenerStatus>>>()
+            .await
+    }
+    .await;
+    match res {
+        Ok(res) => res.data.unwrap_or_default(),
+        Err(e) => {
+            log::warn!("Failed to query config listeners from {}: {}", addr, e);
+            vec![]
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::Mode;
-    #[tokio::test]
-    async fn test_config() {
-        let args = Args {
+    fn test_args(data_dir: &str) -> Args {
+        Args {
             address: "127.0..0.1".to_string(),
             port: 8000,
-            data_dir: "./data".to_string(),
+            data_dir: data_dir.to_string(),
             node_id: 1,
             mode: Mode::Standalone,
             enable_cache_config: false,
-        };
+            access_log: false,
+            access_log_sample_rate: 1.0,
+            access_log_exclude: vec![],
+            webhook_url: vec![],
+            service_degraded_minutes: 3,
+            disk_warn_free_mb: 1024,
+            disk_critical_free_mb: 256,
+            raft_log_retention_days: None,
+            read_only: false,
+            enable_xds: false,
+            enable_grpc: false,
+            enable_udp_heartbeat: false,
+            annotation_otlp_url: None,
+            annotation_grafana_url: None,
+            annotation_grafana_token: None,
+            cluster_secret: None,
+            chaos: false,
+            backup_s3_endpoint: None,
+            backup_s3_bucket: "conreg-backup".to_string(),
+            backup_s3_region: "us-east-1".to_string(),
+            backup_s3_access_key: None,
+            backup_s3_secret_key: None,
+            backup_s3_prefix: "conreg".to_string(),
+            backup_interval_minutes: 1440,
+            backup_retention: 7,
+            master_key: None,
+            decrypt_key: None,
+        }
+    }
+
+    /// 在临时目录中初始化数据库，同一测试进程中只初始化一次
+    async fn init_test_db() -> Args {
+        static DB: tokio::sync::OnceCell<Args> = tokio::sync::OnceCell::const_new();
+        DB.get_or_init(|| async {
+            let data_dir = std::env::temp_dir().join(format!("conreg-test-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&data_dir);
+            let args = test_args(data_dir.to_str().unwrap());
+            crate::init_dir(&args).unwrap();
+            crate::db::init(&args).await.unwrap();
+            args
+        })
+        .await
+        .clone()
+    }
+
+    #[tokio::test]
+    async fn test_config() {
+        let args = test_args("./data");
         let cm = ConfigManager::new(&args).await.unwrap();
         let config = cm.get_config("public", "test").await.unwrap();
         println!("config: {:?}", config);
@@ -591,6 +1559,323 @@ mod tests {
         println!("history: {:?}", history);
     }
 
+    #[test]
+    fn test_matches_filter() {
+        let entry = ConfigEntry {
+            id_: 1,
+            namespace_id: "public".to_string(),
+            id: "app.yaml".to_string(),
+            content: "db:\n  host: localhost\n".to_string(),
+            create_time: Local::now(),
+            update_time: Local::now(),
+            description: None,
+            md5: "".to_string(),
+            format: "yaml".to_string(),
+        };
+        // 子串匹配，不要求从词首开始
+        assert!(entry.matches_filter("ost"));
+        assert!(entry.matches_filter("HOST"));
+        assert!(entry.matches_filter("p.ya"));
+        assert!(!entry.matches_filter("port"));
+    }
+
+    #[test]
+    fn test_promoted_description() {
+        assert_eq!(
+            promoted_description("dev", "abc", None),
+            "promoted from dev abc"
+        );
+        assert_eq!(
+            promoted_description("dev", "abc", Some("")),
+            "promoted from dev abc"
+        );
+        assert_eq!(
+            promoted_description("dev", "abc", Some("db settings")),
+            "db settings (promoted from dev abc)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_config_history_with_page() {
+        let args = init_test_db().await;
+        let cm = ConfigManager::new(&args).await.unwrap();
+        for i in 0..150 {
+            sqlx::query(
+                "INSERT INTO config_history (namespace_id, id, content, create_time, update_time, format, md5) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind("history-page")
+            .bind("app.yaml")
+            .bind(format!("version: {}", i))
+            .bind(Local::now())
+            .bind(Local::now())
+            .bind("yaml")
+            .bind("")
+            .execute(DbPool::get())
+            .await
+            .unwrap();
+        }
+
+        // 第一页，按ID倒序
+        let (total, first) = cm
+            .list_config_history_with_page("history-page", "app.yaml", 1, 10, None)
+            .await
+            .unwrap();
+        assert_eq!(total, 150);
+        assert_eq!(first.len(), 10);
+        assert_eq!(first[0].content, "version: 149");
+        assert!(first.windows(2).all(|w| w[0].id_ > w[1].id_));
+
+        // 从上一页最后一条之后继续
+        let last_id = first.last().unwrap().id_;
+        let (total, next) = cm
+            .list_config_history_with_page("history-page", "app.yaml", 1, 10, Some(last_id))
+            .await
+            .unwrap();
+        assert_eq!(total, 150);
+        assert_eq!(next.len(), 10);
+        assert_eq!(next[0].content, "version: 139");
+        assert!(next.iter().all(|entry| entry.id_ < last_id));
+
+        // 与按页码查询的第二页相同
+        let (_, second) = cm
+            .list_config_history_with_page("history-page", "app.yaml", 2, 10, None)
+            .await
+            .unwrap();
+        assert_eq!(second, next);
+
+        // 超出上限时按最大值返回
+        let (_, all) = cm
+            .list_config_history_with_page("history-page", "app.yaml", 1, 1000, None)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), MAX_HISTORY_PAGE_SIZE as usize);
+        let (_, rest) = cm
+            .list_config_history_with_page(
+                "history-page",
+                "app.yaml",
+                1,
+                1000,
+                Some(all.last().unwrap().id_),
+            )
+            .await
+            .unwrap();
+        assert_eq!(rest.len(), 50);
+        assert_eq!(rest.last().unwrap().content, "version: 0");
+    }
+
+    #[tokio::test]
+    async fn test_history_content_dedup() {
+        let args = init_test_db().await;
+        let cm = ConfigManager::new(&args).await.unwrap();
+        let content = "dedup: 3460\n";
+        let content_md5 = ConfigEntry::gen_content_md5(content);
+        let count = async || -> i64 {
+            sqlx::query_scalar("SELECT COUNT(*) FROM config_content WHERE md5 = ?")
+                .bind(&content_md5)
+                .fetch_one(DbPool::get())
+                .await
+                .unwrap()
+        };
+        for (id_, id) in [(1, "a.yaml"), (2, "b.yaml")] {
+            let entry = ConfigEntry {
+                id_,
+                namespace_id: "history-dedup".to_string(),
+                id: id.to_string(),
+                content: content.to_string(),
+                create_time: Local::now(),
+                update_time: Local::now(),
+                description: None,
+                md5: "".to_string(),
+                format: "yaml".to_string(),
+            };
+            cm.append_history(&entry).await.unwrap();
+        }
+
+        // 相同内容只保存一份
+        assert_eq!(count().await, 1);
+        for id in ["a.yaml", "b.yaml"] {
+            let history = cm.get_history("history-dedup", id).await.unwrap();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].content, content);
+        }
+
+        // 仍被其他历史引用时保留内容
+        cm.delete_history("history-dedup", "a.yaml").await.unwrap();
+        assert_eq!(count().await, 1);
+        let history = cm.get_history("history-dedup", "b.yaml").await.unwrap();
+        assert_eq!(history[0].content, content);
+
+        // 不再被引用时清理内容
+        cm.delete_history("history-dedup", "b.yaml").await.unwrap();
+        assert_eq!(count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_history_content() {
+        let args = init_test_db().await;
+        let cm = ConfigManager::new(&args).await.unwrap();
+        // 去重之前的历史记录，内容保存在历史记录中，content_md5为空
+        sqlx::query(
+            "INSERT INTO config_history (namespace_id, id, content, create_time, update_time, format, md5) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind("history-legacy")
+        .bind("app.yaml")
+        .bind("legacy: true")
+        .bind(Local::now())
+        .bind(Local::now())
+        .bind("yaml")
+        .bind("")
+        .execute(DbPool::get())
+        .await
+        .unwrap();
+        let history = cm.get_history("history-legacy", "app.yaml").await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "legacy: true");
+        let entry = cm
+            .get_history_by_id_(history[0].id_)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.content, "legacy: true");
+
+        // 清理历史内容不影响旧记录
+        cm.delete_history("history-legacy", "other.yaml")
+            .await
+            .unwrap();
+        let history = cm.get_history("history-legacy", "app.yaml").await.unwrap();
+        assert_eq!(history[0].content, "legacy: true");
+    }
+
+    #[tokio::test]
+    async fn test_diff_history_masked() {
+        let args = init_test_db().await;
+        let cm = ConfigManager::new(&args).await.unwrap();
+        let now = Local::now();
+        let mut entry = ConfigEntry {
+            id_: 3503,
+            namespace_id: "diff-masked".to_string(),
+            id: "app.yaml".to_string(),
+            content: "db:\n  host: a\n  password: first-secret\n".to_string(),
+            create_time: now,
+            update_time: now,
+            description: None,
+            md5: "".to_string(),
+            format: "yaml".to_string(),
+        };
+        cm.insert_config(entry.clone()).await.unwrap();
+        entry.content = "db:\n  host: b\n  password: second-secret\n".to_string();
+        entry.update_time = now + chrono::Duration::seconds(1);
+        cm.update_config(entry).await.unwrap();
+        cm.set_sensitive_keys(
+            "diff-masked",
+            "app.yaml",
+            Some(vec!["db.password".to_string()]),
+        )
+        .await
+        .unwrap();
+        let history = cm.get_history("diff-masked", "app.yaml").await.unwrap();
+        let from_id_ = history.last().unwrap().id_;
+
+        // 有写权限的用户看到明文
+        let diff = cm
+            .diff_history("diff-masked", "app.yaml", from_id_, None, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(diff.unified.contains("first-secret"));
+        assert!(diff.unified.contains("second-secret"));
+
+        // 只读用户两个版本均打码，变化的配置项中不包含敏感配置项
+        let sensitive = cm.list_sensitive_keys("diff-masked").await.unwrap();
+        let diff = cm
+            .diff_history("diff-masked", "app.yaml", from_id_, None, &sensitive)
+            .await
+            .unwrap();
+        assert!(!diff.unified.contains("secret"));
+        assert!(diff.unified.contains(sensitive::MASK));
+        let changes = diff.changes.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, "db.host");
+    }
+
+    #[tokio::test]
+    async fn test_export_masked() {
+        let args = init_test_db().await;
+        let cm = ConfigManager::new(&args).await.unwrap();
+        let entry = ConfigEntry {
+            id_: 3515,
+            namespace_id: "export-masked".to_string(),
+            id: "app.yaml".to_string(),
+            content: "db:\n  host: a\n  password: secret\n".to_string(),
+            create_time: Local::now(),
+            update_time: Local::now(),
+            description: None,
+            md5: "".to_string(),
+            format: "yaml".to_string(),
+        };
+        cm.insert_config(entry).await.unwrap();
+        cm.set_sensitive_keys(
+            "export-masked",
+            "app.yaml",
+            Some(vec!["db.password".to_string()]),
+        )
+        .await
+        .unwrap();
+        let export = async |sensitive: &HashMap<String, Vec<String>>| {
+            let bytes = cm
+                .export(
+                    "export-masked",
+                    vec![],
+                    true,
+                    None,
+                    ExportLayout::Conreg,
+                    sensitive,
+                )
+                .await
+                .unwrap();
+            let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut zip.by_name("app.yaml").unwrap(), &mut content)
+                .unwrap();
+            content
+        };
+
+        // 有写权限的用户导出明文
+        assert!(export(&HashMap::new()).await.contains("secret"));
+
+        // 只读用户导出打码后的内容
+        let sensitive = cm.list_sensitive_keys("export-masked").await.unwrap();
+        let content = export(&sensitive).await;
+        assert!(!content.contains("secret"));
+        assert!(content.contains("host: a"));
+        assert!(content.contains(sensitive::MASK));
+    }
+
+    #[tokio::test]
+    async fn test_search_encrypted_namespace() {
+        let args = init_test_db().await;
+        let cm = ConfigManager::new(&args).await.unwrap();
+        assert!(
+            cm.search_configs_with_page("fts-encrypted", "host", 1, 10)
+                .await
+                .is_ok()
+        );
+        sqlx::query(
+            "INSERT INTO config_key (namespace_id, wrapped_key, create_time) VALUES (?, ?, ?)",
+        )
+        .bind("fts-encrypted")
+        .bind("")
+        .bind(Local::now())
+        .execute(DbPool::get())
+        .await
+        .unwrap();
+        let cm = ConfigManager::new(&args).await.unwrap();
+        let e = cm
+            .search_configs_with_page("fts-encrypted", "host", 1, 10)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("encrypted"));
+    }
+
     #[tokio::test]
     async fn test_id() {
         id::init();
@@ -0,0 +1,197 @@
+//! 敏感配置
+//!
+//! 配置可以整体或按配置项标记为敏感，标记按配置ID保存，对该配置ID下的所有标签变体生效。
+//! 后台只读用户（非管理员，且没有命名空间的写权限）查看配置时返回打码后的内容，
+//! 管理员和有写权限的用户看到明文。客户端通过命名空间Token获取配置不受影响。
+
+use crate::config::server::ConfigEntry;
+use crate::config::server::convert::converter;
+use crate::config::server::label::split_variant_id;
+use serde_yaml::Value;
+use std::collections::HashMap;
+
+/// 打码后的值
+pub const MASK: &str = "******";
+
+/// 对配置内容打码
+///
+/// `keys`为`.`分隔的配置项路径，例如`spring.datasource.password`，列表元素使用`key[0]`的形式，
+/// 路径指向对象或列表时，其下所有的值都会被打码。`keys`为空时整个配置打码。
+///
+/// 打码后的内容按配置格式重新生成，配置格式不支持解析或解析失败时整个配置打码
+pub fn mask_content(format: &str, content: &str, keys: &[String]) -> String {
+    if keys.is_empty() {
+        return MASK.to_string();
+    }
+    let Some(converter) = converter(format) else {
+        return MASK.to_string();
+    };
+    let Ok(mut value) = converter.parse(content) else {
+        return MASK.to_string();
+    };
+    mask_keys(&mut value, "", keys);
+    converter
+        .render(&value)
+        .unwrap_or_else(|_| MASK.to_string())
+}
+
+/// 配置为敏感配置时，对配置内容打码
+///
+/// `sensitive`为[`list_sensitive_keys`](super::ConfigManager::list_sensitive_keys)返回的敏感配置项，
+/// 有写权限的用户传入空
+pub fn mask_entry(entry: &mut ConfigEntry, sensitive: &HashMap<String, Vec<String>>) {
+    if let Some(keys) = sensitive.get(split_variant_id(&entry.id).0) {
+        entry.content = mask_content(&entry.format, &entry.content, keys);
+    }
+}
+
+/// 对全文搜索命中的内容片段打码
+///
+/// 片段只是配置内容的一部分，无法按配置格式解析，因此从打码后的完整内容中重新截取：
+/// 取第一个包含命中词的行，并用`<mark></mark>`重新标记命中词。命中词都在打码的配置项中时返回[`MASK`]
+pub fn mask_snippet(format: &str, content: &str, keys: &[String], snippet: &str) -> String {
+    let masked = mask_content(format, content, keys);
+    let terms = snippet
+        .split("<mark>")
+        .skip(1)
+        .filter_map(|part| part.split_once("</mark>"))
+        .map(|(term, _)| term.to_ascii_lowercase())
+        .filter(|term| !term.is_empty())
+        .collect::<Vec<_>>();
+    masked
+        .lines()
+        .find_map(|line| highlight_line(line, &terms))
+        .unwrap_or_else(|| MASK.to_string())
+}
+
+/// 标记行中的命中词，不包含命中词时返回None
+fn highlight_line(line: &str, terms: &[String]) -> Option<String> {
+    // 只转换ASCII字符的大小写，转换后的字节位置与原行一致
+    let lower = line.to_ascii_lowercase();
+    let mut highlighted = String::new();
+    let mut pos = 0;
+    while let Some((start, term)) = terms
+        .iter()
+        .filter_map(|term| lower[pos..].find(term.as_str()).map(|i| (pos + i, term)))
+        .min_by_key(|(start, _)| *start)
+    {
+        let end = start + term.len();
+        highlighted.push_str(&line[pos..start]);
+        highlighted.push_str("<mark>");
+        highlighted.push_str(&line[start..end]);
+        highlighted.push_str("</mark>");
+        pos = end;
+    }
+    if pos == 0 {
+        return None;
+    }
+    highlighted.push_str(&line[pos..]);
+    Some(highlighted)
+}
+
+/// 对路径在`keys`中的配置项打码
+fn mask_keys(value: &mut Value, path: &str, keys: &[String]) {
+    if !path.is_empty() && keys.iter().any(|key| key == path) {
+        mask_all(value);
+        return;
+    }
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                let key = match key {
+                    Value::String(key) => key.clone(),
+                    Value::Number(key) => key.to_string(),
+                    Value::Bool(key) => key.to_string(),
+                    _ => continue,
+                };
+                let path = if path.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                mask_keys(value, &path, keys);
+            }
+        }
+        Value::Sequence(sequence) => {
+            for (index, value) in sequence.iter_mut().enumerate() {
+                mask_keys(value, &format!("{}[{}]", path, index), keys);
+            }
+        }
+        Value::Tagged(tagged) => mask_keys(&mut tagged.value, path, keys),
+        _ => {}
+    }
+}
+
+/// 对所有的值打码，保留对象和列表的结构
+fn mask_all(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => mapping.values_mut().for_each(mask_all),
+        Value::Sequence(sequence) => sequence.iter_mut().for_each(mask_all),
+        Value::Tagged(tagged) => mask_all(&mut tagged.value),
+        Value::Null => {}
+        value => *value = Value::String(MASK.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_content() {
+        let keys = vec!["db.password".to_string(), "tokens".to_string()];
+        let content = "db:\n  url: mysql://localhost\n  password: secret\ntokens:\n- a\n- b\n";
+        let masked = mask_content("yaml", content, &keys);
+        assert!(masked.contains("url: mysql://localhost"));
+        assert!(!masked.contains("secret"));
+        assert!(!masked.contains("- a"));
+        assert_eq!(masked.matches(MASK).count(), 3);
+
+        let masked = mask_content(
+            "properties",
+            "db.url=mysql://localhost\ndb.password=secret",
+            &keys,
+        );
+        assert!(masked.contains("db.url=mysql://localhost"));
+        assert!(masked.contains(&format!("db.password={}", MASK)));
+
+        let keys = vec!["servers[1]".to_string()];
+        let masked = mask_content("json", r#"{"servers": ["a", "b"]}"#, &keys);
+        assert!(masked.contains("\"a\""));
+        assert!(!masked.contains("\"b\""));
+
+        assert_eq!(mask_content("yaml", content, &[]), MASK);
+        assert_eq!(mask_content("text", "password", &keys), MASK);
+        assert_eq!(mask_content("json", "{", &keys), MASK);
+    }
+
+    #[test]
+    fn test_mask_snippet() {
+        let keys = vec!["db.password".to_string()];
+        let content = "db:\n  url: mysql://localhost\n  password: secret\n";
+        assert_eq!(
+            mask_snippet(
+                "yaml",
+                content,
+                &keys,
+                "url: <mark>mysql</mark>://localhost..."
+            ),
+            "  url: <mark>mysql</mark>://localhost"
+        );
+        // 命中词在打码的配置项中
+        assert_eq!(
+            mask_snippet("yaml", content, &keys, "password: <mark>secret</mark>"),
+            MASK
+        );
+        assert_eq!(
+            mask_snippet(
+                "yaml",
+                content,
+                &keys,
+                "<mark>Password</mark>: <mark>secret</mark>"
+            ),
+            format!("  <mark>password</mark>: '{}'", MASK)
+        );
+        assert_eq!(mask_snippet("yaml", content, &[], "<mark>db</mark>:"), MASK);
+    }
+}
@@ -0,0 +1,212 @@
+//! Spring Cloud Config Server兼容接口
+//!
+//! 按Spring Cloud Config Server的接口格式返回配置，已有的Spring Boot服务只需将
+//! `spring.cloud.config.uri`指向`http://<conreg>/config`即可从conreg获取配置，无需修改代码。
+//!
+//! 映射规则：
+//! - `label`对应命名空间，未指定时为`public`
+//! - `{application}-{profile}`、`{application}`、`application-{profile}`、`application`
+//!   对应同名的配置ID，扩展名可以是yml、yaml、properties或json
+//! - 命名空间开启认证时，通过请求头`X-NS-Token`传递Token，
+//!   即`spring.cloud.config.headers.X-NS-Token`
+
+use crate::app::get_app;
+use crate::config::server::convert;
+use rocket::Request;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::log;
+
+/// 默认命名空间
+const DEFAULT_NAMESPACE: &str = "public";
+/// 通用配置的应用名
+const DEFAULT_APPLICATION: &str = "application";
+/// 支持的配置文件扩展名，同名配置存在多个时优先使用靠前的
+const EXTENSIONS: [&str; 4] = ["yml", "yaml", "properties", "json"];
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![environment, environment_with_label]
+}
+
+/// Spring Cloud Config的Environment
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Environment {
+    name: String,
+    profiles: Vec<String>,
+    label: Option<String>,
+    version: Option<String>,
+    state: Option<String>,
+    /// 配置来源，优先级高的在前
+    property_sources: Vec<PropertySource>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PropertySource {
+    name: String,
+    source: Map<String, Value>,
+}
+
+/// 命名空间Token，从请求头`X-NS-Token`获取
+pub struct SpringToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SpringToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(SpringToken(
+            req.headers().get_one("X-NS-Token").map(String::from),
+        ))
+    }
+}
+
+/// 获取配置
+#[get("/<application>/<profile>")]
+async fn environment(
+    application: &str,
+    profile: &str,
+    token: SpringToken,
+) -> Result<Json<Environment>, Status> {
+    build(application, profile, None, token).await
+}
+
+/// 获取指定label（命名空间）的配置
+#[get("/<application>/<profile>/<label>")]
+async fn environment_with_label(
+    application: &str,
+    profile: &str,
+    label: &str,
+    token: SpringToken,
+) -> Result<Json<Environment>, Status> {
+    build(application, profile, Some(label), token).await
+}
+
+async fn build(
+    application: &str,
+    profile: &str,
+    label: Option<&str>,
+    token: SpringToken,
+) -> Result<Json<Environment>, Status> {
+    let namespace_id = label.unwrap_or(DEFAULT_NAMESPACE);
+    match get_app()
+        .namespace_app
+        .manager
+        .auth(namespace_id, token.0.as_deref())
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(Status::Unauthorized),
+        Err(e) => {
+            log::error!("auth namespace {} error: {}", namespace_id, e);
+            return Err(Status::InternalServerError);
+        }
+    }
+
+    let profiles = profile
+        .split(',')
+        .map(str::trim)
+        .filter(|profile| !profile.is_empty())
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let mut property_sources = vec![];
+    for name in source_names(application, &profiles) {
+        match property_source(namespace_id, &name).await {
+            Ok(Some(source)) => property_sources.push(source),
+            Ok(None) => {}
+            Err(e) => {
+                log::error!("load spring property source {} error: {}", name, e);
+                return Err(Status::InternalServerError);
+            }
+        }
+    }
+
+    Ok(Json(Environment {
+        name: application.to_string(),
+        profiles,
+        label: label.map(String::from),
+        version: None,
+        state: None,
+        property_sources,
+    }))
+}
+
+/// 按优先级从高到低排列的配置名：
+/// 先是各profile的配置（后指定的profile优先），再是默认配置，应用配置优先于通用配置
+fn source_names(application: &str, profiles: &[String]) -> Vec<String> {
+    let mut applications = vec![application];
+    if application != DEFAULT_APPLICATION {
+        applications.push(DEFAULT_APPLICATION);
+    }
+    let mut names = vec![];
+    for profile in profiles.iter().rev() {
+        for application in applications.iter() {
+            names.push(format!("{}-{}", application, profile));
+        }
+    }
+    names.extend(
+        applications
+            .iter()
+            .map(|application| application.to_string()),
+    );
+    names
+}
+
+/// 加载配置名对应的配置并展开为键值对，配置不存在时返回None
+async fn property_source(namespace_id: &str, name: &str) -> anyhow::Result<Option<PropertySource>> {
+    let manager = &get_app().config_app.manager;
+    for extension in EXTENSIONS {
+        let id = format!("{}.{}", name, extension);
+        let Some(config) = manager.get_config(namespace_id, &id).await? else {
+            continue;
+        };
+        let Some(converter) = convert::converter(&config.format)
+            .or_else(|| convert::converter_by_file_name(&config.id))
+        else {
+            log::warn!(
+                "config {} with format {} is not supported",
+                id,
+                config.format
+            );
+            continue;
+        };
+        let mut source = Map::new();
+        for (key, value) in convert::flatten(&converter.parse(&config.content)?)? {
+            source.insert(key, serde_json::to_value(value)?);
+        }
+        return Ok(Some(PropertySource {
+            name: format!("conreg:{}/{}", namespace_id, id),
+            source,
+        }));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_names() {
+        let profiles = vec!["dev".to_string(), "db".to_string()];
+        assert_eq!(
+            source_names("user", &profiles),
+            vec![
+                "user-db",
+                "application-db",
+                "user-dev",
+                "application-dev",
+                "user",
+                "application"
+            ]
+        );
+        assert_eq!(
+            source_names("application", &["dev".to_string()]),
+            vec!["application-dev", "application"]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! 配置获取统计
+//!
+//! 记录每个配置被客户端获取的次数和最后获取时间，用于识别长期无人使用、可以安全删除的配置。
+//!
+//! 获取次数先在各节点内存中累加，定期汇总后通过Raft同步到集群写入数据库，
+//! 因此统计结果是近似值，节点异常退出时会丢失未汇总的部分。
+
+use crate::raft::RaftRequest;
+use crate::raft::api::raft_write;
+use crate::schedule::{self, TaskScope};
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::log;
+
+/// 汇总间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 配置获取统计增量
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigFetchStat {
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 配置ID
+    pub id: String,
+    /// 获取次数
+    pub count: u64,
+    /// 最后获取时间
+    pub last_fetch_time: DateTime<Local>,
+}
+
+/// 本节点未汇总的配置获取统计
+#[derive(Debug, Default)]
+pub struct ConfigFetchStats {
+    /// key为(命名空间ID, 配置ID)，value为(获取次数, 最后获取时间)
+    pending: DashMap<(String, String), (u64, DateTime<Local>)>,
+}
+
+impl ConfigFetchStats {
+    /// 记录一次配置获取
+    pub fn record(&self, namespace_id: &str, config_id: &str) {
+        let now = Local::now();
+        self.pending
+            .entry((namespace_id.to_string(), config_id.to_string()))
+            .and_modify(|(count, time)| {
+                *count += 1;
+                *time = now;
+            })
+            .or_insert((1, now));
+    }
+
+    /// 取出所有未汇总的统计
+    fn take(&self) -> Vec<ConfigFetchStat> {
+        let keys = self
+            .pending
+            .iter()
+            .map(|e| e.key().clone())
+            .collect::<Vec<_>>();
+        keys.into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .map(
+                |((namespace_id, id), (count, last_fetch_time))| ConfigFetchStat {
+                    namespace_id,
+                    id,
+                    count,
+                    last_fetch_time,
+                },
+            )
+            .collect()
+    }
+
+    /// 汇总失败时放回，下次重新提交
+    fn restore(&self, stats: Vec<ConfigFetchStat>) {
+        for stat in stats {
+            self.pending
+                .entry((stat.namespace_id, stat.id))
+                .and_modify(|(count, time)| {
+                    *count += stat.count;
+                    *time = (*time).max(stat.last_fetch_time);
+                })
+                .or_insert((stat.count, stat.last_fetch_time));
+        }
+    }
+
+    /// 将本节点的统计提交到集群
+    pub async fn flush(&self) {
+        let stats = self.take();
+        if stats.is_empty() {
+            return;
+        }
+        let res = raft_write(RaftRequest::ConfigFetchStats {
+            stats: stats.clone(),
+        })
+        .await;
+        if !res.is_success() {
+            log::warn!("flush config fetch stats error: {}", res.msg);
+            self.restore(stats);
+        }
+    }
+
+    /// 启动定时汇总任务
+    pub fn start_flush_timer(&'static self) {
+        schedule::schedule(
+            "config-fetch-stats-flush",
+            "汇总本节点的配置获取统计",
+            TaskScope::Node,
+            FLUSH_INTERVAL,
+            move || async move {
+                self.flush().await;
+                Ok(())
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_and_restore() {
+        let stats = ConfigFetchStats::default();
+        stats.record("public", "a.yaml");
+        stats.record("public", "a.yaml");
+        stats.record("public", "b.yaml");
+
+        let taken = stats.take();
+        assert_eq!(taken.len(), 2);
+        assert!(stats.pending.is_empty());
+
+        stats.record("public", "a.yaml");
+        stats.restore(taken);
+        assert_eq!(
+            stats
+                .pending
+                .get(&("public".into(), "a.yaml".into()))
+                .unwrap()
+                .0,
+            3
+        );
+        assert_eq!(
+            stats
+                .pending
+                .get(&("public".into(), "b.yaml".into()))
+                .unwrap()
+                .0,
+            1
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! 配置监听会话
+//!
+//! 记录当前正在进行的长轮询监听，用于排查连接泄漏和异常客户端，并支持强制关闭。
+
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Notify;
+
+/// 监听会话信息
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WatcherInfo {
+    /// 会话ID
+    pub id: u64,
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 客户端地址
+    pub remote_addr: Option<String>,
+    /// 连接时间
+    pub connected_at: DateTime<Local>,
+}
+
+#[derive(Debug)]
+struct Watcher {
+    info: WatcherInfo,
+    /// 关闭通知
+    close: Arc<Notify>,
+}
+
+/// 监听会话注册表
+#[derive(Debug, Default)]
+pub struct Watchers {
+    next_id: AtomicU64,
+    watchers: Arc<DashMap<u64, Watcher>>,
+}
+
+/// 监听会话，drop时自动从注册表中移除
+pub struct WatcherGuard {
+    id: u64,
+    close: Arc<Notify>,
+    watchers: Arc<DashMap<u64, Watcher>>,
+}
+
+impl WatcherGuard {
+    /// 等待会话被强制关闭
+    pub async fn closed(&self) {
+        self.close.notified().await
+    }
+}
+
+impl Drop for WatcherGuard {
+    fn drop(&mut self) {
+        self.watchers.remove(&self.id);
+    }
+}
+
+impl Watchers {
+    /// 注册监听会话
+    pub fn register(&self, namespace_id: &str, remote_addr: Option<String>) -> WatcherGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let close = Arc::new(Notify::new());
+        self.watchers.insert(
+            id,
+            Watcher {
+                info: WatcherInfo {
+                    id,
+                    namespace_id: namespace_id.to_string(),
+                    remote_addr,
+                    connected_at: Local::now(),
+                },
+                close: close.clone(),
+            },
+        );
+        WatcherGuard {
+            id,
+            close,
+            watchers: self.watchers.clone(),
+        }
+    }
+
+    /// 当前所有监听会话，按连接时间排序
+    pub fn list(&self, namespace_id: Option<&str>) -> Vec<WatcherInfo> {
+        let mut list = self
+            .watchers
+            .iter()
+            .filter(|w| namespace_id.is_none_or(|ns| w.info.namespace_id == ns))
+            .map(|w| w.info.clone())
+            .collect::<Vec<_>>();
+        list.sort_by_key(|w| w.id);
+        list
+    }
+
+    /// 命名空间下的监听会话数量
+    pub fn count(&self, namespace_id: &str) -> usize {
+        self.watchers
+            .iter()
+            .filter(|w| w.info.namespace_id == namespace_id)
+            .count()
+    }
+
+    /// 强制关闭监听会话
+    ///
+    /// - id: 会话ID，为空时关闭命名空间下的所有会话
+    /// - namespace_id: 命名空间ID，为空时不限制命名空间
+    ///
+    /// 返回关闭的会话数量
+    pub fn close(&self, id: Option<u64>, namespace_id: Option<&str>) -> usize {
+        let mut count = 0;
+        for w in self.watchers.iter() {
+            if id.is_some_and(|id| w.info.id != id)
+                || namespace_id.is_some_and(|ns| w.info.namespace_id != ns)
+            {
+                continue;
+            }
+            // notify_one会保留通知，即使会话还未开始等待也不会丢失
+            w.close.notify_one();
+            count += 1;
+        }
+        count
+    }
+}
@@ -21,8 +21,75 @@ create table if not exists config_history
     update_time  timestamp    not null,
     description  varchar(500),
     format       varchar(50)  not null,
-    md5          varchar(32)  not null
+    md5          varchar(32)  not null,
+    content_md5  varchar(32)
+);
+-- 配置历史内容，按内容的md5去重存储，由config_history.content_md5引用
+create table if not exists config_content
+(
+    md5     varchar(32) primary key,
+    content text        not null
+);
+-- 敏感配置，keys为敏感配置项路径的JSON数组，为空表示整个配置，对配置ID下的所有标签变体生效
+create table if not exists config_sensitive
+(
+    namespace_id varchar(100) not null,
+    id           varchar(500) not null,
+    keys         text         not null,
+    update_time  timestamp    not null,
+    primary key (namespace_id, id)
+);
+create index if not exists idx_config_history_ns_id on config_history (namespace_id, id, id_);
+-- 命名空间的配置加密数据密钥，由主密钥加密保存，id最大的为当前密钥
+create table if not exists config_key
+(
+    id           integer primary key,
+    namespace_id varchar(100) not null,
+    wrapped_key  text         not null,
+    create_time  timestamp    not null
 );
+create index if not exists idx_config_key_ns on config_key (namespace_id, id);
+
+-- 配置内容全文索引，通过触发器与config表保持同步
+create virtual table if not exists config_fts using fts5
+(
+    namespace_id unindexed,
+    id,
+    content,
+    content = 'config',
+    content_rowid = 'id_'
+);
+create trigger if not exists config_fts_ai after insert on config
+begin
+    insert into config_fts (rowid, namespace_id, id, content)
+    values (new.id_, new.namespace_id, new.id, new.content);
+end;
+create trigger if not exists config_fts_ad after delete on config
+begin
+    insert into config_fts (config_fts, rowid, namespace_id, id, content)
+    values ('delete', old.id_, old.namespace_id, old.id, old.content);
+end;
+create trigger if not exists config_fts_au after update on config
+begin
+    insert into config_fts (config_fts, rowid, namespace_id, id, content)
+    values ('delete', old.id_, old.namespace_id, old.id, old.content);
+    insert into config_fts (rowid, namespace_id, id, content)
+    values (new.id_, new.namespace_id, new.id, new.content);
+end;
+
+-- 配置获取统计，各节点定期汇总后通过Raft同步
+create table if not exists config_fetch_stat
+(
+    namespace_id    varchar(100) not null,
+    id              varchar(500) not null,
+    fetch_count     integer      not null default 0,
+    last_fetch_time timestamp,
+    primary key (namespace_id, id)
+);
+create trigger if not exists config_fetch_stat_ad after delete on config
+begin
+    delete from config_fetch_stat where namespace_id = old.namespace_id and id = old.id;
+end;
 
 create table if not exists namespace
 (
@@ -31,6 +98,7 @@ create table if not exists namespace
     description varchar(500),
     is_auth     boolean      not null default false,
     auth_token  varchar(100),
+    suspended   boolean      not null default false,
     create_time timestamp    not null,
     update_time timestamp    not null
 );
@@ -40,6 +108,9 @@ create table if not exists service
     service_id   varchar(100) not null,
     namespace_id varchar(100) not null,
     meta         varchar(5000),
+    expected_instances integer,
+    warmup_seconds integer,
+    active_set   varchar(100),
     create_time  timestamp    not null,
     update_time  timestamp    not null,
     primary key (namespace_id, service_id)
@@ -56,5 +127,23 @@ create table if not exists user
 insert or ignore into namespace (id, name, description, create_time, update_time)
 values ('public', 'public', 'Reserved namespace', current_timestamp, current_timestamp);
 
+insert or ignore into namespace (id, name, description, create_time, update_time)
+values ('system', 'system', 'Reserved namespace for conreg server nodes', current_timestamp, current_timestamp);
+
 insert or ignore into user (username, password, permissions, create_time)
-values ('conreg', '$2b$12$d/WgXewqZpbUBOGgyGjzw.1XSO2OMHiDVJ9jaZ94vfuXsprG6Rcuu', '[]', current_timestamp);
\ No newline at end of file
+values ('conreg', '$2b$12$d/WgXewqZpbUBOGgyGjzw.1XSO2OMHiDVJ9jaZ94vfuXsprG6Rcuu', '[]', current_timestamp);
+-- 客户端启动配置，客户端通过init_from_url获取，代替打包在镜像中的bootstrap.yaml
+create table if not exists bootstrap_profile
+(
+    namespace_id     varchar(100) not null,
+    service_id       varchar(100) not null,
+    server_addr      varchar(1000),
+    enable_config    boolean      not null default true,
+    config_ids       text,
+    enable_discovery boolean      not null default true,
+    meta             varchar(5000),
+    description      varchar(500),
+    create_time      timestamp    not null,
+    update_time      timestamp    not null,
+    primary key (namespace_id, service_id)
+);
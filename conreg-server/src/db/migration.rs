@@ -0,0 +1,112 @@
+//! 数据库迁移
+//!
+//! 数据库版本记录在sqlite的`user_version`中，启动时依次执行版本号大于当前数据库版本的迁移，
+//! 执行完成后更新数据库版本。
+//!
+//! `init.sql`始终包含最新的表结构，新建的数据库同样会执行所有迁移，因此迁移需要是幂等的。
+//! 新增迁移时，在[`MIGRATIONS`]末尾追加，版本号为上一个迁移的版本号+1。
+
+use super::DbPool;
+use anyhow::bail;
+use sqlx::{Pool, Sqlite};
+use std::pin::Pin;
+use tracing::log;
+
+type MigrateFn =
+    for<'a> fn(&'a Pool<Sqlite>) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+/// 数据库迁移
+struct Migration {
+    /// 迁移后的版本号
+    version: i64,
+    /// 迁移说明
+    description: &'static str,
+    /// 迁移函数
+    migrate: MigrateFn,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "dedup config history content",
+        migrate: |pool| Box::pin(DbPool::dedup_history_content(pool)),
+    },
+    Migration {
+        version: 2,
+        description: "add namespace suspended column",
+        migrate: |pool| {
+            Box::pin(DbPool::add_column_if_not_exists(
+                pool,
+                "namespace",
+                "suspended",
+                "boolean not null default false",
+            ))
+        },
+    },
+    Migration {
+        version: 3,
+        description: "add service expected_instances column",
+        migrate: |pool| {
+            Box::pin(DbPool::add_column_if_not_exists(
+                pool,
+                "service",
+                "expected_instances",
+                "integer",
+            ))
+        },
+    },
+    Migration {
+        version: 4,
+        description: "add service warmup_seconds column",
+        migrate: |pool| {
+            Box::pin(DbPool::add_column_if_not_exists(
+                pool,
+                "service",
+                "warmup_seconds",
+                "integer",
+            ))
+        },
+    },
+    Migration {
+        version: 5,
+        description: "add service active_set column",
+        migrate: |pool| {
+            Box::pin(DbPool::add_column_if_not_exists(
+                pool,
+                "service",
+                "active_set",
+                "varchar(100)",
+            ))
+        },
+    },
+];
+
+/// 当前数据库版本
+const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// 执行数据库迁移
+pub(super) async fn migrate(pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(pool)
+        .await?;
+    if version > SCHEMA_VERSION {
+        bail!(
+            "database schema version {} is newer than supported version {}, please upgrade conreg server",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        log::info!(
+            "migrate database to version {}: {}",
+            migration.version,
+            migration.description
+        );
+        (migration.migrate)(pool).await?;
+        // PRAGMA不支持参数绑定
+        sqlx::query(&format!("PRAGMA user_version = {}", migration.version))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
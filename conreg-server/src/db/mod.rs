@@ -1,4 +1,7 @@
+mod migration;
+
 use crate::Args;
+use crate::config::server::ConfigEntry;
 use sqlx::Pool;
 use sqlx::sqlite::SqlitePoolOptions;
 use std::sync::OnceLock;
@@ -18,11 +21,93 @@ impl DbPool {
         // 初始化数据库
         let sql = include_str!("init.sql");
         sqlx::query(sql).execute(&pool).await?;
+        migration::migrate(&pool).await?;
+        Self::rebuild_fts_if_needed(&pool).await?;
         log::info!("database loaded");
         Ok(DbPool { pool })
     }
 }
 
+impl DbPool {
+    /// 全文索引与配置表的数据量不一致时（如从旧版本升级，配置表已有数据），重建全文索引
+    async fn rebuild_fts_if_needed(pool: &Pool<sqlx::Sqlite>) -> anyhow::Result<()> {
+        let indexed: i64 = sqlx::query_scalar("SELECT COUNT(1) FROM config_fts_docsize")
+            .fetch_one(pool)
+            .await?;
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(1) FROM config")
+            .fetch_one(pool)
+            .await?;
+        if indexed != total {
+            log::info!("rebuild config full-text index, {} configs", total);
+            sqlx::query("INSERT INTO config_fts (config_fts) VALUES ('rebuild')")
+                .execute(pool)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl DbPool {
+    /// 将历史记录中直接存储的内容迁移到`config_content`表中去重存储
+    ///
+    /// 旧版本的`config_history`没有`content_md5`列，内容直接保存在`content`列中
+    async fn dedup_history_content(pool: &Pool<sqlx::Sqlite>) -> anyhow::Result<()> {
+        Self::add_column_if_not_exists(pool, "config_history", "content_md5", "varchar(32)")
+            .await?;
+
+        let rows: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id_, content FROM config_history WHERE content_md5 IS NULL")
+                .fetch_all(pool)
+                .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        log::info!("migrate {} config history contents", rows.len());
+        let mut tx = pool.begin().await?;
+        for (id_, content) in rows {
+            let content_md5 = ConfigEntry::gen_content_md5(&content);
+            sqlx::query("INSERT OR IGNORE INTO config_content (md5, content) VALUES (?, ?)")
+                .bind(&content_md5)
+                .bind(&content)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("UPDATE config_history SET content = '', content_md5 = ? WHERE id_ = ?")
+                .bind(&content_md5)
+                .bind(id_)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+impl DbPool {
+    /// 为旧版本创建的表补充新增的列
+    async fn add_column_if_not_exists(
+        pool: &Pool<sqlx::Sqlite>,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> anyhow::Result<()> {
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+            .bind(table)
+            .fetch_all(pool)
+            .await?;
+        if !columns.iter().any(|c| c == column) {
+            log::info!("add column {}.{}", table, column);
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))
+            .execute(pool)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
 static DB_POOL: OnceLock<DbPool> = OnceLock::new();
 
 pub async fn init(args: &Args) -> anyhow::Result<()> {
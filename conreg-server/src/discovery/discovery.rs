@@ -1,12 +1,19 @@
+use crate::protocol::res::{CodeError, INSTANCE_CONFLICT_CODE};
 use anyhow::bail;
 use chrono::{DateTime, Local};
 use dashmap::DashMap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Deref;
 use std::sync::Arc;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 丢失心跳的最大周期数，超过后实例状态更新为Down
+const MAX_LOST_HEARTBEATS: usize = 3;
+/// 预热开始时的权重比例
+const WARMUP_MIN_FACTOR: f64 = 0.1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ServiceInstance {
     /// 服务实例ID
     pub id: String,
@@ -26,9 +33,15 @@ pub struct ServiceInstance {
     /// 丢失心跳的周期数
     #[serde(skip)]
     lost_heartbeats: usize,
+    /// 最近一次变为Up状态的时间
+    #[serde(skip)]
+    up_since: Option<DateTime<Local>>,
+    /// 有效权重，仅在获取可用实例时计算，预热期内从配置权重的一小部分线性增长到配置权重
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_weight: Option<u64>,
 }
 
-#[derive(Debug, Clone, PartialOrd, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum InstanceStatus {
     /// 服务就绪
     ///
@@ -60,7 +73,25 @@ pub enum InstanceStatus {
     Offline,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 已移除实例的保留时间，期间收到该实例的心跳时返回驱逐通知
+const EVICTED_RETENTION: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// 注册的实例已存在时的处理策略
+///
+/// 实例ID由IP和端口生成，同一地址重复注册即为冲突
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 替换已有实例，状态重置为Ready，客户端重启后重新注册时使用
+    #[default]
+    Replace,
+    /// 保留已有实例的状态，将新的元数据合并到已有元数据中，相同key以新值为准
+    MergeMeta,
+    /// 拒绝注册，用于防止不同应用误用同一地址
+    Reject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum HeartbeatResult {
     /// Ok
     Ok,
@@ -70,6 +101,69 @@ pub enum HeartbeatResult {
     Rejected,
 }
 
+/// 心跳响应
+///
+/// 客户端请求驱逐通知时返回心跳结果及通知，否则仅返回心跳结果，兼容旧版本客户端
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum HeartbeatResponse {
+    Result(HeartbeatResult),
+    WithNotice {
+        result: HeartbeatResult,
+        eviction: Option<EvictionNotice>,
+    },
+}
+
+/// 批量心跳中单个实例的结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HeartbeatBatchResult {
+    pub service_id: String,
+    pub instance_id: String,
+    pub result: HeartbeatResult,
+    /// 驱逐通知
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eviction: Option<EvictionNotice>,
+}
+
+/// 驱逐通知
+///
+/// 实例因心跳超时被判定为不健康或已被移除后，再次发送心跳时返回给客户端，
+/// 通常意味着客户端出现了长时间的GC停顿或网络问题
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EvictionNotice {
+    /// 被判定为不健康的原因
+    pub reason: String,
+    /// 是否已从实例列表中移除，已移除的实例需要重新注册
+    pub removed: bool,
+    /// 最后一次心跳时间
+    pub last_heartbeat: DateTime<Local>,
+    /// 移除时间，未移除时为空
+    pub evicted_at: Option<DateTime<Local>>,
+}
+
+/// 手动修改实例心跳状态的操作
+///
+/// 用于网络短暂抖动后，不等待下一次心跳即恢复实例
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HeartbeatOverride {
+    /// 清零丢失心跳的周期数，并以当前时间作为最后一次心跳时间，不改变实例状态
+    ResetLostHeartbeats,
+    /// 将Sick或Down状态的实例恢复为Up，同时清零丢失心跳的周期数
+    MarkUp,
+}
+
+/// 手动修改实例心跳状态的结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HeartbeatOverrideResult {
+    /// 修改前的状态
+    pub previous_status: InstanceStatus,
+    /// 修改前丢失心跳的周期数
+    pub previous_lost_heartbeats: usize,
+    /// 修改后的状态
+    pub status: InstanceStatus,
+}
+
 impl ServiceInstance {
     pub fn new(service_id: &str, ip: &str, port: u16, meta: HashMap<String, String>) -> Self {
         ServiceInstance {
@@ -81,6 +175,8 @@ impl ServiceInstance {
             meta,
             last_heartbeat: Local::now(),
             lost_heartbeats: 0,
+            up_since: None,
+            effective_weight: None,
         }
     }
 
@@ -89,6 +185,15 @@ impl ServiceInstance {
         format!("{:x}", digest)
     }
 
+    /// 继承服务的元数据，实例已设置的key不覆盖
+    pub fn inherit_meta(&mut self, meta: &HashMap<String, String>) {
+        for (key, value) in meta {
+            self.meta
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+
     pub fn update_heartbeat(&mut self) {
         self.last_heartbeat = Local::now();
     }
@@ -101,6 +206,119 @@ impl ServiceInstance {
     pub fn is_available(&self) -> bool {
         self.status == InstanceStatus::Up
     }
+
+    pub fn status(&self) -> &InstanceStatus {
+        &self.status
+    }
+
+    /// 实例所属集合（蓝绿部署），由元数据中的color指定
+    pub fn color(&self) -> Option<&str> {
+        self.meta.get("color").map(|color| color.as_str())
+    }
+
+    /// 配置的权重，由元数据中的weight指定，默认为1
+    pub fn weight(&self) -> u64 {
+        self.meta
+            .get("weight")
+            .and_then(|weight| weight.parse().ok())
+            .unwrap_or(1)
+    }
+
+    /// 按预热时长计算当前权重
+    ///
+    /// 实例变为Up后的预热期内，权重从配置权重的10%线性增长到配置权重
+    pub fn warmup_weight(&self, warmup: std::time::Duration) -> u64 {
+        let weight = self.weight();
+        let Some(up_since) = self.up_since else {
+            return weight;
+        };
+        let elapsed = Local::now()
+            .signed_duration_since(up_since)
+            .to_std()
+            .unwrap_or_default();
+        if warmup.is_zero() || elapsed >= warmup {
+            return weight;
+        }
+        let factor = (elapsed.as_secs_f64() / warmup.as_secs_f64()).max(WARMUP_MIN_FACTOR);
+        ((weight as f64 * factor).round() as u64).max(1)
+    }
+
+    /// 计算下一次心跳检查后的状态和丢失心跳周期数，状态不变时返回None
+    fn check_heartbeat(&self, timeout: std::time::Duration) -> Option<(InstanceStatus, usize)> {
+        // 手动下线的无须处理
+        if self.status == InstanceStatus::Offline {
+            return None;
+        }
+        // 超过3个心跳周期超时的，状态更新为Down
+        if self.lost_heartbeats >= MAX_LOST_HEARTBEATS {
+            Some((InstanceStatus::Down, self.lost_heartbeats))
+        } else if self.is_heartbeat_timeout(timeout) {
+            let lost_heartbeats = self.lost_heartbeats + 1;
+            Some((
+                InstanceStatus::Sick(format!("lost heartbeats({})", lost_heartbeats)),
+                lost_heartbeats,
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// 模拟驱逐时实例将发生的变化
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum EvictionAction {
+    /// 将被标记为Sick
+    Sick,
+    /// 将被标记为Down
+    Down,
+    /// 将在下次清理时被移除
+    Evict,
+}
+
+/// 模拟驱逐结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EvictionCandidate {
+    /// 服务ID
+    pub service_id: String,
+    /// 服务实例ID
+    pub instance_id: String,
+    /// IP
+    pub ip: String,
+    /// 端口
+    pub port: u16,
+    /// 当前状态
+    pub status: InstanceStatus,
+    /// 将发生的变化
+    pub action: EvictionAction,
+    /// 丢失心跳的周期数
+    pub lost_heartbeats: usize,
+    /// 最后一次心跳时间
+    pub last_heartbeat: DateTime<Local>,
+}
+
+/// 服务实例的完整状态，包括心跳相关的内部状态，用于导出和导入
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InstanceState {
+    #[serde(flatten)]
+    pub instance: ServiceInstance,
+    /// 最后一次心跳时间，导入时未指定则为当前时间
+    #[serde(default = "Local::now")]
+    pub last_heartbeat: DateTime<Local>,
+    /// 丢失心跳的周期数
+    #[serde(default)]
+    pub lost_heartbeats: usize,
+    /// 最近一次变为Up状态的时间
+    #[serde(default)]
+    pub up_since: Option<DateTime<Local>>,
+}
+
+/// 命名空间下所有服务实例的状态
+///
+/// 服务和实例均按ID排序，相同的状态导出结果相同
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DiscoveryState {
+    /// service_id -> 服务实例
+    pub services: BTreeMap<String, Vec<InstanceState>>,
 }
 
 #[derive(Debug)]
@@ -108,11 +326,15 @@ pub struct Discovery {
     /// 服务实例
     /// service_id -> Vec<ServiceInstance>
     services: Arc<DashMap<String, Vec<ServiceInstance>>>,
+    /// 最近因心跳超时被移除的实例，仅保存在本节点内存中
+    /// (service_id, instance_id) -> EvictionNotice
+    evicted: Arc<DashMap<(String, String), EvictionNotice>>,
 }
 impl Clone for Discovery {
     fn clone(&self) -> Self {
         Discovery {
             services: Arc::clone(&self.services),
+            evicted: Arc::clone(&self.evicted),
         }
     }
 }
@@ -121,17 +343,21 @@ impl Discovery {
     pub fn new() -> Self {
         Discovery {
             services: Arc::new(DashMap::new()),
+            evicted: Arc::new(DashMap::new()),
         }
     }
 
     /// 注册服务
     ///
     /// 注册一个服务，同时注册0个或多个服务实例，
-    /// 包含多个实例时，所有实例的service_id应该保持一致
+    /// 包含多个实例时，所有实例的service_id应该保持一致。
+    /// 服务已存在时，实例按冲突策略合并到已有的实例列表中，
+    /// 策略为[`ConflictPolicy::Reject`]时，任一实例已存在则全部不注册
     pub fn register_service(
         &self,
         service_id: &str,
         instances: Vec<ServiceInstance>,
+        policy: ConflictPolicy,
     ) -> anyhow::Result<Vec<ServiceInstance>> {
         // 校验service_id是否一致
         for instance in &instances {
@@ -139,12 +365,59 @@ impl Discovery {
                 bail!("The service_id of the registered service instances should be consistent");
             }
         }
-        let instances = self
-            .services
-            .entry(service_id.to_string())
-            .or_insert(instances)
-            .clone();
-        Ok(instances)
+        let mut service = self.services.entry(service_id.to_string()).or_default();
+        let resolved = instances
+            .into_iter()
+            .map(|instance| Self::resolve_conflict(&service, instance, policy))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for instance in resolved {
+            self.evicted
+                .remove(&(instance.service_id.clone(), instance.id.clone()));
+            service.retain(|item| item.id != instance.id);
+            service.push(instance);
+        }
+        Ok(service.clone())
+    }
+
+    /// 按冲突策略得到注册后的实例，实例已存在且策略为[`ConflictPolicy::Reject`]时返回错误
+    ///
+    /// - instances：服务已有的实例
+    fn resolve_conflict(
+        instances: &[ServiceInstance],
+        instance: ServiceInstance,
+        policy: ConflictPolicy,
+    ) -> anyhow::Result<ServiceInstance> {
+        let Some(existing) = instances.iter().find(|item| item.id == instance.id) else {
+            return Ok(instance);
+        };
+        match policy {
+            ConflictPolicy::Replace => Ok(instance),
+            // 保留实例状态和心跳，仅合并元数据
+            ConflictPolicy::MergeMeta => {
+                let mut merged = existing.clone();
+                merged.meta.extend(instance.meta);
+                Ok(merged)
+            }
+            ConflictPolicy::Reject => bail!(CodeError {
+                code: INSTANCE_CONFLICT_CODE,
+                msg: format!(
+                    "instance {}:{} of service {} is already registered",
+                    instance.ip, instance.port, instance.service_id
+                ),
+            }),
+        }
+    }
+
+    /// 预先检查注册实例的冲突，返回注册后的实例，不修改实例列表
+    pub fn check_conflict(
+        &self,
+        instance: ServiceInstance,
+        policy: ConflictPolicy,
+    ) -> anyhow::Result<ServiceInstance> {
+        match self.services.get(&instance.service_id) {
+            Some(instances) => Self::resolve_conflict(&instances, instance, policy),
+            None => Ok(instance),
+        }
     }
 
     /// 注销服务
@@ -157,12 +430,19 @@ impl Discovery {
         Ok(())
     }
 
-    /// 注册服务实例
-    pub fn register_instance(&self, instance: ServiceInstance) -> anyhow::Result<ServiceInstance> {
+    /// 注册服务实例，实例已存在时按冲突策略处理
+    pub fn register_instance(
+        &self,
+        instance: ServiceInstance,
+        policy: ConflictPolicy,
+    ) -> anyhow::Result<ServiceInstance> {
         let mut instances = self
             .services
             .entry(instance.service_id.clone())
-            .or_insert(vec![]);
+            .or_default();
+        let instance = Self::resolve_conflict(&instances, instance, policy)?;
+        self.evicted
+            .remove(&(instance.service_id.clone(), instance.id.clone()));
         // 删除旧实例
         instances.retain(|item| item.id != instance.id);
         // 添加新实例
@@ -178,6 +458,35 @@ impl Discovery {
         Ok(())
     }
 
+    /// 获取服务实例
+    pub fn get_instance(&self, service_id: &str, instance_id: &str) -> Option<ServiceInstance> {
+        self.services
+            .get(service_id)?
+            .iter()
+            .find(|instance| instance.id == instance_id)
+            .cloned()
+    }
+
+    /// 更新服务实例的元数据，实例不存在时忽略
+    pub fn update_instance_meta(
+        &self,
+        service_id: &str,
+        instance_id: &str,
+        meta: HashMap<String, String>,
+        remove_keys: Vec<String>,
+    ) {
+        if let Some(mut service) = self.services.get_mut(service_id)
+            && let Some(instance) = service
+                .iter_mut()
+                .find(|instance| instance.id == instance_id)
+        {
+            for key in remove_keys {
+                instance.meta.remove(&key);
+            }
+            instance.meta.extend(meta);
+        }
+    }
+
     /// 上线一个服务实例（仅通过手动触发）
     #[allow(unused)]
     pub fn online(&self, service_id: &str, instance_id: &str) -> anyhow::Result<()> {
@@ -214,6 +523,11 @@ impl Discovery {
         Ok(list)
     }
 
+    /// 所有服务的实例总数
+    pub fn instance_count(&self) -> usize {
+        self.services.iter().map(|service| service.len()).sum()
+    }
+
     /// 按服务ID获取可用服务实例
     pub fn get_available_service_instances(
         &self,
@@ -245,6 +559,10 @@ impl Discovery {
                         return Ok(HeartbeatResult::Rejected);
                     }
                     instance.update_heartbeat();
+                    instance.lost_heartbeats = 0;
+                    if instance.status != InstanceStatus::Up {
+                        instance.up_since = Some(Local::now());
+                    }
                     instance.status = InstanceStatus::Up;
                     return Ok(HeartbeatResult::Ok);
                 }
@@ -255,54 +573,211 @@ impl Discovery {
         }
     }
 
-    /// 启动心跳检查
-    pub fn start_heartbeat_check_timer(
+    /// 手动修改实例的心跳状态
+    ///
+    /// 仅Sick、Down和Up状态的实例可以标记为Up，手动下线的实例需通过上线操作恢复
+    pub fn override_heartbeat(
         &self,
-        interval: std::time::Duration,
-        timeout: std::time::Duration,
-    ) {
-        let services = self.services.clone();
-        tokio::spawn(async move {
-            let mut interval_timer = tokio::time::interval(interval);
-            loop {
-                interval_timer.tick().await;
-                services.iter_mut().for_each(|mut service| {
-                    service.iter_mut().for_each(|instance| {
-                        // 手动下线的无须处理
-                        if instance.status == InstanceStatus::Offline {
-                            return;
-                        }
-                        // 超过3个心跳周期超时的，状态更新为Down
-                        if instance.lost_heartbeats >= 3 {
-                            instance.status = InstanceStatus::Down;
-                        } else if instance.is_heartbeat_timeout(timeout) {
-                            instance.lost_heartbeats += 1;
-                            instance.status = InstanceStatus::Sick(format!(
-                                "lost heartbeats({})",
-                                instance.lost_heartbeats
-                            ))
-                        }
-                    });
-                });
+        service_id: &str,
+        instance_id: &str,
+        action: HeartbeatOverride,
+    ) -> anyhow::Result<HeartbeatOverrideResult> {
+        let mut services = self.services.get_mut(service_id);
+        let Some(instance) = services.as_mut().and_then(|services| {
+            services
+                .iter_mut()
+                .find(|instance| instance.id == instance_id)
+        }) else {
+            bail!(
+                "instance [{}] of service [{}] not found",
+                instance_id,
+                service_id
+            );
+        };
+        let previous_status = instance.status.clone();
+        let previous_lost_heartbeats = instance.lost_heartbeats;
+        if action == HeartbeatOverride::MarkUp {
+            match instance.status {
+                InstanceStatus::Sick(_) | InstanceStatus::Down => {
+                    instance.status = InstanceStatus::Up;
+                    instance.up_since = Some(Local::now());
+                }
+                InstanceStatus::Up => {}
+                _ => bail!(
+                    "instance [{}] is {:?}, only Sick or Down instances can be marked up",
+                    instance_id,
+                    instance.status
+                ),
             }
+        }
+        instance.update_heartbeat();
+        instance.lost_heartbeats = 0;
+        Ok(HeartbeatOverrideResult {
+            previous_status,
+            previous_lost_heartbeats,
+            status: instance.status.clone(),
+        })
+    }
+
+    /// 获取实例的驱逐通知，需在更新心跳前调用
+    ///
+    /// 实例处于Sick或Down状态，或最近已被移除时返回通知，否则返回None
+    pub fn eviction_notice(&self, service_id: &str, instance_id: &str) -> Option<EvictionNotice> {
+        if let Some(notice) = self
+            .evicted
+            .get(&(service_id.to_string(), instance_id.to_string()))
+        {
+            return Some(notice.clone());
+        }
+        let services = self.services.get(service_id)?;
+        let instance = services
+            .iter()
+            .find(|instance| instance.id == instance_id)?;
+        // 注册后尚未发送过心跳的实例（心跳时间为默认值）不算被驱逐
+        if instance.last_heartbeat == DateTime::<Local>::default() {
+            return None;
+        }
+        let reason = match &instance.status {
+            InstanceStatus::Sick(reason) => reason.clone(),
+            InstanceStatus::Down => "heartbeat timeout".to_string(),
+            _ => return None,
+        };
+        Some(EvictionNotice {
+            reason,
+            removed: false,
+            last_heartbeat: instance.last_heartbeat,
+            evicted_at: None,
+        })
+    }
+
+    /// 检查实例心跳，更新心跳超时实例的状态
+    pub fn check_heartbeats(&self, timeout: std::time::Duration) {
+        self.services.iter_mut().for_each(|mut service| {
+            service.iter_mut().for_each(|instance| {
+                if let Some((status, lost_heartbeats)) = instance.check_heartbeat(timeout) {
+                    instance.status = status;
+                    instance.lost_heartbeats = lost_heartbeats;
+                }
+            });
         });
     }
 
     /// 清理服务实例
-    pub fn start_cleanup_timer(&self, interval: std::time::Duration) {
-        let services = self.services.clone();
-        tokio::spawn(async move {
-            let mut interval_timer = tokio::time::interval(interval);
-            loop {
-                interval_timer.tick().await;
-                // 清理状态为Down的实例
-                services.iter_mut().for_each(|mut service| {
-                    service.retain(|instance| instance.status != InstanceStatus::Down);
-                })
-            }
+    pub fn cleanup(&self) {
+        let now = Local::now();
+        // 清理状态为Down的实例，并记录下来用于返回驱逐通知
+        self.services.iter_mut().for_each(|mut service| {
+            service.retain(|instance| {
+                if instance.status != InstanceStatus::Down {
+                    return true;
+                }
+                self.evicted.insert(
+                    (instance.service_id.clone(), instance.id.clone()),
+                    EvictionNotice {
+                        reason: "heartbeat timeout".to_string(),
+                        removed: true,
+                        last_heartbeat: instance.last_heartbeat,
+                        evicted_at: Some(now),
+                    },
+                );
+                false
+            });
+        });
+        self.evicted.retain(|_, notice| {
+            notice.evicted_at.is_some_and(|evicted_at| {
+                now.signed_duration_since(evicted_at)
+                    .to_std()
+                    .unwrap_or_default()
+                    < EVICTED_RETENTION
+            })
         });
     }
 
+    /// 模拟驱逐
+    ///
+    /// 按当前心跳超时时间模拟执行一次心跳检查和清理，返回状态将发生变化的实例，不修改实例状态
+    pub fn simulate_eviction(&self, timeout: std::time::Duration) -> Vec<EvictionCandidate> {
+        let mut candidates = vec![];
+        for service in self.services.iter() {
+            for instance in service.iter() {
+                let action = if instance.status == InstanceStatus::Down {
+                    EvictionAction::Evict
+                } else {
+                    match instance.check_heartbeat(timeout) {
+                        Some((InstanceStatus::Down, _)) => EvictionAction::Down,
+                        Some(_) => EvictionAction::Sick,
+                        None => continue,
+                    }
+                };
+                candidates.push(EvictionCandidate {
+                    service_id: instance.service_id.clone(),
+                    instance_id: instance.id.clone(),
+                    ip: instance.ip.clone(),
+                    port: instance.port,
+                    status: instance.status.clone(),
+                    action,
+                    lost_heartbeats: instance.lost_heartbeats,
+                    last_heartbeat: instance.last_heartbeat,
+                });
+            }
+        }
+        candidates
+            .sort_by(|a, b| (&a.service_id, &a.instance_id).cmp(&(&b.service_id, &b.instance_id)));
+        candidates
+    }
+
+    /// 导出所有服务实例的状态
+    pub fn export_state(&self) -> DiscoveryState {
+        let services = self
+            .services
+            .iter()
+            .map(|service| {
+                let mut instances = service
+                    .iter()
+                    .map(|instance| InstanceState {
+                        instance: instance.clone(),
+                        last_heartbeat: instance.last_heartbeat,
+                        lost_heartbeats: instance.lost_heartbeats,
+                        up_since: instance.up_since,
+                    })
+                    .collect::<Vec<_>>();
+                instances.sort_by(|a, b| a.instance.id.cmp(&b.instance.id));
+                (service.key().clone(), instances)
+            })
+            .collect();
+        DiscoveryState { services }
+    }
+
+    /// 导入服务实例的状态，替换当前所有服务实例，并清空驱逐记录
+    pub fn import_state(&self, state: DiscoveryState) -> anyhow::Result<()> {
+        for (service_id, instances) in state.services.iter() {
+            if instances
+                .iter()
+                .any(|item| &item.instance.service_id != service_id)
+            {
+                bail!(
+                    "The service_id of the instances of service [{}] should be consistent",
+                    service_id
+                );
+            }
+        }
+        self.services.clear();
+        self.evicted.clear();
+        for (service_id, instances) in state.services {
+            let instances = instances
+                .into_iter()
+                .map(|item| ServiceInstance {
+                    last_heartbeat: item.last_heartbeat,
+                    lost_heartbeats: item.lost_heartbeats,
+                    up_since: item.up_since,
+                    ..item.instance
+                })
+                .collect();
+            self.services.insert(service_id, instances);
+        }
+        Ok(())
+    }
+
     #[allow(unused)]
     pub fn services(&self) -> DashMap<String, Vec<ServiceInstance>> {
         self.services.deref().clone()
@@ -312,13 +787,273 @@ impl Discovery {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rocket::yansi::Paint;
     use std::time::Duration;
+
+    #[test]
+    fn test_warmup_weight() {
+        let mut instance = ServiceInstance::new(
+            "test",
+            "127.0.0.1",
+            8080,
+            HashMap::from([("weight".to_string(), "100".to_string())]),
+        );
+        let warmup = Duration::from_secs(100);
+        // 未Up过的实例使用配置权重
+        assert_eq!(instance.warmup_weight(warmup), 100);
+
+        instance.up_since = Some(Local::now());
+        assert_eq!(instance.warmup_weight(warmup), 10);
+
+        instance.up_since = Some(Local::now() - chrono::Duration::seconds(50));
+        assert_eq!(instance.warmup_weight(warmup), 50);
+
+        instance.up_since = Some(Local::now() - chrono::Duration::seconds(200));
+        assert_eq!(instance.warmup_weight(warmup), 100);
+    }
+    #[test]
+    fn test_update_instance_meta() {
+        let discovery = Discovery::new();
+        let instance = discovery
+            .register_instance(
+                ServiceInstance::new(
+                    "test",
+                    "127.0.0.1",
+                    8080,
+                    HashMap::from([
+                        ("weight".to_string(), "100".to_string()),
+                        ("zone".to_string(), "a".to_string()),
+                    ]),
+                ),
+                ConflictPolicy::Replace,
+            )
+            .unwrap();
+        discovery.update_instance_meta(
+            "test",
+            &instance.id,
+            HashMap::from([("weight".to_string(), "10".to_string())]),
+            vec!["zone".to_string()],
+        );
+        let instance = discovery.get_instance("test", &instance.id).unwrap();
+        assert_eq!(instance.weight(), 10);
+        assert_eq!(instance.meta.get("zone"), None);
+        assert!(discovery.get_instance("test", "unknown").is_none());
+    }
+
+    #[test]
+    fn test_inherit_meta() {
+        let mut instance = ServiceInstance::new(
+            "test",
+            "127.0.0.1",
+            8080,
+            HashMap::from([("weight".to_string(), "50".to_string())]),
+        );
+        instance.inherit_meta(&HashMap::from([
+            ("weight".to_string(), "10".to_string()),
+            ("zone".to_string(), "a".to_string()),
+        ]));
+        assert_eq!(instance.weight(), 50);
+        assert_eq!(instance.meta.get("zone").map(String::as_str), Some("a"));
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        let discovery = Discovery::new();
+        let new_instance = |meta: &[(&str, &str)]| {
+            ServiceInstance::new(
+                "test",
+                "127.0.0.1",
+                8080,
+                meta.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            )
+        };
+        let instance = discovery
+            .register_instance(new_instance(&[("zone", "a")]), ConflictPolicy::Replace)
+            .unwrap();
+        discovery.heartbeat("test", &instance.id).unwrap();
+
+        // 合并元数据，保留实例状态
+        let merged = discovery
+            .register_instance(new_instance(&[("weight", "10")]), ConflictPolicy::MergeMeta)
+            .unwrap();
+        assert_eq!(merged.meta.get("zone").map(String::as_str), Some("a"));
+        assert_eq!(merged.weight(), 10);
+        assert_eq!(merged.status, InstanceStatus::Up);
+
+        // 拒绝注册，实例保持不变
+        let err = discovery
+            .register_instance(new_instance(&[]), ConflictPolicy::Reject)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CodeError>().unwrap().code,
+            INSTANCE_CONFLICT_CODE
+        );
+        assert_eq!(
+            discovery
+                .get_instance("test", &instance.id)
+                .unwrap()
+                .meta
+                .len(),
+            2
+        );
+
+        // 替换实例，状态重置
+        let replaced = discovery
+            .register_instance(new_instance(&[]), ConflictPolicy::Replace)
+            .unwrap();
+        assert!(replaced.meta.is_empty());
+        assert_eq!(replaced.status, InstanceStatus::Ready);
+
+        // 已存在的服务也会注册提供的实例
+        let other = ServiceInstance::new("test", "127.0.0.1", 8081, HashMap::default());
+        let instances = discovery
+            .register_service("test", vec![other.clone()], ConflictPolicy::Reject)
+            .unwrap();
+        assert_eq!(instances.len(), 2);
+        assert!(
+            discovery
+                .register_service("test", vec![other], ConflictPolicy::Reject)
+                .is_err()
+        );
+        assert_eq!(discovery.get_service_instances("test").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_override_heartbeat() {
+        let discovery = Discovery::new();
+        let instance = discovery
+            .register_instance(
+                ServiceInstance::new("test", "127.0.0.1", 8080, HashMap::default()),
+                ConflictPolicy::Replace,
+            )
+            .unwrap();
+        // 未发送过心跳的实例不能标记为Up
+        assert!(
+            discovery
+                .override_heartbeat("test", &instance.id, HeartbeatOverride::MarkUp)
+                .is_err()
+        );
+
+        discovery.heartbeat("test", &instance.id).unwrap();
+        discovery.check_heartbeats(Duration::ZERO);
+        discovery.check_heartbeats(Duration::ZERO);
+        let result = discovery
+            .override_heartbeat("test", &instance.id, HeartbeatOverride::ResetLostHeartbeats)
+            .unwrap();
+        assert_eq!(result.previous_lost_heartbeats, 2);
+        assert!(matches!(result.status, InstanceStatus::Sick(_)));
+        // 清零后重新计算丢失心跳的周期数
+        discovery.check_heartbeats(Duration::ZERO);
+        assert_eq!(
+            discovery.export_state().services["test"][0].lost_heartbeats,
+            1
+        );
+
+        let result = discovery
+            .override_heartbeat("test", &instance.id, HeartbeatOverride::MarkUp)
+            .unwrap();
+        assert!(matches!(result.previous_status, InstanceStatus::Sick(_)));
+        assert_eq!(result.status, InstanceStatus::Up);
+        assert_eq!(
+            discovery
+                .get_available_service_instances("test")
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // 手动下线的实例需通过上线操作恢复
+        discovery.offline("test", &instance.id).unwrap();
+        assert!(
+            discovery
+                .override_heartbeat("test", &instance.id, HeartbeatOverride::MarkUp)
+                .is_err()
+        );
+        assert!(
+            discovery
+                .override_heartbeat("test", "unknown", HeartbeatOverride::ResetLostHeartbeats)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_export_import_state() {
+        let discovery = Discovery::new();
+        for port in [8082, 8080, 8081] {
+            discovery
+                .register_instance(
+                    ServiceInstance::new("test", "127.0.0.1", port, HashMap::default()),
+                    ConflictPolicy::Replace,
+                )
+                .unwrap();
+        }
+        let up = ServiceInstance::generate_id("127.0.0.1", 8081);
+        discovery.heartbeat("test", &up).unwrap();
+
+        let state = discovery.export_state();
+        let ids = state.services["test"]
+            .iter()
+            .map(|item| item.instance.id.clone())
+            .collect::<Vec<_>>();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+
+        let json = serde_json::to_string(&state).unwrap();
+        let imported = Discovery::new();
+        imported
+            .register_instance(
+                ServiceInstance::new("other", "127.0.0.1", 9000, HashMap::default()),
+                ConflictPolicy::Replace,
+            )
+            .unwrap();
+        imported
+            .import_state(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert!(imported.get_service_instances("other").unwrap().is_empty());
+        let available = imported.get_available_service_instances("test").unwrap();
+        assert_eq!(available.len(), 1);
+        assert_eq!(available[0].id, up);
+        assert_eq!(
+            serde_json::to_string(&imported.export_state()).unwrap(),
+            json
+        );
+
+        // 导入时未指定心跳时间的实例使用当前时间
+        let seeded: DiscoveryState = serde_json::from_value(serde_json::json!({
+            "services": {
+                "seeded": [{
+                    "id": "1",
+                    "service_id": "seeded",
+                    "ip": "10.0.0.1",
+                    "port": 80,
+                    "status": "Up",
+                    "meta": {}
+                }]
+            }
+        }))
+        .unwrap();
+        imported.import_state(seeded.clone()).unwrap();
+        assert_eq!(
+            imported
+                .get_available_service_instances("seeded")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(imported.eviction_notice("seeded", "1").is_none());
+
+        let mut inconsistent = seeded;
+        inconsistent
+            .services
+            .insert("wrong".to_string(), inconsistent.services["seeded"].clone());
+        assert!(imported.import_state(inconsistent).is_err());
+    }
+
     #[tokio::test]
     async fn test_discovery() {
         let discovery = Discovery::new();
-        discovery.start_heartbeat_check_timer(Duration::from_secs(5), Duration::from_secs(10));
-        discovery.start_cleanup_timer(Duration::from_secs(15));
         let instance = discovery
             .register_service(
                 "test",
@@ -328,6 +1063,7 @@ mod tests {
                     8080,
                     HashMap::default(),
                 )],
+                ConflictPolicy::Replace,
             )
             .unwrap();
         println!("instance: {:?}", instance);
@@ -339,8 +1075,9 @@ mod tests {
 
         loop {
             tokio::time::sleep(Duration::from_secs(1)).await;
+            discovery.check_heartbeats(Duration::from_secs(10));
+            discovery.cleanup();
 
-            let services = discovery.get_service_instances("test").unwrap();
             println!("services: {:?}", discovery.get_service_instances("test"));
         }
     }
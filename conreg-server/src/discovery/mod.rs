@@ -6,7 +6,10 @@ use tracing::log;
 mod discovery;
 pub mod server;
 use crate::Args;
-pub use discovery::ServiceInstance;
+pub use discovery::{
+    ConflictPolicy, DiscoveryState, EvictionNotice, HeartbeatOverride, HeartbeatResult,
+    ServiceInstance,
+};
 
 #[derive(Debug)]
 pub struct DiscoveryApp {
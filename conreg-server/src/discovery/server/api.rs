@@ -1,11 +1,19 @@
 use crate::app::get_app;
 use crate::auth::UserPrincipal;
-use crate::discovery::discovery::{HeartbeatResult, ServiceInstance};
-use crate::discovery::server::Service;
+use crate::discovery::discovery::{
+    ConflictPolicy, HeartbeatBatchResult, HeartbeatOverride, HeartbeatOverrideResult,
+    HeartbeatResponse, ServiceInstance,
+};
+use crate::discovery::server::monitor::DegradedService;
+use crate::discovery::server::{EvictionSimulation, InstancesSnapshot, Service};
+use crate::openapi::ApiDoc;
 use crate::protocol::res::{PageRes, Res};
+use crate::system::check_ns_write_permission;
 use rocket::serde::json::Json;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
 pub fn routes() -> Vec<rocket::Route> {
     routes![
@@ -14,16 +22,93 @@ pub fn routes() -> Vec<rocket::Route> {
         list_service,
         register_instance,
         deregister_instance,
+        update_instance_meta,
         list_instances,
         available,
+        watch_instances,
         heartbeat,
+        heartbeat_batch,
         offline_instance,
         online_instance,
+        override_heartbeat,
+        simulate_eviction,
+        set_expected_instances,
+        degraded_services,
+        set_warmup,
+        switch_active_set,
+        active_sets,
+    ]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("register_service", "注册一个空服务，不包含任何实例")
+            .auth()
+            .body::<RegisterServiceReq>()
+            .response::<()>(),
+        ApiDoc::new("deregister_service", "注销服务")
+            .auth()
+            .body::<DeregisterServiceReq>()
+            .response::<()>(),
+        ApiDoc::new("list_service", "分页查询服务列表")
+            .auth()
+            .response::<PageRes<Service>>(),
+        ApiDoc::new("register_instance", "注册服务实例")
+            .body::<RegisterServiceInstanceReq>()
+            .response::<ServiceInstance>(),
+        ApiDoc::new("deregister_instance", "注销服务实例")
+            .body::<DeregisterServiceInstanceReq>()
+            .response::<()>(),
+        ApiDoc::new("update_instance_meta", "更新服务实例的元数据")
+            .body::<UpdateInstanceMetaReq>()
+            .response::<ServiceInstance>(),
+        ApiDoc::new("list_instances", "获取服务的全部实例").response::<Vec<ServiceInstance>>(),
+        ApiDoc::new("available", "获取服务的可用实例").response::<Vec<ServiceInstance>>(),
+        ApiDoc::new("watch_instances", "监听服务可用实例的变化（长轮询）")
+            .optional(&["version"])
+            .response::<Option<InstancesSnapshot>>(),
+        ApiDoc::new("heartbeat", "服务实例心跳")
+            .body::<HeartbeatReq>()
+            .response::<HeartbeatResponse>(),
+        ApiDoc::new("heartbeat_batch", "批量服务实例心跳")
+            .body::<HeartbeatBatchReq>()
+            .response::<Vec<HeartbeatBatchResult>>(),
+        ApiDoc::new("offline_instance", "下线服务实例")
+            .body::<OnlineOrOfflineServiceInstanceReq>()
+            .response::<()>(),
+        ApiDoc::new("online_instance", "上线服务实例")
+            .body::<OnlineOrOfflineServiceInstanceReq>()
+            .response::<()>(),
+        ApiDoc::new("override_heartbeat", "手动修改服务实例的心跳状态")
+            .auth()
+            .body::<OverrideHeartbeatReq>()
+            .response::<HeartbeatOverrideResult>(),
+        ApiDoc::new("simulate_eviction", "模拟心跳检查，预览将被驱逐的实例")
+            .auth()
+            .response::<EvictionSimulation>(),
+        ApiDoc::new("set_expected_instances", "设置服务的期望实例数")
+            .auth()
+            .body::<SetExpectedInstancesReq>()
+            .response::<()>(),
+        ApiDoc::new("degraded_services", "查询可用实例数低于期望实例数的服务")
+            .auth()
+            .optional(&["namespace_id"])
+            .response::<Vec<DegradedService>>(),
+        ApiDoc::new("set_warmup", "设置服务的实例预热时长")
+            .auth()
+            .body::<SetWarmupReq>()
+            .response::<()>(),
+        ApiDoc::new("switch_active_set", "切换服务当前生效的实例集合")
+            .auth()
+            .body::<SwitchActiveSetReq>()
+            .response::<()>(),
+        ApiDoc::new("active_sets", "获取命名空间下各服务当前生效的实例集合")
+            .response::<HashMap<String, String>>(),
     ]
 }
 
 /// 注册一个服务
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct RegisterServiceReq {
     namespace_id: String,
     service_id: String,
@@ -31,20 +116,50 @@ struct RegisterServiceReq {
 }
 
 /// 注销一个服务
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct DeregisterServiceReq {
     namespace_id: String,
     service_id: String,
 }
 
+/// 设置服务的期望实例数
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct SetExpectedInstancesReq {
+    namespace_id: String,
+    service_id: String,
+    /// 期望实例数，为空时取消监控
+    expected_instances: Option<u32>,
+}
+
+/// 设置服务的实例预热时长
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct SetWarmupReq {
+    namespace_id: String,
+    service_id: String,
+    /// 预热时长，单位秒，为空时取消预热
+    warmup_seconds: Option<u32>,
+}
+
+/// 切换服务当前生效的实例集合
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct SwitchActiveSetReq {
+    namespace_id: String,
+    service_id: String,
+    /// 生效的实例集合，对应实例元数据中的color，为空时所有实例均可用
+    active_set: Option<String>,
+}
+
 /// 注册一个服务实例
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct RegisterServiceInstanceReq {
     namespace_id: String,
     service_id: String,
     ip: String,
     port: u16,
     meta: HashMap<String, String>,
+    /// 实例已存在时的处理策略，默认替换
+    #[serde(default)]
+    conflict_policy: ConflictPolicy,
 }
 impl From<RegisterServiceInstanceReq> for ServiceInstance {
     fn from(value: RegisterServiceInstanceReq) -> Self {
@@ -53,7 +168,7 @@ impl From<RegisterServiceInstanceReq> for ServiceInstance {
 }
 
 /// 注销一个服务实例
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct DeregisterServiceInstanceReq {
     namespace_id: String,
     service_id: String,
@@ -61,20 +176,62 @@ struct DeregisterServiceInstanceReq {
 }
 
 /// 心跳请求
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct HeartbeatReq {
     namespace_id: String,
     service_id: String,
     instance_id: String,
+    /// 是否返回驱逐通知，旧版本客户端不传，仅返回心跳结果
+    #[serde(default)]
+    with_notice: bool,
+}
+
+/// 批量心跳请求
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct HeartbeatBatchReq {
+    namespace_id: String,
+    instances: Vec<HeartbeatBatchItem>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct HeartbeatBatchItem {
+    service_id: String,
+    instance_id: String,
+}
+
+/// 更新服务实例的元数据
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct UpdateInstanceMetaReq {
+    namespace_id: String,
+    service_id: String,
+    instance_id: String,
+    /// 新增或覆盖的元数据
+    #[serde(default)]
+    meta: HashMap<String, String>,
+    /// 删除的元数据key
+    #[serde(default)]
+    remove_keys: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 struct OnlineOrOfflineServiceInstanceReq {
     namespace_id: String,
     service_id: String,
     instance_id: String,
 }
 
+/// 手动修改服务实例的心跳状态
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct OverrideHeartbeatReq {
+    namespace_id: String,
+    service_id: String,
+    instance_id: String,
+    /// 操作类型
+    action: HeartbeatOverride,
+    /// 修改原因，记录在审计日志中
+    reason: Option<String>,
+}
+
 /// 注册一个空服务，不包含任何实例
 ///
 /// 该接口仅后台调用
@@ -87,7 +244,7 @@ async fn register_service(req: Json<RegisterServiceReq>, _user: UserPrincipal) -
         .await
     {
         Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -104,7 +261,7 @@ async fn deregister_service(req: Json<DeregisterServiceReq>, _user: UserPrincipa
         .await
     {
         Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -135,16 +292,43 @@ async fn list_service(
 }
 
 /// 注册一个服务实例
+///
+/// 实例ID由IP和端口生成，已存在时按`conflict_policy`处理：
+/// `replace`替换已有实例，`merge_meta`保留已有实例并合并元数据，`reject`返回错误码1004
 #[post("/instance/register", data = "<req>")]
 async fn register_instance(req: Json<RegisterServiceInstanceReq>) -> Res<ServiceInstance> {
+    let policy = req.conflict_policy;
     match get_app()
         .discovery_app
         .manager
-        .register_service_instance_and_sync(&req.0.namespace_id.clone(), req.0.into())
+        .register_service_instance_and_sync(&req.0.namespace_id.clone(), req.0.into(), policy)
         .await
     {
         Ok(res) => Res::success(res),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 更新服务实例的元数据
+///
+/// 用于运行时调整实例的权重、版本等，例如灰度发布或预热时逐步提高权重
+#[post("/instance/update-meta", data = "<req>")]
+async fn update_instance_meta(req: Json<UpdateInstanceMetaReq>) -> Res<ServiceInstance> {
+    let req = req.into_inner();
+    match get_app()
+        .discovery_app
+        .manager
+        .update_instance_meta_and_sync(
+            &req.namespace_id,
+            &req.service_id,
+            &req.instance_id,
+            req.meta,
+            req.remove_keys,
+        )
+        .await
+    {
+        Ok(res) => Res::success(res),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -158,7 +342,7 @@ async fn deregister_instance(req: Json<DeregisterServiceInstanceReq>) -> Res<()>
         .await
     {
         Ok(res) => Res::success(res),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -190,20 +374,70 @@ async fn available(namespace_id: &str, service_id: &str) -> Res<Vec<ServiceInsta
     }
 }
 
+/// 监听服务可用实例的变化
+///
+/// `version`为客户端已知的实例列表版本，与当前版本不一致时立即返回当前的实例列表，
+/// 否则等待实例列表变化，29秒内没有变化时返回None
+#[get("/instance/watch?<namespace_id>&<service_id>&<version>")]
+async fn watch_instances(
+    namespace_id: &str,
+    service_id: &str,
+    version: Option<&str>,
+) -> Res<Option<InstancesSnapshot>> {
+    match get_app()
+        .discovery_app
+        .manager
+        .watch_available_instances(
+            namespace_id,
+            service_id,
+            version.unwrap_or_default(),
+            Duration::from_secs(29),
+        )
+        .await
+    {
+        Ok(snapshot) => Res::success(snapshot),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
 /// 接收客户端心跳
 #[post("/heartbeat", data = "<req>")]
-async fn heartbeat(req: Json<HeartbeatReq>) -> Res<HeartbeatResult> {
+async fn heartbeat(req: Json<HeartbeatReq>) -> Res<HeartbeatResponse> {
     match get_app()
         .discovery_app
         .manager
         .heartbeat_and_sync(&req.namespace_id, &req.service_id, &req.instance_id)
         .await
     {
-        Ok(result) => Res::success(result),
-        Err(e) => Res::error(&e.to_string()),
+        Ok((result, eviction)) if req.with_notice => {
+            Res::success(HeartbeatResponse::WithNotice { result, eviction })
+        }
+        Ok((result, _)) => Res::success(HeartbeatResponse::Result(result)),
+        Err(e) => Res::from_error(&e),
     }
 }
 
+/// 批量接收心跳
+///
+/// 供sidecar代理、网关等管理大量实例的客户端使用，一次请求更新同一命名空间下多个实例的心跳
+#[post("/heartbeat-batch", data = "<req>")]
+async fn heartbeat_batch(req: Json<HeartbeatBatchReq>) -> Res<Vec<HeartbeatBatchResult>> {
+    let req = req.into_inner();
+    let instances = req
+        .instances
+        .into_iter()
+        .map(|item| (item.service_id, item.instance_id))
+        .collect();
+    match get_app()
+        .discovery_app
+        .manager
+        .heartbeat_batch_and_sync(&req.namespace_id, instances)
+        .await
+    {
+        Ok(results) => Res::success(results),
+        Err(e) => Res::from_error(&e),
+    }
+}
 
 #[post("/instance/offline", data = "<req>")]
 async fn offline_instance(req: Json<OnlineOrOfflineServiceInstanceReq>) -> Res<()> {
@@ -214,7 +448,7 @@ async fn offline_instance(req: Json<OnlineOrOfflineServiceInstanceReq>) -> Res<(
         .await
     {
         Ok(res) => Res::success(res),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -227,6 +461,136 @@ async fn online_instance(req: Json<OnlineOrOfflineServiceInstanceReq>) -> Res<()
         .await
     {
         Ok(res) => Res::success(res),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 手动修改服务实例的心跳状态
+///
+/// 网络短暂抖动导致实例被判定为不健康时，可清零丢失心跳的周期数或直接将实例恢复为Up，
+/// 不必等待下一次心跳。需要命名空间的写权限，修改记录在审计日志中
+#[post("/instance/heartbeat-override", data = "<req>")]
+async fn override_heartbeat(
+    req: Json<OverrideHeartbeatReq>,
+    user: UserPrincipal,
+) -> Res<HeartbeatOverrideResult> {
+    if !check_ns_write_permission(&user, &req.namespace_id).await {
+        return Res::error("no permission");
+    }
+    match get_app()
+        .discovery_app
+        .manager
+        .override_heartbeat_and_sync(
+            &req.namespace_id,
+            &req.service_id,
+            &req.instance_id,
+            req.action,
+            &user.username,
+            req.reason.as_deref(),
+        )
+        .await
+    {
+        Ok(result) => Res::success(result),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 模拟驱逐
+///
+/// 返回按当前心跳超时配置此刻将被标记为Sick、Down或被清理的实例，不修改实例状态，
+/// 用于在调整心跳配置前验证影响范围
+#[get("/simulate-eviction?<namespace_id>")]
+async fn simulate_eviction(namespace_id: &str, _user: UserPrincipal) -> Res<EvictionSimulation> {
+    match get_app()
+        .discovery_app
+        .manager
+        .simulate_eviction(namespace_id)
+        .await
+    {
+        Ok(res) => Res::success(res),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 设置服务的期望实例数
+///
+/// 健康实例数持续低于期望实例数时，服务将出现在降级服务列表中，并发送Webhook通知
+#[post("/service/expected-instances", data = "<req>")]
+async fn set_expected_instances(
+    req: Json<SetExpectedInstancesReq>,
+    _user: UserPrincipal,
+) -> Res<()> {
+    match get_app()
+        .discovery_app
+        .manager
+        .set_expected_instances_and_sync(&req.namespace_id, &req.service_id, req.expected_instances)
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 降级服务列表
+///
+/// namespace_id为空时返回所有命名空间的降级服务
+#[get("/service/degraded?<namespace_id>")]
+async fn degraded_services(
+    namespace_id: Option<&str>,
+    _user: UserPrincipal,
+) -> Res<Vec<DegradedService>> {
+    Res::success(
+        get_app()
+            .discovery_app
+            .manager
+            .list_degraded_services(namespace_id),
+    )
+}
+
+/// 设置服务的实例预热时长
+///
+/// 实例变为Up后的预热期内，可用实例列表中的有效权重从配置权重的10%线性增长到配置权重
+#[post("/service/warmup", data = "<req>")]
+async fn set_warmup(req: Json<SetWarmupReq>, _user: UserPrincipal) -> Res<()> {
+    match get_app()
+        .discovery_app
+        .manager
+        .set_warmup_and_sync(&req.namespace_id, &req.service_id, req.warmup_seconds)
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 切换服务当前生效的实例集合（蓝绿部署）
+///
+/// 切换后，可用实例列表仅返回元数据color与生效集合一致的实例，未设置color的实例不受影响
+#[post("/service/active-set", data = "<req>")]
+async fn switch_active_set(req: Json<SwitchActiveSetReq>, _user: UserPrincipal) -> Res<()> {
+    match get_app()
+        .discovery_app
+        .manager
+        .switch_active_set_and_sync(&req.namespace_id, &req.service_id, req.active_set.clone())
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 获取命名空间下各服务当前生效的实例集合
+///
+/// 客户端定时拉取，在生效集合变化时立即刷新服务实例
+#[get("/service/active-sets?<namespace_id>")]
+async fn active_sets(namespace_id: &str) -> Res<HashMap<String, String>> {
+    match get_app()
+        .discovery_app
+        .manager
+        .get_active_sets(namespace_id)
+        .await
+    {
+        Ok(active_sets) => Res::success(active_sets),
         Err(e) => Res::error(&e.to_string()),
     }
-}
\ No newline at end of file
+}
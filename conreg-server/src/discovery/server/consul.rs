@@ -0,0 +1,250 @@
+//! Consul兼容接口
+//!
+//! 提供Consul HTTP API的最小子集，已集成Consul的生态（如Prometheus的consul_sd、Fabio）
+//! 可以直接使用conreg的注册中心：
+//! - `GET /v1/agent/self`：仅返回数据中心和节点名称
+//! - `GET /v1/catalog/services`：服务列表
+//! - `GET /v1/health/service/<service>`：服务实例及健康状态，`passing`参数仅返回可用实例
+//!
+//! 映射规则：
+//! - 查询参数`ns`对应命名空间，未指定时为`public`
+//! - 实例元数据`tags`（逗号分隔）作为Consul的Tags，其余元数据作为Meta
+//! - 命名空间开启认证时，通过请求头`X-Consul-Token`或查询参数`token`传递命名空间Token
+//!
+//! 不支持阻塞查询，响应头`X-Consul-Index`为当前Raft日志索引，请求立即返回。
+
+use crate::app::get_app;
+use crate::discovery::ServiceInstance;
+use crate::discovery::discovery::InstanceStatus;
+use crate::event::journal;
+use rocket::Request;
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::json::Json;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use tracing::log;
+
+/// 默认命名空间
+const DEFAULT_NAMESPACE: &str = "public";
+/// 数据中心，conreg没有数据中心的概念，固定返回
+const DATACENTER: &str = "dc1";
+/// 导出为Consul Tags的元数据key
+const TAGS_META_KEY: &str = "tags";
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![agent_self, catalog_services, health_service]
+}
+
+/// Consul响应，附带`X-Consul-Index`响应头
+#[derive(Responder)]
+pub struct ConsulRes<T> {
+    inner: Json<T>,
+    index: Header<'static>,
+}
+
+impl<T> ConsulRes<T> {
+    fn new(data: T) -> Self {
+        Self {
+            inner: Json(data),
+            index: Header::new("X-Consul-Index", journal::revision().max(1).to_string()),
+        }
+    }
+}
+
+/// Consul Token，从请求头`X-Consul-Token`获取
+pub struct ConsulToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ConsulToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ConsulToken(
+            req.headers().get_one("X-Consul-Token").map(String::from),
+        ))
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ServiceEntry {
+    node: Value,
+    service: Value,
+    checks: Vec<Value>,
+}
+
+/// 校验命名空间Token，返回命名空间ID
+async fn auth(
+    ns: Option<&str>,
+    token: Option<&str>,
+    header_token: ConsulToken,
+) -> Result<String, Status> {
+    let namespace_id = ns.unwrap_or(DEFAULT_NAMESPACE);
+    let token = token.or(header_token.0.as_deref());
+    match get_app()
+        .namespace_app
+        .manager
+        .auth(namespace_id, token)
+        .await
+    {
+        Ok(true) => Ok(namespace_id.to_string()),
+        Ok(false) => Err(Status::Forbidden),
+        Err(e) => {
+            log::error!("auth namespace {} error: {}", namespace_id, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// 本节点信息
+#[get("/agent/self")]
+async fn agent_self() -> ConsulRes<Value> {
+    ConsulRes::new(json!({
+        "Config": {
+            "Datacenter": DATACENTER,
+            "NodeName": "conreg",
+        },
+        "Member": { "Name": "conreg" },
+    }))
+}
+
+/// 服务列表，key为服务ID，value为服务下所有实例的Tags
+#[get("/catalog/services?<ns>&<token>")]
+async fn catalog_services(
+    ns: Option<&str>,
+    token: Option<&str>,
+    header_token: ConsulToken,
+) -> Result<ConsulRes<BTreeMap<String, Vec<String>>>, Status> {
+    let namespace_id = auth(ns, token, header_token).await?;
+    let manager = &get_app().discovery_app.manager;
+    let services = manager
+        .list_services(&namespace_id, 1, 10000)
+        .await
+        .map_err(|e| {
+            log::error!("list services error: {}", e);
+            Status::InternalServerError
+        })?
+        .1;
+
+    let mut catalog = BTreeMap::new();
+    for service in services {
+        let instances = manager
+            .get_instances(&namespace_id, &service.service_id)
+            .await
+            .unwrap_or_default();
+        let mut tags = instances.iter().flat_map(tags).collect::<Vec<_>>();
+        tags.sort();
+        tags.dedup();
+        catalog.insert(service.service_id, tags);
+    }
+    Ok(ConsulRes::new(catalog))
+}
+
+/// 服务实例及健康状态
+#[get("/health/service/<service>?<ns>&<token>&<passing>")]
+async fn health_service(
+    service: &str,
+    ns: Option<&str>,
+    token: Option<&str>,
+    passing: Option<&str>,
+    header_token: ConsulToken,
+) -> Result<ConsulRes<Vec<ServiceEntry>>, Status> {
+    let namespace_id = auth(ns, token, header_token).await?;
+    let manager = &get_app().discovery_app.manager;
+    // passing参数可以不带值，即`?passing`
+    let instances = if passing.is_some_and(|passing| passing != "false") {
+        manager
+            .get_available_instances(&namespace_id, service)
+            .await
+    } else {
+        manager.get_instances(&namespace_id, service).await
+    }
+    .map_err(|e| {
+        log::error!("get instances of service {} error: {}", service, e);
+        Status::InternalServerError
+    })?;
+
+    Ok(ConsulRes::new(
+        instances.iter().map(service_entry).collect::<Vec<_>>(),
+    ))
+}
+
+fn tags(instance: &ServiceInstance) -> Vec<String> {
+    instance
+        .meta
+        .get(TAGS_META_KEY)
+        .map(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn service_entry(instance: &ServiceInstance) -> ServiceEntry {
+    let (status, output) = match instance.status() {
+        InstanceStatus::Up => ("passing", String::new()),
+        InstanceStatus::Ready => ("warning", "waiting for the first heartbeat".to_string()),
+        InstanceStatus::Sick(reason) => ("critical", reason.clone()),
+        InstanceStatus::Down => ("critical", "heartbeat timeout".to_string()),
+        InstanceStatus::Offline => ("critical", "offline".to_string()),
+    };
+    let meta = instance
+        .meta
+        .iter()
+        .filter(|(key, _)| key.as_str() != TAGS_META_KEY)
+        .collect::<HashMap<_, _>>();
+    ServiceEntry {
+        node: json!({
+            "ID": instance.ip,
+            "Node": instance.ip,
+            "Address": instance.ip,
+            "Datacenter": DATACENTER,
+            "TaggedAddresses": {},
+            "Meta": {},
+        }),
+        service: json!({
+            "ID": instance.id,
+            "Service": instance.service_id,
+            "Tags": tags(instance),
+            "Address": instance.ip,
+            "Port": instance.port,
+            "Meta": meta,
+            "Weights": { "Passing": instance.weight(), "Warning": 1 },
+        }),
+        checks: vec![json!({
+            "Node": instance.ip,
+            "CheckID": format!("service:{}", instance.id),
+            "Name": "Service heartbeat",
+            "Status": status,
+            "Output": output,
+            "ServiceID": instance.id,
+            "ServiceName": instance.service_id,
+        })],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let mut instance = ServiceInstance::new("svc", "127.0.0.1", 8080, HashMap::new());
+        assert!(tags(&instance).is_empty());
+        instance
+            .meta
+            .insert(TAGS_META_KEY.to_string(), "web, v1,,".to_string());
+        instance.meta.insert("zone".to_string(), "a".to_string());
+        assert_eq!(tags(&instance), vec!["web", "v1"]);
+
+        let entry = service_entry(&instance);
+        assert_eq!(entry.service["Tags"], json!(["web", "v1"]));
+        assert_eq!(entry.service["Meta"], json!({"zone": "a"}));
+        assert_eq!(entry.checks[0]["Status"], "warning");
+    }
+}
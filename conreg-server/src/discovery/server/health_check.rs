@@ -0,0 +1,256 @@
+//! 主动健康检查
+//!
+//! 注册中心默认只依赖客户端心跳判断实例是否健康，无法集成客户端SDK的服务（如只提供gRPC接口的服务）
+//! 可以在实例或服务的元数据中开启主动健康检查，由Leader节点定期探测实例，探测成功视为一次心跳：
+//! - `health-check`：探测方式，`http`、`tcp`或`grpc`
+//! - `health-check-port`：探测端口，默认为实例端口
+//! - `health-check-path`：HTTP探测的路径，默认为`/health`，返回2xx时视为健康
+//! - `health-check-service`：gRPC探测的服务名，默认为空，即检查整个服务端
+//!
+//! gRPC探测使用标准的[gRPC Health Checking Protocol](https://github.com/grpc/grpc/blob/master/doc/health-checking.md)，
+//! 返回`SERVING`时视为健康。探测失败时不做处理，实例按心跳超时的规则变为不健康。
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic_health::ServingStatus;
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_client::HealthClient;
+
+/// 探测超时时间
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// HTTP探测的默认路径
+const DEFAULT_HTTP_PATH: &str = "/health";
+
+/// 健康检查的探测方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    /// HTTP GET请求，返回2xx时视为健康
+    Http { path: String },
+    /// 建立TCP连接
+    Tcp,
+    /// gRPC Health Checking Protocol，`service`为空时检查整个服务端
+    Grpc { service: String },
+}
+
+/// 实例的健康检查设置
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthCheck {
+    pub probe: Probe,
+    /// 探测端口，为空时使用实例端口
+    pub port: Option<u16>,
+}
+
+impl HealthCheck {
+    /// 从元数据中读取健康检查设置，未开启或设置无效时返回None
+    pub fn from_meta(meta: &HashMap<String, String>) -> Option<Self> {
+        let probe = match meta.get("health-check")?.trim().to_lowercase().as_str() {
+            "http" => Probe::Http {
+                path: meta
+                    .get("health-check-path")
+                    .filter(|path| !path.is_empty())
+                    .map(|path| match path.starts_with('/') {
+                        true => path.clone(),
+                        false => format!("/{}", path),
+                    })
+                    .unwrap_or_else(|| DEFAULT_HTTP_PATH.to_string()),
+            },
+            "tcp" => Probe::Tcp,
+            "grpc" => Probe::Grpc {
+                service: meta
+                    .get("health-check-service")
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+            _ => return None,
+        };
+        Some(HealthCheck {
+            probe,
+            port: meta
+                .get("health-check-port")
+                .and_then(|port| port.parse().ok()),
+        })
+    }
+
+    /// 探测实例，健康时返回Ok
+    pub async fn probe(&self, ip: &str, port: u16) -> anyhow::Result<()> {
+        let address = format!("{}:{}", ip, self.port.unwrap_or(port));
+        match tokio::time::timeout(PROBE_TIMEOUT, self.probe_(&address)).await {
+            Ok(res) => res,
+            Err(_) => anyhow::bail!("probe {} timed out after {:?}", address, PROBE_TIMEOUT),
+        }
+    }
+
+    async fn probe_(&self, address: &str) -> anyhow::Result<()> {
+        match &self.probe {
+            Probe::Http { path } => {
+                let status = reqwest::Client::builder()
+                    .no_proxy()
+                    .build()?
+                    .get(format!("http://{}{}", address, path))
+                    .send()
+                    .await?
+                    .status();
+                if !status.is_success() {
+                    anyhow::bail!("http probe {} returned {}", address, status);
+                }
+            }
+            Probe::Tcp => {
+                tokio::net::TcpStream::connect(address).await?;
+            }
+            Probe::Grpc { service } => {
+                let channel =
+                    tonic::transport::Endpoint::from_shared(format!("http://{}", address))?
+                        .connect()
+                        .await?;
+                let status = HealthClient::new(channel)
+                    .check(HealthCheckRequest {
+                        service: service.clone(),
+                    })
+                    .await?
+                    .into_inner()
+                    .status;
+                if status != ServingStatus::Serving as i32 {
+                    anyhow::bail!("grpc probe {} returned status {}", address, status);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn meta(items: &[(&str, &str)]) -> HashMap<String, String> {
+        items
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_meta() {
+        assert_eq!(HealthCheck::from_meta(&meta(&[])), None);
+        assert_eq!(
+            HealthCheck::from_meta(&meta(&[("health-check", "udp")])),
+            None
+        );
+        assert_eq!(
+            HealthCheck::from_meta(&meta(&[("health-check", "HTTP")])),
+            Some(HealthCheck {
+                probe: Probe::Http {
+                    path: "/health".to_string()
+                },
+                port: None,
+            })
+        );
+        assert_eq!(
+            HealthCheck::from_meta(&meta(&[
+                ("health-check", "http"),
+                ("health-check-path", "ready"),
+                ("health-check-port", "9000"),
+            ])),
+            Some(HealthCheck {
+                probe: Probe::Http {
+                    path: "/ready".to_string()
+                },
+                port: Some(9000),
+            })
+        );
+        assert_eq!(
+            HealthCheck::from_meta(&meta(&[
+                ("health-check", "grpc"),
+                ("health-check-service", "demo.Greeter"),
+            ])),
+            Some(HealthCheck {
+                probe: Probe::Grpc {
+                    service: "demo.Greeter".to_string()
+                },
+                port: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tcp_and_http_probe() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or_default();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let status = match request.starts_with("GET /health ") {
+                    true => "200 OK",
+                    false => "503 Service Unavailable",
+                };
+                let _ = stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                            status
+                        )
+                        .as_bytes(),
+                    )
+                    .await;
+            }
+        });
+
+        let tcp = HealthCheck::from_meta(&meta(&[("health-check", "tcp")])).unwrap();
+        assert!(tcp.probe("127.0.0.1", port).await.is_ok());
+        assert!(tcp.probe("127.0.0.1", 1).await.is_err());
+
+        let http = HealthCheck::from_meta(&meta(&[("health-check", "http")])).unwrap();
+        assert!(http.probe("127.0.0.1", port).await.is_ok());
+        let http = HealthCheck::from_meta(&meta(&[
+            ("health-check", "http"),
+            ("health-check-path", "/ready"),
+        ]))
+        .unwrap();
+        assert!(http.probe("127.0.0.1", port).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_probe() {
+        let (reporter, service) = tonic_health::server::health_reporter();
+        reporter
+            .set_service_status("demo.Greeter", ServingStatus::Serving)
+            .await;
+        reporter
+            .set_service_status("demo.Stopped", ServingStatus::NotServing)
+            .await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let probe = |service: &str| {
+            HealthCheck::from_meta(&meta(&[
+                ("health-check", "grpc"),
+                ("health-check-service", service),
+            ]))
+            .unwrap()
+        };
+        // 空服务名检查整个服务端
+        assert!(probe("").probe("127.0.0.1", port).await.is_ok());
+        assert!(probe("demo.Greeter").probe("127.0.0.1", port).await.is_ok());
+        assert!(
+            probe("demo.Stopped")
+                .probe("127.0.0.1", port)
+                .await
+                .is_err()
+        );
+        assert!(
+            probe("demo.Unknown")
+                .probe("127.0.0.1", port)
+                .await
+                .is_err()
+        );
+        assert!(probe("").probe("127.0.0.1", 1).await.is_err());
+    }
+}
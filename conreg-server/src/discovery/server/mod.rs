@@ -1,31 +1,118 @@
 pub mod api;
+pub mod consul;
+pub mod health_check;
+pub mod monitor;
+pub mod prometheus;
+pub mod self_register;
+pub mod udp;
+pub mod xds;
 
 use crate::Args;
+use crate::app::get_app;
 use crate::db::DbPool;
-use crate::discovery::discovery::{Discovery, HeartbeatResult, ServiceInstance};
+use crate::discovery::discovery::{
+    ConflictPolicy, Discovery, DiscoveryState, EvictionCandidate, EvictionNotice,
+    HeartbeatBatchResult, HeartbeatOverride, HeartbeatOverrideResult, HeartbeatResult,
+    InstanceStatus, ServiceInstance,
+};
+use crate::discovery::server::health_check::HealthCheck;
+use crate::discovery::server::monitor::{DegradedService, ServiceMonitor};
+use crate::protocol::res::CodeError;
 use crate::raft::RaftRequest;
 use crate::raft::api::raft_write;
-use anyhow::bail;
+use crate::schedule::{self, TaskScope};
+use crate::webhook;
+use anyhow::{Context, bail};
 use chrono::{DateTime, Local};
 use dashmap::DashMap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
 use sqlx::sqlite::SqliteRow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Deref;
 use std::time::Duration;
 use tracing::log;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// 批量心跳的最大实例数
+const MAX_HEARTBEAT_BATCH: usize = 1000;
+/// 心跳检查间隔
+const HEARTBEAT_CHECK_INTERVAL: Duration = Duration::from_secs(6);
+/// 心跳超时时间
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+/// 实例清理间隔
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+/// 服务可用性检查间隔
+const MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+/// 监听服务实例时检查实例变化的间隔
+const WATCH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// 主动健康检查间隔，小于心跳超时时间，探测成功的实例不会因心跳超时变为不健康
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Service {
-    service_id: String,
-    namespace_id: String,
+    pub service_id: String,
+    pub namespace_id: String,
     meta: HashMap<String, String>,
     create_time: DateTime<Local>,
+    /// 期望实例数，为空时不监控
+    #[serde(default)]
+    expected_instances: Option<u32>,
+    /// 实例预热时长，单位秒，为空时不预热
+    #[serde(default)]
+    warmup_seconds: Option<u32>,
+    /// 当前生效的实例集合（蓝绿部署），为空时所有实例均可用
+    #[serde(default)]
+    active_set: Option<String>,
     state: State,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// 模拟驱逐结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EvictionSimulation {
+    /// 心跳检查间隔，单位秒
+    pub heartbeat_check_interval_secs: u64,
+    /// 心跳超时时间，单位秒
+    pub heartbeat_timeout_secs: u64,
+    /// 实例清理间隔，单位秒
+    pub cleanup_interval_secs: u64,
+    /// 状态将发生变化的实例
+    pub instances: Vec<EvictionCandidate>,
+}
+
+/// 服务可用实例快照
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InstancesSnapshot {
+    /// 实例列表的版本，客户端下次监听时使用
+    pub version: String,
+    /// 可用实例
+    pub instances: Vec<ServiceInstance>,
+}
+
+impl InstancesSnapshot {
+    fn new(instances: Vec<ServiceInstance>) -> Self {
+        // 版本仅包含实例ID、地址和元数据，预热期内持续变化的有效权重不影响版本
+        let mut keys = instances
+            .iter()
+            .map(|instance| {
+                format!(
+                    "{}|{}|{}|{:?}",
+                    instance.id,
+                    instance.ip,
+                    instance.port,
+                    instance.meta.iter().collect::<BTreeMap<_, _>>()
+                )
+            })
+            .collect::<Vec<_>>();
+        keys.sort();
+        InstancesSnapshot {
+            version: format!("{:x}", md5::compute(keys.join("\n"))),
+            instances,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 struct State {
     /// 实例数量，包含所有状态的
     total_instances: usize,
@@ -43,6 +130,9 @@ impl sqlx::FromRow<'_, SqliteRow> for Service {
             namespace_id: row.try_get("namespace_id")?,
             meta,
             create_time: row.try_get("create_time")?,
+            expected_instances: row.try_get("expected_instances")?,
+            warmup_seconds: row.try_get("warmup_seconds")?,
+            active_set: row.try_get("active_set")?,
             state: State::default(),
         })
     }
@@ -59,8 +149,8 @@ impl sqlx::FromRow<'_, SqliteRow> for Service {
 /// 2. 如果注册中心向客户端发起心跳，需要客户端支持接收心跳请求，需要客户端改造。
 /// 3. 当注册的非临时实例过多时，由注册中心主动发起并维护实例心跳时会占用过多资源
 ///
-/// 对于非http服务或者无法集成客户端sdk的服务（如语言不支持），考虑提供一个平台无关的工具，
-/// 用这个工具来自定义验证实例是否正常的逻辑，并维护心跳。
+/// 对于无法集成客户端sdk的服务（如语言不支持），可以在元数据中开启主动健康检查，
+/// 由注册中心按HTTP、TCP或gRPC协议探测实例，探测成功视为一次心跳，见[`health_check`]。
 #[derive(Debug)]
 pub struct DiscoveryManager {
     /// 启动参数
@@ -68,6 +158,8 @@ pub struct DiscoveryManager {
     args: Args,
     /// 命名空间ID -> 服务发现组件实例
     discoveries: DashMap<String, Discovery>,
+    /// 服务可用性监控
+    monitor: ServiceMonitor,
 }
 
 impl DiscoveryManager {
@@ -75,15 +167,21 @@ impl DiscoveryManager {
         Ok(DiscoveryManager {
             args: args.clone(),
             discoveries: DashMap::default(),
+            monitor: ServiceMonitor::default(),
         })
     }
 
+    /// 将服务注册变更直接提交到raft集群执行，非Leader节点由[`raft_write`]转发到Leader，
+    /// 不经过本节点的HTTP接口
     async fn sync(&self, request: RaftRequest) -> anyhow::Result<()> {
         log::debug!("sync discovery request: {:?}", request);
         let res = raft_write(request).await;
         if !res.is_success() {
             log::error!("sync discovery error: {:?}", res.msg);
-            bail!("sync discovery error: {}", res.msg);
+            bail!(CodeError {
+                code: res.code,
+                msg: format!("sync discovery error: {}", res.msg),
+            });
         }
         log::debug!("sync discovery success");
         Ok(())
@@ -101,9 +199,6 @@ impl DiscoveryManager {
                 bail!("namespace [{}] not found", namespace_id);
             }
             let discovery = Discovery::new();
-            discovery.start_heartbeat_check_timer(Duration::from_secs(6), Duration::from_secs(5));
-            discovery.start_cleanup_timer(Duration::from_secs(10));
-
             self.discoveries
                 .insert(namespace_id.to_string(), discovery.clone());
 
@@ -140,7 +235,8 @@ impl DiscoveryManager {
                 .execute(DbPool::get())
                 .await?;
         } else {
-            sqlx::query("update service set meta = ?, update_time = ? where namespace_id = ? and service_id = ?")
+            // 注册实例时不传元数据，保留服务已有的元数据
+            sqlx::query("update service set meta = coalesce(?, meta), update_time = ? where namespace_id = ? and service_id = ?")
                 .bind(meta_json)
                 .bind(Local::now())
                 .bind(namespace_id.to_string())
@@ -168,6 +264,11 @@ impl DiscoveryManager {
         meta: HashMap<String, String>,
     ) -> anyhow::Result<()> {
         let _ = self.try_get_discovery(namespace_id).await?;
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
 
         self.sync(RaftRequest::RegisterService {
             service: Service {
@@ -175,6 +276,9 @@ impl DiscoveryManager {
                 namespace_id: namespace_id.to_string(),
                 meta,
                 create_time: Local::now(),
+                expected_instances: None,
+                warmup_seconds: None,
+                active_set: None,
                 state: State::default(),
             },
         })
@@ -192,7 +296,7 @@ impl DiscoveryManager {
         .await?;
 
         let discovery = self.try_get_discovery(&service.namespace_id).await?;
-        discovery.register_service(&service.service_id, vec![])?;
+        discovery.register_service(&service.service_id, vec![], ConflictPolicy::default())?;
 
         Ok(())
     }
@@ -233,6 +337,357 @@ impl DiscoveryManager {
         Ok((total, rows))
     }
 
+    /// 获取命名空间下所有的服务ID
+    pub async fn list_service_ids(&self, namespace_id: &str) -> anyhow::Result<HashSet<String>> {
+        let ids: Vec<String> =
+            sqlx::query_scalar("SELECT service_id FROM service WHERE namespace_id = ?")
+                .bind(namespace_id)
+                .fetch_all(DbPool::get())
+                .await?;
+        Ok(ids.into_iter().collect())
+    }
+
+    /// 设置服务的期望实例数，并同步到集群
+    ///
+    /// expected_instances为空时取消监控
+    pub async fn set_expected_instances_and_sync(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        expected_instances: Option<u32>,
+    ) -> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        let count: u64 = sqlx::query_scalar(
+            "select count(1) from service where namespace_id = ? and service_id = ?",
+        )
+        .bind(namespace_id)
+        .bind(service_id)
+        .fetch_one(DbPool::get())
+        .await?;
+        if count == 0 {
+            bail!("service [{}] not found", service_id);
+        }
+
+        self.sync(RaftRequest::SetServiceExpectedInstances {
+            namespace_id: namespace_id.to_string(),
+            service_id: service_id.to_string(),
+            expected_instances,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 设置服务的期望实例数
+    pub async fn set_expected_instances(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        expected_instances: Option<u32>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("update service set expected_instances = ?, update_time = ? where namespace_id = ? and service_id = ?")
+            .bind(expected_instances)
+            .bind(Local::now())
+            .bind(namespace_id)
+            .bind(service_id)
+            .execute(DbPool::get())
+            .await?;
+        Ok(())
+    }
+
+    /// 设置服务的实例预热时长，并同步到集群
+    ///
+    /// warmup_seconds为空时取消预热
+    pub async fn set_warmup_and_sync(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        warmup_seconds: Option<u32>,
+    ) -> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        let count: u64 = sqlx::query_scalar(
+            "select count(1) from service where namespace_id = ? and service_id = ?",
+        )
+        .bind(namespace_id)
+        .bind(service_id)
+        .fetch_one(DbPool::get())
+        .await?;
+        if count == 0 {
+            bail!("service [{}] not found", service_id);
+        }
+
+        self.sync(RaftRequest::SetServiceWarmup {
+            namespace_id: namespace_id.to_string(),
+            service_id: service_id.to_string(),
+            warmup_seconds,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 设置服务的实例预热时长
+    pub async fn set_warmup(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        warmup_seconds: Option<u32>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("update service set warmup_seconds = ?, update_time = ? where namespace_id = ? and service_id = ?")
+            .bind(warmup_seconds)
+            .bind(Local::now())
+            .bind(namespace_id)
+            .bind(service_id)
+            .execute(DbPool::get())
+            .await?;
+        Ok(())
+    }
+
+    /// 切换服务当前生效的实例集合，并同步到集群
+    ///
+    /// active_set为空时取消切换，所有实例均可用
+    pub async fn switch_active_set_and_sync(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        active_set: Option<String>,
+    ) -> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        let count: u64 = sqlx::query_scalar(
+            "select count(1) from service where namespace_id = ? and service_id = ?",
+        )
+        .bind(namespace_id)
+        .bind(service_id)
+        .fetch_one(DbPool::get())
+        .await?;
+        if count == 0 {
+            bail!("service [{}] not found", service_id);
+        }
+
+        self.sync(RaftRequest::SwitchServiceActiveSet {
+            namespace_id: namespace_id.to_string(),
+            service_id: service_id.to_string(),
+            active_set,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 切换服务当前生效的实例集合
+    pub async fn switch_active_set(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        active_set: Option<String>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("update service set active_set = ?, update_time = ? where namespace_id = ? and service_id = ?")
+            .bind(active_set)
+            .bind(Local::now())
+            .bind(namespace_id)
+            .bind(service_id)
+            .execute(DbPool::get())
+            .await?;
+        Ok(())
+    }
+
+    /// 获取命名空间下设置了生效实例集合的服务，key为服务ID，value为生效的实例集合
+    pub async fn get_active_sets(
+        &self,
+        namespace_id: &str,
+    ) -> anyhow::Result<HashMap<String, String>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "select service_id, active_set from service where namespace_id = ? and active_set is not null",
+        )
+        .bind(namespace_id)
+        .fetch_all(DbPool::get())
+        .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// 降级服务列表
+    ///
+    /// 返回健康实例数持续低于期望实例数超过指定时间的服务
+    pub fn list_degraded_services(&self, namespace_id: Option<&str>) -> Vec<DegradedService> {
+        self.monitor.list(namespace_id, self.degraded_threshold())
+    }
+
+    fn degraded_threshold(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.args.service_degraded_minutes as i64)
+    }
+
+    /// 启动服务可用性监控
+    pub fn start_monitor(&'static self) {
+        schedule::schedule(
+            "service-monitor",
+            "检查服务的健康实例数是否低于期望实例数",
+            TaskScope::Node,
+            MONITOR_INTERVAL,
+            move || self.check_services(),
+        );
+    }
+
+    /// 启动所有命名空间下实例的心跳检查和清理
+    pub fn start_instance_check(&'static self) {
+        schedule::schedule(
+            "heartbeat-check",
+            "检查实例心跳，将心跳超时的实例标记为不健康",
+            TaskScope::Node,
+            HEARTBEAT_CHECK_INTERVAL,
+            move || async move {
+                self.discoveries
+                    .iter()
+                    .for_each(|discovery| discovery.check_heartbeats(HEARTBEAT_TIMEOUT));
+                Ok(())
+            },
+        );
+        schedule::schedule(
+            "instance-cleanup",
+            "清理心跳超时的实例",
+            TaskScope::Node,
+            CLEANUP_INTERVAL,
+            move || async move {
+                self.discoveries
+                    .iter()
+                    .for_each(|discovery| discovery.cleanup());
+                Ok(())
+            },
+        );
+    }
+
+    /// 启动主动健康检查，只由Leader节点探测，结果作为心跳同步到集群
+    pub fn start_health_check(&'static self) {
+        schedule::schedule(
+            "health-check",
+            "探测开启了主动健康检查的实例，探测成功视为一次心跳",
+            TaskScope::Cluster,
+            HEALTH_CHECK_INTERVAL,
+            move || self.check_health(),
+        );
+    }
+
+    /// 探测所有命名空间下开启了主动健康检查的实例
+    async fn check_health(&self) -> anyhow::Result<()> {
+        let namespace_ids = self
+            .discoveries
+            .iter()
+            .map(|discovery| discovery.key().clone())
+            .collect::<Vec<_>>();
+        for namespace_id in namespace_ids {
+            let Some(discovery) = self.discoveries.get(&namespace_id).map(|d| d.clone()) else {
+                continue;
+            };
+            // 实例未设置的健康检查设置从服务的元数据中继承
+            let service_metas: Vec<(String, Option<String>)> =
+                sqlx::query_as("select service_id, meta from service where namespace_id = ?")
+                    .bind(&namespace_id)
+                    .fetch_all(DbPool::get())
+                    .await?;
+            let service_metas = service_metas
+                .into_iter()
+                .filter_map(|(service_id, meta)| {
+                    let meta: HashMap<String, String> = serde_json::from_str(&meta?).ok()?;
+                    Some((service_id, meta))
+                })
+                .collect::<HashMap<_, _>>();
+
+            let mut probes = tokio::task::JoinSet::new();
+            for service in discovery.services().iter() {
+                for instance in service.value() {
+                    if *instance.status() == InstanceStatus::Offline {
+                        continue;
+                    }
+                    let mut meta = instance.meta.clone();
+                    if let Some(service_meta) = service_metas.get(&instance.service_id) {
+                        for (key, value) in service_meta {
+                            meta.entry(key.clone()).or_insert_with(|| value.clone());
+                        }
+                    }
+                    let Some(health_check) = HealthCheck::from_meta(&meta) else {
+                        continue;
+                    };
+                    let (service_id, instance_id) =
+                        (instance.service_id.clone(), instance.id.clone());
+                    let (ip, port) = (instance.ip.clone(), instance.port);
+                    probes.spawn(async move {
+                        match health_check.probe(&ip, port).await {
+                            Ok(()) => Some((service_id, instance_id)),
+                            Err(e) => {
+                                log::debug!(
+                                    "health check of instance {} of service {} failed: {}",
+                                    instance_id,
+                                    service_id,
+                                    e
+                                );
+                                None
+                            }
+                        }
+                    });
+                }
+            }
+            let healthy = probes
+                .join_all()
+                .await
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            for instances in healthy.chunks(MAX_HEARTBEAT_BATCH) {
+                self.heartbeat_batch_and_sync(&namespace_id, instances.to_vec())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 比较设置了期望实例数的服务的健康实例数
+    async fn check_services(&self) -> anyhow::Result<()> {
+        let services: Vec<(String, String, u32)> = sqlx::query_as(
+            "select namespace_id, service_id, expected_instances from service where expected_instances is not null",
+        )
+        .fetch_all(DbPool::get())
+        .await?;
+
+        // 仅Leader节点发送通知，避免重复
+        let app = get_app();
+        let is_leader = app.raft.metrics().borrow().current_leader == Some(app.id);
+        let threshold = self.degraded_threshold();
+
+        for (namespace_id, service_id, expected_instances) in services.iter() {
+            let up_instances = match self.discoveries.get(namespace_id) {
+                Some(discovery) => discovery.get_available_service_instances(service_id)?.len(),
+                None => 0,
+            };
+            if let Some(event) = self.monitor.update(
+                namespace_id,
+                service_id,
+                *expected_instances,
+                up_instances,
+                threshold,
+                is_leader,
+            ) {
+                log::warn!("service availability changed: {:?}", event);
+                webhook::send(event);
+            }
+        }
+
+        // 移除已删除或取消监控的服务
+        self.monitor.retain(|(namespace_id, service_id)| {
+            services
+                .iter()
+                .any(|(ns, id, _)| ns == namespace_id && id == service_id)
+        });
+        Ok(())
+    }
+
     /// 注销服务，并同步到集群
     pub async fn deregister_service_and_sync(
         &self,
@@ -240,6 +695,11 @@ impl DiscoveryManager {
         service_id: &str,
     ) -> anyhow::Result<()> {
         let _ = self.try_get_discovery(namespace_id).await?;
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
 
         self.sync(RaftRequest::DeregisterService {
             namespace_id: namespace_id.to_string(),
@@ -268,19 +728,29 @@ impl DiscoveryManager {
     }
 
     /// 注册服务实例，并同步到集群
+    ///
+    /// 同步前按冲突策略检查本节点的实例列表，返回注册后的实例
     pub async fn register_service_instance_and_sync(
         &self,
         namespace_id: &str,
         instance: ServiceInstance,
+        policy: ConflictPolicy,
     ) -> anyhow::Result<ServiceInstance> {
-        let _ = self.try_get_discovery(namespace_id).await?;
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        let registered = discovery.check_conflict(instance.clone(), policy)?;
 
         self.sync(RaftRequest::RegisterServiceInstance {
             namespace_id: namespace_id.to_string(),
-            instance: instance.clone(),
+            instance,
+            policy,
         })
         .await?;
-        Ok(instance)
+        Ok(registered)
     }
 
     /// 注册服务实例
@@ -291,10 +761,11 @@ impl DiscoveryManager {
         &self,
         namespace_id: &str,
         instance: ServiceInstance,
+        policy: ConflictPolicy,
     ) -> anyhow::Result<ServiceInstance> {
         let discovery = self.try_get_discovery(namespace_id).await?;
         // 注册实例，如果service_id不存在则自动注册service
-        let instance = discovery.register_instance(instance)?;
+        let instance = discovery.register_instance(instance, policy)?;
         // 持久化，如果已存在则更新
         self.upsert_service(namespace_id, &instance.service_id, None)
             .await?;
@@ -309,6 +780,11 @@ impl DiscoveryManager {
         instance_id: &str,
     ) -> anyhow::Result<()> {
         let _ = self.try_get_discovery(namespace_id).await?;
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
 
         self.sync(RaftRequest::DeregisterServiceInstance {
             namespace_id: namespace_id.to_string(),
@@ -330,6 +806,53 @@ impl DiscoveryManager {
         Ok(())
     }
 
+    /// 更新服务实例的元数据并同步到集群，返回更新后的实例
+    ///
+    /// meta中的key覆盖原有的值，remove_keys中的key被删除，其余元数据保持不变
+    pub async fn update_instance_meta_and_sync(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        instance_id: &str,
+        meta: HashMap<String, String>,
+        remove_keys: Vec<String>,
+    ) -> anyhow::Result<ServiceInstance> {
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        if discovery.get_instance(service_id, instance_id).is_none() {
+            bail!("instance {} of service {} not found", instance_id, service_id);
+        }
+
+        self.sync(RaftRequest::UpdateServiceInstanceMeta {
+            namespace_id: namespace_id.to_string(),
+            service_id: service_id.to_string(),
+            instance_id: instance_id.to_string(),
+            meta,
+            remove_keys,
+        })
+        .await?;
+        discovery
+            .get_instance(service_id, instance_id)
+            .with_context(|| format!("instance {} of service {} not found", instance_id, service_id))
+    }
+
+    pub async fn update_instance_meta(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        instance_id: &str,
+        meta: HashMap<String, String>,
+        remove_keys: Vec<String>,
+    ) -> anyhow::Result<()> {
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        discovery.update_instance_meta(service_id, instance_id, meta, remove_keys);
+        Ok(())
+    }
+
     /// 获取服务实例
     pub async fn get_instances(
         &self,
@@ -342,24 +865,159 @@ impl DiscoveryManager {
     }
 
     /// 获取可用服务实例
+    ///
+    /// 实例未设置的元数据（如默认权重、可用区）从服务的元数据中继承
     pub async fn get_available_instances(
         &self,
         namespace_id: &str,
         service_id: &str,
     ) -> anyhow::Result<Vec<ServiceInstance>> {
         let discovery = self.try_get_discovery(namespace_id).await?;
-        let instances = discovery.get_available_service_instances(service_id)?;
+        let mut instances = discovery.get_available_service_instances(service_id)?;
+
+        let (meta, warmup_seconds, active_set): (Option<String>, Option<u32>, Option<String>) =
+            sqlx::query_as(
+                "select meta, warmup_seconds, active_set from service where namespace_id = ? and service_id = ?",
+            )
+            .bind(namespace_id)
+            .bind(service_id)
+            .fetch_optional(DbPool::get())
+            .await?
+            .unwrap_or_default();
+
+        // 实例继承服务的元数据，实例自身设置的优先，需在按集合过滤和计算权重之前
+        let meta: HashMap<String, String> = meta
+            .and_then(|meta| serde_json::from_str(&meta).ok())
+            .unwrap_or_default();
+        if !meta.is_empty() {
+            for instance in instances.iter_mut() {
+                instance.inherit_meta(&meta);
+            }
+        }
+
+        // 仅返回当前生效集合的实例，未标记集合的实例不受影响
+        if let Some(active_set) = active_set {
+            instances.retain(|instance| instance.color().is_none_or(|color| color == active_set));
+        }
+
+        // 计算预热期内实例的有效权重
+        if let Some(warmup_seconds) = warmup_seconds {
+            let warmup = Duration::from_secs(warmup_seconds as u64);
+            for instance in instances.iter_mut() {
+                instance.effective_weight = Some(instance.warmup_weight(warmup));
+            }
+        }
         Ok(instances)
     }
 
+    /// 监听服务可用实例的变化
+    ///
+    /// `version`与当前实例列表的版本不一致时立即返回当前实例，否则每隔[`WATCH_CHECK_INTERVAL`]检查一次，
+    /// 在`timeout`内没有变化时返回None。心跳超时导致的状态变化由各节点本地判定，没有变更事件，因此按间隔检查
+    pub async fn watch_available_instances(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        version: &str,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<InstancesSnapshot>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let snapshot = InstancesSnapshot::new(
+                self.get_available_instances(namespace_id, service_id)
+                    .await?,
+            );
+            if snapshot.version != version {
+                return Ok(Some(snapshot));
+            }
+            if tokio::time::Instant::now() + WATCH_CHECK_INTERVAL > deadline {
+                return Ok(None);
+            }
+            tokio::time::sleep(WATCH_CHECK_INTERVAL).await;
+        }
+    }
+
+    /// 命名空间下的服务实例数量
+    pub fn count_instances(&self, namespace_id: &str) -> usize {
+        self.discoveries
+            .get(namespace_id)
+            .map(|discovery| discovery.instance_count())
+            .unwrap_or_default()
+    }
+
+    /// 模拟驱逐
+    ///
+    /// 按当前心跳检查配置，返回此刻将被标记为Sick、Down或被清理的实例，不修改实例状态
+    pub async fn simulate_eviction(
+        &self,
+        namespace_id: &str,
+    ) -> anyhow::Result<EvictionSimulation> {
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        Ok(EvictionSimulation {
+            heartbeat_check_interval_secs: HEARTBEAT_CHECK_INTERVAL.as_secs(),
+            heartbeat_timeout_secs: HEARTBEAT_TIMEOUT.as_secs(),
+            cleanup_interval_secs: CLEANUP_INTERVAL.as_secs(),
+            instances: discovery.simulate_eviction(HEARTBEAT_TIMEOUT),
+        })
+    }
+
+    /// 导出命名空间下所有服务实例的状态
+    pub async fn export_state(&self, namespace_id: &str) -> anyhow::Result<DiscoveryState> {
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        Ok(discovery.export_state())
+    }
+
+    /// 导入服务实例的状态，替换命名空间下的所有服务实例，并同步到集群
+    pub async fn import_state_and_sync(
+        &self,
+        namespace_id: &str,
+        state: DiscoveryState,
+    ) -> anyhow::Result<()> {
+        let _ = self.try_get_discovery(namespace_id).await?;
+        self.sync(RaftRequest::ImportDiscoveryState {
+            namespace_id: namespace_id.to_string(),
+            state,
+        })
+        .await
+    }
+
+    /// 导入服务实例的状态
+    ///
+    /// 与注册实例一样，不存在的服务会持久化到数据库
+    pub async fn import_state(
+        &self,
+        namespace_id: &str,
+        state: DiscoveryState,
+    ) -> anyhow::Result<()> {
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        let service_ids = state.services.keys().cloned().collect::<Vec<_>>();
+        discovery.import_state(state)?;
+        for service_id in service_ids {
+            self.upsert_service(namespace_id, &service_id, None).await?;
+        }
+        Ok(())
+    }
+
     /// 更新心跳，并同步到集群
+    ///
+    /// 同时返回实例在本次心跳前的驱逐通知
     pub async fn heartbeat_and_sync(
         &self,
         namespace_id: &str,
         service_id: &str,
         instance_id: &str,
-    ) -> anyhow::Result<HeartbeatResult> {
-        let _ = self.try_get_discovery(namespace_id).await?;
+    ) -> anyhow::Result<(HeartbeatResult, Option<EvictionNotice>)> {
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        let notice = discovery.eviction_notice(service_id, instance_id);
+        if let Some(notice) = &notice {
+            log::warn!(
+                "instance {} of service {} was evicted ({}), last heartbeat at {}",
+                instance_id,
+                service_id,
+                notice.reason,
+                notice.last_heartbeat
+            );
+        }
 
         let res = self
             .heartbeat(namespace_id, service_id, instance_id)
@@ -372,7 +1030,52 @@ impl DiscoveryManager {
         })
         .await?;
 
-        Ok(res)
+        Ok((res, notice))
+    }
+
+    /// 批量更新心跳，并作为一条请求同步到集群
+    ///
+    /// 用于sidecar代理、网关等管理大量实例的场景，instances为(service_id, instance_id)列表
+    pub async fn heartbeat_batch_and_sync(
+        &self,
+        namespace_id: &str,
+        instances: Vec<(String, String)>,
+    ) -> anyhow::Result<Vec<HeartbeatBatchResult>> {
+        if instances.len() > MAX_HEARTBEAT_BATCH {
+            bail!(
+                "too many instances in one heartbeat batch, max: {}",
+                MAX_HEARTBEAT_BATCH
+            );
+        }
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        let mut results = Vec::with_capacity(instances.len());
+        for (service_id, instance_id) in instances.iter() {
+            let eviction = discovery.eviction_notice(service_id, instance_id);
+            if let Some(notice) = &eviction {
+                log::warn!(
+                    "instance {} of service {} was evicted ({}), last heartbeat at {}",
+                    instance_id,
+                    service_id,
+                    notice.reason,
+                    notice.last_heartbeat
+                );
+            }
+            let result = discovery.heartbeat(service_id, instance_id)?;
+            results.push(HeartbeatBatchResult {
+                service_id: service_id.clone(),
+                instance_id: instance_id.clone(),
+                result,
+                eviction,
+            });
+        }
+
+        self.sync(RaftRequest::HeartbeatBatch {
+            namespace_id: namespace_id.to_string(),
+            instances,
+        })
+        .await?;
+
+        Ok(results)
     }
     /// 更新心跳
     pub async fn heartbeat(
@@ -386,12 +1089,78 @@ impl DiscoveryManager {
         Ok(hr)
     }
 
+    /// 手动修改实例的心跳状态，并同步到集群
+    ///
+    /// 修改记录在审计日志中，包括操作人、原因以及修改前后的状态
+    pub async fn override_heartbeat_and_sync(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        instance_id: &str,
+        action: HeartbeatOverride,
+        operator: &str,
+        reason: Option<&str>,
+    ) -> anyhow::Result<HeartbeatOverrideResult> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
+        let result = self
+            .override_heartbeat(namespace_id, service_id, instance_id, action)
+            .await?;
+
+        self.sync(RaftRequest::OverrideHeartbeat {
+            namespace_id: namespace_id.to_string(),
+            service_id: service_id.to_string(),
+            instance_id: instance_id.to_string(),
+            action,
+        })
+        .await?;
+
+        log::warn!(
+            "[audit] heartbeat state of instance {} of service {} in namespace {} overridden by {}: {:?}, status {:?} -> {:?}, lost heartbeats {} -> 0, reason: {}",
+            instance_id,
+            service_id,
+            namespace_id,
+            operator,
+            action,
+            result.previous_status,
+            result.status,
+            result.previous_lost_heartbeats,
+            reason.unwrap_or("-")
+        );
+        Ok(result)
+    }
+
+    /// 手动修改实例的心跳状态
+    pub async fn override_heartbeat(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        instance_id: &str,
+        action: HeartbeatOverride,
+    ) -> anyhow::Result<HeartbeatOverrideResult> {
+        let discovery = self.try_get_discovery(namespace_id).await?;
+        discovery.override_heartbeat(service_id, instance_id, action)
+    }
+
     pub async fn offline(&self, namespace_id: &str, service_id: &str, instance_id: &str)-> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
         let discovery = self.try_get_discovery(namespace_id).await?;
         discovery.offline(service_id, instance_id)?;
         Ok(())
     }
     pub async fn online(&self, namespace_id: &str, service_id: &str, instance_id: &str)-> anyhow::Result<()> {
+        get_app()
+            .namespace_app
+            .manager
+            .check_not_suspended(namespace_id)
+            .await?;
         let discovery = self.try_get_discovery(namespace_id).await?;
         discovery.online(service_id, instance_id)?;
         Ok(())
@@ -0,0 +1,143 @@
+//! 服务可用性监控
+//!
+//! 可以为服务设置期望实例数，监控任务定期比较服务的健康实例数，
+//! 持续低于期望实例数超过`--service-degraded-minutes`分钟的服务视为降级，
+//! 可通过降级服务接口查询，并由Leader节点发送Webhook通知。
+
+use crate::webhook::WebhookEvent;
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 降级服务
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DegradedService {
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 服务ID
+    pub service_id: String,
+    /// 期望实例数
+    pub expected_instances: u32,
+    /// 健康实例数
+    pub up_instances: usize,
+    /// 健康实例数开始低于期望实例数的时间
+    pub degraded_since: DateTime<Local>,
+}
+
+#[derive(Debug)]
+struct Shortfall {
+    service: DegradedService,
+    /// 是否已发送降级通知
+    alerted: bool,
+}
+
+/// 服务可用性监控状态
+#[derive(Debug, Default)]
+pub struct ServiceMonitor {
+    /// 健康实例数低于期望实例数的服务，key为(命名空间ID, 服务ID)
+    shortfalls: DashMap<(String, String), Shortfall>,
+}
+
+impl ServiceMonitor {
+    /// 更新服务的健康实例数
+    ///
+    /// - threshold: 持续低于期望实例数多久后视为降级
+    /// - alert: 是否发送通知，仅Leader节点发送，避免重复通知
+    ///
+    /// 返回需要发送的通知
+    pub fn update(
+        &self,
+        namespace_id: &str,
+        service_id: &str,
+        expected_instances: u32,
+        up_instances: usize,
+        threshold: chrono::Duration,
+        alert: bool,
+    ) -> Option<WebhookEvent> {
+        let key = (namespace_id.to_string(), service_id.to_string());
+        if up_instances >= expected_instances as usize {
+            return match self.shortfalls.remove(&key) {
+                Some((_, mut shortfall)) if shortfall.alerted && alert => {
+                    shortfall.service.up_instances = up_instances;
+                    Some(WebhookEvent::ServiceRecovered(shortfall.service))
+                }
+                _ => None,
+            };
+        }
+
+        let now = Local::now();
+        let mut shortfall = self.shortfalls.entry(key).or_insert_with(|| Shortfall {
+            service: DegradedService {
+                namespace_id: namespace_id.to_string(),
+                service_id: service_id.to_string(),
+                expected_instances,
+                up_instances,
+                degraded_since: now,
+            },
+            alerted: false,
+        });
+        shortfall.service.expected_instances = expected_instances;
+        shortfall.service.up_instances = up_instances;
+        if alert && !shortfall.alerted && now - shortfall.service.degraded_since >= threshold {
+            shortfall.alerted = true;
+            return Some(WebhookEvent::ServiceDegraded(shortfall.service.clone()));
+        }
+        None
+    }
+
+    /// 移除不再需要监控的服务
+    pub fn retain(&self, f: impl Fn(&(String, String)) -> bool) {
+        self.shortfalls.retain(|key, _| f(key));
+    }
+
+    /// 降级服务列表
+    ///
+    /// - namespace_id: 为空时返回所有命名空间的降级服务
+    /// - threshold: 持续低于期望实例数多久后视为降级
+    pub fn list(
+        &self,
+        namespace_id: Option<&str>,
+        threshold: chrono::Duration,
+    ) -> Vec<DegradedService> {
+        let deadline = Local::now() - threshold;
+        let mut list = self
+            .shortfalls
+            .iter()
+            .filter(|s| namespace_id.is_none_or(|ns| s.service.namespace_id == ns))
+            .filter(|s| s.service.degraded_since <= deadline)
+            .map(|s| s.service.clone())
+            .collect::<Vec<_>>();
+        list.sort_by_key(|s| s.degraded_since);
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let monitor = ServiceMonitor::default();
+        let zero = chrono::Duration::zero();
+
+        // 非Leader节点只记录状态，不通知
+        assert!(monitor.update("public", "svc", 2, 1, zero, false).is_none());
+        assert_eq!(monitor.list(None, zero).len(), 1);
+
+        // Leader节点通知一次
+        assert!(matches!(
+            monitor.update("public", "svc", 2, 1, zero, true),
+            Some(WebhookEvent::ServiceDegraded(_))
+        ));
+        assert!(monitor.update("public", "svc", 2, 0, zero, true).is_none());
+
+        // 恢复后通知
+        assert!(matches!(
+            monitor.update("public", "svc", 2, 2, zero, true),
+            Some(WebhookEvent::ServiceRecovered(_))
+        ));
+        assert!(monitor.list(None, zero).is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+//! Prometheus HTTP服务发现接口
+//!
+//! 按Prometheus的`http_sd_config`格式返回抓取目标，Prometheus配置如下即可自动抓取已注册的服务实例：
+//! ```yaml
+//! scrape_configs:
+//!   - job_name: conreg
+//!     http_sd_configs:
+//!       - url: http://<conreg>/api/integrations/prometheus/http-sd?namespace_id=public
+//!         http_headers:
+//!           X-NS-Token:
+//!             values: ["<token>"]
+//! ```
+//!
+//! 仅返回可用实例，每个实例为一个目标组，实例元数据作为`__meta_conreg_meta_<key>`标签，
+//! 可通过`relabel_configs`转换为实际的标签。
+
+use crate::app::get_app;
+use crate::auth::NamespaceAuth;
+use crate::discovery::ServiceInstance;
+use crate::openapi::ApiDoc;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::log;
+
+/// 标签前缀，Prometheus会在relabel之后丢弃`__`开头的标签
+const LABEL_PREFIX: &str = "__meta_conreg_";
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![http_sd]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("http_sd", "Prometheus HTTP服务发现，返回可用实例的抓取目标")
+            .namespace_auth()
+            .optional(&["service_id"]),
+    ]
+}
+
+/// 抓取目标组
+#[derive(Debug, Serialize)]
+pub struct TargetGroup {
+    targets: Vec<String>,
+    labels: BTreeMap<String, String>,
+}
+
+/// 获取抓取目标，未指定服务时返回命名空间下全部服务的可用实例
+#[get("/http-sd?<namespace_id>&<service_id>")]
+async fn http_sd(
+    namespace_id: &str,
+    service_id: Option<&str>,
+    _auth: NamespaceAuth,
+) -> Result<Json<Vec<TargetGroup>>, Status> {
+    let manager = &get_app().discovery_app.manager;
+    let service_ids = match service_id {
+        Some(service_id) => vec![service_id.to_string()],
+        None => manager
+            .list_services(namespace_id, 1, 10000)
+            .await
+            .map_err(|e| {
+                log::error!("list services error: {}", e);
+                Status::InternalServerError
+            })?
+            .1
+            .into_iter()
+            .map(|service| service.service_id)
+            .collect(),
+    };
+
+    let mut groups = vec![];
+    for service_id in service_ids {
+        let instances = manager
+            .get_available_instances(namespace_id, &service_id)
+            .await
+            .map_err(|e| {
+                log::error!("get instances of service {} error: {}", service_id, e);
+                Status::InternalServerError
+            })?;
+        groups.extend(
+            instances
+                .iter()
+                .map(|instance| target_group(namespace_id, instance)),
+        );
+    }
+    Ok(Json(groups))
+}
+
+fn target_group(namespace_id: &str, instance: &ServiceInstance) -> TargetGroup {
+    let mut labels = BTreeMap::from([
+        (label("namespace"), namespace_id.to_string()),
+        (label("service"), instance.service_id.clone()),
+        (label("instance_id"), instance.id.clone()),
+    ]);
+    for (key, value) in &instance.meta {
+        labels.insert(label(&format!("meta_{}", key)), value.clone());
+    }
+    TargetGroup {
+        targets: vec![format!("{}:{}", instance.ip, instance.port)],
+        labels,
+    }
+}
+
+/// 生成标签名，Prometheus的标签名只能包含字母、数字和下划线
+fn label(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!("{}{}", LABEL_PREFIX, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_target_group() {
+        let meta = HashMap::from([("metrics.path".to_string(), "/metrics".to_string())]);
+        let instance = ServiceInstance::new("svc", "10.0.0.1", 8080, meta);
+        let group = target_group("public", &instance);
+        assert_eq!(group.targets, vec!["10.0.0.1:8080"]);
+        assert_eq!(group.labels["__meta_conreg_service"], "svc");
+        assert_eq!(group.labels["__meta_conreg_meta_metrics_path"], "/metrics");
+    }
+}
@@ -0,0 +1,82 @@
+//! 服务端节点自注册
+//!
+//! 每个节点启动后将自身注册到保留命名空间`system`下的`conreg-server`服务，并定时发送心跳，
+//! 元数据包含节点ID和当前的Raft角色。
+//! 客户端可通过服务发现接口动态获取服务端地址列表，监控也可以通过注册中心自身的API观察节点状态。
+
+use crate::Args;
+use crate::app::get_app;
+use crate::discovery::discovery::{ConflictPolicy, HeartbeatResult, ServiceInstance};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::log;
+
+/// 服务端节点所在的保留命名空间
+pub const SYSTEM_NAMESPACE: &str = "system";
+/// 服务端节点的服务ID
+pub const SERVICE_ID: &str = "conreg-server";
+/// 心跳间隔，需小于心跳超时时间
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// 启动自注册任务
+pub fn start(args: &Args) {
+    let ip = args.address.clone();
+    let port = args.port;
+    tokio::spawn(async move {
+        // 已注册的Raft角色，角色变化时重新注册以更新元数据
+        let mut registered_role: Option<String> = None;
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = register_or_heartbeat(&ip, port, &mut registered_role).await {
+                log::warn!("self register error: {}", e);
+            }
+        }
+    });
+}
+
+async fn register_or_heartbeat(
+    ip: &str,
+    port: u16,
+    registered_role: &mut Option<String>,
+) -> anyhow::Result<()> {
+    let app = get_app();
+    let metrics = app.raft.metrics().borrow().clone();
+    // 集群还没有Leader时无法写入，等待选举完成
+    if metrics.current_leader.is_none() {
+        return Ok(());
+    }
+    let role = format!("{:?}", metrics.state);
+    let manager = &app.discovery_app.manager;
+
+    if registered_role.as_deref() != Some(role.as_str()) {
+        let meta = HashMap::from([
+            ("node_id".to_string(), app.id.to_string()),
+            ("raft_role".to_string(), role.clone()),
+        ]);
+        manager
+            .register_service_instance_and_sync(
+                SYSTEM_NAMESPACE,
+                ServiceInstance::new(SERVICE_ID, ip, port, meta),
+                ConflictPolicy::Replace,
+            )
+            .await?;
+        log::info!("self registered as {}, raft role: {}", SERVICE_ID, role);
+        *registered_role = Some(role);
+        // 注册请求由Raft异步应用，下个周期再发送心跳
+        return Ok(());
+    }
+
+    let instance_id = ServiceInstance::generate_id(ip, port);
+    let (result, _) = manager
+        .heartbeat_and_sync(SYSTEM_NAMESPACE, SERVICE_ID, &instance_id)
+        .await?;
+    match result {
+        HeartbeatResult::Ok => {}
+        // 实例已被清理，下次重新注册
+        HeartbeatResult::NoInstanceFound => *registered_role = None,
+        // 手动下线的实例保持下线状态
+        HeartbeatResult::Rejected => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! UDP心跳
+//!
+//! 通过启动参数`--enable-udp-heartbeat`开启，在HTTP服务的地址和端口上监听UDP，
+//! 供大规模集群中的客户端以单个数据报发送心跳，减少每次心跳建立HTTP请求的开销。注册、注销等仍使用HTTP。
+//!
+//! 请求和响应均为JSON：
+//! - 请求：[`UdpHeartbeatReq`]，命名空间开启认证时需要使用命名空间Token对请求签名，
+//!   签名为`{namespace_id}\n{service_id}\n{instance_id}\n{timestamp}`的HMAC-SHA256，十六进制编码
+//! - 响应：与HTTP心跳接口相同的[`Res`]，数据为带驱逐通知的心跳结果
+//!
+//! 时间戳与服务端时间相差超过[`MAX_CLOCK_SKEW`]的请求被拒绝，避免请求被截获后长期重放。
+
+use crate::app::get_app;
+use crate::discovery::discovery::HeartbeatResponse;
+use crate::protocol::res::Res;
+use anyhow::{Context, bail};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tracing::log;
+
+/// 请求时间戳与服务端时间允许的最大偏差，毫秒
+const MAX_CLOCK_SKEW: i64 = 60_000;
+
+/// 数据报的最大长度，超出的部分被截断并导致解析失败
+const MAX_DATAGRAM_SIZE: usize = 2048;
+
+/// UDP心跳请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UdpHeartbeatReq {
+    pub namespace_id: String,
+    pub service_id: String,
+    pub instance_id: String,
+    /// 发送时间，毫秒时间戳
+    pub timestamp: i64,
+    /// 签名，命名空间未开启认证时可以为空
+    pub signature: Option<String>,
+}
+
+impl UdpHeartbeatReq {
+    /// 参与签名的内容
+    fn payload(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.namespace_id, self.service_id, self.instance_id, self.timestamp
+        )
+    }
+}
+
+/// 使用命名空间Token计算签名
+fn sign(auth_token: &str, payload: &str) -> String {
+    // HMAC支持任意长度的密钥
+    let mut mac = Hmac::<Sha256>::new_from_slice(auth_token.as_bytes()).unwrap();
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// 启动UDP心跳监听
+///
+/// 在当前线程绑定端口，端口被占用时返回错误，监听在后台任务中运行
+pub async fn start(address: &str, port: u16) -> anyhow::Result<()> {
+    let addr = SocketAddr::new(IpAddr::from_str(address)?, port);
+    let socket = UdpSocket::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind UDP heartbeat port {}", port))?;
+    log::info!("UDP heartbeat listening on {}", addr);
+    let socket = Arc::new(socket);
+    tokio::spawn(async move {
+        let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+        loop {
+            let (len, peer) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    log::warn!("receive UDP heartbeat error: {}", e);
+                    continue;
+                }
+            };
+            let datagram = buf[..len].to_vec();
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                let res = match handle(&datagram).await {
+                    Ok(res) => Res::success(res),
+                    Err(e) => {
+                        log::debug!("UDP heartbeat from {} rejected: {}", peer, e);
+                        Res::from_error(&e)
+                    }
+                };
+                match serde_json::to_vec(&res) {
+                    Ok(res) => {
+                        if let Err(e) = socket.send_to(&res, peer).await {
+                            log::debug!("send UDP heartbeat response to {} error: {}", peer, e);
+                        }
+                    }
+                    Err(e) => log::error!("serialize UDP heartbeat response error: {}", e),
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// 校验并处理一个心跳请求
+async fn handle(datagram: &[u8]) -> anyhow::Result<HeartbeatResponse> {
+    let req: UdpHeartbeatReq =
+        serde_json::from_slice(datagram).context("Invalid heartbeat datagram")?;
+    if (Utc::now().timestamp_millis() - req.timestamp).abs() > MAX_CLOCK_SKEW {
+        bail!("Heartbeat timestamp expired");
+    }
+    let namespace = get_app()
+        .namespace_app
+        .manager
+        .get_namespace(&req.namespace_id)
+        .await?;
+    if let Some(namespace) = namespace
+        && namespace.is_auth
+    {
+        let expected = sign(
+            namespace.auth_token.as_deref().unwrap_or_default(),
+            &req.payload(),
+        );
+        if req.signature.as_deref() != Some(expected.as_str()) {
+            bail!("No Permission");
+        }
+    }
+    let (result, eviction) = get_app()
+        .discovery_app
+        .manager
+        .heartbeat_and_sync(&req.namespace_id, &req.service_id, &req.instance_id)
+        .await?;
+    Ok(HeartbeatResponse::WithNotice { result, eviction })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let req = UdpHeartbeatReq {
+            namespace_id: "public".to_string(),
+            service_id: "app".to_string(),
+            instance_id: "1".to_string(),
+            timestamp: 1700000000000,
+            signature: None,
+        };
+        assert_eq!(req.payload(), "public\napp\n1\n1700000000000");
+        let signature = sign("token", &req.payload());
+        assert_eq!(signature.len(), 64);
+        assert_eq!(signature, sign("token", &req.payload()));
+        assert_ne!(signature, sign("other", &req.payload()));
+        assert_eq!(
+            signature,
+            "b766a82eae42c9d07d579236cc166622f6eb0714176d9f6a2c3e9c1f0c9cc20b"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Envoy xDS接口（实验性）
+//!
+//! 将conreg的服务和实例转换为Envoy的集群（CDS）和端点（EDS），Sidecar代理可以直接使用conreg作为服务发现源。
+//! 需要通过`--enable-xds`启用，支持两种传输方式：
+//! - gRPC：在gRPC端口（HTTP端口 + 1000）上提供ADS、CDS和EDS服务，资源变化时主动推送，见`crate::grpc::xds`
+//! - REST-JSON：`POST /v3/discovery:clusters`和`POST /v3/discovery:endpoints`，由Envoy轮询获取
+//!
+//! 每个服务对应一个EDS类型的集群，集群的端点为服务的实例及健康状态。
+//!
+//! 命名空间和Token通过节点元数据传递，gRPC也可以通过`x-ns-token`元数据传递Token：
+//! ```yaml
+//! node:
+//!   id: sidecar-1
+//!   cluster: demo
+//!   metadata:
+//!     namespace_id: public
+//!     ns_token: <token>
+//!     # Envoy中指向conreg的集群名，生成的集群通过该集群获取端点，默认为conreg
+//!     xds_cluster: conreg
+//! dynamic_resources:
+//!   ads_config:
+//!     api_type: GRPC
+//!     transport_api_version: V3
+//!     grpc_services:
+//!       - envoy_grpc:
+//!           cluster_name: conreg
+//!   cds_config:
+//!     resource_api_version: V3
+//!     ads: {}
+//! ```
+//! `conreg`集群需要开启HTTP/2并指向conreg的gRPC端口。使用REST-JSON时，`conreg`集群指向HTTP端口，`cds_config`改为：
+//! ```yaml
+//!   cds_config:
+//!     resource_api_version: V3
+//!     api_config_source:
+//!       api_type: REST
+//!       transport_api_version: V3
+//!       cluster_names: [conreg]
+//!       refresh_delay: 5s
+//! ```
+//!
+//! 响应的版本号为资源内容的摘要，REST请求中的`version_info`与之相同时返回`304 Not Modified`。
+
+use crate::app::get_app;
+use crate::discovery::ServiceInstance;
+use crate::discovery::discovery::InstanceStatus;
+use anyhow::Context;
+use conreg_grpc::envoy::config::core::v3::HealthStatus;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::log;
+
+/// 默认命名空间
+pub(crate) const DEFAULT_NAMESPACE: &str = "public";
+/// Envoy中指向conreg的默认集群名
+pub(crate) const DEFAULT_XDS_CLUSTER: &str = "conreg";
+/// 生成的集群从conreg获取端点的轮询间隔
+const REFRESH_DELAY: &str = "5s";
+
+pub(crate) const CLUSTER_TYPE: &str = "type.googleapis.com/envoy.config.cluster.v3.Cluster";
+pub(crate) const ENDPOINT_TYPE: &str =
+    "type.googleapis.com/envoy.config.endpoint.v3.ClusterLoadAssignment";
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![clusters, endpoints]
+}
+
+/// xDS请求，仅包含用到的字段
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryRequest {
+    version_info: String,
+    node: Node,
+    resource_names: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Node {
+    id: String,
+    metadata: NodeMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct NodeMetadata {
+    namespace_id: Option<String>,
+    ns_token: Option<String>,
+    xds_cluster: Option<String>,
+}
+
+/// xDS响应
+#[derive(Debug, Serialize)]
+pub struct DiscoveryResponse {
+    version_info: String,
+    resources: Vec<Value>,
+    type_url: &'static str,
+    nonce: String,
+}
+
+/// 集群发现
+#[post("/discovery:clusters", data = "<req>")]
+async fn clusters(req: Json<DiscoveryRequest>) -> Result<Json<DiscoveryResponse>, Status> {
+    let namespace_id = auth(&req.node).await?;
+    let xds_cluster = req
+        .node
+        .metadata
+        .xds_cluster
+        .as_deref()
+        .unwrap_or(DEFAULT_XDS_CLUSTER);
+    let resources = list_service_ids(&namespace_id, &req.resource_names)
+        .await
+        .map_err(|e| {
+            log::error!("list services error: {}", e);
+            Status::InternalServerError
+        })?
+        .iter()
+        .map(|service_id| cluster(service_id, xds_cluster))
+        .collect();
+    response(&req, CLUSTER_TYPE, resources)
+}
+
+/// 端点发现，未指定资源名时返回命名空间下全部服务的端点
+#[post("/discovery:endpoints", data = "<req>")]
+async fn endpoints(req: Json<DiscoveryRequest>) -> Result<Json<DiscoveryResponse>, Status> {
+    let namespace_id = auth(&req.node).await?;
+    let resources = list_service_instances(&namespace_id, &req.resource_names)
+        .await
+        .map_err(|e| {
+            log::error!("list service instances error: {}", e);
+            Status::InternalServerError
+        })?
+        .iter()
+        .map(|(service_id, instances)| load_assignment(service_id, instances))
+        .collect();
+    response(&req, ENDPOINT_TYPE, resources)
+}
+
+/// 命名空间下的服务ID，按ID排序，`resource_names`不为空时只返回其中的服务
+pub(crate) async fn list_service_ids(
+    namespace_id: &str,
+    resource_names: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let mut service_ids = get_app()
+        .discovery_app
+        .manager
+        .list_services(namespace_id, 1, 10000)
+        .await?
+        .1
+        .into_iter()
+        .map(|service| service.service_id)
+        .filter(|service_id| resource_names.is_empty() || resource_names.contains(service_id))
+        .collect::<Vec<_>>();
+    service_ids.sort();
+    Ok(service_ids)
+}
+
+/// 服务及其实例，按服务ID和实例ID排序，`resource_names`为空时返回命名空间下的全部服务
+pub(crate) async fn list_service_instances(
+    namespace_id: &str,
+    resource_names: &[String],
+) -> anyhow::Result<Vec<(String, Vec<ServiceInstance>)>> {
+    let mut service_ids = if resource_names.is_empty() {
+        list_service_ids(namespace_id, resource_names).await?
+    } else {
+        resource_names.to_vec()
+    };
+    service_ids.sort();
+    service_ids.dedup();
+    let manager = &get_app().discovery_app.manager;
+    let mut services = vec![];
+    for service_id in service_ids {
+        let mut instances = manager
+            .get_instances(namespace_id, &service_id)
+            .await
+            .with_context(|| format!("get instances of service {} error", service_id))?;
+        instances.sort_by(|a, b| a.id.cmp(&b.id));
+        services.push((service_id, instances));
+    }
+    Ok(services)
+}
+
+/// 校验节点元数据中的命名空间Token，返回命名空间ID
+async fn auth(node: &Node) -> Result<String, Status> {
+    let namespace_id = node
+        .metadata
+        .namespace_id
+        .as_deref()
+        .unwrap_or(DEFAULT_NAMESPACE);
+    match get_app()
+        .namespace_app
+        .manager
+        .auth(namespace_id, node.metadata.ns_token.as_deref())
+        .await
+    {
+        Ok(true) => Ok(namespace_id.to_string()),
+        Ok(false) => {
+            log::warn!("xds node {} has no permission to {}", node.id, namespace_id);
+            Err(Status::Unauthorized)
+        }
+        Err(e) => {
+            log::error!("auth namespace {} error: {}", namespace_id, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// 生成响应，资源未变化时返回304
+fn response(
+    req: &DiscoveryRequest,
+    type_url: &'static str,
+    resources: Vec<Value>,
+) -> Result<Json<DiscoveryResponse>, Status> {
+    let version = format!(
+        "{:x}",
+        md5::compute(Value::Array(resources.clone()).to_string())
+    );
+    if req.version_info == version {
+        return Err(Status::NotModified);
+    }
+    Ok(Json(DiscoveryResponse {
+        nonce: version.clone(),
+        version_info: version,
+        resources,
+        type_url,
+    }))
+}
+
+fn cluster(service_id: &str, xds_cluster: &str) -> Value {
+    json!({
+        "@type": CLUSTER_TYPE,
+        "name": service_id,
+        "type": "EDS",
+        "connect_timeout": "5s",
+        "eds_cluster_config": {
+            "service_name": service_id,
+            "eds_config": {
+                "resource_api_version": "V3",
+                "api_config_source": {
+                    "api_type": "REST",
+                    "transport_api_version": "V3",
+                    "cluster_names": [xds_cluster],
+                    "refresh_delay": REFRESH_DELAY,
+                },
+            },
+        },
+    })
+}
+
+fn load_assignment(service_id: &str, instances: &[ServiceInstance]) -> Value {
+    let lb_endpoints = instances
+        .iter()
+        .map(|instance| {
+            json!({
+                "endpoint": {
+                    "address": {
+                        "socket_address": {
+                            "address": instance.ip,
+                            "port_value": instance.port,
+                        },
+                    },
+                },
+                "health_status": health_status(instance.status()).as_str_name(),
+                "load_balancing_weight": instance.weight().max(1),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "@type": ENDPOINT_TYPE,
+        "cluster_name": service_id,
+        "endpoints": [{ "lb_endpoints": lb_endpoints }],
+    })
+}
+
+/// 实例状态对应的Envoy健康状态
+pub(crate) fn health_status(status: &InstanceStatus) -> HealthStatus {
+    match status {
+        InstanceStatus::Up => HealthStatus::Healthy,
+        InstanceStatus::Ready => HealthStatus::Unknown,
+        InstanceStatus::Sick(_) => HealthStatus::Degraded,
+        InstanceStatus::Down => HealthStatus::Unhealthy,
+        InstanceStatus::Offline => HealthStatus::Draining,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_response_version() {
+        let instance = ServiceInstance::new("svc", "10.0.0.1", 8080, HashMap::new());
+        let resources = vec![load_assignment("svc", &[instance])];
+        assert_eq!(
+            resources[0]["endpoints"][0]["lb_endpoints"][0]["health_status"],
+            "UNKNOWN"
+        );
+
+        let mut req = DiscoveryRequest::default();
+        let res = response(&req, ENDPOINT_TYPE, resources.clone()).unwrap();
+        req.version_info = res.version_info.clone();
+        assert_eq!(
+            response(&req, ENDPOINT_TYPE, resources).unwrap_err(),
+            Status::NotModified
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! 数据目录磁盘空间保护
+//!
+//! 定时检查数据目录所在磁盘的可用空间：
+//! - 低于启动参数`--disk-warn-free-mb`时记录警告日志
+//! - 低于启动参数`--disk-critical-free-mb`时进入只读模式，拒绝写入请求（错误码`DISK_READ_ONLY_CODE`），
+//!   避免磁盘写满导致sled/sqlite数据损坏
+//!
+//! 可用空间恢复后自动退出只读模式。
+//! 心跳和缓存写入（如登录Token）不受只读模式限制：前者否则会导致所有服务实例被判定为不健康，
+//! 后者否则会导致无法登录控制台处理故障。
+
+use crate::Args;
+use crate::protocol::res::{CodeError, DISK_READ_ONLY_CODE};
+use crate::raft::RaftRequest;
+use crate::schedule::{self, TaskScope};
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+use tracing::log;
+
+/// 检查间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+const MB: u64 = 1024 * 1024;
+
+static DISK_GUARD: OnceLock<DiskGuard> = OnceLock::new();
+
+/// 磁盘空间级别
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum DiskLevel {
+    /// 正常
+    Ok,
+    /// 可用空间不足，仅记录警告
+    Low,
+    /// 可用空间严重不足，已进入只读模式
+    Critical,
+}
+
+/// 磁盘空间状态
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DiskStatus {
+    /// 数据目录
+    pub data_dir: String,
+    /// 磁盘总空间，单位字节
+    pub total_bytes: u64,
+    /// 磁盘可用空间，单位字节
+    pub available_bytes: u64,
+    /// 空间级别
+    pub level: DiskLevel,
+    /// 是否处于只读模式
+    pub read_only: bool,
+    /// 检查时间
+    pub checked_at: DateTime<Local>,
+}
+
+struct DiskGuard {
+    data_dir: String,
+    warn_bytes: u64,
+    critical_bytes: u64,
+    read_only: AtomicBool,
+    status: RwLock<Option<DiskStatus>>,
+}
+
+impl DiskGuard {
+    fn new(data_dir: &str, warn_free_mb: u64, critical_free_mb: u64) -> Self {
+        Self {
+            data_dir: data_dir.to_string(),
+            warn_bytes: warn_free_mb * MB,
+            critical_bytes: critical_free_mb * MB,
+            read_only: AtomicBool::new(false),
+            status: RwLock::new(None),
+        }
+    }
+
+    fn check(&self) -> anyhow::Result<DiskStatus> {
+        let total_bytes = fs2::total_space(&self.data_dir)?;
+        let available_bytes = fs2::available_space(&self.data_dir)?;
+        let level = self.update(available_bytes);
+
+        Ok(DiskStatus {
+            data_dir: self.data_dir.clone(),
+            total_bytes,
+            available_bytes,
+            level,
+            read_only: level == DiskLevel::Critical,
+            checked_at: Local::now(),
+        })
+    }
+
+    /// 按可用空间计算空间级别
+    fn level(&self, available_bytes: u64) -> DiskLevel {
+        if available_bytes < self.critical_bytes {
+            DiskLevel::Critical
+        } else if available_bytes < self.warn_bytes {
+            DiskLevel::Low
+        } else {
+            DiskLevel::Ok
+        }
+    }
+
+    /// 按可用空间更新只读模式，返回空间级别
+    fn update(&self, available_bytes: u64) -> DiskLevel {
+        let level = self.level(available_bytes);
+        let read_only = level == DiskLevel::Critical;
+        let was_read_only = self.read_only.swap(read_only, Ordering::Relaxed);
+        match level {
+            DiskLevel::Critical => log::error!(
+                "disk space of {} is critically low ({} MB available), writes are rejected",
+                self.data_dir,
+                available_bytes / MB
+            ),
+            DiskLevel::Low => log::warn!(
+                "disk space of {} is low ({} MB available)",
+                self.data_dir,
+                available_bytes / MB
+            ),
+            DiskLevel::Ok => {}
+        }
+        if was_read_only && !read_only {
+            log::info!(
+                "disk space of {} recovered, writes are accepted",
+                self.data_dir
+            );
+        }
+        level
+    }
+
+    /// 检查是否允许写入，见[`check_writable`]
+    fn check_writable(&self, request: &RaftRequest) -> anyhow::Result<()> {
+        if self.read_only.load(Ordering::Relaxed) && !is_exempt(request) {
+            return Err(CodeError {
+                code: DISK_READ_ONLY_CODE,
+                msg: format!(
+                    "node is read-only: disk space of {} is critically low",
+                    self.data_dir
+                ),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+pub fn init(args: &Args) {
+    let guard = DISK_GUARD.get_or_init(|| {
+        DiskGuard::new(
+            &args.data_dir,
+            args.disk_warn_free_mb,
+            args.disk_critical_free_mb,
+        )
+    });
+    schedule::schedule(
+        "disk-check",
+        "检查数据目录所在磁盘的可用空间",
+        TaskScope::Node,
+        CHECK_INTERVAL,
+        move || async move {
+            let status = guard
+                .check()
+                .with_context(|| format!("check disk space of {} error", guard.data_dir))?;
+            *guard.status.write().unwrap() = Some(status);
+            Ok(())
+        },
+    );
+}
+
+/// 最近一次检查的磁盘空间状态
+pub fn status() -> Option<DiskStatus> {
+    DISK_GUARD
+        .get()
+        .and_then(|guard| guard.status.read().unwrap().clone())
+}
+
+/// 磁盘空间只读模式下仍允许的写入：心跳（包括手动修改心跳状态）和缓存写入
+///
+/// 与启动参数`--read-only`的只读节点不同，见[`raft_write`](crate::raft::api::raft_write)
+pub fn is_exempt(request: &RaftRequest) -> bool {
+    matches!(
+        request,
+        RaftRequest::Heartbeat { .. }
+            | RaftRequest::HeartbeatBatch { .. }
+            | RaftRequest::OverrideHeartbeat { .. }
+            | RaftRequest::CacheWrite { .. }
+    )
+}
+
+/// 检查是否允许写入，只读模式下拒绝除心跳（包括手动修改心跳状态）和缓存写入外的写入请求
+pub fn check_writable(request: &RaftRequest) -> anyhow::Result<()> {
+    match DISK_GUARD.get() {
+        Some(guard) => guard.check_writable(request),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::HeartbeatOverride;
+    use serde_json::Value;
+
+    fn requests() -> Vec<(RaftRequest, bool)> {
+        vec![
+            (
+                RaftRequest::Heartbeat {
+                    namespace_id: "public".to_string(),
+                    service_id: "svc".to_string(),
+                    instance_id: "1".to_string(),
+                },
+                true,
+            ),
+            (
+                RaftRequest::HeartbeatBatch {
+                    namespace_id: "public".to_string(),
+                    instances: vec![("svc".to_string(), "1".to_string())],
+                },
+                true,
+            ),
+            (
+                RaftRequest::OverrideHeartbeat {
+                    namespace_id: "public".to_string(),
+                    service_id: "svc".to_string(),
+                    instance_id: "1".to_string(),
+                    action: HeartbeatOverride::MarkUp,
+                },
+                true,
+            ),
+            (
+                RaftRequest::CacheWrite {
+                    key: "token".to_string(),
+                    value: Value::Null,
+                    ttl: None,
+                },
+                true,
+            ),
+            (
+                RaftRequest::DeleteConfig {
+                    namespace_id: "public".to_string(),
+                    id: "app.yaml".to_string(),
+                },
+                false,
+            ),
+            (
+                RaftRequest::DeleteUser {
+                    username: "user".to_string(),
+                },
+                false,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_level() {
+        let guard = DiskGuard::new("data", 1024, 256);
+        assert_eq!(guard.level(2048 * MB), DiskLevel::Ok);
+        assert_eq!(guard.level(1024 * MB), DiskLevel::Ok);
+        assert_eq!(guard.level(1024 * MB - 1), DiskLevel::Low);
+        assert_eq!(guard.level(256 * MB), DiskLevel::Low);
+        assert_eq!(guard.level(256 * MB - 1), DiskLevel::Critical);
+        assert_eq!(guard.level(0), DiskLevel::Critical);
+    }
+
+    #[test]
+    fn test_check_writable() {
+        let guard = DiskGuard::new("data", 1024, 256);
+        let requests = requests();
+
+        // 空间不足仅记录警告，允许写入
+        assert_eq!(guard.update(512 * MB), DiskLevel::Low);
+        for (request, _) in &requests {
+            assert!(guard.check_writable(request).is_ok());
+        }
+
+        // 只读模式下仅允许心跳和缓存写入
+        assert_eq!(guard.update(128 * MB), DiskLevel::Critical);
+        for (request, exempt) in &requests {
+            match guard.check_writable(request) {
+                Ok(()) => assert!(exempt, "{:?} should be rejected", request),
+                Err(e) => {
+                    assert!(!exempt, "{:?} should be accepted", request);
+                    let e = e.downcast_ref::<CodeError>().unwrap();
+                    assert_eq!(e.code, DISK_READ_ONLY_CODE);
+                }
+            }
+        }
+
+        // 空间恢复后退出只读模式
+        assert_eq!(guard.update(2048 * MB), DiskLevel::Ok);
+        for (request, _) in &requests {
+            assert!(guard.check_writable(request).is_ok());
+        }
+    }
+}
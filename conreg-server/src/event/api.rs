@@ -0,0 +1,128 @@
+use crate::auth::{NamespaceAuth, UserPrincipal};
+use crate::event::dead_letter::{DeadLetter, store};
+use crate::event::journal;
+use crate::event::journal::ReplayRes;
+use crate::openapi::ApiDoc;
+use crate::protocol::res::Res;
+use rocket::serde::json::Json;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![list, replay, replay_all, delete]
+}
+
+/// 变更事件回放接口，挂载在`/api/event`
+pub fn journal_routes() -> Vec<rocket::Route> {
+    routes![replay_events]
+}
+
+pub fn journal_docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("replay_events", "回放命名空间在指定版本号之后的变更事件")
+            .namespace_auth()
+            .response::<ReplayRes>(),
+    ]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("list", "当前节点的死信列表")
+            .auth()
+            .response::<Vec<DeadLetter>>(),
+        ApiDoc::new("replay", "重新处理死信")
+            .auth()
+            .body::<DeadLetterReq>()
+            .response::<()>(),
+        ApiDoc::new("replay_all", "重新处理全部死信")
+            .auth()
+            .response::<ReplayAllRes>(),
+        ApiDoc::new("delete", "删除死信")
+            .auth()
+            .body::<DeadLetterReq>()
+            .response::<()>(),
+    ]
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct DeadLetterReq {
+    pub(crate) id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ReplayAllRes {
+    pub(crate) success: usize,
+    pub(crate) failed: usize,
+}
+
+/// 当前节点的死信列表
+#[get("/list")]
+async fn list(user: UserPrincipal) -> Res<Vec<DeadLetter>> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    match store().list() {
+        Ok(res) => Res::success(res),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 重放死信
+#[post("/replay", data = "<req>")]
+async fn replay(req: Json<DeadLetterReq>, user: UserPrincipal) -> Res<()> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    match store().replay(req.0.id, true).await {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 按失败顺序重放所有死信
+#[post("/replay_all")]
+async fn replay_all(user: UserPrincipal) -> Res<ReplayAllRes> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    let letters = match store().list() {
+        Ok(letters) => letters,
+        Err(e) => return Res::error(&e.to_string()),
+    };
+    let mut res = ReplayAllRes {
+        success: 0,
+        failed: 0,
+    };
+    for letter in letters {
+        match store().replay(letter.id, true).await {
+            Ok(_) => res.success += 1,
+            Err(_) => res.failed += 1,
+        }
+    }
+    Res::success(res)
+}
+
+/// 删除死信，放弃处理
+#[post("/delete", data = "<req>")]
+async fn delete(req: Json<DeadLetterReq>, user: UserPrincipal) -> Res<()> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    match store().remove(req.0.id) {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 回放命名空间在指定版本号之后的变更事件
+///
+/// 客户端重连后使用上次获取的`revision`回放断开期间的变更，`complete`为false时需要全量拉取。
+/// 首次调用可传0获取当前版本号。
+#[get("/replay?<namespace_id>&<since_revision>")]
+async fn replay_events(
+    namespace_id: &str,
+    since_revision: u64,
+    _auth: NamespaceAuth,
+) -> Res<ReplayRes> {
+    Res::success(journal::replay(namespace_id, since_revision))
+}
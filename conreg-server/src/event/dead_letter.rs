@@ -0,0 +1,394 @@
+//! 死信队列
+//!
+//! 事件处理失败（如sqlite被锁）时，将事件持久化到sled的`dead_letter`树中，
+//! 由后台任务按指数退避重试，超过最大重试次数后停止自动重试，等待人工处理。
+//!
+//! 同一数据（如同一个配置）有死信未处理时，后续对该数据的变更不再直接应用，而是排在死信之后，
+//! 按原有顺序重放，避免重放较早的变更覆盖较新的数据。
+//!
+//! 注意：每个节点独立应用Raft日志，死信也是节点本地的，管理接口只作用于当前节点。
+
+use crate::event::handle_raft_request;
+use crate::raft::RaftRequest;
+use crate::schedule::{self, TaskScope};
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::log;
+
+/// 最大自动重试次数
+const MAX_ATTEMPTS: u32 = 10;
+/// 首次重试间隔
+const BASE_BACKOFF: Duration = Duration::from_secs(5);
+/// 最大重试间隔
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+/// 后台重试任务的扫描间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+static DEAD_LETTER: OnceLock<DeadLetterStore> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeadLetter {
+    pub id: u64,
+    pub request: RaftRequest,
+    /// 最后一次失败的原因
+    pub error: String,
+    /// 已处理次数，包含第一次处理，排在其他死信之后未处理过的为0
+    pub attempts: u32,
+    pub first_failed_at: DateTime<Local>,
+    pub last_failed_at: DateTime<Local>,
+    /// 下一次自动重试的时间，为空表示不再自动重试
+    pub next_retry_at: Option<DateTime<Local>>,
+}
+
+pub struct DeadLetterStore {
+    db: Arc<sled::Db>,
+    tree: sled::Tree,
+}
+
+/// 初始化死信队列，并启动后台重试任务
+pub fn init(db: Arc<sled::Db>) {
+    let tree = db
+        .open_tree("dead_letter")
+        .expect("Failed to create dead_letter tree");
+    if DEAD_LETTER.set(DeadLetterStore { db, tree }).is_err() {
+        return;
+    }
+    schedule::schedule(
+        "dead-letter-retry",
+        "重试本节点到期的死信",
+        TaskScope::Node,
+        RETRY_INTERVAL,
+        retry_due,
+    );
+}
+
+pub fn store() -> &'static DeadLetterStore {
+    DEAD_LETTER.get().expect("dead letter store not init")
+}
+
+/// 计算第n次失败后的重试间隔
+fn backoff(attempts: u32) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+/// 死信对应的数据，同一数据的变更需要按顺序应用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LetterKey {
+    Namespace(String),
+    /// (命名空间ID, 配置ID)
+    Config(String, String),
+    /// (命名空间ID, 服务ID)
+    Bootstrap(String, String),
+    User(String),
+}
+
+impl LetterKey {
+    fn namespace_id(&self) -> Option<&str> {
+        match self {
+            LetterKey::Namespace(namespace_id)
+            | LetterKey::Config(namespace_id, _)
+            | LetterKey::Bootstrap(namespace_id, _) => Some(namespace_id),
+            LetterKey::User(_) => None,
+        }
+    }
+
+    /// 两个数据的变更是否需要按顺序应用，命名空间的变更与其下所有数据的变更都需要按顺序应用
+    fn conflicts(&self, other: &LetterKey) -> bool {
+        match (self, other) {
+            (LetterKey::Namespace(namespace_id), key)
+            | (key, LetterKey::Namespace(namespace_id)) => key.namespace_id() == Some(namespace_id),
+            _ => self == other,
+        }
+    }
+}
+
+impl std::fmt::Display for LetterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LetterKey::Namespace(id) => write!(f, "namespace {}", id),
+            LetterKey::Config(namespace_id, id) => write!(f, "config {}/{}", namespace_id, id),
+            LetterKey::Bootstrap(namespace_id, service_id) => {
+                write!(f, "bootstrap profile {}/{}", namespace_id, service_id)
+            }
+            LetterKey::User(username) => write!(f, "user {}", username),
+        }
+    }
+}
+
+/// 获取变更对应的数据，为None时不限制顺序
+pub(crate) fn key(request: &RaftRequest) -> Option<LetterKey> {
+    match request {
+        RaftRequest::SetConfig { entry } | RaftRequest::UpdateConfig { entry } => Some(
+            LetterKey::Config(entry.namespace_id.clone(), entry.id.clone()),
+        ),
+        RaftRequest::DeleteConfig { namespace_id, id }
+        | RaftRequest::SetConfigSensitiveKeys {
+            namespace_id, id, ..
+        } => Some(LetterKey::Config(namespace_id.clone(), id.clone())),
+        RaftRequest::UpsertNamespace { namespace } => {
+            Some(LetterKey::Namespace(namespace.id.clone()))
+        }
+        RaftRequest::DeleteNamespace { id } => Some(LetterKey::Namespace(id.clone())),
+        RaftRequest::UpsertBootstrapProfile { profile } => Some(LetterKey::Bootstrap(
+            profile.namespace_id.clone(),
+            profile.service_id.clone(),
+        )),
+        RaftRequest::DeleteBootstrapProfile {
+            namespace_id,
+            service_id,
+        } => Some(LetterKey::Bootstrap(
+            namespace_id.clone(),
+            service_id.clone(),
+        )),
+        RaftRequest::CreateUser { username, .. }
+        | RaftRequest::DeleteUser { username }
+        | RaftRequest::UpdateUser { username, .. } => Some(LetterKey::User(username.clone())),
+        _ => None,
+    }
+}
+
+fn next_retry_at(attempts: u32) -> Option<DateTime<Local>> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    Some(Local::now() + backoff(attempts))
+}
+
+impl DeadLetterStore {
+    /// 记录处理失败的事件
+    pub fn push(&self, request: RaftRequest, error: &anyhow::Error) -> anyhow::Result<u64> {
+        let id = self.db.generate_id()?;
+        let now = Local::now();
+        let letter = DeadLetter {
+            id,
+            request,
+            error: error.to_string(),
+            attempts: 1,
+            first_failed_at: now,
+            last_failed_at: now,
+            next_retry_at: next_retry_at(1),
+        };
+        self.save(&letter)?;
+        Ok(id)
+    }
+
+    /// 同一数据有未处理的死信时，将变更排在死信之后，返回true；否则返回false，由调用方直接处理
+    pub fn hold_back(&self, request: &RaftRequest) -> anyhow::Result<bool> {
+        let Some(key) = key(request) else {
+            return Ok(false);
+        };
+        let Some(blocker) = self.first_pending(&key, u64::MAX)? else {
+            return Ok(false);
+        };
+        let id = self.db.generate_id()?;
+        let now = Local::now();
+        self.save(&DeadLetter {
+            id,
+            request: request.clone(),
+            error: format!("held back behind dead letter {} of {}", blocker, key),
+            attempts: 0,
+            first_failed_at: now,
+            last_failed_at: now,
+            next_retry_at: Some(now),
+        })?;
+        Ok(true)
+    }
+
+    /// 需要与`key`按顺序应用的数据中，早于`before`的第一条死信
+    fn first_pending(&self, key: &LetterKey, before: u64) -> anyhow::Result<Option<u64>> {
+        if self.count() == 0 {
+            return Ok(None);
+        }
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|letter| {
+                letter.id < before
+                    && self::key(&letter.request).is_some_and(|other| other.conflicts(key))
+            })
+            .map(|letter| letter.id))
+    }
+
+    fn save(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        self.tree
+            .insert(letter.id.to_be_bytes(), serde_json::to_vec(letter)?)?;
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: u64) -> anyhow::Result<Option<DeadLetter>> {
+        match self.tree.get(id.to_be_bytes())? {
+            Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 按失败顺序列出所有死信
+    pub fn list(&self) -> anyhow::Result<Vec<DeadLetter>> {
+        let mut list = Vec::new();
+        for item in self.tree.iter() {
+            let (_, v) = item?;
+            list.push(serde_json::from_slice(&v)?);
+        }
+        Ok(list)
+    }
+
+    pub fn count(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn remove(&self, id: u64) -> anyhow::Result<()> {
+        self.tree.remove(id.to_be_bytes())?;
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    /// 重新处理死信，成功则删除，失败则更新重试信息
+    ///
+    /// `manual`为true时表示人工重放，失败后不计入自动重试次数上限，保持原有的重试计划
+    ///
+    /// 同一数据有更早的死信时不处理，需要先重放或删除更早的死信
+    pub async fn replay(&self, id: u64, manual: bool) -> anyhow::Result<()> {
+        let mut letter = self
+            .get(id)?
+            .with_context(|| format!("Dead letter {} not found", id))?;
+        if let Some(key) = key(&letter.request)
+            && let Some(blocker) = self.first_pending(&key, id)?
+        {
+            anyhow::bail!(
+                "dead letter {} of {} must be replayed or deleted first",
+                blocker,
+                key
+            );
+        }
+        match handle_raft_request(letter.request.clone()).await {
+            Ok(_) => {
+                log::info!("dead letter {} replayed successfully", id);
+                self.remove(id)
+            }
+            Err(e) => {
+                letter.error = e.to_string();
+                letter.last_failed_at = Local::now();
+                if !manual {
+                    letter.attempts += 1;
+                    letter.next_retry_at = next_retry_at(letter.attempts);
+                }
+                self.save(&letter)?;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// 重试所有到期的死信
+///
+/// 按失败顺序处理，同一数据的死信未到期或重放失败时，跳过该数据之后的死信，保证按原有顺序重放
+async fn retry_due() -> anyhow::Result<()> {
+    let store = store();
+    if store.count() == 0 {
+        return Ok(());
+    }
+    let now = Local::now();
+    // 有未处理死信的数据
+    let mut blocked: Vec<LetterKey> = Vec::new();
+    for letter in store.list()? {
+        let key = key(&letter.request);
+        if let Some(key) = &key
+            && blocked.iter().any(|blocked| blocked.conflicts(key))
+        {
+            continue;
+        }
+        let replayed = if letter.next_retry_at.is_none_or(|t| t > now) {
+            false
+        } else {
+            match store.replay(letter.id, false).await {
+                Ok(_) => true,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to replay dead letter {} (attempt {}): {}",
+                        letter.id,
+                        letter.attempts + 1,
+                        e
+                    );
+                    false
+                }
+            }
+        };
+        if !replayed && let Some(key) = key {
+            blocked.push(key);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), Duration::from_secs(5));
+        assert_eq!(backoff(2), Duration::from_secs(10));
+        assert_eq!(backoff(4), Duration::from_secs(40));
+        assert_eq!(backoff(9), MAX_BACKOFF);
+        assert!(next_retry_at(MAX_ATTEMPTS).is_none());
+    }
+
+    #[test]
+    fn test_hold_back() {
+        let db = Arc::new(sled::Config::new().temporary(true).open().unwrap());
+        let store = DeadLetterStore {
+            tree: db.open_tree("dead_letter").unwrap(),
+            db,
+        };
+        let delete = |id: &str| RaftRequest::DeleteConfig {
+            namespace_id: "public".to_string(),
+            id: id.to_string(),
+        };
+        // 没有死信时直接处理
+        assert!(!store.hold_back(&delete("a.yaml")).unwrap());
+
+        let first = store
+            .push(delete("a.yaml"), &anyhow::anyhow!("database is locked"))
+            .unwrap();
+        // 同一配置的后续变更排在死信之后，其他配置不受影响
+        assert!(store.hold_back(&delete("a.yaml")).unwrap());
+        assert!(!store.hold_back(&delete("b.yaml")).unwrap());
+        let letters = store.list().unwrap();
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[1].attempts, 0);
+        let a = LetterKey::Config("public".to_string(), "a.yaml".to_string());
+        assert_eq!(store.first_pending(&a, letters[1].id).unwrap(), Some(first));
+        assert_eq!(store.first_pending(&a, first).unwrap(), None);
+
+        // 删除命名空间与其下所有配置的变更按顺序应用
+        assert!(
+            store
+                .hold_back(&RaftRequest::DeleteNamespace {
+                    id: "public".to_string()
+                })
+                .unwrap()
+        );
+        assert!(
+            !store
+                .hold_back(&RaftRequest::DeleteNamespace {
+                    id: "dev".to_string()
+                })
+                .unwrap()
+        );
+        assert!(!a.conflicts(&LetterKey::Config("dev".to_string(), "a.yaml".to_string())));
+        assert!(!a.conflicts(&LetterKey::User("public".to_string())));
+        assert_eq!(
+            key(&RaftRequest::Delete {
+                key: "k".to_string()
+            }),
+            None
+        );
+    }
+}
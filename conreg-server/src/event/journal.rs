@@ -0,0 +1,280 @@
+//! 变更事件日志
+//!
+//! 在内存中为每个命名空间保留最近的变更事件（配置、服务和服务实例的变更），
+//! 客户端重连后可通过`GET /api/event/replay`增量获取断开期间的变更，而不必每次都全量拉取。
+//!
+//! 事件的版本号即Raft日志索引，在应用日志时记录，因此各节点一致。事件日志仅保存在内存中，
+//! 且每个命名空间最多保留[`MAX_EVENTS`]条，请求的版本号早于可回放的范围时返回`complete = false`，
+//! 客户端需要全量拉取。
+//!
+//! 心跳超时导致的实例状态变化由各节点本地判定，不记录在事件日志中。
+
+use crate::raft::RaftRequest;
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 每个命名空间保留的最大事件数
+const MAX_EVENTS: usize = 1024;
+
+static JOURNAL: LazyLock<Journal> = LazyLock::new(Journal::default);
+
+/// 变更类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ChangeKind {
+    /// 配置新增或更新
+    ConfigChanged { config_id: String, md5: String },
+    /// 配置删除
+    ConfigDeleted { config_id: String },
+    /// 服务注册
+    ServiceRegistered { service_id: String },
+    /// 服务注销
+    ServiceDeregistered { service_id: String },
+    /// 服务实例注册
+    InstanceRegistered {
+        service_id: String,
+        instance_id: String,
+    },
+    /// 服务实例注销
+    InstanceDeregistered {
+        service_id: String,
+        instance_id: String,
+    },
+    /// 服务生效实例集合切换
+    ActiveSetSwitched {
+        service_id: String,
+        active_set: Option<String>,
+    },
+}
+
+/// 变更事件
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChangeEvent {
+    /// 版本号，即Raft日志索引
+    pub revision: u64,
+    #[serde(flatten)]
+    pub kind: ChangeKind,
+    /// 记录时间
+    pub time: DateTime<Local>,
+}
+
+/// 回放结果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReplayRes {
+    /// 当前版本号，客户端下次回放时使用
+    pub revision: u64,
+    /// 是否完整，为false时表示部分事件已不可回放，需要全量拉取
+    pub complete: bool,
+    /// 请求的版本号之后的事件，按版本号升序
+    pub events: Vec<ChangeEvent>,
+}
+
+#[derive(Debug, Default)]
+struct NamespaceEvents {
+    events: VecDeque<ChangeEvent>,
+    /// 已丢弃的最大版本号
+    dropped: u64,
+}
+
+#[derive(Debug)]
+struct Journal {
+    namespaces: DashMap<String, NamespaceEvents>,
+    /// 本节点应用的第一条日志的索引，此前的事件未记录
+    first: AtomicU64,
+    /// 本节点应用的最后一条日志的索引
+    last: AtomicU64,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            namespaces: DashMap::new(),
+            first: AtomicU64::new(u64::MAX),
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Journal {
+    fn record(&self, revision: u64, req: &RaftRequest) {
+        let _ =
+            self.first
+                .compare_exchange(u64::MAX, revision, Ordering::Relaxed, Ordering::Relaxed);
+        self.last.fetch_max(revision, Ordering::Relaxed);
+
+        let Some((namespace_id, kind)) = change_of(req) else {
+            return;
+        };
+        let mut namespace = self.namespaces.entry(namespace_id).or_default();
+        if namespace.events.len() >= MAX_EVENTS
+            && let Some(dropped) = namespace.events.pop_front()
+        {
+            namespace.dropped = dropped.revision;
+        }
+        namespace.events.push_back(ChangeEvent {
+            revision,
+            kind,
+            time: Local::now(),
+        });
+    }
+
+    fn replay(&self, namespace_id: &str, since_revision: u64) -> ReplayRes {
+        let revision = self.last.load(Ordering::Relaxed);
+        let first = self.first.load(Ordering::Relaxed);
+        let (events, dropped) = self
+            .namespaces
+            .get(namespace_id)
+            .map(|namespace| {
+                let events = namespace
+                    .events
+                    .iter()
+                    .filter(|event| event.revision > since_revision)
+                    .cloned()
+                    .collect();
+                (events, namespace.dropped)
+            })
+            .unwrap_or_default();
+        // 请求的版本号之后的日志均已在本节点应用并记录，且未被丢弃时才是完整的
+        let complete = since_revision >= revision
+            || (since_revision >= first.saturating_sub(1) && since_revision >= dropped);
+        ReplayRes {
+            revision,
+            complete,
+            events,
+        }
+    }
+}
+
+/// 解析Raft请求对应的变更，返回命名空间ID和变更类型
+fn change_of(req: &RaftRequest) -> Option<(String, ChangeKind)> {
+    let change = match req {
+        RaftRequest::SetConfig { entry } | RaftRequest::UpdateConfig { entry } => (
+            entry.namespace_id.clone(),
+            ChangeKind::ConfigChanged {
+                config_id: entry.id.clone(),
+                md5: entry.md5.clone(),
+            },
+        ),
+        RaftRequest::DeleteConfig { namespace_id, id } => (
+            namespace_id.clone(),
+            ChangeKind::ConfigDeleted {
+                config_id: id.clone(),
+            },
+        ),
+        RaftRequest::RegisterService { service } => (
+            service.namespace_id.clone(),
+            ChangeKind::ServiceRegistered {
+                service_id: service.service_id.clone(),
+            },
+        ),
+        RaftRequest::DeregisterService {
+            namespace_id,
+            service_id,
+        } => (
+            namespace_id.clone(),
+            ChangeKind::ServiceDeregistered {
+                service_id: service_id.clone(),
+            },
+        ),
+        RaftRequest::RegisterServiceInstance {
+            namespace_id,
+            instance,
+            ..
+        } => (
+            namespace_id.clone(),
+            ChangeKind::InstanceRegistered {
+                service_id: instance.service_id.clone(),
+                instance_id: instance.id.clone(),
+            },
+        ),
+        RaftRequest::DeregisterServiceInstance {
+            namespace_id,
+            service_id,
+            instance_id,
+        } => (
+            namespace_id.clone(),
+            ChangeKind::InstanceDeregistered {
+                service_id: service_id.clone(),
+                instance_id: instance_id.clone(),
+            },
+        ),
+        RaftRequest::SwitchServiceActiveSet {
+            namespace_id,
+            service_id,
+            active_set,
+        } => (
+            namespace_id.clone(),
+            ChangeKind::ActiveSetSwitched {
+                service_id: service_id.clone(),
+                active_set: active_set.clone(),
+            },
+        ),
+        _ => return None,
+    };
+    Some(change)
+}
+
+/// 记录已应用的Raft日志，非变更请求仅更新版本号
+pub fn record(revision: u64, req: &RaftRequest) {
+    JOURNAL.record(revision, req);
+}
+
+/// 当前版本号，即本节点应用的最后一条日志的索引
+pub fn revision() -> u64 {
+    JOURNAL.last.load(Ordering::Relaxed)
+}
+
+/// 回放命名空间在指定版本号之后的变更事件
+pub fn replay(namespace_id: &str, since_revision: u64) -> ReplayRes {
+    JOURNAL.replay(namespace_id, since_revision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deregister(instance_id: &str) -> RaftRequest {
+        RaftRequest::DeregisterServiceInstance {
+            namespace_id: "public".to_string(),
+            service_id: "svc".to_string(),
+            instance_id: instance_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let journal = Journal::default();
+        journal.record(10, &deregister("a"));
+        journal.record(
+            11,
+            &RaftRequest::Delete {
+                key: "k".to_string(),
+            },
+        );
+        journal.record(12, &deregister("b"));
+
+        let res = journal.replay("public", 10);
+        assert!(res.complete);
+        assert_eq!(res.revision, 12);
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(res.events[0].revision, 12);
+
+        // 早于本节点记录的范围
+        assert!(!journal.replay("public", 5).complete);
+        assert!(journal.replay("public", 9).complete);
+        // 已是最新版本
+        assert!(journal.replay("public", 12).events.is_empty());
+        assert!(journal.replay("other", 12).complete);
+
+        for i in 0..MAX_EVENTS as u64 {
+            journal.record(13 + i, &deregister("c"));
+        }
+        assert!(!journal.replay("public", 10).complete);
+        assert!(journal.replay("public", 13).complete);
+    }
+}
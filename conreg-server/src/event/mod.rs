@@ -1,46 +1,280 @@
 use crate::app::get_app;
+use crate::metrics::metrics;
 use crate::raft::RaftRequest;
 use crate::{cache, system};
-use std::sync::LazyLock;
-use std::sync::atomic::AtomicBool;
+use dashmap::DashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Instant;
 use tokio::sync::mpsc;
 use tracing::log;
 
+pub mod api;
+pub mod dead_letter;
+pub mod journal;
+
 pub enum Event {
     RaftRequestEvent(RaftRequest),
 }
 
 impl Event {
-    pub fn send(self) -> Result<(), Box<mpsc::error::SendError<Event>>> {
-        EVENT_BUS.send(self)
+    pub async fn send(self) -> Result<(), Box<mpsc::error::SendError<Event>>> {
+        EVENT_BUS.send(self).await
+    }
+
+    /// 事件分类，不同分类的事件使用不同的队列
+    fn class(&self) -> EventClass {
+        match self {
+            Event::RaftRequestEvent(req) => match req {
+                RaftRequest::SetConfig { .. }
+                | RaftRequest::UpdateConfig { .. }
+                | RaftRequest::DeleteConfig { .. }
+                | RaftRequest::ConfigFetchStats { .. }
+                | RaftRequest::SetConfigSensitiveKeys { .. }
+                | RaftRequest::RotateConfigKey { .. }
+                | RaftRequest::UpsertBootstrapProfile { .. }
+                | RaftRequest::DeleteBootstrapProfile { .. } => EventClass::Config,
+                RaftRequest::UpsertNamespace { .. }
+                | RaftRequest::DeleteNamespace { .. }
+                | RaftRequest::CreateUser { .. }
+                | RaftRequest::DeleteUser { .. }
+                | RaftRequest::UpdateUser { .. } => EventClass::Namespace,
+                RaftRequest::RegisterService { .. }
+                | RaftRequest::DeregisterService { .. }
+                | RaftRequest::SetServiceExpectedInstances { .. }
+                | RaftRequest::SetServiceWarmup { .. }
+                | RaftRequest::SwitchServiceActiveSet { .. }
+                | RaftRequest::RegisterServiceInstance { .. }
+                | RaftRequest::DeregisterServiceInstance { .. }
+                | RaftRequest::UpdateServiceInstanceMeta { .. }
+                | RaftRequest::Heartbeat { .. }
+                | RaftRequest::HeartbeatBatch { .. }
+                | RaftRequest::OverrideHeartbeat { .. }
+                | RaftRequest::ImportDiscoveryState { .. } => EventClass::Discovery,
+                RaftRequest::Set { .. }
+                | RaftRequest::Delete { .. }
+                | RaftRequest::CacheWrite { .. }
+                | RaftRequest::PrepareClusterSecret { .. }
+                | RaftRequest::ActivateClusterSecret
+                | RaftRequest::Unknown(_) => EventClass::Cache,
+            },
+        }
+    }
+
+    /// 心跳事件的key，用于合并队列中重复的心跳
+    fn heartbeat_key(&self) -> Option<HeartbeatKey> {
+        match self {
+            Event::RaftRequestEvent(RaftRequest::Heartbeat {
+                namespace_id,
+                service_id,
+                instance_id,
+            }) => Some((
+                namespace_id.clone(),
+                service_id.clone(),
+                instance_id.clone(),
+            )),
+            _ => None,
+        }
     }
 }
 
+/// 事件分类
+///
+/// 按优先级从高到低排列，处理时优先处理高优先级队列中的事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum EventClass {
+    /// 配置变更
+    Config,
+    /// 命名空间及用户变更
+    Namespace,
+    /// 服务注册、注销及心跳
+    Discovery,
+    /// 缓存写入
+    Cache,
+}
+
+impl EventClass {
+    /// 所有分类，按优先级从高到低排列
+    const ALL: [EventClass; 4] = [
+        EventClass::Config,
+        EventClass::Namespace,
+        EventClass::Discovery,
+        EventClass::Cache,
+    ];
+
+    /// 队列容量
+    fn capacity(&self) -> usize {
+        match self {
+            EventClass::Config => 10000,
+            EventClass::Namespace => 1000,
+            EventClass::Discovery => 10000,
+            EventClass::Cache => 10000,
+        }
+    }
+}
+
+/// 排队中的事件
+struct QueuedEvent {
+    event: Event,
+    /// 入队序号，与Raft日志的应用顺序一致
+    seq: u64,
+    /// 入队时间
+    queued_at: Instant,
+}
+
+/// 心跳标识：(命名空间ID, 服务ID, 实例ID)
+type HeartbeatKey = (String, String, String);
+
+/// 事件总线
+///
+/// 每类事件使用独立的有界队列，队列满时：
+/// - 心跳事件直接丢弃，心跳是周期性的，丢弃后由下一次心跳补偿
+/// - 其他事件等待队列空闲，以背压的方式减缓Raft日志的应用速度，保证不丢失数据
+///
+/// 另外，同一实例的心跳在队列中未被处理时，后续的心跳会被合并掉。
+///
+/// 同一队列中的事件按入队顺序处理。配置、服务注册及缓存之间相互独立，可以按优先级乱序处理，
+/// 但它们都依赖命名空间（如删除命名空间会删除其下的配置），因此命名空间事件作为屏障，
+/// 与其他队列的事件之间保持入队顺序，见[`next_class`]。
 pub struct EventBus {
-    sender: mpsc::UnboundedSender<Event>,
+    config: mpsc::Sender<QueuedEvent>,
+    namespace: mpsc::Sender<QueuedEvent>,
+    discovery: mpsc::Sender<QueuedEvent>,
+    cache: mpsc::Sender<QueuedEvent>,
+    /// 下一个入队序号
+    seq: AtomicU64,
+    /// 队列中等待处理的心跳
+    pending_heartbeats: Arc<DashSet<HeartbeatKey>>,
 }
 
 impl EventBus {
     pub fn new() -> Self {
-        let (sender, receiver) = mpsc::unbounded_channel::<Event>();
-        let handler = EventHandler::new(receiver);
+        let (config, config_receiver) = mpsc::channel(EventClass::Config.capacity());
+        let (namespace, namespace_receiver) = mpsc::channel(EventClass::Namespace.capacity());
+        let (discovery, discovery_receiver) = mpsc::channel(EventClass::Discovery.capacity());
+        let (cache, cache_receiver) = mpsc::channel(EventClass::Cache.capacity());
+        let pending_heartbeats = Arc::new(DashSet::new());
+
+        let handler = EventHandler::new(
+            EventReceivers {
+                config: config_receiver,
+                namespace: namespace_receiver,
+                discovery: discovery_receiver,
+                cache: cache_receiver,
+            },
+            pending_heartbeats.clone(),
+        );
 
         tokio::spawn(async move {
             handler.handle_events().await;
         });
 
-        Self { sender }
+        Self {
+            config,
+            namespace,
+            discovery,
+            cache,
+            seq: AtomicU64::new(0),
+            pending_heartbeats,
+        }
+    }
+
+    fn sender(&self, class: EventClass) -> &mpsc::Sender<QueuedEvent> {
+        match class {
+            EventClass::Config => &self.config,
+            EventClass::Namespace => &self.namespace,
+            EventClass::Discovery => &self.discovery,
+            EventClass::Cache => &self.cache,
+        }
+    }
+
+    fn queued(&self, event: Event) -> QueuedEvent {
+        QueuedEvent {
+            event,
+            seq: self.seq.fetch_add(1, Ordering::Relaxed),
+            queued_at: Instant::now(),
+        }
     }
 
-    pub fn send(&self, event: Event) -> Result<(), Box<mpsc::error::SendError<Event>>> {
-        self.sender.send(event).map_err(Box::new)
+    pub async fn send(&self, event: Event) -> Result<(), Box<mpsc::error::SendError<Event>>> {
+        let class = event.class();
+        let sender = self.sender(class);
+
+        if let Some(key) = event.heartbeat_key() {
+            // 同一实例已有心跳在排队，合并
+            if !self.pending_heartbeats.insert(key.clone()) {
+                metrics().event_merged(class.into());
+                return Ok(());
+            }
+            return match sender.try_send(self.queued(event)) {
+                Ok(_) => {
+                    metrics().event_enqueued(class.into());
+                    Ok(())
+                }
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    self.pending_heartbeats.remove(&key);
+                    metrics().event_shed(class.into());
+                    log::debug!("event bus overloaded, heartbeat shed: {:?}", key);
+                    Ok(())
+                }
+                Err(mpsc::error::TrySendError::Closed(queued)) => {
+                    self.pending_heartbeats.remove(&key);
+                    Err(Box::new(mpsc::error::SendError(queued.event)))
+                }
+            };
+        }
+
+        sender
+            .send(self.queued(event))
+            .await
+            .map_err(|e| Box::new(mpsc::error::SendError(e.0.event)))?;
+        metrics().event_enqueued(class.into());
+        Ok(())
     }
 }
 
 static EVENT_BUS: LazyLock<EventBus> = LazyLock::new(EventBus::new);
 
+struct EventReceivers {
+    config: mpsc::Receiver<QueuedEvent>,
+    namespace: mpsc::Receiver<QueuedEvent>,
+    discovery: mpsc::Receiver<QueuedEvent>,
+    cache: mpsc::Receiver<QueuedEvent>,
+}
+
+impl EventReceivers {
+    fn receiver(&mut self, class: EventClass) -> &mut mpsc::Receiver<QueuedEvent> {
+        match class {
+            EventClass::Config => &mut self.config,
+            EventClass::Namespace => &mut self.namespace,
+            EventClass::Discovery => &mut self.discovery,
+            EventClass::Cache => &mut self.cache,
+        }
+    }
+}
+
+/// 选择下一个处理的队列，`seqs`为各队列队首事件的入队序号，按[`EventClass::ALL`]的顺序排列
+///
+/// 按优先级选择有事件的队列，但命名空间事件作为屏障：
+/// - 其他队列中比它先入队的事件处理完之前，不处理命名空间事件
+/// - 命名空间事件处理完之前，不处理其他队列中比它后入队的事件
+fn next_class(seqs: [Option<u64>; 4]) -> Option<EventClass> {
+    let first = seqs.iter().flatten().min().copied();
+    let barrier = seqs[EventClass::Namespace as usize];
+    EventClass::ALL
+        .into_iter()
+        .find(|class| match (seqs[*class as usize], barrier) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(seq), Some(barrier)) => seq < barrier || Some(seq) == first,
+        })
+}
+
 pub struct EventHandler {
-    receiver: mpsc::UnboundedReceiver<Event>,
+    receivers: EventReceivers,
+    /// 队列中等待处理的心跳
+    pending_heartbeats: Arc<DashSet<HeartbeatKey>>,
     /// 初始化标记
     /// 这是一个不优雅的实现，因为在App初始化未完成前，Raft已经初始化，Raft已经开始工作，
     /// 这就会导致在Event处理中get_app()时，App未完成初始化，导致panic。
@@ -49,190 +283,407 @@ pub struct EventHandler {
 }
 
 impl EventHandler {
-    pub fn new(receiver: mpsc::UnboundedReceiver<Event>) -> Self {
+    fn new(receivers: EventReceivers, pending_heartbeats: Arc<DashSet<HeartbeatKey>>) -> Self {
         Self {
-            receiver,
+            receivers,
+            pending_heartbeats,
             init_flag: AtomicBool::new(false),
         }
     }
 
     pub async fn handle_events(mut self) {
-        while let Some(event) = self.receiver.recv().await {
+        // 各队列的队首事件，按EventClass::ALL的顺序排列
+        let mut heads: [Option<QueuedEvent>; 4] = [None, None, None, None];
+        loop {
+            for class in EventClass::ALL {
+                if heads[class as usize].is_none() {
+                    heads[class as usize] = self.receivers.receiver(class).try_recv().ok();
+                }
+            }
+            if heads.iter().all(Option::is_none) {
+                // 所有队列都为空，等待任意队列的事件
+                let (class, e) = tokio::select! {
+                    biased;
+                    Some(e) = self.receivers.config.recv() => (EventClass::Config, e),
+                    Some(e) = self.receivers.namespace.recv() => (EventClass::Namespace, e),
+                    Some(e) = self.receivers.discovery.recv() => (EventClass::Discovery, e),
+                    Some(e) = self.receivers.cache.recv() => (EventClass::Cache, e),
+                    else => break,
+                };
+                heads[class as usize] = Some(e);
+            }
+
+            let Some(class) = next_class(heads.each_ref().map(|e| e.as_ref().map(|e| e.seq)))
+            else {
+                continue;
+            };
+            let Some(QueuedEvent {
+                event, queued_at, ..
+            }) = heads[class as usize].take()
+            else {
+                continue;
+            };
+            metrics().event_dequeued(class.into(), queued_at.elapsed());
+            if let Some(key) = event.heartbeat_key() {
+                self.pending_heartbeats.remove(&key);
+            }
             self.process_event(event).await;
         }
     }
 
     async fn process_event(&self, event: Event) {
-        if !self.init_flag.load(std::sync::atomic::Ordering::Acquire) {
+        if !self.init_flag.load(Ordering::Acquire) {
             tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-            self.init_flag
-                .store(true, std::sync::atomic::Ordering::Release);
+            self.init_flag.store(true, Ordering::Release);
         }
         match event {
             Event::RaftRequestEvent(req) => {
-                self.handle_raft_request(req).await;
-            }
-        }
-    }
-
-    async fn handle_raft_request(&self, req: RaftRequest) {
-        match req {
-            // 这两个在apply时已经处理
-            RaftRequest::Set { .. } | RaftRequest::Delete { .. } => {}
-            // 配置中心配置变更
-            RaftRequest::SetConfig { entry } => {
-                match get_app().config_app.manager.insert_config(entry).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing SetConfig request: {}", e);
+                let cmd: &'static str = (&req).into();
+                // 同一数据有未处理的死信时，排在死信之后按顺序重放
+                match dead_letter::store().hold_back(&req) {
+                    Ok(true) => {
+                        log::warn!("{} request held back behind a dead letter", cmd);
+                        return;
                     }
-                };
-            }
-            // 配置中心删除配置
-            RaftRequest::DeleteConfig { namespace_id, id } => {
-                match get_app()
-                    .config_app
-                    .manager
-                    .delete_config(&namespace_id, &id)
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing DeleteConfig request: {}", e);
-                    }
-                };
-            }
-            RaftRequest::UpdateConfig { entry } => {
-                match get_app().config_app.manager.update_config(entry).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing UpdateConfig request: {}", e);
-                    }
-                };
-            }
-            RaftRequest::UpsertNamespace { namespace } => {
-                match get_app()
-                    .namespace_app
-                    .manager
-                    .upsert_namespace(namespace)
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing UpsertNamespace request: {}", e);
-                    }
-                };
-            }
-            RaftRequest::DeleteNamespace { id } => {
-                match get_app().namespace_app.manager.delete_namespace(&id).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing DeleteNamespace request: {}", e);
-                    }
-                };
-            }
-            RaftRequest::RegisterService { service } => {
-                match get_app()
-                    .discovery_app
-                    .manager
-                    .register_service(service)
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing RegisterService request: {}", e);
-                    }
-                };
-            }
-            RaftRequest::DeregisterService {
-                namespace_id,
-                service_id,
-            } => {
-                match get_app()
-                    .discovery_app
-                    .manager
-                    .deregister_service(&namespace_id, &service_id)
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing DeregisterService request: {}", e);
-                    }
-                };
-            }
-            RaftRequest::RegisterServiceInstance {
-                namespace_id,
-                instance,
-            } => {
-                match get_app()
-                    .discovery_app
-                    .manager
-                    .register_service_instance(&namespace_id, instance)
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing RegisterServiceInstance request: {}", e);
+                    Ok(false) => {}
+                    Err(e) => log::error!("Failed to check dead letters of {}: {}", cmd, e),
+                }
+                let start = Instant::now();
+                let result = handle_raft_request(req.clone()).await;
+                metrics().observe_event_handle(cmd, start.elapsed(), result.is_ok());
+                if let Err(e) = result {
+                    log::error!("Error processing {} request: {}", cmd, e);
+                    // 心跳是周期性的，失败后由下一次心跳补偿，无需进入死信
+                    if matches!(
+                        req,
+                        RaftRequest::Heartbeat { .. } | RaftRequest::HeartbeatBatch { .. }
+                    ) {
+                        return;
                     }
-                };
-            }
-            RaftRequest::DeregisterServiceInstance {
-                namespace_id,
-                service_id,
-                instance_id,
-            } => {
-                match get_app()
-                    .discovery_app
-                    .manager
-                    .deregister_instance(&namespace_id, &service_id, &instance_id)
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing DeregisterServiceInstance request: {}", e);
+                    match dead_letter::store().push(req, &e) {
+                        Ok(id) => log::warn!("{} request saved to dead letter {}", cmd, id),
+                        Err(e) => {
+                            log::error!("Failed to save {} request to dead letter: {}", cmd, e)
+                        }
                     }
-                };
+                }
             }
-            RaftRequest::Heartbeat {
-                namespace_id,
-                service_id,
-                instance_id,
-            } => {
-                match get_app()
+        }
+    }
+}
+
+/// 将Raft请求应用到业务数据
+pub(crate) async fn handle_raft_request(req: RaftRequest) -> anyhow::Result<()> {
+    match req {
+        // 这几个在apply时已经处理
+        RaftRequest::Set { .. }
+        | RaftRequest::Delete { .. }
+        | RaftRequest::PrepareClusterSecret { .. }
+        | RaftRequest::ActivateClusterSecret
+        | RaftRequest::Unknown(_) => {}
+        // 配置中心配置变更
+        RaftRequest::SetConfig { entry } => {
+            get_app().config_app.manager.insert_config(entry).await?;
+        }
+        // 配置中心删除配置
+        RaftRequest::DeleteConfig { namespace_id, id } => {
+            get_app()
+                .config_app
+                .manager
+                .delete_config(&namespace_id, &id)
+                .await?;
+        }
+        RaftRequest::UpdateConfig { entry } => {
+            get_app().config_app.manager.update_config(entry).await?;
+        }
+        RaftRequest::ConfigFetchStats { stats } => {
+            get_app().config_app.manager.add_fetch_stats(stats).await?;
+        }
+        RaftRequest::SetConfigSensitiveKeys {
+            namespace_id,
+            id,
+            keys,
+        } => {
+            get_app()
+                .config_app
+                .manager
+                .set_sensitive_keys(&namespace_id, &id, keys)
+                .await?;
+        }
+        RaftRequest::RotateConfigKey { key } => {
+            get_app().config_app.manager.encryption.rotate(key).await?;
+        }
+        RaftRequest::UpsertNamespace { namespace } => {
+            get_app()
+                .namespace_app
+                .manager
+                .upsert_namespace(namespace)
+                .await?;
+        }
+        RaftRequest::DeleteNamespace { id } => {
+            get_app()
+                .namespace_app
+                .manager
+                .delete_namespace(&id)
+                .await?;
+        }
+        RaftRequest::UpsertBootstrapProfile { profile } => {
+            get_app()
+                .bootstrap_app
+                .manager
+                .upsert_profile(profile)
+                .await?;
+        }
+        RaftRequest::DeleteBootstrapProfile {
+            namespace_id,
+            service_id,
+        } => {
+            get_app()
+                .bootstrap_app
+                .manager
+                .delete_profile(&namespace_id, &service_id)
+                .await?;
+        }
+        RaftRequest::RegisterService { service } => {
+            get_app()
+                .discovery_app
+                .manager
+                .register_service(service)
+                .await?;
+        }
+        RaftRequest::DeregisterService {
+            namespace_id,
+            service_id,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .deregister_service(&namespace_id, &service_id)
+                .await?;
+        }
+        RaftRequest::SetServiceExpectedInstances {
+            namespace_id,
+            service_id,
+            expected_instances,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .set_expected_instances(&namespace_id, &service_id, expected_instances)
+                .await?;
+        }
+        RaftRequest::SetServiceWarmup {
+            namespace_id,
+            service_id,
+            warmup_seconds,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .set_warmup(&namespace_id, &service_id, warmup_seconds)
+                .await?;
+        }
+        RaftRequest::SwitchServiceActiveSet {
+            namespace_id,
+            service_id,
+            active_set,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .switch_active_set(&namespace_id, &service_id, active_set)
+                .await?;
+        }
+        RaftRequest::RegisterServiceInstance {
+            namespace_id,
+            instance,
+            policy,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .register_service_instance(&namespace_id, instance, policy)
+                .await?;
+        }
+        RaftRequest::DeregisterServiceInstance {
+            namespace_id,
+            service_id,
+            instance_id,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .deregister_instance(&namespace_id, &service_id, &instance_id)
+                .await?;
+        }
+        RaftRequest::UpdateServiceInstanceMeta {
+            namespace_id,
+            service_id,
+            instance_id,
+            meta,
+            remove_keys,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .update_instance_meta(&namespace_id, &service_id, &instance_id, meta, remove_keys)
+                .await?;
+        }
+        RaftRequest::Heartbeat {
+            namespace_id,
+            service_id,
+            instance_id,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .heartbeat(&namespace_id, &service_id, &instance_id)
+                .await?;
+        }
+        RaftRequest::HeartbeatBatch {
+            namespace_id,
+            instances,
+        } => {
+            for (service_id, instance_id) in instances {
+                get_app()
                     .discovery_app
                     .manager
                     .heartbeat(&namespace_id, &service_id, &instance_id)
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing Heartbeat request: {}", e);
-                    }
-                };
-            }
-            RaftRequest::CacheWrite { key, value, ttl } => {
-                match cache::set(key, &value, ttl).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("Error processing CacheWrite request: {}", e);
-                    }
-                }
-            }
-            RaftRequest::CreateUser { username, password } => {
-                if let Err(e) = system::create_user(&username, &password).await {
-                    log::error!("Error processing CreateUser request: {}", e);
-                }
-            }
-            RaftRequest::DeleteUser { username } => {
-                if let Err(e) = system::delete_user(&username).await {
-                    log::error!("Error processing DeleteUser request: {}", e);
-                }
-            }
-            RaftRequest::UpdateUser { username,password,permissions } => {
-                if let Err(e) = system::update_user(&username, password, permissions).await {
-                    log::error!("Error processing UpdateUser request: {}", e);
-                }
+                    .await?;
             }
         }
+        RaftRequest::OverrideHeartbeat {
+            namespace_id,
+            service_id,
+            instance_id,
+            action,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .override_heartbeat(&namespace_id, &service_id, &instance_id, action)
+                .await?;
+        }
+        RaftRequest::ImportDiscoveryState {
+            namespace_id,
+            state,
+        } => {
+            get_app()
+                .discovery_app
+                .manager
+                .import_state(&namespace_id, state)
+                .await?;
+        }
+        RaftRequest::CacheWrite { key, value, ttl } => {
+            cache::set(key, &value, ttl).await?;
+        }
+        RaftRequest::CreateUser { username, password } => {
+            system::create_user(&username, &password).await?;
+        }
+        RaftRequest::DeleteUser { username } => {
+            system::delete_user(&username).await?;
+        }
+        RaftRequest::UpdateUser {
+            username,
+            password,
+            permissions,
+        } => {
+            system::update_user(&username, password, permissions).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_class() {
+        let class = |req| Event::RaftRequestEvent(req).class();
+        assert_eq!(
+            class(RaftRequest::DeleteConfig {
+                namespace_id: "dev".to_string(),
+                id: "app.yaml".to_string(),
+            }),
+            EventClass::Config
+        );
+        assert_eq!(
+            class(RaftRequest::DeleteNamespace {
+                id: "dev".to_string(),
+            }),
+            EventClass::Namespace
+        );
+        assert_eq!(
+            class(RaftRequest::DeleteUser {
+                username: "dev".to_string(),
+            }),
+            EventClass::Namespace
+        );
+        assert_eq!(
+            class(RaftRequest::DeregisterServiceInstance {
+                namespace_id: "dev".to_string(),
+                service_id: "svc".to_string(),
+                instance_id: "1".to_string(),
+            }),
+            EventClass::Discovery
+        );
+        assert_eq!(
+            class(RaftRequest::Delete {
+                key: "key".to_string(),
+            }),
+            EventClass::Cache
+        );
+
+        // 心跳与实例注册在同一个队列中，保持先注册后心跳的顺序
+        let heartbeat = Event::RaftRequestEvent(RaftRequest::Heartbeat {
+            namespace_id: "dev".to_string(),
+            service_id: "svc".to_string(),
+            instance_id: "1".to_string(),
+        });
+        assert_eq!(heartbeat.class(), EventClass::Discovery);
+        assert!(heartbeat.heartbeat_key().is_some());
+        let batch = Event::RaftRequestEvent(RaftRequest::HeartbeatBatch {
+            namespace_id: "dev".to_string(),
+            instances: vec![],
+        });
+        assert_eq!(batch.class(), EventClass::Discovery);
+        assert!(batch.heartbeat_key().is_none());
+    }
+
+    #[test]
+    fn test_next_class() {
+        // 按优先级选择，配置优先
+        assert_eq!(next_class([None, None, None, None]), None);
+        assert_eq!(
+            next_class([Some(3), None, Some(1), Some(2)]),
+            Some(EventClass::Config)
+        );
+        assert_eq!(
+            next_class([None, None, Some(2), Some(1)]),
+            Some(EventClass::Discovery)
+        );
+        // 先入队的事件处理完之前不处理命名空间事件
+        assert_eq!(
+            next_class([None, Some(2), Some(1), None]),
+            Some(EventClass::Discovery)
+        );
+        assert_eq!(
+            next_class([None, Some(2), None, Some(1)]),
+            Some(EventClass::Cache)
+        );
+        // 命名空间事件处理完之前不处理后入队的事件
+        assert_eq!(
+            next_class([Some(3), Some(2), None, None]),
+            Some(EventClass::Namespace)
+        );
+        assert_eq!(
+            next_class([Some(1), Some(3), Some(4), Some(2)]),
+            Some(EventClass::Config)
+        );
+        assert_eq!(
+            next_class([Some(5), Some(3), Some(4), Some(2)]),
+            Some(EventClass::Cache)
+        );
     }
 }
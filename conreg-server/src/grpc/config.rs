@@ -0,0 +1,173 @@
+//! 配置中心gRPC接口
+
+use crate::app::get_app;
+use crate::config::server::ConfigEntry;
+use crate::config::server::journal::Replay;
+use crate::config::server::label::{parse_labels, split_variant_id};
+use conreg_grpc::NS_TOKEN_METADATA;
+use conreg_grpc::config_server::Config;
+use conreg_grpc::{ConfigChanged, GetConfigRequest, GetConfigResponse, WatchConfigRequest};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::log;
+
+pub struct ConfigService;
+
+impl From<ConfigEntry> for conreg_grpc::ConfigEntry {
+    fn from(value: ConfigEntry) -> Self {
+        conreg_grpc::ConfigEntry {
+            id: value.id,
+            content: value.content,
+            format: value.format,
+            md5: value.md5,
+        }
+    }
+}
+
+/// 校验命名空间Token，与HTTP接口的`NamespaceAuth`一致
+async fn namespace_auth<T>(request: &Request<T>, namespace_id: &str) -> Result<(), Status> {
+    let token = request
+        .metadata()
+        .get(NS_TOKEN_METADATA)
+        .and_then(|token| token.to_str().ok());
+    match get_app()
+        .namespace_app
+        .manager
+        .auth(namespace_id, token)
+        .await
+    {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Status::unauthenticated("No Permission")),
+        Err(e) => {
+            log::error!("auth error: {}", e);
+            Err(Status::internal("Auth Error"))
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Config for ConfigService {
+    async fn get_config(
+        &self,
+        request: Request<GetConfigRequest>,
+    ) -> Result<Response<GetConfigResponse>, Status> {
+        let namespace_id = request.get_ref().namespace_id.clone();
+        namespace_auth(&request, &namespace_id).await?;
+        let req = request.into_inner();
+        let selector = req
+            .labels
+            .as_deref()
+            .map(parse_labels)
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?
+            .unwrap_or_default();
+        let manager = &get_app().config_app.manager;
+        let entry = manager
+            .get_config_by_labels(&namespace_id, &req.id, &selector)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        if let Some(entry) = &entry {
+            manager.fetch_stats.record(&namespace_id, &entry.id);
+        }
+        Ok(Response::new(GetConfigResponse {
+            entry: entry.map(Into::into),
+        }))
+    }
+
+    type WatchConfigStream = ReceiverStream<Result<ConfigChanged, Status>>;
+
+    /// 监听配置变化
+    ///
+    /// 与长轮询不同，一个流持续推送命名空间下的所有变更，直到客户端断开或会话被强制关闭
+    async fn watch_config(
+        &self,
+        request: Request<WatchConfigRequest>,
+    ) -> Result<Response<Self::WatchConfigStream>, Status> {
+        let remote = request.remote_addr().map(|addr| addr.to_string());
+        let WatchConfigRequest {
+            namespace_id,
+            cursor,
+        } = request.into_inner();
+        let manager = &get_app().config_app.manager;
+        // 先订阅再读取变更日志，读取之后的变更从订阅中收到
+        let mut receiver = manager.subscribe();
+        let watcher = manager.watchers.register(&namespace_id, remote);
+        let journal = &manager.journal;
+        // 补发游标之后的变更，没有需要补发的变更时只发送当前游标
+        let mut initial = vec![];
+        let mut last_revision = 0;
+        match journal.since(&namespace_id, &cursor) {
+            Replay::Changes(changes) if !changes.is_empty() => {
+                log::info!(
+                    "replay {} config changes after cursor {}, namespace id: {}",
+                    changes.len(),
+                    cursor,
+                    namespace_id
+                );
+                for (revision, config_id) in changes {
+                    last_revision = revision;
+                    initial.push(ConfigChanged {
+                        config_id: split_variant_id(&config_id).0.to_string(),
+                        cursor: journal.cursor_at(revision),
+                        reset: false,
+                    });
+                }
+            }
+            replay => {
+                last_revision = journal.revision(&namespace_id);
+                initial.push(ConfigChanged {
+                    config_id: String::new(),
+                    cursor: journal.cursor_at(last_revision),
+                    // 首次监听时没有游标，不需要重新拉取
+                    reset: replay == Replay::Reset && !cursor.is_empty(),
+                });
+            }
+        }
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            for changed in initial {
+                if tx.send(Ok(changed)).await.is_err() {
+                    return;
+                }
+            }
+            loop {
+                let event = tokio::select! {
+                    res = receiver.recv() => res,
+                    _ = tx.closed() => break,
+                    _ = watcher.closed() => {
+                        let _ = tx.send(Err(Status::aborted("watch session closed by server"))).await;
+                        break;
+                    }
+                };
+                match event {
+                    // 已经补发过的变更不再推送
+                    Ok(event)
+                        if event.namespace_id == namespace_id && event.revision > last_revision =>
+                    {
+                        log::info!("config changed, namespace id: {}", event.namespace_id);
+                        // 推送不带标签的配置ID，客户端按配置ID通知监听器
+                        let changed = ConfigChanged {
+                            config_id: split_variant_id(&event.config_id).0.to_string(),
+                            cursor: get_app()
+                                .config_app
+                                .manager
+                                .journal
+                                .cursor_at(event.revision),
+                            reset: false,
+                        };
+                        if tx.send(Ok(changed)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    // 落后时丢失的变更由客户端的定时拉取补偿
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
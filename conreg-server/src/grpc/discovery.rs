@@ -0,0 +1,136 @@
+//! 注册中心gRPC接口
+
+use crate::app::get_app;
+use crate::discovery::{ConflictPolicy, EvictionNotice, HeartbeatResult, ServiceInstance};
+use crate::protocol::res::{CodeError, INSTANCE_CONFLICT_CODE};
+use conreg_grpc::discovery_server::Discovery;
+use conreg_grpc::{
+    GetInstancesRequest, GetInstancesResponse, HeartbeatRequest, HeartbeatResponse, Instance,
+    RegisterRequest,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+pub struct DiscoveryService;
+
+impl From<ServiceInstance> for Instance {
+    fn from(value: ServiceInstance) -> Self {
+        Instance {
+            id: value.id,
+            service_id: value.service_id,
+            ip: value.ip,
+            port: value.port as u32,
+            meta: value.meta,
+            effective_weight: value.effective_weight,
+        }
+    }
+}
+
+impl From<HeartbeatResult> for conreg_grpc::HeartbeatResult {
+    fn from(value: HeartbeatResult) -> Self {
+        match value {
+            HeartbeatResult::Ok => conreg_grpc::HeartbeatResult::Ok,
+            HeartbeatResult::NoInstanceFound => conreg_grpc::HeartbeatResult::NoInstanceFound,
+            HeartbeatResult::Rejected => conreg_grpc::HeartbeatResult::Rejected,
+        }
+    }
+}
+
+impl From<conreg_grpc::ConflictPolicy> for ConflictPolicy {
+    fn from(value: conreg_grpc::ConflictPolicy) -> Self {
+        match value {
+            conreg_grpc::ConflictPolicy::Replace => ConflictPolicy::Replace,
+            conreg_grpc::ConflictPolicy::MergeMeta => ConflictPolicy::MergeMeta,
+            conreg_grpc::ConflictPolicy::Reject => ConflictPolicy::Reject,
+        }
+    }
+}
+
+impl From<EvictionNotice> for conreg_grpc::EvictionNotice {
+    fn from(value: EvictionNotice) -> Self {
+        conreg_grpc::EvictionNotice {
+            reason: value.reason,
+            removed: value.removed,
+            last_heartbeat: value.last_heartbeat.to_rfc3339(),
+            evicted_at: value.evicted_at.map(|time| time.to_rfc3339()),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Discovery for DiscoveryService {
+    async fn register(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<Instance>, Status> {
+        let req = request.into_inner();
+        let port = u16::try_from(req.port).map_err(|_| Status::invalid_argument("Invalid port"))?;
+        let policy = req.conflict_policy().into();
+        let instance = ServiceInstance::new(&req.service_id, &req.ip, port, req.meta);
+        match get_app()
+            .discovery_app
+            .manager
+            .register_service_instance_and_sync(&req.namespace_id, instance, policy)
+            .await
+        {
+            Ok(instance) => Ok(Response::new(instance.into())),
+            Err(e) => match e.downcast_ref::<CodeError>() {
+                Some(e) if e.code == INSTANCE_CONFLICT_CODE => Err(Status::already_exists(&e.msg)),
+                _ => Err(Status::internal(e.to_string())),
+            },
+        }
+    }
+
+    type HeartbeatStream = ReceiverStream<Result<HeartbeatResponse, Status>>;
+
+    /// 接收心跳
+    ///
+    /// 每收到一个心跳请求返回一个响应，处理出错时返回错误并结束流，由客户端重新建立
+    async fn heartbeat(
+        &self,
+        request: Request<Streaming<HeartbeatRequest>>,
+    ) -> Result<Response<Self::HeartbeatStream>, Status> {
+        let mut stream = request.into_inner();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Ok(Some(req)) = stream.message().await {
+                let res = match get_app()
+                    .discovery_app
+                    .manager
+                    .heartbeat_and_sync(&req.namespace_id, &req.service_id, &req.instance_id)
+                    .await
+                {
+                    Ok((result, eviction)) => Ok(HeartbeatResponse {
+                        result: conreg_grpc::HeartbeatResult::from(result).into(),
+                        eviction: eviction.map(Into::into),
+                    }),
+                    Err(e) => Err(Status::internal(e.to_string())),
+                };
+                let failed = res.is_err();
+                if tx.send(res).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_instances(
+        &self,
+        request: Request<GetInstancesRequest>,
+    ) -> Result<Response<GetInstancesResponse>, Status> {
+        let req = request.into_inner();
+        match get_app()
+            .discovery_app
+            .manager
+            .get_available_instances(&req.namespace_id, &req.service_id)
+            .await
+        {
+            Ok(instances) => Ok(Response::new(GetInstancesResponse {
+                instances: instances.into_iter().map(Into::into).collect(),
+            })),
+            Err(e) => Err(Status::internal(e.to_string())),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! gRPC接口
+//!
+//! 监听HTTP端口 + [`GRPC_PORT_OFFSET`]，通过启动参数`--enable-grpc`开启客户端与服务端通信的另一种传输方式：
+//! - 配置中心：获取配置，通过服务端流推送配置变更，替代长轮询
+//! - 注册中心：注册实例、获取可用实例，通过双向流发送心跳，替代每次心跳一个HTTP请求
+//!
+//! 通过`--enable-xds`开启Envoy的ADS、CDS和EDS服务，见[`xds`]。
+//!
+//! 协议定义见`conreg-grpc/proto`。命名空间Token通过`x-ns-token`元数据传递。
+
+use crate::Args;
+use anyhow::Context;
+use conreg_grpc::GRPC_PORT_OFFSET;
+use conreg_grpc::config_server::ConfigServer;
+use conreg_grpc::discovery_server::DiscoveryServer;
+use conreg_grpc::envoy::service::cluster::v3::cluster_discovery_service_server::ClusterDiscoveryServiceServer;
+use conreg_grpc::envoy::service::discovery::v3::aggregated_discovery_service_server::AggregatedDiscoveryServiceServer;
+use conreg_grpc::envoy::service::endpoint::v3::endpoint_discovery_service_server::EndpointDiscoveryServiceServer;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tracing::log;
+
+mod config;
+mod discovery;
+mod xds;
+
+/// 启动gRPC服务
+///
+/// 在当前线程绑定端口，端口被占用时返回错误，服务在后台任务中运行。
+/// 根据`--enable-grpc`和`--enable-xds`注册对应的服务
+pub fn start(args: &Args) -> anyhow::Result<()> {
+    let port = args
+        .port
+        .checked_add(GRPC_PORT_OFFSET)
+        .context("gRPC port out of range")?;
+    let addr = SocketAddr::new(IpAddr::from_str(&args.address)?, port);
+    let incoming = TcpIncoming::bind(addr)
+        .map_err(|e| anyhow::anyhow!("Failed to bind gRPC port {}: {}", port, e))?;
+    log::info!("gRPC server listening on {}", addr);
+    let (enable_grpc, enable_xds) = (args.enable_grpc, args.enable_xds);
+    tokio::spawn(async move {
+        let res = Server::builder()
+            .add_optional_service(enable_grpc.then(|| ConfigServer::new(config::ConfigService)))
+            .add_optional_service(
+                enable_grpc.then(|| DiscoveryServer::new(discovery::DiscoveryService)),
+            )
+            .add_optional_service(
+                enable_xds.then(|| AggregatedDiscoveryServiceServer::new(xds::AggregatedDiscovery)),
+            )
+            .add_optional_service(
+                enable_xds.then(|| ClusterDiscoveryServiceServer::new(xds::ClusterDiscovery)),
+            )
+            .add_optional_service(
+                enable_xds.then(|| EndpointDiscoveryServiceServer::new(xds::EndpointDiscovery)),
+            )
+            .serve_with_incoming(incoming)
+            .await;
+        if let Err(e) = res {
+            log::error!("gRPC server error: {}", e);
+        }
+    });
+    Ok(())
+}
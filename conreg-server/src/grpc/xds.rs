@@ -0,0 +1,546 @@
+//! Envoy xDS gRPC接口（实验性）
+//!
+//! 提供ADS、CDS和EDS服务，使用全量（state-of-the-world）协议，服务和实例的转换规则见[`crate::discovery::server::xds`]。
+//!
+//! 每个流保存各资源类型的订阅，收到请求后以及每隔[`PUSH_INTERVAL`]检查订阅的资源，版本号变化时推送：
+//! - 请求的`response_nonce`不是最近一次响应的nonce时，为过期的请求，忽略
+//! - 请求的`version_info`与最近一次响应不同时，为Envoy拒绝了响应（NACK），记录日志，资源再次变化时才推送
+//!
+//! 命名空间在流的第一个请求中鉴权，只支持集群和端点两种资源，其他类型的订阅被忽略。
+
+use crate::app::get_app;
+use crate::discovery::ServiceInstance;
+use crate::discovery::server::xds::{
+    CLUSTER_TYPE, DEFAULT_NAMESPACE, DEFAULT_XDS_CLUSTER, ENDPOINT_TYPE, health_status,
+    list_service_ids, list_service_instances,
+};
+use conreg_grpc::NS_TOKEN_METADATA;
+use conreg_grpc::envoy::config::cluster::v3::Cluster;
+use conreg_grpc::envoy::config::cluster::v3::cluster::{
+    ClusterDiscoveryType, DiscoveryType, EdsClusterConfig,
+};
+use conreg_grpc::envoy::config::core::v3::api_config_source::ApiType;
+use conreg_grpc::envoy::config::core::v3::config_source::ConfigSourceSpecifier;
+use conreg_grpc::envoy::config::core::v3::grpc_service::{EnvoyGrpc, TargetSpecifier};
+use conreg_grpc::envoy::config::core::v3::socket_address::PortSpecifier;
+use conreg_grpc::envoy::config::core::v3::{
+    Address, AggregatedConfigSource, ApiConfigSource, ApiVersion, ConfigSource, GrpcService, Node,
+    SocketAddress, address,
+};
+use conreg_grpc::envoy::config::endpoint::v3::lb_endpoint::HostIdentifier;
+use conreg_grpc::envoy::config::endpoint::v3::{
+    ClusterLoadAssignment, Endpoint, LbEndpoint, LocalityLbEndpoints,
+};
+use conreg_grpc::envoy::service::cluster::v3::cluster_discovery_service_server::ClusterDiscoveryService;
+use conreg_grpc::envoy::service::discovery::v3::aggregated_discovery_service_server::AggregatedDiscoveryService;
+use conreg_grpc::envoy::service::discovery::v3::{DiscoveryRequest, DiscoveryResponse};
+use conreg_grpc::envoy::service::endpoint::v3::endpoint_discovery_service_server::EndpointDiscoveryService;
+use prost::Message;
+use prost_types::Any;
+use prost_types::value::Kind;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::log;
+
+/// 检查订阅的资源是否变化的间隔
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct AggregatedDiscovery;
+
+pub struct ClusterDiscovery;
+
+pub struct EndpointDiscovery;
+
+type DiscoveryStream = ReceiverStream<Result<DiscoveryResponse, Status>>;
+
+#[tonic::async_trait]
+impl AggregatedDiscoveryService for AggregatedDiscovery {
+    type StreamAggregatedResourcesStream = DiscoveryStream;
+
+    async fn stream_aggregated_resources(
+        &self,
+        request: Request<Streaming<DiscoveryRequest>>,
+    ) -> Result<Response<DiscoveryStream>, Status> {
+        serve(request, Transport::Ads)
+    }
+}
+
+#[tonic::async_trait]
+impl ClusterDiscoveryService for ClusterDiscovery {
+    type StreamClustersStream = DiscoveryStream;
+
+    async fn stream_clusters(
+        &self,
+        request: Request<Streaming<DiscoveryRequest>>,
+    ) -> Result<Response<DiscoveryStream>, Status> {
+        serve(request, Transport::Cds)
+    }
+}
+
+#[tonic::async_trait]
+impl EndpointDiscoveryService for EndpointDiscovery {
+    type StreamEndpointsStream = DiscoveryStream;
+
+    async fn stream_endpoints(
+        &self,
+        request: Request<Streaming<DiscoveryRequest>>,
+    ) -> Result<Response<DiscoveryStream>, Status> {
+        serve(request, Transport::Eds)
+    }
+}
+
+/// 流所属的xDS服务
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transport {
+    /// 聚合服务，集群和端点在同一个流中
+    Ads,
+    /// 集群发现服务
+    Cds,
+    /// 端点发现服务
+    Eds,
+}
+
+impl Transport {
+    /// 请求的资源类型，单一资源的服务允许请求不带类型，不支持的类型返回None
+    fn type_url(&self, type_url: &str) -> Option<&'static str> {
+        match (self, type_url) {
+            (Transport::Cds, "") => Some(CLUSTER_TYPE),
+            (Transport::Eds, "") => Some(ENDPOINT_TYPE),
+            (Transport::Ads | Transport::Cds, CLUSTER_TYPE) => Some(CLUSTER_TYPE),
+            (Transport::Ads | Transport::Eds, ENDPOINT_TYPE) => Some(ENDPOINT_TYPE),
+            _ => None,
+        }
+    }
+}
+
+/// 处理一个xDS流，直到客户端断开或鉴权失败
+fn serve(
+    request: Request<Streaming<DiscoveryRequest>>,
+    transport: Transport,
+) -> Result<Response<DiscoveryStream>, Status> {
+    let token = request
+        .metadata()
+        .get(NS_TOKEN_METADATA)
+        .and_then(|token| token.to_str().ok())
+        .map(str::to_string);
+    let mut requests = request.into_inner();
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut session = Session::new(transport, token);
+        let mut ticker = tokio::time::interval(PUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                req = requests.message() => match req {
+                    Ok(Some(req)) => {
+                        if let Err(status) = session.accept(req).await {
+                            let _ = tx.send(Err(status)).await;
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::debug!("xds stream error: {}", e);
+                        break;
+                    }
+                },
+                _ = ticker.tick() => {}
+                _ = tx.closed() => break,
+            }
+            // 查询失败时等下一次检查再推送
+            let responses = match session.changed().await {
+                Ok(responses) => responses,
+                Err(e) => {
+                    log::error!("xds resources error: {}", e);
+                    continue;
+                }
+            };
+            for res in responses {
+                if tx.send(Ok(res)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(Response::new(ReceiverStream::new(rx)))
+}
+
+/// 一个xDS流的状态
+struct Session {
+    transport: Transport,
+    /// 通过`x-ns-token`元数据传递的命名空间Token，优先于节点元数据中的Token
+    token: Option<String>,
+    /// 节点所属的命名空间，第一个请求鉴权通过后设置
+    namespace_id: Option<String>,
+    /// Envoy中指向conreg的集群名
+    xds_cluster: String,
+    /// 各资源类型的订阅，key为资源类型
+    subscriptions: HashMap<&'static str, Subscription>,
+    /// 最近一次响应的nonce
+    nonce: u64,
+}
+
+/// 资源类型的订阅
+#[derive(Debug, Default)]
+struct Subscription {
+    /// 订阅的资源名，为空时订阅全部资源
+    resource_names: Vec<String>,
+    /// 最近一次响应的版本号
+    version: String,
+    /// 最近一次响应的nonce
+    nonce: String,
+}
+
+impl Session {
+    fn new(transport: Transport, token: Option<String>) -> Self {
+        Session {
+            transport,
+            token,
+            namespace_id: None,
+            xds_cluster: DEFAULT_XDS_CLUSTER.to_string(),
+            subscriptions: HashMap::new(),
+            nonce: 0,
+        }
+    }
+
+    /// 处理请求，更新订阅
+    async fn accept(&mut self, req: DiscoveryRequest) -> Result<(), Status> {
+        if self.namespace_id.is_none() {
+            self.auth(req.node.as_ref()).await?;
+        }
+        let Some(type_url) = self.transport.type_url(&req.type_url) else {
+            log::debug!("ignore unsupported xds resource type: {}", req.type_url);
+            return Ok(());
+        };
+        let subscription = self.subscriptions.entry(type_url).or_default();
+        if !req.response_nonce.is_empty() {
+            if req.response_nonce != subscription.nonce {
+                return Ok(());
+            }
+            if req.version_info != subscription.version {
+                log::warn!(
+                    "xds response {} of {} rejected by node: {:?}",
+                    subscription.version,
+                    type_url,
+                    req.node.map(|node| node.id)
+                );
+            }
+        }
+        if subscription.resource_names != req.resource_names {
+            subscription.resource_names = req.resource_names;
+            // 订阅的资源变化，重新推送
+            subscription.version.clear();
+        }
+        Ok(())
+    }
+
+    /// 校验节点的命名空间Token
+    async fn auth(&mut self, node: Option<&Node>) -> Result<(), Status> {
+        let namespace_id =
+            node_metadata(node, "namespace_id").unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+        let token = self
+            .token
+            .clone()
+            .or_else(|| node_metadata(node, "ns_token"));
+        match get_app()
+            .namespace_app
+            .manager
+            .auth(&namespace_id, token.as_deref())
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                log::warn!(
+                    "xds node {:?} has no permission to {}",
+                    node.map(|node| &node.id),
+                    namespace_id
+                );
+                return Err(Status::unauthenticated("No Permission"));
+            }
+            Err(e) => {
+                log::error!("auth namespace {} error: {}", namespace_id, e);
+                return Err(Status::internal("Auth Error"));
+            }
+        }
+        if let Some(xds_cluster) = node_metadata(node, "xds_cluster") {
+            self.xds_cluster = xds_cluster;
+        }
+        self.namespace_id = Some(namespace_id);
+        Ok(())
+    }
+
+    /// 版本号变化的订阅对应的响应
+    async fn changed(&mut self) -> anyhow::Result<Vec<DiscoveryResponse>> {
+        let Some(namespace_id) = &self.namespace_id else {
+            return Ok(vec![]);
+        };
+        let mut responses = vec![];
+        for (type_url, subscription) in self.subscriptions.iter_mut() {
+            let resources = if *type_url == CLUSTER_TYPE {
+                list_service_ids(namespace_id, &subscription.resource_names)
+                    .await?
+                    .iter()
+                    .map(|service_id| {
+                        to_any(
+                            CLUSTER_TYPE,
+                            &cluster(service_id, self.transport, &self.xds_cluster),
+                        )
+                    })
+                    .collect()
+            } else {
+                list_service_instances(namespace_id, &subscription.resource_names)
+                    .await?
+                    .iter()
+                    .map(|(service_id, instances)| {
+                        to_any(ENDPOINT_TYPE, &load_assignment(service_id, instances))
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let version = version(&resources);
+            if version == subscription.version {
+                continue;
+            }
+            self.nonce += 1;
+            subscription.version = version.clone();
+            subscription.nonce = self.nonce.to_string();
+            responses.push(DiscoveryResponse {
+                version_info: version,
+                resources,
+                type_url: type_url.to_string(),
+                nonce: subscription.nonce.clone(),
+            });
+        }
+        Ok(responses)
+    }
+}
+
+/// 节点元数据中的字符串字段
+fn node_metadata(node: Option<&Node>, key: &str) -> Option<String> {
+    match node?.metadata.as_ref()?.fields.get(key)?.kind.as_ref()? {
+        Kind::StringValue(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn to_any(type_url: &str, message: &impl Message) -> Any {
+    Any {
+        type_url: type_url.to_string(),
+        value: message.encode_to_vec(),
+    }
+}
+
+/// 资源的版本号，为编码后内容的摘要
+fn version(resources: &[Any]) -> String {
+    let mut context = md5::Context::new();
+    for resource in resources {
+        context.consume(&resource.value);
+    }
+    format!("{:x}", context.finalize())
+}
+
+/// 服务对应的集群，端点通过EDS从conreg获取
+fn cluster(service_id: &str, transport: Transport, xds_cluster: &str) -> Cluster {
+    let specifier = match transport {
+        // 通过ADS获取的集群，端点也通过ADS获取
+        Transport::Ads => ConfigSourceSpecifier::Ads(AggregatedConfigSource {}),
+        _ => ConfigSourceSpecifier::ApiConfigSource(ApiConfigSource {
+            api_type: ApiType::Grpc.into(),
+            transport_api_version: ApiVersion::V3.into(),
+            grpc_services: vec![GrpcService {
+                target_specifier: Some(TargetSpecifier::EnvoyGrpc(EnvoyGrpc {
+                    cluster_name: xds_cluster.to_string(),
+                })),
+            }],
+        }),
+    };
+    Cluster {
+        name: service_id.to_string(),
+        cluster_discovery_type: Some(ClusterDiscoveryType::Type(DiscoveryType::Eds.into())),
+        eds_cluster_config: Some(EdsClusterConfig {
+            eds_config: Some(ConfigSource {
+                resource_api_version: ApiVersion::V3.into(),
+                config_source_specifier: Some(specifier),
+            }),
+            service_name: service_id.to_string(),
+        }),
+        connect_timeout: Some(prost_types::Duration {
+            seconds: 5,
+            nanos: 0,
+        }),
+    }
+}
+
+fn load_assignment(service_id: &str, instances: &[ServiceInstance]) -> ClusterLoadAssignment {
+    let lb_endpoints = instances
+        .iter()
+        .map(|instance| LbEndpoint {
+            host_identifier: Some(HostIdentifier::Endpoint(Endpoint {
+                address: Some(Address {
+                    address: Some(address::Address::SocketAddress(SocketAddress {
+                        address: instance.ip.clone(),
+                        port_specifier: Some(PortSpecifier::PortValue(instance.port as u32)),
+                        ..Default::default()
+                    })),
+                }),
+            })),
+            health_status: health_status(instance.status()).into(),
+            load_balancing_weight: Some(instance.weight().clamp(1, u32::MAX as u64) as u32),
+        })
+        .collect();
+    ClusterLoadAssignment {
+        cluster_name: service_id.to_string(),
+        endpoints: vec![LocalityLbEndpoints { lb_endpoints }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use conreg_grpc::envoy::config::core::v3::HealthStatus;
+    use prost_types::{Struct, Value};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_type_url() {
+        assert_eq!(Transport::Ads.type_url(CLUSTER_TYPE), Some(CLUSTER_TYPE));
+        assert_eq!(Transport::Ads.type_url(ENDPOINT_TYPE), Some(ENDPOINT_TYPE));
+        assert_eq!(Transport::Ads.type_url(""), None);
+        assert_eq!(Transport::Cds.type_url(""), Some(CLUSTER_TYPE));
+        assert_eq!(Transport::Cds.type_url(ENDPOINT_TYPE), None);
+        assert_eq!(Transport::Eds.type_url(""), Some(ENDPOINT_TYPE));
+        // 不支持的资源类型
+        assert_eq!(
+            Transport::Ads.type_url("type.googleapis.com/envoy.config.listener.v3.Listener"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resources() {
+        // ADS的集群通过ADS获取端点，CDS的集群通过xds_cluster获取端点
+        let ads = cluster("svc", Transport::Ads, "conreg");
+        let source = ads.eds_cluster_config.unwrap().eds_config.unwrap();
+        assert!(matches!(
+            source.config_source_specifier,
+            Some(ConfigSourceSpecifier::Ads(_))
+        ));
+        let cds = cluster("svc", Transport::Cds, "xds");
+        let source = cds.eds_cluster_config.unwrap().eds_config.unwrap();
+        let Some(ConfigSourceSpecifier::ApiConfigSource(api)) = source.config_source_specifier
+        else {
+            panic!("expect api config source");
+        };
+        assert_eq!(
+            api.grpc_services[0].target_specifier,
+            Some(TargetSpecifier::EnvoyGrpc(EnvoyGrpc {
+                cluster_name: "xds".to_string()
+            }))
+        );
+
+        let instance = ServiceInstance::new("svc", "10.0.0.1", 8080, HashMap::new());
+        let assignment = load_assignment("svc", &[instance]);
+        let endpoint = &assignment.endpoints[0].lb_endpoints[0];
+        assert_eq!(endpoint.health_status, HealthStatus::Unknown as i32);
+        assert!(endpoint.load_balancing_weight.unwrap() >= 1);
+
+        // 编码后可以还原，版本号随内容变化
+        let any = to_any(ENDPOINT_TYPE, &assignment);
+        assert_eq!(
+            ClusterLoadAssignment::decode(any.value.as_slice()).unwrap(),
+            assignment
+        );
+        let empty = to_any(ENDPOINT_TYPE, &load_assignment("svc", &[]));
+        let resources = vec![any];
+        assert_ne!(version(&resources), version(&[empty]));
+        assert_eq!(version(&resources), version(&resources.clone()));
+    }
+
+    #[tokio::test]
+    async fn test_accept() {
+        let mut session = Session::new(Transport::Ads, None);
+        // 跳过鉴权
+        session.namespace_id = Some("public".to_string());
+        let subscription = |session: &mut Session| session.subscriptions.remove(CLUSTER_TYPE);
+
+        let req = DiscoveryRequest {
+            type_url: CLUSTER_TYPE.to_string(),
+            resource_names: vec!["svc".to_string()],
+            ..Default::default()
+        };
+        session.accept(req.clone()).await.unwrap();
+        let sub = subscription(&mut session).unwrap();
+        assert_eq!(sub.resource_names, vec!["svc".to_string()]);
+
+        // 过期的nonce不更新订阅
+        session.subscriptions.insert(
+            CLUSTER_TYPE,
+            Subscription {
+                resource_names: vec![],
+                version: "v1".to_string(),
+                nonce: "2".to_string(),
+            },
+        );
+        let stale = DiscoveryRequest {
+            response_nonce: "1".to_string(),
+            ..req.clone()
+        };
+        session.accept(stale).await.unwrap();
+        assert!(
+            session.subscriptions[CLUSTER_TYPE]
+                .resource_names
+                .is_empty()
+        );
+
+        // 订阅的资源变化时清空版本号，重新推送
+        let ack = DiscoveryRequest {
+            response_nonce: "2".to_string(),
+            version_info: "v1".to_string(),
+            ..req
+        };
+        session.accept(ack).await.unwrap();
+        let sub = subscription(&mut session).unwrap();
+        assert_eq!(sub.resource_names, vec!["svc".to_string()]);
+        assert!(sub.version.is_empty());
+
+        // 不支持的资源类型被忽略
+        let listener = DiscoveryRequest {
+            type_url: "type.googleapis.com/envoy.config.listener.v3.Listener".to_string(),
+            ..Default::default()
+        };
+        session.accept(listener).await.unwrap();
+        assert!(session.subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_node_metadata() {
+        let node = Node {
+            id: "sidecar-1".to_string(),
+            metadata: Some(Struct {
+                fields: BTreeMap::from([
+                    (
+                        "namespace_id".to_string(),
+                        Value {
+                            kind: Some(Kind::StringValue("dev".to_string())),
+                        },
+                    ),
+                    (
+                        "weight".to_string(),
+                        Value {
+                            kind: Some(Kind::NumberValue(1.0)),
+                        },
+                    ),
+                ]),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            node_metadata(Some(&node), "namespace_id"),
+            Some("dev".to_string())
+        );
+        assert_eq!(node_metadata(Some(&node), "weight"), None);
+        assert_eq!(node_metadata(Some(&node), "ns_token"), None);
+        assert_eq!(node_metadata(None, "namespace_id"), None);
+    }
+}
@@ -14,14 +14,25 @@ use std::path::Path;
 use std::str::FromStr;
 use tracing::log;
 
+mod access_log;
+mod annotation;
 mod app;
+mod backup;
+mod bootstrap;
+mod chaos;
 mod config;
 mod db;
 mod discovery;
+mod disk;
 mod event;
+mod grpc;
+mod metrics;
 mod namespace;
+mod openapi;
 mod protocol;
 mod raft;
+mod schedule;
+mod webhook;
 
 mod auth;
 mod cache;
@@ -49,6 +60,98 @@ pub struct Args {
     /// Whether to enable configuration cache
     #[arg(long, default_value_t = false)]
     enable_cache_config: bool,
+    /// Whether to enable access log, written to `{data_dir}/logs/access.log`
+    #[arg(long, default_value_t = false)]
+    access_log: bool,
+    /// Access log sample rate, between 0 and 1. Requests with status >= 400 are always logged
+    #[arg(long, default_value_t = 1.0)]
+    access_log_sample_rate: f64,
+    /// Path prefixes excluded from access log, separated by commas
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "/api/cluster/append,/api/cluster/vote,/api/cluster/snapshot"
+    )]
+    access_log_exclude: Vec<String>,
+    /// Webhook URLs to receive server events, separated by commas
+    #[arg(long, value_delimiter = ',')]
+    webhook_url: Vec<String>,
+    /// Minutes a service must stay below its expected instance count before it is reported as degraded
+    #[arg(long, default_value_t = 3)]
+    service_degraded_minutes: u64,
+    /// Free space of the data directory's disk, in MB, below which a warning is logged
+    #[arg(long, default_value_t = 1024)]
+    disk_warn_free_mb: u64,
+    /// Free space of the data directory's disk, in MB, below which the node rejects writes
+    #[arg(long, default_value_t = 256)]
+    disk_critical_free_mb: u64,
+    /// Days to keep raft logs before they are purged, regardless of snapshot policy. Disabled if not set
+    #[arg(long)]
+    raft_log_retention_days: Option<u64>,
+    /// Serve reads only and reject all writes, e.g. for replicas exposed in a DMZ.
+    /// Only supported in cluster mode. The node never starts an election, so it does not become the leader;
+    /// joining the cluster as a learner is recommended
+    #[arg(long, default_value_t = false)]
+    read_only: bool,
+    /// Enable the experimental Envoy xDS services: ADS, CDS and EDS over gRPC on the HTTP port + 1000,
+    /// and the REST-JSON CDS/EDS endpoints under `/v3`
+    #[arg(long, default_value_t = false)]
+    enable_xds: bool,
+    /// Enable the gRPC transport for clients, listening on the HTTP port + 1000
+    #[arg(long, default_value_t = false)]
+    enable_grpc: bool,
+    /// Enable UDP heartbeats for clients, listening on the same address and port as HTTP over UDP
+    #[arg(long, default_value_t = false)]
+    enable_udp_heartbeat: bool,
+    /// OTLP/HTTP logs endpoint receiving config publish and rollback events, e.g. `http://otel-collector:4318/v1/logs`
+    #[arg(long)]
+    annotation_otlp_url: Option<String>,
+    /// Grafana URL where config publish and rollback events are written as annotations
+    #[arg(long)]
+    annotation_grafana_url: Option<String>,
+    /// Grafana service account token used to write annotations
+    #[arg(long)]
+    annotation_grafana_token: Option<String>,
+    /// Shared secret for node-to-node authentication, must be the same on all nodes.
+    /// Used until the cluster stores its own secret on init, and accepted afterwards as well
+    #[arg(long)]
+    cluster_secret: Option<String>,
+    /// Developer mode for resilience testing, exposes `/api/chaos` to inject raft network delays and drops,
+    /// to force leadership changes and to export or seed discovery state. Never enable in production
+    #[arg(long, default_value_t = false)]
+    chaos: bool,
+    /// S3-compatible endpoint config backups are written to, e.g. `http://minio:9000`. Backup is disabled if not set
+    #[arg(long)]
+    backup_s3_endpoint: Option<String>,
+    /// Bucket config backups are written to, must already exist
+    #[arg(long, default_value = "conreg-backup")]
+    backup_s3_bucket: String,
+    /// Region used to sign S3 requests
+    #[arg(long, default_value = "us-east-1")]
+    backup_s3_region: String,
+    /// Access key of the backup bucket
+    #[arg(long)]
+    backup_s3_access_key: Option<String>,
+    /// Secret key of the backup bucket
+    #[arg(long)]
+    backup_s3_secret_key: Option<String>,
+    /// Key prefix of config backups in the bucket
+    #[arg(long, default_value = "conreg")]
+    backup_s3_prefix: String,
+    /// Minutes between two scheduled config backups, taken by the leader node
+    #[arg(long, default_value_t = 1440)]
+    backup_interval_minutes: u64,
+    /// Number of config backups to keep, older ones are deleted after each backup
+    #[arg(long, default_value_t = 7)]
+    backup_retention: usize,
+    /// Master key wrapping the per-namespace data keys that encrypt config content at rest,
+    /// 32 bytes hex encoded and the same on all nodes. Falls back to the `CONREG_MASTER_KEY` environment variable
+    #[arg(long)]
+    master_key: Option<String>,
+    /// Key of `DEC(...)` config values, the same as the `decrypt-key` of clients, used by the console
+    /// to encrypt and decrypt values. 32 bytes hex encoded. Falls back to the `CONREG_DECRYPT_KEY` environment variable
+    #[arg(long)]
+    decrypt_key: Option<String>,
 }
 
 #[derive(Parser, Debug, Clone, ValueEnum)]
@@ -80,6 +183,14 @@ impl Args {
             anyhow::bail!("Node ID must be greater than 0");
         }
 
+        if matches!(self.mode, Mode::Standalone) && self.read_only {
+            anyhow::bail!("Read-only mode is not supported in standalone mode");
+        }
+
+        if !(0f64..=1f64).contains(&self.access_log_sample_rate) {
+            anyhow::bail!("Access log sample rate must be between 0 and 1");
+        }
+
         Ok(())
     }
 }
@@ -104,9 +215,37 @@ async fn main() -> anyhow::Result<()> {
     // 初始化缓存
     cache::init(&args)?;
 
+    // 初始化节点间鉴权
+    auth::cluster::init(&args)?;
+
+    // 初始化Webhook
+    webhook::init(&args)?;
+
+    // 初始化配置变更标注
+    annotation::init(&args)?;
+
+    // 启动磁盘空间检查
+    disk::init(&args);
+
+    // 初始化故障注入
+    chaos::init(&args);
+
+    // 初始化配置备份
+    backup::init(&args)?;
+
     // 初始化app
     app::init().await?;
 
+    // 启动gRPC服务
+    if args.enable_grpc || args.enable_xds {
+        grpc::start(&args)?;
+    }
+
+    // 启动UDP心跳监听
+    if args.enable_udp_heartbeat {
+        discovery::server::udp::start(&args.address, args.port).await?;
+    }
+
     start_http_server(&args).await?;
 
     app::cleanup();
@@ -130,7 +269,32 @@ async fn start_http_server(args: &Args) -> anyhow::Result<()> {
     builder = builder.mount("/api/config", config::server::api::routes());
     builder = builder.mount("/api/namespace", namespace::server::api::routes());
     builder = builder.mount("/api/discovery", discovery::server::api::routes());
+    builder = builder.mount("/api/bootstrap", bootstrap::server::api::routes());
     builder = builder.mount("/api/system", system::api::routes());
+    builder = builder.mount("/api/metrics", metrics::api::routes());
+    builder = builder.mount("/api/dead_letter", event::api::routes());
+    builder = builder.mount("/api/event", event::api::journal_routes());
+    builder = builder.mount("/api/backup", backup::api::routes());
+    builder = builder.mount(
+        "/api/integrations/prometheus",
+        discovery::server::prometheus::routes(),
+    );
+    builder = builder.mount("/api", openapi::api::routes());
+    // Spring Cloud Config Server兼容接口
+    builder = builder.mount("/config", config::server::spring::routes());
+    // Consul兼容接口
+    builder = builder.mount("/v1", discovery::server::consul::routes());
+    // Envoy xDS接口
+    if args.enable_xds {
+        builder = builder.mount("/v3", discovery::server::xds::routes());
+    }
+    // 故障注入接口
+    if args.chaos {
+        builder = builder.mount("/api/chaos", chaos::api::routes());
+    }
+
+    // 根据已挂载的路由生成OpenAPI文档
+    openapi::init(builder.routes());
 
     // 前端
     #[cfg(not(debug_assertions))]
@@ -140,6 +304,11 @@ async fn start_http_server(args: &Args) -> anyhow::Result<()> {
 
     //builder = builder.manage(App::new(&args).await);
 
+    // 访问日志
+    if let Some(access_log) = access_log::AccessLog::new(args)? {
+        builder = builder.attach(access_log);
+    }
+
     let args_clone = args.clone();
     builder = builder.attach(AdHoc::on_liftoff("Post-startup tasks", move |_| {
         Box::pin(async move {
@@ -248,5 +417,33 @@ pub(crate) async fn after_http_server_start(args: &Args) -> anyhow::Result<()> {
         }
     }
 
+    // 将当前节点注册到注册中心，只读节点无法写入，不注册
+    if !args.read_only {
+        discovery::server::self_register::start(args);
+    }
+
+    // 定时汇总配置获取统计
+    get_app().config_app.manager.fetch_stats.start_flush_timer();
+
+    // 实例心跳检查和清理
+    get_app().discovery_app.manager.start_instance_check();
+
+    // 服务可用性监控
+    get_app().discovery_app.manager.start_monitor();
+
+    // 主动健康检查
+    get_app().discovery_app.manager.start_health_check();
+
+    // 命名空间资源使用情况采样
+    get_app().namespace_app.manager.start_usage_sampler();
+
+    // 按时间清理Raft日志
+    if let Some(days) = args.raft_log_retention_days {
+        raft::purge::start_purge_task(days);
+    }
+
+    // 定时备份配置
+    backup::start_backup_task();
+
     Ok(())
 }
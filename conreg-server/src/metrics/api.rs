@@ -0,0 +1,22 @@
+use crate::metrics::metrics;
+use crate::openapi::ApiDoc;
+use rocket::http::ContentType;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![prometheus]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![ApiDoc::new("prometheus", "导出Prometheus格式的运行指标")]
+}
+
+/// 导出Prometheus格式的运行指标
+///
+/// 示例：`curl http://127.0.0.1:8000/api/metrics`
+#[get("/")]
+async fn prometheus() -> (ContentType, String) {
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        metrics().render(),
+    )
+}
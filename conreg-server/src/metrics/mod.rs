@@ -0,0 +1,274 @@
+//! 运行指标
+//!
+//! 以Prometheus文本格式导出，主要关注：
+//! - 事件总线的队列深度、排队耗时
+//! - 状态机应用日志的数量和耗时
+//! - 各类请求在事件处理中的耗时和失败次数
+//! - Raft日志与状态机之间的应用延迟
+
+use crate::app::get_app;
+use crate::event::dead_letter;
+use dashmap::DashMap;
+use std::fmt::Write;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+pub mod api;
+
+/// 耗时统计，记录次数、总耗时和失败次数
+#[derive(Debug, Default)]
+struct Timing {
+    count: AtomicU64,
+    /// 总耗时，单位微秒
+    sum_micros: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Timing {
+    fn observe(&self, elapsed: Duration, success: bool) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        if !success {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// 事件总线中等待处理的事件数量，key为事件分类
+    event_queue_depth: DashMap<&'static str, AtomicI64>,
+    /// 因队列已满被丢弃的事件数量，key为事件分类
+    event_shed: DashMap<&'static str, AtomicU64>,
+    /// 因重复被合并的事件数量，key为事件分类
+    event_merged: DashMap<&'static str, AtomicU64>,
+    /// 事件在队列中的等待耗时
+    event_wait: Timing,
+    /// 事件处理耗时，key为请求类型
+    event_handle: DashMap<&'static str, Timing>,
+    /// 状态机应用日志耗时，key为请求类型
+    raft_apply: DashMap<&'static str, Timing>,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    /// 事件入队
+    pub fn event_enqueued(&self, class: &'static str) {
+        self.event_queue_depth
+            .entry(class)
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 事件出队
+    pub fn event_dequeued(&self, class: &'static str, wait: Duration) {
+        self.event_queue_depth
+            .entry(class)
+            .or_default()
+            .fetch_sub(1, Ordering::Relaxed);
+        self.event_wait.observe(wait, true);
+    }
+
+    /// 事件因队列已满被丢弃
+    pub fn event_shed(&self, class: &'static str) {
+        self.event_shed
+            .entry(class)
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 事件因重复被合并
+    pub fn event_merged(&self, class: &'static str) {
+        self.event_merged
+            .entry(class)
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录事件处理结果
+    pub fn observe_event_handle(&self, cmd: &'static str, elapsed: Duration, success: bool) {
+        self.event_handle
+            .entry(cmd)
+            .or_default()
+            .observe(elapsed, success);
+    }
+
+    /// 记录状态机应用日志结果
+    pub fn observe_raft_apply(&self, cmd: &'static str, elapsed: Duration, success: bool) {
+        self.raft_apply
+            .entry(cmd)
+            .or_default()
+            .observe(elapsed, success);
+    }
+
+    /// 渲染为Prometheus文本格式
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        write_by_class(
+            &mut out,
+            "conreg_event_queue_depth",
+            "Number of events waiting in the event bus, by event class",
+            "gauge",
+            &self.event_queue_depth,
+        );
+        write_by_class(
+            &mut out,
+            "conreg_event_shed_total",
+            "Number of events dropped because the queue was full, by event class",
+            "counter",
+            &self.event_shed,
+        );
+        write_by_class(
+            &mut out,
+            "conreg_event_merged_total",
+            "Number of duplicate events merged while queued, by event class",
+            "counter",
+            &self.event_merged,
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP conreg_event_wait_seconds Time events spent waiting in the event bus"
+        );
+        let _ = writeln!(out, "# TYPE conreg_event_wait_seconds summary");
+        write_timing(
+            &mut out,
+            "conreg_event_wait_seconds",
+            None,
+            &self.event_wait,
+        );
+
+        write_timings(
+            &mut out,
+            "conreg_event_handle",
+            "Time spent handling events, by request type",
+            &self.event_handle,
+        );
+        write_timings(
+            &mut out,
+            "conreg_raft_apply",
+            "Time spent applying raft log entries to the state machine, by request type",
+            &self.raft_apply,
+        );
+
+        gauge(
+            &mut out,
+            "conreg_dead_letter_count",
+            "Number of failed events waiting in the dead letter queue",
+            dead_letter::store().count(),
+        );
+
+        let raft_metrics = get_app().raft.metrics().borrow().clone();
+        let last_log_index = raft_metrics.last_log_index.unwrap_or_default();
+        let last_applied = raft_metrics
+            .last_applied
+            .map(|log_id| log_id.index)
+            .unwrap_or_default();
+        gauge(
+            &mut out,
+            "conreg_raft_last_log_index",
+            "Index of the last raft log entry",
+            last_log_index,
+        );
+        gauge(
+            &mut out,
+            "conreg_raft_last_applied_index",
+            "Index of the last raft log entry applied to the state machine",
+            last_applied,
+        );
+        gauge(
+            &mut out,
+            "conreg_raft_apply_lag",
+            "Number of raft log entries not yet applied to the state machine",
+            last_log_index.saturating_sub(last_applied),
+        );
+        gauge(
+            &mut out,
+            "conreg_raft_is_leader",
+            "Whether this node is the raft leader",
+            (raft_metrics.current_leader == Some(raft_metrics.id)) as u8,
+        );
+
+        out
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn write_by_class<T: std::fmt::Debug>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    values: &DashMap<&'static str, T>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let mut classes = values.iter().map(|e| *e.key()).collect::<Vec<_>>();
+    classes.sort();
+    for class in classes {
+        if let Some(value) = values.get(class) {
+            let _ = writeln!(out, "{}{{class=\"{}\"}} {:?}", name, class, *value);
+        }
+    }
+}
+
+fn write_timings(
+    out: &mut String,
+    prefix: &str,
+    help: &str,
+    timings: &DashMap<&'static str, Timing>,
+) {
+    let name = format!("{}_seconds", prefix);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} summary", name);
+    let mut cmds = timings.iter().map(|e| *e.key()).collect::<Vec<_>>();
+    cmds.sort();
+    for cmd in &cmds {
+        if let Some(timing) = timings.get(cmd) {
+            write_timing(out, &name, Some(cmd), &timing);
+        }
+    }
+
+    let name = format!("{}_failures_total", prefix);
+    let _ = writeln!(out, "# HELP {} Number of failures, by request type", name);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for cmd in &cmds {
+        if let Some(timing) = timings.get(cmd) {
+            let _ = writeln!(
+                out,
+                "{}{{cmd=\"{}\"}} {}",
+                name,
+                cmd,
+                timing.failures.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
+
+fn write_timing(out: &mut String, name: &str, cmd: Option<&str>, timing: &Timing) {
+    let labels = cmd
+        .map(|cmd| format!("{{cmd=\"{}\"}}", cmd))
+        .unwrap_or_default();
+    let sum = timing.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000f64;
+    let _ = writeln!(out, "{}_sum{} {}", name, labels, sum);
+    let _ = writeln!(
+        out,
+        "{}_count{} {}",
+        name,
+        labels,
+        timing.count.load(Ordering::Relaxed)
+    );
+}
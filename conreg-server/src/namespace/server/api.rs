@@ -1,16 +1,44 @@
 use crate::app::get_app;
 use crate::auth::UserPrincipal;
 use crate::namespace::server::Namespace;
+use crate::namespace::server::usage::NamespaceUsageReport;
+use crate::openapi::ApiDoc;
 use crate::protocol::res::{PageRes, Res};
 use crate::system::UserPermission;
 use rocket::serde::json::Json;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub fn routes() -> Vec<rocket::Route> {
-    routes![upsert, delete, list]
+    routes![upsert, delete, list, suspend, usage]
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("upsert", "创建或更新命名空间")
+            .auth()
+            .body::<UpsertConfigReq>()
+            .response::<()>(),
+        ApiDoc::new("delete", "删除命名空间")
+            .auth()
+            .body::<DeleteConfigReq>()
+            .response::<()>(),
+        ApiDoc::new("list", "分页查询命名空间列表")
+            .auth()
+            .response::<PageRes<Namespace>>(),
+        ApiDoc::new("suspend", "暂停或恢复命名空间")
+            .auth()
+            .body::<SuspendNamespaceReq>()
+            .response::<()>(),
+        ApiDoc::new("usage", "查询命名空间资源使用情况")
+            .auth()
+            .optional(&["namespace_id"])
+            .response::<Vec<NamespaceUsageReport>>(),
+    ]
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "UpsertNamespaceReq")]
 struct UpsertConfigReq {
     id: String,
     name: String,
@@ -18,10 +46,17 @@ struct UpsertConfigReq {
     is_auth: bool,
     auth_token: Option<String>,
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "DeleteNamespaceReq")]
 struct DeleteConfigReq {
     id: String,
 }
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct SuspendNamespaceReq {
+    id: String,
+    /// true为暂停，false为恢复
+    suspended: bool,
+}
 
 /// 创建或更新命名空间
 /// 如果是新建命名空间，自动给当前用户赋予读写权限
@@ -31,7 +66,7 @@ async fn upsert(req: Json<UpsertConfigReq>, user: UserPrincipal) -> Res<()> {
     // 先看看是否存在
     let exists = match manager.exists_namespace(&req.id).await {
         Ok(exists) => exists,
-        Err(e) => return Res::error(&e.to_string()),
+        Err(e) => return Res::from_error(&e),
     };
     // 创建或更新命名空间
     match manager
@@ -45,7 +80,7 @@ async fn upsert(req: Json<UpsertConfigReq>, user: UserPrincipal) -> Res<()> {
         .await
     {
         Ok(is_new) => is_new,
-        Err(e) => return Res::error(&e.to_string()),
+        Err(e) => return Res::from_error(&e),
     };
 
     // 新建命名空间时，给当前用户自动赋予读写权限
@@ -84,12 +119,12 @@ async fn delete(req: Json<DeleteConfigReq>, _user: UserPrincipal) -> Res<()> {
         .delete_namespace_and_sync(&req.id)
         .await
     {
-        return Res::error(&e.to_string());
+        return Res::from_error(&e);
     }
 
     // 清理所有用户的该命名空间的权限
     if let Err(e) = crate::system::clean_ns_permissions_and_sync(&req.id).await {
-        return Res::error(&e.to_string());
+        return Res::from_error(&e);
     }
 
     Res::success(())
@@ -120,3 +155,62 @@ async fn list(page_num: i32, page_size: i32, user: UserPrincipal) -> Res<PageRes
         Err(e) => Res::error(&e.to_string()),
     }
 }
+
+/// 暂停或恢复命名空间
+///
+/// 暂停期间，该命名空间下的配置发布、服务注册和注销等变更操作将被拒绝，
+/// 返回错误码`NAMESPACE_SUSPENDED_CODE`，用于故障处理时冻结环境。
+#[post("/suspend", data = "<req>")]
+async fn suspend(req: Json<SuspendNamespaceReq>, user: UserPrincipal) -> Res<()> {
+    let has_permission =
+        crate::system::check_ns_permission(&user, UserPermission::ReadWriteNs(req.id.clone()))
+            .await;
+    if !has_permission {
+        return Res::error("no permission");
+    }
+    match get_app()
+        .namespace_app
+        .manager
+        .set_suspended_and_sync(&req.id, req.suspended)
+        .await
+    {
+        Ok(_) => Res::success(()),
+        Err(e) => Res::from_error(&e),
+    }
+}
+
+/// 命名空间资源使用情况
+///
+/// 返回配置数量和大小、服务数量、实例数量、配置监听连接数及最近24小时的采样历史，
+/// namespace_id为空时返回当前用户有权限的所有命名空间。
+/// 监听连接数和采样历史为本节点数据。
+#[get("/usage?<namespace_id>")]
+async fn usage(namespace_id: Option<&str>, user: UserPrincipal) -> Res<Vec<NamespaceUsageReport>> {
+    let manager = &get_app().namespace_app.manager;
+    let candidates = match namespace_id {
+        Some(namespace_id) => vec![namespace_id.to_string()],
+        None => match manager.get_all_namespace().await {
+            Ok(namespaces) => namespaces
+                .into_iter()
+                .map(|namespace| namespace.id)
+                .collect(),
+            Err(e) => return Res::error(&e.to_string()),
+        },
+    };
+
+    let mut namespace_ids = vec![];
+    for id in candidates {
+        if crate::system::check_ns_permission(&user, UserPermission::ReadWriteNs(id.clone())).await
+        {
+            namespace_ids.push(id);
+        }
+    }
+    if namespace_id.is_some() && namespace_ids.is_empty() {
+        return Res::error("no permission");
+    }
+
+    match manager.get_usage(&namespace_ids).await {
+        Ok(usage) => Res::success(usage),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
@@ -1,17 +1,27 @@
 pub mod api;
+pub mod usage;
 
 use crate::Args;
+use crate::app::get_app;
 use crate::db::DbPool;
+use crate::discovery::server::self_register::SYSTEM_NAMESPACE;
+use crate::namespace::server::usage::{
+    NamespaceUsage, NamespaceUsageReport, USAGE_SAMPLE_INTERVAL, UsageHistory,
+};
+use crate::protocol::res::{CodeError, NAMESPACE_SUSPENDED_CODE};
 use crate::raft::RaftRequest;
 use crate::raft::api::raft_write;
+use crate::schedule::{self, TaskScope};
 use anyhow::bail;
 use chrono::{DateTime, Local};
 use dashmap::DashMap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tracing::log;
 
 /// 命名空间
-#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Namespace {
     /// 命名空间ID
     pub id: String,
@@ -23,6 +33,11 @@ pub struct Namespace {
     pub is_auth: bool,
     /// 认证Token
     pub auth_token: Option<String>,
+    /// 是否已暂停
+    ///
+    /// 暂停后拒绝配置发布和服务注册、注销等变更操作，用于故障处理时冻结环境
+    #[serde(default)]
+    pub suspended: bool,
     /// 创建时间
     pub create_time: DateTime<Local>,
     /// 更新时间
@@ -40,12 +55,15 @@ pub struct NamespaceManager {
     /// - 增删改的操作需要首先由Raft同步到集群，然后各个节点收到消息后才会进行持久化操作
     /// - 如果在未持久化前移除缓存，则可能在持久化前的读操作重新写入了缓存，导致脏数据
     cache: DashMap<String, Namespace>,
+    /// 资源使用历史采样
+    usage_history: UsageHistory,
 }
 
 impl NamespaceManager {
     pub async fn new(_args: &Args) -> anyhow::Result<Self> {
         Ok(Self {
             cache: DashMap::new(),
+            usage_history: UsageHistory::default(),
         })
     }
 
@@ -76,12 +94,18 @@ impl NamespaceManager {
         is_auth: bool,
         auth_token: Option<String>,
     ) -> anyhow::Result<()> {
+        // 暂停状态需要单独修改，这里保持原状态
+        let suspended = self
+            .get_namespace(id)
+            .await?
+            .is_some_and(|namespace| namespace.suspended);
         let namespace = Namespace {
             id: id.to_string(),
             name: name.to_string(),
             description: description.clone(),
             is_auth,
             auth_token,
+            suspended,
             create_time: Local::now(),
             update_time: Local::now(),
         };
@@ -91,6 +115,37 @@ impl NamespaceManager {
         Ok(())
     }
 
+    /// 暂停或恢复命名空间，并同步到集群
+    pub async fn set_suspended_and_sync(&self, id: &str, suspended: bool) -> anyhow::Result<()> {
+        let namespace = match self.get_namespace(id).await? {
+            Some(namespace) => namespace,
+            None => bail!("namespace {} not found", id),
+        };
+        self.sync(RaftRequest::UpsertNamespace {
+            namespace: Namespace {
+                suspended,
+                update_time: Local::now(),
+                ..namespace
+            },
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 校验命名空间未暂停，已暂停时返回`NAMESPACE_SUSPENDED_CODE`错误
+    pub async fn check_not_suspended(&self, id: &str) -> anyhow::Result<()> {
+        if let Some(namespace) = self.get_namespace(id).await?
+            && namespace.suspended
+        {
+            return Err(CodeError {
+                code: NAMESPACE_SUSPENDED_CODE,
+                msg: format!("namespace {} is suspended", id),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
     pub async fn upsert_namespace(&self, namespace: Namespace) -> anyhow::Result<()> {
         let old = self.get_namespace(&namespace.id).await?;
         match old {
@@ -105,12 +160,13 @@ impl NamespaceManager {
     }
 
     async fn insert_namespace(&self, namespace: &Namespace) -> anyhow::Result<()> {
-        sqlx::query("insert into namespace (id, name, description, is_auth, auth_token, create_time, update_time) values (?, ?, ?, ?, ?, ?, ?)")
+        sqlx::query("insert into namespace (id, name, description, is_auth, auth_token, suspended, create_time, update_time) values (?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&namespace.id)
             .bind(&namespace.name)
             .bind(&namespace.description)
             .bind(namespace.is_auth)
             .bind(&namespace.auth_token)
+            .bind(namespace.suspended)
             .bind(namespace.create_time)
             .bind(namespace.update_time)
             .execute(DbPool::get())
@@ -120,11 +176,12 @@ impl NamespaceManager {
     }
 
     async fn update_namespace(&self, namespace: &Namespace) -> anyhow::Result<()> {
-        sqlx::query("update namespace set name = ?, description = ?, is_auth = ?, auth_token = ?, update_time = ? where id = ?")
+        sqlx::query("update namespace set name = ?, description = ?, is_auth = ?, auth_token = ?, suspended = ?, update_time = ? where id = ?")
             .bind(&namespace.name)
             .bind(&namespace.description)
             .bind(namespace.is_auth)
             .bind(&namespace.auth_token)
+            .bind(namespace.suspended)
             .bind(namespace.update_time)
             .bind(&namespace.id)
             .execute(DbPool::get())
@@ -137,6 +194,10 @@ impl NamespaceManager {
         if id == "public" {
             bail!("public is the system's default reserved namespace and cannot be deleted.");
         }
+        if id == SYSTEM_NAMESPACE {
+            bail!("system is the reserved namespace of server nodes and cannot be deleted.");
+        }
+        self.check_not_suspended(id).await?;
         self.sync(RaftRequest::DeleteNamespace { id: id.to_string() })
             .await?;
         Ok(())
@@ -148,6 +209,11 @@ impl NamespaceManager {
             .bind(id)
             .execute(DbPool::get())
             .await?;
+        // 删除客户端启动配置
+        sqlx::query("delete from bootstrap_profile where namespace_id = ?")
+            .bind(id)
+            .execute(DbPool::get())
+            .await?;
         sqlx::query("delete from namespace where id = ?")
             .bind(id)
             .execute(DbPool::get())
@@ -161,13 +227,15 @@ impl NamespaceManager {
         let res = raft_write(request).await;
         if !res.is_success() {
             log::error!("sync namespace error: {:?}", res.msg);
-            bail!("sync namespace error: {}", res.msg);
+            bail!(CodeError {
+                code: res.code,
+                msg: format!("sync namespace error: {}", res.msg),
+            });
         }
         log::info!("sync namespace success");
         Ok(())
     }
 
-    #[allow(unused)]
     pub async fn get_all_namespace(&self) -> anyhow::Result<Vec<Namespace>> {
         let namespaces = sqlx::query_as(
             r#"
@@ -179,6 +247,90 @@ impl NamespaceManager {
         Ok(namespaces)
     }
 
+    /// 统计命名空间当前的资源使用情况
+    pub async fn collect_usage(
+        &self,
+        namespace_ids: &[String],
+    ) -> anyhow::Result<Vec<NamespaceUsage>> {
+        // 配置数量和大小，key为命名空间ID
+        let configs: HashMap<String, (u64, u64)> = sqlx::query_as::<_, (String, u64, u64)>(
+            "select namespace_id, count(1), coalesce(sum(length(cast(content as blob))), 0) from config group by namespace_id",
+        )
+        .fetch_all(DbPool::get())
+        .await?
+        .into_iter()
+        .map(|(namespace_id, count, bytes)| (namespace_id, (count, bytes)))
+        .collect();
+        // 服务数量，key为命名空间ID
+        let services: HashMap<String, u64> =
+            sqlx::query_as("select namespace_id, count(1) from service group by namespace_id")
+                .fetch_all(DbPool::get())
+                .await?
+                .into_iter()
+                .collect();
+
+        let app = get_app();
+        let now = Local::now();
+        Ok(namespace_ids
+            .iter()
+            .map(|namespace_id| {
+                let (config_count, config_bytes) =
+                    configs.get(namespace_id).copied().unwrap_or_default();
+                NamespaceUsage {
+                    namespace_id: namespace_id.clone(),
+                    config_count,
+                    config_bytes,
+                    service_count: services.get(namespace_id).copied().unwrap_or_default(),
+                    instance_count: app.discovery_app.manager.count_instances(namespace_id),
+                    watch_connections: app.config_app.manager.watchers.count(namespace_id),
+                    time: now,
+                }
+            })
+            .collect())
+    }
+
+    /// 获取命名空间当前的资源使用情况及历史采样
+    pub async fn get_usage(
+        &self,
+        namespace_ids: &[String],
+    ) -> anyhow::Result<Vec<NamespaceUsageReport>> {
+        Ok(self
+            .collect_usage(namespace_ids)
+            .await?
+            .into_iter()
+            .map(|usage| NamespaceUsageReport {
+                history: self.usage_history.get(&usage.namespace_id),
+                usage,
+            })
+            .collect())
+    }
+
+    /// 启动资源使用情况的定时采样
+    pub fn start_usage_sampler(&'static self) {
+        schedule::schedule(
+            "namespace-usage-sample",
+            "采样命名空间的资源使用情况",
+            TaskScope::Node,
+            USAGE_SAMPLE_INTERVAL,
+            move || self.sample_usage(),
+        );
+    }
+
+    async fn sample_usage(&self) -> anyhow::Result<()> {
+        let namespace_ids = self
+            .get_all_namespace()
+            .await?
+            .into_iter()
+            .map(|namespace| namespace.id)
+            .collect::<Vec<_>>();
+        for usage in self.collect_usage(&namespace_ids).await? {
+            self.usage_history.push(usage);
+        }
+        self.usage_history
+            .retain(|namespace_id| namespace_ids.iter().any(|id| id == namespace_id));
+        Ok(())
+    }
+
     /// 列表查询（分页）
     async fn list_namespace_with_page(
         &self,
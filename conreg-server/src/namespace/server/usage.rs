@@ -0,0 +1,99 @@
+//! 命名空间资源使用情况
+//!
+//! 统计每个命名空间的配置数量和大小、服务数量、实例数量以及配置监听连接数，
+//! 并定时采样保存最近的历史，用于多租户部署下的容量规划和配额调整。
+//!
+//! 监听连接仅存在于处理长轮询的节点上，因此监听连接数为本节点数据，历史采样也仅保存在本节点内存中。
+
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// 采样间隔
+pub const USAGE_SAMPLE_INTERVAL: Duration = Duration::from_secs(300);
+/// 每个命名空间保留的最大采样数（24小时）
+const MAX_SAMPLES: usize = 288;
+
+/// 命名空间资源使用情况
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct NamespaceUsage {
+    /// 命名空间ID
+    pub namespace_id: String,
+    /// 配置数量
+    pub config_count: u64,
+    /// 配置内容总大小，单位字节
+    pub config_bytes: u64,
+    /// 服务数量
+    pub service_count: u64,
+    /// 服务实例数量
+    pub instance_count: usize,
+    /// 配置监听连接数
+    pub watch_connections: usize,
+    /// 统计时间
+    pub time: DateTime<Local>,
+}
+
+/// 命名空间当前资源使用情况及历史采样
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NamespaceUsageReport {
+    #[serde(flatten)]
+    pub usage: NamespaceUsage,
+    /// 历史采样，按时间升序
+    pub history: Vec<NamespaceUsage>,
+}
+
+/// 资源使用历史采样，key为命名空间ID
+#[derive(Debug, Default)]
+pub struct UsageHistory {
+    samples: DashMap<String, VecDeque<NamespaceUsage>>,
+}
+
+impl UsageHistory {
+    /// 记录一次采样，超过最大采样数时丢弃最旧的
+    pub fn push(&self, usage: NamespaceUsage) {
+        let mut samples = self.samples.entry(usage.namespace_id.clone()).or_default();
+        if samples.len() >= MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(usage);
+    }
+
+    /// 获取命名空间的历史采样
+    pub fn get(&self, namespace_id: &str) -> Vec<NamespaceUsage> {
+        self.samples
+            .get(namespace_id)
+            .map(|samples| samples.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// 移除已删除命名空间的采样
+    pub fn retain(&self, f: impl Fn(&str) -> bool) {
+        self.samples.retain(|namespace_id, _| f(namespace_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_history() {
+        let history = UsageHistory::default();
+        for i in 0..MAX_SAMPLES + 10 {
+            history.push(NamespaceUsage {
+                namespace_id: "public".to_string(),
+                config_count: i as u64,
+                ..Default::default()
+            });
+        }
+        let samples = history.get("public");
+        assert_eq!(samples.len(), MAX_SAMPLES);
+        assert_eq!(samples[0].config_count, 10);
+
+        history.retain(|namespace_id| namespace_id != "public");
+        assert!(history.get("public").is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use crate::openapi;
+use crate::openapi::ApiDoc;
+use rocket::response::content::RawHtml;
+use rocket::serde::json::Json;
+use serde_json::Value;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![openapi_json, swagger]
+}
+
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("openapi_json", "获取OpenAPI文档"),
+        ApiDoc::new("swagger", "Swagger UI"),
+    ]
+}
+
+/// 获取OpenAPI文档
+///
+/// 示例：`curl http://127.0.0.1:8000/api/openapi.json`
+#[get("/openapi.json")]
+async fn openapi_json() -> Option<Json<Value>> {
+    openapi::get().cloned().map(Json)
+}
+
+/// Swagger UI
+///
+/// 页面静态资源从CDN加载，需要浏览器能访问外网
+#[get("/swagger")]
+async fn swagger() -> RawHtml<&'static str> {
+    RawHtml(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8">
+    <title>Conreg API</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+<script>
+    window.onload = () => {
+        window.ui = SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });
+    };
+</script>
+</body>
+</html>"##,
+    )
+}
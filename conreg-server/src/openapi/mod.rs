@@ -0,0 +1,282 @@
+//! OpenAPI文档
+//!
+//! 根据已挂载的Rocket路由生成OpenAPI 3.0文档，便于其他语言的第三方客户端据此生成代码：
+//! - 路径、请求方法和查询参数取自路由本身，新增路由无需额外处理即可出现在文档中
+//! - 接口说明、鉴权方式、请求体和响应的数据结构由各模块的`docs()`提供，按处理函数名匹配
+//! - 数据结构通过`schemars`从类型定义生成，统一放在`components.schemas`中
+//!
+//! 文档在启动时生成一次，通过`GET /api/openapi.json`获取，`GET /api/swagger`提供Swagger UI。
+
+use crate::protocol::res::Res;
+use crate::{
+    backup, bootstrap, chaos, config, discovery, event, metrics, namespace, raft, system,
+};
+use rocket::Route;
+use schemars::JsonSchema;
+use schemars::r#gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+pub mod api;
+
+static OPENAPI: OnceLock<Value> = OnceLock::new();
+
+/// Bearer Token鉴权，即控制台登录后获取的Token
+const BEARER_AUTH: &str = "bearer";
+/// 命名空间Token鉴权，通过请求头`X-NS-Token`传递
+const NAMESPACE_AUTH: &str = "namespace_token";
+/// 节点间鉴权，通过请求头`X-Cluster-Signature`传递集群密钥签名
+const CLUSTER_AUTH: &str = "cluster_signature";
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// 接口文档描述
+pub struct ApiDoc {
+    /// 处理函数名
+    name: &'static str,
+    /// 接口说明
+    summary: &'static str,
+    /// 请求体的数据结构
+    body: Option<SchemaFn>,
+    /// 响应的数据结构
+    response: Option<SchemaFn>,
+    /// 鉴权方式
+    security: Option<&'static str>,
+    /// 可选的查询参数，其余查询参数均为必填
+    optional_params: &'static [&'static str],
+}
+
+impl ApiDoc {
+    pub fn new(name: &'static str, summary: &'static str) -> Self {
+        Self {
+            name,
+            summary,
+            body: None,
+            response: None,
+            security: None,
+            optional_params: &[],
+        }
+    }
+
+    /// 设置Json请求体的数据结构
+    pub fn body<T: JsonSchema>(mut self) -> Self {
+        self.body = Some(|generator| generator.subschema_for::<T>());
+        self
+    }
+
+    /// 设置响应数据的数据结构，响应统一包装为`Res<T>`
+    pub fn response<T: JsonSchema>(mut self) -> Self {
+        self.response = Some(|generator| generator.subschema_for::<Res<T>>());
+        self
+    }
+
+    /// 需要登录，即请求头携带`Authorization: Bearer <token>`
+    pub fn auth(mut self) -> Self {
+        self.security = Some(BEARER_AUTH);
+        self
+    }
+
+    /// 需要命名空间Token，即请求头携带`X-NS-Token`
+    pub fn namespace_auth(mut self) -> Self {
+        self.security = Some(NAMESPACE_AUTH);
+        self
+    }
+
+    /// 需要节点间鉴权，即请求头携带`X-Cluster-Timestamp`、`X-Cluster-Nonce`和`X-Cluster-Signature`
+    pub fn cluster_auth(mut self) -> Self {
+        self.security = Some(CLUSTER_AUTH);
+        self
+    }
+
+    /// 设置可选的查询参数
+    pub fn optional(mut self, params: &'static [&'static str]) -> Self {
+        self.optional_params = params;
+        self
+    }
+}
+
+/// 各模块的接口文档，key为挂载路径
+fn docs() -> HashMap<&'static str, Vec<ApiDoc>> {
+    HashMap::from([
+        ("/api", api::docs()),
+        ("/api/cluster", raft::api::docs()),
+        ("/api/config", config::server::api::docs()),
+        ("/api/namespace", namespace::server::api::docs()),
+        ("/api/discovery", discovery::server::api::docs()),
+        ("/api/bootstrap", bootstrap::server::api::docs()),
+        ("/api/system", system::api::docs()),
+        ("/api/metrics", metrics::api::docs()),
+        ("/api/dead_letter", event::api::docs()),
+        ("/api/event", event::api::journal_docs()),
+        ("/api/backup", backup::api::docs()),
+        ("/api/chaos", chaos::api::docs()),
+        (
+            "/api/integrations/prometheus",
+            discovery::server::prometheus::docs(),
+        ),
+    ])
+}
+
+/// 根据已挂载的路由生成OpenAPI文档
+pub fn init<'a>(routes: impl Iterator<Item = &'a Route>) {
+    OPENAPI.get_or_init(|| build(routes));
+}
+
+/// 获取OpenAPI文档
+pub fn get() -> Option<&'static Value> {
+    OPENAPI.get()
+}
+
+fn build<'a>(routes: impl Iterator<Item = &'a Route>) -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let docs = docs();
+    let mut paths = Map::new();
+
+    for route in routes {
+        let base = route.uri.base();
+        // 仅处理API路由，忽略前端静态资源
+        if !base.starts_with("/api") {
+            continue;
+        }
+        let name = route.name.as_deref().unwrap_or_default();
+        let doc = docs
+            .get(base)
+            .and_then(|docs| docs.iter().find(|doc| doc.name == name));
+        let tag = base.trim_start_matches("/api").trim_start_matches('/');
+        let tag = if tag.is_empty() { "openapi" } else { tag };
+
+        let mut operation = Map::new();
+        operation.insert("tags".to_string(), json!([tag]));
+        operation.insert("operationId".to_string(), json!(format!("{tag}_{name}")));
+        operation.insert(
+            "summary".to_string(),
+            json!(doc.map(|doc| doc.summary).unwrap_or(name)),
+        );
+
+        let parameters = route
+            .uri
+            .query()
+            .map(query_params)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|param| {
+                let required = doc.is_none_or(|doc| !doc.optional_params.contains(&param));
+                json!({
+                    "name": param,
+                    "in": "query",
+                    "required": required,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect::<Vec<_>>();
+        if !parameters.is_empty() {
+            operation.insert("parameters".to_string(), json!(parameters));
+        }
+
+        if let Some(body) = doc.and_then(|doc| doc.body) {
+            operation.insert(
+                "requestBody".to_string(),
+                json!({
+                    "required": true,
+                    "content": { "application/json": { "schema": body(&mut generator) } },
+                }),
+            );
+        }
+
+        let mut response = json!({ "description": "OK" });
+        if let Some(schema) = doc.and_then(|doc| doc.response) {
+            response["content"] =
+                json!({ "application/json": { "schema": schema(&mut generator) } });
+        }
+        operation.insert("responses".to_string(), json!({ "200": response }));
+
+        if let Some(security) = doc.and_then(|doc| doc.security) {
+            operation.insert("security".to_string(), json!([{ security: [] }]));
+        }
+
+        let path = route.uri.path().trim_end_matches('/');
+        let path = if path.is_empty() { "/" } else { path };
+        let item = paths.entry(path.to_string()).or_insert_with(|| json!({}));
+        item[route.method.as_str().to_lowercase()] = Value::Object(operation);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Conreg API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": generator.take_definitions(),
+            "securitySchemes": {
+                BEARER_AUTH: { "type": "http", "scheme": "bearer" },
+                NAMESPACE_AUTH: { "type": "apiKey", "in": "header", "name": "X-NS-Token" },
+                CLUSTER_AUTH: {
+                    "type": "apiKey",
+                    "in": "header",
+                    "name": "X-Cluster-Signature",
+                    "description": "HMAC-SHA256(secret, \"{method}\\n{path}?{query}\\n{timestamp}\\n{nonce}\\n{sha256(body)}\"), with the timestamp in X-Cluster-Timestamp and a single-use nonce in X-Cluster-Nonce",
+                },
+            },
+        },
+    })
+}
+
+/// 解析路由中的查询参数名，如`<namespace_id>&<id>`
+fn query_params(query: &str) -> Vec<&str> {
+    query
+        .split('&')
+        .filter_map(|param| param.strip_prefix('<')?.strip_suffix('>'))
+        .map(|param| param.trim_end_matches(".."))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_params() {
+        assert_eq!(
+            query_params("<namespace_id>&<page_num>&<page_size>"),
+            vec!["namespace_id", "page_num", "page_size"]
+        );
+        assert_eq!(query_params("<params..>"), vec!["params"]);
+        assert!(query_params("static=1").is_empty());
+    }
+
+    #[test]
+    fn test_docs_cover_routes() {
+        let modules = [
+            ("/api", api::routes()),
+            ("/api/cluster", raft::api::routes()),
+            ("/api/config", config::server::api::routes()),
+            ("/api/namespace", namespace::server::api::routes()),
+            ("/api/discovery", discovery::server::api::routes()),
+            ("/api/bootstrap", bootstrap::server::api::routes()),
+            ("/api/system", system::api::routes()),
+            ("/api/metrics", metrics::api::routes()),
+            ("/api/dead_letter", event::api::routes()),
+            ("/api/event", event::api::journal_routes()),
+            ("/api/backup", backup::api::routes()),
+            ("/api/chaos", chaos::api::routes()),
+            (
+                "/api/integrations/prometheus",
+                discovery::server::prometheus::routes(),
+            ),
+        ];
+        let docs = docs();
+        for (base, routes) in modules {
+            for route in routes {
+                let name = route.name.as_deref().unwrap_or_default();
+                assert!(
+                    docs[base].iter().any(|doc| doc.name == name),
+                    "route {base} {name} has no api doc"
+                );
+            }
+        }
+    }
+}
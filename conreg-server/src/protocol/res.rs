@@ -1,10 +1,11 @@
 use rocket::Request;
 use rocket::response::Responder;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 ///通用Json响应返回
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Res<T> {
     pub code: i32,
     pub msg: String,
@@ -15,6 +16,31 @@ pub struct Res<T> {
 const SUCCESS_CODE: i32 = 0;
 /// 系统错误
 const ERROR_CODE: i32 = 1;
+/// 命名空间已暂停
+pub const NAMESPACE_SUSPENDED_CODE: i32 = 1001;
+/// 磁盘空间不足，节点处于只读模式
+pub const DISK_READ_ONLY_CODE: i32 = 1002;
+/// 节点以只读模式启动，拒绝写入
+pub const READ_ONLY_CODE: i32 = 1003;
+/// 注册的服务实例已存在
+pub const INSTANCE_CONFLICT_CODE: i32 = 1004;
+
+/// 带错误码的错误
+///
+/// 可以通过`anyhow::Error`传递，由`Res::from_error`转换为对应错误码的响应
+#[derive(Debug)]
+pub struct CodeError {
+    pub code: i32,
+    pub msg: String,
+}
+
+impl std::fmt::Display for CodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for CodeError {}
 
 impl<T> Res<T>
 where
@@ -36,6 +62,18 @@ where
         }
     }
 
+    /// 从错误创建响应，如果是`CodeError`，则使用其错误码
+    pub fn from_error(e: &anyhow::Error) -> Self {
+        match e.downcast_ref::<CodeError>() {
+            Some(e) => Res {
+                code: e.code,
+                msg: e.msg.clone(),
+                data: None,
+            },
+            None => Self::error(&e.to_string()),
+        }
+    }
+
     #[allow(unused)]
     pub fn is_success(&self) -> bool {
         self.code == 0
@@ -48,7 +86,7 @@ where
 }
 
 #[allow(unused)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PageRes<T> {
     pub page_num: i32,
     pub page_size: i32,
@@ -1,28 +1,70 @@
 use crate::app::get_app;
+use crate::auth::cluster::is_cluster_key;
+use crate::auth::{ClusterAuth, ClusterJson};
+use crate::disk;
 use crate::handle_raft_error;
-use crate::protocol::res::Res;
+use crate::protocol::res::{CodeError, READ_ONLY_CODE, Res};
 use crate::raft::RaftRequest;
 use crate::raft::api::{ForwardRequest, forward_request_to_leader};
 use crate::raft::declare_types::ClientWriteResponse;
 use openraft::error::{ClientWriteError, RaftError};
+use rocket::http::Status;
 use rocket::post;
-use rocket::serde::json::Json;
+use std::collections::BTreeMap;
 use tracing::log;
 
 /// 写入数据
 ///
 /// 仅当集群中超过半数节点存活时，才会写入成功，否则会阻塞，直到有超过半数的可用节点。
+///
+/// 该接口用于节点间转发写请求，需要节点间鉴权，业务写入应调用[`raft_write`]。
+/// 转发的写请求已经在接收请求的节点上检查过只读模式，这里不再检查。
 #[post("/write", data = "<req>")]
-pub async fn write(req: Json<RaftRequest>) -> Res<ClientWriteResponse> {
-    raft_write(req.0).await
+pub async fn write(req: ClusterJson<RaftRequest>) -> Res<ClientWriteResponse> {
+    client_write(req.0).await
 }
 
+/// 业务写入，只读节点拒绝写入
+///
+/// 配置获取统计和缓存写入（如登录Token）除外，以便统计只读节点上的配置获取，并允许登录控制台查看数据。
+///
+/// 注意与磁盘空间不足时的只读模式（见[`disk::check_writable`]）区分：
+/// - 启动参数`--read-only`的只读节点是只读副本，不接收服务注册，因此拒绝心跳，但允许配置获取统计
+/// - 磁盘空间只读模式是故障保护，允许心跳以免服务实例被判定为不健康，但拒绝配置获取统计
+///
+/// 两者都允许缓存写入
 pub async fn raft_write(req: RaftRequest) -> Res<ClientWriteResponse> {
+    if get_app().read_only && !is_read_only_exempt(&req) {
+        return Res::from_error(
+            &CodeError {
+                code: READ_ONLY_CODE,
+                msg: "node is read-only, writes are rejected".to_string(),
+            }
+            .into(),
+        );
+    }
+    client_write(req).await
+}
+
+/// 只读节点仍允许的写入：配置获取统计和缓存写入
+fn is_read_only_exempt(req: &RaftRequest) -> bool {
+    matches!(
+        req,
+        RaftRequest::ConfigFetchStats { .. } | RaftRequest::CacheWrite { .. }
+    )
+}
+
+/// 提交写入到Raft，非Leader节点转发到Leader
+async fn client_write(req: RaftRequest) -> Res<ClientWriteResponse> {
+    // 磁盘空间严重不足时拒绝写入
+    if let Err(e) = disk::check_writable(&req) {
+        return Res::from_error(&e);
+    }
     match get_app().raft.client_write(req.clone()).await {
         Ok(response) => Res::success(response),
         Err(err) => {
             let res: Res<ClientWriteResponse> =
-                handle_raft_error!(err, ForwardRequest::RaftRequest(req));
+                handle_raft_error!(err, ForwardRequest::RaftRequest(Box::new(req)));
             res
         }
     }
@@ -35,11 +77,105 @@ pub async fn raft_write(req: RaftRequest) -> Res<ClientWriteResponse> {
 /// 该方法会阻塞，直到集群处于一致状态。
 /// 如果不是Leader节点，该方法会返回Err，需要转发到Leader节点。
 /// 这样读写都在Leader节点上，可能性能会有损失。
+///
+/// 集群密钥等内部数据（`cluster:`开头的key）不允许读取，返回403。
 #[get("/read?<key>")]
-pub async fn read(key: &str) -> Res<Option<String>> {
+pub async fn read(key: &str, _auth: ClusterAuth) -> Result<Res<Option<String>>, Status> {
     let state_machine = &get_app().state_machine;
-    match state_machine.read().await.data.get(key).cloned() {
-        Some(value) => Res::success(Some(value)),
-        None => Res::success(None),
+    read_value(&state_machine.read().await.data, key).map(Res::success)
+}
+
+/// 读取状态机中的数据，集群密钥等内部数据不允许读取
+fn read_value(data: &BTreeMap<String, String>, key: &str) -> Result<Option<String>, Status> {
+    if is_cluster_key(key) {
+        log::warn!("rejected reading cluster key {}", key);
+        return Err(Status::Forbidden);
+    }
+    Ok(data.get(key).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::HeartbeatOverride;
+    use serde_json::Value;
+
+    #[test]
+    fn test_read_only_exempt() {
+        // (请求, 只读节点是否允许, 磁盘空间只读模式是否允许)
+        let requests = [
+            (RaftRequest::ConfigFetchStats { stats: vec![] }, true, false),
+            (
+                RaftRequest::CacheWrite {
+                    key: "token".to_string(),
+                    value: Value::Null,
+                    ttl: None,
+                },
+                true,
+                true,
+            ),
+            (
+                RaftRequest::Heartbeat {
+                    namespace_id: "public".to_string(),
+                    service_id: "svc".to_string(),
+                    instance_id: "1".to_string(),
+                },
+                false,
+                true,
+            ),
+            (
+                RaftRequest::HeartbeatBatch {
+                    namespace_id: "public".to_string(),
+                    instances: vec![],
+                },
+                false,
+                true,
+            ),
+            (
+                RaftRequest::OverrideHeartbeat {
+                    namespace_id: "public".to_string(),
+                    service_id: "svc".to_string(),
+                    instance_id: "1".to_string(),
+                    action: HeartbeatOverride::ResetLostHeartbeats,
+                },
+                false,
+                true,
+            ),
+            (
+                RaftRequest::DeleteConfig {
+                    namespace_id: "public".to_string(),
+                    id: "app.yaml".to_string(),
+                },
+                false,
+                false,
+            ),
+            (
+                RaftRequest::DeleteNamespace {
+                    id: "dev".to_string(),
+                },
+                false,
+                false,
+            ),
+        ];
+        for (req, read_only, disk) in &requests {
+            assert_eq!(is_read_only_exempt(req), *read_only, "{:?}", req);
+            assert_eq!(disk::is_exempt(req), *disk, "{:?}", req);
+        }
+    }
+
+    #[test]
+    fn test_read_value() {
+        let data = BTreeMap::from([
+            ("key".to_string(), "value".to_string()),
+            ("cluster:secret".to_string(), "secret".to_string()),
+            ("cluster:secret:next".to_string(), "next".to_string()),
+        ]);
+        assert_eq!(read_value(&data, "key"), Ok(Some("value".to_string())));
+        assert_eq!(read_value(&data, "missing"), Ok(None));
+        assert_eq!(read_value(&data, "cluster:secret"), Err(Status::Forbidden));
+        assert_eq!(
+            read_value(&data, "cluster:secret:next"),
+            Err(Status::Forbidden)
+        );
     }
 }
@@ -1,15 +1,21 @@
 use crate::app::get_app;
+use crate::auth::ClusterJson;
+use crate::auth::cluster::{ClusterSecret, bootstrap_secret, rotate, with_cluster_signature};
+use crate::disk;
+use crate::disk::DiskStatus;
 use crate::handle_raft_error;
 use crate::protocol::res::Res;
 use crate::raft::api::{ForwardRequest, forward_request_to_leader};
 use crate::raft::declare_types::{Node, RaftMetrics};
-use crate::raft::{NodeId, TypeConfig};
+use crate::raft::{NodeId, TypeConfig, purge};
 use openraft::error::{ClientWriteError, RaftError};
 use openraft::raft::ClientWriteResponse;
-use rocket::serde::json::Json;
 use rocket::{get, post};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::time::Duration;
 use tracing::log;
 
 /// 初始化集群
@@ -18,8 +24,11 @@ use tracing::log;
 /// 后续可通过`add_learner`添加
 ///
 /// 示例：`curl -X POST http://127.0.0.1:8000/api/cluster/init -d []`
+///
+/// 初始化完成后设置集群密钥，使用`--cluster-secret`指定的密钥，未指定时随机生成并在返回信息中给出，
+/// 见[`crate::auth::cluster`]
 #[post("/init", data = "<req>")]
-pub async fn init(req: Json<Vec<(NodeId, String)>>) -> Res<String> {
+pub async fn init(req: ClusterJson<Vec<(NodeId, String)>>) -> Res<String> {
     let app = get_app();
     if app.raft.is_initialized().await.unwrap() {
         return Res::success("Cluster already initialized, no need to reinitialize".to_string());
@@ -39,15 +48,94 @@ pub async fn init(req: Json<Vec<(NodeId, String)>>) -> Res<String> {
             nodes.insert(id, Node { addr });
         }
     };
-    match app.raft.initialize(nodes).await {
+    if let Err(e) = app.raft.initialize(nodes).await {
+        log::error!("{}", e);
+        return Res::error(&e.to_string());
+    }
+
+    let (secret, generated) = match bootstrap_secret() {
+        Some(secret) => (secret, false),
+        None => (ClusterSecret::generate(), true),
+    };
+    match init_secret(secret.clone()).await {
+        Ok(_) if generated => Res::success(format!(
+            "Cluster initialization completed, cluster secret: {}",
+            secret.as_str()
+        )),
         Ok(_) => Res::success("Cluster initialization completed".to_string()),
         Err(e) => {
-            log::error!("{}", e);
-            Res::error(&e.to_string())
+            log::error!("failed to set cluster secret: {}", e);
+            Res::success(format!(
+                "Cluster initialization completed, but failed to set cluster secret: {}, \
+                 retry with `conreg-cmt rotate-secret`",
+                e
+            ))
         }
     }
 }
 
+/// 初始化后设置集群密钥
+///
+/// 等待选出Leader后在Leader节点上轮换密钥，Leader为其他节点时转发给Leader处理
+async fn init_secret(secret: ClusterSecret) -> anyhow::Result<()> {
+    let app = get_app();
+    let metrics = app
+        .raft
+        .wait(Some(Duration::from_secs(10)))
+        .metrics(|m| m.current_leader.is_some(), "leader elected")
+        .await?;
+    let leader = metrics.current_leader.unwrap();
+    if leader == app.id {
+        rotate(secret).await?;
+        return Ok(());
+    }
+
+    let Some(node) = metrics.membership_config.membership().get_node(&leader) else {
+        anyhow::bail!("leader {} not found in membership", leader);
+    };
+    let url = format!("http://{}/api/cluster/rotate-secret", node.addr);
+    let request = reqwest::Client::new().post(&url).json(&RotateSecretReq {
+        secret: Some(secret),
+    });
+    let res = with_cluster_signature(request)
+        .send()
+        .await?
+        .json::<Res<ClusterSecret>>()
+        .await?;
+    if !res.is_success() {
+        anyhow::bail!("{}", res.msg);
+    }
+    Ok(())
+}
+
+/// 轮换集群密钥请求
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RotateSecretReq {
+    /// 新密钥，为空时随机生成
+    pub secret: Option<ClusterSecret>,
+}
+
+/// 轮换集群密钥
+///
+/// 只能在Leader节点调用，新密钥复制到所有节点后启用，返回新密钥。
+/// 旧密钥在下次轮换前仍被接受，各节点的`--cluster-secret`可在轮换后逐步更新。
+///
+/// 示例：`conreg-cmt rotate-secret`
+#[post("/rotate-secret", data = "<req>")]
+pub async fn rotate_secret(req: ClusterJson<RotateSecretReq>) -> Res<ClusterSecret> {
+    let secret = match req.into_inner().secret {
+        Some(secret) if secret.as_str().trim().is_empty() => {
+            return Res::error("Cluster secret cannot be empty");
+        }
+        Some(secret) => secret,
+        None => ClusterSecret::generate(),
+    };
+    match rotate(secret).await {
+        Ok(secret) => Res::success(secret),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
 /// 添加一个Learner节点
 ///
 /// Learner节点接收主节点的日志，但不参与投票。
@@ -56,7 +144,9 @@ pub async fn init(req: Json<Vec<(NodeId, String)>>) -> Res<String> {
 ///
 /// 示例：`curl -X POST http://localhost:8000/add-learner -d '[2,"127.0.0.1:8001"]'`
 #[post("/add-learner", data = "<req>")]
-pub async fn add_learner(req: Json<(NodeId, String)>) -> Res<ClientWriteResponse<TypeConfig>> {
+pub async fn add_learner(
+    req: ClusterJson<(NodeId, String)>,
+) -> Res<ClientWriteResponse<TypeConfig>> {
     let (node_id, api_addr) = req.0;
     let node = Node {
         addr: api_addr.clone(),
@@ -72,7 +162,7 @@ pub async fn add_learner(req: Json<(NodeId, String)>) -> Res<ClientWriteResponse
 /// 示例：`curl -X POST http://localhost:8000/change-membership -d '[1,2,3]'`
 #[post("/change-membership", data = "<req>")]
 pub async fn change_membership(
-    req: Json<BTreeSet<NodeId>>,
+    req: ClusterJson<BTreeSet<NodeId>>,
 ) -> Res<ClientWriteResponse<TypeConfig>> {
     match get_app().raft.change_membership(req.0.clone(), false).await {
         Ok(res) => Res::success(res),
@@ -88,3 +178,81 @@ pub async fn metrics() -> Res<RaftMetrics> {
     let metrics = get_app().raft.metrics().borrow().clone();
     Res::success(metrics)
 }
+
+/// 集群节点
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ClusterNode {
+    /// 节点ID
+    pub id: NodeId,
+    /// 节点地址
+    pub addr: String,
+}
+
+/// 获取集群节点列表
+///
+/// 返回当前集群成员配置中的所有节点，包含Learner节点，客户端可据此动态发现服务端地址。
+///
+/// 示例：`curl -X GET http://localhost:8000/api/cluster/nodes`
+#[get("/nodes")]
+pub async fn nodes() -> Res<Vec<ClusterNode>> {
+    let metrics = get_app().raft.metrics().borrow().clone();
+    let nodes = metrics
+        .membership_config
+        .membership()
+        .nodes()
+        .map(|(id, node)| ClusterNode {
+            id: *id,
+            addr: node.addr.clone(),
+        })
+        .collect();
+    Res::success(nodes)
+}
+
+/// 节点健康状态
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct NodeHealth {
+    /// 节点ID
+    pub id: NodeId,
+    /// 当前Leader节点ID
+    pub leader: Option<NodeId>,
+    /// 是否处于只读模式，以只读模式启动或磁盘空间严重不足时为true
+    pub read_only: bool,
+    /// 数据目录磁盘空间，启动后首次检查完成前为空
+    pub disk: Option<DiskStatus>,
+}
+
+/// 获取本节点健康状态
+///
+/// 示例：`curl -X GET http://localhost:8000/api/cluster/health`
+#[get("/health")]
+pub async fn health() -> Res<NodeHealth> {
+    let app = get_app();
+    let disk = disk::status();
+    Res::success(NodeHealth {
+        id: app.id,
+        leader: app.raft.metrics().borrow().current_leader,
+        read_only: app.read_only || disk.as_ref().is_some_and(|disk| disk.read_only),
+        disk,
+    })
+}
+
+/// 清理日志请求
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PurgeLogReq {
+    /// 清理到的日志索引（包含）
+    pub upto: u64,
+}
+
+/// 清理本节点的Raft日志
+///
+/// 只能清理已包含在快照中的日志，成员变更进行中或存在未复制完成的节点时拒绝清理。
+/// 清理由Raft异步执行，返回提交清理的日志索引。
+///
+/// 示例：`curl -X POST http://localhost:8000/api/cluster/purge-log -d '{"upto":1000}'`
+#[post("/purge-log", data = "<req>")]
+pub async fn purge_log(req: ClusterJson<PurgeLogReq>) -> Res<u64> {
+    match purge::purge_log(req.upto).await {
+        Ok(upto) => Res::success(upto),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
@@ -1,3 +1,5 @@
+use crate::auth::cluster::with_cluster_signature;
+use crate::openapi::ApiDoc;
 use crate::protocol::res::Res;
 use crate::raft::declare_types::ClientWriteResponse;
 use crate::raft::{NodeId, RaftRequest};
@@ -19,17 +21,56 @@ pub fn routes() -> Vec<rocket::Route> {
         raft::snapshot,
         cluster::init,
         cluster::metrics,
+        cluster::nodes,
+        cluster::health,
+        cluster::purge_log,
         cluster::change_membership,
         cluster::add_learner,
+        cluster::rotate_secret,
         app::read,
         app::write,
     ]
 }
 
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("vote", "节点间通信：投票请求").cluster_auth(),
+        ApiDoc::new("append", "节点间通信：日志复制及心跳").cluster_auth(),
+        ApiDoc::new("snapshot", "节点间通信：安装快照").cluster_auth(),
+        ApiDoc::new("init", "初始化集群")
+            .cluster_auth()
+            .body::<Vec<(NodeId, String)>>()
+            .response::<String>(),
+        ApiDoc::new("metrics", "获取集群信息"),
+        ApiDoc::new("nodes", "获取集群节点列表").response::<Vec<cluster::ClusterNode>>(),
+        ApiDoc::new("health", "获取本节点健康状态").response::<cluster::NodeHealth>(),
+        ApiDoc::new("purge_log", "清理本节点的Raft日志")
+            .cluster_auth()
+            .body::<cluster::PurgeLogReq>()
+            .response::<u64>(),
+        ApiDoc::new("change_membership", "添加或删除集群节点")
+            .cluster_auth()
+            .body::<BTreeSet<NodeId>>(),
+        ApiDoc::new("add_learner", "添加一个Learner节点")
+            .cluster_auth()
+            .body::<(NodeId, String)>(),
+        ApiDoc::new("rotate_secret", "轮换集群密钥")
+            .cluster_auth()
+            .body::<cluster::RotateSecretReq>()
+            .response::<String>(),
+        ApiDoc::new("read", "读取数据")
+            .cluster_auth()
+            .response::<Option<String>>(),
+        ApiDoc::new("write", "写入数据")
+            .cluster_auth()
+            .body::<RaftRequest>(),
+    ]
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 enum ForwardRequest {
-    RaftRequest(RaftRequest),
+    RaftRequest(Box<RaftRequest>),
     AddLearner(NodeId, String),
     MembershipRequest(BTreeSet<NodeId>),
 }
@@ -57,7 +98,10 @@ async fn forward_request_to_leader(
     let client = reqwest::Client::new();
 
     let forward_url = request.to_forward_url(leader_addr);
-    match client.post(&forward_url).json(&request).send().await {
+    match with_cluster_signature(client.post(&forward_url).json(&request))
+        .send()
+        .await
+    {
         Ok(response) => match response.json::<Res<ClientWriteResponse>>().await {
             Ok(result) => {
                 if result.is_success() {
@@ -1,4 +1,5 @@
 use crate::app::get_app;
+use crate::auth::ClusterJson;
 use crate::raft::declare_types::VoteRequest;
 use crate::raft::{NodeId, TypeConfig};
 use openraft::error::RaftError;
@@ -12,7 +13,7 @@ use tracing::log;
 
 #[post("/vote", data = "<req>")]
 pub async fn vote(
-    req: Json<VoteRequest>,
+    req: ClusterJson<VoteRequest>,
 ) -> Result<Json<Result<VoteResponse<NodeId>, RaftError<NodeId>>>, Status> {
     match get_app().raft.vote(req.into_inner()).await {
         Ok(response) => Ok(Json(Ok(response))),
@@ -33,7 +34,7 @@ pub async fn vote(
 /// 4. Follower的 /append 接口被调用
 #[post("/append", data = "<req>")]
 pub async fn append(
-    req: Json<AppendEntriesRequest<TypeConfig>>,
+    req: ClusterJson<AppendEntriesRequest<TypeConfig>>,
 ) -> Result<Json<Result<AppendEntriesResponse<NodeId>, RaftError<NodeId>>>, Status> {
     match get_app().raft.append_entries(req.0).await {
         Ok(response) => Ok(Json(Ok(response))),
@@ -43,7 +44,7 @@ pub async fn append(
 
 #[post("/snapshot", data = "<req>")]
 pub async fn snapshot(
-    req: Json<InstallSnapshotRequest<TypeConfig>>,
+    req: ClusterJson<InstallSnapshotRequest<TypeConfig>>,
 ) -> Result<Json<Result<InstallSnapshotResponse<NodeId>, RaftError<NodeId>>>, Status> {
     match get_app().raft.install_snapshot(req.0).await {
         Ok(response) => Ok(Json(Ok(response))),
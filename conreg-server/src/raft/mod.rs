@@ -1,21 +1,38 @@
+use crate::auth::cluster::ClusterSecret;
+use crate::bootstrap::server::BootstrapProfile;
 use crate::config::server::ConfigEntry;
-use crate::discovery::ServiceInstance;
+use crate::config::server::encryption::DataKey;
+use crate::config::server::stats::ConfigFetchStat;
 use crate::discovery::server::Service;
+use crate::discovery::{ConflictPolicy, DiscoveryState, HeartbeatOverride, ServiceInstance};
 use crate::namespace::server::Namespace;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use schemars::JsonSchema;
+use serde::de::value::MapDeserializer;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::io::Cursor;
 
 pub mod api;
 mod declare_types;
 pub mod network;
+pub mod purge;
 pub mod store;
 
 pub use api::raft_write as write;
 
+/// 当前节点写入的请求的协议版本，写入请求中的`v`字段
+///
+/// 新增请求类型或修改已有请求的数据结构时加1，旧版本写入的请求没有`v`字段，视为版本0。
+pub const RAFT_REQUEST_VERSION: u32 = 1;
+
 // 1. 定义客户端的请求和响应
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(tag = "cmd", content = "data")]
+//
+// 序列化格式为`{"v":1,"cmd":"Set","data":{...}}`，通过`remote = "Self"`生成不带版本号的序列化方法，
+// 再由手动实现的`Serialize`和`Deserialize`加上版本号，并处理无法识别的请求，见[`RaftRequest::Unknown`]
+#[derive(Serialize, Deserialize, Debug, Clone, strum_macros::IntoStaticStr, JsonSchema)]
+#[serde(remote = "Self", tag = "cmd", content = "data")]
 pub enum RaftRequest {
     /// 设置键值对
     Set { key: String, value: String },
@@ -27,10 +44,27 @@ pub enum RaftRequest {
     UpdateConfig { entry: ConfigEntry },
     /// 配置中心删除配置
     DeleteConfig { namespace_id: String, id: String },
+    /// 设置配置的敏感配置项，keys为None时取消标记
+    SetConfigSensitiveKeys {
+        namespace_id: String,
+        id: String,
+        keys: Option<Vec<String>>,
+    },
+    /// 轮换命名空间的配置加密数据密钥，命名空间未开启加密时开启加密
+    RotateConfigKey { key: DataKey },
+    /// 配置获取统计
+    ConfigFetchStats { stats: Vec<ConfigFetchStat> },
     /// 新增或更新命名空间
     UpsertNamespace { namespace: Namespace },
     /// 删除命名空间
     DeleteNamespace { id: String },
+    /// 新增或更新客户端启动配置
+    UpsertBootstrapProfile { profile: BootstrapProfile },
+    /// 删除客户端启动配置
+    DeleteBootstrapProfile {
+        namespace_id: String,
+        service_id: String,
+    },
     /// 注册服务
     RegisterService { service: Service },
     /// 注销服务
@@ -38,10 +72,31 @@ pub enum RaftRequest {
         namespace_id: String,
         service_id: String,
     },
+    /// 设置服务的期望实例数
+    SetServiceExpectedInstances {
+        namespace_id: String,
+        service_id: String,
+        expected_instances: Option<u32>,
+    },
+    /// 设置服务的实例预热时长
+    SetServiceWarmup {
+        namespace_id: String,
+        service_id: String,
+        warmup_seconds: Option<u32>,
+    },
+    /// 切换服务当前生效的实例集合
+    SwitchServiceActiveSet {
+        namespace_id: String,
+        service_id: String,
+        active_set: Option<String>,
+    },
     /// 注册服务实例
     RegisterServiceInstance {
         namespace_id: String,
         instance: ServiceInstance,
+        /// 实例已存在时的处理策略
+        #[serde(default)]
+        policy: ConflictPolicy,
     },
     /// 注销服务实例
     DeregisterServiceInstance {
@@ -49,12 +104,37 @@ pub enum RaftRequest {
         service_id: String,
         instance_id: String,
     },
+    /// 更新服务实例的元数据，合并meta并删除remove_keys中的key
+    UpdateServiceInstanceMeta {
+        namespace_id: String,
+        service_id: String,
+        instance_id: String,
+        meta: HashMap<String, String>,
+        remove_keys: Vec<String>,
+    },
     /// 服务实例心跳
     Heartbeat {
         namespace_id: String,
         service_id: String,
         instance_id: String,
     },
+    /// 批量服务实例心跳，instances为(service_id, instance_id)列表
+    HeartbeatBatch {
+        namespace_id: String,
+        instances: Vec<(String, String)>,
+    },
+    /// 手动修改服务实例的心跳状态
+    OverrideHeartbeat {
+        namespace_id: String,
+        service_id: String,
+        instance_id: String,
+        action: HeartbeatOverride,
+    },
+    /// 导入服务实例的状态，替换命名空间下的所有服务实例，仅用于测试
+    ImportDiscoveryState {
+        namespace_id: String,
+        state: DiscoveryState,
+    },
     /// 缓存写入
     CacheWrite {
         key: String,
@@ -62,10 +142,7 @@ pub enum RaftRequest {
         ttl: Option<u64>,
     },
     /// 创建用户
-    CreateUser {
-        username: String,
-        password: String,
-    },
+    CreateUser { username: String, password: String },
     /// 删除用户
     DeleteUser { username: String },
     /// 更新用户
@@ -74,8 +151,125 @@ pub enum RaftRequest {
         password: Option<String>,
         permissions: Option<Vec<String>>,
     },
+    /// 集群密钥轮换第一步：保存待启用的密钥，见[`crate::auth::cluster`]
+    PrepareClusterSecret { secret: ClusterSecret },
+    /// 集群密钥轮换第二步：启用待启用的密钥
+    ActivateClusterSecret,
+    /// 无法识别的请求
+    ///
+    /// 滚动升级期间，集群中可能同时存在新旧版本的节点，旧版本节点会收到新版本新增的请求，
+    /// 或者新版本（`v`大于[`RAFT_REQUEST_VERSION`]）修改了数据结构的请求。此时保留原始数据，
+    /// 应用时记录日志并跳过，避免节点崩溃。序列化时按原始数据输出，保证日志复制到其他节点时内容不变。
+    ///
+    /// 请求类型可识别、版本不高于当前版本但数据不合法时，反序列化失败，不会作为无法识别的请求跳过，
+    /// 避免各节点静默跳过后数据不一致。
+    #[serde(skip)]
+    Unknown(UnknownRequest),
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+
+/// 无法识别的请求的原始数据
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct UnknownRequest {
+    /// 协议版本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub v: Option<u32>,
+    /// 请求类型
+    pub cmd: String,
+    /// 请求数据
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl Serialize for RaftRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        /// 不带版本号的请求
+        struct Tagged<'a>(&'a RaftRequest);
+
+        impl Serialize for Tagged<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                RaftRequest::serialize(self.0, serializer)
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Versioned<'a> {
+            v: u32,
+            #[serde(flatten)]
+            request: Tagged<'a>,
+        }
+
+        match self {
+            RaftRequest::Unknown(unknown) => unknown.serialize(serializer),
+            request => Versioned {
+                v: RAFT_REQUEST_VERSION,
+                request: Tagged(request),
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RaftRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = UnknownRequest::deserialize(deserializer)?;
+        if !is_known_command(&raw.cmd) {
+            return Ok(RaftRequest::Unknown(raw));
+        }
+        let mut tagged = Map::new();
+        tagged.insert("cmd".to_string(), Value::String(raw.cmd.clone()));
+        if let Some(data) = raw.data.clone() {
+            tagged.insert("data".to_string(), data);
+        }
+        match RaftRequest::deserialize(Value::Object(tagged)) {
+            Ok(request) => Ok(request),
+            // 新版本修改了数据结构
+            Err(_) if raw.v.unwrap_or_default() > RAFT_REQUEST_VERSION => {
+                Ok(RaftRequest::Unknown(raw))
+            }
+            Err(e) => Err(de::Error::custom(format!(
+                "invalid data of raft request {}: {}",
+                raw.cmd, e
+            ))),
+        }
+    }
+}
+
+/// 请求类型是否可识别
+///
+/// 只传入请求类型进行反序列化，请求类型无法识别时serde调用[`de::Error::unknown_variant`]，
+/// 以此判断，不依赖错误信息的内容
+fn is_known_command(cmd: &str) -> bool {
+    /// 只区分是否为无法识别的请求类型的错误
+    #[derive(Debug)]
+    struct Probe {
+        unknown: bool,
+    }
+
+    impl Display for Probe {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "unknown: {}", self.unknown)
+        }
+    }
+
+    impl std::error::Error for Probe {}
+
+    impl de::Error for Probe {
+        fn custom<T: Display>(_: T) -> Self {
+            Probe { unknown: false }
+        }
+
+        fn unknown_variant(_: &str, _: &'static [&'static str]) -> Self {
+            Probe { unknown: true }
+        }
+    }
+
+    let probe = MapDeserializer::<_, Probe>::new([("cmd", cmd)].into_iter());
+    !matches!(
+        RaftRequest::deserialize(probe),
+        Err(Probe { unknown: true })
+    )
+}
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct RaftResponse {
     pub value: Option<String>,
 }
@@ -97,3 +291,67 @@ pub type Network = network::NetworkFactory;
 
 // 节点ID
 pub type NodeId = u64;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_request() {
+        // 可识别的请求，旧版本写入的请求没有版本号
+        let req: RaftRequest =
+            serde_json::from_str(r#"{"cmd":"Delete","data":{"key":"k"}}"#).unwrap();
+        assert!(matches!(req, RaftRequest::Delete { .. }));
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            format!(
+                r#"{{"v":{},"cmd":"Delete","data":{{"key":"k"}}}}"#,
+                RAFT_REQUEST_VERSION
+            )
+        );
+        let req: RaftRequest =
+            serde_json::from_str(r#"{"v":1,"cmd":"ActivateClusterSecret"}"#).unwrap();
+        assert!(matches!(req, RaftRequest::ActivateClusterSecret));
+        let json = serde_json::to_string(&req).unwrap();
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            RaftRequest::ActivateClusterSecret
+        ));
+
+        // 无法识别的请求类型，序列化后保持原样
+        let raw = r#"{"v":2,"cmd":"NewCommand","data":{"a":1}}"#;
+        let req: RaftRequest = serde_json::from_str(raw).unwrap();
+        assert!(matches!(&req, RaftRequest::Unknown(r) if r.cmd == "NewCommand"));
+        assert_eq!(serde_json::to_string(&req).unwrap(), raw);
+        let raw = r#"{"cmd":"Unknown","data":{"a":1}}"#;
+        let req: RaftRequest = serde_json::from_str(raw).unwrap();
+        assert!(matches!(&req, RaftRequest::Unknown(r) if r.cmd == "Unknown"));
+
+        // 新版本修改了数据结构的请求
+        let raw = format!(
+            r#"{{"v":{},"cmd":"Delete","data":{{"name":"k"}}}}"#,
+            RAFT_REQUEST_VERSION + 1
+        );
+        let req: RaftRequest = serde_json::from_str(&raw).unwrap();
+        assert!(matches!(&req, RaftRequest::Unknown(r) if r.cmd == "Delete"));
+        assert_eq!(serde_json::to_string(&req).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        // 请求类型可识别但数据不合法，反序列化失败，而不是作为无法识别的请求跳过
+        for raw in [
+            r#"{"cmd":"Delete","data":{"name":"k"}}"#.to_string(),
+            format!(
+                r#"{{"v":{},"cmd":"Delete","data":{{"name":"k"}}}}"#,
+                RAFT_REQUEST_VERSION
+            ),
+            r#"{"cmd":"Delete"}"#.to_string(),
+        ] {
+            let err = serde_json::from_str::<RaftRequest>(&raw).unwrap_err();
+            assert!(err.to_string().contains("Delete"), "{}", err);
+        }
+        // 缺少请求类型
+        assert!(serde_json::from_str::<RaftRequest>(r#"{"data":{"key":"k"}}"#).is_err());
+    }
+}
@@ -1,5 +1,9 @@
 use std::fmt::Display;
 
+use crate::auth::cluster::with_cluster_signature;
+use crate::chaos;
+use crate::raft::NodeId;
+
 use openraft::BasicNode;
 use openraft::RaftTypeConfig;
 use openraft::error::InstallSnapshotError;
@@ -28,7 +32,7 @@ pub struct NetworkFactory {}
 
 impl<C> RaftNetworkFactory<C> for NetworkFactory
 where
-    C: RaftTypeConfig<Node = BasicNode>,
+    C: RaftTypeConfig<NodeId = NodeId, Node = BasicNode>,
     <C as RaftTypeConfig>::SnapshotData: AsyncRead + AsyncWrite + AsyncSeek + Unpin,
 {
     type Network = Network<C>;
@@ -52,13 +56,12 @@ where
 {
     addr: String,
     client: Client,
-    #[allow(unused)]
     target: C::NodeId,
 }
 
 impl<C> Network<C>
 where
-    C: RaftTypeConfig,
+    C: RaftTypeConfig<NodeId = NodeId>,
 {
     async fn request<Req, Resp, Err>(
         &mut self,
@@ -70,6 +73,11 @@ where
         Resp: Serialize + DeserializeOwned,
         Err: std::error::Error + Serialize + DeserializeOwned,
     {
+        // 故障注入，仅在`--chaos`模式下生效
+        chaos::inject(self.target).await.map_err(|e| {
+            RPCError::Network(NetworkError::new(&std::io::Error::other(e.to_string())))
+        })?;
+
         let url = format!("http://{}/api/cluster/{}", self.addr, uri);
         log::debug!(
             "network send request to {}",
@@ -77,10 +85,7 @@ where
             //serde_json::to_string_pretty(&req).unwrap()
         );
 
-        let resp = self
-            .client
-            .post(url.clone())
-            .json(&req)
+        let resp = with_cluster_signature(self.client.post(url.clone()).json(&req))
             .send()
             .await
             .map_err(|e| {
@@ -105,7 +110,7 @@ where
 
 impl<C> RaftNetwork<C> for Network<C>
 where
-    C: RaftTypeConfig,
+    C: RaftTypeConfig<NodeId = NodeId>,
 {
     /// 追加日志
     async fn append_entries(
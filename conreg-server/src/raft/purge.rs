@@ -0,0 +1,216 @@
+//! Raft日志清理
+//!
+//! openraft在生成快照后会自动清理已包含在快照中的日志，但快照按日志数量触发，
+//! 写入较少的单机节点长期运行时，日志会持续累积。这里提供：
+//! - 按时间清理：启动参数`--raft-log-retention-days`，定时清理早于保留天数的日志
+//! - 手动清理：`POST /api/cluster/purge-log`，清理到指定的日志索引
+//!
+//! 清理前进行安全检查：
+//! - 只能清理已包含在快照中的日志，否则节点重启后无法恢复状态
+//! - 成员变更进行中时不清理，避免丢失未完成的成员配置日志
+//! - Leader节点不清理尚未复制到所有节点的日志，否则落后的节点只能通过安装快照追赶
+
+use crate::app::get_app;
+use crate::raft::declare_types::RaftMetrics;
+use crate::schedule::{self, TaskScope};
+use anyhow::bail;
+use std::time::Duration;
+use tracing::log;
+
+/// 按时间清理的检查间隔
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 清理到指定日志索引（包含），检查通过后提交给Raft异步执行，返回实际清理到的索引
+pub async fn purge_log(upto: u64) -> anyhow::Result<u64> {
+    let metrics = get_app().raft.metrics().borrow().clone();
+    check_purge(&metrics, upto)?;
+
+    get_app().raft.trigger().purge_log(upto).await?;
+    log::info!("raft logs up to index {} are being purged", upto);
+    Ok(upto)
+}
+
+/// 清理前的安全检查，见模块文档
+fn check_purge(metrics: &RaftMetrics, upto: u64) -> anyhow::Result<()> {
+    if let Some(purged) = metrics.purged
+        && upto <= purged.index
+    {
+        bail!("logs up to index {} have already been purged", purged.index);
+    }
+
+    let Some(snapshot) = metrics.snapshot else {
+        bail!("no snapshot has been built yet, logs can not be purged");
+    };
+    if upto > snapshot.index {
+        bail!(
+            "logs after snapshot index {} can not be purged, build a snapshot first",
+            snapshot.index
+        );
+    }
+
+    if metrics
+        .membership_config
+        .membership()
+        .get_joint_config()
+        .len()
+        > 1
+    {
+        bail!("membership change in progress, logs can not be purged");
+    }
+
+    // 仅Leader节点有复制信息
+    if let Some(replication) = &metrics.replication {
+        for (node_id, matched) in replication.iter() {
+            if *node_id == metrics.id {
+                continue;
+            }
+            let matched = matched.map(|log_id| log_id.index);
+            if matched.is_none_or(|index| index < upto) {
+                bail!(
+                    "node {} has only replicated up to index {:?}, logs can not be purged",
+                    node_id,
+                    matched
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 启动按时间清理任务，清理早于保留天数的日志
+pub fn start_purge_task(retention_days: u64) {
+    schedule::schedule(
+        "raft-log-purge",
+        "清理本节点早于保留天数的Raft日志",
+        TaskScope::Node,
+        PURGE_INTERVAL,
+        move || purge_expired(retention_days),
+    );
+}
+
+async fn purge_expired(retention_days: u64) -> anyhow::Result<()> {
+    let app = get_app();
+    let expire_time = expire_time(chrono::Local::now().timestamp_millis(), retention_days);
+    let Some(index) = app.log_store.last_index_before(expire_time)? else {
+        return Ok(());
+    };
+
+    let metrics = app.raft.metrics().borrow().clone();
+
+    // 过期日志尚未包含在快照中时，先生成快照，下一轮再清理剩余部分
+    let snapshot_index = metrics.snapshot.map(|snapshot| snapshot.index);
+    if snapshot_index.is_none_or(|snapshot_index| snapshot_index < index) {
+        app.raft.trigger().snapshot().await?;
+    }
+    let Some(snapshot_index) = snapshot_index else {
+        return Ok(());
+    };
+
+    let upto = index.min(snapshot_index);
+    if metrics.purged.is_some_and(|purged| upto <= purged.index) {
+        return Ok(());
+    }
+    purge_log(upto).await?;
+    Ok(())
+}
+
+/// 日志的过期时间（毫秒时间戳），在该时间及之前写入的日志已超过保留天数
+fn expire_time(now: i64, retention_days: u64) -> i64 {
+    now - Duration::from_secs(retention_days * 24 * 3600).as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raft::LogStore;
+    use openraft::testing::log_id;
+    use openraft::{Membership, StoredMembership};
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::Arc;
+
+    /// 三个节点的Leader，快照到索引100，已提交到索引120
+    fn leader_metrics() -> RaftMetrics {
+        let mut metrics = RaftMetrics::new_initial(1);
+        metrics.last_applied = Some(log_id(1, 1, 120));
+        metrics.snapshot = Some(log_id(1, 1, 100));
+        metrics.purged = Some(log_id(1, 1, 10));
+        metrics.membership_config = Arc::new(StoredMembership::new(
+            Some(log_id(1, 1, 1)),
+            Membership::new(vec![BTreeSet::from([1, 2, 3])], ()),
+        ));
+        metrics.replication = Some(BTreeMap::from([
+            (1, Some(log_id(1, 1, 120))),
+            (2, Some(log_id(1, 1, 120))),
+            (3, Some(log_id(1, 1, 120))),
+        ]));
+        metrics
+    }
+
+    #[test]
+    fn test_check_purge() {
+        let metrics = leader_metrics();
+        assert!(check_purge(&metrics, 50).is_ok());
+        assert!(check_purge(&metrics, 100).is_ok());
+
+        // 已清理过的日志
+        assert!(check_purge(&metrics, 10).is_err());
+        // 超出快照或已提交的索引
+        let e = check_purge(&metrics, 101).unwrap_err();
+        assert!(e.to_string().contains("snapshot"), "{}", e);
+        assert!(check_purge(&metrics, 130).is_err());
+
+        // 尚未生成快照
+        let mut no_snapshot = leader_metrics();
+        no_snapshot.snapshot = None;
+        assert!(check_purge(&no_snapshot, 50).is_err());
+
+        // 成员变更进行中
+        let mut joint = leader_metrics();
+        joint.membership_config = Arc::new(StoredMembership::new(
+            Some(log_id(1, 1, 110)),
+            Membership::new(
+                vec![BTreeSet::from([1, 2, 3]), BTreeSet::from([1, 2, 4])],
+                (),
+            ),
+        ));
+        assert!(check_purge(&joint, 50).is_err());
+
+        // 有节点尚未复制到要清理的索引
+        let mut lagging = leader_metrics();
+        lagging.replication = Some(BTreeMap::from([
+            (1, Some(log_id(1, 1, 120))),
+            (2, Some(log_id(1, 1, 120))),
+            (3, Some(log_id(1, 1, 40))),
+        ]));
+        assert!(check_purge(&lagging, 40).is_ok());
+        let e = check_purge(&lagging, 50).unwrap_err();
+        assert!(e.to_string().contains("node 3"), "{}", e);
+    }
+
+    #[test]
+    fn test_expired_logs() {
+        const DAY: i64 = 24 * 3600 * 1000;
+        let now = chrono::Local::now().timestamp_millis();
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let times = db.open_tree("log_times").unwrap();
+        for (index, age) in [
+            (1u64, 10 * DAY),
+            (2, 8 * DAY),
+            (3, 6 * DAY),
+            (4, DAY),
+            (5, 0),
+        ] {
+            times
+                .insert(index.to_be_bytes(), &(now - age).to_be_bytes())
+                .unwrap();
+        }
+        let log_store = LogStore::new(Arc::new(db));
+
+        // 仅选择早于保留天数的日志
+        let last_index = |days| log_store.last_index_before(expire_time(now, days)).unwrap();
+        assert_eq!(last_index(7), Some(2));
+        assert_eq!(last_index(5), Some(3));
+        assert_eq!(last_index(30), None);
+        assert_eq!(last_index(0), Some(5));
+    }
+}
@@ -0,0 +1,68 @@
+//! 状态机数据迁移
+//!
+//! 状态机快照中记录了数据版本，加载快照时（节点启动或安装Leader发送的快照）会依次执行
+//! 版本号大于快照版本的迁移，将旧版本的数据转换为当前的数据结构。
+//!
+//! 新增迁移时，在[`MIGRATIONS`]末尾追加，版本号为上一个迁移的版本号+1。
+
+use super::StateMachineData;
+use serde_json::Value;
+use tracing::log;
+
+/// 状态机数据迁移
+struct Migration {
+    /// 迁移后的版本号
+    version: u32,
+    /// 迁移说明
+    description: &'static str,
+    /// 迁移函数，直接修改序列化后的状态机数据
+    migrate: fn(&mut Value) -> anyhow::Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "stamp state machine data version",
+    migrate: |_| Ok(()),
+}];
+
+/// 当前状态机数据版本
+pub const STATE_MACHINE_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// 反序列化状态机数据，并迁移到当前版本
+pub fn load(data: &[u8]) -> anyhow::Result<StateMachineData> {
+    let mut value: Value = serde_json::from_slice(data)?;
+    // 没有版本号的为引入版本号之前的数据
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > STATE_MACHINE_VERSION {
+        log::warn!(
+            "state machine data version {} is newer than supported version {}, unknown fields will be ignored",
+            version,
+            STATE_MACHINE_VERSION
+        );
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        log::info!(
+            "migrate state machine data to version {}: {}",
+            migration.version,
+            migration.description
+        );
+        (migration.migrate)(&mut value)?;
+    }
+    let mut state_machine: StateMachineData = serde_json::from_value(value)?;
+    state_machine.version = STATE_MACHINE_VERSION;
+    Ok(state_machine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        // 引入版本号之前的数据
+        let data = r#"{"last_applied_log":null,"last_membership":{"log_id":null,"membership":{"configs":[],"nodes":{}}},"data":{"k":"v"}}"#;
+        let state_machine = load(data.as_bytes()).unwrap();
+        assert_eq!(state_machine.version, STATE_MACHINE_VERSION);
+        assert_eq!(state_machine.data.get("k"), Some(&"v".to_string()));
+    }
+}
@@ -1,6 +1,9 @@
+mod migration;
 pub mod sled_log_store;
 
-use crate::event::Event;
+use crate::auth::cluster;
+use crate::event::{Event, dead_letter, journal};
+use crate::metrics::metrics;
 use crate::raft::declare_types::{
     Entry, EntryPayload, LogId, SnapshotData, SnapshotMeta, StorageError, StoredMembership,
 };
@@ -18,6 +21,7 @@ use std::io::Cursor;
 use std::ops::Deref;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::RwLock;
 use tracing::log;
 
@@ -30,8 +34,11 @@ pub struct StoredSnapshot {
 }
 
 /// 定义状态机数据
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateMachineData {
+    /// 数据版本，用于加载快照时迁移旧版本数据，见[`migration`]
+    #[serde(default)]
+    pub version: u32,
     /// 当前已处理的日志ID
     pub last_applied_log: Option<LogId>,
     /// 记录当前状态机所知道的最新集群成员配置
@@ -40,6 +47,17 @@ pub struct StateMachineData {
     pub data: BTreeMap<String, String>,
 }
 
+impl Default for StateMachineData {
+    fn default() -> Self {
+        Self {
+            version: migration::STATE_MACHINE_VERSION,
+            last_applied_log: None,
+            last_membership: Default::default(),
+            data: Default::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StateMachineStore {
     /// 当前状态机数据
@@ -65,7 +83,8 @@ impl StateMachineStore {
 
         // 从快照中恢复状态机
         if let Some(s) = snapshot {
-            let prev: StateMachineData = serde_json::from_slice(s.snapshot.get_ref()).unwrap();
+            let prev = migration::load(s.snapshot.get_ref()).unwrap();
+            cluster::load(&prev.data);
             state_machine.state_machine = Arc::new(RwLock::new(prev));
         }
 
@@ -81,10 +100,15 @@ impl StateMachineStore {
         // 然后会重新应用从快照点到最新的日志条目。
         state_machine.last_applied_log = Some(entry.log_id);
 
+        // 记录变更事件，供客户端重连后增量回放
+        if let EntryPayload::Normal(ref req) = entry.payload {
+            journal::record(entry.log_id.index, req);
+        }
+
         // 业务处理
         // TODO 可能的问题：
         // 1. 目前均按照成功处理，处理失败时打印日志，可能会导致部分处理失败的被跳过
-        // 2. SetConfig以Event的方式处理，无法获取结果，如果Event处理失败，可能会导致数据丢失。
+        // 2. SetConfig以Event的方式处理，无法获取结果，Event处理失败时会进入死信队列重试，见`event::dead_letter`。
         match entry.payload {
             EntryPayload::Blank => Ok(RaftResponse { value: None }),
             EntryPayload::Normal(ref req) => match req {
@@ -98,23 +122,54 @@ impl StateMachineStore {
                     let old = state_machine.data.remove(key);
                     Ok(RaftResponse { value: old })
                 }
+                // 集群密钥直接保存在状态机中，随快照复制到新节点
+                RaftRequest::PrepareClusterSecret { secret } => {
+                    cluster::apply_prepare(&mut state_machine.data, secret);
+                    Ok(RaftResponse { value: None })
+                }
+                RaftRequest::ActivateClusterSecret => {
+                    cluster::apply_activate(&mut state_machine.data);
+                    Ok(RaftResponse { value: None })
+                }
+                // 无法识别的请求直接跳过，所有相同版本的节点行为一致
+                RaftRequest::Unknown(unknown) => {
+                    log::warn!(
+                        "Skip unknown raft request at {}, cmd: {}, version: {:?}, maybe written by a newer version node",
+                        entry.log_id,
+                        unknown.cmd,
+                        unknown.v
+                    );
+                    Ok(RaftResponse { value: None })
+                }
                 // 处理配置中心的配置变更操作
                 RaftRequest::SetConfig { .. }
                 | RaftRequest::DeleteConfig { .. }
                 | RaftRequest::UpdateConfig { .. }
+                | RaftRequest::ConfigFetchStats { .. }
+                | RaftRequest::SetConfigSensitiveKeys { .. }
+                | RaftRequest::RotateConfigKey { .. }
                 // 考虑拆分一下？
                 | RaftRequest::UpsertNamespace { .. }
                 | RaftRequest::DeleteNamespace { .. }
+                | RaftRequest::UpsertBootstrapProfile { .. }
+                | RaftRequest::DeleteBootstrapProfile { .. }
                 | RaftRequest::RegisterService { .. }
                 | RaftRequest::DeregisterService { .. }
+                | RaftRequest::SetServiceExpectedInstances { .. }
+                | RaftRequest::SetServiceWarmup { .. }
+                | RaftRequest::SwitchServiceActiveSet { .. }
                 | RaftRequest::RegisterServiceInstance { .. }
                 | RaftRequest::DeregisterServiceInstance { .. }
+                | RaftRequest::UpdateServiceInstanceMeta { .. }
                 | RaftRequest::Heartbeat { .. }
+                | RaftRequest::HeartbeatBatch { .. }
+                | RaftRequest::OverrideHeartbeat { .. }
+                | RaftRequest::ImportDiscoveryState { .. }
                 | RaftRequest::CacheWrite { .. }
                 | RaftRequest::CreateUser { .. }
                 | RaftRequest::DeleteUser { .. }
                 | RaftRequest::UpdateUser { .. } => {
-                    match Event::RaftRequestEvent(req.clone()).send() {
+                    match Event::RaftRequestEvent(req.clone()).send().await {
                         Ok(_) => Ok(RaftResponse { value: None }),
                         Err(e) => {
                             log::error!("Failed to send RaftRequestEvent: {:?}", e);
@@ -221,7 +276,7 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
     /// 目前使用持久化状态机快照的方式
     async fn apply<I>(&mut self, entries: I) -> Result<Vec<RaftResponse>, StorageError>
     where
-        I: IntoIterator<Item=Entry> + Send,
+        I: IntoIterator<Item = Entry> + Send,
     {
         // 需要处理的日志条目
         let entries_iter = entries.into_iter();
@@ -229,7 +284,15 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
 
         for entry in entries_iter {
             log::debug!("apply entry: {:?}", entry);
-            res.push(self.apply_entry(entry).await?);
+            let cmd: &'static str = match entry.payload {
+                EntryPayload::Blank => "Blank",
+                EntryPayload::Normal(ref req) => req.into(),
+                EntryPayload::Membership(_) => "Membership",
+            };
+            let start = Instant::now();
+            let result = self.apply_entry(entry).await;
+            metrics().observe_raft_apply(cmd, start.elapsed(), result.is_ok());
+            res.push(result?);
         }
         Ok(res)
     }
@@ -261,8 +324,10 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
         };
 
         // Update the state machine.
-        let updated_state_machine: StateMachineData = serde_json::from_slice(&new_snapshot.data)
-            .map_err(|e| StorageIOError::read_snapshot(Some(new_snapshot.meta.signature()), &e))?;
+        let updated_state_machine = migration::load(&new_snapshot.data).map_err(|e| {
+            StorageIOError::read_snapshot(Some(new_snapshot.meta.signature()), AnyError::error(e))
+        })?;
+        cluster::load(&updated_state_machine.data);
 
         self.state_machine = Arc::new(RwLock::new(updated_state_machine));
 
@@ -338,6 +403,8 @@ where
         .expect("Failed to create sm_meta tree");
     // 日志
     db.open_tree("logs").expect("Failed to create logs tree");
+    // 死信队列
+    dead_letter::init(db.clone());
 
     (
         SledLogStore::new(db.clone()),
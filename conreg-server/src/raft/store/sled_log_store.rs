@@ -44,6 +44,15 @@ where
         self.db.open_tree("logs").expect("Failed to open logs tree")
     }
 
+    /// 获取日志写入时间树，key为日志索引，value为写入时间（毫秒时间戳）
+    ///
+    /// 日志条目本身不包含时间，按时间清理日志时据此确定清理范围
+    fn log_times_tree(&self) -> sled::Tree {
+        self.db
+            .open_tree("log_times")
+            .expect("Failed to open log times tree")
+    }
+
     /// 获取元数据树
     fn meta_tree(&self) -> sled::Tree {
         self.db.open_tree("meta").expect("Failed to open meta tree")
@@ -63,6 +72,24 @@ where
         tree.flush()?;
         Ok(())
     }
+
+    /// 获取在指定时间（毫秒时间戳）及之前写入的最大日志索引
+    ///
+    /// 升级前写入的日志没有记录时间，会在其后有记录时间的日志过期时一并清理
+    pub fn last_index_before(&self, time: i64) -> Result<Option<u64>, sled::Error> {
+        let mut index = None;
+        for item in self.log_times_tree().iter() {
+            let (key, val) = item?;
+            let (Ok(key), Ok(val)) = (key[..].try_into(), val[..].try_into()) else {
+                continue;
+            };
+            if i64::from_be_bytes(val) > time {
+                break;
+            }
+            index = Some(u64::from_be_bytes(key));
+        }
+        Ok(index)
+    }
 }
 
 impl<C> RaftLogReader<C> for SledLogStore<C>
@@ -203,12 +230,17 @@ where
         I: IntoIterator<Item = C::Entry> + Send,
     {
         let tree = self.logs_tree();
+        let times = self.log_times_tree();
+        let now = chrono::Local::now().timestamp_millis().to_be_bytes();
         for entry in entries {
             let id = entry.get_log_id().index;
             let serialized =
                 serde_json::to_vec(&entry).map_err(|e| StorageIOError::write_logs(&e))?;
             tree.insert(id.to_be_bytes(), serialized)
                 .map_err(|e| StorageIOError::write_logs(&e))?;
+            times
+                .insert(id.to_be_bytes(), &now)
+                .map_err(|e| StorageIOError::write_logs(&e))?;
         }
 
         tree.flush_async()
@@ -234,7 +266,10 @@ where
             batch.remove(key);
         }
 
-        tree.apply_batch(batch)
+        tree.apply_batch(batch.clone())
+            .map_err(|e| StorageIOError::write_logs(&e))?;
+        self.log_times_tree()
+            .apply_batch(batch)
             .map_err(|e| StorageIOError::write_logs(&e))?;
         tree.flush_async()
             .await
@@ -262,7 +297,10 @@ where
             batch.remove(key);
         }
 
-        tree.apply_batch(batch)
+        tree.apply_batch(batch.clone())
+            .map_err(|e| StorageIOError::write(&e))?;
+        self.log_times_tree()
+            .apply_batch(batch)
             .map_err(|e| StorageIOError::write(&e))?;
         Ok(())
     }
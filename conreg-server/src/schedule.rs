@@ -0,0 +1,277 @@
+//! 定时任务
+//!
+//! 统一管理服务端的定时任务，记录每个任务的执行情况，并支持通过`/api/system/tasks`查看和手动触发。
+//!
+//! 任务按执行范围分为两类：
+//! - [`TaskScope::Node`]：每个节点各自执行，处理本节点的数据，如心跳检查、实例清理、Raft日志清理
+//! - [`TaskScope::Cluster`]：集群中只由Leader执行，如配置备份。执行前还会通过[`cache::lock`]加锁，
+//!   使用共享缓存时可防止Leader切换期间重复执行，本地缓存下仅依赖Leader判断
+//!
+//! 同一任务在一个节点上不会并发执行，手动触发时如果任务正在执行则返回错误。
+//! 执行情况只记录在内存中，且只包含本节点的执行记录。
+
+use crate::app::get_app;
+use crate::cache;
+use crate::cache::caches::CacheKey;
+use anyhow::bail;
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tracing::log;
+
+/// 集群任务锁的超时时间，单位秒
+const LOCK_TTL: u64 = 30;
+
+static TASKS: LazyLock<DashMap<String, Arc<Task>>> = LazyLock::new(DashMap::new);
+
+/// 任务执行范围
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum TaskScope {
+    /// 每个节点各自执行
+    Node,
+    /// 只由Leader执行
+    Cluster,
+}
+
+/// 任务执行情况
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TaskStatus {
+    /// 任务名称
+    pub name: String,
+    /// 任务描述
+    pub description: String,
+    /// 执行范围
+    pub scope: TaskScope,
+    /// 执行间隔，单位秒
+    pub interval_secs: u64,
+    /// 是否正在执行
+    pub running: bool,
+    /// 本节点执行次数，不包含因不是Leader而跳过的次数
+    pub run_count: u64,
+    /// 本节点执行失败次数
+    pub failure_count: u64,
+    /// 最后一次执行的开始时间
+    pub last_run_at: Option<DateTime<Local>>,
+    /// 最后一次执行的耗时，单位毫秒
+    pub last_duration_ms: Option<u64>,
+    /// 最后一次执行失败的原因，执行成功时为空
+    pub last_error: Option<String>,
+    /// 下一次定时执行的时间
+    pub next_run_at: Option<DateTime<Local>>,
+}
+
+type TaskFn = Box<
+    dyn Fn() -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> + Send + Sync + 'static,
+>;
+
+struct Task {
+    run: TaskFn,
+    running: AtomicBool,
+    status: Mutex<TaskStatus>,
+}
+
+impl Task {
+    /// 执行任务，返回是否执行，集群任务在非Leader节点上不执行
+    ///
+    /// 任务本身的执行结果记录在执行情况中，返回错误表示任务无法执行
+    async fn execute(&self) -> anyhow::Result<bool> {
+        let (name, scope) = {
+            let status = self.status.lock().unwrap();
+            (status.name.clone(), status.scope)
+        };
+        if scope == TaskScope::Cluster && !is_leader() {
+            return Ok(false);
+        }
+        if self.running.swap(true, Ordering::AcqRel) {
+            bail!("task {} is already running", name);
+        }
+
+        let lock_key = CacheKey::TaskLock(name.clone()).to_string();
+        let result = match scope {
+            TaskScope::Node => {
+                self.run_and_record().await;
+                Ok(true)
+            }
+            TaskScope::Cluster => match cache::lock(&lock_key, LOCK_TTL).await {
+                Ok(()) => {
+                    self.run_and_record().await;
+                    if let Err(e) = cache::unlock(&lock_key).await {
+                        log::warn!("unlock task {} error: {}", name, e);
+                    }
+                    Ok(true)
+                }
+                Err(e) => Err(e.context(format!("task {} is running on another node", name))),
+            },
+        };
+        self.running.store(false, Ordering::Release);
+        result
+    }
+
+    async fn run_and_record(&self) {
+        let start = Instant::now();
+        self.status.lock().unwrap().last_run_at = Some(Local::now());
+        let result = (self.run)().await;
+
+        let mut status = self.status.lock().unwrap();
+        status.run_count += 1;
+        status.last_duration_ms = Some(start.elapsed().as_millis() as u64);
+        status.last_error = result.as_ref().err().map(|e| e.to_string());
+        if let Err(e) = &result {
+            status.failure_count += 1;
+            log::error!("task {} failed: {}", status.name, e);
+        }
+    }
+}
+
+fn is_leader() -> bool {
+    let app = get_app();
+    app.raft.metrics().borrow().current_leader == Some(app.id)
+}
+
+/// 注册定时任务，第一次执行在一个间隔之后
+///
+/// - name：任务名称，用于查看和手动触发，不能重复
+/// - description：任务描述
+/// - scope：执行范围
+/// - interval：执行间隔
+/// - run：任务内容
+pub fn schedule<F, Fut>(name: &str, description: &str, scope: TaskScope, interval: Duration, run: F)
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let next_run_at = Local::now() + interval;
+    let task = Arc::new(Task {
+        run: Box::new(move || Box::pin(run())),
+        running: AtomicBool::new(false),
+        status: Mutex::new(TaskStatus {
+            name: name.to_string(),
+            description: description.to_string(),
+            scope,
+            interval_secs: interval.as_secs(),
+            running: false,
+            run_count: 0,
+            failure_count: 0,
+            last_run_at: None,
+            last_duration_ms: None,
+            last_error: None,
+            next_run_at: Some(next_run_at),
+        }),
+    });
+    if TASKS.insert(name.to_string(), task.clone()).is_some() {
+        log::warn!("task {} is scheduled more than once", name);
+    }
+
+    tokio::spawn(async move {
+        let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            timer.tick().await;
+            task.status.lock().unwrap().next_run_at = Some(Local::now() + interval);
+            // 手动触发的执行尚未结束时跳过本次
+            if task.running.load(Ordering::Acquire) {
+                continue;
+            }
+            if let Err(e) = task.execute().await {
+                log::warn!("{:#}", e);
+            }
+        }
+    });
+}
+
+/// 获取本节点所有定时任务的执行情况，按名称排序
+pub fn list() -> Vec<TaskStatus> {
+    let mut tasks = TASKS
+        .iter()
+        .map(|task| {
+            let mut status = task.status.lock().unwrap().clone();
+            status.running = task.running.load(Ordering::Acquire);
+            status
+        })
+        .collect::<Vec<_>>();
+    tasks.sort_by(|a, b| a.name.cmp(&b.name));
+    tasks
+}
+
+/// 在本节点立即执行一次任务，等待执行完成后返回执行情况
+///
+/// 集群任务只能在Leader节点上触发
+pub async fn trigger(name: &str) -> anyhow::Result<TaskStatus> {
+    let Some(task) = TASKS.get(name).map(|task| task.clone()) else {
+        bail!("task {} not found", name);
+    };
+    if !task.execute().await? {
+        let leader = get_app()
+            .raft
+            .metrics()
+            .borrow()
+            .current_leader
+            .map(|id| id.to_string())
+            .unwrap_or("none".to_string());
+        bail!(
+            "task {} runs on the leader only, trigger it on the leader node {}",
+            name,
+            leader
+        );
+    }
+    let mut status = task.status.lock().unwrap().clone();
+    status.running = task.running.load(Ordering::Acquire);
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+
+    #[tokio::test]
+    async fn test_trigger() {
+        static RUNS: AtomicU64 = AtomicU64::new(0);
+        schedule(
+            "test-trigger",
+            "test task",
+            TaskScope::Node,
+            Duration::from_secs(3600),
+            || async {
+                if RUNS.fetch_add(1, Ordering::Relaxed) == 1 {
+                    bail!("second run failed");
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(())
+            },
+        );
+        let status = list()
+            .into_iter()
+            .find(|task| task.name == "test-trigger")
+            .unwrap();
+        assert_eq!(status.run_count, 0);
+        assert!(status.next_run_at.is_some());
+
+        let status = trigger("test-trigger").await.unwrap();
+        assert_eq!(status.run_count, 1);
+        assert_eq!(status.failure_count, 0);
+        assert!(!status.running);
+
+        // 任务执行失败时记录失败原因
+        let status = trigger("test-trigger").await.unwrap();
+        assert_eq!(status.run_count, 2);
+        assert_eq!(status.failure_count, 1);
+        assert_eq!(status.last_error.as_deref(), Some("second run failed"));
+
+        // 同一任务不会并发执行
+        let (first, second) = tokio::join!(trigger("test-trigger"), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            trigger("test-trigger").await
+        });
+        assert_eq!(first.unwrap().run_count, 3);
+        assert!(second.is_err());
+
+        assert!(trigger("no-such-task").await.is_err());
+    }
+}
@@ -1,8 +1,15 @@
+use crate::app::get_app;
 use crate::auth::UserPrincipal;
+use crate::config::server::enc_dec::DECRYPT_KEY_ENV;
+use crate::config::server::watcher::WatcherInfo;
+use crate::openapi::ApiDoc;
 use crate::protocol::res::{PageRes, Res};
+use crate::schedule::{self, TaskStatus};
 use crate::system::user;
 use rocket::serde::json::Json;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use tracing::log;
 
 pub fn routes() -> Vec<rocket::Route> {
     routes![
@@ -14,45 +21,131 @@ pub fn routes() -> Vec<rocket::Route> {
         user_create,
         user_delete,
         user_update,
+        watchers,
+        close_watchers,
+        tasks,
+        run_task,
+        encrypt,
+        decrypt,
     ]
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+pub fn docs() -> Vec<ApiDoc> {
+    vec![
+        ApiDoc::new("login", "登录")
+            .body::<LoginReq>()
+            .response::<LoginRes>(),
+        ApiDoc::new("update_password", "修改当前用户密码")
+            .auth()
+            .body::<UpdatePasswordReq>()
+            .response::<()>(),
+        ApiDoc::new("logout", "退出登录").auth().response::<()>(),
+        ApiDoc::new("get_permissions", "获取当前用户的权限")
+            .auth()
+            .response::<Vec<String>>(),
+        ApiDoc::new("user_list", "分页查询用户列表")
+            .auth()
+            .response::<PageRes<user::UserInfo>>(),
+        ApiDoc::new("user_create", "创建用户")
+            .auth()
+            .body::<CreateUserReq>()
+            .response::<()>(),
+        ApiDoc::new("user_delete", "删除用户")
+            .auth()
+            .body::<DeleteUserReq>()
+            .response::<()>(),
+        ApiDoc::new("user_update", "更新用户")
+            .auth()
+            .body::<UpdateUserReq>()
+            .response::<()>(),
+        ApiDoc::new("watchers", "查询配置监听会话")
+            .auth()
+            .optional(&["namespace_id"])
+            .response::<Vec<WatcherInfo>>(),
+        ApiDoc::new("close_watchers", "关闭配置监听会话，返回关闭的会话数")
+            .auth()
+            .body::<CloseWatchersReq>()
+            .response::<usize>(),
+        ApiDoc::new("tasks", "查询本节点的定时任务执行情况")
+            .auth()
+            .response::<Vec<TaskStatus>>(),
+        ApiDoc::new("run_task", "在本节点立即执行定时任务")
+            .auth()
+            .body::<RunTaskReq>()
+            .response::<TaskStatus>(),
+        ApiDoc::new("encrypt", "使用服务端的密钥加密配置值，返回DEC(...)")
+            .auth()
+            .body::<EncryptReq>()
+            .response::<String>(),
+        ApiDoc::new("decrypt", "使用服务端的密钥解密DEC(...)配置值")
+            .auth()
+            .body::<DecryptReq>()
+            .response::<String>(),
+    ]
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct LoginReq {
     pub(crate) username: String,
     pub(crate) password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct LoginRes {
     pub(crate) username: String,
     pub(crate) token: String,
     pub(crate) permissions: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct UpdatePasswordReq {
     pub(crate) password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct CreateUserReq {
     pub(crate) username: String,
     pub(crate) password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct UpdateUserReq {
     pub(crate) username: String,
     pub(crate) password: Option<String>,
     pub(crate) permissions: Option<Vec<String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct DeleteUserReq {
     pub(crate) username: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct CloseWatchersReq {
+    /// 会话ID，为空时关闭命名空间下的所有会话
+    pub(crate) id: Option<u64>,
+    /// 命名空间ID，为空时不限制命名空间
+    pub(crate) namespace_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct RunTaskReq {
+    /// 任务名称
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct EncryptReq {
+    /// 需要加密的配置值
+    pub(crate) content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct DecryptReq {
+    /// 密文，可以带DEC(...)包裹
+    pub(crate) ciphertext: String,
+}
+
 /// 登录
 #[post("/login", data = "<req>")]
 async fn login(req: Json<LoginReq>) -> Res<LoginRes> {
@@ -67,7 +160,7 @@ async fn login(req: Json<LoginReq>) -> Res<LoginRes> {
 async fn update_password(req: Json<UpdatePasswordReq>, user: UserPrincipal) -> Res<()> {
     match user::update_password(req.0, user).await {
         Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -105,7 +198,7 @@ async fn user_create(req: Json<CreateUserReq>, user: UserPrincipal) -> Res<()> {
     }
     match user::create_user_and_sync(req.0).await {
         Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -117,7 +210,7 @@ async fn user_delete(req: Json<DeleteUserReq>, user: UserPrincipal) -> Res<()> {
     }
     match user::delete_user_and_sync(&req.0.username).await {
         Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -129,7 +222,7 @@ async fn user_update(req: Json<UpdateUserReq>, user: UserPrincipal) -> Res<()> {
     }
     match user::update_user_and_sync(req.0).await {
         Ok(_) => Res::success(()),
-        Err(e) => Res::error(&e.to_string()),
+        Err(e) => Res::from_error(&e),
     }
 }
 
@@ -141,3 +234,96 @@ async fn get_permissions(user: UserPrincipal) -> Res<Vec<String>> {
         Err(e) => Res::error(&e.to_string()),
     }
 }
+
+/// 当前节点的配置监听会话列表
+#[get("/watchers?<namespace_id>")]
+async fn watchers(namespace_id: Option<&str>, user: UserPrincipal) -> Res<Vec<WatcherInfo>> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    Res::success(get_app().config_app.manager.watchers.list(namespace_id))
+}
+
+/// 强制关闭配置监听会话，返回关闭的会话数量
+#[post("/watchers/close", data = "<req>")]
+async fn close_watchers(req: Json<CloseWatchersReq>, user: UserPrincipal) -> Res<usize> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    if req.id.is_none() && req.namespace_id.is_none() {
+        return Res::error("id or namespace_id is required");
+    }
+    Res::success(
+        get_app()
+            .config_app
+            .manager
+            .watchers
+            .close(req.id, req.namespace_id.as_deref()),
+    )
+}
+
+/// 当前节点的定时任务执行情况
+#[get("/tasks")]
+async fn tasks(user: UserPrincipal) -> Res<Vec<TaskStatus>> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    Res::success(schedule::list())
+}
+
+/// 在当前节点立即执行定时任务，执行完成后返回执行情况
+#[post("/tasks/run", data = "<req>")]
+async fn run_task(req: Json<RunTaskReq>, user: UserPrincipal) -> Res<TaskStatus> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    match schedule::trigger(&req.name).await {
+        Ok(status) => Res::success(status),
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 使用服务端的密钥加密配置值，返回`DEC(密文)`，可直接写入配置
+#[post("/encrypt", data = "<req>")]
+async fn encrypt(req: Json<EncryptReq>, user: UserPrincipal) -> Res<String> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    let Some(key) = &get_app().config_app.manager.decrypt_key else {
+        return Res::error(&format!(
+            "decrypt key is not set, specify it with --decrypt-key or {}",
+            DECRYPT_KEY_ENV
+        ));
+    };
+    match key.encrypt(&req.content) {
+        Ok(ciphertext) => {
+            log::info!("[audit] config value encrypted by {}", user.username);
+            Res::success(ciphertext)
+        }
+        Err(e) => Res::error(&e.to_string()),
+    }
+}
+
+/// 使用服务端的密钥解密`DEC(密文)`
+#[post("/decrypt", data = "<req>")]
+async fn decrypt(req: Json<DecryptReq>, user: UserPrincipal) -> Res<String> {
+    if !user.is_admin() {
+        return Res::error("No permission");
+    }
+    let Some(key) = &get_app().config_app.manager.decrypt_key else {
+        return Res::error(&format!(
+            "decrypt key is not set, specify it with --decrypt-key or {}",
+            DECRYPT_KEY_ENV
+        ));
+    };
+    let result = key.decrypt(&req.ciphertext);
+    log::warn!(
+        "[audit] config value decrypted by {}: {}",
+        user.username,
+        if result.is_ok() { "success" } else { "failed" }
+    );
+    match result {
+        Ok(content) => Res::success(content),
+        Err(_) => Res::error("invalid ciphertext or the key does not match"),
+    }
+}
@@ -4,8 +4,8 @@ pub mod api;
 mod user;
 
 pub use user::{
-    append_user_permissions_and_sync, check_ns_permission, clean_ns_permissions_and_sync,
-    create_user, delete_user, get_user_permissions, update_user,
+    append_user_permissions_and_sync, check_ns_permission, check_ns_write_permission,
+    clean_ns_permissions_and_sync, create_user, delete_user, get_user_permissions, update_user,
 };
 
 #[allow(clippy::enum_variant_names)]
@@ -13,7 +13,6 @@ pub(crate) enum UserPermission {
     ReadWritePublicNs,
     #[allow(unused)]
     ReadNs(String),
-    #[allow(unused)]
     WriteNs(String),
     ReadWriteNs(String),
 }
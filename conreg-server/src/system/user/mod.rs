@@ -2,6 +2,7 @@ use crate::auth::UserPrincipal;
 use crate::cache;
 use crate::cache::caches::CacheKey;
 use crate::db::DbPool;
+use crate::protocol::res::CodeError;
 use crate::raft::RaftRequest;
 use crate::raft::api::raft_write;
 use crate::system::UserPermission;
@@ -9,6 +10,7 @@ use crate::system::api::{CreateUserReq, LoginReq, LoginRes, UpdatePasswordReq, U
 use anyhow::bail;
 use chrono::{DateTime, Local};
 use rocket::serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use std::time::Duration;
 use tracing::log;
 
@@ -25,7 +27,7 @@ pub struct User {
 }
 
 /// 用户信息（脱敏）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UserInfo {
     pub username: String,
     pub permissions: Option<Vec<String>>,
@@ -321,7 +323,10 @@ async fn sync(request: RaftRequest) -> anyhow::Result<()> {
     let res = raft_write(request).await;
     if !res.is_success() {
         log::error!("sync user info error: {:?}", res.msg);
-        bail!("sync user info error: {}", res.msg);
+        bail!(CodeError {
+            code: res.code,
+            msg: format!("sync user info error: {}", res.msg),
+        });
     }
     log::debug!("sync user info success");
     Ok(())
@@ -349,6 +354,12 @@ pub async fn check_ns_permission(user: &UserPrincipal, permission: UserPermissio
     }
 }
 
+/// 检查用户是否对指定命名空间有写权限，管理员、有`w`或`rw`权限的用户可以写入
+pub async fn check_ns_write_permission(user: &UserPrincipal, namespace_id: &str) -> bool {
+    check_ns_permission(user, UserPermission::WriteNs(namespace_id.to_string())).await
+        || check_ns_permission(user, UserPermission::ReadWriteNs(namespace_id.to_string())).await
+}
+
 mod tests {
     #[test]
     pub fn gen_password() {
@@ -0,0 +1,82 @@
+//! Webhook通知
+//!
+//! 将服务端产生的事件以JSON的格式POST到启动参数`--webhook-url`指定的地址，
+//! 可配置多个地址，发送失败时仅记录日志，不重试。
+//!
+//! 请求体格式：
+//! ```json
+//! {"event": "ServiceDegraded", "node_id": 1, "time": "2025-01-01 00:00:00", "data": {...}}
+//! ```
+
+use crate::Args;
+use crate::discovery::server::monitor::DegradedService;
+use chrono::Local;
+use serde::Serialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::log;
+
+/// 请求超时时间
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+static WEBHOOK: OnceLock<Webhook> = OnceLock::new();
+
+/// Webhook事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data")]
+pub enum WebhookEvent {
+    /// 服务健康实例数持续低于期望实例数
+    ServiceDegraded(DegradedService),
+    /// 服务健康实例数恢复
+    ServiceRecovered(DegradedService),
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+    node_id: u64,
+    time: String,
+}
+
+struct Webhook {
+    urls: Vec<String>,
+    node_id: u64,
+    client: reqwest::Client,
+}
+
+pub fn init(args: &Args) -> anyhow::Result<()> {
+    let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+    let _ = WEBHOOK.set(Webhook {
+        urls: args.webhook_url.clone(),
+        node_id: args.node_id,
+        client,
+    });
+    Ok(())
+}
+
+/// 异步发送事件到所有Webhook地址
+pub fn send(event: WebhookEvent) {
+    let Some(webhook) = WEBHOOK.get() else {
+        return;
+    };
+    if webhook.urls.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        let payload = WebhookPayload {
+            event: &event,
+            node_id: webhook.node_id,
+            time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        };
+        for url in &webhook.urls {
+            match webhook.client.post(url).json(&payload).send().await {
+                Ok(res) if res.status().is_success() => {
+                    log::debug!("webhook sent to {}: {:?}", url, event);
+                }
+                Ok(res) => log::warn!("webhook {} responded with {}", url, res.status()),
+                Err(e) => log::warn!("webhook {} send error: {}", url, e),
+            }
+        }
+    });
+}
@@ -0,0 +1,939 @@
+//! # Conreg E2E
+//!
+//! End-to-end test harness spanning conreg-client, a conreg-server cluster and conreg-cmt.
+//!
+//! The server keeps its state in process-wide singletons, so every node runs as a separate
+//! process started from the built `conreg-server` binary, and the cluster is initialized with
+//! the built `conreg-cmt` binary, just like a real deployment. Each node listens on a free local
+//! port and stores its data in a temporary directory that is removed when the [`Cluster`] is
+//! dropped. If a test fails, the directory is kept and its path printed, node logs are written
+//! to `node{id}.log` there.
+//!
+//! The client SDK is also initialized once per process, so each test file under `tests/` holds
+//! a single scenario that uses the client.
+//!
+//! The tests are ignored by default because they need the binaries, build them and run:
+//!
+//! ```shell
+//! cargo build -p conreg-server -p conreg-cmt
+//! cargo test -p conreg-e2e -- --ignored
+//! ```
+//!
+//! Set `CONREG_SERVER_BIN` and `CONREG_CMT_BIN` to use binaries from another location.
+
+use anyhow::{Context, bail};
+use conreg_grpc::GRPC_PORT_OFFSET;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::future::Future;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Cluster secret the nodes and conreg-cmt are started with
+pub const CLUSTER_SECRET: &str = "conreg-e2e-secret";
+/// Namespace used by the tests
+pub const NAMESPACE: &str = "public";
+/// Default timeout waiting for the cluster to converge
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Built-in admin user of conreg-server
+const ADMIN: &str = "conreg";
+
+/// Locate a workspace binary, `env` overrides the default `target/debug/{name}`
+fn binary(name: &str, env: &str) -> anyhow::Result<PathBuf> {
+    let path = match std::env::var_os(env) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let target_dir = match std::env::var_os("CARGO_TARGET_DIR") {
+                Some(dir) => PathBuf::from(dir),
+                None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target"),
+            };
+            target_dir.join("debug").join(name)
+        }
+    };
+    if !path.exists() {
+        bail!(
+            "{} not found at {}, run `cargo build -p {}` or set {}",
+            name,
+            path.display(),
+            name,
+            env
+        );
+    }
+    Ok(path)
+}
+
+/// Pick a free local port
+fn free_port() -> anyhow::Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// Pick a free local port for a node, the gRPC port above it must be free as well
+fn free_node_port() -> anyhow::Result<u16> {
+    for _ in 0..100 {
+        let port = free_port()?;
+        if port
+            .checked_add(GRPC_PORT_OFFSET)
+            .is_some_and(|grpc_port| TcpListener::bind(("127.0.0.1", grpc_port)).is_ok())
+        {
+            return Ok(port);
+        }
+    }
+    bail!("no free port found for a node")
+}
+
+/// Poll `f` until it returns `Some`, fail with the last error after `timeout`
+pub async fn eventually<T, F, Fut>(what: &str, timeout: Duration, mut f: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<Option<T>>>,
+{
+    let deadline = Instant::now() + timeout;
+    let mut last_error = None;
+    loop {
+        match f().await {
+            Ok(Some(value)) => return Ok(value),
+            Ok(None) => {}
+            Err(e) => last_error = Some(e),
+        }
+        if Instant::now() >= deadline {
+            match last_error {
+                Some(e) => bail!("timed out waiting for {}, last error: {:#}", what, e),
+                None => bail!("timed out waiting for {}", what),
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Res<T> {
+    code: i32,
+    msg: String,
+    data: Option<T>,
+}
+
+/// A conreg API response with a non-zero code
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: i32,
+    pub msg: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Read the data of a conreg API response, fail if the request is not successful.
+/// A non-zero response code fails with an [`ApiError`]
+async fn response_data<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> anyhow::Result<Option<T>> {
+    let status = response.status();
+    if !status.is_success() {
+        bail!("{}: {}", status, response.text().await.unwrap_or_default());
+    }
+    let res = response.json::<Res<T>>().await?;
+    if res.code != 0 {
+        return Err(ApiError {
+            code: res.code,
+            msg: res.msg,
+        }
+        .into());
+    }
+    Ok(res.data)
+}
+
+/// Sign a request to a cluster management or chaos endpoint with [`CLUSTER_SECRET`],
+/// after its query and body are set
+fn with_cluster_signature(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let request = builder.try_clone().unwrap().build().unwrap();
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .unwrap_or_default();
+    let url = request.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let mut mac = Hmac::<Sha256>::new_from_slice(CLUSTER_SECRET.as_bytes()).unwrap();
+    mac.update(
+        format!(
+            "{}\n{}\n{}\n{}\n{}",
+            request.method(),
+            target,
+            timestamp,
+            nonce,
+            hex::encode(Sha256::digest(body))
+        )
+        .as_bytes(),
+    );
+    builder
+        .header("X-Cluster-Timestamp", timestamp)
+        .header("X-Cluster-Nonce", nonce)
+        .header(
+            "X-Cluster-Signature",
+            hex::encode(mac.finalize().into_bytes()),
+        )
+}
+
+/// A conreg-server process
+pub struct Node {
+    pub id: u64,
+    /// Address in the form of `127.0.0.1:{port}`
+    pub addr: String,
+    /// Directory of the cluster, holding the data directory and log of the node
+    dir: PathBuf,
+    /// Extra command line arguments
+    args: Vec<String>,
+    process: Option<Child>,
+}
+
+impl Node {
+    fn start(id: u64, dir: &std::path::Path, args: &[&str]) -> anyhow::Result<Self> {
+        let mut node = Node {
+            id,
+            addr: format!("127.0.0.1:{}", free_node_port()?),
+            dir: dir.to_path_buf(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            process: None,
+        };
+        node.spawn()?;
+        Ok(node)
+    }
+
+    /// Start the process on the address and data directory of this node
+    fn spawn(&mut self) -> anyhow::Result<()> {
+        let (_, port) = self.addr.rsplit_once(':').context("invalid node address")?;
+        let data_dir = self.data_dir();
+        let log = File::options()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(format!("node{}.log", self.id)))?;
+        let process = Command::new(binary("conreg-server", "CONREG_SERVER_BIN")?)
+            .args(["--address", "127.0.0.1"])
+            .args(["--port", port])
+            .args(["--data-dir", &data_dir.to_string_lossy()])
+            .args(["--mode", "cluster"])
+            .args(["--node-id", &self.id.to_string()])
+            .args(["--cluster-secret", CLUSTER_SECRET])
+            .args(&self.args)
+            .stdout(Stdio::from(log.try_clone()?))
+            .stderr(Stdio::from(log))
+            .spawn()
+            .context("start conreg-server")?;
+        self.process = Some(process);
+        Ok(())
+    }
+
+    /// Start a stopped node again with the same address and data
+    pub fn restart(&mut self) -> anyhow::Result<()> {
+        if self.is_running() {
+            bail!("node {} is running", self.id);
+        }
+        self.spawn()
+    }
+
+    /// Kill the process, simulating a crashed node
+    pub fn stop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+
+    /// Stop the process with SIGTERM and wait for it to exit, simulating a graceful restart
+    pub fn shutdown(&mut self) -> anyhow::Result<()> {
+        if let Some(mut process) = self.process.take() {
+            let status = Command::new("kill")
+                .args(["-TERM", &process.id().to_string()])
+                .status()?;
+            if !status.success() {
+                let _ = process.kill();
+                bail!("failed to send SIGTERM to node {}", self.id);
+            }
+            process.wait()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.process.is_some()
+    }
+
+    /// Data directory of the node
+    pub fn data_dir(&self) -> PathBuf {
+        self.dir.join(format!("node{}", self.id))
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// A conreg-server cluster initialized with conreg-cmt
+pub struct Cluster {
+    pub nodes: Vec<Node>,
+    dir: PathBuf,
+    http: reqwest::Client,
+}
+
+impl Cluster {
+    /// Start `size` nodes and initialize them as one cluster, wait until a leader is elected
+    pub async fn start(size: u64) -> anyhow::Result<Self> {
+        Self::start_with_args(size, &[]).await
+    }
+
+    /// Same as [`Cluster::start`], passing extra command line arguments to every node, e.g. `--chaos`
+    /// or `--enable-grpc`
+    pub async fn start_with_args(size: u64, args: &[&str]) -> anyhow::Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "conreg-e2e-{}-{}",
+            std::process::id(),
+            free_port()?
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let mut cluster = Cluster {
+            nodes: vec![],
+            dir,
+            http: reqwest::Client::builder().no_proxy().build()?,
+        };
+        for id in 1..=size {
+            let node = Node::start(id, &cluster.dir, args)?;
+            cluster.nodes.push(node);
+        }
+        for node in cluster.nodes.iter() {
+            let url = node.url("/api/cluster/health");
+            eventually(&format!("node {} to start", node.id), TIMEOUT, || async {
+                Ok(response_data::<Value>(cluster.http.get(&url).send().await?)
+                    .await?
+                    .map(|_| ()))
+            })
+            .await?;
+        }
+
+        cluster.cmt_init().await?;
+        cluster.wait_converged().await?;
+        Ok(cluster)
+    }
+
+    /// Initialize the cluster with `conreg-cmt init`
+    async fn cmt_init(&self) -> anyhow::Result<()> {
+        let mut command = tokio::process::Command::new(binary("conreg-cmt", "CONREG_CMT_BIN")?);
+        command
+            .args(["--server", &self.nodes[0].addr])
+            .args(["--cluster-secret", CLUSTER_SECRET])
+            .arg("init")
+            .args(
+                self.nodes
+                    .iter()
+                    .map(|node| format!("{}={}", node.id, node.addr)),
+            );
+        let output = command.output().await.context("run conreg-cmt")?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() || !stdout.contains('✅') {
+            bail!(
+                "conreg-cmt init failed: {}{}",
+                stdout,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    /// Run a `conreg-cmt` command against `node`, return its standard output
+    pub async fn cmt(&self, node: &Node, args: &[&str]) -> anyhow::Result<String> {
+        let output = tokio::process::Command::new(binary("conreg-cmt", "CONREG_CMT_BIN")?)
+            .args(["--server", &node.addr])
+            .args(["--cluster-secret", CLUSTER_SECRET])
+            .args(args)
+            .output()
+            .await
+            .context("run conreg-cmt")?;
+        if !output.status.success() {
+            bail!(
+                "conreg-cmt {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Wait until every running node knows the same leader and all members
+    pub async fn wait_converged(&self) -> anyhow::Result<u64> {
+        let members = self.nodes.len();
+        eventually("cluster to converge", TIMEOUT, || async {
+            let mut leaders = vec![];
+            for node in self.running() {
+                let metrics = self.metrics(node).await?;
+                let nodes = metrics["membership_config"]["membership"]["nodes"]
+                    .as_object()
+                    .map(|nodes| nodes.len())
+                    .unwrap_or_default();
+                if nodes != members {
+                    return Ok(None);
+                }
+                leaders.push(metrics["current_leader"].as_u64());
+            }
+            Ok(match leaders.first() {
+                Some(Some(leader))
+                    if leaders.iter().all(|l| *l == Some(*leader))
+                        && self.node(*leader).is_running() =>
+                {
+                    Some(*leader)
+                }
+                _ => None,
+            })
+        })
+        .await
+    }
+
+    async fn metrics(&self, node: &Node) -> anyhow::Result<Value> {
+        response_data::<Value>(
+            self.http
+                .get(node.url("/api/cluster/metrics"))
+                .send()
+                .await?,
+        )
+        .await?
+        .context("empty metrics")
+    }
+
+    pub fn node(&self, id: u64) -> &Node {
+        self.nodes.iter().find(|node| node.id == id).unwrap()
+    }
+
+    pub fn node_mut(&mut self, id: u64) -> &mut Node {
+        self.nodes.iter_mut().find(|node| node.id == id).unwrap()
+    }
+
+    /// Nodes that are not stopped
+    pub fn running(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(|node| node.is_running())
+    }
+
+    /// Addresses of all nodes
+    pub fn addrs(&self) -> Vec<String> {
+        self.nodes.iter().map(|node| node.addr.clone()).collect()
+    }
+
+    /// Log in as the built-in admin user, return the token
+    ///
+    /// The token is replicated through raft, wait until every running node accepts it.
+    pub async fn login(&self, node: &Node) -> anyhow::Result<String> {
+        self.login_as(node, ADMIN, ADMIN).await
+    }
+
+    /// Log in as the given user, return the token, see [`Cluster::login`]
+    pub async fn login_as(
+        &self,
+        node: &Node,
+        username: &str,
+        password: &str,
+    ) -> anyhow::Result<String> {
+        let res = response_data::<Value>(
+            self.http
+                .post(node.url("/api/system/login"))
+                .json(&json!({ "username": username, "password": password }))
+                .send()
+                .await?,
+        )
+        .await?;
+        let token = res
+            .and_then(|res| res["token"].as_str().map(str::to_string))
+            .context("no token returned")?;
+        for node in self.running() {
+            let url = node.url("/api/system/user/permissions");
+            eventually(
+                &format!("node {} to accept the token", node.id),
+                TIMEOUT,
+                || async {
+                    let response = self.http.get(&url).bearer_auth(&token).send().await?;
+                    Ok(response.status().is_success().then_some(()))
+                },
+            )
+            .await?;
+        }
+        Ok(token)
+    }
+
+    /// Create a console user with the given namespace permissions, e.g. `r:ns:public`
+    pub async fn create_user(
+        &self,
+        node: &Node,
+        token: &str,
+        username: &str,
+        password: &str,
+        permissions: &[&str],
+    ) -> anyhow::Result<()> {
+        response_data::<Value>(
+            self.http
+                .post(node.url("/api/system/user/add"))
+                .bearer_auth(token)
+                .json(&json!({ "username": username, "password": password }))
+                .send()
+                .await?,
+        )
+        .await?;
+        // 新用户异步写入后才能更新，更新失败时重试
+        eventually("user to be updated", TIMEOUT, || async {
+            response_data::<Value>(
+                self.http
+                    .post(node.url("/api/system/user/update"))
+                    .bearer_auth(token)
+                    .json(&json!({ "username": username, "permissions": permissions }))
+                    .send()
+                    .await?,
+            )
+            .await?;
+            Ok(Some(()))
+        })
+        .await
+    }
+
+    /// Mark a config as sensitive, empty `keys` mark the whole config, `None` clears the mark
+    pub async fn set_sensitive(
+        &self,
+        node: &Node,
+        token: &str,
+        id: &str,
+        keys: Option<&[&str]>,
+    ) -> anyhow::Result<()> {
+        response_data::<Value>(
+            self.http
+                .post(node.url("/api/config/sensitive"))
+                .bearer_auth(token)
+                .json(&json!({ "namespace_id": NAMESPACE, "id": id, "keys": keys }))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Send a GET request to a console API as the user of `token`, return the response data
+    pub async fn console_get(
+        &self,
+        node: &Node,
+        token: &str,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> anyhow::Result<Value> {
+        let data = response_data::<Value>(
+            self.http
+                .get(node.url(path))
+                .bearer_auth(token)
+                .query(query)
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(data.unwrap_or_default())
+    }
+
+    /// Send a POST request to a console API as the user of `token`, return the response data
+    pub async fn console_post(
+        &self,
+        node: &Node,
+        token: &str,
+        path: &str,
+        body: Value,
+    ) -> anyhow::Result<Value> {
+        let data = response_data::<Value>(
+            self.http
+                .post(node.url(path))
+                .bearer_auth(token)
+                .json(&body)
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(data.unwrap_or_default())
+    }
+
+    /// Get the content of a config as a console user
+    pub async fn console_get_config(
+        &self,
+        node: &Node,
+        token: &str,
+        id: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let entry = response_data::<Value>(
+            self.http
+                .get(node.url("/api/config/get"))
+                .bearer_auth(token)
+                .header("X-Console", "true")
+                .query(&[("namespace_id", NAMESPACE), ("id", id)])
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(entry.and_then(|entry| entry["content"].as_str().map(str::to_string)))
+    }
+
+    /// List the contents of the configs on the first page as a console user, keyed by config id
+    pub async fn console_list_configs(
+        &self,
+        node: &Node,
+        token: &str,
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let page = response_data::<Value>(
+            self.http
+                .get(node.url("/api/config/list"))
+                .bearer_auth(token)
+                .query(&[
+                    ("namespace_id", NAMESPACE),
+                    ("page_num", "1"),
+                    ("page_size", "100"),
+                ])
+                .send()
+                .await?,
+        )
+        .await?
+        .context("no page returned")?;
+        Ok(page["list"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                Some((
+                    item["id"].as_str()?.to_string(),
+                    item["content"].as_str()?.to_string(),
+                ))
+            })
+            .collect())
+    }
+
+    /// Create a namespace without authentication
+    pub async fn create_namespace(&self, node: &Node, token: &str, id: &str) -> anyhow::Result<()> {
+        response_data::<Value>(
+            self.http
+                .post(node.url("/api/namespace/upsert"))
+                .bearer_auth(token)
+                .json(&json!({ "id": id, "name": id, "is_auth": false }))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Publish a yaml config, wait until it is applied on a majority of nodes
+    pub async fn publish_config(
+        &self,
+        node: &Node,
+        token: &str,
+        id: &str,
+        content: &str,
+    ) -> anyhow::Result<()> {
+        self.publish_config_in(node, token, NAMESPACE, id, content)
+            .await
+    }
+
+    /// Publish a yaml config to the given namespace, wait until it is applied on a majority of nodes
+    pub async fn publish_config_in(
+        &self,
+        node: &Node,
+        token: &str,
+        namespace: &str,
+        id: &str,
+        content: &str,
+    ) -> anyhow::Result<()> {
+        response_data::<Value>(
+            self.http
+                .post(node.url("/api/config/upsert"))
+                .bearer_auth(token)
+                .json(&json!({
+                    "namespace_id": namespace,
+                    "id": id,
+                    "content": content,
+                    "format": "yaml",
+                    "verify_quorum": true,
+                }))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Get the content of a config from the local database of a node
+    pub async fn get_config(&self, node: &Node, id: &str) -> anyhow::Result<Option<String>> {
+        let entry = response_data::<Value>(
+            self.http
+                .get(node.url("/api/config/get"))
+                .query(&[("namespace_id", NAMESPACE), ("id", id)])
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(entry.and_then(|entry| entry["content"].as_str().map(str::to_string)))
+    }
+
+    /// Get the md5 of a config from the local database of a node
+    pub async fn config_md5(&self, node: &Node, id: &str) -> anyhow::Result<Option<String>> {
+        let entry = response_data::<Value>(
+            self.http
+                .get(node.url("/api/config/get"))
+                .query(&[("namespace_id", NAMESPACE), ("id", id)])
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(entry.and_then(|entry| entry["md5"].as_str().map(str::to_string)))
+    }
+
+    /// Conditionally get a config with a known md5, return false if the server answers 304
+    pub async fn config_modified(&self, node: &Node, id: &str, md5: &str) -> anyhow::Result<bool> {
+        let response = self
+            .http
+            .get(node.url("/api/config/get"))
+            .query(&[("namespace_id", NAMESPACE), ("id", id), ("md5", md5)])
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(false);
+        }
+        response_data::<Value>(response).await?;
+        Ok(true)
+    }
+
+    /// Long-poll the changes of the given configs, return the ids whose md5 differs
+    pub async fn watch_configs(
+        &self,
+        node: &Node,
+        configs: &[(&str, &str)],
+    ) -> anyhow::Result<Vec<String>> {
+        let configs = configs
+            .iter()
+            .map(|(id, md5)| (id.to_string(), Value::from(*md5)))
+            .collect::<serde_json::Map<_, _>>();
+        let changed = response_data::<Vec<String>>(
+            self.http
+                .post(node.url("/api/config/watch"))
+                .json(&json!({
+                    "namespace_id": NAMESPACE,
+                    "configs": configs,
+                }))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(changed.unwrap_or_default())
+    }
+
+    /// Register a service instance, return the instance ID
+    pub async fn register_instance(
+        &self,
+        node: &Node,
+        service_id: &str,
+        port: u16,
+    ) -> anyhow::Result<String> {
+        let instance = self
+            .register_instance_with(node, service_id, port, json!({}), "replace")
+            .await?;
+        instance["id"]
+            .as_str()
+            .map(str::to_string)
+            .context("no instance returned")
+    }
+
+    /// Register a service instance with metadata and a conflict policy (`replace`, `merge_meta`
+    /// or `reject`), return the registered instance
+    pub async fn register_instance_with(
+        &self,
+        node: &Node,
+        service_id: &str,
+        port: u16,
+        meta: Value,
+        conflict_policy: &str,
+    ) -> anyhow::Result<Value> {
+        response_data::<Value>(
+            self.http
+                .post(node.url("/api/discovery/instance/register"))
+                .json(&json!({
+                    "namespace_id": NAMESPACE,
+                    "service_id": service_id,
+                    "ip": "127.0.0.1",
+                    "port": port,
+                    "meta": meta,
+                    "conflict_policy": conflict_policy,
+                }))
+                .send()
+                .await?,
+        )
+        .await?
+        .context("no instance returned")
+    }
+
+    /// Deregister a service instance
+    pub async fn deregister_instance(
+        &self,
+        node: &Node,
+        service_id: &str,
+        instance_id: &str,
+    ) -> anyhow::Result<()> {
+        response_data::<Value>(
+            self.http
+                .post(node.url("/api/discovery/instance/deregister"))
+                .json(&json!({
+                    "namespace_id": NAMESPACE,
+                    "service_id": service_id,
+                    "instance_id": instance_id,
+                }))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Send a heartbeat for a service instance
+    pub async fn heartbeat(
+        &self,
+        node: &Node,
+        service_id: &str,
+        instance_id: &str,
+    ) -> anyhow::Result<()> {
+        response_data::<Value>(
+            self.http
+                .post(node.url("/api/discovery/heartbeat"))
+                .json(&json!({
+                    "namespace_id": NAMESPACE,
+                    "service_id": service_id,
+                    "instance_id": instance_id,
+                }))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// IDs of the instances of a service known by a node, only healthy ones if `available`
+    pub async fn instance_ids(
+        &self,
+        node: &Node,
+        service_id: &str,
+        available: bool,
+    ) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .instances(node, service_id, available)
+            .await?
+            .iter()
+            .filter_map(|instance| instance["id"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// Instances of a service known by a node, only healthy ones if `available`
+    pub async fn instances(
+        &self,
+        node: &Node,
+        service_id: &str,
+        available: bool,
+    ) -> anyhow::Result<Vec<Value>> {
+        let path = if available {
+            "/api/discovery/instance/available"
+        } else {
+            "/api/discovery/instance/list"
+        };
+        let instances = response_data::<Vec<Value>>(
+            self.http
+                .get(node.url(path))
+                .query(&[("namespace_id", NAMESPACE), ("service_id", service_id)])
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(instances.unwrap_or_default())
+    }
+}
+
+impl Cluster {
+    /// Inject raft network faults on a node started with `--chaos`,
+    /// see `conreg-server/src/chaos` for the fields of `faults`
+    pub async fn chaos_faults(&self, node: &Node, faults: Value) -> anyhow::Result<()> {
+        let url = node.url("/api/chaos/faults");
+        response_data::<Value>(
+            with_cluster_signature(self.http.post(&url).json(&faults))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Force a node started with `--chaos` to start an election, return the leader of the new term
+    pub async fn chaos_elect(&self, node: &Node) -> anyhow::Result<u64> {
+        let url = node.url("/api/chaos/elect");
+        response_data::<u64>(with_cluster_signature(self.http.post(&url)).send().await?)
+            .await?
+            .context("no leader returned")
+    }
+
+    /// Export the discovery state of the test namespace from a node started with `--chaos`,
+    /// instances are sorted so that equal states export equal values
+    pub async fn export_discovery(&self, node: &Node) -> anyhow::Result<Value> {
+        let url = node.url("/api/chaos/discovery");
+        response_data::<Value>(
+            with_cluster_signature(self.http.get(&url).query(&[("namespace_id", NAMESPACE)]))
+                .send()
+                .await?,
+        )
+        .await?
+        .context("no discovery state returned")
+    }
+
+    /// Replace all instances of the test namespace through a node started with `--chaos`,
+    /// see `DiscoveryState` in `conreg-server/src/discovery` for the shape of `state`
+    pub async fn import_discovery(&self, node: &Node, state: Value) -> anyhow::Result<()> {
+        let url = node.url("/api/chaos/discovery");
+        response_data::<Value>(
+            with_cluster_signature(
+                self.http
+                    .post(&url)
+                    .json(&json!({ "namespace_id": NAMESPACE, "state": state })),
+            )
+            .send()
+            .await?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        for node in self.nodes.iter_mut() {
+            node.stop();
+        }
+        if std::thread::panicking() {
+            eprintln!("cluster data and logs kept in {}", self.dir.display());
+        } else {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! The client keeps receiving config changes while leadership moves around a lossy network.
+
+use conreg_client::conf::{ConRegConfigBuilder, ConfigConfigBuilder};
+use conreg_client::{AppConfig, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+use serde_json::json;
+
+const CONFIG_ID: &str = "chaos.yaml";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn client_follows_changes_under_chaos() {
+    let cluster = Cluster::start_with_args(3, &["--chaos"]).await.unwrap();
+    let token = cluster.login(&cluster.nodes[0]).await.unwrap();
+    cluster
+        .publish_config(&cluster.nodes[0], &token, CONFIG_ID, "version: 0")
+        .await
+        .unwrap();
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .config(
+                ConfigConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .namespace(NAMESPACE)
+                    .config_ids(vec![CONFIG_ID.to_string()])
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    for node in cluster.nodes.iter() {
+        cluster
+            .chaos_faults(node, json!({ "delay_ms": 50, "drop_rate": 0.2 }))
+            .await
+            .unwrap();
+    }
+
+    for version in 1..=3 {
+        // 在一个Follower上强制选举，进入新的任期
+        let old_leader = cluster.wait_converged().await.unwrap();
+        let follower = cluster
+            .nodes
+            .iter()
+            .find(|node| node.id != old_leader)
+            .unwrap();
+        let new_leader = cluster.chaos_elect(follower).await.unwrap();
+        println!("round {}: leader {} -> {}", version, old_leader, new_leader);
+        let new_leader = cluster.wait_converged().await.unwrap();
+
+        let content = format!("version: {}", version);
+        eventually("write under chaos", TIMEOUT, || async {
+            cluster
+                .publish_config(cluster.node(new_leader), &token, CONFIG_ID, &content)
+                .await
+                .map(Some)
+        })
+        .await
+        .unwrap();
+        // 变更发生在两次长轮询之间时，由每60秒一次的补偿任务拉取
+        eventually("client to receive the change", TIMEOUT * 2, || async {
+            Ok((AppConfig::get::<u32>("version") == Some(version)).then_some(()))
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+//! With a master key, a namespace's configs can be encrypted at rest with a data key that is
+//! rotated online, while clients and the console keep reading plaintext from every node.
+
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+use serde_json::json;
+
+const MASTER_KEY: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+const BEFORE: &str = "db:\n  password: plain-before-rotation\n";
+const AFTER: &str = "db:\n  password: sealed-after-rotation\n";
+
+/// Whether the sqlite files of the node contain `text`
+fn db_contains(dir: &std::path::Path, text: &str) -> bool {
+    std::fs::read_dir(dir.join("db"))
+        .unwrap()
+        .filter_map(|entry| std::fs::read(entry.ok()?.path()).ok())
+        .any(|data| {
+            data.windows(text.len())
+                .any(|window| window == text.as_bytes())
+        })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn configs_encrypted_at_rest_after_rotation() {
+    let mut cluster = Cluster::start_with_args(3, &["--master-key", MASTER_KEY])
+        .await
+        .unwrap();
+    let node = cluster.node(1);
+    let token = cluster.login(node).await.unwrap();
+    cluster
+        .publish_config(node, &token, "app.yaml", BEFORE)
+        .await
+        .unwrap();
+    let status = cluster
+        .console_get(
+            node,
+            &token,
+            "/api/config/encryption",
+            &[("namespace_id", NAMESPACE)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(status["enabled"], false);
+
+    // 首次轮换开启加密，已有的配置在所有节点上重新加密
+    let rotated = cluster
+        .console_post(
+            node,
+            &token,
+            "/api/config/encryption/rotate",
+            json!({ "namespace_id": NAMESPACE }),
+        )
+        .await
+        .unwrap();
+    let key_id = rotated["key_id"].clone();
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to encrypt the config", node.id),
+            TIMEOUT,
+            || async {
+                let status = cluster
+                    .console_get(
+                        node,
+                        &token,
+                        "/api/config/encryption",
+                        &[("namespace_id", NAMESPACE)],
+                    )
+                    .await?;
+                Ok(
+                    (status["key_id"] == key_id && !db_contains(&node.data_dir(), BEFORE))
+                        .then_some(()),
+                )
+            },
+        )
+        .await
+        .unwrap();
+        let content = cluster.get_config(node, "app.yaml").await.unwrap();
+        assert_eq!(content.as_deref(), Some(BEFORE));
+    }
+
+    // 新发布的配置不以明文保存，历史可正常查看
+    cluster
+        .publish_config(node, &token, "app.yaml", AFTER)
+        .await
+        .unwrap();
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to apply the new config", node.id),
+            TIMEOUT,
+            || async {
+                let content = cluster.get_config(node, "app.yaml").await?;
+                Ok((content.as_deref() == Some(AFTER)).then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!db_contains(&node.data_dir(), AFTER));
+    }
+    let histories = cluster
+        .console_get(
+            node,
+            &token,
+            "/api/config/histories",
+            &[
+                ("namespace_id", NAMESPACE),
+                ("id", "app.yaml"),
+                ("page_size", "10"),
+            ],
+        )
+        .await
+        .unwrap();
+    let contents = histories["list"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|history| history["content"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(contents, vec![AFTER, BEFORE]);
+
+    // 再次轮换后仍可读取，重启的节点使用主密钥解密数据密钥
+    let rotated = cluster
+        .console_post(
+            node,
+            &token,
+            "/api/config/encryption/rotate",
+            json!({ "namespace_id": NAMESPACE }),
+        )
+        .await
+        .unwrap();
+    assert_ne!(rotated["key_id"], key_id);
+    let node = cluster.node_mut(2);
+    node.shutdown().unwrap();
+    node.restart().unwrap();
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to rotate the data key", node.id),
+            TIMEOUT,
+            || async {
+                let status = cluster
+                    .console_get(
+                        node,
+                        &token,
+                        "/api/config/encryption",
+                        &[("namespace_id", NAMESPACE)],
+                    )
+                    .await?;
+                Ok((status["key_id"] == rotated["key_id"]).then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+        let content = cluster.get_config(node, "app.yaml").await.unwrap();
+        assert_eq!(content.as_deref(), Some(AFTER));
+    }
+}
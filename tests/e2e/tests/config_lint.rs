@@ -0,0 +1,46 @@
+//! Configs referencing services that don't exist in the namespace are reported by the lint
+//! endpoint and by `conreg-cmt lint`.
+
+use conreg_e2e::{Cluster, NAMESPACE};
+use serde_json::json;
+
+const CONTENT: &str = "orders: lb://e2e-orders/api\nusers: lb-rr://e2e-users/api\n";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn lint_reports_unknown_service_refs() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let token = cluster.login(node).await.unwrap();
+    cluster
+        .register_instance(node, "e2e-orders", 18080)
+        .await
+        .unwrap();
+
+    let issues = cluster
+        .console_post(
+            node,
+            &token,
+            &format!("/api/config/lint?namespace_id={}", NAMESPACE),
+            json!({ "content": CONTENT }),
+        )
+        .await
+        .unwrap();
+    let issues = issues.as_array().unwrap();
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(issues[0]["line"], 2);
+    assert_eq!(issues[0]["rule"], "unknown-service");
+
+    let file = std::env::temp_dir().join(format!("conreg-e2e-lint-{}.yaml", std::process::id()));
+    std::fs::write(&file, CONTENT).unwrap();
+    let output = cluster
+        .cmt(
+            node,
+            &["lint", "--namespace", NAMESPACE, file.to_str().unwrap()],
+        )
+        .await
+        .unwrap();
+    let _ = std::fs::remove_file(&file);
+    assert!(output.contains(":2: service 'e2e-users'"), "{}", output);
+    assert!(!output.contains("e2e-orders"), "{}", output);
+}
@@ -0,0 +1,109 @@
+//! A config version is promoted from one namespace to another, recording where it came from.
+
+use conreg_e2e::Cluster;
+use serde_json::json;
+
+const DEV: &str = "e2e-dev";
+const PROD: &str = "e2e-prod";
+const CONFIG_ID: &str = "promotion.yaml";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn config_version_promoted_between_namespaces() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let token = cluster.login(node).await.unwrap();
+    for namespace in [DEV, PROD] {
+        cluster
+            .create_namespace(node, &token, namespace)
+            .await
+            .unwrap();
+    }
+    for content in ["version: 1", "version: 2"] {
+        cluster
+            .publish_config_in(node, &token, DEV, CONFIG_ID, content)
+            .await
+            .unwrap();
+    }
+    let histories = cluster
+        .console_get(
+            node,
+            &token,
+            "/api/config/histories",
+            &[
+                ("namespace_id", DEV),
+                ("id", CONFIG_ID),
+                ("page_size", "10"),
+            ],
+        )
+        .await
+        .unwrap();
+    let v1 = histories["list"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["content"] == "version: 1")
+        .expect("version 1 in history");
+
+    let promote = |history_id: Option<&serde_json::Value>| {
+        cluster.console_post(
+            node,
+            &token,
+            "/api/config/promote",
+            json!({
+                "source_namespace_id": DEV,
+                "target_namespace_id": PROD,
+                "id": CONFIG_ID,
+                "history_id_": history_id,
+            }),
+        )
+    };
+    let get_prod = || {
+        cluster.console_get(
+            node,
+            &token,
+            "/api/config/get",
+            &[("namespace_id", PROD), ("id", CONFIG_ID)],
+        )
+    };
+
+    // 晋级历史版本
+    let promotion = promote(Some(&v1["id_"])).await.unwrap();
+    assert_eq!(promotion["source_namespace_id"], DEV);
+    assert_eq!(promotion["source_md5"], v1["md5"]);
+    let prod = get_prod().await.unwrap();
+    assert_eq!(prod["content"], "version: 1");
+    assert_eq!(
+        prod["description"],
+        format!("promoted from {} {}", DEV, v1["md5"].as_str().unwrap())
+    );
+
+    // 晋级当前版本
+    promote(None).await.unwrap();
+    assert_eq!(get_prod().await.unwrap()["content"], "version: 2");
+    let compare = cluster
+        .console_get(
+            node,
+            &token,
+            "/api/config/compare",
+            &[("left_ns", DEV), ("right_ns", PROD), ("id", CONFIG_ID)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(compare[0]["status"], "Same");
+
+    let err = cluster
+        .console_post(
+            node,
+            &token,
+            "/api/config/promote",
+            json!({
+                "source_namespace_id": DEV,
+                "target_namespace_id": DEV,
+                "id": CONFIG_ID,
+            }),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("can not be the same"), "{}", err);
+}
@@ -0,0 +1,96 @@
+//! A client merges configs from several namespaces and watches all of them.
+
+use conreg_client::conf::{ConRegConfigBuilder, ConfigConfigBuilder};
+use conreg_client::{AppConfig, try_init_with};
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+
+const SHARED: &str = "e2e-shared";
+const APP: &str = "e2e-app";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn client_merges_and_watches_several_namespaces() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let token = cluster.login(&cluster.nodes[0]).await.unwrap();
+    for namespace in [SHARED, APP] {
+        cluster
+            .create_namespace(&cluster.nodes[0], &token, namespace)
+            .await
+            .unwrap();
+    }
+    cluster
+        .publish_config_in(
+            &cluster.nodes[0],
+            &token,
+            SHARED,
+            "common.yaml",
+            "db:\n  host: shared\n  port: 3306\n",
+        )
+        .await
+        .unwrap();
+    cluster
+        .publish_config_in(
+            &cluster.nodes[0],
+            &token,
+            APP,
+            "app.yaml",
+            "db:\n  host: app\n",
+        )
+        .await
+        .unwrap();
+
+    let source = |namespace: &str, config_id: &str| {
+        ConfigConfigBuilder::default()
+            .server_addr(cluster.addrs())
+            .namespace(namespace)
+            .config_ids(vec![config_id.to_string()])
+            .build()
+            .unwrap()
+    };
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .config(vec![source(SHARED, "common.yaml"), source(APP, "app.yaml")])
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    // 后面的配置源优先
+    assert_eq!(AppConfig::get::<String>("db.host").as_deref(), Some("app"));
+    assert_eq!(AppConfig::get::<u16>("db.port"), Some(3306));
+
+    // 两个命名空间的变更都能收到
+    cluster
+        .publish_config_in(
+            &cluster.nodes[1],
+            &token,
+            SHARED,
+            "common.yaml",
+            "db:\n  host: shared\n  port: 3307\n",
+        )
+        .await
+        .unwrap();
+    eventually("client to receive the shared change", TIMEOUT, || async {
+        Ok((AppConfig::get::<u16>("db.port") == Some(3307)).then_some(()))
+    })
+    .await
+    .unwrap();
+    assert_eq!(AppConfig::get::<String>("db.host").as_deref(), Some("app"));
+
+    cluster
+        .publish_config_in(
+            &cluster.nodes[2],
+            &token,
+            APP,
+            "app.yaml",
+            "db:\n  host: app2\n",
+        )
+        .await
+        .unwrap();
+    eventually("client to receive the app change", TIMEOUT, || async {
+        Ok((AppConfig::get::<String>("db.host").as_deref() == Some("app2")).then_some(()))
+    })
+    .await
+    .unwrap();
+    assert_eq!(AppConfig::get::<u16>("db.port"), Some(3307));
+}
@@ -0,0 +1,121 @@
+//! A config published on one node reaches a client watching another node, and
+//! watching by md5 only reports the configs that actually changed, and a get with a known md5
+//! skips downloading an unchanged config.
+
+use conreg_client::conf::{ConRegConfigBuilder, ConfigConfigBuilder};
+use conreg_client::{AppConfig, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+
+const CONFIG_ID: &str = "e2e.yaml";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn config_publish_propagates_to_client_watch() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let token = cluster.login(&cluster.nodes[0]).await.unwrap();
+    cluster
+        .publish_config(&cluster.nodes[0], &token, CONFIG_ID, "name: v1")
+        .await
+        .unwrap();
+
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .config(
+                ConfigConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .namespace(NAMESPACE)
+                    .config_ids(vec![CONFIG_ID.to_string()])
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(AppConfig::get::<String>("name").as_deref(), Some("v1"));
+
+    // 在另一个节点发布，客户端通过监听收到变更
+    cluster
+        .publish_config(&cluster.nodes[2], &token, CONFIG_ID, "name: v2")
+        .await
+        .unwrap();
+    eventually("client to receive the new config", TIMEOUT, || async {
+        Ok((AppConfig::get::<String>("name").as_deref() == Some("v2")).then_some(()))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn watch_by_md5_returns_only_changed_ids() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let token = cluster.login(&cluster.nodes[0]).await.unwrap();
+    for id in ["a.yaml", "b.yaml"] {
+        cluster
+            .publish_config(&cluster.nodes[0], &token, id, "name: v1")
+            .await
+            .unwrap();
+    }
+
+    // 提交过期的MD5时立即返回
+    let mut changed = cluster
+        .watch_configs(&cluster.nodes[1], &[("a.yaml", ""), ("b.yaml", "")])
+        .await
+        .unwrap();
+    changed.sort();
+    assert_eq!(changed, vec!["a.yaml", "b.yaml"]);
+
+    // MD5一致时阻塞，直到监听的配置发生变更，只返回变更的配置
+    let node = &cluster.nodes[1];
+    let md5_a = cluster.config_md5(node, "a.yaml").await.unwrap().unwrap();
+    let md5_b = cluster.config_md5(node, "b.yaml").await.unwrap().unwrap();
+    let configs = [("a.yaml", md5_a.as_str()), ("b.yaml", md5_b.as_str())];
+    let watch = cluster.watch_configs(node, &configs);
+    let publish = async {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        cluster
+            .publish_config(&cluster.nodes[2], &token, "b.yaml", "name: v2")
+            .await
+    };
+    let (changed, published) = tokio::join!(watch, publish);
+    published.unwrap();
+    assert_eq!(changed.unwrap(), vec!["b.yaml"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn get_with_known_md5_returns_not_modified() {
+    let cluster = Cluster::start(1).await.unwrap();
+    let token = cluster.login(&cluster.nodes[0]).await.unwrap();
+    let node = &cluster.nodes[0];
+    cluster
+        .publish_config(node, &token, CONFIG_ID, "name: v1")
+        .await
+        .unwrap();
+    let md5 = cluster.config_md5(node, CONFIG_ID).await.unwrap().unwrap();
+    assert!(
+        !cluster
+            .config_modified(node, CONFIG_ID, &md5)
+            .await
+            .unwrap()
+    );
+    assert!(
+        cluster
+            .config_modified(node, CONFIG_ID, "stale")
+            .await
+            .unwrap()
+    );
+
+    cluster
+        .publish_config(node, &token, CONFIG_ID, "name: v2")
+        .await
+        .unwrap();
+    assert!(
+        cluster
+            .config_modified(node, CONFIG_ID, &md5)
+            .await
+            .unwrap()
+    );
+}
@@ -0,0 +1,71 @@
+//! Thousands of instances seeded through the chaos endpoint reach every node in one raft write.
+
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+use serde_json::{Map, Value, json};
+
+const SERVICES: usize = 10;
+const INSTANCES_PER_SERVICE: usize = 200;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn imported_state_is_replicated() {
+    let cluster = Cluster::start_with_args(3, &["--chaos"]).await.unwrap();
+
+    let mut services = Map::new();
+    for s in 0..SERVICES {
+        let service_id = format!("e2e-seed-{}", s);
+        let instances = (0..INSTANCES_PER_SERVICE)
+            .map(|i| {
+                json!({
+                    "id": format!("{}-{:04}", service_id, i),
+                    "service_id": service_id,
+                    "ip": "127.0.0.1",
+                    "port": 10000 + i,
+                    "status": "Up",
+                    "meta": {},
+                })
+            })
+            .collect::<Vec<_>>();
+        services.insert(service_id, Value::Array(instances));
+    }
+    cluster
+        .import_discovery(&cluster.nodes[1], json!({ "services": services }))
+        .await
+        .unwrap();
+
+    // 导入通过Raft同步，各节点异步应用
+    let count = |state: &Value| {
+        state["services"]
+            .as_object()
+            .map(|services| {
+                services
+                    .values()
+                    .filter_map(Value::as_array)
+                    .map(Vec::len)
+                    .sum::<usize>()
+            })
+            .unwrap_or_default()
+    };
+    let expected = eventually("imported state to be applied", TIMEOUT, || async {
+        let state = cluster.export_discovery(&cluster.nodes[1]).await?;
+        Ok((count(&state) == SERVICES * INSTANCES_PER_SERVICE).then_some(state))
+    })
+    .await
+    .unwrap();
+
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to hold the imported state", node.id),
+            TIMEOUT,
+            || async {
+                let state = cluster.export_discovery(node).await?;
+                let ids = cluster.instance_ids(node, "e2e-seed-0", true).await?;
+                Ok((state["services"] == expected["services"]
+                    && ids.len() == INSTANCES_PER_SERVICE)
+                    .then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+}
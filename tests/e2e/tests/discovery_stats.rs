@@ -0,0 +1,59 @@
+//! `AppDiscovery::stats` reports the local view of the registry and goes stale when the
+//! registry is unreachable.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::{AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+
+const SERVICE_ID: &str = "e2e-discovery-stats";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn stats_track_instances_and_heartbeat_failures() {
+    let mut cluster = Cluster::start(1).await.unwrap();
+
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .service_id(SERVICE_ID)
+            .client(ClientConfigBuilder::default().port(9320).build().unwrap())
+            .discovery(
+                DiscoveryConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .heartbeat_interval(1)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(AppDiscovery::stats().unwrap().services.is_empty());
+
+    AppDiscovery::wait_for_instances(SERVICE_ID, 1, TIMEOUT)
+        .await
+        .unwrap();
+    let stats = AppDiscovery::stats().unwrap();
+    assert_eq!(stats.services[SERVICE_ID].instances, 1);
+    assert_eq!(
+        stats.last_fetch_at,
+        Some(stats.services[SERVICE_ID].last_fetch_at)
+    );
+    assert_eq!(stats.consecutive_heartbeat_failures, 0);
+
+    cluster.node_mut(1).stop();
+    eventually("heartbeats to fail", TIMEOUT, || async {
+        let failures = AppDiscovery::stats()?.consecutive_heartbeat_failures;
+        Ok((failures >= 2).then_some(()))
+    })
+    .await
+    .unwrap();
+
+    cluster.node_mut(1).restart().unwrap();
+    eventually("heartbeats to recover", TIMEOUT, || async {
+        let failures = AppDiscovery::stats()?.consecutive_heartbeat_failures;
+        Ok((failures == 0).then_some(()))
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,70 @@
+//! An instance that stops sending heartbeats is evicted on every node.
+
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+use std::time::Duration;
+
+const SERVICE_ID: &str = "e2e-eviction";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn instance_without_heartbeat_is_evicted() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let alive = cluster
+        .register_instance(&cluster.nodes[0], SERVICE_ID, 9001)
+        .await
+        .unwrap();
+    let dead = cluster
+        .register_instance(&cluster.nodes[0], SERVICE_ID, 9002)
+        .await
+        .unwrap();
+    let both = {
+        let mut both = vec![alive.clone(), dead.clone()];
+        both.sort();
+        both
+    };
+
+    // 两个实例都发送心跳，直到所有节点都认为它们可用
+    let heartbeat = |ids: Vec<String>| {
+        let cluster = &cluster;
+        async move {
+            loop {
+                for id in ids.iter() {
+                    let _ = cluster.heartbeat(&cluster.nodes[1], SERVICE_ID, id).await;
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    };
+    let converged = |expected: Vec<String>, timeout| {
+        let cluster = &cluster;
+        async move {
+            for node in cluster.running() {
+                eventually(
+                    &format!("node {} to see instances {:?}", node.id, expected),
+                    timeout,
+                    || async {
+                        let mut available = cluster.instance_ids(node, SERVICE_ID, true).await?;
+                        available.sort();
+                        Ok((available == expected).then_some(()))
+                    },
+                )
+                .await
+                .unwrap();
+            }
+        }
+    };
+    tokio::select! {
+        _ = heartbeat(both.clone()) => unreachable!(),
+        _ = converged(both, TIMEOUT) => {}
+    }
+
+    // 停止其中一个实例的心跳，所有节点都将其剔除
+    tokio::select! {
+        _ = heartbeat(vec![alive.clone()]) => unreachable!(),
+        _ = converged(vec![alive.clone()], TIMEOUT * 2) => {}
+    }
+    for node in cluster.running() {
+        let available = cluster.instance_ids(node, SERVICE_ID, true).await.unwrap();
+        assert!(!available.contains(&dead));
+    }
+}
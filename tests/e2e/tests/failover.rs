@@ -0,0 +1,50 @@
+//! Writes keep working and converge after the leader is killed.
+
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+
+const CONFIG_ID: &str = "failover.yaml";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn writes_converge_after_leader_failover() {
+    let mut cluster = Cluster::start(3).await.unwrap();
+    let old_leader = cluster.wait_converged().await.unwrap();
+    let follower = cluster
+        .nodes
+        .iter()
+        .map(|node| node.id)
+        .find(|id| *id != old_leader)
+        .unwrap();
+    let token = cluster.login(cluster.node(follower)).await.unwrap();
+    cluster
+        .publish_config(cluster.node(follower), &token, CONFIG_ID, "version: 1")
+        .await
+        .unwrap();
+
+    cluster.node_mut(old_leader).stop();
+
+    // 选出新Leader前写入会失败，重试直到成功
+    eventually("write after failover", TIMEOUT, || async {
+        cluster
+            .publish_config(cluster.node(follower), &token, CONFIG_ID, "version: 2")
+            .await
+            .map(Some)
+    })
+    .await
+    .unwrap();
+
+    let new_leader = cluster.wait_converged().await.unwrap();
+    assert_ne!(new_leader, old_leader);
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to apply the write", node.id),
+            TIMEOUT,
+            || async {
+                let content = cluster.get_config(node, CONFIG_ID).await?;
+                Ok((content.as_deref() == Some("version: 2")).then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+}
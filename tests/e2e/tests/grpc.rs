@@ -0,0 +1,79 @@
+//! A client using the gRPC protocol loads and watches configs, registers and keeps its instance alive.
+
+use conreg_client::conf::{
+    ClientConfigBuilder, ConRegConfigBuilder, ConfigConfigBuilder, DiscoveryConfigBuilder, Protocol,
+};
+use conreg_client::{AppConfig, AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+
+const CONFIG_ID: &str = "e2e-grpc.yaml";
+const SERVICE_ID: &str = "e2e-grpc";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn client_communicates_over_grpc() {
+    let cluster = Cluster::start_with_args(3, &["--enable-grpc"])
+        .await
+        .unwrap();
+    let token = cluster.login(&cluster.nodes[0]).await.unwrap();
+    cluster
+        .publish_config(&cluster.nodes[0], &token, CONFIG_ID, "name: v1")
+        .await
+        .unwrap();
+
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .service_id(SERVICE_ID)
+            .protocol(Protocol::Grpc)
+            .client(ClientConfigBuilder::default().port(9100).build().unwrap())
+            .config(
+                ConfigConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .namespace(NAMESPACE)
+                    .config_ids(vec![CONFIG_ID.to_string()])
+                    .build()
+                    .unwrap(),
+            )
+            .discovery(
+                DiscoveryConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .namespace(NAMESPACE)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(AppConfig::get::<String>("name").as_deref(), Some("v1"));
+
+    // 变更通过服务端流推送
+    cluster
+        .publish_config(&cluster.nodes[2], &token, CONFIG_ID, "name: v2")
+        .await
+        .unwrap();
+    eventually("client to receive the new config", TIMEOUT, || async {
+        Ok((AppConfig::get::<String>("name").as_deref() == Some("v2")).then_some(()))
+    })
+    .await
+    .unwrap();
+
+    // 心跳流使实例变为可用，所有节点都能看到
+    let instances = AppDiscovery::wait_for_instances(SERVICE_ID, 1, TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(instances[0].port, 9100);
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to see the instance", node.id),
+            TIMEOUT,
+            || async {
+                let available = cluster.instance_ids(node, SERVICE_ID, true).await?;
+                Ok((available == vec![instances[0].id.clone()]).then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+}
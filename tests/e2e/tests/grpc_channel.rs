@@ -0,0 +1,173 @@
+//! gRPC channels created for `lb://service_id` balance over the instances of the service and
+//! follow instances coming and going.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::{AppDiscovery, lb, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+use conreg_grpc::config_client::ConfigClient;
+use conreg_grpc::config_server::{Config, ConfigServer};
+use conreg_grpc::{
+    ConfigChanged, ConfigEntry, GetConfigRequest, GetConfigResponse, WatchConfigRequest,
+};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tonic::codegen::tokio_stream::{self, wrappers::TcpListenerStream};
+use tonic::transport::{Channel, Server};
+use tonic::{Request, Response, Status};
+
+const SERVICE_ID: &str = "e2e-grpc-upstream";
+
+/// A gRPC backend answering every config request with its own port
+struct Backend {
+    port: u16,
+}
+
+#[tonic::async_trait]
+impl Config for Backend {
+    async fn get_config(
+        &self,
+        _request: Request<GetConfigRequest>,
+    ) -> Result<Response<GetConfigResponse>, Status> {
+        Ok(Response::new(GetConfigResponse {
+            entry: Some(ConfigEntry {
+                content: self.port.to_string(),
+                ..Default::default()
+            }),
+        }))
+    }
+
+    type WatchConfigStream = tokio_stream::Empty<Result<ConfigChanged, Status>>;
+
+    async fn watch_config(
+        &self,
+        _request: Request<WatchConfigRequest>,
+    ) -> Result<Response<Self::WatchConfigStream>, Status> {
+        Ok(Response::new(tokio_stream::empty()))
+    }
+}
+
+/// Start a gRPC backend, returns its port
+async fn start_backend() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(
+        Server::builder()
+            .add_service(ConfigServer::new(Backend { port }))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    port
+}
+
+/// Ports of the backends answering `times` requests
+async fn answered_ports(client: &mut ConfigClient<Channel>, times: usize) -> HashSet<u16> {
+    let mut ports = HashSet::new();
+    for _ in 0..times {
+        let response = client
+            .get_config(GetConfigRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+        ports.insert(response.entry.unwrap().content.parse().unwrap());
+    }
+    ports
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn grpc_channel_follows_instances() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let first = [start_backend().await, start_backend().await];
+    let mut first_ids = vec![];
+    for port in first {
+        first_ids.push(
+            cluster
+                .register_instance(node, SERVICE_ID, port)
+                .await
+                .unwrap(),
+        );
+    }
+    let instances = Mutex::new(first_ids.clone());
+    let heartbeat = async {
+        loop {
+            for instance_id in instances.lock().await.iter() {
+                let _ = cluster.heartbeat(node, SERVICE_ID, instance_id).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    };
+
+    let checked = async {
+        try_init_with(
+            ConRegConfigBuilder::default()
+                .service_id("e2e-grpc-caller")
+                .client(ClientConfigBuilder::default().port(18200).build().unwrap())
+                .discovery(
+                    DiscoveryConfigBuilder::default()
+                        .server_addr(cluster.addrs())
+                        .namespace(NAMESPACE)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        AppDiscovery::wait_for_instances(SERVICE_ID, 2, TIMEOUT)
+            .await
+            .unwrap();
+
+        let channel = lb::grpc::channel(&format!("lb://{}", SERVICE_ID))
+            .await
+            .unwrap();
+        let mut client = ConfigClient::new(channel);
+        assert_eq!(answered_ports(&mut client, 20).await, HashSet::from(first));
+
+        // 新实例上线后加入通道
+        let added = start_backend().await;
+        let added_id = cluster
+            .register_instance(node, SERVICE_ID, added)
+            .await
+            .unwrap();
+        instances.lock().await.push(added_id);
+        eventually("the new instance to answer", TIMEOUT, || {
+            let mut client = client.clone();
+            async move {
+                Ok(answered_ports(&mut client, 10)
+                    .await
+                    .contains(&added)
+                    .then_some(()))
+            }
+        })
+        .await
+        .unwrap();
+
+        // 实例下线后从通道中移除
+        instances.lock().await.retain(|id| !first_ids.contains(id));
+        for instance_id in first_ids.iter() {
+            cluster
+                .deregister_instance(node, SERVICE_ID, instance_id)
+                .await
+                .unwrap();
+        }
+        eventually("the removed instances to stop answering", TIMEOUT, || {
+            let mut client = client.clone();
+            async move {
+                Ok((answered_ports(&mut client, 10).await == HashSet::from([added])).then_some(()))
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            answered_ports(&mut client, 20).await,
+            HashSet::from([added])
+        );
+    };
+    tokio::select! {
+        _ = heartbeat => unreachable!(),
+        _ = checked => {}
+    }
+}
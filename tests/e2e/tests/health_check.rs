@@ -0,0 +1,79 @@
+//! An instance registered without a client SDK is kept alive by active gRPC health checks, and
+//! becomes unavailable once its health service reports it is not serving.
+
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+use serde_json::json;
+use tonic_health::ServingStatus;
+
+const SERVICE_ID: &str = "e2e-health-check";
+const GRPC_SERVICE: &str = "demo.Greeter";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn grpc_health_check_keeps_instance_alive() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = cluster.node(1);
+
+    // 只提供gRPC健康检查服务的实例
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+    reporter
+        .set_service_status(GRPC_SERVICE, ServingStatus::Serving)
+        .await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(health_service)
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+    );
+
+    let instance = cluster
+        .register_instance_with(
+            node,
+            SERVICE_ID,
+            port,
+            json!({ "health-check": "grpc", "health-check-service": GRPC_SERVICE }),
+            "replace",
+        )
+        .await
+        .unwrap();
+    let instance_id = instance["id"].as_str().unwrap().to_string();
+
+    // 不发送心跳，探测成功后所有节点返回该实例
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to return the probed instance", node.id),
+            TIMEOUT,
+            || async {
+                let available = cluster.instance_ids(node, SERVICE_ID, true).await?;
+                Ok((available == vec![instance_id.clone()]).then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    // 多个心跳超时周期后仍然可用
+    tokio::time::sleep(std::time::Duration::from_secs(12)).await;
+    assert_eq!(
+        cluster.instance_ids(node, SERVICE_ID, true).await.unwrap(),
+        vec![instance_id.clone()]
+    );
+
+    // 健康检查返回NOT_SERVING后实例变为不可用
+    reporter
+        .set_service_status(GRPC_SERVICE, ServingStatus::NotServing)
+        .await;
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to drop the unhealthy instance", node.id),
+            TIMEOUT,
+            || async {
+                let available = cluster.instance_ids(node, SERVICE_ID, true).await?;
+                Ok(available.is_empty().then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+//! Operators can clear the lost heartbeats of an instance or mark a sick instance up without
+//! waiting for its next heartbeat, and every node applies the override.
+
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+use serde_json::json;
+
+const SERVICE_ID: &str = "e2e-heartbeat-override";
+const OVERRIDE_PATH: &str = "/api/discovery/instance/heartbeat-override";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn sick_instance_marked_up_on_every_node() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = cluster.node(1);
+    let token = cluster.login(node).await.unwrap();
+    let instance_id = cluster
+        .register_instance(node, SERVICE_ID, 9101)
+        .await
+        .unwrap();
+    cluster
+        .heartbeat(node, SERVICE_ID, &instance_id)
+        .await
+        .unwrap();
+
+    // 停止心跳后实例在所有节点上变为不可用
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to mark the instance sick", node.id),
+            TIMEOUT,
+            || async {
+                let instances = cluster.instances(node, SERVICE_ID, false).await?;
+                Ok(instances
+                    .iter()
+                    .any(|instance| instance["status"].get("Sick").is_some())
+                    .then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    let body = |action: &str| {
+        json!({
+            "namespace_id": NAMESPACE,
+            "service_id": SERVICE_ID,
+            "instance_id": instance_id,
+            "action": action,
+            "reason": "network blip",
+        })
+    };
+    // 清零丢失心跳的周期数不改变状态
+    let reset = cluster
+        .console_post(node, &token, OVERRIDE_PATH, body("reset_lost_heartbeats"))
+        .await
+        .unwrap();
+    assert!(reset["previous_lost_heartbeats"].as_u64().unwrap() >= 1);
+    assert!(reset["status"].get("Sick").is_some());
+
+    // 标记为Up后所有节点立即返回该实例
+    let marked = cluster
+        .console_post(node, &token, OVERRIDE_PATH, body("mark_up"))
+        .await
+        .unwrap();
+    assert!(marked["previous_status"].get("Sick").is_some());
+    assert_eq!(marked["status"], "Up");
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to return the instance", node.id),
+            TIMEOUT,
+            || async {
+                let available = cluster.instance_ids(node, SERVICE_ID, true).await?;
+                Ok((available == vec![instance_id.clone()]).then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    // 不存在的实例返回错误
+    let missing = cluster
+        .console_post(
+            node,
+            &token,
+            OVERRIDE_PATH,
+            json!({
+                "namespace_id": NAMESPACE,
+                "service_id": SERVICE_ID,
+                "instance_id": "missing",
+                "action": "mark_up",
+            }),
+        )
+        .await;
+    assert!(missing.is_err());
+}
@@ -0,0 +1,84 @@
+//! Registering an instance whose address is already registered follows the requested conflict
+//! policy on every node.
+
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+use serde_json::json;
+
+const SERVICE_ID: &str = "e2e-conflict";
+const PORT: u16 = 18090;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn conflicting_registrations_follow_policy() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let instance = cluster
+        .register_instance_with(node, SERVICE_ID, PORT, json!({ "zone": "a" }), "reject")
+        .await
+        .unwrap();
+    let instance_id = instance["id"].as_str().unwrap().to_string();
+    eventually("the instance to be registered", TIMEOUT, || async {
+        let ids = cluster.instance_ids(node, SERVICE_ID, false).await?;
+        Ok((ids == vec![instance_id.clone()]).then_some(()))
+    })
+    .await
+    .unwrap();
+    cluster
+        .heartbeat(node, SERVICE_ID, &instance_id)
+        .await
+        .unwrap();
+
+    // 拒绝重复注册
+    let rejected = cluster
+        .register_instance_with(node, SERVICE_ID, PORT, json!({}), "reject")
+        .await
+        .unwrap_err();
+    assert!(
+        rejected.to_string().contains("already registered"),
+        "{}",
+        rejected
+    );
+
+    // 合并元数据，实例保持可用
+    let merged = cluster
+        .register_instance_with(
+            node,
+            SERVICE_ID,
+            PORT,
+            json!({ "weight": "10" }),
+            "merge_meta",
+        )
+        .await
+        .unwrap();
+    assert_eq!(merged["meta"], json!({ "zone": "a", "weight": "10" }));
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to merge the metadata", node.id),
+            TIMEOUT,
+            || async {
+                let instances = cluster.instances(node, SERVICE_ID, true).await?;
+                Ok(instances
+                    .first()
+                    .is_some_and(|instance| instance["meta"] == merged["meta"])
+                    .then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    // 默认替换已有实例
+    cluster
+        .register_instance(node, SERVICE_ID, PORT)
+        .await
+        .unwrap();
+    eventually("the instance to be replaced", TIMEOUT, || async {
+        let instances = cluster.instances(node, SERVICE_ID, false).await?;
+        Ok(instances
+            .first()
+            .is_some_and(|instance| instance["meta"] == json!({}))
+            .then_some(()))
+    })
+    .await
+    .unwrap();
+}
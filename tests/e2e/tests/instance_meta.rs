@@ -0,0 +1,65 @@
+//! Metadata of a registered instance is updated at runtime and replicated to all nodes.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::{AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+use serde_yaml::Value;
+use std::collections::HashMap;
+
+const SERVICE_ID: &str = "e2e-instance-meta";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn instance_meta_updated_on_all_nodes() {
+    let cluster = Cluster::start(3).await.unwrap();
+
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .service_id(SERVICE_ID)
+            .client(ClientConfigBuilder::default().port(9300).build().unwrap())
+            .discovery(
+                DiscoveryConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .meta(HashMap::from([
+                        ("weight".to_string(), Value::from("10")),
+                        ("zone".to_string(), Value::from("a")),
+                    ]))
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    AppDiscovery::wait_for_instances(SERVICE_ID, 1, TIMEOUT)
+        .await
+        .unwrap();
+
+    AppDiscovery::update_metadata(HashMap::from([
+        ("weight".to_string(), "80".to_string()),
+        ("version".to_string(), "2".to_string()),
+    ]))
+    .await
+    .unwrap();
+
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to see the new metadata", node.id),
+            TIMEOUT,
+            || async {
+                let instances = cluster.instances(node, SERVICE_ID, true).await?;
+                Ok(instances
+                    .first()
+                    .filter(|instance| {
+                        instance["meta"]["weight"] == "80"
+                            && instance["meta"]["version"] == "2"
+                            && instance["meta"]["zone"] == "a"
+                    })
+                    .map(|_| ()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+//! The load balance client retries failed requests on another instance.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::lb::{LoadBalanceClient, LoadBalanceStrategy, RequestOptions, RetryPolicy};
+use conreg_client::{AppDiscovery, Instance, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT};
+use reqwest::{Method, StatusCode};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const SERVICE_ID: &str = "e2e-retry";
+
+/// Start a backend answering every request with `status`
+async fn start_backend(status: u16) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                continue;
+            };
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    port
+}
+
+/// A port nobody listens on
+fn closed_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn failed_requests_retried_on_another_instance() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let healthy = start_backend(200).await;
+    let mut instances = vec![];
+    for port in [start_backend(503).await, closed_port(), healthy] {
+        let instance_id = cluster
+            .register_instance(node, SERVICE_ID, port)
+            .await
+            .unwrap();
+        instances.push(instance_id);
+    }
+    let heartbeat = async {
+        loop {
+            for instance_id in instances.iter() {
+                let _ = cluster.heartbeat(node, SERVICE_ID, instance_id).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    };
+
+    let checked = async {
+        let caller_port = start_backend(200).await;
+        try_init_with(
+            ConRegConfigBuilder::default()
+                .service_id("e2e-retry-caller")
+                .client(
+                    ClientConfigBuilder::default()
+                        .port(caller_port)
+                        .build()
+                        .unwrap(),
+                )
+                .discovery(
+                    DiscoveryConfigBuilder::default()
+                        .server_addr(cluster.addrs())
+                        .namespace(NAMESPACE)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        AppDiscovery::wait_for_instances(SERVICE_ID, 3, TIMEOUT)
+            .await
+            .unwrap();
+
+        let url = format!("lb://{}/hello", SERVICE_ID);
+        let mut client = LoadBalanceClient::new();
+        client.set_strategy(SERVICE_ID, LoadBalanceStrategy::RoundRobin);
+
+        // 未设置重试策略时，失败直接返回
+        let mut failed = 0;
+        for _ in 0..6 {
+            let request = client
+                .resolve(Method::GET, &url, RequestOptions::default())
+                .await
+                .unwrap();
+            match client.execute(request).await {
+                Ok(response) if response.status() == StatusCode::OK => {}
+                _ => failed += 1,
+            }
+        }
+        assert!(failed > 0);
+
+        // 设置重试策略后，每个请求最终都落到健康的实例上
+        client.set_retry_policy(
+            SERVICE_ID,
+            RetryPolicy::new(3).per_try_timeout(Duration::from_secs(2)),
+        );
+        for _ in 0..6 {
+            let request = client
+                .resolve(Method::GET, &url, RequestOptions::default())
+                .await
+                .unwrap();
+            let response = client.execute(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.extensions().get::<Instance>().unwrap().port,
+                healthy
+            );
+        }
+
+        // 单次请求的重试策略优先，只尝试一次时不重试
+        let mut failed = 0;
+        for _ in 0..6 {
+            let request = client
+                .resolve(
+                    Method::GET,
+                    &url,
+                    RequestOptions::default().retry(RetryPolicy::new(1)),
+                )
+                .await
+                .unwrap();
+            match client.execute(request).await {
+                Ok(response) if response.status() == StatusCode::OK => {}
+                _ => failed += 1,
+            }
+        }
+        assert!(failed > 0);
+    };
+    tokio::select! {
+        _ = heartbeat => unreachable!(),
+        _ = checked => {}
+    }
+}
@@ -0,0 +1,109 @@
+//! The load balance client routes requests across the registered instances.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::lb::{LoadBalanceClient, LoadBalanceStrategy};
+use conreg_client::{AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const SERVICE_ID: &str = "e2e-backend";
+
+/// Start a backend answering every request with its own port
+async fn start_backend() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                continue;
+            };
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let body = port.to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    port
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn load_balance_routes_across_instances() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let mut instances = vec![];
+    let mut ports = HashSet::new();
+    for node in cluster.nodes.iter().take(2) {
+        let port = start_backend().await;
+        let instance_id = cluster
+            .register_instance(node, SERVICE_ID, port)
+            .await
+            .unwrap();
+        instances.push((node, instance_id));
+        ports.insert(port.to_string());
+    }
+    let heartbeat = async {
+        loop {
+            for (node, instance_id) in instances.iter() {
+                let _ = cluster.heartbeat(node, SERVICE_ID, instance_id).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    };
+
+    let routed = async {
+        // 调用方本身也注册到注册中心
+        let caller_port = start_backend().await;
+        try_init_with(
+            ConRegConfigBuilder::default()
+                .service_id("e2e-caller")
+                .client(
+                    ClientConfigBuilder::default()
+                        .port(caller_port)
+                        .build()
+                        .unwrap(),
+                )
+                .discovery(
+                    DiscoveryConfigBuilder::default()
+                        .server_addr(cluster.addrs())
+                        .namespace(NAMESPACE)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        AppDiscovery::wait_for_instances(SERVICE_ID, ports.len(), TIMEOUT)
+            .await
+            .unwrap();
+
+        let mut client = LoadBalanceClient::new();
+        client.set_strategy(SERVICE_ID, LoadBalanceStrategy::RoundRobin);
+        let mut routed = HashSet::new();
+        for _ in 0..10 {
+            let request = client
+                .get(&format!("lb://{}/hello", SERVICE_ID))
+                .await
+                .unwrap();
+            let response = client.send(request).await.unwrap();
+            routed.insert(response.text().await.unwrap());
+        }
+        routed
+    };
+    let routed = tokio::select! {
+        _ = heartbeat => unreachable!(),
+        routed = routed => routed,
+    };
+    assert_eq!(routed, ports);
+}
@@ -0,0 +1,79 @@
+//! A suspended namespace rejects config publishing and instance registration with the suspended
+//! error code, and accepts them again once resumed.
+
+use conreg_e2e::{ApiError, Cluster, NAMESPACE, TIMEOUT, eventually};
+use serde_json::json;
+
+const CONFIG_ID: &str = "suspend.yaml";
+const SERVICE_ID: &str = "e2e-suspend";
+const PORT: u16 = 18095;
+/// `NAMESPACE_SUSPENDED_CODE` of conreg-server
+const NAMESPACE_SUSPENDED_CODE: i32 = 1001;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn suspended_namespace_rejects_writes() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let token = cluster.login(node).await.unwrap();
+    let suspend = |suspended: bool| {
+        cluster.console_post(
+            node,
+            &token,
+            "/api/namespace/suspend",
+            json!({ "id": NAMESPACE, "suspended": suspended }),
+        )
+    };
+    let error_code = |e: anyhow::Error| match e.downcast_ref::<ApiError>() {
+        Some(e) => Ok(e.code),
+        None => Err(e),
+    };
+
+    suspend(true).await.unwrap();
+    let code = eventually("config publishing to be rejected", TIMEOUT, || async {
+        match cluster
+            .publish_config(node, &token, CONFIG_ID, "version: 1")
+            .await
+        {
+            Ok(()) => Ok(None),
+            Err(e) => error_code(e).map(Some),
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(code, NAMESPACE_SUSPENDED_CODE);
+    let e = cluster
+        .register_instance(node, SERVICE_ID, PORT)
+        .await
+        .unwrap_err();
+    assert_eq!(error_code(e).unwrap(), NAMESPACE_SUSPENDED_CODE);
+    assert_eq!(cluster.get_config(node, CONFIG_ID).await.unwrap(), None);
+
+    suspend(false).await.unwrap();
+    eventually("config publishing to be accepted", TIMEOUT, || async {
+        match cluster
+            .publish_config(node, &token, CONFIG_ID, "version: 1")
+            .await
+        {
+            Ok(()) => Ok(Some(())),
+            Err(e) => match error_code(e)? {
+                NAMESPACE_SUSPENDED_CODE => Ok(None),
+                code => anyhow::bail!("unexpected error code {}", code),
+            },
+        }
+    })
+    .await
+    .unwrap();
+    cluster
+        .register_instance(node, SERVICE_ID, PORT)
+        .await
+        .unwrap();
+    assert_eq!(
+        cluster
+            .get_config(node, CONFIG_ID)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("version: 1")
+    );
+}
@@ -0,0 +1,46 @@
+//! With `fail-open`, the application starts while the registry is unreachable,
+//! and the instance is registered in the background once the registry is back.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::try_init_with;
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+
+const SERVICE_ID: &str = "e2e-registration-fail-open";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn registration_retried_in_background() {
+    let mut cluster = Cluster::start(1).await.unwrap();
+    cluster.node_mut(1).stop();
+
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .service_id(SERVICE_ID)
+            .client(ClientConfigBuilder::default().port(9310).build().unwrap())
+            .discovery(
+                DiscoveryConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .register_retries(1)
+                    .retry_backoff_ms(200)
+                    .register_max_backoff_ms(1000)
+                    .fail_open(true)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .expect("initialization should not fail with fail-open");
+
+    cluster.node_mut(1).restart().unwrap();
+    cluster.wait_converged().await.unwrap();
+
+    let node = cluster.node(1);
+    eventually("the instance to be registered", TIMEOUT, || async {
+        let ids = cluster.instance_ids(node, SERVICE_ID, false).await?;
+        Ok((!ids.is_empty()).then_some(()))
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,49 @@
+//! Scheduled tasks are listed per node and can be triggered manually.
+
+use conreg_e2e::Cluster;
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn scheduled_tasks_listed_and_triggered() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let token = cluster.login(node).await.unwrap();
+
+    let tasks = cluster
+        .console_get(node, &token, "/api/system/tasks", &[])
+        .await
+        .unwrap();
+    let names = tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["name"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    for name in ["heartbeat-check", "instance-cleanup", "service-monitor"] {
+        assert!(names.contains(&name.to_string()), "{:?}", names);
+    }
+
+    let status = cluster
+        .console_post(
+            node,
+            &token,
+            "/api/system/tasks/run",
+            json!({ "name": "instance-cleanup" }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status["run_count"], 1, "{}", status);
+    assert_eq!(status["scope"], "Node");
+    assert!(status["last_error"].is_null(), "{}", status);
+
+    let unknown = cluster
+        .console_post(
+            node,
+            &token,
+            "/api/system/tasks/run",
+            json!({ "name": "no-such-task" }),
+        )
+        .await;
+    assert!(unknown.is_err());
+}
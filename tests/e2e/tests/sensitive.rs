@@ -0,0 +1,83 @@
+//! Sensitive configs are masked for read-only console users, admins and clients see plaintext.
+
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+
+const CONTENT: &str = "db:\n  host: localhost\n  password: secret\n";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn sensitive_keys_are_masked_for_read_only_users() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let token = cluster.login(node).await.unwrap();
+    cluster
+        .publish_config(node, &token, "app.yaml", CONTENT)
+        .await
+        .unwrap();
+    cluster
+        .create_user(node, &token, "viewer", "viewer", &["r:ns:public"])
+        .await
+        .unwrap();
+    let viewer = cluster.login_as(node, "viewer", "viewer").await.unwrap();
+
+    // 只读用户不能修改敏感标记
+    assert!(
+        cluster
+            .set_sensitive(node, &viewer, "app.yaml", Some(&[]))
+            .await
+            .is_err()
+    );
+    cluster
+        .set_sensitive(node, &token, "app.yaml", Some(&["db.password"]))
+        .await
+        .unwrap();
+
+    let masked = eventually("sensitive key to be masked", TIMEOUT, || async {
+        let content = cluster
+            .console_get_config(node, &viewer, "app.yaml")
+            .await?;
+        Ok(content.filter(|content| !content.contains("secret")))
+    })
+    .await
+    .unwrap();
+    assert!(masked.contains("host: localhost"));
+    let list = cluster.console_list_configs(node, &viewer).await.unwrap();
+    assert!(
+        list.iter()
+            .any(|(id, content)| id == "app.yaml" && !content.contains("secret"))
+    );
+
+    // 管理员和客户端看到明文
+    let content = cluster.console_get_config(node, &token, "app.yaml").await;
+    assert_eq!(content.unwrap().as_deref(), Some(CONTENT));
+    let content = cluster.get_config(node, "app.yaml").await.unwrap();
+    assert_eq!(content.as_deref(), Some(CONTENT));
+
+    // 整个配置标记为敏感
+    cluster
+        .set_sensitive(node, &token, "app.yaml", Some(&[]))
+        .await
+        .unwrap();
+    eventually("whole config to be masked", TIMEOUT, || async {
+        let content = cluster
+            .console_get_config(node, &viewer, "app.yaml")
+            .await?;
+        Ok((content.as_deref() == Some("******")).then_some(()))
+    })
+    .await
+    .unwrap();
+
+    // 取消标记后恢复明文
+    cluster
+        .set_sensitive(node, &token, "app.yaml", None)
+        .await
+        .unwrap();
+    eventually("mask to be cleared", TIMEOUT, || async {
+        let content = cluster
+            .console_get_config(node, &viewer, "app.yaml")
+            .await?;
+        Ok((content.as_deref() == Some(CONTENT)).then_some(()))
+    })
+    .await
+    .unwrap();
+}
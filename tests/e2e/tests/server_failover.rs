@@ -0,0 +1,71 @@
+//! The client skips an unreachable server node instead of failing the request.
+
+use conreg_client::conf::{
+    ClientConfigBuilder, ConRegConfigBuilder, ConfigConfigBuilder, DiscoveryConfigBuilder,
+};
+use conreg_client::{AppConfig, AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+
+const SERVICE_ID: &str = "e2e-server-failover";
+const CONFIG_ID: &str = "server-failover.yaml";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn client_fails_over_to_running_nodes() {
+    let mut cluster = Cluster::start(3).await.unwrap();
+    let token = cluster.login(&cluster.nodes[0]).await.unwrap();
+    cluster
+        .publish_config(&cluster.nodes[0], &token, CONFIG_ID, "version: 1")
+        .await
+        .unwrap();
+    // 停止一个Follower并将其放在地址列表的第一个，客户端的每个请求都应转移到其他节点
+    let leader = cluster.wait_converged().await.unwrap();
+    let stopped = if leader == 1 { 2 } else { 1 };
+    let mut addrs = vec![cluster.node(stopped).addr.clone()];
+    addrs.extend(
+        cluster
+            .nodes
+            .iter()
+            .filter(|node| node.id != stopped)
+            .map(|node| node.addr.clone()),
+    );
+    cluster.node_mut(stopped).stop();
+
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .service_id(SERVICE_ID)
+            .client(ClientConfigBuilder::default().port(9330).build().unwrap())
+            .config(
+                ConfigConfigBuilder::default()
+                    .server_addr(addrs.clone())
+                    .config_ids(vec![CONFIG_ID.to_string()])
+                    .build()
+                    .unwrap(),
+            )
+            .discovery(
+                DiscoveryConfigBuilder::default()
+                    .server_addr(addrs)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(AppConfig::get::<u32>("version"), Some(1));
+    AppDiscovery::wait_for_instances(SERVICE_ID, 1, TIMEOUT)
+        .await
+        .unwrap();
+
+    let node = cluster.node(leader);
+    cluster
+        .publish_config(node, &token, CONFIG_ID, "version: 2")
+        .await
+        .unwrap();
+    eventually("client to receive the change", TIMEOUT, || async {
+        Ok((AppConfig::get::<u32>("version") == Some(2)).then_some(()))
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,71 @@
+//! Instances inherit the metadata of their service in available-instance responses, keys set
+//! on the instance itself take precedence.
+
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+use serde_json::json;
+
+const SERVICE_ID: &str = "e2e-service-meta";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn instances_inherit_service_meta() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let token = cluster.login(node).await.unwrap();
+    cluster
+        .console_post(
+            node,
+            &token,
+            "/api/discovery/service/register",
+            json!({
+                "namespace_id": NAMESPACE,
+                "service_id": SERVICE_ID,
+                "meta": { "weight": "10", "zone": "zone-a" },
+            }),
+        )
+        .await
+        .unwrap();
+
+    let mut instances = vec![];
+    for (port, meta) in [(18111, json!({})), (18112, json!({ "zone": "zone-b" }))] {
+        let instance = cluster
+            .register_instance_with(node, SERVICE_ID, port, meta, "replace")
+            .await
+            .unwrap();
+        instances.push(instance["id"].as_str().unwrap().to_string());
+    }
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to see both instances", node.id),
+            TIMEOUT,
+            || async {
+                for instance_id in instances.iter() {
+                    cluster.heartbeat(node, SERVICE_ID, instance_id).await?;
+                }
+                let available = cluster.instances(node, SERVICE_ID, true).await?;
+                Ok((available.len() == 2).then_some(available))
+            },
+        )
+        .await
+        .map(|mut available| {
+            available.sort_by_key(|instance| instance["port"].as_u64());
+            assert_eq!(
+                available[0]["meta"],
+                json!({ "weight": "10", "zone": "zone-a" })
+            );
+            assert_eq!(
+                available[1]["meta"],
+                json!({ "weight": "10", "zone": "zone-b" })
+            );
+        })
+        .unwrap();
+    }
+
+    // 实例列表返回实例自身的元数据
+    let listed = cluster.instances(node, SERVICE_ID, false).await.unwrap();
+    assert!(
+        listed
+            .iter()
+            .all(|instance| instance["meta"].get("weight").is_none())
+    );
+}
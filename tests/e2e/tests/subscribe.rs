@@ -0,0 +1,90 @@
+//! A subscriber is told about registered and deregistered instances without waiting for the
+//! periodic instance sync.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::{AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SERVICE_ID: &str = "e2e-subscribed";
+
+/// Well below the 30 seconds of the periodic instance sync
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn subscriber_receives_instance_changes() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let instance_id = cluster
+        .register_instance(&cluster.nodes[0], SERVICE_ID, 18080)
+        .await
+        .unwrap();
+    let heartbeat = async {
+        loop {
+            let _ = cluster
+                .heartbeat(&cluster.nodes[0], SERVICE_ID, &instance_id)
+                .await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    };
+
+    let subscribed = async {
+        try_init_with(
+            ConRegConfigBuilder::default()
+                .service_id("e2e-subscriber")
+                .client(ClientConfigBuilder::default().port(18081).build().unwrap())
+                .discovery(
+                    DiscoveryConfigBuilder::default()
+                        .server_addr(cluster.addrs())
+                        .namespace(NAMESPACE)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let received = Arc::new(Mutex::new(None::<Vec<String>>));
+        let sink = received.clone();
+        let subscription = AppDiscovery::subscribe(SERVICE_ID, move |instances| {
+            *sink.lock().unwrap() = Some(instances.iter().map(|i| i.id.clone()).collect());
+        })
+        .unwrap();
+        assert_eq!(subscription.service_id(), SERVICE_ID);
+        eventually("subscriber to receive the instance", TIMEOUT, || async {
+            Ok(
+                (received.lock().unwrap().as_deref() == Some(&[instance_id.clone()][..]))
+                    .then_some(()),
+            )
+        })
+        .await
+        .unwrap();
+        received
+    };
+    let received = tokio::select! {
+        _ = heartbeat => unreachable!(),
+        received = subscribed => received,
+    };
+
+    // 在另一个节点注销，订阅者无需等待定时同步即可收到变更
+    cluster
+        .deregister_instance(&cluster.nodes[1], SERVICE_ID, &instance_id)
+        .await
+        .unwrap();
+    eventually(
+        "subscriber to see the deregistration",
+        PUSH_TIMEOUT,
+        || async { Ok((received.lock().unwrap().as_deref() == Some(&[][..])).then_some(())) },
+    )
+    .await
+    .unwrap();
+    assert!(
+        AppDiscovery::get_instances(SERVICE_ID)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,164 @@
+//! Tower based clients resolve load balance urls through the conreg discover layer.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::lb::{ConregDiscoverLayer, LoadBalanceClient, LoadBalanceStrategy};
+use conreg_client::{AppDiscovery, Instance, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT};
+use reqwest::header::{HeaderName, HeaderValue};
+use std::future::{Future, poll_fn};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tower::{BoxError, Layer, Service};
+
+const SERVICE_ID: &str = "e2e-tower";
+
+/// Start a backend answering every request with its own port and the received request head
+async fn start_backend() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                continue;
+            };
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or_default();
+                let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let body = format!("{}\n{}", port, head);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    port
+}
+
+/// A plain tower http client, standing in for hyper or tonic
+#[derive(Clone)]
+struct HttpClient(reqwest::Client);
+
+impl Service<http::Request<String>> for HttpClient {
+    type Response = http::Response<String>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<String>) -> Self::Future {
+        let client = self.0.clone();
+        Box::pin(async move {
+            let response = client.execute(request.try_into()?).await?;
+            let status = response.status();
+            let body = response.text().await?;
+            Ok(http::Response::builder().status(status).body(body)?)
+        })
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn tower_requests_load_balanced() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let mut ports = vec![];
+    let mut instances = vec![];
+    for _ in 0..2 {
+        let port = start_backend().await;
+        let instance_id = cluster
+            .register_instance(node, SERVICE_ID, port)
+            .await
+            .unwrap();
+        ports.push(port);
+        instances.push(instance_id);
+    }
+    let heartbeat = async {
+        loop {
+            for instance_id in instances.iter() {
+                let _ = cluster.heartbeat(node, SERVICE_ID, instance_id).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    };
+
+    let checked = async {
+        let caller_port = start_backend().await;
+        try_init_with(
+            ConRegConfigBuilder::default()
+                .service_id("e2e-tower-caller")
+                .client(
+                    ClientConfigBuilder::default()
+                        .port(caller_port)
+                        .build()
+                        .unwrap(),
+                )
+                .discovery(
+                    DiscoveryConfigBuilder::default()
+                        .server_addr(cluster.addrs())
+                        .namespace(NAMESPACE)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        AppDiscovery::wait_for_instances(SERVICE_ID, 2, TIMEOUT)
+            .await
+            .unwrap();
+
+        let mut client = LoadBalanceClient::new();
+        client.set_strategy(SERVICE_ID, LoadBalanceStrategy::RoundRobin);
+        client.set_base_path(SERVICE_ID, "/api");
+        client.set_default_header(
+            SERVICE_ID,
+            HeaderName::from_static("x-conreg-e2e"),
+            HeaderValue::from_static("tower"),
+        );
+        let mut service =
+            ConregDiscoverLayer::new(client).layer(HttpClient(reqwest::Client::new()));
+
+        // 轮询所有实例，并应用服务的路径前缀和默认请求头
+        let mut selected = vec![];
+        for _ in 0..4 {
+            poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+            let request = http::Request::get(format!("lb://{}/hello", SERVICE_ID))
+                .body(String::new())
+                .unwrap();
+            let response = service.call(request).await.unwrap();
+            let instance = response.extensions().get::<Instance>().unwrap().clone();
+            let body = response.into_body();
+            assert!(body.starts_with(&format!("{}\n", instance.port)));
+            assert!(body.contains("get /api/hello "));
+            assert!(body.contains("x-conreg-e2e: tower"));
+            selected.push(instance.port);
+        }
+        selected.sort();
+        selected.dedup();
+        ports.sort();
+        assert_eq!(selected, ports);
+
+        // 非负载协议的请求原样透传
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let request = http::Request::get(format!("http://127.0.0.1:{}/plain", caller_port))
+            .body(String::new())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        assert!(response.extensions().get::<Instance>().is_none());
+        assert!(response.body().starts_with(&format!("{}\n", caller_port)));
+    };
+    tokio::select! {
+        _ = heartbeat => unreachable!(),
+        _ = checked => {}
+    }
+}
@@ -0,0 +1,58 @@
+//! A client sending heartbeats over UDP keeps its instance available, while registering over HTTP.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::{AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, TIMEOUT, eventually};
+
+const SERVICE_ID: &str = "e2e-udp-heartbeat";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn instance_kept_alive_by_udp_heartbeats() {
+    let cluster = Cluster::start_with_args(3, &["--enable-udp-heartbeat"])
+        .await
+        .unwrap();
+
+    try_init_with(
+        ConRegConfigBuilder::default()
+            .service_id(SERVICE_ID)
+            .client(ClientConfigBuilder::default().port(9200).build().unwrap())
+            .discovery(
+                DiscoveryConfigBuilder::default()
+                    .server_addr(cluster.addrs())
+                    .udp_heartbeat(true)
+                    .max_retries(2)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let instances = AppDiscovery::wait_for_instances(SERVICE_ID, 1, TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(instances[0].port, 9200);
+    for node in cluster.running() {
+        eventually(
+            &format!("node {} to see the instance", node.id),
+            TIMEOUT,
+            || async {
+                let available = cluster.instance_ids(node, SERVICE_ID, true).await?;
+                Ok((available == vec![instances[0].id.clone()]).then_some(()))
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    // 超过心跳超时时间后实例仍然可用
+    tokio::time::sleep(std::time::Duration::from_secs(8)).await;
+    let available = cluster
+        .instance_ids(&cluster.nodes[0], SERVICE_ID, true)
+        .await
+        .unwrap();
+    assert_eq!(available, vec![instances[0].id.clone()]);
+}
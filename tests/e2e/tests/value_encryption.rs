@@ -0,0 +1,65 @@
+//! Admins encrypt and decrypt `DEC(...)` config values with the key configured on the server,
+//! interchangeably with the `decrypt-key` of clients.
+
+use conreg_client::AppConfig;
+use conreg_e2e::Cluster;
+use serde_json::json;
+
+const DECRYPT_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn encrypt_and_decrypt_values_with_server_key() {
+    let cluster = Cluster::start_with_args(3, &["--decrypt-key", DECRYPT_KEY])
+        .await
+        .unwrap();
+    let token = cluster.login(cluster.node(1)).await.unwrap();
+
+    // 在一个节点加密，在其他节点解密
+    let encrypted = cluster
+        .console_post(
+            cluster.node(1),
+            &token,
+            "/api/system/encrypt",
+            json!({ "content": "p@ssw0rd" }),
+        )
+        .await
+        .unwrap();
+    let encrypted = encrypted.as_str().unwrap();
+    assert!(encrypted.starts_with("DEC(") && encrypted.ends_with(')'));
+    let decrypted = cluster
+        .console_post(
+            cluster.node(2),
+            &token,
+            "/api/system/decrypt",
+            json!({ "ciphertext": encrypted }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(decrypted, "p@ssw0rd");
+
+    // 与客户端使用相同密钥加密的值互通
+    let encrypted = AppConfig::encrypt(DECRYPT_KEY, "from-client").unwrap();
+    let decrypted = cluster
+        .console_post(
+            cluster.node(3),
+            &token,
+            "/api/system/decrypt",
+            json!({ "ciphertext": encrypted }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(decrypted, "from-client");
+
+    // 密钥不匹配的密文无法解密
+    let other = AppConfig::encrypt(&"ff".repeat(32), "secret").unwrap();
+    let result = cluster
+        .console_post(
+            cluster.node(1),
+            &token,
+            "/api/system/decrypt",
+            json!({ "ciphertext": other }),
+        )
+        .await;
+    assert!(result.is_err());
+}
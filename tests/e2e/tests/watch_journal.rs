@@ -0,0 +1,70 @@
+//! gRPC watchers reconnecting with their last cursor receive changes applied before a graceful
+//! restart of the node immediately, and are told to reload everything on another node.
+
+use conreg_e2e::{Cluster, NAMESPACE, Node, TIMEOUT, eventually};
+use conreg_grpc::config_client::ConfigClient;
+use conreg_grpc::{ConfigChanged, GRPC_PORT_OFFSET, WatchConfigRequest};
+
+const CONFIG_ID: &str = "e2e-journal.yaml";
+
+/// Open a watch stream on `node` and return its first message
+async fn first_change(node: &Node, cursor: &str) -> anyhow::Result<ConfigChanged> {
+    let (host, port) = node.addr.rsplit_once(':').unwrap();
+    let port = port.parse::<u16>()? + GRPC_PORT_OFFSET;
+    let mut client = ConfigClient::connect(format!("http://{}:{}", host, port)).await?;
+    let mut stream = client
+        .watch_config(WatchConfigRequest {
+            namespace_id: NAMESPACE.to_string(),
+            cursor: cursor.to_string(),
+        })
+        .await?
+        .into_inner();
+    let changed = tokio::time::timeout(TIMEOUT, stream.message()).await??;
+    changed.ok_or(anyhow::anyhow!("watch stream closed"))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn watch_cursor_survives_graceful_restart() {
+    let mut cluster = Cluster::start_with_args(3, &["--enable-grpc"])
+        .await
+        .unwrap();
+    let token = cluster.login(cluster.node(1)).await.unwrap();
+
+    // 首次监听只返回当前游标
+    let initial = first_change(cluster.node(1), "").await.unwrap();
+    assert!(initial.config_id.is_empty());
+    assert!(!initial.reset);
+
+    // 断开期间发布配置，然后正常重启节点
+    cluster
+        .publish_config(cluster.node(1), &token, CONFIG_ID, "name: v1")
+        .await
+        .unwrap();
+    let node = cluster.node(1);
+    eventually("node 1 to apply the config", TIMEOUT, || async {
+        Ok(cluster.get_config(node, CONFIG_ID).await?.map(|_| ()))
+    })
+    .await
+    .unwrap();
+    cluster.node_mut(1).shutdown().unwrap();
+    cluster.node_mut(1).restart().unwrap();
+    cluster.wait_converged().await.unwrap();
+
+    // 带上游标重新连接，立即收到重启前的变更
+    let node = cluster.node(1);
+    let replayed = eventually("the change to be replayed", TIMEOUT, || async {
+        Ok(Some(first_change(node, &initial.cursor).await?))
+    })
+    .await
+    .unwrap();
+    assert_eq!(replayed.config_id, CONFIG_ID);
+    assert!(!replayed.reset);
+
+    // 其他节点不认识这个游标
+    let other = first_change(cluster.node(2), &initial.cursor)
+        .await
+        .unwrap();
+    assert!(other.reset);
+    assert!(other.config_id.is_empty());
+}
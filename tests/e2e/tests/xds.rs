@@ -0,0 +1,164 @@
+//! An Envoy-like xDS client gets clusters and endpoints over ADS and receives pushed changes.
+
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT};
+use conreg_grpc::GRPC_PORT_OFFSET;
+use conreg_grpc::envoy::config::cluster::v3::Cluster as EnvoyCluster;
+use conreg_grpc::envoy::config::core::v3::Node;
+use conreg_grpc::envoy::config::endpoint::v3::ClusterLoadAssignment;
+use conreg_grpc::envoy::service::discovery::v3::aggregated_discovery_service_client::AggregatedDiscoveryServiceClient;
+use conreg_grpc::envoy::service::discovery::v3::{DiscoveryRequest, DiscoveryResponse};
+use prost::Message;
+use prost_types::value::Kind;
+use prost_types::{Struct, Value};
+use std::collections::BTreeMap;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Streaming;
+
+const SERVICE_ID: &str = "e2e-xds";
+const CLUSTER_TYPE: &str = "type.googleapis.com/envoy.config.cluster.v3.Cluster";
+const ENDPOINT_TYPE: &str = "type.googleapis.com/envoy.config.endpoint.v3.ClusterLoadAssignment";
+
+/// Wait for the next response of a resource type, skipping other types
+async fn next_response(
+    responses: &mut Streaming<DiscoveryResponse>,
+    type_url: &str,
+) -> DiscoveryResponse {
+    tokio::time::timeout(TIMEOUT, async {
+        loop {
+            let res = responses.message().await.unwrap().unwrap();
+            if res.type_url == type_url {
+                return res;
+            }
+        }
+    })
+    .await
+    .unwrap()
+}
+
+/// Request acknowledging the previous response of the same type, or the initial request
+fn request(
+    type_url: &str,
+    resource_names: &[&str],
+    ack: Option<&DiscoveryResponse>,
+) -> DiscoveryRequest {
+    DiscoveryRequest {
+        version_info: ack.map(|res| res.version_info.clone()).unwrap_or_default(),
+        node: Some(Node {
+            id: "e2e-sidecar".to_string(),
+            cluster: "e2e".to_string(),
+            metadata: Some(Struct {
+                fields: BTreeMap::from([(
+                    "namespace_id".to_string(),
+                    Value {
+                        kind: Some(Kind::StringValue(NAMESPACE.to_string())),
+                    },
+                )]),
+            }),
+        }),
+        resource_names: resource_names.iter().map(|name| name.to_string()).collect(),
+        type_url: type_url.to_string(),
+        response_nonce: ack.map(|res| res.nonce.clone()).unwrap_or_default(),
+    }
+}
+
+/// Sorted ports of the endpoints of the service
+fn ports(res: &DiscoveryResponse) -> Vec<u32> {
+    use conreg_grpc::envoy::config::core::v3::address::Address;
+    use conreg_grpc::envoy::config::core::v3::socket_address::PortSpecifier;
+    use conreg_grpc::envoy::config::endpoint::v3::lb_endpoint::HostIdentifier;
+
+    let assignment = ClusterLoadAssignment::decode(res.resources[0].value.as_slice()).unwrap();
+    assert_eq!(assignment.cluster_name, SERVICE_ID);
+    let mut ports = assignment.endpoints[0]
+        .lb_endpoints
+        .iter()
+        .map(|endpoint| {
+            let Some(HostIdentifier::Endpoint(endpoint)) = &endpoint.host_identifier else {
+                panic!("no endpoint");
+            };
+            let Some(Address::SocketAddress(addr)) =
+                endpoint.address.as_ref().unwrap().address.as_ref()
+            else {
+                panic!("no socket address");
+            };
+            let Some(PortSpecifier::PortValue(port)) = addr.port_specifier else {
+                panic!("no port");
+            };
+            port
+        })
+        .collect::<Vec<_>>();
+    ports.sort();
+    ports
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn envoy_receives_clusters_and_endpoints_over_ads() {
+    let cluster = Cluster::start_with_args(3, &["--enable-xds"])
+        .await
+        .unwrap();
+    let first = cluster
+        .register_instance(&cluster.nodes[0], SERVICE_ID, 9201)
+        .await
+        .unwrap();
+    cluster
+        .register_instance(&cluster.nodes[0], SERVICE_ID, 9202)
+        .await
+        .unwrap();
+
+    // 连接另一个节点的gRPC端口，实例通过Raft同步
+    let port = cluster.nodes[1].addr.rsplit(':').next().unwrap();
+    let port = port.parse::<u16>().unwrap() + GRPC_PORT_OFFSET;
+    let mut client =
+        AggregatedDiscoveryServiceClient::connect(format!("http://127.0.0.1:{}", port))
+            .await
+            .unwrap();
+    let (tx, rx) = mpsc::channel(16);
+    let mut responses = client
+        .stream_aggregated_resources(ReceiverStream::new(rx))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // CDS：每个服务一个通过ADS获取端点的EDS集群
+    // 服务可能还没有同步到连接的节点，确认后等待推送
+    tx.send(request(CLUSTER_TYPE, &[], None)).await.unwrap();
+    loop {
+        let clusters = next_response(&mut responses, CLUSTER_TYPE).await;
+        tx.send(request(CLUSTER_TYPE, &[], Some(&clusters)))
+            .await
+            .unwrap();
+        if clusters
+            .resources
+            .iter()
+            .any(|any| EnvoyCluster::decode(any.value.as_slice()).unwrap().name == SERVICE_ID)
+        {
+            break;
+        }
+    }
+
+    // EDS：订阅服务的端点
+    tx.send(request(ENDPOINT_TYPE, &[SERVICE_ID], None))
+        .await
+        .unwrap();
+    let endpoints = loop {
+        let endpoints = next_response(&mut responses, ENDPOINT_TYPE).await;
+        tx.send(request(ENDPOINT_TYPE, &[SERVICE_ID], Some(&endpoints)))
+            .await
+            .unwrap();
+        if ports(&endpoints).len() == 2 {
+            break endpoints;
+        }
+    };
+    assert_eq!(ports(&endpoints), vec![9201, 9202]);
+
+    // 注销实例后推送新的端点，不需要再次请求
+    cluster
+        .deregister_instance(&cluster.nodes[0], SERVICE_ID, &first)
+        .await
+        .unwrap();
+    let changed = next_response(&mut responses, ENDPOINT_TYPE).await;
+    assert_ne!(changed.version_info, endpoints.version_info);
+    assert_eq!(ports(&changed), vec![9202]);
+}
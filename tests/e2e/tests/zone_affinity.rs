@@ -0,0 +1,119 @@
+//! With zone affinity, the load balance client selects instances in the caller's zone and
+//! spills over to other zones once the local zone has no available instance.
+
+use conreg_client::conf::{ClientConfigBuilder, ConRegConfigBuilder, DiscoveryConfigBuilder};
+use conreg_client::lb::{LoadBalanceClient, LoadBalanceStrategy, RequestOptions};
+use conreg_client::{AppDiscovery, try_init_with};
+use conreg_e2e::{Cluster, NAMESPACE, TIMEOUT, eventually};
+use reqwest::Method;
+use serde_json::json;
+use std::time::Duration;
+
+const SERVICE_ID: &str = "e2e-zoned";
+const CALLER_ID: &str = "e2e-zoned-caller";
+const LOCAL_PORT: u16 = 18101;
+const REMOTE_PORT: u16 = 18102;
+
+/// Ports of the instances selected by `times` requests
+async fn selected_ports(client: &LoadBalanceClient, times: usize) -> Vec<u16> {
+    let url = format!("lb://{}/hello", SERVICE_ID);
+    let mut ports = vec![];
+    for _ in 0..times {
+        let request = client
+            .resolve(Method::GET, &url, RequestOptions::default())
+            .await
+            .unwrap();
+        ports.push(request.instance().unwrap().port);
+    }
+    ports
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires built conreg-server and conreg-cmt binaries"]
+async fn same_zone_instances_preferred() {
+    let cluster = Cluster::start(3).await.unwrap();
+    let node = &cluster.nodes[0];
+    let mut instances = vec![];
+    for (port, zone) in [(LOCAL_PORT, "zone-a"), (REMOTE_PORT, "zone-b")] {
+        let instance = cluster
+            .register_instance_with(node, SERVICE_ID, port, json!({ "zone": zone }), "replace")
+            .await
+            .unwrap();
+        instances.push(instance["id"].as_str().unwrap().to_string());
+    }
+    let local_id = instances[0].clone();
+    let heartbeat = async {
+        loop {
+            for instance_id in instances.iter() {
+                let _ = cluster.heartbeat(node, SERVICE_ID, instance_id).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    };
+
+    let checked = async {
+        try_init_with(
+            ConRegConfigBuilder::default()
+                .service_id(CALLER_ID)
+                .client(ClientConfigBuilder::default().port(18100).build().unwrap())
+                .discovery(
+                    DiscoveryConfigBuilder::default()
+                        .server_addr(cluster.addrs())
+                        .namespace(NAMESPACE)
+                        .zone("zone-a")
+                        .zone_affinity(true)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        // 注册的实例带上本实例的可用区
+        let caller = eventually("the caller to be registered", TIMEOUT, || async {
+            Ok(cluster.instances(node, CALLER_ID, false).await?.pop())
+        })
+        .await
+        .unwrap();
+        assert_eq!(caller["meta"]["zone"], "zone-a");
+
+        let _subscription = AppDiscovery::subscribe(SERVICE_ID, |_| {}).unwrap();
+        AppDiscovery::wait_for_instances(SERVICE_ID, 2, TIMEOUT)
+            .await
+            .unwrap();
+
+        let mut client = LoadBalanceClient::new();
+        client.set_strategy(SERVICE_ID, LoadBalanceStrategy::RoundRobin);
+        assert_eq!(selected_ports(&client, 4).await, vec![LOCAL_PORT; 4]);
+
+        // 单个服务关闭就近选择后轮询所有实例
+        client.set_zone_affinity(SERVICE_ID, None);
+        let mut ports = selected_ports(&client, 4).await;
+        ports.sort();
+        ports.dedup();
+        assert_eq!(ports, vec![LOCAL_PORT, REMOTE_PORT]);
+
+        // 本可用区没有可用实例时溢出到其他可用区
+        let client = {
+            let mut client = LoadBalanceClient::new();
+            client.set_strategy(SERVICE_ID, LoadBalanceStrategy::RoundRobin);
+            client
+        };
+        cluster
+            .deregister_instance(node, SERVICE_ID, &local_id)
+            .await
+            .unwrap();
+        eventually("the local instance to be removed", TIMEOUT, || async {
+            let instances = AppDiscovery::get_instances(SERVICE_ID).await?;
+            Ok((instances.len() == 1).then_some(()))
+        })
+        .await
+        .unwrap();
+        assert_eq!(selected_ports(&client, 4).await, vec![REMOTE_PORT; 4]);
+    };
+    tokio::select! {
+        _ = heartbeat => unreachable!(),
+        _ = checked => {}
+    }
+}